@@ -1,17 +1,41 @@
-use flintdb::config::Config;
+use clap::Parser;
+use flintdb::bench;
+use flintdb::commands;
+use flintdb::config::{Cli, Command, Config};
+use flintdb::logging;
 use flintdb::server::Server;
 
 #[tokio::main]
 pub async fn main() {
-    // Initialize tracing subscriber
-    tracing_subscriber::fmt()
-        .with_env_filter(
-            tracing_subscriber::EnvFilter::try_from_default_env()
-                .unwrap_or_else(|_| "flintdb=info".into())
-        )
-        .init();
+    let cli = Cli::parse();
+    let config = Config::from_flags(cli.flags);
+
+    // Logging is configured by flags, so it starts once they are read; the
+    // interactive shell and the benchmark keep their terminal to themselves
+    let quiet = cli.command.as_ref().is_some_and(Command::is_interactive);
+    if let Err(e) = logging::init(&config, quiet) {
+        eprintln!("flint: {}", e);
+        std::process::exit(1);
+    }
+
+    if let Some(command) = cli.command {
+        let result = match command {
+            Command::Snapshot { args } => commands::snapshot(&config, &args),
+            Command::Bench { args } => bench::run(&config, &args),
+            #[cfg(feature = "repl")]
+            Command::Repl { args } => flintdb::repl::run(&config, &args),
+        };
+        match result {
+            Ok(summary) if summary.is_empty() => {}
+            Ok(summary) => println!("{}", summary),
+            Err(e) => {
+                eprintln!("flint: {}", e);
+                std::process::exit(1);
+            }
+        }
+        return;
+    }
 
-    let config = Config::from_args();
     let server = Server::new(config);
     server.start().await;
-}
\ No newline at end of file
+}
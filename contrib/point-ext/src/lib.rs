@@ -1,7 +1,7 @@
 //! Point type extension for Flint
 //!
 //! Demonstrates all extension traits:
-//! - TypeExtension: Point type serialization/deserialization
+//! - TypeExtension: Point type serialization/deserialization and output to clients
 //! - OperatorExtension: Distance operator (<->)
 //! - FunctionExtension: magnitude() and distance() scalar functions
 //!
@@ -80,8 +80,20 @@ impl TypeExtension for PointType {
     }
 
     fn to_pgwire_type(&self) -> pgwire::api::Type {
-        // No direct pgwire type for point - use UNKNOWN
-        pgwire::api::Type::UNKNOWN
+        pgwire::api::Type::POINT
+    }
+
+    fn output(&self, value: &dyn Any) -> Option<String> {
+        value.downcast_ref::<Point>().map(|point| format!("({},{})", point.x, point.y))
+    }
+
+    fn send(&self, value: &dyn Any) -> Result<Vec<u8>, String> {
+        // As Postgres sends a point: x then y, each a big-endian float8
+        let point = value.downcast_ref::<Point>().ok_or_else(|| "Invalid point value".to_string())?;
+        let mut bytes = Vec::with_capacity(16);
+        bytes.extend_from_slice(&point.x.to_be_bytes());
+        bytes.extend_from_slice(&point.y.to_be_bytes());
+        Ok(bytes)
     }
 }
 
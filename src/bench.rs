@@ -0,0 +1,346 @@
+//! `flint bench`: measure the engine's throughput and latency, so changes to
+//! the executor and storage can be compared release to release
+//!
+//! The built-in mixes are modelled on pgbench: `tpcb` runs its TPC-B-like
+//! transaction (three balance updates, a read and a history insert),
+//! `select-only` reads accounts by key and `insert-only` appends history rows.
+//! They run in a scratch directory inside the data directory, removed after,
+//! so the database there is left alone. The random stream has a fixed seed,
+//! so every run executes the same statements.
+//!
+//! `replay` runs the statements of a JSON server log (--log-format=json), in
+//! the order they were received and each in its client's session, or of a
+//! plain SQL file, against the database in the data directory itself. It
+//! writes what the statements write, so it is best run on a copy, such as a
+//! restored snapshot.
+
+use std::collections::{HashMap, HashSet};
+use std::net::SocketAddr;
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use pgwire::api::results::Response;
+
+use crate::config::Config;
+use crate::executor::Executor;
+use crate::executor::cursor::SessionId;
+use crate::parser;
+use crate::storage;
+
+const BENCH_USAGE: &str = "usage: flint bench tpcb|select-only|insert-only [TRANSACTIONS] | flint bench replay FILE";
+
+/// Transactions a built-in mix runs when not told how many
+const DEFAULT_TRANSACTIONS: usize = 1000;
+
+/// Scratch directory of the built-in mixes, inside the data directory
+const SCRATCH_DIR: &str = "bench";
+
+/// Rows of the built-in mixes' tables: one branch with pgbench's ten tellers,
+/// and a tenth of its accounts so the load stays quick
+const BRANCHES: i64 = 1;
+const TELLERS: i64 = 10;
+const ACCOUNTS: i64 = 10_000;
+
+/// Accounts loaded per INSERT
+const LOAD_BATCH: i64 = 1000;
+
+/// Log message the server gives each query it receives
+const RECEIVED_QUERY: &str = "received query";
+
+/// `flint bench MIX [TRANSACTIONS]` or `flint bench replay FILE`
+/// Returns the report for the user
+pub fn run(config: &Config, args: &[String]) -> Result<String, String> {
+    match args {
+        [mix] => run_mix(config, mix, DEFAULT_TRANSACTIONS),
+        [action, file] if action == "replay" => replay(config, Path::new(file)),
+        [mix, transactions] => {
+            let transactions = transactions.parse()
+                .ok()
+                .filter(|&transactions| transactions > 0)
+                .ok_or_else(|| format!("Invalid transaction count: {}", transactions))?;
+            run_mix(config, mix, transactions)
+        }
+        _ => Err(BENCH_USAGE.to_string()),
+    }
+}
+
+/// A built-in mix
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Mix {
+    Tpcb,
+    SelectOnly,
+    InsertOnly,
+}
+
+impl Mix {
+    fn from_name(name: &str) -> Option<Mix> {
+        match name {
+            "tpcb" => Some(Mix::Tpcb),
+            "select-only" => Some(Mix::SelectOnly),
+            "insert-only" => Some(Mix::InsertOnly),
+            _ => None,
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Mix::Tpcb => "tpcb",
+            Mix::SelectOnly => "select-only",
+            Mix::InsertOnly => "insert-only",
+        }
+    }
+
+    /// SQL of one transaction, with its keys and delta drawn from `rng`
+    fn transaction(self, rng: &mut Rng) -> String {
+        let aid = rng.between(1, ACCOUNTS);
+        let tid = rng.between(1, TELLERS);
+        let bid = rng.between(1, BRANCHES);
+        let delta = rng.between(-5000, 5000);
+        let history = format!(
+            "INSERT INTO bench_history (tid, bid, aid, delta) VALUES ({}, {}, {}, {});",
+            tid, bid, aid, delta
+        );
+        match self {
+            Mix::Tpcb => format!(
+                "UPDATE bench_accounts SET abalance = abalance + {delta} WHERE aid = {aid};\n\
+                 SELECT abalance FROM bench_accounts WHERE aid = {aid};\n\
+                 UPDATE bench_tellers SET tbalance = tbalance + {delta} WHERE tid = {tid};\n\
+                 UPDATE bench_branches SET bbalance = bbalance + {delta} WHERE bid = {bid};\n\
+                 {history}"
+            ),
+            Mix::SelectOnly => format!("SELECT abalance FROM bench_accounts WHERE aid = {};", aid),
+            Mix::InsertOnly => history,
+        }
+    }
+}
+
+/// Load the built-in tables into a scratch directory and time `transactions`
+/// transactions of a mix against them
+fn run_mix(config: &Config, mix: &str, transactions: usize) -> Result<String, String> {
+    let mix = Mix::from_name(mix).ok_or_else(|| BENCH_USAGE.to_string())?;
+    let scratch = config.data_dir.join(SCRATCH_DIR);
+    remove_scratch(&scratch)?;
+    std::fs::create_dir_all(&scratch)
+        .map_err(|e| format!("Failed to create {}: {}", scratch.display(), e))?;
+
+    let result = (|| {
+        let _data_dir_lock = storage::lock_data_dir(&scratch)?;
+        let mut scratch_config = config.clone();
+        scratch_config.data_dir = scratch.clone();
+        let executor = Executor::new(&scratch_config).map_err(|e| e.into_error_info().message)?;
+        let session_id = SocketAddr::from(([127, 0, 0, 1], 0));
+        load(&executor, session_id)?;
+
+        let mut rng = Rng::new();
+        let mut report = Report::default();
+        let start = Instant::now();
+        for _ in 0..transactions {
+            let sql = mix.transaction(&mut rng);
+            report.record(run_timed(&executor, session_id, &sql));
+        }
+        report.elapsed = start.elapsed();
+        Ok(report.summary(mix.name(), "transactions", "tps"))
+    })();
+    remove_scratch(&scratch)?;
+    result
+}
+
+/// Create and fill the built-in mixes' tables
+fn load(executor: &Executor, session_id: SessionId) -> Result<(), String> {
+    let mut statements = vec![
+        "CREATE TABLE bench_branches (bid INT PRIMARY KEY, bbalance INT, filler STRING);".to_string(),
+        "CREATE TABLE bench_tellers (tid INT PRIMARY KEY, bid INT, tbalance INT, filler STRING);".to_string(),
+        "CREATE TABLE bench_accounts (aid INT PRIMARY KEY, bid INT, abalance INT, filler STRING);".to_string(),
+        "CREATE TABLE bench_history (hid SERIAL PRIMARY KEY, tid INT, bid INT, aid INT, delta INT);".to_string(),
+    ];
+    let rows = |first: i64, last: i64, row: &dyn Fn(i64) -> String| {
+        (first..=last).map(row).collect::<Vec<_>>().join(", ")
+    };
+    statements.push(format!("INSERT INTO bench_branches VALUES {};", rows(1, BRANCHES, &|bid| format!("({}, 0, '')", bid))));
+    statements.push(format!(
+        "INSERT INTO bench_tellers VALUES {};",
+        rows(1, TELLERS, &|tid| format!("({}, {}, 0, '')", tid, (tid - 1) * BRANCHES / TELLERS + 1))
+    ));
+    for first in (1..=ACCOUNTS).step_by(LOAD_BATCH as usize) {
+        let last = (first + LOAD_BATCH - 1).min(ACCOUNTS);
+        statements.push(format!(
+            "INSERT INTO bench_accounts VALUES {};",
+            rows(first, last, &|aid| format!("({}, {}, 0, '')", aid, (aid - 1) * BRANCHES / ACCOUNTS + 1))
+        ));
+    }
+    for sql in statements {
+        if let Err(e) = run_timed(executor, session_id, &sql) {
+            return Err(format!("Failed to load the benchmark tables: {}", e));
+        }
+    }
+    Ok(())
+}
+
+/// Remove a scratch directory left by this or an earlier run
+fn remove_scratch(scratch: &Path) -> Result<(), String> {
+    match std::fs::remove_dir_all(scratch) {
+        Ok(()) => Ok(()),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+        Err(e) => Err(format!("Failed to remove {}: {}", scratch.display(), e)),
+    }
+}
+
+/// Time the statements of a log or SQL file, run in order against the
+/// database in the data directory
+fn replay(config: &Config, file: &Path) -> Result<String, String> {
+    let text = std::fs::read_to_string(file)
+        .map_err(|e| format!("Failed to read {}: {}", file.display(), e))?;
+    let statements = match logged_queries(&text) {
+        Some(queries) => queries,
+        None => sql_statements(&text)?,
+    };
+    if statements.is_empty() {
+        return Err(format!("No statements to replay in {}", file.display()));
+    }
+
+    let _data_dir_lock = storage::lock_data_dir(&config.data_dir)?;
+    let executor = Executor::new(config).map_err(|e| e.into_error_info().message)?;
+    let mut report = Report::default();
+    let start = Instant::now();
+    for (session_id, sql) in &statements {
+        report.record(run_timed(&executor, *session_id, sql));
+    }
+    report.elapsed = start.elapsed();
+    for session_id in statements.iter().map(|(session_id, _)| *session_id).collect::<HashSet<_>>() {
+        executor.end_session(session_id);
+    }
+    Ok(report.summary(&format!("replay {}", file.display()), "statements", "qps"))
+}
+
+/// Queries of a JSON server log, each with the client that sent it, or None
+/// if the text isn't a JSON log
+fn logged_queries(text: &str) -> Option<Vec<(SessionId, String)>> {
+    let mut is_log = false;
+    let mut sessions: HashMap<String, SessionId> = HashMap::new();
+    let mut queries = Vec::new();
+    for line in text.lines().filter(|line| !line.trim().is_empty()) {
+        let event: serde_json::Value = serde_json::from_str(line).ok()?;
+        is_log = true;
+        if event["fields"]["message"] != RECEIVED_QUERY {
+            continue;
+        }
+        let Some(query) = event["fields"]["query"].as_str() else {
+            continue;
+        };
+        // Each client gets a session of its own, whatever its address now
+        let client = event["span"]["client_addr"].as_str().unwrap_or_default().to_string();
+        let next_port = sessions.len() as u16 + 1;
+        let session_id = *sessions.entry(client)
+            .or_insert_with(|| SocketAddr::from(([127, 0, 0, 1], next_port)));
+        queries.push((session_id, query.to_string()));
+    }
+    is_log.then_some(queries)
+}
+
+/// Statements of a SQL file, each as written, all in one session
+fn sql_statements(text: &str) -> Result<Vec<(SessionId, String)>, String> {
+    let session_id = SocketAddr::from(([127, 0, 0, 1], 0));
+    let chars: Vec<char> = text.chars().collect();
+    let starts: Vec<usize> = parser::parse_with_locations(text)
+        .map_err(|e| e.into_error_info().message)?
+        .into_iter()
+        .filter_map(|(_, location)| parser::char_position(text, location))
+        .map(|position| position - 1)
+        .collect();
+    Ok(starts.iter()
+        .enumerate()
+        .map(|(idx, &start)| {
+            let end = starts.get(idx + 1).copied().unwrap_or(chars.len());
+            (session_id, chars[start..end].iter().collect::<String>().trim().to_string())
+        })
+        .collect())
+}
+
+/// Run a query, returning how long it took or the first error it gave
+fn run_timed(executor: &Executor, session_id: SessionId, sql: &str) -> Result<Duration, String> {
+    let start = Instant::now();
+    let responses = executor.execute(session_id, sql)
+        .map_err(|e| e.into_error_info().message)?;
+    // Reading the rows is part of the query's cost, as it is for a client
+    for response in responses {
+        match response {
+            Response::Query(mut query) => {
+                let rows = query.data_rows();
+                while let Some(row) = futures::executor::block_on(futures::StreamExt::next(rows)) {
+                    row.map_err(|e| e.to_string())?;
+                }
+            }
+            Response::Error(error_info) => return Err(error_info.message),
+            _ => {}
+        }
+    }
+    Ok(start.elapsed())
+}
+
+/// Latencies of the queries or transactions of a run
+#[derive(Default)]
+struct Report {
+    latencies: Vec<Duration>,
+    failed: usize,
+    /// Wall time of the whole run
+    elapsed: Duration,
+}
+
+impl Report {
+    fn record(&mut self, result: Result<Duration, String>) {
+        match result {
+            Ok(latency) => self.latencies.push(latency),
+            Err(e) => {
+                tracing::debug!(error = %e, "benchmark query failed");
+                self.failed += 1;
+            }
+        }
+    }
+
+    /// Latency at a percentile (nearest rank) of the successful runs
+    fn percentile(sorted: &[Duration], percent: usize) -> Duration {
+        if sorted.is_empty() {
+            return Duration::ZERO;
+        }
+        let rank = (sorted.len() * percent).div_ceil(100).max(1);
+        sorted[rank - 1]
+    }
+
+    fn summary(&self, label: &str, unit: &str, rate_unit: &str) -> String {
+        let mut sorted = self.latencies.clone();
+        sorted.sort();
+        let ms = |duration: Duration| duration.as_secs_f64() * 1000.0;
+        let total = self.latencies.len() + self.failed;
+        let rate = self.latencies.len() as f64 / self.elapsed.as_secs_f64().max(f64::EPSILON);
+        format!(
+            "{}: {} {}, {} failed, {:.3} s\nthroughput: {:.1} {}\nlatency ms: p50 {:.3}  p95 {:.3}  p99 {:.3}  max {:.3}",
+            label, total, unit, self.failed, self.elapsed.as_secs_f64(),
+            rate, rate_unit,
+            ms(Self::percentile(&sorted, 50)),
+            ms(Self::percentile(&sorted, 95)),
+            ms(Self::percentile(&sorted, 99)),
+            ms(sorted.last().copied().unwrap_or_default()),
+        )
+    }
+}
+
+/// xorshift64*, seeded the same on every run so runs compare like for like
+struct Rng(u64);
+
+impl Rng {
+    fn new() -> Self {
+        Rng(0x2545_F491_4F6C_DD1D)
+    }
+
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        self.0.wrapping_mul(0x2545_F491_4F6C_DD1D)
+    }
+
+    /// Uniform in `low..=high`
+    fn between(&mut self, low: i64, high: i64) -> i64 {
+        low + (self.next() % (high - low + 1) as u64) as i64
+    }
+}
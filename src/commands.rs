@@ -0,0 +1,45 @@
+//! Offline commands the flint binary runs in place of the server
+//!
+//! Each takes the data directory lock first, so it refuses to run while a
+//! server (or another command) is using the directory and sees the files at
+//! a consistent point.
+
+use std::path::Path;
+
+use crate::config::Config;
+use crate::storage::{self, snapshot, Database};
+
+const SNAPSHOT_USAGE: &str = "usage: flint snapshot create FILE | flint snapshot restore FILE";
+
+/// `flint snapshot create FILE` packs the database into a single file;
+/// `flint snapshot restore FILE` unpacks one into an empty data directory
+/// Returns a summary for the user
+pub fn snapshot(config: &Config, args: &[String]) -> Result<String, String> {
+    let [action, file] = args else {
+        return Err(SNAPSHOT_USAGE.to_string());
+    };
+    if !matches!(action.as_str(), "create" | "restore") {
+        return Err(SNAPSHOT_USAGE.to_string());
+    }
+    let file = Path::new(file);
+    let _data_dir_lock = storage::lock_data_dir(&config.data_dir)?;
+
+    match action.as_str() {
+        "create" => {
+            if !storage::holds_database(&config.data_dir) {
+                return Err(format!("No database in {}", config.data_dir.display()));
+            }
+            // Opening the database recovers it, so the snapshot starts clean
+            let db = Database::new(config)?;
+            let stats = db.snapshot(file)?;
+            Ok(format!(
+                "created snapshot {}: {} files, {} bytes packed into {}",
+                file.display(), stats.files, stats.bytes, stats.snapshot_bytes
+            ))
+        }
+        _ => {
+            let files = snapshot::restore(file, &config.data_dir)?;
+            Ok(format!("restored {} files from {}", files, file.display()))
+        }
+    }
+}
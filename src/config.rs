@@ -1,22 +1,384 @@
+use std::path::PathBuf;
+use std::time::Duration;
+
+use clap::{ArgAction, Args, Parser, Subcommand};
+use tracing::Level;
+
+use crate::executor::evaluator::IntegerOverflow;
+use crate::executor::workload::WorkloadLimits;
+use crate::logging::{LogConfig, LogFormat, LogRotation};
+
+/// Default work_mem: bytes of materialized rows one query may hold
+const DEFAULT_WORK_MEM: usize = 64 * 1024 * 1024;
+
+/// Default bytes of encoded result rows a response holds in memory before
+/// they are spooled to disk
+const DEFAULT_RESULT_SPOOL_SIZE: usize = 16 * 1024 * 1024;
+
+/// Default cap on the statements one session keeps prepared
+const DEFAULT_MAX_PREPARED_STATEMENTS: usize = 1000;
+
+/// Default cap on the bytes of SQL one session keeps prepared
+const DEFAULT_PREPARED_STATEMENT_MEM: usize = 4 * 1024 * 1024;
+
+/// Default cap on the batch queries (bulk loads and maintenance) running at
+/// once; interactive queries have none
+const DEFAULT_MAX_BATCH_QUERIES: usize = 2;
+
+/// Default cap on the client connections open at once, as in Postgres
+const DEFAULT_MAX_CONNECTIONS: usize = 100;
+
+/// Default time a shutdown gives running queries to finish
+const DEFAULT_SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Default interval between passes of the row expiry worker
+const DEFAULT_TTL_CHECK_INTERVAL: Duration = Duration::from_secs(60);
+
+#[derive(Clone)]
 pub struct Config {
+    /// Address and port to listen on (--bind-addr=ADDR, --port=N)
     pub(crate) bind_addr: String,
     pub(crate) port: u16,
+    /// Client connections that may be open at once; more are refused
+    /// (--max-connections=N)
+    pub(crate) max_connections: usize,
+    /// Directory holding the catalog, table, index and WAL files
+    /// (--data-dir=PATH), by default the working directory
+    pub(crate) data_dir: PathBuf,
+    /// Reject every statement that writes (--read-only), as on a replica or
+    /// during a maintenance window
+    pub(crate) read_only: bool,
+    /// A read-only server is promoted to accept writes once this file appears
+    /// (--promote-trigger-file=PATH); the file is removed on promotion
+    pub(crate) promote_trigger_file: Option<PathBuf>,
+    /// Whether a write waits for the WAL to reach stable storage before it
+    /// returns (--synchronous-commit=on|off); off risks losing the last
+    /// writes in a crash, though never corrupts the database
+    pub(crate) synchronous_commit: bool,
+    /// Start even when WAL records can't be replayed, from a corrupt entry
+    /// partway through the log or a change that no longer applies
+    /// (--allow-lossy-recovery); those changes are lost, so by default the
+    /// server refuses to start and leaves the files as they are
+    pub(crate) allow_lossy_recovery: bool,
+    /// Bytes of sorted, grouped and joined rows one query may hold before it
+    /// fails (--work-mem=SIZE, e.g. 64MB)
+    pub(crate) work_mem: usize,
+    /// Bytes of encoded rows a response holds in memory before they are
+    /// spooled to a temporary file (--result-spool-size=SIZE, e.g. 16MB)
+    pub(crate) result_spool_size: usize,
+    /// Statements a session keeps prepared before the least recently used
+    /// are evicted (--max-prepared-statements=N)
+    pub(crate) max_prepared_statements: usize,
+    /// Bytes of prepared SQL a session keeps before the least recently used
+    /// statements are evicted (--prepared-statement-mem=SIZE, e.g. 4MB)
+    pub(crate) prepared_statement_mem: usize,
+    /// Backup to restore into the empty data directory before starting
+    /// (--restore-from=LOCATION, a directory or s3:// URL); incremental
+    /// backups bring in their base backups
+    pub(crate) restore_from: Option<String>,
+    /// Directory flint_backup() writes backups under and
+    /// flint_verify_backup() reads them from (--backup-dir=DIR); without it
+    /// both only take s3:// locations
+    pub(crate) backup_dir: Option<PathBuf>,
+    /// How often rows past their table's TTL are deleted
+    /// (--ttl-check-interval=SECONDS)
+    pub(crate) ttl_check_interval: Duration,
+    /// Whether integer arithmetic that overflows a bigint fails, or gives a
+    /// float instead (--integer-overflow=error|float)
+    pub(crate) integer_overflow: IntegerOverflow,
+    /// Queries of each workload class that may run at once; the rest wait
+    /// in line (--max-interactive-queries=N, --max-batch-queries=N)
+    pub(crate) workload_limits: WorkloadLimits,
+    /// How long a shutdown on SIGTERM or SIGINT waits for running queries
+    /// before it checkpoints without them (--shutdown-timeout=SECONDS)
+    pub(crate) shutdown_timeout: Duration,
+    /// Queries that run for at least this long are logged with their plan
+    /// and the rows they returned (--log-min-duration-statement=MS); 0 logs
+    /// every query, and none are logged by default
+    pub(crate) log_min_duration_statement: Option<Duration>,
+    /// Where the log goes and in what form (see `logging`)
+    pub(crate) log: LogConfig,
+    /// Certificate and key for clients that ask for TLS; without them such
+    /// clients are told the server doesn't support it
+    pub(crate) tls: Option<TlsConfig>,
+    /// Address to serve Prometheus metrics on over HTTP
+    /// (--metrics-addr=HOST:PORT); off by default
+    pub(crate) metrics_addr: Option<String>,
+    /// Load every extension built in (--load-all-extensions)
     #[cfg(feature = "extensions")]
     pub(crate) load_all_extensions: bool,
+    /// Extensions to load otherwise (--extensions=NAME,...)
     #[cfg(feature = "extensions")]
     pub(crate) enabled_extensions: Vec<String>,
 }
 
+/// TLS for client connections, negotiated with the SSLRequest clients send
+/// before their startup message
+#[derive(Clone)]
+pub struct TlsConfig {
+    /// PEM file with the server's certificate, followed by any intermediate
+    /// certificates of its chain
+    pub(crate) cert_path: PathBuf,
+    /// PEM file with the certificate's private key
+    pub(crate) key_path: PathBuf,
+}
+
+/// Command line of the flint binary: the server's flags, and an offline
+/// command to run in place of the server
+#[derive(Parser)]
+#[command(name = "flint", version, about, args_override_self = true)]
+pub struct Cli {
+    #[command(flatten)]
+    pub flags: Flags,
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+/// Offline commands; each checks its own arguments, so they are passed on
+/// as given
+#[derive(Subcommand)]
+pub enum Command {
+    /// Pack the database into a file, or unpack one into an empty data
+    /// directory: snapshot create|restore FILE
+    Snapshot { args: Vec<String> },
+    /// Measure throughput and latency: bench MIX [TRANSACTIONS] or
+    /// bench replay FILE
+    Bench { args: Vec<String> },
+    /// Run SQL interactively on the data directory
+    #[cfg(feature = "repl")]
+    Repl { args: Vec<String> },
+}
+
+impl Command {
+    /// Whether the command's output goes to the terminal, so logs should
+    /// not bury it
+    pub fn is_interactive(&self) -> bool {
+        match self {
+            Command::Snapshot { .. } => false,
+            Command::Bench { .. } => true,
+            #[cfg(feature = "repl")]
+            Command::Repl { .. } => true,
+        }
+    }
+}
+
+/// Flags of the server and the offline commands, given before or after the
+/// command; when one is given twice the last wins
+#[derive(Args)]
+pub struct Flags {
+    /// Address to listen on
+    #[arg(long, global = true, default_value = "127.0.0.1", value_name = "ADDR")]
+    bind_addr: String,
+    /// Port to listen on
+    #[arg(long, global = true, default_value_t = 5432)]
+    port: u16,
+    /// Client connections that may be open at once
+    #[arg(long, global = true, default_value_t = DEFAULT_MAX_CONNECTIONS, value_parser = parse_count, value_name = "N")]
+    max_connections: usize,
+    /// Directory holding the catalog, table, index and WAL files
+    #[arg(long, global = true, default_value = ".", value_name = "PATH")]
+    data_dir: PathBuf,
+    /// Reject every statement that writes
+    #[arg(long, global = true)]
+    read_only: bool,
+    /// Promote a read-only server once this file appears
+    #[arg(long, global = true, value_name = "PATH")]
+    promote_trigger_file: Option<PathBuf>,
+    /// Whether a commit waits for the WAL to reach stable storage (on|off)
+    #[arg(long, global = true, default_value = "on", action = ArgAction::Set, value_parser = parse_switch, value_name = "on|off")]
+    synchronous_commit: bool,
+    /// Start even if WAL records can't be replayed, losing their changes
+    #[arg(long, global = true)]
+    allow_lossy_recovery: bool,
+    /// Memory for sorted, grouped and joined rows of one query, e.g. 64MB
+    #[arg(long, global = true, default_value_t = DEFAULT_WORK_MEM, value_parser = parse_size_arg, value_name = "SIZE")]
+    work_mem: usize,
+    /// Memory for a response's rows before they are spooled to disk
+    #[arg(long, global = true, default_value_t = DEFAULT_RESULT_SPOOL_SIZE, value_parser = parse_size_arg, value_name = "SIZE")]
+    result_spool_size: usize,
+    /// Statements a session keeps prepared
+    #[arg(long, global = true, default_value_t = DEFAULT_MAX_PREPARED_STATEMENTS, value_name = "N")]
+    max_prepared_statements: usize,
+    /// Memory for a session's prepared statements
+    #[arg(long, global = true, default_value_t = DEFAULT_PREPARED_STATEMENT_MEM, value_parser = parse_size_arg, value_name = "SIZE")]
+    prepared_statement_mem: usize,
+    /// Backup to restore into the empty data directory before starting, a
+    /// directory or s3:// URL
+    #[arg(long, global = true, value_name = "LOCATION")]
+    restore_from: Option<String>,
+    /// Directory backups taken with flint_backup() go under; backups to
+    /// directories are refused without it
+    #[arg(long, global = true, value_name = "DIR")]
+    backup_dir: Option<PathBuf>,
+    /// Seconds between passes deleting rows past their table's TTL
+    #[arg(long, global = true, default_value_t = DEFAULT_TTL_CHECK_INTERVAL.as_secs(), value_parser = parse_seconds, value_name = "SECONDS")]
+    ttl_check_interval: u64,
+    /// What integer arithmetic that overflows a bigint does (error|float)
+    #[arg(long, global = true, default_value = "error", value_parser = parse_integer_overflow, value_name = "MODE")]
+    integer_overflow: IntegerOverflow,
+    /// Interactive queries that may run at once; unlimited by default
+    #[arg(long, global = true, value_parser = parse_count, value_name = "N")]
+    max_interactive_queries: Option<usize>,
+    /// Batch queries (bulk loads and maintenance) that may run at once
+    #[arg(long, global = true, default_value_t = DEFAULT_MAX_BATCH_QUERIES, value_parser = parse_count, value_name = "N")]
+    max_batch_queries: usize,
+    /// Seconds a shutdown waits for running queries
+    #[arg(long, global = true, default_value_t = DEFAULT_SHUTDOWN_TIMEOUT.as_secs(), value_name = "SECONDS")]
+    shutdown_timeout: u64,
+    /// Log queries that run for at least this many milliseconds; 0 logs all
+    #[arg(long, global = true, value_name = "MS")]
+    log_min_duration_statement: Option<u64>,
+    /// Verbosity of flint's log (error, warn, info, debug or trace),
+    /// overriding RUST_LOG
+    #[arg(long, global = true, value_name = "LEVEL")]
+    log_level: Option<Level>,
+    /// Format of each logged event (text|json)
+    #[arg(long, global = true, default_value = "text", value_parser = parse_log_format, value_name = "FORMAT")]
+    log_format: LogFormat,
+    /// Directory for log files instead of stderr
+    #[arg(long, global = true, value_name = "DIR")]
+    log_directory: Option<PathBuf>,
+    /// How often a new log file is started (hourly|daily|never)
+    #[arg(long, global = true, default_value = "daily", value_parser = parse_log_rotation, value_name = "ROTATION")]
+    log_rotation: LogRotation,
+    /// Rotated log files kept; all by default
+    #[arg(long, global = true, value_parser = parse_count, value_name = "N")]
+    log_max_files: Option<usize>,
+    /// PEM certificate chain for TLS, given with --tls-key
+    #[arg(long, global = true, requires = "tls_key", value_name = "PATH")]
+    tls_cert: Option<PathBuf>,
+    /// PEM private key for TLS, given with --tls-cert
+    #[arg(long, global = true, requires = "tls_cert", value_name = "PATH")]
+    tls_key: Option<PathBuf>,
+    /// Address to serve Prometheus metrics on over HTTP
+    #[arg(long, global = true, value_name = "HOST:PORT")]
+    metrics_addr: Option<String>,
+    /// Extensions to load, separated by commas
+    #[cfg(feature = "extensions")]
+    #[arg(long, global = true, value_delimiter = ',', default_value = "point-ext", value_name = "NAMES")]
+    extensions: Vec<String>,
+    /// Load every extension built in, whatever --extensions says
+    #[cfg(feature = "extensions")]
+    #[arg(long, global = true)]
+    load_all_extensions: bool,
+}
+
 impl Config {
+    /// Configuration from the process's command line; invalid flags print
+    /// usage and exit
     pub fn from_args() -> Self {
+        Self::from_flags(Cli::parse().flags)
+    }
+
+    pub fn from_flags(flags: Flags) -> Self {
         Config {
-            bind_addr: "127.0.0.1".to_string(),
-            port: 5432,
+            bind_addr: flags.bind_addr,
+            port: flags.port,
+            max_connections: flags.max_connections,
+            data_dir: flags.data_dir,
+            read_only: flags.read_only,
+            promote_trigger_file: flags.promote_trigger_file,
+            synchronous_commit: flags.synchronous_commit,
+            allow_lossy_recovery: flags.allow_lossy_recovery,
+            work_mem: flags.work_mem,
+            result_spool_size: flags.result_spool_size,
+            max_prepared_statements: flags.max_prepared_statements,
+            prepared_statement_mem: flags.prepared_statement_mem,
+            restore_from: flags.restore_from,
+            // Absolute, so locations are checked against it however they are given
+            backup_dir: flags.backup_dir.map(|dir| std::path::absolute(&dir).unwrap_or(dir)),
+            ttl_check_interval: Duration::from_secs(flags.ttl_check_interval),
+            integer_overflow: flags.integer_overflow,
+            shutdown_timeout: Duration::from_secs(flags.shutdown_timeout),
+            workload_limits: WorkloadLimits {
+                interactive: flags.max_interactive_queries,
+                batch: Some(flags.max_batch_queries),
+            },
+            log_min_duration_statement: flags.log_min_duration_statement.map(Duration::from_millis),
+            log: LogConfig {
+                level: flags.log_level,
+                format: flags.log_format,
+                directory: flags.log_directory,
+                rotation: flags.log_rotation,
+                max_files: flags.log_max_files,
+            },
+            tls: match (flags.tls_cert, flags.tls_key) {
+                (Some(cert_path), Some(key_path)) => Some(TlsConfig { cert_path, key_path }),
+                _ => None,
+            },
+            metrics_addr: flags.metrics_addr,
             #[cfg(feature = "extensions")]
-            load_all_extensions: false,
+            load_all_extensions: flags.load_all_extensions,
             #[cfg(feature = "extensions")]
-            enabled_extensions: vec!["point-ext".into()],
+            enabled_extensions: flags.extensions,
         }
     }
 }
 
+/// A count that must be at least one
+fn parse_count(count: &str) -> Result<usize, String> {
+    match count.parse() {
+        Ok(count) if count > 0 => Ok(count),
+        _ => Err(format!("expected a positive number, got {}", count)),
+    }
+}
+
+/// An interval in seconds that must be at least one
+fn parse_seconds(secs: &str) -> Result<u64, String> {
+    match secs.parse() {
+        Ok(secs) if secs > 0 => Ok(secs),
+        _ => Err(format!("expected a positive number of seconds, got {}", secs)),
+    }
+}
+
+fn parse_size_arg(size: &str) -> Result<usize, String> {
+    parse_size(size).ok_or_else(|| format!("expected a size such as 4096, 64kB, 16MB or 1GB, got {}", size))
+}
+
+fn parse_switch(value: &str) -> Result<bool, String> {
+    match value {
+        "on" => Ok(true),
+        "off" => Ok(false),
+        _ => Err(format!("expected on or off, got {}", value)),
+    }
+}
+
+fn parse_integer_overflow(mode: &str) -> Result<IntegerOverflow, String> {
+    match mode {
+        "error" => Ok(IntegerOverflow::Error),
+        "float" => Ok(IntegerOverflow::PromoteToFloat),
+        _ => Err(format!("expected error or float, got {}", mode)),
+    }
+}
+
+fn parse_log_format(format: &str) -> Result<LogFormat, String> {
+    match format {
+        "text" => Ok(LogFormat::Text),
+        "json" => Ok(LogFormat::Json),
+        _ => Err(format!("expected text or json, got {}", format)),
+    }
+}
+
+fn parse_log_rotation(rotation: &str) -> Result<LogRotation, String> {
+    match rotation {
+        "hourly" => Ok(LogRotation::Hourly),
+        "daily" => Ok(LogRotation::Daily),
+        "never" => Ok(LogRotation::Never),
+        _ => Err(format!("expected hourly, daily or never, got {}", rotation)),
+    }
+}
+
+/// Parse a memory size: a number of bytes, optionally with a kB, MB or GB unit
+fn parse_size(size: &str) -> Option<usize> {
+    let size = size.trim();
+    let digits = size.find(|c: char| !c.is_ascii_digit()).unwrap_or(size.len());
+    let (number, unit) = size.split_at(digits);
+    let multiplier = match unit.trim() {
+        "" | "B" => 1,
+        "kB" => 1024,
+        "MB" => 1024 * 1024,
+        "GB" => 1024 * 1024 * 1024,
+        _ => return None,
+    };
+    number.parse::<usize>().ok()?.checked_mul(multiplier)
+}
@@ -0,0 +1,116 @@
+//! Adaptive join execution
+//!
+//! Joins are planned as nested loops, which compare every left row with every
+//! right row. That is only cheap while the inputs are about as small as
+//! estimated, and estimates can be far off: a join's output is guessed with a
+//! default selectivity, so a join on a key with many duplicates feeds the join
+//! above it many times the rows it was planned for. Once a join has read its
+//! inputs it compares their actual sizes with the estimates. When the pairs to
+//! compare exceed the estimate many times over and the condition equates a
+//! column of each side, the join is run as a hash join on those columns
+//! instead, and the switch is logged. The hash join yields the same rows in
+//! the same order as the nested loop would.
+
+use std::collections::HashMap;
+
+use sqlparser::ast::{BinaryOperator, Expr};
+
+use crate::executor::aggregate;
+use crate::executor::error::ExecutorError;
+use crate::executor::evaluator;
+use crate::executor::memory::{self, MemoryBudget};
+use crate::types::{DataType, Row, Schema, Value};
+
+pub type Result<T> = std::result::Result<T, ExecutorError>;
+
+/// How many times the estimated pairs a join has to compare before it
+/// switches to a hash join
+const MISESTIMATE_FACTOR: f64 = 10.0;
+
+/// Pairs below which a nested loop is left alone, however far off the
+/// estimate: building a hash table would cost as much as the loop
+const MIN_ADAPTIVE_PAIRS: usize = 10_000;
+
+/// Whether a join that was estimated to compare `estimated_pairs` pairs of
+/// rows and has `actual_pairs` to compare should switch strategy
+pub fn is_misestimated(estimated_pairs: f64, actual_pairs: usize) -> bool {
+    actual_pairs >= MIN_ADAPTIVE_PAIRS && actual_pairs as f64 > estimated_pairs * MISESTIMATE_FACTOR
+}
+
+/// Columns an equi-join condition equates, as the position of the left
+/// column in the joined row and of the right column in the right rows
+/// The condition, or one of its ANDed terms, has to be `left = right` between
+/// columns of the same type whose equal values always encode the same; floats
+/// don't, as 1 = 1.0 would be missed
+pub fn equi_join_key(condition: &Expr, schema: &Schema, left_width: usize) -> Option<(usize, usize)> {
+    match condition {
+        Expr::Nested(inner) => equi_join_key(inner, schema, left_width),
+        Expr::BinaryOp { left, op: BinaryOperator::And, right } => {
+            equi_join_key(left, schema, left_width).or_else(|| equi_join_key(right, schema, left_width))
+        }
+        Expr::BinaryOp { left, op: BinaryOperator::Eq, right } => {
+            let a = column_position(left, schema)?;
+            let b = column_position(right, schema)?;
+            let (left_idx, right_idx) = match (a < left_width, b < left_width) {
+                (true, false) => (a, b),
+                (false, true) => (b, a),
+                _ => return None,
+            };
+            let data_type = &schema.columns[left_idx].data_type;
+            let hashable = matches!(data_type, DataType::Int | DataType::String | DataType::Bool);
+            (hashable && *data_type == schema.columns[right_idx].data_type).then_some((left_idx, right_idx - left_width))
+        }
+        _ => None,
+    }
+}
+
+fn column_position(expr: &Expr, schema: &Schema) -> Option<usize> {
+    let name = match expr {
+        Expr::Identifier(ident) => ident.value.clone(),
+        Expr::CompoundIdentifier(parts) => parts.iter().map(|part| part.value.as_str()).collect::<Vec<_>>().join("."),
+        _ => return None,
+    };
+    evaluator::resolve_column(schema, &name).ok()
+}
+
+/// Join by hashing the right rows on their key column, then probing with each
+/// left row's key; candidate pairs are kept if the whole condition holds
+/// NULL keys equal nothing, so they are neither hashed nor probed
+pub fn hash_join(
+    left_rows: &[Row],
+    right_rows: &[Row],
+    (left_key, right_key): (usize, usize),
+    condition: &Expr,
+    schema: &Schema,
+    budget: &MemoryBudget,
+) -> Result<Vec<Row>> {
+    let mut buckets: HashMap<Vec<u8>, Vec<usize>> = HashMap::new();
+    for (idx, right_row) in right_rows.iter().enumerate() {
+        let value = &right_row.values[right_key];
+        if matches!(value, Value::Null) {
+            continue;
+        }
+        let key = aggregate::group_key(std::slice::from_ref(value))?;
+        budget.charge(key.len() + std::mem::size_of::<usize>(), "JOIN")?;
+        buckets.entry(key).or_default().push(idx);
+    }
+
+    let mut joined = Vec::new();
+    for left_row in left_rows {
+        let value = &left_row.values[left_key];
+        if matches!(value, Value::Null) {
+            continue;
+        }
+        let Some(matches) = buckets.get(&aggregate::group_key(std::slice::from_ref(value))?) else {
+            continue;
+        };
+        for &idx in matches {
+            let row = Row::new(left_row.values.iter().chain(&right_rows[idx].values).cloned().collect());
+            if matches!(evaluator::eval_expr(condition, &row, schema)?, Value::Bool(true)) {
+                budget.charge(memory::row_size(&row), "JOIN")?;
+                joined.push(row);
+            }
+        }
+    }
+    Ok(joined)
+}
@@ -0,0 +1,19 @@
+//! Session-level advisory locks (pg_advisory_lock and friends)
+//!
+//! Locks on application-chosen keys that flint never takes itself, for
+//! applications to coordinate through: electing a leader, or making sure only
+//! one instance runs migrations. A session may take a lock it already holds,
+//! and keeps it until it has unlocked it as many times. As in Postgres the
+//! locks are not tied to transactions, so ROLLBACK does not release them, but
+//! closing the connection does. The locks are kept with the write lock (see
+//! `transaction`), so a wait that would deadlock, on another advisory lock or
+//! on the write lock, fails instead, as does one past the session's
+//! lock_timeout.
+
+/// Key of an advisory lock: one bigint, or a pair of integers, which are a
+/// separate key space as in Postgres
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum LockKey {
+    Single(i64),
+    Pair(i32, i32),
+}
@@ -0,0 +1,194 @@
+//! Aggregate functions: COUNT, SUM, AVG, MIN and MAX
+//!
+//! Each aggregate call in a query gets an accumulator that is fed the call's
+//! argument for every input row and produces one value at the end. NULL
+//! arguments are skipped, so an aggregate over no non-NULL values is NULL,
+//! except COUNT, which is 0.
+//!
+//! With GROUP BY, rows are hashed into groups by the values of the GROUP BY
+//! expressions and each group gets its own accumulators.
+
+use sqlparser::ast::{Expr, Function, FunctionArg, FunctionArgExpr, FunctionArguments};
+
+use crate::executor::error::ExecutorError;
+use crate::executor::evaluator;
+use crate::types::Value;
+
+pub type Result<T> = std::result::Result<T, ExecutorError>;
+
+/// Names of the aggregate functions, as written in lowercase
+pub const AGGREGATE_FUNCTIONS: &[&str] = &["count", "sum", "avg", "min", "max"];
+
+/// Whether an expression is a call to an aggregate function
+pub fn is_aggregate_call(expr: &Expr) -> bool {
+    match expr {
+        Expr::Function(func) => function_name(func)
+            .is_some_and(|name| AGGREGATE_FUNCTIONS.contains(&name.as_str())),
+        _ => false,
+    }
+}
+
+fn function_name(func: &Function) -> Option<String> {
+    match func.name.0.as_slice() {
+        [part] => part.as_ident().map(|ident| ident.value.to_lowercase()),
+        _ => None,
+    }
+}
+
+/// Hashable encoding of a row's GROUP BY values; equal values encode the same,
+/// so NULLs form one group, as in Postgres
+pub fn group_key(values: &[Value]) -> Result<Vec<u8>> {
+    let mut key = Vec::new();
+    for value in values {
+        let value = match value {
+            // 0.0 and -0.0 compare equal, so they share a group
+            Value::Float(f) if *f == 0.0 => &Value::Float(0.0),
+            Value::Extension { .. } => return Err(ExecutorError::UnsupportedStatement(
+                "GROUP BY on extension type values not supported".to_string(),
+            )),
+            value => value,
+        };
+        bincode::encode_into_std_write(value, &mut key, bincode::config::standard())
+            .map_err(|e| ExecutorError::Execution(format!("Failed to encode group key: {}", e)))?;
+    }
+    Ok(key)
+}
+
+/// A parsed aggregate call: which function, and the argument it is fed
+#[derive(Debug)]
+pub struct AggregateCall {
+    name: String,
+    /// None for COUNT(*), which counts rows rather than values
+    pub arg: Option<Expr>,
+}
+
+impl AggregateCall {
+    pub fn parse(expr: &Expr) -> Result<Self> {
+        let Expr::Function(func) = expr else {
+            return Err(ExecutorError::Execution(format!("Not an aggregate call: {}", expr)));
+        };
+        let name = function_name(func)
+            .filter(|name| AGGREGATE_FUNCTIONS.contains(&name.as_str()))
+            .ok_or_else(|| ExecutorError::Execution(format!("Not an aggregate call: {}", expr)))?;
+        if func.over.is_some() || func.filter.is_some() || !func.within_group.is_empty() {
+            return Err(ExecutorError::UnsupportedStatement(format!(
+                "Window functions, FILTER and WITHIN GROUP not supported: {}",
+                expr
+            )));
+        }
+
+        let FunctionArguments::List(list) = &func.args else {
+            return Err(ExecutorError::Execution(format!("{}() requires an argument", name)));
+        };
+        if list.duplicate_treatment.is_some() || !list.clauses.is_empty() {
+            return Err(ExecutorError::UnsupportedStatement(format!(
+                "DISTINCT and ORDER BY in aggregate arguments not supported: {}",
+                expr
+            )));
+        }
+        let arg = match list.args.as_slice() {
+            [FunctionArg::Unnamed(FunctionArgExpr::Wildcard)] if name == "count" => None,
+            [FunctionArg::Unnamed(FunctionArgExpr::Expr(arg))] => Some(arg.clone()),
+            _ => return Err(ExecutorError::Execution(format!(
+                "{}() takes exactly one argument",
+                name
+            ))),
+        };
+
+        Ok(AggregateCall { name, arg })
+    }
+
+    /// A fresh accumulator for this call
+    pub fn accumulator(&self) -> Accumulator {
+        match self.name.as_str() {
+            "count" => Accumulator::Count(0),
+            "sum" => Accumulator::Sum(None),
+            "avg" => Accumulator::Avg { sum: 0.0, count: 0 },
+            "min" => Accumulator::Min(None),
+            _ => Accumulator::Max(None),
+        }
+    }
+}
+
+/// Running state of one aggregate call
+#[derive(Debug)]
+pub enum Accumulator {
+    Count(i64),
+    Sum(Option<Value>),
+    Avg { sum: f64, count: i64 },
+    Min(Option<Value>),
+    Max(Option<Value>),
+}
+
+impl Accumulator {
+    /// Feed the value of the call's argument for one row (None for COUNT(*))
+    pub fn update(&mut self, value: Option<Value>) -> Result<()> {
+        let value = match value {
+            None => {
+                if let Accumulator::Count(count) = self {
+                    *count += 1;
+                }
+                return Ok(());
+            }
+            Some(Value::Null) => return Ok(()),
+            Some(value) => value,
+        };
+
+        let is_min = matches!(self, Accumulator::Min(_));
+        match self {
+            Accumulator::Count(count) => *count += 1,
+            Accumulator::Sum(sum) => {
+                *sum = Some(match (sum.take(), value) {
+                    (None, value @ (Value::Int(_) | Value::Float(_))) => value,
+                    (Some(Value::Int(a)), Value::Int(b)) => match a.checked_add(b) {
+                        Some(sum) => Value::Int(sum),
+                        None => evaluator::integer_overflow(a as f64 + b as f64)?,
+                    },
+                    (Some(Value::Int(a)), Value::Float(b)) => Value::Float(a as f64 + b),
+                    (Some(Value::Float(a)), Value::Int(b)) => Value::Float(a + b as f64),
+                    (Some(Value::Float(a)), Value::Float(b)) => Value::Float(a + b),
+                    (_, value) => return Err(ExecutorError::Execution(format!(
+                        "sum() needs numbers, got {:?}",
+                        value
+                    ))),
+                });
+            }
+            Accumulator::Avg { sum, count } => {
+                *sum += match value {
+                    Value::Int(n) => n as f64,
+                    Value::Float(f) => f,
+                    value => return Err(ExecutorError::Execution(format!(
+                        "avg() needs numbers, got {:?}",
+                        value
+                    ))),
+                };
+                *count += 1;
+            }
+            Accumulator::Min(current) | Accumulator::Max(current) => {
+                let keep_new = match current {
+                    None => true,
+                    Some(current) => {
+                        let ordering = evaluator::compare_values(&value, current)?;
+                        if is_min { ordering.is_lt() } else { ordering.is_gt() }
+                    }
+                };
+                if keep_new {
+                    *current = Some(value);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// The aggregate's result over every value fed to it
+    pub fn finish(self) -> Value {
+        match self {
+            Accumulator::Count(count) => Value::Int(count),
+            Accumulator::Avg { count: 0, .. } => Value::Null,
+            Accumulator::Avg { sum, count } => Value::Float(sum / count as f64),
+            Accumulator::Sum(value) | Accumulator::Min(value) | Accumulator::Max(value) => {
+                value.unwrap_or(Value::Null)
+            }
+        }
+    }
+}
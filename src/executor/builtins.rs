@@ -0,0 +1,160 @@
+//! Built-in scalar functions the evaluator calls directly (pg_sleep(),
+//! version() and the clock functions)
+//!
+//! Unlike the executor's own functions (statistics, sequences, backups,
+//! advisory locks), these need nothing from the database or the session, so
+//! they are evaluated per row like any other expression, wherever an
+//! expression may appear. Each is an entry in BUILTINS giving its argument
+//! counts and result type, which typing reports before any row is evaluated.
+//!
+//! There is no timestamp type yet, so timestamps are returned as text in the
+//! form Postgres prints a timestamptz, in UTC.
+
+use std::cell::Cell;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use tracing::debug;
+
+use crate::executor::error::ExecutorError;
+use crate::types::{DataType, Value};
+
+pub type Result<T> = std::result::Result<T, ExecutorError>;
+
+/// Longest pg_sleep() accepted, so a typo can't hold a connection for days
+const MAX_SLEEP: Duration = Duration::from_secs(24 * 60 * 60);
+
+pub struct Builtin {
+    pub name: &'static str,
+    min_args: usize,
+    max_args: usize,
+    pub return_type: DataType,
+    eval: fn(&[Value]) -> Result<Value>,
+}
+
+static BUILTINS: &[Builtin] = &[
+    Builtin { name: "pg_sleep", min_args: 1, max_args: 1, return_type: DataType::Null, eval: pg_sleep },
+    Builtin { name: "clock_timestamp", min_args: 0, max_args: 0, return_type: DataType::String, eval: clock_timestamp },
+    Builtin { name: "statement_timestamp", min_args: 0, max_args: 0, return_type: DataType::String, eval: statement_timestamp },
+    Builtin { name: "version", min_args: 0, max_args: 0, return_type: DataType::String, eval: version },
+];
+
+/// Postgres version flint reports itself compatible with, as pgwire does in
+/// the server_version parameter
+pub const POSTGRES_VERSION: &str = "16.6";
+
+thread_local! {
+    /// When the statement running on this thread started; queries run on a
+    /// blocking thread of their own for their whole execution
+    static STATEMENT_START: Cell<Option<SystemTime>> = const { Cell::new(None) };
+}
+
+/// The built-in function called `name`, if there is one
+/// Built-ins may be qualified with pg_catalog, where Postgres keeps them
+pub fn lookup(name: &str) -> Option<&'static Builtin> {
+    let name = name.to_lowercase();
+    let name = name.strip_prefix("pg_catalog.").unwrap_or(&name);
+    BUILTINS.iter().find(|builtin| builtin.name == name)
+}
+
+impl Builtin {
+    /// Call the function with evaluated arguments
+    pub fn call(&self, args: &[Value]) -> Result<Value> {
+        if args.len() < self.min_args || args.len() > self.max_args {
+            let expected = match (self.min_args, self.max_args) {
+                (0, 0) => "no arguments".to_string(),
+                (min, max) if min == max => format!("{} argument{}", min, if min == 1 { "" } else { "s" }),
+                (min, max) => format!("{} to {} arguments", min, max),
+            };
+            return Err(ExecutorError::Execution(format!(
+                "{}() takes {}, got {}",
+                self.name, expected, args.len()
+            )));
+        }
+        (self.eval)(args)
+    }
+}
+
+/// Record the start of a query, which statement_timestamp() reports for
+/// every statement in it, as Postgres does for a simple query message
+pub fn start_statement() {
+    STATEMENT_START.with(|start| start.set(Some(SystemTime::now())));
+}
+
+/// pg_sleep(seconds): wait, returning nothing
+fn pg_sleep(args: &[Value]) -> Result<Value> {
+    let seconds = match &args[0] {
+        Value::Null => return Ok(Value::Null),
+        Value::Int(seconds) => *seconds as f64,
+        Value::Float(seconds) => *seconds,
+        other => return Err(ExecutorError::Execution(format!(
+            "pg_sleep() expects a number of seconds, got {:?}",
+            other
+        ))),
+    };
+    if seconds.is_nan() {
+        return Err(ExecutorError::Execution("pg_sleep() expects a number of seconds, got NaN".to_string()));
+    }
+    let duration = Duration::try_from_secs_f64(seconds.max(0.0)).unwrap_or(MAX_SLEEP);
+    if duration > MAX_SLEEP {
+        return Err(ExecutorError::Execution(format!(
+            "pg_sleep() may sleep at most {} seconds",
+            MAX_SLEEP.as_secs()
+        )));
+    }
+    debug!(seconds, "sleeping");
+    std::thread::sleep(duration);
+    Ok(Value::Null)
+}
+
+/// clock_timestamp(): the current time, which changes during a statement
+fn clock_timestamp(_args: &[Value]) -> Result<Value> {
+    Ok(Value::String(format_timestamp(SystemTime::now())))
+}
+
+/// statement_timestamp(): the time the current query started
+fn statement_timestamp(_args: &[Value]) -> Result<Value> {
+    let start = STATEMENT_START.with(Cell::get).unwrap_or_else(SystemTime::now);
+    Ok(Value::String(format_timestamp(start)))
+}
+
+/// version(): the Postgres version clients may expect, then flint's own
+fn version(_args: &[Value]) -> Result<Value> {
+    Ok(Value::String(format!("PostgreSQL {} (flint {})", POSTGRES_VERSION, env!("CARGO_PKG_VERSION"))))
+}
+
+/// Format a time as Postgres prints a timestamptz in UTC, e.g.
+/// `2024-03-01 12:30:05.25+00`
+fn format_timestamp(time: SystemTime) -> String {
+    let since_epoch = time.duration_since(UNIX_EPOCH).unwrap_or_default();
+    let secs = since_epoch.as_secs() as i64;
+    let (year, month, day) = civil_from_days(secs.div_euclid(86_400));
+    let secs_of_day = secs.rem_euclid(86_400);
+    let mut formatted = format!(
+        "{:04}-{:02}-{:02} {:02}:{:02}:{:02}",
+        year, month, day,
+        secs_of_day / 3600, secs_of_day % 3600 / 60, secs_of_day % 60
+    );
+    let micros = since_epoch.subsec_micros();
+    if micros > 0 {
+        let fraction = format!("{:06}", micros);
+        formatted.push('.');
+        formatted.push_str(fraction.trim_end_matches('0'));
+    }
+    formatted.push_str("+00");
+    formatted
+}
+
+/// Year, month and day of a count of days since 1970-01-01
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    // Days since 0000-03-01, so the leap day ends each 400 year era
+    let days = days + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days.rem_euclid(146_097);
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_from_march = (5 * day_of_year + 2) / 153;
+    let day = (day_of_year - (153 * month_from_march + 2) / 5 + 1) as u32;
+    let month = if month_from_march < 10 { month_from_march + 3 } else { month_from_march - 9 } as u32;
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
@@ -0,0 +1,282 @@
+//! Conversion of values between types: `CAST(x AS type)`, `x::type` and
+//! typed literals (`DATE '2024-01-01'`)
+//!
+//! A cast names a SQL type, which is first resolved to a CastTarget. Targets
+//! are finer than DataType where a cast has to check or reshape its result:
+//! integer widths have their own ranges, and varchar(n) truncates. There are
+//! no date or timestamp types yet, so casts to them validate the text and
+//! return it in the form Postgres prints, as the clock functions do.
+
+use sqlparser::ast::{CharacterLength, DataType as SqlDataType, TimezoneInfo};
+
+use crate::executor::error::ExecutorError;
+use crate::types::{DataType, Value};
+
+pub type Result<T> = std::result::Result<T, ExecutorError>;
+
+/// A type values can be cast to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CastTarget {
+    SmallInt,
+    Integer,
+    BigInt,
+    Float,
+    /// Text, cut to a maximum length in characters if there is one
+    Text(Option<u64>),
+    /// Text blank-padded to a fixed length in characters
+    Char(u64),
+    Bool,
+    Date,
+    Timestamp { with_time_zone: bool },
+}
+
+impl CastTarget {
+    /// Resolve the SQL type named in a cast
+    pub fn from_sql(data_type: &SqlDataType) -> Result<Self> {
+        let length = |length: &Option<CharacterLength>| match length {
+            Some(CharacterLength::IntegerLength { length, .. }) => Some(*length),
+            _ => None,
+        };
+        let target = match data_type {
+            SqlDataType::SmallInt(_) | SqlDataType::Int2(_) => CastTarget::SmallInt,
+            SqlDataType::Int(_) | SqlDataType::Integer(_) | SqlDataType::Int4(_) => CastTarget::Integer,
+            SqlDataType::BigInt(_) | SqlDataType::Int8(_) => CastTarget::BigInt,
+            SqlDataType::Float(_)
+            | SqlDataType::Float4
+            | SqlDataType::Float8
+            | SqlDataType::Float64
+            | SqlDataType::Real
+            | SqlDataType::Double(_)
+            | SqlDataType::DoublePrecision
+            | SqlDataType::Numeric(_)
+            | SqlDataType::Decimal(_) => CastTarget::Float,
+            SqlDataType::Text | SqlDataType::String(_) => CastTarget::Text(None),
+            SqlDataType::Varchar(len) | SqlDataType::CharacterVarying(len) => CastTarget::Text(length(len)),
+            SqlDataType::Char(len) | SqlDataType::Character(len) => CastTarget::Char(length(len).unwrap_or(1)),
+            SqlDataType::Bool | SqlDataType::Boolean => CastTarget::Bool,
+            SqlDataType::Date => CastTarget::Date,
+            SqlDataType::Timestamp(_, tz) => CastTarget::Timestamp {
+                with_time_zone: matches!(tz, TimezoneInfo::WithTimeZone | TimezoneInfo::Tz),
+            },
+            other => return Err(ExecutorError::Execution(format!("cannot cast to type {}", other))),
+        };
+        Ok(target)
+    }
+
+    /// The type of the cast's result
+    pub fn data_type(self) -> DataType {
+        match self {
+            CastTarget::SmallInt | CastTarget::Integer | CastTarget::BigInt => DataType::Int,
+            CastTarget::Float => DataType::Float,
+            CastTarget::Bool => DataType::Bool,
+            CastTarget::Text(_) | CastTarget::Char(_) | CastTarget::Date | CastTarget::Timestamp { .. } => {
+                DataType::String
+            }
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            CastTarget::SmallInt => "smallint",
+            CastTarget::Integer => "integer",
+            CastTarget::BigInt => "bigint",
+            CastTarget::Float => "double precision",
+            CastTarget::Text(_) => "text",
+            CastTarget::Char(_) => "character",
+            CastTarget::Bool => "boolean",
+            CastTarget::Date => "date",
+            CastTarget::Timestamp { with_time_zone: false } => "timestamp",
+            CastTarget::Timestamp { with_time_zone: true } => "timestamp with time zone",
+        }
+    }
+}
+
+/// Cast a value; NULL casts to NULL of any type
+pub fn cast_value(value: Value, target: CastTarget) -> Result<Value> {
+    let invalid = |text: &str| ExecutorError::Execution(format!(
+        "invalid input syntax for type {}: \"{}\"",
+        target.name(), text
+    ));
+    let cannot = |value: &Value| ExecutorError::Execution(format!(
+        "cannot cast {} to {}",
+        value_type_name(value), target.name()
+    ));
+
+    match (value, target) {
+        (Value::Null, _) => Ok(Value::Null),
+
+        (Value::Int(n), CastTarget::SmallInt | CastTarget::Integer | CastTarget::BigInt) => check_int_range(n, target),
+        (Value::Float(f), CastTarget::SmallInt | CastTarget::Integer | CastTarget::BigInt) => {
+            // Rounds half to even, as Postgres does
+            let rounded = f.round_ties_even();
+            if !rounded.is_finite() || rounded < i64::MIN as f64 || rounded >= i64::MAX as f64 {
+                return Err(out_of_range(target));
+            }
+            check_int_range(rounded as i64, target)
+        }
+        (Value::Bool(b), CastTarget::Integer) => Ok(Value::Int(i64::from(b))),
+        (Value::String(s), CastTarget::SmallInt | CastTarget::Integer | CastTarget::BigInt) => {
+            let n = s.trim().parse::<i64>().map_err(|_| invalid(&s))?;
+            check_int_range(n, target)
+        }
+
+        (Value::Int(n), CastTarget::Float) => Ok(Value::Float(n as f64)),
+        (Value::Float(f), CastTarget::Float) => Ok(Value::Float(f)),
+        (Value::String(s), CastTarget::Float) => {
+            let trimmed = s.trim();
+            let f = match trimmed.to_lowercase().as_str() {
+                "nan" => f64::NAN,
+                "infinity" | "inf" | "+infinity" | "+inf" => f64::INFINITY,
+                "-infinity" | "-inf" => f64::NEG_INFINITY,
+                _ => trimmed.parse::<f64>().ok().filter(|f| f.is_finite()).ok_or_else(|| invalid(&s))?,
+            };
+            Ok(Value::Float(f))
+        }
+
+        (Value::Bool(b), CastTarget::Bool) => Ok(Value::Bool(b)),
+        (Value::Int(n), CastTarget::Bool) => Ok(Value::Bool(n != 0)),
+        (Value::String(s), CastTarget::Bool) => parse_bool(&s).map(Value::Bool).ok_or_else(|| invalid(&s)),
+
+        (Value::String(s), CastTarget::Date) => parse_date(s.trim())
+            .map(|(year, month, day)| Value::String(format!("{:04}-{:02}-{:02}", year, month, day)))
+            .ok_or_else(|| invalid(&s)),
+        (Value::String(s), CastTarget::Timestamp { with_time_zone }) => parse_timestamp(s.trim(), with_time_zone)
+            .map(Value::String)
+            .ok_or_else(|| invalid(&s)),
+
+        (Value::Int(n), CastTarget::Text(_) | CastTarget::Char(_)) => Ok(Value::String(fit_text(n.to_string(), target))),
+        (Value::Float(f), CastTarget::Text(_) | CastTarget::Char(_)) => Ok(Value::String(fit_text(f.to_string(), target))),
+        (Value::Bool(b), CastTarget::Text(_) | CastTarget::Char(_)) => Ok(Value::String(fit_text(b.to_string(), target))),
+        (Value::String(s), CastTarget::Text(_) | CastTarget::Char(_)) => Ok(Value::String(fit_text(s, target))),
+
+        (value, _) => Err(cannot(&value)),
+    }
+}
+
+fn check_int_range(n: i64, target: CastTarget) -> Result<Value> {
+    let in_range = match target {
+        CastTarget::SmallInt => i16::try_from(n).is_ok(),
+        CastTarget::Integer => i32::try_from(n).is_ok(),
+        _ => true,
+    };
+    if !in_range {
+        return Err(out_of_range(target));
+    }
+    Ok(Value::Int(n))
+}
+
+fn out_of_range(target: CastTarget) -> ExecutorError {
+    ExecutorError::OutOfRange(format!("{} out of range", target.name()))
+}
+
+/// Cut text to a varchar's length, or pad it to a char's
+fn fit_text(text: String, target: CastTarget) -> String {
+    match target {
+        CastTarget::Text(Some(max)) => text.chars().take(max as usize).collect(),
+        CastTarget::Char(len) => {
+            let fitted: String = text.chars().take(len as usize).collect();
+            let padding = len as usize - fitted.chars().count();
+            fitted + &" ".repeat(padding)
+        }
+        _ => text,
+    }
+}
+
+fn value_type_name(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "unknown",
+        Value::Int(_) => "integer",
+        Value::Float(_) => "double precision",
+        Value::String(_) => "text",
+        Value::Bool(_) => "boolean",
+        Value::Extension { .. } => "an extension type",
+    }
+}
+
+/// Boolean input as Postgres accepts it: any unambiguous prefix of true,
+/// false, yes or no, on or off, and 1 or 0, in any case
+fn parse_bool(text: &str) -> Option<bool> {
+    let text = text.trim().to_lowercase();
+    if text.is_empty() {
+        return None;
+    }
+    match text.as_str() {
+        "1" | "on" => return Some(true),
+        "0" | "of" | "off" => return Some(false),
+        _ => {}
+    }
+    if "true".starts_with(&text) || "yes".starts_with(&text) {
+        Some(true)
+    } else if "false".starts_with(&text) || "no".starts_with(&text) {
+        Some(false)
+    } else {
+        None
+    }
+}
+
+/// Parse a `YYYY-MM-DD` date, checking the day exists
+fn parse_date(text: &str) -> Option<(i64, u32, u32)> {
+    let mut parts = text.splitn(3, '-');
+    let year = parts.next()?.parse::<i64>().ok()?;
+    let month = parts.next()?.parse::<u32>().ok()?;
+    let day = parts.next()?.parse::<u32>().ok()?;
+    let leap = year % 4 == 0 && (year % 100 != 0 || year % 400 == 0);
+    let days_in_month = match month {
+        1 | 3 | 5 | 7 | 8 | 10 | 12 => 31,
+        4 | 6 | 9 | 11 => 30,
+        2 if leap => 29,
+        2 => 28,
+        _ => return None,
+    };
+    if !(1..=9999).contains(&year) || day == 0 || day > days_in_month {
+        return None;
+    }
+    Some((year, month, day))
+}
+
+/// Parse a `YYYY-MM-DD[ HH:MM[:SS[.ffffff]]]` timestamp, with a `+HH` or `Z`
+/// offset for a timestamp with time zone, and print it back normalized
+/// Offsets other than UTC are not converted, so they are rejected
+fn parse_timestamp(text: &str, with_time_zone: bool) -> Option<String> {
+    let (date, time) = match text.split_once([' ', 'T']) {
+        Some((date, time)) => (date, time.trim()),
+        None => (text, "00:00:00"),
+    };
+    let (year, month, day) = parse_date(date)?;
+
+    let time = match time.strip_suffix("+00").or_else(|| time.strip_suffix('Z')) {
+        Some(time) => time,
+        None if time.contains(['+', '-', 'Z']) => return None,
+        None => time,
+    };
+    let (clock, fraction) = match time.split_once('.') {
+        Some((clock, fraction)) => (clock, Some(fraction)),
+        None => (time, None),
+    };
+    let mut fields = clock.split(':');
+    let hour = fields.next()?.parse::<u32>().ok()?;
+    let minute = fields.next()?.parse::<u32>().ok()?;
+    let second = fields.next().map_or(Some(0), |s| s.parse::<u32>().ok())?;
+    if fields.next().is_some() || hour > 23 || minute > 59 || second > 59 {
+        return None;
+    }
+
+    let mut formatted = format!(
+        "{:04}-{:02}-{:02} {:02}:{:02}:{:02}",
+        year, month, day, hour, minute, second
+    );
+    if let Some(fraction) = fraction {
+        if fraction.is_empty() || fraction.len() > 6 || !fraction.bytes().all(|b| b.is_ascii_digit()) {
+            return None;
+        }
+        let fraction = fraction.trim_end_matches('0');
+        if !fraction.is_empty() {
+            formatted.push('.');
+            formatted.push_str(fraction);
+        }
+    }
+    if with_time_zone {
+        formatted.push_str("+00");
+    }
+    Some(formatted)
+}
@@ -0,0 +1,230 @@
+//! The databases of a server (CREATE DATABASE and DROP DATABASE)
+//!
+//! A server holds any number of databases, each with its own catalog, tables
+//! and WAL, and each connection uses the one its startup message names. The
+//! database every server has, `postgres`, is the data directory itself, so
+//! data directories from before there were others are used as they were; the
+//! others live in directories of their own under `databases/`. Every
+//! database is opened when the server starts, each with its own executor.
+//! The executors share the server's read-only state and limits on running
+//! queries, so promoting the server promotes every database and a shutdown
+//! waits for the queries of all of them.
+
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::{Arc, Weak};
+use std::time::Duration;
+
+use parking_lot::Mutex;
+use tracing::{info, warn};
+
+use crate::config::Config;
+use crate::executor::cursor::SessionId;
+use crate::executor::error::ExecutorError;
+use crate::executor::Executor;
+
+pub type Result<T> = std::result::Result<T, ExecutorError>;
+
+/// Name of the database kept in the data directory itself, which every
+/// server has
+pub const DEFAULT_DATABASE: &str = "postgres";
+
+/// Directory under the data directory holding the other databases
+const DATABASES_DIR: &str = "databases";
+
+/// Longest database name, as in Postgres
+const MAX_NAME_LENGTH: usize = 63;
+
+pub(crate) struct Cluster {
+    config: Config,
+    state: Mutex<ClusterState>,
+}
+
+struct ClusterState {
+    /// Every database of the server, by name
+    databases: HashMap<String, Arc<Executor>>,
+    /// Database each open connection is using
+    connections: HashMap<SessionId, String>,
+}
+
+impl Cluster {
+    /// Open the default database and every other one the data directory holds
+    /// Fails if any of them can't be opened
+    pub fn new(config: &Config) -> Result<Arc<Self>> {
+        let mut failure = None;
+        let cluster = Arc::new_cyclic(|cluster| {
+            let databases = open_databases(config, cluster).unwrap_or_else(|e| {
+                failure = Some(e);
+                HashMap::new()
+            });
+            Cluster {
+                config: config.clone(),
+                state: Mutex::new(ClusterState { databases, connections: HashMap::new() }),
+            }
+        });
+        match failure {
+            Some(e) => Err(e),
+            None => Ok(cluster),
+        }
+    }
+
+    /// Start a connection's use of database `name`
+    pub fn connect(&self, session_id: SessionId, name: &str) -> Result<()> {
+        let mut state = self.state.lock();
+        if !state.databases.contains_key(name) {
+            return Err(does_not_exist(name));
+        }
+        state.connections.insert(session_id, name.to_string());
+        Ok(())
+    }
+
+    /// Executor of the database a connection is using
+    pub fn executor(&self, session_id: SessionId) -> Arc<Executor> {
+        let state = self.state.lock();
+        let name = state.connections.get(&session_id).map_or(DEFAULT_DATABASE, String::as_str);
+        state.databases.get(name)
+            .or_else(|| state.databases.get(DEFAULT_DATABASE))
+            .expect("the default database is never dropped")
+            .clone()
+    }
+
+    /// Every database's executor, for work done on all of them
+    pub fn executors(&self) -> Vec<Arc<Executor>> {
+        self.state.lock().databases.values().cloned().collect()
+    }
+
+    /// End a connection's use of its database, dropping its session's state
+    pub fn end_session(&self, session_id: SessionId) {
+        let executor = {
+            let mut state = self.state.lock();
+            let Some(name) = state.connections.remove(&session_id) else {
+                return;
+            };
+            state.databases.get(&name).cloned()
+        };
+        if let Some(executor) = executor {
+            executor.end_session(session_id);
+        }
+    }
+
+    /// Refuse further queries and checkpoint every database once the running
+    /// queries finish or `timeout` passes
+    pub fn shutdown(&self, timeout: Duration) -> Result<()> {
+        let mut result = Ok(());
+        for executor in self.executors() {
+            // Queries are admitted server-wide, so only the first waits
+            if let Err(e) = executor.shutdown(timeout) {
+                let e = e.into_error_info().message;
+                warn!(database = %executor.database, error = %e, "database not checkpointed");
+                result = Err(ExecutorError::Execution(e));
+            }
+        }
+        result
+    }
+
+    /// Create an empty database, returning false if one of the name already
+    /// exists and `if_not_exists` is set
+    pub fn create_database(&self, name: &str, if_not_exists: bool) -> Result<bool> {
+        check_name(name)?;
+        let mut state = self.state.lock();
+        if state.databases.contains_key(name) {
+            if if_not_exists {
+                return Ok(false);
+            }
+            return Err(ExecutorError::DuplicateDatabase(format!("database \"{}\" already exists", name)));
+        }
+        let default = state.databases.get(DEFAULT_DATABASE)
+            .expect("the default database is never dropped");
+        let executor = Executor::open(&database_config(&self.config, name), name, default.cluster.clone(), Some(default))?;
+        state.databases.insert(name.to_string(), Arc::new(executor));
+        info!(database = %name, "created database");
+        Ok(true)
+    }
+
+    /// Drop a database and its files, returning false if there is none of
+    /// the name and `if_exists` is set
+    /// As in Postgres, the database can't be in use, by any connection or by
+    /// the one dropping it
+    pub fn drop_database(&self, name: &str, current: &str, if_exists: bool) -> Result<bool> {
+        let mut state = self.state.lock();
+        if !state.databases.contains_key(name) {
+            if if_exists {
+                return Ok(false);
+            }
+            return Err(does_not_exist(name));
+        }
+        if name == current {
+            return Err(ExecutorError::ObjectInUse("cannot drop the currently open database".to_string()));
+        }
+        if name == DEFAULT_DATABASE {
+            return Err(ExecutorError::UnsupportedStatement(format!(
+                "database \"{}\" holds the data directory and cannot be dropped",
+                name
+            )));
+        }
+        if state.connections.values().any(|database| database == name) {
+            return Err(ExecutorError::ObjectInUse(format!("database \"{}\" is being accessed by other users", name)));
+        }
+
+        // Its files are closed with its executor, before they are removed
+        drop(state.databases.remove(name));
+        let dir = database_dir(&self.config, name);
+        std::fs::remove_dir_all(&dir)
+            .map_err(|e| ExecutorError::Execution(format!("failed to remove {}: {}", dir.display(), e)))?;
+        info!(database = %name, "dropped database");
+        Ok(true)
+    }
+}
+
+/// Executors of the default database and every other one the data
+/// directory holds, by name
+fn open_databases(config: &Config, cluster: &Weak<Cluster>) -> Result<HashMap<String, Arc<Executor>>> {
+    let default = Executor::open(config, DEFAULT_DATABASE, cluster.clone(), None)?;
+    let mut databases = HashMap::new();
+    let dir = config.data_dir.join(DATABASES_DIR);
+    match std::fs::read_dir(&dir) {
+        Ok(entries) => {
+            for entry in entries.flatten() {
+                let name = entry.file_name().to_string_lossy().to_string();
+                if !entry.path().is_dir() || check_name(&name).is_err() {
+                    warn!(path = %entry.path().display(), "skipping unexpected entry of the databases directory");
+                    continue;
+                }
+                let executor = Executor::open(&database_config(config, &name), &name, cluster.clone(), Some(&default))?;
+                info!(database = %name, "opened database");
+                databases.insert(name, Arc::new(executor));
+            }
+        }
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+        Err(e) => warn!(error = %e, path = %dir.display(), "failed to list databases"),
+    }
+    databases.insert(DEFAULT_DATABASE.to_string(), Arc::new(default));
+    Ok(databases)
+}
+
+/// Refuse a database name that isn't also a plain directory name
+fn check_name(name: &str) -> Result<()> {
+    if name.is_empty() || name.len() > MAX_NAME_LENGTH
+        || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+    {
+        return Err(ExecutorError::UnsupportedStatement(format!(
+            "invalid database name \"{}\": use up to {} letters, digits and underscores",
+            name, MAX_NAME_LENGTH
+        )));
+    }
+    Ok(())
+}
+
+fn does_not_exist(name: &str) -> ExecutorError {
+    ExecutorError::UndefinedDatabase(format!("database \"{}\" does not exist", name))
+}
+
+/// Directory of a database other than the default
+fn database_dir(config: &Config, name: &str) -> PathBuf {
+    config.data_dir.join(DATABASES_DIR).join(name)
+}
+
+/// The server's configuration, for a database other than the default
+fn database_config(config: &Config, name: &str) -> Config {
+    Config { data_dir: database_dir(config, name), ..config.clone() }
+}
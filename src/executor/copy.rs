@@ -0,0 +1,531 @@
+//! COPY ... FROM STDIN and COPY ... TO STDOUT: rows streamed into and out of
+//! a table through the copy sub-protocol
+//!
+//! After a COPY FROM statement the client sends the data in CopyData messages,
+//! whose boundaries need not fall between rows, and ends it with CopyDone.
+//! Complete rows are parsed as they arrive and written in batches, each
+//! checked and inserted like one multi-row INSERT: rows sharing a block are
+//! written together and the indexes take the batch's entries at once, instead
+//! of a block and index update per row. Storage has no rollback yet, so when a
+//! row fails the batches written before it stay.
+//!
+//! A COPY TO runs its scan or query when the statement does, and the server
+//! then sends the rows in CopyData messages of a batch each, formatted as
+//! they go out rather than all at once, and ends with CopyDone.
+//!
+//! Both of Postgres's text formats are read and written: `text`, with fields
+//! separated by tabs, `\N` for NULL and backslash escapes, and `csv`, with
+//! quoted fields and an optional header line.
+
+use sqlparser::ast::{CopyLegacyCsvOption, CopyLegacyOption, CopyOption};
+
+use crate::executor::cast::{self, CastTarget};
+use crate::executor::error::ExecutorError;
+use crate::executor::Result;
+use crate::types::{DataType, Row, Schema, Value};
+
+/// Rows written, or sent, at a time
+pub const COPY_BATCH_ROWS: usize = 1000;
+
+/// How the data of a COPY is laid out
+#[derive(Debug, Clone)]
+pub struct CopyOptions {
+    pub csv: bool,
+    pub delimiter: u8,
+    /// Text of a NULL field: `\N` in text format, an unquoted empty field in CSV
+    pub null: String,
+    /// The first line names the columns and is skipped
+    pub header: bool,
+    pub quote: u8,
+    /// Escapes a quote inside a quoted CSV field; by default the quote itself,
+    /// so a quote is written twice
+    pub escape: u8,
+}
+
+impl CopyOptions {
+    pub fn from_statement(options: &[CopyOption], legacy_options: &[CopyLegacyOption]) -> Result<Self> {
+        let mut csv = false;
+        let mut delimiter = None;
+        let mut null = None;
+        let mut header = false;
+        let mut quote = None;
+        let mut escape = None;
+        let unsupported = |option: String| ExecutorError::UnsupportedStatement(format!("COPY option {} is not supported", option));
+        for option in options {
+            match option {
+                CopyOption::Format(format) => match format.value.to_lowercase().as_str() {
+                    "csv" => csv = true,
+                    "text" => csv = false,
+                    "binary" => return Err(unsupported(option.to_string())),
+                    _ => return Err(ExecutorError::Execution(format!("COPY format \"{}\" not recognized", format.value))),
+                },
+                CopyOption::Delimiter(c) => delimiter = Some(*c),
+                CopyOption::Null(text) => null = Some(text.clone()),
+                CopyOption::Header(enabled) => header = *enabled,
+                CopyOption::Quote(c) => quote = Some(*c),
+                CopyOption::Escape(c) => escape = Some(*c),
+                CopyOption::Encoding(encoding)
+                    if encoding.eq_ignore_ascii_case("utf8") || encoding.eq_ignore_ascii_case("utf-8") => {}
+                other => return Err(unsupported(other.to_string())),
+            }
+        }
+        for option in legacy_options {
+            match option {
+                CopyLegacyOption::Csv(csv_options) => {
+                    csv = true;
+                    for csv_option in csv_options {
+                        match csv_option {
+                            CopyLegacyCsvOption::Header => header = true,
+                            CopyLegacyCsvOption::Quote(c) => quote = Some(*c),
+                            CopyLegacyCsvOption::Escape(c) => escape = Some(*c),
+                            other => return Err(unsupported(other.to_string())),
+                        }
+                    }
+                }
+                CopyLegacyOption::Delimiter(c) => delimiter = Some(*c),
+                CopyLegacyOption::Null(text) => null = Some(text.clone()),
+                CopyLegacyOption::Header => header = true,
+                other => return Err(unsupported(other.to_string())),
+            }
+        }
+
+        if !csv && (quote.is_some() || escape.is_some()) {
+            return Err(ExecutorError::Execution("COPY QUOTE and ESCAPE are only available in CSV mode".to_string()));
+        }
+        let single_byte = |c: char, name: &str| {
+            if c.is_ascii() && c != '\n' && c != '\r' {
+                Ok(c as u8)
+            } else {
+                Err(ExecutorError::Execution(format!("COPY {} must be a single one-byte character", name)))
+            }
+        };
+        let delimiter = single_byte(delimiter.unwrap_or(if csv { ',' } else { '\t' }), "delimiter")?;
+        let quote = single_byte(quote.unwrap_or('"'), "quote")?;
+        let escape = single_byte(escape.map_or(quote as char, |c| c), "escape")?;
+        if csv && delimiter == quote {
+            return Err(ExecutorError::Execution("COPY delimiter and quote must be different".to_string()));
+        }
+        Ok(CopyOptions {
+            csv,
+            delimiter,
+            null: null.unwrap_or_else(|| if csv { String::new() } else { "\\N".to_string() }),
+            header,
+            quote,
+            escape,
+        })
+    }
+}
+
+/// A COPY FROM STDIN in progress: the data received so far, parsed into the
+/// fields of the rows not yet written
+#[derive(Debug)]
+pub struct CopyIn {
+    pub table_name: String,
+    pub schema: Schema,
+    /// Position in the table's schema of each column the data gives, in order
+    pub targets: Vec<usize>,
+    /// Columns the data doesn't give that take the next value of a sequence
+    pub serials: Vec<(usize, String)>,
+    options: CopyOptions,
+    /// Data received that doesn't end a row yet
+    pending: Vec<u8>,
+    /// Line of the data the next row starts on, for error messages
+    line: usize,
+    /// The end-of-data marker `\.` was read; anything after it is ignored
+    ended: bool,
+    /// Fields of the rows parsed but not written yet
+    pub rows: Vec<Vec<Option<String>>>,
+    /// Rows written so far
+    pub copied: usize,
+    /// The error that stopped the copy; the rest of the data is discarded
+    /// until the client ends it, and the error is reported then
+    pub error: Option<ExecutorError>,
+}
+
+impl CopyIn {
+    pub fn new(table_name: String, schema: Schema, targets: Vec<usize>, serials: Vec<(usize, String)>, options: CopyOptions) -> Self {
+        CopyIn {
+            table_name,
+            schema,
+            targets,
+            serials,
+            options,
+            pending: Vec::new(),
+            line: 1,
+            ended: false,
+            rows: Vec::new(),
+            copied: 0,
+            error: None,
+        }
+    }
+
+    /// Take in a CopyData message, parsing the rows it completes
+    pub fn feed(&mut self, data: &[u8]) -> Result<()> {
+        if self.ended {
+            return Ok(());
+        }
+        self.pending.extend_from_slice(data);
+        let mut start = 0;
+        while let Some(end) = self.record_end(start) {
+            let record = self.pending[start..end].to_vec();
+            start = end + 1;
+            self.parse_record(&record)?;
+            if self.ended {
+                break;
+            }
+        }
+        self.pending.drain(..start);
+        Ok(())
+    }
+
+    /// Parse what is left once the client ends the data, which may be a last
+    /// row without a newline
+    pub fn finish(&mut self) -> Result<()> {
+        let record = std::mem::take(&mut self.pending);
+        if !self.ended && !record.is_empty() {
+            self.parse_record(&record)?;
+        }
+        Ok(())
+    }
+
+    /// Values of a row's fields, in the table's columns; the columns the data
+    /// doesn't give are NULL, serial columns included
+    pub fn values(&self, fields: Vec<Option<String>>) -> Result<Vec<Value>> {
+        let mut values = vec![Value::Null; self.schema.len()];
+        for (field, &target) in fields.into_iter().zip(&self.targets) {
+            let Some(text) = field else {
+                continue;
+            };
+            let column = &self.schema.columns[target];
+            let cast_target = match &column.data_type {
+                DataType::Int => CastTarget::BigInt,
+                DataType::Float => CastTarget::Float,
+                DataType::Bool => CastTarget::Bool,
+                DataType::String => CastTarget::Text(None),
+                other => return Err(ExecutorError::UnsupportedStatement(format!(
+                    "COPY FROM does not support column \"{}\" of type {:?}",
+                    column.name, other
+                ))),
+            };
+            values[target] = cast::cast_value(Value::String(text), cast_target)?;
+        }
+        Ok(values)
+    }
+
+    /// Position of the newline ending the record that starts at `start` in
+    /// the pending data, if it has been received
+    fn record_end(&self, start: usize) -> Option<usize> {
+        let data = &self.pending;
+        if !self.options.csv {
+            // Newlines in text-format values are escaped
+            return data[start..].iter().position(|&b| b == b'\n').map(|offset| start + offset);
+        }
+        let mut in_quotes = false;
+        let mut i = start;
+        while i < data.len() {
+            let b = data[i];
+            if in_quotes {
+                let escaped = data.get(i + 1).is_some_and(|&next| next == self.options.quote || next == self.options.escape);
+                if b == self.options.escape && self.options.escape != self.options.quote && escaped {
+                    i += 1;
+                } else if b == self.options.quote {
+                    in_quotes = false;
+                }
+            } else if b == self.options.quote {
+                in_quotes = true;
+            } else if b == b'\n' {
+                return Some(i);
+            }
+            i += 1;
+        }
+        None
+    }
+
+    /// Parse one record, without its newline, into the fields of a row
+    fn parse_record(&mut self, record: &[u8]) -> Result<()> {
+        let record = record.strip_suffix(b"\r").unwrap_or(record);
+        let line = self.line;
+        self.line += 1 + record.iter().filter(|&&b| b == b'\n').count();
+        if record == b"\\." {
+            self.ended = true;
+            return Ok(());
+        }
+        if self.options.header && line == 1 {
+            return Ok(());
+        }
+
+        let fields = if self.options.csv {
+            parse_csv_record(record, &self.options)
+        } else {
+            parse_text_record(record, &self.options)
+        }
+        .map_err(|message| self.error_on(line, message))?;
+        if fields.len() > self.targets.len() {
+            return Err(self.error_on(line, "extra data after last expected column".to_string()));
+        }
+        if fields.len() < self.targets.len() {
+            let missing = &self.schema.columns[self.targets[fields.len()]].name;
+            return Err(self.error_on(line, format!("missing data for column \"{}\"", missing)));
+        }
+        self.rows.push(fields);
+        Ok(())
+    }
+
+    /// The error a line of the data caused, naming where it is
+    fn error_on(&self, line: usize, message: String) -> ExecutorError {
+        ExecutorError::Execution(format!("{} (COPY {}, line {})", message, self.table_name, line))
+    }
+}
+
+/// A COPY TO STDOUT whose rows are being sent
+#[derive(Debug)]
+pub struct CopyOut {
+    /// Names of the columns, for the header line
+    columns: Vec<String>,
+    rows: std::vec::IntoIter<Row>,
+    options: CopyOptions,
+    header_sent: bool,
+    /// Rows sent so far
+    pub copied: usize,
+}
+
+impl CopyOut {
+    pub fn new(columns: Vec<String>, rows: Vec<Row>, options: CopyOptions) -> Self {
+        CopyOut {
+            columns,
+            rows: rows.into_iter(),
+            options,
+            header_sent: false,
+            copied: 0,
+        }
+    }
+
+    /// Number of columns of each row
+    pub fn column_count(&self) -> usize {
+        self.columns.len()
+    }
+
+    /// The data of the next batch of rows, None once every row has been sent
+    pub fn next_chunk(&mut self) -> Option<Vec<u8>> {
+        let mut data = Vec::new();
+        if self.options.header && !self.header_sent {
+            self.header_sent = true;
+            let names: Vec<Option<String>> = self.columns.iter().cloned().map(Some).collect();
+            self.write_record(&mut data, names);
+        }
+        let rows: Vec<Row> = self.rows.by_ref().take(COPY_BATCH_ROWS).collect();
+        for row in rows {
+            let fields = row.values.into_iter().map(|value| match value {
+                Value::Int(n) => Some(n.to_string()),
+                Value::Float(f) => Some(format_float(f)),
+                Value::Bool(b) => Some(if b { "t" } else { "f" }.to_string()),
+                Value::String(s) => Some(s),
+                // Extension values come as their text already; the rest are
+                // sent as NULL, as in query results
+                Value::Null | Value::Extension { .. } => None,
+            }).collect();
+            self.write_record(&mut data, fields);
+            self.copied += 1;
+        }
+        if data.is_empty() {
+            None
+        } else {
+            Some(data)
+        }
+    }
+
+    fn write_record(&self, data: &mut Vec<u8>, fields: Vec<Option<String>>) {
+        for (idx, field) in fields.into_iter().enumerate() {
+            if idx > 0 {
+                data.push(self.options.delimiter);
+            }
+            match field {
+                None => data.extend_from_slice(self.options.null.as_bytes()),
+                Some(text) if self.options.csv => write_csv_field(data, &text, &self.options),
+                Some(text) => write_text_field(data, &text, &self.options),
+            }
+        }
+        data.push(b'\n');
+    }
+}
+
+/// A float in Postgres's text form
+fn format_float(f: f64) -> String {
+    if f.is_nan() {
+        "NaN".to_string()
+    } else if f.is_infinite() {
+        if f > 0.0 { "Infinity" } else { "-Infinity" }.to_string()
+    } else {
+        f.to_string()
+    }
+}
+
+/// Write a text-format field, escaping what would be read back as a
+/// delimiter, newline or escape
+fn write_text_field(data: &mut Vec<u8>, text: &str, options: &CopyOptions) {
+    for &b in text.as_bytes() {
+        match b {
+            b'\\' => data.extend_from_slice(b"\\\\"),
+            b'\n' => data.extend_from_slice(b"\\n"),
+            b'\r' => data.extend_from_slice(b"\\r"),
+            b'\t' => data.extend_from_slice(b"\\t"),
+            0x08 => data.extend_from_slice(b"\\b"),
+            0x0c => data.extend_from_slice(b"\\f"),
+            0x0b => data.extend_from_slice(b"\\v"),
+            b if b == options.delimiter => data.extend_from_slice(&[b'\\', b]),
+            b => data.push(b),
+        }
+    }
+}
+
+/// Write a CSV field, quoted if it holds a delimiter, quote or newline, or
+/// would be read back as NULL or the end of the data
+fn write_csv_field(data: &mut Vec<u8>, text: &str, options: &CopyOptions) {
+    let bytes = text.as_bytes();
+    let needs_quotes = text == options.null
+        || text == "\\."
+        || bytes.iter().any(|&b| b == options.delimiter || b == options.quote || b == b'\n' || b == b'\r');
+    if !needs_quotes {
+        data.extend_from_slice(bytes);
+        return;
+    }
+    data.push(options.quote);
+    for &b in bytes {
+        if b == options.quote || b == options.escape {
+            data.push(options.escape);
+        }
+        data.push(b);
+    }
+    data.push(options.quote);
+}
+
+/// Fields of a text-format record: NULL where a field is the NULL text, and
+/// otherwise with its backslash escapes replaced
+fn parse_text_record(record: &[u8], options: &CopyOptions) -> std::result::Result<Vec<Option<String>>, String> {
+    let mut fields = Vec::new();
+    for raw in split_unescaped(record, options.delimiter) {
+        if raw == options.null.as_bytes() {
+            fields.push(None);
+            continue;
+        }
+        let mut bytes = Vec::with_capacity(raw.len());
+        let mut i = 0;
+        while i < raw.len() {
+            if raw[i] != b'\\' || i + 1 == raw.len() {
+                bytes.push(raw[i]);
+                i += 1;
+                continue;
+            }
+            let c = raw[i + 1];
+            i += 2;
+            match c {
+                b'b' => bytes.push(0x08),
+                b'f' => bytes.push(0x0c),
+                b'n' => bytes.push(b'\n'),
+                b'r' => bytes.push(b'\r'),
+                b't' => bytes.push(b'\t'),
+                b'v' => bytes.push(0x0b),
+                b'0'..=b'7' => {
+                    // Up to three octal digits
+                    let mut value = (c - b'0') as u32;
+                    for _ in 0..2 {
+                        match raw.get(i) {
+                            Some(d @ b'0'..=b'7') => {
+                                value = value * 8 + (d - b'0') as u32;
+                                i += 1;
+                            }
+                            _ => break,
+                        }
+                    }
+                    bytes.push(value as u8);
+                }
+                b'x' if raw.get(i).is_some_and(u8::is_ascii_hexdigit) => {
+                    // One or two hex digits
+                    let digits = raw[i..].iter().take(2).take_while(|d| d.is_ascii_hexdigit()).count();
+                    let hex = std::str::from_utf8(&raw[i..i + digits]).unwrap_or("0");
+                    bytes.push(u8::from_str_radix(hex, 16).unwrap_or(0));
+                    i += digits;
+                }
+                other => bytes.push(other),
+            }
+        }
+        let text = String::from_utf8(bytes).map_err(|_| "invalid byte sequence for encoding \"UTF8\"".to_string())?;
+        fields.push(Some(text));
+    }
+    Ok(fields)
+}
+
+/// Split a text-format record at each delimiter that isn't escaped
+fn split_unescaped(record: &[u8], delimiter: u8) -> Vec<&[u8]> {
+    let mut fields = Vec::new();
+    let mut start = 0;
+    let mut i = 0;
+    while i < record.len() {
+        if record[i] == b'\\' {
+            i += 2;
+            continue;
+        }
+        if record[i] == delimiter {
+            fields.push(&record[start..i]);
+            start = i + 1;
+        }
+        i += 1;
+    }
+    fields.push(&record[start..]);
+    fields
+}
+
+/// Fields of a CSV record: NULL where an unquoted field is the NULL text; a
+/// quoted field is never NULL
+fn parse_csv_record(record: &[u8], options: &CopyOptions) -> std::result::Result<Vec<Option<String>>, String> {
+    let mut fields = Vec::new();
+    let mut i = 0;
+    loop {
+        let mut bytes = Vec::new();
+        let mut quoted = false;
+        while i < record.len() && record[i] != options.delimiter {
+            if record[i] != options.quote {
+                bytes.push(record[i]);
+                i += 1;
+                continue;
+            }
+            // A quoted run, which may hold delimiters and newlines
+            quoted = true;
+            i += 1;
+            loop {
+                match record.get(i) {
+                    None => return Err("unterminated CSV quoted field".to_string()),
+                    // An escaped quote, or with an escape other than the
+                    // quote, an escaped escape
+                    Some(&b) if b == options.escape
+                        && let Some(&next) = record.get(i + 1)
+                        && (next == options.quote || next == options.escape) =>
+                    {
+                        bytes.push(next);
+                        i += 2;
+                    }
+                    Some(&b) if b == options.quote => {
+                        i += 1;
+                        break;
+                    }
+                    Some(&b) => {
+                        bytes.push(b);
+                        i += 1;
+                    }
+                }
+            }
+        }
+        let text = String::from_utf8(bytes).map_err(|_| "invalid byte sequence for encoding \"UTF8\"".to_string())?;
+        if !quoted && text == options.null {
+            fields.push(None);
+        } else {
+            fields.push(Some(text));
+        }
+        if i >= record.len() {
+            break;
+        }
+        // Past the delimiter
+        i += 1;
+    }
+    Ok(fields)
+}
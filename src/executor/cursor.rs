@@ -0,0 +1,130 @@
+//! Cursors (DECLARE ... CURSOR FOR SELECT, FETCH, CLOSE) and the per-connection
+//! session state that holds them and the session's prepared statements
+//!
+//! A cursor's query runs to completion at DECLARE, since the executor
+//! materializes results; FETCH then hands the rows to the client a batch at a
+//! time, so a client can page through a large result without receiving all of
+//! it in one response. Cursors are forward-only. Unless declared WITH HOLD, a
+//! cursor may only be declared inside a transaction block and is closed when
+//! the block ends.
+
+use std::collections::{HashMap, VecDeque};
+use std::net::SocketAddr;
+
+use pgwire::api::portal::Format;
+
+use crate::executor::copy::{CopyIn, CopyOut};
+use crate::executor::prepared::PreparedStatements;
+use crate::executor::workload::WorkloadClass;
+use crate::storage::base::TxId;
+use crate::types::{Row, Schema};
+
+/// Identifies a client connection; sessions are keyed by the client's address
+pub type SessionId = SocketAddr;
+
+/// An open cursor: the rows of its query not yet fetched
+#[derive(Debug)]
+pub struct Cursor {
+    pub schema: Option<Schema>,
+    rows: VecDeque<Row>,
+    /// WITH HOLD cursors outlive the transaction block they were declared in
+    pub hold: bool,
+}
+
+impl Cursor {
+    pub fn new(rows: Vec<Row>, schema: Option<Schema>, hold: bool) -> Self {
+        Cursor {
+            schema,
+            rows: rows.into(),
+            hold,
+        }
+    }
+
+    /// Take up to `count` rows from the cursor, or all remaining rows if None
+    pub fn fetch(&mut self, count: Option<usize>) -> Vec<Row> {
+        let count = count.unwrap_or(self.rows.len()).min(self.rows.len());
+        self.rows.drain(..count).collect()
+    }
+}
+
+/// State a client connection keeps between queries
+#[derive(Debug)]
+pub struct Session {
+    pub id: SessionId,
+    /// Between BEGIN and COMMIT/ROLLBACK
+    pub in_transaction: bool,
+    /// The open transaction block was made READ ONLY
+    pub read_only: bool,
+    /// Transaction the session's writes run in: the open block's once it
+    /// has written, or else the running statement's (see `transaction`)
+    pub transaction: Option<TxId>,
+    /// A statement of the open transaction block failed, so the block's
+    /// writes were rolled back and only its end is accepted
+    pub failed: bool,
+    pub cursors: HashMap<String, Cursor>,
+    pub prepared: PreparedStatements,
+    /// Class set with SET workload_class for every query of the session;
+    /// None classes each query by its work
+    pub workload_class: Option<WorkloadClass>,
+    /// Class the running query was admitted under, whose place it gives up
+    /// while it waits for a lock
+    pub admitted: Option<WorkloadClass>,
+    /// Temporary tables, by the name the session gave each, with the name
+    /// each is stored under (see `temp`)
+    pub temp_tables: HashMap<String, String>,
+    /// Number of the session's temporary schema, given when it creates its
+    /// first temporary table
+    pub temp_schema: Option<u32>,
+    /// Formats the client asked for the result columns of the statement
+    /// running, with the extended query protocol; None for the simple query
+    /// protocol, which always sends text
+    pub result_format: Option<Format>,
+    /// COPY FROM STDIN waiting for the rest of its data
+    pub copy_in: Option<CopyIn>,
+    /// COPY TO STDOUTs of the running query whose rows are still to be sent,
+    /// in the order of their responses
+    pub copy_out: VecDeque<CopyOut>,
+    /// Run-time parameters set with SET, by name (see `settings`)
+    pub settings: HashMap<String, String>,
+    /// Parameters set with SET LOCAL, until the transaction block ends
+    pub local_settings: HashMap<String, String>,
+}
+
+impl Session {
+    pub fn new(id: SessionId) -> Self {
+        Session {
+            id,
+            in_transaction: false,
+            read_only: false,
+            transaction: None,
+            failed: false,
+            cursors: HashMap::new(),
+            prepared: PreparedStatements::default(),
+            workload_class: None,
+            admitted: None,
+            temp_tables: HashMap::new(),
+            temp_schema: None,
+            result_format: None,
+            copy_in: None,
+            copy_out: VecDeque::new(),
+            settings: HashMap::new(),
+            local_settings: HashMap::new(),
+        }
+    }
+
+    /// Leave the transaction block, closing the cursors scoped to it
+    pub fn end_transaction(&mut self) {
+        self.in_transaction = false;
+        self.read_only = false;
+        self.failed = false;
+        self.cursors.retain(|_, cursor| cursor.hold);
+        self.local_settings.clear();
+    }
+
+    /// Whether the session has nothing worth keeping between queries
+    pub fn is_idle(&self) -> bool {
+        !self.in_transaction && self.transaction.is_none() && self.cursors.is_empty() && self.prepared.is_empty()
+            && self.workload_class.is_none() && self.temp_tables.is_empty() && self.copy_in.is_none()
+            && self.copy_out.is_empty() && self.settings.is_empty()
+    }
+}
@@ -1,37 +1,160 @@
 use pgwire::error::{ErrorInfo, PgWireError};
 
+#[derive(Debug)]
 pub enum ExecutorError {
     Parse(String),
     Plan(String),
     Execution(String),
     UnsupportedStatement(String),
+    /// A write attempted by a read-only server or transaction
+    ReadOnly(String),
+    /// A query that needs more memory than work_mem allows
+    ResourceExhausted(String),
+    /// A number too large for its type, such as an integer overflow
+    OutOfRange(String),
+    /// A value longer than its column's declared length, such as VARCHAR(n),
+    /// or otherwise outside its column's type modifier
+    ValueTooLong(String),
+    /// A row that fails one of its table's CHECK constraints
+    CheckViolation(String),
+    /// A write that would leave a row referring to a row that doesn't exist
+    ForeignKeyViolation(String),
+    /// A query refused because the server is shutting down, which also ends
+    /// the connection
+    Shutdown(String),
+    /// A database named by a connection or statement that doesn't exist
+    UndefinedDatabase(String),
+    /// CREATE DATABASE of a name already taken
+    DuplicateDatabase(String),
+    /// A database dropped while connections are using it
+    ObjectInUse(String),
+    /// A statement that can't run inside a transaction block
+    ActiveTransaction(String),
+    /// A statement sent to a transaction block after one of its statements
+    /// failed
+    FailedTransaction(String),
+    /// A lock wait that ran past the session's lock_timeout
+    LockNotAvailable(String),
+    /// A lock wait that would never end, as the sessions it waits for are
+    /// waiting for it
+    Deadlock(String),
+    /// A schema named by a statement that doesn't exist
+    InvalidSchema(String),
+    /// CREATE SCHEMA of a name already taken
+    DuplicateSchema(String),
     // StorageError(storage::Error)
 }
 
-impl From<ExecutorError> for PgWireError {
-    fn from(e: ExecutorError) -> PgWireError {
-        match e {
-            ExecutorError::Parse(msg) => PgWireError::UserError(Box::new(ErrorInfo::new(
+impl ExecutorError {
+    /// Error fields sent to the client
+    pub fn into_error_info(self) -> ErrorInfo {
+        match self {
+            ExecutorError::Parse(msg) => ErrorInfo::new(
                 "ERROR".to_string(),
                 "42601".to_string(), // syntax_error
                 msg,
-            ))),
-            ExecutorError::UnsupportedStatement(msg) => PgWireError::UserError(Box::new(ErrorInfo::new(
+            ),
+            ExecutorError::UnsupportedStatement(msg) => ErrorInfo::new(
                 "ERROR".to_string(),
                 "0A000".to_string(), // feature_not_supported
                 msg,
-            ))),
-            ExecutorError::Plan(msg) => PgWireError::UserError(Box::new(ErrorInfo::new(
+            ),
+            ExecutorError::ReadOnly(msg) => ErrorInfo::new(
+                "ERROR".to_string(),
+                "25006".to_string(), // read_only_sql_transaction
+                msg,
+            ),
+            ExecutorError::ResourceExhausted(msg) => ErrorInfo::new(
+                "ERROR".to_string(),
+                "53200".to_string(), // out_of_memory
+                msg,
+            ),
+            ExecutorError::OutOfRange(msg) => ErrorInfo::new(
+                "ERROR".to_string(),
+                "22003".to_string(), // numeric_value_out_of_range
+                msg,
+            ),
+            ExecutorError::ValueTooLong(msg) => ErrorInfo::new(
+                "ERROR".to_string(),
+                "22001".to_string(), // string_data_right_truncation
+                msg,
+            ),
+            ExecutorError::CheckViolation(msg) => ErrorInfo::new(
+                "ERROR".to_string(),
+                "23514".to_string(), // check_violation
+                msg,
+            ),
+            ExecutorError::ForeignKeyViolation(msg) => ErrorInfo::new(
+                "ERROR".to_string(),
+                "23503".to_string(), // foreign_key_violation
+                msg,
+            ),
+            ExecutorError::Shutdown(msg) => ErrorInfo::new(
+                "FATAL".to_string(),
+                "57P01".to_string(), // admin_shutdown
+                msg,
+            ),
+            ExecutorError::UndefinedDatabase(msg) => ErrorInfo::new(
+                "ERROR".to_string(),
+                "3D000".to_string(), // invalid_catalog_name
+                msg,
+            ),
+            ExecutorError::DuplicateDatabase(msg) => ErrorInfo::new(
+                "ERROR".to_string(),
+                "42P04".to_string(), // duplicate_database
+                msg,
+            ),
+            ExecutorError::ObjectInUse(msg) => ErrorInfo::new(
+                "ERROR".to_string(),
+                "55006".to_string(), // object_in_use
+                msg,
+            ),
+            ExecutorError::ActiveTransaction(msg) => ErrorInfo::new(
+                "ERROR".to_string(),
+                "25001".to_string(), // active_sql_transaction
+                msg,
+            ),
+            ExecutorError::FailedTransaction(msg) => ErrorInfo::new(
+                "ERROR".to_string(),
+                "25P02".to_string(), // in_failed_sql_transaction
+                msg,
+            ),
+            ExecutorError::LockNotAvailable(msg) => ErrorInfo::new(
+                "ERROR".to_string(),
+                "55P03".to_string(), // lock_not_available
+                msg,
+            ),
+            ExecutorError::Deadlock(msg) => ErrorInfo::new(
+                "ERROR".to_string(),
+                "40P01".to_string(), // deadlock_detected
+                msg,
+            ),
+            ExecutorError::InvalidSchema(msg) => ErrorInfo::new(
+                "ERROR".to_string(),
+                "3F000".to_string(), // invalid_schema_name
+                msg,
+            ),
+            ExecutorError::DuplicateSchema(msg) => ErrorInfo::new(
+                "ERROR".to_string(),
+                "42P06".to_string(), // duplicate_schema
+                msg,
+            ),
+            ExecutorError::Plan(msg) => ErrorInfo::new(
                 "ERROR".to_string(),
                 "42P01".to_string(), // undefined_table
                 msg,
-            ))),
-            ExecutorError::Execution(msg) => PgWireError::UserError(Box::new(ErrorInfo::new(
+            ),
+            ExecutorError::Execution(msg) => ErrorInfo::new(
                 "ERROR".to_string(),
                 "XX000".to_string(), // internal_error
                 msg,
-            )))
+            )
         }
     }
 }
 
+impl From<ExecutorError> for PgWireError {
+    fn from(e: ExecutorError) -> PgWireError {
+        PgWireError::UserError(Box::new(e.into_error_info()))
+    }
+}
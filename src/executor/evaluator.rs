@@ -1,6 +1,10 @@
-use sqlparser::ast::{Expr, BinaryOperator};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use sqlparser::ast::{BinaryOperator, CastKind, Expr, UnaryOperator};
 use tracing::debug;
 
+use crate::executor::builtins;
+use crate::executor::cast;
 use crate::executor::error::ExecutorError;
 use crate::types::{Row, Schema, Value};
 
@@ -36,29 +40,86 @@ pub fn eval_expr(expr: &Expr, row: &Row, schema: &Schema) -> Result<Value> {
         Expr::Identifier(ident) => {
             let col_name = &ident.value;
             debug!(column = %col_name, "evaluating column reference");
-
-            if let Some(idx) = schema.get_column_index(col_name) {
-                row.get(idx)
-                    .cloned()
-                    .ok_or_else(|| ExecutorError::Execution(format!("Column index out of bounds: {}", col_name)))
-            } else {
-                Err(ExecutorError::Execution(format!(
-                    "Column not found: {}",
-                    col_name
-                )))
-            }
+            column_value(col_name, row, schema)
+        }
+        Expr::CompoundIdentifier(parts) => {
+            let col_name = parts.iter()
+                .map(|part| part.value.as_str())
+                .collect::<Vec<_>>()
+                .join(".");
+            debug!(column = %col_name, "evaluating qualified column reference");
+            column_value(&col_name, row, schema)
         }
 
         // Binary operations
         Expr::BinaryOp { left, op, right } => {
+            if let (Expr::Tuple(left_items), Expr::Tuple(right_items)) = (&**left, &**right) {
+                return eval_row_comparison(left_items, op, right_items, row, schema);
+            }
             let left_val = eval_expr(left, row, schema)?;
             let right_val = eval_expr(right, row, schema)?;
             eval_binary_op(&left_val, op, &right_val)
         }
 
+        // Unary operations: -x, +x, NOT x, ~x, @x
+        Expr::UnaryOp { op, expr } => {
+            let val = eval_expr(expr, row, schema)?;
+            eval_unary_op(op, &val)
+        }
+
+        // x IS [NOT] NULL, never NULL itself
+        Expr::IsNull(inner) => Ok(Value::Bool(matches!(eval_expr(inner, row, schema)?, Value::Null))),
+        Expr::IsNotNull(inner) => Ok(Value::Bool(!matches!(eval_expr(inner, row, schema)?, Value::Null))),
+
+        // x [NOT] IN (a, b, ...)
+        Expr::InList { expr, list, negated } => {
+            let val = eval_expr(expr, row, schema)?;
+            let items = list.iter()
+                .map(|item| eval_expr(item, row, schema))
+                .collect::<Result<Vec<_>>>()?;
+            eval_in_list(&val, &items, *negated)
+        }
+
+        // x [NOT] BETWEEN low AND high
+        Expr::Between { expr, negated, low, high } => {
+            let val = eval_expr(expr, row, schema)?;
+            let low_val = eval_expr(low, row, schema)?;
+            let high_val = eval_expr(high, row, schema)?;
+            eval_between(&val, &low_val, &high_val, *negated)
+        }
+
         // Parenthesized expression
         Expr::Nested(inner) => eval_expr(inner, row, schema),
 
+        // CAST(x AS type) and x::type; TRY_CAST gives NULL where a cast fails
+        Expr::Cast { kind, expr, data_type, format: None } => {
+            let target = cast::CastTarget::from_sql(data_type)?;
+            let value = eval_expr(expr, row, schema)?;
+            match kind {
+                CastKind::TryCast | CastKind::SafeCast => Ok(cast::cast_value(value, target).unwrap_or(Value::Null)),
+                CastKind::Cast | CastKind::DoubleColon => cast::cast_value(value, target),
+            }
+        }
+
+        // Typed literals: DATE '2024-01-01', INTEGER '5'
+        Expr::TypedString(typed) => {
+            let target = cast::CastTarget::from_sql(&typed.data_type)?;
+            let Some(text) = typed.value.value.clone().into_string() else {
+                return Err(ExecutorError::Execution(format!("Unsupported typed literal: {}", typed)));
+            };
+            cast::cast_value(Value::String(text), target)
+        }
+
+        // Built-in scalar functions; the executor answers its own functions
+        // before expressions get here, and SQL functions are inlined
+        Expr::Function(func) if let Some(builtin) = builtins::lookup(&func.name.to_string()) => {
+            let args = function_args(func)?
+                .iter()
+                .map(|arg| eval_expr(arg, row, schema))
+                .collect::<Result<Vec<_>>>()?;
+            builtin.call(&args)
+        }
+
         // Wildcard (shouldn't reach here in typical evaluation)
         Expr::Wildcard(_) => Ok(Value::Null),
 
@@ -69,6 +130,266 @@ pub fn eval_expr(expr: &Expr, row: &Row, schema: &Schema) -> Result<Value> {
     }
 }
 
+/// Positional argument expressions of a function call
+pub(crate) fn function_args(func: &sqlparser::ast::Function) -> Result<Vec<&Expr>> {
+    use sqlparser::ast::{FunctionArg, FunctionArgExpr, FunctionArguments};
+
+    match &func.args {
+        FunctionArguments::None => Ok(Vec::new()),
+        FunctionArguments::List(list) => list.args.iter()
+            .map(|arg| match arg {
+                FunctionArg::Unnamed(FunctionArgExpr::Expr(arg)) => Ok(arg),
+                other => Err(ExecutorError::Execution(format!(
+                    "Unsupported argument to {}(): {}",
+                    func.name, other
+                ))),
+            })
+            .collect(),
+        FunctionArguments::Subquery(_) => Err(ExecutorError::Execution(format!(
+            "{}() does not take a subquery",
+            func.name
+        ))),
+    }
+}
+
+fn column_value(col_name: &str, row: &Row, schema: &Schema) -> Result<Value> {
+    let idx = resolve_column(schema, col_name)?;
+    row.get(idx)
+        .cloned()
+        .ok_or_else(|| ExecutorError::Execution(format!("Column index out of bounds: {}", col_name)))
+}
+
+/// Position of a column reference in a schema
+/// The columns of joined rows are qualified by their table ("users.id"): an
+/// unqualified name matches the one column of that name in any table, and is
+/// ambiguous if several tables have it. A qualified name against a schema of
+/// unqualified columns, as for a single table, is looked up by its column name
+pub fn resolve_column(schema: &Schema, name: &str) -> Result<usize> {
+    if let Some(idx) = schema.get_column_index(name) {
+        return Ok(idx);
+    }
+    let qualified = |column: &str| column.contains('.');
+    let found = match name.rsplit_once('.') {
+        None => {
+            let suffix = format!(".{}", name.to_lowercase());
+            let mut matches = schema.columns.iter()
+                .enumerate()
+                .filter(|(_, column)| column.name.to_lowercase().ends_with(&suffix))
+                .map(|(idx, _)| idx);
+            let found = matches.next();
+            if found.is_some() && matches.next().is_some() {
+                return Err(ExecutorError::Execution(format!("Column reference is ambiguous: {}", name)));
+            }
+            found
+        }
+        Some((_, column)) if !schema.columns.iter().any(|c| qualified(&c.name)) => {
+            schema.get_column_index(column)
+        }
+        Some(_) => None,
+    };
+    found.ok_or_else(|| ExecutorError::Execution(format!("Column not found: {}", name)))
+}
+
+/// What integer arithmetic does when a result doesn't fit in a bigint
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IntegerOverflow {
+    /// Fail with numeric_value_out_of_range, as Postgres does
+    Error,
+    /// Give the result as a float instead, keeping its magnitude but not
+    /// every digit; there is no wider integer or decimal type to promote to
+    PromoteToFloat,
+}
+
+/// Set once at startup from --integer-overflow
+static PROMOTE_INTEGER_OVERFLOW: AtomicBool = AtomicBool::new(false);
+
+pub fn set_integer_overflow(mode: IntegerOverflow) {
+    PROMOTE_INTEGER_OVERFLOW.store(mode == IntegerOverflow::PromoteToFloat, Ordering::Relaxed);
+}
+
+/// Whether integer arithmetic that overflows gives a float
+pub fn promotes_integer_overflow() -> bool {
+    PROMOTE_INTEGER_OVERFLOW.load(Ordering::Relaxed)
+}
+
+/// The result of integer arithmetic that overflowed, given the same
+/// operation done in floating point
+pub fn integer_overflow(promoted: f64) -> Result<Value> {
+    if promotes_integer_overflow() {
+        return Ok(Value::Float(promoted));
+    }
+    Err(ExecutorError::OutOfRange("bigint out of range".to_string()))
+}
+
+/// Apply an arithmetic operator to two numbers
+fn eval_arithmetic(
+    left: &Value,
+    right: &Value,
+    symbol: &str,
+    int_op: impl Fn(i64, i64) -> Option<i64>,
+    float_op: impl Fn(f64, f64) -> f64,
+) -> Result<Value> {
+    match (left, right) {
+        (Value::Null, _) | (_, Value::Null) => Ok(Value::Null),
+        (Value::Int(a), Value::Int(b)) => match int_op(*a, *b) {
+            Some(result) => Ok(Value::Int(result)),
+            None => integer_overflow(float_op(*a as f64, *b as f64)),
+        },
+        (Value::Float(a), Value::Float(b)) => Ok(Value::Float(float_op(*a, *b))),
+        (Value::Int(a), Value::Float(b)) => Ok(Value::Float(float_op(*a as f64, *b))),
+        (Value::Float(a), Value::Int(b)) => Ok(Value::Float(float_op(*a, *b as f64))),
+        _ => Err(ExecutorError::Execution(format!("Type mismatch in {}", symbol))),
+    }
+}
+
+/// Apply a bitwise operator to two integers
+fn eval_bitwise(left: &Value, right: &Value, symbol: &str, int_op: impl Fn(i64, i64) -> i64) -> Result<Value> {
+    match (left, right) {
+        (Value::Null, _) | (_, Value::Null) => Ok(Value::Null),
+        (Value::Int(a), Value::Int(b)) => Ok(Value::Int(int_op(*a, *b))),
+        _ => Err(ExecutorError::Execution(format!("Type mismatch in {}", symbol))),
+    }
+}
+
+/// Evaluate a unary operation; NULL gives NULL
+fn eval_unary_op(op: &UnaryOperator, value: &Value) -> Result<Value> {
+    use UnaryOperator::*;
+
+    match (op, value) {
+        (Minus | Plus | Not | PGBitwiseNot | PGAbs, Value::Null) => Ok(Value::Null),
+        (Minus, Value::Int(n)) => n.checked_neg().map_or_else(|| integer_overflow(-(*n as f64)), |n| Ok(Value::Int(n))),
+        (Minus, Value::Float(f)) => Ok(Value::Float(-f)),
+        (Plus, Value::Int(_) | Value::Float(_)) => Ok(value.clone()),
+        (Not, Value::Bool(b)) => Ok(Value::Bool(!b)),
+        (PGBitwiseNot, Value::Int(n)) => Ok(Value::Int(!n)),
+        (PGAbs, Value::Int(n)) => n.checked_abs().map_or_else(|| integer_overflow((*n as f64).abs()), |n| Ok(Value::Int(n))),
+        (PGAbs, Value::Float(f)) => Ok(Value::Float(f.abs())),
+        (Minus | Plus | Not | PGBitwiseNot | PGAbs, _) => Err(ExecutorError::Execution(format!(
+            "Type mismatch in unary {}",
+            op
+        ))),
+        _ => Err(ExecutorError::Execution(format!(
+            "Unsupported unary operator: {:?}",
+            op
+        ))),
+    }
+}
+
+/// Order two non-NULL values for sorting: numbers numerically, strings by
+/// byte order and false before true
+pub fn compare_values(left: &Value, right: &Value) -> Result<std::cmp::Ordering> {
+    match (left, right) {
+        (Value::Int(a), Value::Int(b)) => Ok(a.cmp(b)),
+        (Value::Float(a), Value::Float(b)) => Ok(a.total_cmp(b)),
+        (Value::Int(a), Value::Float(b)) => Ok((*a as f64).total_cmp(b)),
+        (Value::Float(a), Value::Int(b)) => Ok(a.total_cmp(&(*b as f64))),
+        (Value::String(a), Value::String(b)) => Ok(a.cmp(b)),
+        (Value::Bool(a), Value::Bool(b)) => Ok(a.cmp(b)),
+        _ => Err(ExecutorError::Execution(format!(
+            "Cannot compare {:?} with {:?}",
+            left, right
+        ))),
+    }
+}
+
+/// Compare two row constructors, as in (a, b) = (1, 2)
+/// = and <> compare every pair of fields; the ordering operators compare
+/// lexicographically, decided by the first pair that is not equal
+fn eval_row_comparison(left: &[Expr], op: &BinaryOperator, right: &[Expr], row: &Row, schema: &Schema) -> Result<Value> {
+    if left.len() != right.len() {
+        return Err(ExecutorError::Execution(format!(
+            "Row comparison needs rows of the same length, got {} and {}",
+            left.len(),
+            right.len()
+        )));
+    }
+
+    let mut pairs = Vec::new();
+    for (left_expr, right_expr) in left.iter().zip(right) {
+        pairs.push((eval_expr(left_expr, row, schema)?, eval_expr(right_expr, row, schema)?));
+    }
+    let is_true = |left: &Value, op: &BinaryOperator, right: &Value| -> Result<bool> {
+        Ok(matches!(eval_binary_op(left, op, right)?, Value::Bool(true)))
+    };
+
+    match op {
+        BinaryOperator::Eq | BinaryOperator::NotEq => {
+            let mut all_equal = true;
+            for (left_val, right_val) in &pairs {
+                if is_true(left_val, &BinaryOperator::NotEq, right_val)? {
+                    return Ok(Value::Bool(*op == BinaryOperator::NotEq));
+                }
+                all_equal &= is_true(left_val, &BinaryOperator::Eq, right_val)?;
+            }
+            // A NULL field makes the rows neither equal nor different
+            Ok(Value::Bool(all_equal && *op == BinaryOperator::Eq))
+        }
+        BinaryOperator::Lt | BinaryOperator::LtEq | BinaryOperator::Gt | BinaryOperator::GtEq => {
+            for (left_val, right_val) in &pairs {
+                if !is_true(left_val, &BinaryOperator::Eq, right_val)? {
+                    return eval_binary_op(left_val, op, right_val);
+                }
+            }
+            Ok(Value::Bool(matches!(op, BinaryOperator::LtEq | BinaryOperator::GtEq)))
+        }
+        _ => Err(ExecutorError::Execution(format!(
+            "Unsupported operator in row comparison: {:?}",
+            op
+        ))),
+    }
+}
+
+/// Evaluate `value IN (items)`, or NOT IN when `negated`
+/// As in Postgres, when no item is equal but a comparison involved NULL the
+/// result is NULL rather than false, so `x NOT IN (1, NULL)` is never true
+fn eval_in_list(value: &Value, items: &[Value], negated: bool) -> Result<Value> {
+    let mut saw_null = matches!(value, Value::Null);
+    for item in items {
+        if matches!(item, Value::Null) {
+            saw_null = true;
+        } else if matches!(eval_binary_op(value, &BinaryOperator::Eq, item)?, Value::Bool(true)) {
+            return Ok(Value::Bool(!negated));
+        }
+    }
+    if saw_null {
+        Ok(Value::Null)
+    } else {
+        Ok(Value::Bool(negated))
+    }
+}
+
+/// Evaluate `value BETWEEN low AND high` (both bounds inclusive), or NOT
+/// BETWEEN when `negated`
+/// A NULL operand makes its comparison unknown, so the result is NULL unless
+/// the other comparison alone decides it
+pub fn eval_between(value: &Value, low: &Value, high: &Value, negated: bool) -> Result<Value> {
+    let compare = |left: &Value, op: BinaryOperator, right: &Value| -> Result<Option<bool>> {
+        if matches!(left, Value::Null) || matches!(right, Value::Null) {
+            return Ok(None);
+        }
+        Ok(Some(matches!(eval_binary_op(left, &op, right)?, Value::Bool(true))))
+    };
+    let result = match (compare(value, BinaryOperator::GtEq, low)?, compare(value, BinaryOperator::LtEq, high)?) {
+        (Some(false), _) | (_, Some(false)) => Some(false),
+        (Some(true), Some(true)) => Some(true),
+        _ => None,
+    };
+    Ok(result.map_or(Value::Null, |inside| Value::Bool(inside != negated)))
+}
+
+/// Whether the evaluator implements an operator itself; any other is left to
+/// extensions
+pub fn is_builtin_operator(op: &BinaryOperator) -> bool {
+    use BinaryOperator::*;
+
+    matches!(
+        op,
+        Eq | NotEq | Lt | LtEq | Gt | GtEq | And | Or
+            | Plus | Minus | Multiply | Divide | Modulo | PGExp
+            | BitwiseAnd | BitwiseOr | PGBitwiseXor | PGBitwiseShiftLeft | PGBitwiseShiftRight
+    )
+}
+
 /// Evaluate a binary operation
 fn eval_binary_op(left: &Value, op: &BinaryOperator, right: &Value) -> Result<Value> {
     use BinaryOperator::*;
@@ -83,7 +404,7 @@ fn eval_binary_op(left: &Value, op: &BinaryOperator, right: &Value) -> Result<Va
                 (Value::Float(a), Value::Int(b)) => *a == *b as f64,
                 (Value::String(a), Value::String(b)) => a == b,
                 (Value::Bool(a), Value::Bool(b)) => a == b,
-                (Value::Null, _) | (_, Value::Null) => false, // NULL comparisons are false
+                (Value::Null, _) | (_, Value::Null) => return Ok(Value::Null), // comparing with NULL gives NULL
                 _ => return Err(ExecutorError::Execution(
                     "Type mismatch in comparison".to_string(),
                 )),
@@ -99,7 +420,7 @@ fn eval_binary_op(left: &Value, op: &BinaryOperator, right: &Value) -> Result<Va
                 (Value::Float(a), Value::Int(b)) => *a != *b as f64,
                 (Value::String(a), Value::String(b)) => a != b,
                 (Value::Bool(a), Value::Bool(b)) => a != b,
-                (Value::Null, _) | (_, Value::Null) => false,
+                (Value::Null, _) | (_, Value::Null) => return Ok(Value::Null),
                 _ => return Err(ExecutorError::Execution(
                     "Type mismatch in comparison".to_string(),
                 )),
@@ -114,7 +435,7 @@ fn eval_binary_op(left: &Value, op: &BinaryOperator, right: &Value) -> Result<Va
                 (Value::Int(a), Value::Float(b)) => *a as f64 > *b,
                 (Value::Float(a), Value::Int(b)) => *a > *b as f64,
                 (Value::String(a), Value::String(b)) => a > b,
-                (Value::Null, _) | (_, Value::Null) => false,
+                (Value::Null, _) | (_, Value::Null) => return Ok(Value::Null),
                 _ => return Err(ExecutorError::Execution(
                     "Type mismatch in comparison".to_string(),
                 )),
@@ -129,7 +450,7 @@ fn eval_binary_op(left: &Value, op: &BinaryOperator, right: &Value) -> Result<Va
                 (Value::Int(a), Value::Float(b)) => (*a as f64) < *b,
                 (Value::Float(a), Value::Int(b)) => *a < (*b as f64),
                 (Value::String(a), Value::String(b)) => a < b,
-                (Value::Null, _) | (_, Value::Null) => false,
+                (Value::Null, _) | (_, Value::Null) => return Ok(Value::Null),
                 _ => return Err(ExecutorError::Execution(
                     "Type mismatch in comparison".to_string(),
                 )),
@@ -144,7 +465,7 @@ fn eval_binary_op(left: &Value, op: &BinaryOperator, right: &Value) -> Result<Va
                 (Value::Int(a), Value::Float(b)) => *a as f64 >= *b,
                 (Value::Float(a), Value::Int(b)) => *a >= *b as f64,
                 (Value::String(a), Value::String(b)) => a >= b,
-                (Value::Null, _) | (_, Value::Null) => false,
+                (Value::Null, _) | (_, Value::Null) => return Ok(Value::Null),
                 _ => return Err(ExecutorError::Execution(
                     "Type mismatch in comparison".to_string(),
                 )),
@@ -159,7 +480,7 @@ fn eval_binary_op(left: &Value, op: &BinaryOperator, right: &Value) -> Result<Va
                 (Value::Int(a), Value::Float(b)) => (*a as f64) <= *b,
                 (Value::Float(a), Value::Int(b)) => *a <= (*b as f64),
                 (Value::String(a), Value::String(b)) => a <= b,
-                (Value::Null, _) | (_, Value::Null) => false,
+                (Value::Null, _) | (_, Value::Null) => return Ok(Value::Null),
                 _ => return Err(ExecutorError::Execution(
                     "Type mismatch in comparison".to_string(),
                 )),
@@ -167,84 +488,74 @@ fn eval_binary_op(left: &Value, op: &BinaryOperator, right: &Value) -> Result<Va
             Ok(Value::Bool(result))
         }
 
-        // Arithmetic operators
-        Plus => {
-            match (left, right) {
-                (Value::Int(a), Value::Int(b)) => Ok(Value::Int(a + b)),
-                (Value::Float(a), Value::Float(b)) => Ok(Value::Float(a + b)),
-                (Value::Int(a), Value::Float(b)) => Ok(Value::Float(*a as f64 + b)),
-                (Value::Float(a), Value::Int(b)) => Ok(Value::Float(a + *b as f64)),
-                _ => Err(ExecutorError::Execution("Type mismatch in +".to_string())),
+        // Arithmetic operators: integers stay integers and fail on overflow,
+        // a float operand makes the result a float, and NULL gives NULL
+        Plus => eval_arithmetic(left, right, "+", i64::checked_add, |a, b| a + b),
+        Minus => eval_arithmetic(left, right, "-", i64::checked_sub, |a, b| a - b),
+        Multiply => eval_arithmetic(left, right, "*", i64::checked_mul, |a, b| a * b),
+        Divide | Modulo => {
+            let is_zero = match right {
+                Value::Int(b) => *b == 0,
+                Value::Float(b) => *b == 0.0,
+                _ => false,
+            };
+            if is_zero && !matches!(left, Value::Null) {
+                return Err(ExecutorError::Execution("Division by zero".to_string()));
             }
-        }
-
-        Minus => {
-            match (left, right) {
-                (Value::Int(a), Value::Int(b)) => Ok(Value::Int(a - b)),
-                (Value::Float(a), Value::Float(b)) => Ok(Value::Float(a - b)),
-                (Value::Int(a), Value::Float(b)) => Ok(Value::Float(*a as f64 - b)),
-                (Value::Float(a), Value::Int(b)) => Ok(Value::Float(a - *b as f64)),
-                _ => Err(ExecutorError::Execution("Type mismatch in -".to_string())),
+            if *op == Divide {
+                eval_arithmetic(left, right, "/", i64::checked_div, |a, b| a / b)
+            } else {
+                // i64::MIN % -1 overflows in Rust, but is 0
+                eval_arithmetic(left, right, "%", |a, b| Some(a.wrapping_rem(b)), |a, b| a % b)
             }
         }
-
-        Multiply => {
-            match (left, right) {
-                (Value::Int(a), Value::Int(b)) => Ok(Value::Int(a * b)),
-                (Value::Float(a), Value::Float(b)) => Ok(Value::Float(a * b)),
-                (Value::Int(a), Value::Float(b)) => Ok(Value::Float(*a as f64 * b)),
-                (Value::Float(a), Value::Int(b)) => Ok(Value::Float(a * *b as f64)),
-                _ => Err(ExecutorError::Execution("Type mismatch in *".to_string())),
+        // Exponentiation is always done in floating point, as in Postgres
+        PGExp => {
+            let as_float = |value: &Value| match value {
+                Value::Int(n) => Some(*n as f64),
+                Value::Float(f) => Some(*f),
+                _ => None,
+            };
+            if matches!(left, Value::Null) || matches!(right, Value::Null) {
+                return Ok(Value::Null);
             }
-        }
-
-        Divide => {
-            match (left, right) {
-                (Value::Int(a), Value::Int(b)) => {
-                    if *b == 0 {
-                        Err(ExecutorError::Execution("Division by zero".to_string()))
-                    } else {
-                        Ok(Value::Int(a / b))
-                    }
-                }
-                (Value::Float(a), Value::Float(b)) => {
-                    if *b == 0.0 {
-                        Err(ExecutorError::Execution("Division by zero".to_string()))
-                    } else {
-                        Ok(Value::Float(a / b))
-                    }
-                }
-                (Value::Int(a), Value::Float(b)) => {
-                    if *b == 0.0 {
-                        Err(ExecutorError::Execution("Division by zero".to_string()))
-                    } else {
-                        Ok(Value::Float(*a as f64 / b))
-                    }
-                }
-                (Value::Float(a), Value::Int(b)) => {
-                    if *b == 0 {
-                        Err(ExecutorError::Execution("Division by zero".to_string()))
-                    } else {
-                        Ok(Value::Float(a / *b as f64))
-                    }
-                }
-                _ => Err(ExecutorError::Execution("Type mismatch in /".to_string())),
+            let (Some(base), Some(exponent)) = (as_float(left), as_float(right)) else {
+                return Err(ExecutorError::Execution("Type mismatch in ^".to_string()));
+            };
+            let result = base.powf(exponent);
+            if result.is_infinite() {
+                return Err(ExecutorError::OutOfRange("value out of range: overflow".to_string()));
             }
+            Ok(Value::Float(result))
         }
 
-        // Logical operators
+        // Bitwise operators, on integers only
+        BitwiseAnd => eval_bitwise(left, right, "&", |a, b| a & b),
+        BitwiseOr => eval_bitwise(left, right, "|", |a, b| a | b),
+        PGBitwiseXor => eval_bitwise(left, right, "#", |a, b| a ^ b),
+        PGBitwiseShiftLeft => eval_bitwise(left, right, "<<", |a, b| a.wrapping_shl(b as u32)),
+        PGBitwiseShiftRight => eval_bitwise(left, right, ">>", |a, b| a.wrapping_shr(b as u32)),
+
+        // Logical operators, with three-valued logic: false AND NULL is
+        // false and true OR NULL is true, otherwise NULL gives NULL
         And => {
             match (left, right) {
-                (Value::Bool(a), Value::Bool(b)) => Ok(Value::Bool(*a && *b)),
-                (Value::Null, _) | (_, Value::Null) => Ok(Value::Null),
+                (Value::Bool(false), Value::Bool(_) | Value::Null) | (Value::Bool(_) | Value::Null, Value::Bool(false)) => {
+                    Ok(Value::Bool(false))
+                }
+                (Value::Bool(true), Value::Bool(true)) => Ok(Value::Bool(true)),
+                (Value::Bool(_) | Value::Null, Value::Bool(_) | Value::Null) => Ok(Value::Null),
                 _ => Err(ExecutorError::Execution("Type mismatch in AND".to_string())),
             }
         }
 
         Or => {
             match (left, right) {
-                (Value::Bool(a), Value::Bool(b)) => Ok(Value::Bool(*a || *b)),
-                (Value::Null, _) | (_, Value::Null) => Ok(Value::Null),
+                (Value::Bool(true), Value::Bool(_) | Value::Null) | (Value::Bool(_) | Value::Null, Value::Bool(true)) => {
+                    Ok(Value::Bool(true))
+                }
+                (Value::Bool(false), Value::Bool(false)) => Ok(Value::Bool(false)),
+                (Value::Bool(_) | Value::Null, Value::Bool(_) | Value::Null) => Ok(Value::Null),
                 _ => Err(ExecutorError::Execution("Type mismatch in OR".to_string())),
             }
         }
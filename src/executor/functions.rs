@@ -0,0 +1,158 @@
+//! Inlining of SQL-defined functions (CREATE FUNCTION ... LANGUAGE SQL)
+//!
+//! A SQL function's body is a single expression. Calls are replaced by the
+//! body with each parameter substituted by the argument expression, before
+//! the surrounding expression is evaluated, so the evaluator never sees them.
+
+use sqlparser::ast::{Expr, FunctionArg, FunctionArgExpr, FunctionArguments, SelectItem, SetExpr, Statement};
+use tracing::debug;
+
+use crate::executor::error::ExecutorError;
+use crate::executor::evaluator;
+use crate::parser;
+use crate::storage::catalog::FunctionMetadata;
+
+pub type Result<T> = std::result::Result<T, ExecutorError>;
+
+/// Nesting limit for functions calling functions, which also stops recursion
+const MAX_INLINE_DEPTH: usize = 32;
+
+/// Parse a function body written as `SELECT <expression>`
+pub fn parse_function_body(sql: &str) -> Result<Expr> {
+    let invalid = || ExecutorError::Execution(format!(
+        "SQL function body must be a single SELECT of one expression without FROM: {}",
+        sql
+    ));
+
+    let stmts = parser::parse(sql)?;
+    let [Statement::Query(query)] = stmts.as_slice() else {
+        return Err(invalid());
+    };
+    let SetExpr::Select(select) = query.body.as_ref() else {
+        return Err(invalid());
+    };
+    if !select.from.is_empty() || select.selection.is_some() {
+        return Err(invalid());
+    }
+    match select.projection.as_slice() {
+        [SelectItem::UnnamedExpr(expr)] | [SelectItem::ExprWithAlias { expr, .. }] => Ok(expr.clone()),
+        _ => Err(invalid()),
+    }
+}
+
+/// Replace parameter references in a function body, by name or as $n, with
+/// the argument expressions
+/// Any other identifier is an error: a function cannot see the caller's columns
+pub fn substitute_params(body: &Expr, params: &[String], args: &[Expr]) -> Result<Expr> {
+    let substitute = |expr: &Expr| substitute_params(expr, params, args);
+
+    match body {
+        Expr::Identifier(ident) => params.iter()
+            .position(|param| !param.is_empty() && *param == ident.value)
+            .map(|idx| Expr::Nested(Box::new(args[idx].clone())))
+            .ok_or_else(|| ExecutorError::Execution(format!(
+                "SQL function body refers to unknown parameter: {}",
+                ident.value
+            ))),
+        Expr::Value(val) => match &val.value {
+            sqlparser::ast::Value::Placeholder(placeholder) => placeholder.strip_prefix('$')
+                .and_then(|position| position.parse::<usize>().ok())
+                .filter(|position| (1..=args.len()).contains(position))
+                .map(|position| Expr::Nested(Box::new(args[position - 1].clone())))
+                .ok_or_else(|| ExecutorError::Execution(format!(
+                    "SQL function body refers to unknown parameter: {}",
+                    placeholder
+                ))),
+            _ => Ok(body.clone()),
+        },
+        Expr::BinaryOp { left, op, right } => Ok(Expr::BinaryOp {
+            left: Box::new(substitute(left)?),
+            op: op.clone(),
+            right: Box::new(substitute(right)?),
+        }),
+        Expr::UnaryOp { op, expr } => Ok(Expr::UnaryOp {
+            op: *op,
+            expr: Box::new(substitute(expr)?),
+        }),
+        Expr::Nested(inner) => Ok(Expr::Nested(Box::new(substitute(inner)?))),
+        Expr::Function(_) => map_function_args(body, substitute),
+        _ => Ok(body.clone()),
+    }
+}
+
+/// Inline every call to a SQL function in `expr`
+/// `lookup` resolves a function name to its definition, if it has one
+pub fn inline_functions(expr: &Expr, lookup: &dyn Fn(&str) -> Option<FunctionMetadata>) -> Result<Expr> {
+    inline_at_depth(expr, lookup, 0)
+}
+
+fn inline_at_depth(expr: &Expr, lookup: &dyn Fn(&str) -> Option<FunctionMetadata>, depth: usize) -> Result<Expr> {
+    let inline = |expr: &Expr| inline_at_depth(expr, lookup, depth);
+
+    match expr {
+        Expr::Function(func) => {
+            // Arguments are inlined first, so they may call SQL functions too
+            let inlined = map_function_args(expr, inline)?;
+            let name = func.name.0.iter()
+                .filter_map(|part| part.as_ident())
+                .map(|ident| ident.value.clone())
+                .collect::<Vec<_>>()
+                .join(".");
+            let Some(function_meta) = lookup(&name) else {
+                return Ok(inlined);
+            };
+            if depth >= MAX_INLINE_DEPTH {
+                return Err(ExecutorError::Execution(format!(
+                    "SQL function {} nests deeper than {} calls; is it recursive?",
+                    function_meta.name, MAX_INLINE_DEPTH
+                )));
+            }
+
+            let args: Vec<Expr> = match &inlined {
+                Expr::Function(func) => evaluator::function_args(func)?.into_iter().cloned().collect(),
+                _ => Vec::new(),
+            };
+            if args.len() != function_meta.args.len() {
+                return Err(ExecutorError::Execution(format!(
+                    "Function {} takes {} argument(s), got {}",
+                    function_meta.name,
+                    function_meta.args.len(),
+                    args.len()
+                )));
+            }
+
+            let params: Vec<String> = function_meta.args.iter().map(|(name, _)| name.clone()).collect();
+            let body = parse_function_body(&format!("SELECT {}", function_meta.body))?;
+            let substituted = substitute_params(&body, &params, &args)?;
+            debug!(function = %function_meta.name, depth, "inlined SQL function");
+
+            Ok(Expr::Nested(Box::new(inline_at_depth(&substituted, lookup, depth + 1)?)))
+        }
+        Expr::BinaryOp { left, op, right } => Ok(Expr::BinaryOp {
+            left: Box::new(inline(left)?),
+            op: op.clone(),
+            right: Box::new(inline(right)?),
+        }),
+        Expr::UnaryOp { op, expr } => Ok(Expr::UnaryOp {
+            op: *op,
+            expr: Box::new(inline(expr)?),
+        }),
+        Expr::Nested(inner) => Ok(Expr::Nested(Box::new(inline(inner)?))),
+        _ => Ok(expr.clone()),
+    }
+}
+
+/// Copy of a function call with `map` applied to each positional argument
+fn map_function_args(expr: &Expr, map: impl Fn(&Expr) -> Result<Expr>) -> Result<Expr> {
+    let mut expr = expr.clone();
+    if let Expr::Function(func) = &mut expr
+        && let FunctionArguments::List(list) = &mut func.args
+    {
+        for arg in &mut list.args {
+            if let FunctionArg::Unnamed(FunctionArgExpr::Expr(arg_expr)) = arg {
+                *arg_expr = map(arg_expr)?;
+            }
+        }
+    }
+    Ok(expr)
+}
@@ -0,0 +1,69 @@
+//! Per-query memory accounting against work_mem
+//!
+//! The executor materializes the rows of each operator, so a large sort,
+//! aggregate or join could otherwise grow until the server is killed. The
+//! operators that build up rows of their own charge an estimate of each row's
+//! size to the query's budget as they go, and the query fails with a resource
+//! error once the total passes work_mem. Charges are not released until the
+//! query ends. Nothing is spilled to disk; only the encoded rows of a large
+//! response are (see `spool`).
+
+use std::cell::Cell;
+
+use crate::executor::error::ExecutorError;
+use crate::types::{Row, Value};
+
+pub type Result<T> = std::result::Result<T, ExecutorError>;
+
+/// Memory a single query may use for materialized rows
+pub struct MemoryBudget {
+    limit: usize,
+    used: Cell<usize>,
+}
+
+impl MemoryBudget {
+    pub fn new(limit: usize) -> Self {
+        MemoryBudget {
+            limit,
+            used: Cell::new(0),
+        }
+    }
+
+    /// Account for `bytes` more held by `operator`, failing once the query is
+    /// over its budget
+    pub fn charge(&self, bytes: usize, operator: &str) -> Result<()> {
+        let used = self.used.get().saturating_add(bytes);
+        self.used.set(used);
+        if used > self.limit {
+            return Err(ExecutorError::ResourceExhausted(format!(
+                "{} needs more than work_mem ({} bytes); narrow the query or raise --work-mem",
+                operator, self.limit
+            )));
+        }
+        Ok(())
+    }
+
+    /// Account for the rows held by `operator`
+    pub fn charge_rows(&self, rows: &[Row], operator: &str) -> Result<()> {
+        self.charge(rows.iter().map(row_size).sum(), operator)
+    }
+
+    pub fn used(&self) -> usize {
+        self.used.get()
+    }
+}
+
+/// Estimated bytes a row takes in memory
+pub fn row_size(row: &Row) -> usize {
+    std::mem::size_of::<Row>() + values_size(&row.values)
+}
+
+/// Estimated bytes a list of values takes in memory, including string contents
+pub fn values_size(values: &[Value]) -> usize {
+    values.iter()
+        .map(|value| std::mem::size_of::<Value>() + match value {
+            Value::String(text) => text.capacity(),
+            _ => 0,
+        })
+        .sum()
+}
@@ -1,236 +1,2216 @@
+pub mod adaptive;
+pub mod advisory;
+pub mod aggregate;
+pub mod builtins;
+pub mod cast;
+pub mod cluster;
+pub mod copy;
+pub mod cursor;
 pub mod error;
 pub mod evaluator;
+pub mod functions;
+pub mod memory;
+pub mod prepared;
+pub mod psql;
+pub mod referential;
+pub mod schema;
+pub mod settings;
+pub mod slowlog;
+pub mod spool;
+pub mod temp;
+pub mod transaction;
+pub mod typing;
+pub mod typmod;
+pub mod upsert;
+pub mod wire;
+pub mod workload;
 
-use std::sync::Arc;
+use std::borrow::Cow;
+use std::collections::{HashMap, HashSet};
+use std::path::{Component, Path, PathBuf};
+use std::sync::{Arc, Weak};
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+use std::time::Duration;
 use futures::stream;
-use pgwire::api::results::{DataRowEncoder, FieldFormat, FieldInfo, QueryResponse, Response, Tag};
+use pgwire::api::portal::Format;
+use pgwire::api::results::{CopyResponse, DataRowEncoder, FieldFormat, FieldInfo, QueryResponse, Response, Tag};
 use pgwire::api::Type;
 use sqlparser::ast::Statement;
-use tracing::{debug, info};
+use tracing::{debug, info, warn};
 
 use crate::config::Config;
+use crate::executor::advisory::LockKey;
+use crate::executor::cluster::Cluster;
+use crate::executor::copy::{CopyIn, CopyOptions, CopyOut, COPY_BATCH_ROWS};
+use crate::executor::cursor::{Cursor, Session, SessionId};
 use crate::executor::error::ExecutorError;
-use crate::planner::{self, Operator};
+use crate::executor::memory::MemoryBudget;
+use crate::executor::prepared::PreparedLimits;
+use crate::executor::spool::{ResultSpool, SpoolConfig};
+use crate::executor::transaction::{Lock, Locks};
+use crate::executor::workload::{Admission, WorkloadClass};
+use crate::planner::{self, Operator, SortKey};
 use crate::parser;
-use crate::storage::Database;
-use crate::types::{Row, Value, Schema};
+use crate::storage::{archive, backup, mvcc, Database, TuplePointer};
+use crate::storage::index::ScanDirection;
+use crate::types::{DataType, Row, Value, Schema};
 
 pub type Result<T> = std::result::Result<T, ExecutorError>;
 
+/// How long a shutdown waits for the database lock once queries have had
+/// their time to finish
+const SHUTDOWN_LOCK_WAIT: Duration = Duration::from_secs(1);
+
+/// Nesting limit for procedures calling procedures, which also stops recursion
+const MAX_CALL_DEPTH: usize = 16;
+
+/// Functions evaluated by the executor itself, which SQL functions may not shadow
+const BUILTIN_FUNCTIONS: &[&str] = &[
+    "flint_table_size",
+    "flint_row_count",
+    "flint_approx_row_count",
+    "nextval",
+    "setval",
+    "flint_promote",
+    "flint_backup",
+    "flint_verify_backup",
+    "pg_advisory_lock",
+    "pg_try_advisory_lock",
+    "pg_advisory_unlock",
+    "pg_advisory_unlock_all",
+];
+
+/// Advisory lock functions, answered for the session running the statement
+const ADVISORY_LOCK_FUNCTIONS: &[&str] = &[
+    "pg_advisory_lock",
+    "pg_try_advisory_lock",
+    "pg_advisory_unlock",
+    "pg_advisory_unlock_all",
+];
+
 pub(crate) struct Executor {
+    /// Name of the database this executor runs queries on
+    database: String,
+    /// The server's databases, for CREATE and DROP DATABASE; dangling for an
+    /// executor of its own, as `flint repl` uses
+    cluster: Weak<Cluster>,
     db: Arc<parking_lot::RwLock<Database>>,
+    /// Sessions with an open transaction block or cursors; idle ones are not kept
+    sessions: parking_lot::Mutex<HashMap<SessionId, Session>>,
+    /// Server started with --read-only and not promoted since: every
+    /// transaction is read-only; shared by the server's databases
+    read_only: Arc<AtomicBool>,
+    /// Bytes of materialized rows a query may hold
+    work_mem: usize,
+    /// Where large responses are spooled
+    spool: SpoolConfig,
+    /// Caps on each session's prepared statements
+    prepared_limits: PreparedLimits,
+    /// The only directory backup functions may use (--backup-dir)
+    backup_dir: Option<PathBuf>,
+    /// The write lock, held by the transaction writing until it ends, and
+    /// sessions' advisory locks (see `transaction`)
+    locks: Locks,
+    /// Limits on the queries each workload class runs at once, shared by
+    /// the server's databases
+    admission: Arc<Admission>,
+    /// Temporary schemas handed out to sessions so far
+    temp_schemas: AtomicU32,
 }
 
-impl Executor {
-    pub fn new(config: &Config) -> Self {
-        Executor {
-            db: Arc::new(parking_lot::RwLock::new(Database::new(config)))
+impl Executor {
+    pub fn new(config: &Config) -> Result<Self> {
+        Self::open(config, cluster::DEFAULT_DATABASE, Weak::new(), None)
+    }
+
+    /// An executor for the database `name` of a server, stored in
+    /// `config.data_dir`; databases after the first share its read-only state
+    /// and limits on running queries, which are the server's
+    fn open(config: &Config, name: &str, cluster: Weak<Cluster>, first: Option<&Executor>) -> Result<Self> {
+        evaluator::set_integer_overflow(config.integer_overflow);
+        let db = Database::new(config)
+            .map_err(|e| ExecutorError::Execution(format!("cannot open database \"{}\": {}", name, e)))?;
+        Ok(Executor {
+            database: name.to_string(),
+            cluster,
+            db: Arc::new(parking_lot::RwLock::new(db)),
+            sessions: parking_lot::Mutex::new(HashMap::new()),
+            read_only: first.map_or_else(|| Arc::new(AtomicBool::new(config.read_only)), |first| first.read_only.clone()),
+            work_mem: config.work_mem,
+            spool: SpoolConfig {
+                dir: config.data_dir.join("tmp"),
+                max_memory: config.result_spool_size,
+            },
+            prepared_limits: PreparedLimits {
+                max_statements: config.max_prepared_statements,
+                max_bytes: config.prepared_statement_mem,
+            },
+            backup_dir: config.backup_dir.clone(),
+            locks: Locks::default(),
+            admission: first.map_or_else(|| Arc::new(Admission::new(config.workload_limits)), |first| first.admission.clone()),
+            temp_schemas: AtomicU32::new(0),
+        })
+    }
+
+    pub fn execute(&self, session_id: SessionId, query: &str) -> Result<Vec<Response>> {
+        slowlog::start_query();
+        debug!("parsing query");
+        let stmts = parser::parse_with_locations(query)?;
+
+        if stmts.is_empty() {
+            debug!("empty query");
+            return Ok(vec![Response::EmptyQuery]);
+        }
+
+        info!(statement_count = stmts.len(), "parsed statements");
+        builtins::start_statement();
+
+        // A connection runs one query at a time, so its session can be taken
+        // out of the map for the duration of the query
+        let mut session = self.sessions.lock().remove(&session_id).unwrap_or_else(|| Session::new(session_id));
+        // Rows left by a COPY TO of an earlier query are of one whose sending
+        // failed
+        session.copy_out.clear();
+        let class = session.workload_class
+            .unwrap_or_else(|| WorkloadClass::of_statements(stmts.iter().map(|(stmt, _)| stmt)));
+        let _permit = self.admission.admit(class)?;
+        session.admitted = Some(class);
+        let mut responses = Vec::new();
+        for (idx, (stmt, location)) in stmts.iter().enumerate() {
+            debug!(statement_idx = idx, "planning statement");
+            match self.execute_top_level(stmt, &mut session) {
+                Ok(response) => responses.push(response),
+                Err(e) => {
+                    // As in Postgres, the statements after a failing one are not
+                    // run; the ones before it keep their effects and results
+                    info!(statement_idx = idx, line = location.line, "statement failed");
+                    let mut error_info = e.into_error_info();
+                    if stmts.len() > 1 {
+                        error_info.position = parser::char_position(query, *location).map(|pos| pos.to_string());
+                    }
+                    responses.push(Response::Error(Box::new(error_info)));
+                    break;
+                }
+            }
+        }
+        session.admitted = None;
+        if !session.is_idle() {
+            self.sessions.lock().insert(session_id, session);
+        }
+
+        info!(response_count = responses.len(), "execution complete");
+        Ok(responses)
+    }
+
+    /// Run a statement of the extended query protocol, with `params` bound to
+    /// its `$n` placeholders and its result columns in `result_format`
+    /// A failing statement is answered with an error response, as in `execute`
+    pub fn execute_portal(&self, session_id: SessionId, query: &str, params: Vec<Value>, result_format: &Format) -> Result<Response> {
+        slowlog::start_query();
+        let Some((stmt, _)) = self.parse_portal(query, Some(params))? else {
+            return Ok(Response::EmptyQuery);
+        };
+        builtins::start_statement();
+
+        let mut session = self.sessions.lock().remove(&session_id).unwrap_or_else(|| Session::new(session_id));
+        session.copy_out.clear();
+        let class = session.workload_class
+            .unwrap_or_else(|| WorkloadClass::of_statements(std::iter::once(&stmt)));
+        let _permit = self.admission.admit(class)?;
+        session.admitted = Some(class);
+        session.result_format = Some(result_format.clone());
+        let response = self.execute_top_level(&stmt, &mut session)
+            .unwrap_or_else(|e| {
+                info!("statement failed");
+                Response::Error(Box::new(e.into_error_info()))
+            });
+        session.result_format = None;
+        session.admitted = None;
+        if !session.is_idle() {
+            self.sessions.lock().insert(session_id, session);
+        }
+        Ok(response)
+    }
+
+    /// Log a query that ran past the slow query threshold, with the rows it
+    /// returned and the estimated plan of its statements; call it on the
+    /// thread that ran the query, once it is done
+    pub fn log_slow_query(&self, query: &str, elapsed: Duration) {
+        let stmts = parser::parse(query).unwrap_or_default();
+        let db = self.db.read();
+        let plan: Vec<String> = stmts.iter()
+            .filter(|stmt| matches!(stmt, Statement::Query(_) | Statement::Update { .. } | Statement::Delete(_)))
+            .filter_map(|stmt| planner::plan(stmt).ok())
+            .flat_map(|plan| planner::cost::estimate(&plan, &*db).explain_lines(true))
+            .collect();
+        drop(db);
+        slowlog::log(query, elapsed, &plan);
+    }
+
+    /// Number of parameters of a statement of the extended query protocol,
+    /// and the columns of its result in `result_format` if it returns rows
+    /// The statement is planned but not run; `params` are bound first if
+    /// known, and are NULL otherwise
+    pub fn describe(
+        &self,
+        session_id: SessionId,
+        query: &str,
+        params: Option<Vec<Value>>,
+        result_format: &Format,
+    ) -> Result<(usize, Option<Vec<FieldInfo>>)> {
+        let Some((stmt, param_count)) = self.parse_portal(query, params)? else {
+            return Ok((0, None));
+        };
+        if let Statement::ShowVariable { variable } = &stmt {
+            let schema = settings::show_schema(&settings::show_name(variable))?;
+            return Ok((param_count, Some(self.field_infos(&schema, Some(result_format)))));
+        }
+        if !matches!(stmt, Statement::Query(_)) {
+            return Ok((param_count, None));
+        }
+
+        let stmt = match self.sessions.lock().get(&session_id) {
+            Some(session) => *self.resolve_names(&stmt, session)?,
+            None => stmt,
+        };
+        let plan = planner::plan(&stmt)?;
+        let fields = self.output_schema(&plan)
+            .map(|schema| self.field_infos(&schema, Some(result_format)));
+        Ok((param_count, fields))
+    }
+
+    /// The one statement of an extended query protocol query, with `params`
+    /// bound or NULL if not given, and its number of parameters; None for an
+    /// empty query
+    fn parse_portal(&self, query: &str, params: Option<Vec<Value>>) -> Result<Option<(Statement, usize)>> {
+        let mut stmts = parser::parse(query)?;
+        if stmts.len() > 1 {
+            return Err(ExecutorError::Parse("cannot insert multiple commands into a prepared statement".to_string()));
+        }
+        let Some(mut stmt) = stmts.pop() else {
+            return Ok(None);
+        };
+        let param_count = planner::count_placeholders(&stmt);
+        let params = params.unwrap_or_else(|| vec![Value::Null; param_count]);
+        if params.len() != param_count {
+            return Err(ExecutorError::Execution(format!(
+                "bind message supplies {} parameters, but prepared statement requires {}",
+                params.len(),
+                param_count
+            )));
+        }
+        prepared::bind_parameters(&mut stmt, params)?;
+        Ok(Some((stmt, param_count)))
+    }
+
+    /// Take in a CopyData message of the session's COPY FROM STDIN, writing
+    /// each full batch of rows it completes
+    /// An error stops the copy but is only reported once the client ends the
+    /// data, since until then the client is still sending it
+    pub fn copy_data(&self, session_id: SessionId, data: &[u8]) -> Result<()> {
+        let mut session = self.copy_session(session_id)?;
+        let class = session.workload_class.unwrap_or(WorkloadClass::Batch);
+        if let Some(copy_in) = &mut session.copy_in
+            && copy_in.error.is_none()
+        {
+            let mut result = copy_in.feed(data);
+            if result.is_ok() && copy_in.rows.len() >= COPY_BATCH_ROWS {
+                let _permit = self.admission.admit(class)?;
+                mvcc::set_current(session.transaction);
+                result = self.write_copy_batch(copy_in);
+                mvcc::set_current(None);
+            }
+            if let Err(e) = result {
+                info!(table = %copy_in.table_name, rows = copy_in.copied, "COPY failed");
+                copy_in.error = Some(e);
+            }
+        }
+        self.sessions.lock().insert(session_id, session);
+        Ok(())
+    }
+
+    /// End the session's COPY FROM STDIN once the client has sent all of its
+    /// data, writing the rows left, and with it the COPY's transaction unless
+    /// a transaction block goes on; returns the number of rows copied
+    pub fn copy_done(&self, session_id: SessionId) -> Result<usize> {
+        let mut session = self.copy_session(session_id)?;
+        let class = session.workload_class.unwrap_or(WorkloadClass::Batch);
+        let mut copy_in = session.copy_in.take()
+            .ok_or_else(|| ExecutorError::Execution("no COPY in progress".to_string()))?;
+
+        mvcc::set_current(session.transaction);
+        let result = self.finish_copy(&mut copy_in, class);
+        let result = self.end_statement(&mut session, result);
+        mvcc::set_current(None);
+        if !session.is_idle() {
+            self.sessions.lock().insert(session_id, session);
+        }
+        result
+    }
+
+    /// Write the rows a COPY FROM STDIN has left once its data has ended
+    fn finish_copy(&self, copy_in: &mut CopyIn, class: WorkloadClass) -> Result<usize> {
+        if let Some(e) = copy_in.error.take() {
+            return Err(e);
+        }
+        copy_in.finish()?;
+        if !copy_in.rows.is_empty() {
+            let _permit = self.admission.admit(class)?;
+            self.write_copy_batch(copy_in)?;
+        }
+        info!(table = %copy_in.table_name, rows = copy_in.copied, "COPY complete");
+        Ok(copy_in.copied)
+    }
+
+    /// Abandon the session's COPY FROM STDIN, which the client failed; the
+    /// rows already written are rolled back with the COPY's transaction, or
+    /// the transaction block's
+    pub fn copy_fail(&self, session_id: SessionId) {
+        let Ok(mut session) = self.copy_session(session_id) else {
+            return;
+        };
+        if let Some(copy_in) = session.copy_in.take() {
+            info!(table = %copy_in.table_name, rows = copy_in.copied, "COPY abandoned by the client");
+            // Like a failed statement, it fails the transaction block it is in
+            self.rollback_writes(&mut session);
+            session.failed = session.in_transaction;
+        }
+        if !session.is_idle() {
+            self.sessions.lock().insert(session_id, session);
+        }
+    }
+
+    /// The session, taken out of the map as `execute` does, of a connection
+    /// in the middle of a COPY
+    fn copy_session(&self, session_id: SessionId) -> Result<Session> {
+        self.sessions.lock().remove(&session_id)
+            .ok_or_else(|| ExecutorError::Execution("no COPY in progress".to_string()))
+    }
+
+    /// Run the scan or query of a COPY TO STDOUT; its rows are kept in the
+    /// session for the handler to send after the response, through
+    /// copy_out_data
+    fn copy_to(&self, relation: planner::CopyRelation, options: CopyOptions, session: &mut Session) -> Result<Response> {
+        let (columns, rows) = match relation {
+            planner::CopyRelation::Table { name, columns } => {
+                let schema = self.db.read().get_schema(&name)
+                    .map_err(ExecutorError::Execution)?;
+                let targets = Self::insert_targets(&name, &schema, &columns)?;
+                let (rows, _) = self.execute_plan_with_schema(Operator::TableScan { table: name })?;
+                let rows = rows.into_iter()
+                    .map(|mut row| Row::new(targets.iter()
+                        .map(|&idx| std::mem::replace(&mut row.values[idx], Value::Null))
+                        .collect()))
+                    .collect();
+                (targets.iter().map(|&idx| schema.columns[idx].name.clone()).collect(), rows)
+            }
+            planner::CopyRelation::Query(query) => {
+                let plan = planner::plan(&Statement::Query(query))?;
+                let (rows, schema) = self.execute_plan_with_schema(plan)?;
+                let columns = match schema {
+                    Some(schema) => schema.columns.into_iter().map(|column| column.name).collect(),
+                    None => (0..rows.first().map_or(0, |row| row.len())).map(|i| format!("?column?{}", i)).collect(),
+                };
+                (columns, rows)
+            }
+        };
+
+        // Values of extension types are sent as their text
+        #[cfg(feature = "extensions")]
+        let rows = {
+            let type_registry = self.db.read().type_registry.clone();
+            let mut rows = rows;
+            for row in &mut rows {
+                for value in &mut row.values {
+                    if let Value::Extension { type_oid, data } = value {
+                        let type_ext = type_registry.get_by_oid(*type_oid)
+                            .ok_or_else(|| ExecutorError::Execution(format!("type with OID {} is not registered", type_oid)))?;
+                        *value = type_ext.output(data.as_ref()).map_or(Value::Null, Value::String);
+                    }
+                }
+            }
+            rows
+        };
+
+        let copy_out = CopyOut::new(columns, rows, options);
+        let column_count = copy_out.column_count();
+        session.copy_out.push_back(copy_out);
+        Ok(Response::CopyOut(CopyResponse::new(0, column_count, vec![0; column_count])))
+    }
+
+    /// The next batch of rows of the session's COPY TO STDOUT, as the data
+    /// of a CopyData message; None once every row has been sent
+    pub fn copy_out_data(&self, session_id: SessionId) -> Result<Option<Vec<u8>>> {
+        let mut session = self.copy_session(session_id)?;
+        let data = session.copy_out.front_mut().and_then(CopyOut::next_chunk);
+        if !session.is_idle() {
+            self.sessions.lock().insert(session_id, session);
+        }
+        Ok(data)
+    }
+
+    /// End the session's COPY TO STDOUT once its rows are sent, returning how
+    /// many there were
+    pub fn copy_out_done(&self, session_id: SessionId) -> Result<usize> {
+        let mut session = self.copy_session(session_id)?;
+        let copy_out = session.copy_out.pop_front();
+        if !session.is_idle() {
+            self.sessions.lock().insert(session_id, session);
+        }
+        let copied = copy_out.map_or(0, |copy_out| copy_out.copied);
+        info!(rows = copied, "COPY TO complete");
+        Ok(copied)
+    }
+
+    /// Write the rows a COPY FROM STDIN has parsed, as one batch checked like
+    /// the rows of a multi-row INSERT
+    fn write_copy_batch(&self, copy_in: &mut CopyIn) -> Result<()> {
+        let fields = std::mem::take(&mut copy_in.rows);
+        let mut db = self.db.write();
+        let mut rows = Vec::with_capacity(fields.len());
+        for row_fields in fields {
+            let mut values = copy_in.values(row_fields)?;
+            for (col_idx, sequence) in &copy_in.serials {
+                let value = db.nextval(sequence)
+                    .map_err(ExecutorError::Execution)?;
+                values[*col_idx] = Value::Int(value);
+            }
+            let mut row = Row::new(values);
+            typmod::apply(&db, &copy_in.schema, &mut row)?;
+            rows.push(row);
+        }
+
+        let checks = Self::table_checks(&db, &copy_in.table_name)?;
+        for row in &rows {
+            Self::check_row(&copy_in.table_name, &checks, row, &copy_in.schema)?;
+        }
+        referential::check_references(&db, &copy_in.table_name, &copy_in.schema, &rows)?;
+        let inserted = db.insert_rows(&copy_in.table_name, rows)
+            .map_err(ExecutorError::Execution)?;
+        copy_in.copied += inserted;
+        debug!(table = %copy_in.table_name, rows = inserted, "COPY batch written");
+        Ok(())
+    }
+
+    /// Promote a read-only server to accept writes, as when failing over from
+    /// a primary; lasts until the server is restarted
+    pub fn promote(&self) -> Result<()> {
+        if !self.read_only.swap(false, Ordering::SeqCst) {
+            return Err(ExecutorError::Execution(
+                "server is not read-only, so there is nothing to promote".to_string(),
+            ));
+        }
+        info!("server promoted, accepting writes");
+        Ok(())
+    }
+
+    /// Stop taking queries and make everything written durable, for a clean
+    /// shutdown: running queries get up to `timeout` to finish, then the
+    /// database is checkpointed unless one of them still holds it
+    pub fn shutdown(&self, timeout: Duration) -> Result<()> {
+        let running = self.admission.close(timeout);
+        if running > 0 {
+            warn!(running, "queries still running after the shutdown timeout");
+        }
+        let mut db = self.db.try_write_for(SHUTDOWN_LOCK_WAIT)
+            .ok_or_else(|| ExecutorError::Execution("database still in use, shutting down without a checkpoint".to_string()))?;
+        db.checkpoint().map_err(ExecutorError::Execution)
+    }
+
+    /// Delete the rows every table's retention policy has expired, one table
+    /// at a time so queries can run in between; nothing is deleted while the
+    /// server is read-only
+    /// Returns the number of rows deleted
+    pub fn expire_rows(&self) -> Result<usize> {
+        if self.read_only.load(Ordering::SeqCst) {
+            return Ok(0);
+        }
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|elapsed| elapsed.as_secs() as i64)
+            .unwrap_or(0);
+        let tables = self.db.read().ttl_tables();
+        let mut expired = 0;
+        for (table, _) in tables {
+            // A table is left for the next run while a transaction writes
+            if !self.locks.try_lock(Lock::Write, None) {
+                debug!(table = %table, "transaction writing, leaving expired rows for later");
+                continue;
+            }
+            let deleted = self.db.write().expire_rows(&table, now);
+            self.locks.unlock(Lock::Write, None);
+            expired += deleted.map_err(ExecutorError::Execution)?;
+        }
+        Ok(expired)
+    }
+
+    /// Forget a session once its connection is closed, rolling back its open
+    /// transaction and dropping its temporary tables
+    pub fn end_session(&self, session_id: SessionId) {
+        if let Some(mut session) = self.sessions.lock().remove(&session_id) {
+            debug!(cursors = session.cursors.len(), prepared = session.prepared.len(), "session ended");
+            if session.transaction.is_some() {
+                self.rollback_writes(&mut session);
+                info!("rolled back open transaction of closed session");
+            }
+            if !session.temp_tables.is_empty() {
+                let mut db = self.db.write();
+                for stored_name in session.temp_tables.values() {
+                    if let Err(e) = db.drop_temp_table(stored_name) {
+                        warn!(table = %stored_name, error = %e, "failed to drop temporary table of closed session");
+                    }
+                }
+                info!(tables = session.temp_tables.len(), "dropped temporary tables of closed session");
+            }
+        }
+        let released = self.locks.unlock_advisory(session_id);
+        if released > 0 {
+            info!(locks = released, "released advisory locks of closed session");
+        }
+    }
+
+    /// The statement with each table name replaced by the name the table is
+    /// stored under: the session's temporary tables (see `temp`), then the
+    /// tables of other schemas (see `schema`)
+    fn resolve_names(&self, stmt: &Statement, session: &Session) -> Result<Box<Statement>> {
+        let search_path = settings::search_path(session);
+        let db = self.db.read();
+        let resolved = if session.temp_tables.is_empty() {
+            schema::resolve(stmt, &db, &search_path, &self.database)?
+        } else {
+            schema::resolve(&temp::resolve(stmt, &session.temp_tables), &db, &search_path, &self.database)?
+        };
+        Ok(Box::new(resolved))
+    }
+
+    /// Execute a statement the client sent in the session's transaction,
+    /// ending the transaction with it unless a transaction block or a COPY
+    /// FROM STDIN goes on (see `transaction`)
+    fn execute_top_level(&self, stmt: &Statement, session: &mut Session) -> Result<Response> {
+        if session.failed && !matches!(stmt, Statement::Commit { .. } | Statement::Rollback { .. }) {
+            return Err(ExecutorError::FailedTransaction(
+                "current transaction is aborted, commands ignored until end of transaction block".to_string(),
+            ));
+        }
+        mvcc::set_current(session.transaction);
+        let mut result = self.execute_statement(stmt, session, 0);
+        if session.copy_in.is_none() {
+            result = self.end_statement(session, result);
+        }
+        mvcc::set_current(None);
+        result
+    }
+
+    /// End a statement's part in the session's transaction: outside a
+    /// transaction block, the transaction commits if the statement succeeded
+    /// and rolls back if not; inside one, a failure rolls back the whole
+    /// block, as in Postgres
+    fn end_statement<T>(&self, session: &mut Session, result: Result<T>) -> Result<T> {
+        if session.in_transaction {
+            if result.is_err() {
+                self.rollback_writes(session);
+                session.failed = true;
+            }
+            return result;
+        }
+        match result {
+            Ok(value) => self.commit_writes(session).map(|()| value),
+            Err(e) => {
+                self.rollback_writes(session);
+                Err(e)
+            }
+        }
+    }
+
+    /// Start the session's transaction before a statement writes, unless it
+    /// has one, once the transaction writing has ended
+    fn begin_writes(&self, session: &mut Session) -> Result<()> {
+        if session.transaction.is_none() {
+            self.wait_for_lock(Lock::Write, session)?;
+            let txid = self.db.write().begin_transaction();
+            session.transaction = Some(txid);
+            mvcc::set_current(Some(txid));
+        }
+        Ok(())
+    }
+
+    /// Take a lock for the session, giving up the running query's place in
+    /// its workload class while it waits (see `transaction`)
+    fn wait_for_lock(&self, lock: Lock, session: &Session) -> Result<()> {
+        if self.locks.try_lock(lock, Some(session.id)) {
+            return Ok(());
+        }
+        debug!(lock = ?lock, "waiting for lock");
+        let timeout = settings::lock_timeout(session);
+        match session.admitted {
+            Some(class) => self.admission.give_way(class, || self.locks.lock(lock, session.id, timeout)),
+            None => self.locks.lock(lock, session.id, timeout),
+        }
+    }
+
+    /// Commit the session's transaction, letting the next one write; one
+    /// that fails to commit is rolled back
+    fn commit_writes(&self, session: &mut Session) -> Result<()> {
+        let Some(txid) = session.transaction.take() else {
+            return Ok(());
+        };
+        mvcc::set_current(None);
+        let committed = self.db.write().commit_transaction(txid);
+        self.locks.unlock(Lock::Write, Some(session.id));
+        committed.map_err(ExecutorError::Execution)
+    }
+
+    /// Roll back the session's transaction, letting the next one write
+    fn rollback_writes(&self, session: &mut Session) {
+        if let Some(txid) = session.transaction.take() {
+            mvcc::set_current(None);
+            self.db.write().rollback_transaction(txid);
+            self.locks.unlock(Lock::Write, Some(session.id));
+        }
+    }
+
+    /// Execute one statement; `call_depth` counts the procedure calls it is nested in
+    fn execute_statement(&self, stmt: &Statement, session: &mut Session, call_depth: usize) -> Result<Response> {
+        // Boxed, as this frame is on the stack once per nested CALL
+        let resolved = self.resolve_names(stmt, session)?;
+        let stmt = &*resolved;
+
+        // Writes are refused before any of their work is done; a CALL is not
+        // refused itself, but each statement of the procedure body is checked
+        if (self.read_only.load(Ordering::SeqCst) || session.read_only)
+            && let Some(command) = planner::write_command(stmt)
+        {
+            return Err(ExecutorError::ReadOnly(format!(
+                "cannot execute {} in a read-only transaction",
+                command
+            )));
+        }
+        // ROLLBACK only undoes changes to rows, so TRUNCATE and changes to
+        // definitions are refused inside a transaction block; VACUUM would
+        // also drop the tuples of the block's own transaction
+        if session.in_transaction
+            && let Some(command) = planner::write_command(stmt)
+            && !matches!(command.as_str(), "INSERT" | "UPDATE" | "DELETE" | "COPY FROM" | "nextval()" | "setval()")
+        {
+            let command = match stmt {
+                Statement::Drop { object_type, .. } => format!("DROP {}", object_type),
+                _ => command,
+            };
+            return Err(ExecutorError::ActiveTransaction(format!("{} cannot run inside a transaction block", command)));
+        }
+        // Sequences aren't transactional, so queries that only advance one
+        // run outside any transaction
+        if planner::write_command(stmt).is_some_and(|command| !matches!(command.as_str(), "nextval()" | "setval()")) {
+            self.begin_writes(session)?;
+        }
+
+        // Handle DDL/DML/transactions directly (not via planner)
+        match stmt {
+            Statement::StartTransaction { modes, .. } => {
+                debug!("executing: start transaction");
+                let read_only = self.check_access_mode(planner::extract_transaction_read_only(modes))?;
+                session.in_transaction = true;
+                session.read_only = read_only.unwrap_or(false);
+                Ok(Response::TransactionStart(Tag::new("BEGIN")))
+            }
+            Statement::Set(sqlparser::ast::Set::SetTransaction { modes, snapshot, session: characteristics }) => {
+                debug!("executing: set transaction");
+                if *characteristics || snapshot.is_some() {
+                    return Err(ExecutorError::UnsupportedStatement(
+                        "Only SET TRANSACTION with an access or isolation mode is supported".to_string(),
+                    ));
+                }
+                let read_only = self.check_access_mode(planner::extract_transaction_read_only(modes))?;
+                // As in Postgres, outside a transaction block there is no
+                // transaction for the modes to apply to, so they do nothing
+                if session.in_transaction
+                    && let Some(read_only) = read_only
+                {
+                    session.read_only = read_only;
+                }
+                Ok(Response::Execution(Tag::new("SET")))
+            }
+            Statement::Set(sqlparser::ast::Set::SingleAssignment { scope, hivevar: false, variable, values })
+                if variable.to_string().eq_ignore_ascii_case("workload_class") =>
+            {
+                debug!("executing: set workload_class");
+                session.workload_class = planner::extract_workload_class(scope.as_ref(), values)?;
+                Ok(Response::Execution(Tag::new("SET")))
+            }
+            Statement::Set(
+                sqlparser::ast::Set::SingleAssignment { hivevar: false, .. }
+                | sqlparser::ast::Set::SetTimeZone { .. }
+                | sqlparser::ast::Set::SetNames { .. },
+            )
+            | Statement::ShowVariable { .. } => self.execute_setting(stmt, session),
+            Statement::CreateDatabase { .. }
+            | Statement::Drop { object_type: sqlparser::ast::ObjectType::Database, .. } => {
+                self.execute_database_ddl(stmt)
+            }
+            Statement::CreateSchema { .. }
+            | Statement::Drop { object_type: sqlparser::ast::ObjectType::Schema, .. } => {
+                self.execute_schema_ddl(stmt)
+            }
+            Statement::Rollback { .. } => {
+                debug!("executing: rollback");
+                self.rollback_writes(session);
+                session.end_transaction();
+                Ok(Response::TransactionEnd(Tag::new("ROLLBACK")))
+            }
+            Statement::Commit { .. } => {
+                debug!("executing: commit");
+                // A block a statement failed in was rolled back then
+                let failed = session.failed;
+                session.end_transaction();
+                if failed {
+                    return Ok(Response::TransactionEnd(Tag::new("ROLLBACK")));
+                }
+                self.commit_writes(session)?;
+                Ok(Response::TransactionEnd(Tag::new("COMMIT")))
+            }
+            Statement::Declare { stmts } => {
+                debug!("executing: declare cursor");
+                let (cursor_name, query, hold) = planner::extract_declare_cursor(stmts)?;
+                if !hold && !session.in_transaction {
+                    return Err(ExecutorError::Execution(
+                        "DECLARE CURSOR can only be used in transaction blocks".to_string(),
+                    ));
+                }
+                if session.cursors.contains_key(&cursor_name) {
+                    return Err(ExecutorError::Execution(format!("Cursor already exists: {}", cursor_name)));
+                }
+
+                let plan = planner::plan(&Statement::Query(query))?;
+                let (rows, schema) = self.execute_plan_with_schema(plan)?;
+                debug!(cursor = %cursor_name, rows = rows.len(), hold, "cursor declared");
+                session.cursors.insert(cursor_name, Cursor::new(rows, schema, hold));
+                Ok(Response::Execution(Tag::new("DECLARE CURSOR")))
+            }
+            Statement::Fetch { name, direction, .. } => {
+                debug!("executing: fetch");
+                let count = planner::extract_fetch_count(direction)?;
+                let cursor = session.cursors.get_mut(&name.value)
+                    .ok_or_else(|| ExecutorError::Execution(format!("Cursor not found: {}", name.value)))?;
+                let rows = cursor.fetch(count);
+                debug!(cursor = %name.value, rows = rows.len(), "fetched from cursor");
+                self.rows_to_response(rows, cursor.schema.clone(), session.result_format.as_ref())
+            }
+            Statement::Close { cursor } => {
+                debug!("executing: close cursor");
+                match cursor {
+                    sqlparser::ast::CloseCursor::All => session.cursors.clear(),
+                    sqlparser::ast::CloseCursor::Specific { name } => {
+                        session.cursors.remove(&name.value)
+                            .ok_or_else(|| ExecutorError::Execution(format!("Cursor not found: {}", name.value)))?;
+                    }
+                }
+                Ok(Response::Execution(Tag::new("CLOSE CURSOR")))
+            }
+            Statement::Prepare { name, data_types, statement } => {
+                debug!("executing: prepare");
+                let param_count = planner::extract_prepare(data_types, statement)?;
+                let evicted = session.prepared.prepare(&name.value, (**statement).clone(), param_count, self.prepared_limits)
+                    .map_err(ExecutorError::ResourceExhausted)?;
+                if !evicted.is_empty() {
+                    info!(evicted = ?evicted, "evicted least recently used prepared statements");
+                }
+                debug!(name = %name.value, param_count, "statement prepared");
+                Ok(Response::Execution(Tag::new("PREPARE")))
+            }
+            Statement::Execute { name, parameters, immediate, into, using, .. } => {
+                debug!("executing: execute");
+                let (statement_name, args) = planner::extract_execute(name.as_ref(), parameters, *immediate, into, using)?;
+                let (mut prepared, param_count) = session.prepared.get(&statement_name)
+                    .ok_or_else(|| ExecutorError::Execution(format!(
+                        "prepared statement \"{}\" does not exist",
+                        statement_name
+                    )))?;
+                if args.len() != param_count {
+                    return Err(ExecutorError::Execution(format!(
+                        "wrong number of parameters for prepared statement \"{}\": expected {}, got {}",
+                        statement_name, param_count, args.len()
+                    )));
+                }
+
+                // Arguments are evaluated once and bound as literals
+                let mut values = Vec::with_capacity(args.len());
+                for arg in &args {
+                    let arg = self.inline_sql_functions(arg)?;
+                    let value = match self.eval_sequence_function(&arg)? {
+                        Some(value) => value,
+                        None => evaluator::eval_expr(&arg, &Row::new(vec![]), &Schema::new(Vec::new()))?,
+                    };
+                    values.push(value);
+                }
+                prepared::bind_parameters(&mut prepared, values)?;
+
+                debug!(name = %statement_name, "executing prepared statement");
+                self.execute_statement(&prepared, session, call_depth)
+            }
+            Statement::Deallocate { name, .. } => {
+                debug!("executing: deallocate");
+                if name.quote_style.is_none() && name.value.eq_ignore_ascii_case("all") {
+                    session.prepared.deallocate_all();
+                    return Ok(Response::Execution(Tag::new("DEALLOCATE ALL")));
+                }
+                if !session.prepared.deallocate(&name.value) {
+                    return Err(ExecutorError::Execution(format!(
+                        "prepared statement \"{}\" does not exist",
+                        name.value
+                    )));
+                }
+                Ok(Response::Execution(Tag::new("DEALLOCATE")))
+            }
+            Statement::CreateTable(ct) => {
+                debug!("executing: create table");
+                let (table_name, schema, _primary_key_col) = planner::extract_create_table(ct)?;
+                if ct.temporary || temp::in_temp_schema(&table_name).is_some() {
+                    self.create_temp_table(ct, table_name, schema, session)?;
+                    return Ok(Response::EmptyQuery);
+                }
+                let options = planner::extract_create_table_options(ct)?;
+                let checks = planner::extract_create_table_checks(ct, &table_name, &schema)?;
+                let foreign_keys = planner::extract_create_table_foreign_keys(ct, &table_name)?;
+                let serials = planner::extract_create_table_serials(ct)?;
+                let mut db = self.db.write();
+                // Checked under the lock the table is created under, so scripts
+                // run concurrently can't both find it missing
+                if ct.if_not_exists && (db.get_table(&table_name).is_ok() || db.sequence_exists(&table_name)) {
+                    debug!(table = %table_name, "relation already exists, skipping");
+                } else {
+                    db.create_table(table_name.clone(), schema, options, checks, foreign_keys, serials)
+                        .map_err(ExecutorError::Execution)?;
+                    debug!(table = %table_name, "table created");
+                }
+                Ok(Response::EmptyQuery)
+            }
+            Statement::Insert(ins) => {
+                debug!("executing: insert");
+                let (table_name, columns, row_exprs) = planner::extract_insert(ins)?;
+                let on_conflict = planner::extract_on_conflict(ins)?
+                    .map(|on_conflict| self.inline_on_conflict(on_conflict))
+                    .transpose()?;
+
+                // Get the schema from the table
+                let db = self.db.read();
+                let schema = db.get_schema(&table_name)
+                    .map_err(ExecutorError::Execution)?;
+                let serials: Vec<(usize, String)> = db.serial_columns(&table_name)
+                    .map_err(ExecutorError::Execution)?
+                    .into_iter()
+                    .filter_map(|(column, sequence)| Some((schema.get_column_index(&column)?, sequence)))
+                    .collect();
+                drop(db);
+                let targets = Self::insert_targets(&table_name, &schema, &columns)?;
+
+                // Evaluate each row of expressions into the columns it targets;
+                // the other columns, and any given DEFAULT, are NULL, except
+                // that SERIAL columns take the next value of their sequence
+                let mut rows_to_insert = Vec::new();
+                for row_exprs_for_row in row_exprs {
+                    if row_exprs_for_row.len() > targets.len() {
+                        return Err(ExecutorError::Parse("INSERT has more expressions than target columns".to_string()));
+                    }
+                    if !columns.is_empty() && row_exprs_for_row.len() < targets.len() {
+                        return Err(ExecutorError::Parse("INSERT has more target columns than expressions".to_string()));
+                    }
+                    let mut values = vec![Value::Null; schema.len()];
+                    let mut given = vec![false; schema.len()];
+                    // Create an empty row for schema context (INSERT doesn't reference existing columns)
+                    let empty_row = Row::new(vec![]);
+                    for (expr, &target) in row_exprs_for_row.iter().zip(&targets) {
+                        let Some(expr) = expr else {
+                            continue;
+                        };
+                        let expr = &self.inline_sql_functions(expr)?;
+                        values[target] = match self.eval_sequence_function(expr)? {
+                            Some(val) => val,
+                            None => evaluator::eval_expr(expr, &empty_row, &schema)?,
+                        };
+                        given[target] = true;
+                    }
+                    for (col_idx, sequence) in serials.iter().filter(|(col_idx, _)| !given[*col_idx]) {
+                        let value = self.db.write().nextval(sequence)
+                            .map_err(ExecutorError::Execution)?;
+                        values[*col_idx] = Value::Int(value);
+                    }
+                    rows_to_insert.push(Row::new(values));
+                }
+
+                // Insert the rows as one batch so rows sharing a block are written together
+                let mut db = self.db.write();
+                for row in &mut rows_to_insert {
+                    typmod::apply(&db, &schema, row)?;
+                }
+                let (rows_to_insert, mut updates) = match &on_conflict {
+                    Some(on_conflict) => {
+                        let alias = ins.table_alias.as_ref().map(|alias| alias.value.as_str());
+                        let resolution = upsert::resolve(&db, &table_name, alias, &schema, on_conflict, rows_to_insert)?;
+                        (resolution.inserts, resolution.updates)
+                    }
+                    None => (rows_to_insert, Vec::new()),
+                };
+                for (_, _, new_row) in &mut updates {
+                    typmod::apply(&db, &schema, new_row)?;
+                }
+
+                // Every row is checked before any is written
+                let checks = Self::table_checks(&db, &table_name)?;
+                let updated_rows: Vec<Row> = updates.iter().map(|(_, _, new_row)| new_row.clone()).collect();
+                for row in rows_to_insert.iter().chain(&updated_rows) {
+                    Self::check_row(&table_name, &checks, row, &schema)?;
+                }
+                referential::check_references(&db, &table_name, &schema, &rows_to_insert)?;
+                referential::check_references(&db, &table_name, &schema, &updated_rows)?;
+                let changes: Vec<(Row, Row)> = updates.iter()
+                    .map(|(_, old_row, new_row)| (old_row.clone(), new_row.clone()))
+                    .collect();
+                referential::check_key_changes(&db, &table_name, &schema, &changes)?;
+
+                let inserted = db.insert_rows(&table_name, rows_to_insert)
+                    .map_err(ExecutorError::Execution)?;
+                debug!(table = %table_name, rows = inserted, "rows inserted");
+                if !updates.is_empty() {
+                    let updates = updates.into_iter().map(|(tuple_ptr, _, new_row)| (tuple_ptr, new_row)).collect();
+                    let updated = db.update_rows(&table_name, updates)
+                        .map_err(ExecutorError::Execution)?;
+                    debug!(table = %table_name, rows = updated, "conflicting rows updated");
+                }
+                Ok(Response::EmptyQuery)
+            }
+            Statement::Copy { source, to, target, options, legacy_options, values } => {
+                debug!("executing: copy");
+                let relation = planner::extract_copy(source, *to, target)?;
+                let options = CopyOptions::from_statement(options, legacy_options)?;
+                if *to {
+                    return self.copy_to(relation, options, session);
+                }
+                let planner::CopyRelation::Table { name: table_name, columns } = relation else {
+                    return Err(ExecutorError::Parse("COPY FROM requires a table".to_string()));
+                };
+                // sqlparser reads whatever follows the COPY as its data
+                if values.iter().any(|value| value.as_ref().is_none_or(|value| !value.trim().is_empty())) {
+                    return Err(ExecutorError::UnsupportedStatement(
+                        "COPY FROM STDIN must be the last statement of a query, with its data sent separately".to_string(),
+                    ));
+                }
+
+                let db = self.db.read();
+                let schema = db.get_schema(&table_name)
+                    .map_err(ExecutorError::Execution)?;
+                let serials: Vec<(usize, String)> = db.serial_columns(&table_name)
+                    .map_err(ExecutorError::Execution)?
+                    .into_iter()
+                    .filter_map(|(column, sequence)| Some((schema.get_column_index(&column)?, sequence)))
+                    .collect();
+                drop(db);
+                let targets = Self::insert_targets(&table_name, &schema, &columns)?;
+                let serials = serials.into_iter().filter(|(col_idx, _)| !targets.contains(col_idx)).collect();
+
+                // The rows arrive after this response, through copy_data
+                let column_count = targets.len();
+                session.copy_in = Some(CopyIn::new(table_name, schema, targets, serials, options));
+                Ok(Response::CopyIn(CopyResponse::new(0, column_count, vec![0; column_count])))
+            }
+            Statement::CreateIndex(ci) => {
+                debug!("executing: create index");
+                let (table_name, columns, index_type, descending) = planner::extract_create_index(ci)?;
+
+                // Extract index name from the CREATE INDEX statement
+                let index_name = ci.name.as_ref()
+                    .map(|name| name.0.iter()
+                        .filter_map(|part| part.as_ident())
+                        .map(|ident| ident.value.clone())
+                        .collect::<Vec<_>>()
+                        .join("."))
+                    .unwrap_or_else(|| format!("idx_{}", table_name));
+
+                // Build under an upgradable read, which keeps out writers but
+                // lets queries (and pg_stat_progress_create_index) run meanwhile
+                // Only one is held at a time, so no other CREATE INDEX can add
+                // the index between this check and the add below
+                let db = self.db.upgradable_read();
+                if ci.if_not_exists && db.index_exists(&index_name) {
+                    debug!(index_name = %index_name, "index already exists, skipping");
+                    return Ok(Response::EmptyQuery);
+                }
+                let built = db
+                    .build_secondary_index(
+                        index_name.clone(),
+                        table_name.clone(),
+                        columns.clone(),
+                        index_type.clone(),
+                        descending,
+                    )
+                    .map_err(ExecutorError::Execution)?;
+                parking_lot::RwLockUpgradableReadGuard::upgrade(db)
+                    .add_secondary_index(built)
+                    .map_err(ExecutorError::Execution)?;
+
+                debug!(table = %table_name, columns = ?columns, index_type = %index_type, index_name = %index_name, descending, "secondary index created");
+                Ok(Response::EmptyQuery)
+            }
+            Statement::AlterTable { name, if_exists, operations, .. } => {
+                debug!("executing: alter table");
+                let (table_name, action) = planner::extract_alter_table(name, operations)?;
+                let mut db = self.db.write();
+                if *if_exists && db.get_table(&table_name).is_err() {
+                    debug!(table = %table_name, "table does not exist, skipping");
+                } else {
+                    match &action {
+                        planner::AlterTableAction::RenameTable(new_name) => db.rename_table(&table_name, new_name),
+                        planner::AlterTableAction::RenameColumn { old, new } => {
+                            let checks = db.table_checks(&table_name)
+                                .map_err(ExecutorError::Execution)?;
+                            let checks = planner::rename_check_column(&checks, old, new)?;
+                            db.rename_column(&table_name, old, new, checks)
+                        }
+                        planner::AlterTableAction::SetOptions(options) => db.set_table_options(&table_name, options.clone()),
+                    }
+                    .map_err(ExecutorError::Execution)?;
+                }
+                Ok(Response::Execution(Tag::new("ALTER TABLE")))
+            }
+            Statement::Comment { object_type, object_name, comment, if_exists } => {
+                debug!("executing: comment");
+                let target = planner::extract_comment(object_type, object_name)?;
+                let mut db = self.db.write();
+                let table_name = match &target {
+                    planner::CommentTarget::Table(table) => table,
+                    planner::CommentTarget::Column { table, .. } => table,
+                };
+                if *if_exists && db.get_table(table_name).is_err() {
+                    debug!(table = %table_name, "comment target does not exist, skipping");
+                } else {
+                    match &target {
+                        planner::CommentTarget::Table(table) => db.set_table_comment(table, comment.clone()),
+                        planner::CommentTarget::Column { table, column } => db.set_column_comment(table, column, comment.clone()),
+                    }
+                    .map_err(ExecutorError::Execution)?;
+                }
+                Ok(Response::Execution(Tag::new("COMMENT")))
+            }
+            Statement::CreateSequence { temporary, if_not_exists, name, data_type, sequence_options, owned_by } => {
+                debug!("executing: create sequence");
+                let (sequence_name, options) = planner::extract_create_sequence(
+                    name,
+                    data_type.as_ref(),
+                    sequence_options,
+                    *temporary,
+                    owned_by.as_ref(),
+                )?;
+                let mut db = self.db.write();
+                if *if_not_exists && db.sequence_exists(&sequence_name) {
+                    debug!(sequence = %sequence_name, "sequence already exists, skipping");
+                } else {
+                    db.create_sequence(sequence_name, options)
+                        .map_err(ExecutorError::Execution)?;
+                }
+                Ok(Response::Execution(Tag::new("CREATE SEQUENCE")))
+            }
+            Statement::Drop { object_type: sqlparser::ast::ObjectType::Table, if_exists, names, .. } => {
+                debug!("executing: drop table");
+                let table_names = planner::extract_drop_table(names)?;
+                let mut db = self.db.write();
+                for table_name in table_names {
+                    let table_name = temp::in_temp_schema(&table_name).unwrap_or(&table_name);
+                    match session.temp_tables.get(table_name) {
+                        Some(stored_name) => {
+                            db.drop_temp_table(stored_name)
+                                .map_err(ExecutorError::Execution)?;
+                            session.temp_tables.remove(table_name);
+                        }
+                        None if *if_exists && db.get_table(table_name).is_err() => {
+                            debug!(table = %table_name, "table does not exist, skipping");
+                        }
+                        None => {
+                            db.get_table(table_name)
+                                .map_err(ExecutorError::Execution)?;
+                            return Err(ExecutorError::UnsupportedStatement(
+                                "DROP TABLE is only supported for temporary tables".to_string(),
+                            ));
+                        }
+                    }
+                }
+                Ok(Response::Execution(Tag::new("DROP TABLE")))
+            }
+            Statement::Drop { object_type: sqlparser::ast::ObjectType::Sequence, if_exists, names, .. } => {
+                debug!("executing: drop sequence");
+                let sequence_names = planner::extract_drop_sequence(names)?;
+                let mut db = self.db.write();
+                for sequence_name in sequence_names {
+                    if *if_exists && !db.sequence_exists(&sequence_name) {
+                        debug!(sequence = %sequence_name, "sequence does not exist, skipping");
+                        continue;
+                    }
+                    db.drop_sequence(&sequence_name)
+                        .map_err(ExecutorError::Execution)?;
+                }
+                Ok(Response::Execution(Tag::new("DROP SEQUENCE")))
+            }
+            Statement::CreateFunction(cf) => {
+                debug!("executing: create function");
+                let (function_meta, or_replace) = planner::extract_create_function(cf)?;
+                if BUILTIN_FUNCTIONS.contains(&function_meta.name.to_lowercase().as_str())
+                    || builtins::lookup(&function_meta.name).is_some()
+                {
+                    return Err(ExecutorError::Execution(format!(
+                        "Function {} is built in and cannot be redefined",
+                        function_meta.name
+                    )));
+                }
+                let mut db = self.db.write();
+                if cf.if_not_exists && db.get_function(&function_meta.name).is_some() {
+                    debug!(function = %function_meta.name, "function already exists, skipping");
+                } else {
+                    db.create_function(function_meta, or_replace)
+                        .map_err(ExecutorError::Execution)?;
+                }
+                Ok(Response::Execution(Tag::new("CREATE FUNCTION")))
+            }
+            Statement::DropFunction { if_exists, func_desc, .. } => {
+                debug!("executing: drop function");
+                let function_names = planner::extract_drop_function(func_desc)?;
+                let mut db = self.db.write();
+                for function_name in function_names {
+                    if *if_exists && db.get_function(&function_name).is_none() {
+                        debug!(function = %function_name, "function does not exist, skipping");
+                        continue;
+                    }
+                    db.drop_function(&function_name)
+                        .map_err(ExecutorError::Execution)?;
+                }
+                Ok(Response::Execution(Tag::new("DROP FUNCTION")))
+            }
+            Statement::CreateProcedure { or_alter, name, params, language, body } => {
+                debug!("executing: create procedure");
+                let (procedure_meta, or_replace) = planner::extract_create_procedure(
+                    *or_alter,
+                    name,
+                    params.as_deref(),
+                    language.as_ref(),
+                    body,
+                )?;
+                self.db.write()
+                    .create_procedure(procedure_meta, or_replace)
+                    .map_err(ExecutorError::Execution)?;
+                Ok(Response::Execution(Tag::new("CREATE PROCEDURE")))
+            }
+            Statement::DropProcedure { if_exists, proc_desc, .. } => {
+                debug!("executing: drop procedure");
+                let procedure_names = planner::extract_drop_procedure(proc_desc)?;
+                let mut db = self.db.write();
+                for procedure_name in procedure_names {
+                    if *if_exists && db.get_procedure(&procedure_name).is_none() {
+                        debug!(procedure = %procedure_name, "procedure does not exist, skipping");
+                        continue;
+                    }
+                    db.drop_procedure(&procedure_name)
+                        .map_err(ExecutorError::Execution)?;
+                }
+                Ok(Response::Execution(Tag::new("DROP PROCEDURE")))
+            }
+            Statement::Call(func) => {
+                debug!("executing: call");
+                self.call_procedure(func, session, call_depth)?;
+                Ok(Response::Execution(Tag::new("CALL")))
+            }
+            Statement::Vacuum(vacuum) => {
+                debug!("executing: vacuum");
+                let mut db = self.db.write();
+                let tables = match planner::extract_vacuum(vacuum)? {
+                    Some(table_name) => vec![table_name],
+                    None => db.table_names(),
+                };
+                for table_name in tables {
+                    let stats = db.compact_table(&table_name)
+                        .map_err(ExecutorError::Execution)?;
+                    info!(table = %table_name, live_tuples = stats.live_tuples, blocks_before = stats.blocks_before, blocks_after = stats.blocks_after, "table compacted");
+                }
+                Ok(Response::Execution(Tag::new("VACUUM")))
+            }
+            Statement::Truncate { table_names, partitions, .. } => {
+                debug!("executing: truncate");
+                let tables = planner::extract_truncate(table_names, partitions.as_deref())?;
+                let mut db = self.db.write();
+                // Every table is checked before any is emptied
+                for table_name in &tables {
+                    db.get_table(table_name)
+                        .map_err(ExecutorError::Execution)?;
+                }
+                referential::check_truncate(&db, &tables)?;
+                for table_name in &tables {
+                    db.truncate_table(table_name)
+                        .map_err(ExecutorError::Execution)?;
+                    info!(table = %table_name, "table truncated");
+                }
+                Ok(Response::Execution(Tag::new("TRUNCATE TABLE")))
+            }
+            Statement::Explain { analyze, statement, format, options, .. } => {
+                debug!("executing: explain");
+                self.explain(statement, *analyze, format.as_ref(), options.as_deref(), session.result_format.as_ref())
+            }
+            _ => {
+                // psql's meta-commands query the catalogs in ways the planner
+                // can't run; those queries are answered from the system views
+                let answer = match stmt {
+                    Statement::Query(query) => psql::answer(query, &self.db.read()),
+                    _ => None,
+                };
+                if let Some((schema, rows)) = answer {
+                    debug!(rows = rows.len(), "answered catalog query");
+                    return self.rows_to_response(rows, Some(schema), session.result_format.as_ref());
+                }
+
+                let stmt = self.eval_advisory_lock_functions(stmt, session)?;
+                let plan = planner::plan(&stmt)?;
+                debug!(plan = ?planner::cost::estimate(&plan, &*self.db.read()), "executing plan");
+                self.execute_plan(plan, session.result_format.as_ref())
+            }
+        }
+    }
+
+    /// Set or show one of the session's run-time parameters (see `settings`)
+    fn execute_setting(&self, stmt: &Statement, session: &mut Session) -> Result<Response> {
+        use sqlparser::ast::{ContextModifier, Set};
+
+        match stmt {
+            Statement::Set(Set::SingleAssignment { scope, variable, values, .. }) => {
+                debug!("executing: set");
+                settings::set(session, scope.as_ref(), &settings::set_name(variable), settings::value_text(values))?;
+            }
+            Statement::Set(Set::SetTimeZone { local, value }) => {
+                debug!("executing: set time zone");
+                let scope = local.then_some(ContextModifier::Local);
+                settings::set(session, scope.as_ref(), "timezone", settings::value_text(std::slice::from_ref(value)))?;
+            }
+            Statement::Set(Set::SetNames { charset_name, .. }) => {
+                debug!("executing: set names");
+                settings::set(session, None, "client_encoding", Some(charset_name.value.clone()))?;
+            }
+            Statement::ShowVariable { variable } => {
+                debug!("executing: show");
+                let (schema, rows) = settings::show(session, &settings::show_name(variable))?;
+                return self.rows_to_response(rows, Some(schema), session.result_format.as_ref());
+            }
+            _ => return Err(ExecutorError::UnsupportedStatement(format!("Unsupported statement: {}", stmt))),
+        }
+        Ok(Response::Execution(Tag::new("SET")))
+    }
+
+    /// CREATE DATABASE and DROP DATABASE, which change the server's set of
+    /// databases rather than this one (see `cluster`)
+    fn execute_database_ddl(&self, stmt: &Statement) -> Result<Response> {
+        let command = match stmt {
+            Statement::CreateDatabase { .. } => "CREATE DATABASE",
+            _ => "DROP DATABASE",
+        };
+        let cluster = self.cluster.upgrade()
+            .ok_or_else(|| ExecutorError::UnsupportedStatement(format!("{} is only supported by the server", command)))?;
+        let database_name = |name: &sqlparser::ast::ObjectName| match name.0.as_slice() {
+            [part] => part.as_ident()
+                .map(|ident| ident.value.clone())
+                .ok_or_else(|| ExecutorError::Parse(format!("invalid database name: {}", name))),
+            _ => Err(ExecutorError::Parse(format!("invalid database name: {}", name))),
+        };
+
+        match stmt {
+            Statement::CreateDatabase { db_name, if_not_exists, location, managed_location, .. } => {
+                debug!("executing: create database");
+                if location.is_some() || managed_location.is_some() {
+                    return Err(ExecutorError::UnsupportedStatement(
+                        "CREATE DATABASE with a location is not supported".to_string(),
+                    ));
+                }
+                let name = database_name(db_name)?;
+                if !cluster.create_database(&name, *if_not_exists)? {
+                    debug!(database = %name, "database already exists, skipping");
+                }
+            }
+            Statement::Drop { names, if_exists, .. } => {
+                debug!("executing: drop database");
+                for name in names {
+                    let name = database_name(name)?;
+                    if !cluster.drop_database(&name, &self.database, *if_exists)? {
+                        debug!(database = %name, "database does not exist, skipping");
+                    }
+                }
+            }
+            _ => return Err(ExecutorError::UnsupportedStatement(format!("Unsupported statement: {}", stmt))),
+        }
+        Ok(Response::Execution(Tag::new(command)))
+    }
+
+    /// CREATE SCHEMA and DROP SCHEMA (see `schema`)
+    fn execute_schema_ddl(&self, stmt: &Statement) -> Result<Response> {
+        let schema_name = |name: &sqlparser::ast::ObjectName| match name.0.as_slice() {
+            [part] => part.as_ident()
+                .map(|ident| ident.value.clone())
+                .ok_or_else(|| ExecutorError::Parse(format!("invalid schema name: {}", name))),
+            _ => Err(ExecutorError::Parse(format!("invalid schema name: {}", name))),
+        };
+
+        match stmt {
+            Statement::CreateSchema { schema_name: sqlparser::ast::SchemaName::Simple(name), if_not_exists, .. } => {
+                debug!("executing: create schema");
+                let name = schema_name(name)?;
+                if schema::is_reserved(&name) || name.contains('.') {
+                    return Err(ExecutorError::UnsupportedStatement(format!("unacceptable schema name \"{}\"", name)));
+                }
+                let mut db = self.db.write();
+                if db.schema_exists(&name) {
+                    if *if_not_exists {
+                        debug!(schema = %name, "schema already exists, skipping");
+                        return Ok(Response::Execution(Tag::new("CREATE SCHEMA")));
+                    }
+                    return Err(ExecutorError::DuplicateSchema(format!("schema \"{}\" already exists", name)));
+                }
+                db.create_schema(name)
+                    .map_err(ExecutorError::Execution)?;
+                Ok(Response::Execution(Tag::new("CREATE SCHEMA")))
+            }
+            Statement::CreateSchema { .. } => Err(ExecutorError::UnsupportedStatement(
+                "CREATE SCHEMA with AUTHORIZATION is not supported".to_string(),
+            )),
+            Statement::Drop { names, if_exists, .. } => {
+                debug!("executing: drop schema");
+                let mut db = self.db.write();
+                for name in names {
+                    let name = schema_name(name)?;
+                    if name == crate::storage::system::PUBLIC_SCHEMA {
+                        return Err(ExecutorError::UnsupportedStatement(
+                            "schema \"public\" cannot be dropped".to_string(),
+                        ));
+                    }
+                    if !db.schema_exists(&name) {
+                        if *if_exists {
+                            debug!(schema = %name, "schema does not exist, skipping");
+                            continue;
+                        }
+                        return Err(ExecutorError::InvalidSchema(format!("schema \"{}\" does not exist", name)));
+                    }
+                    db.drop_schema(&name)
+                        .map_err(ExecutorError::Execution)?;
+                }
+                Ok(Response::Execution(Tag::new("DROP SCHEMA")))
+            }
+            _ => Err(ExecutorError::UnsupportedStatement(format!("Unsupported statement: {}", stmt))),
+        }
+    }
+
+    /// Create a temporary table for the session (see `temp`)
+    fn create_temp_table(&self, ct: &sqlparser::ast::CreateTable, table_name: String, schema: Schema, session: &mut Session) -> Result<()> {
+        if ct.on_commit.is_some_and(|on_commit| on_commit != sqlparser::ast::OnCommit::PreserveRows) {
+            return Err(ExecutorError::UnsupportedStatement(
+                "Only ON COMMIT PRESERVE ROWS is supported for temporary tables".to_string(),
+            ));
+        }
+        let table_name = temp::in_temp_schema(&table_name).map_or(table_name.clone(), str::to_string);
+        if session.temp_tables.contains_key(&table_name) {
+            if ct.if_not_exists {
+                debug!(table = %table_name, "temporary table already exists, skipping");
+                return Ok(());
+            }
+            return Err(ExecutorError::Execution(format!("Table already exists: {}", table_name)));
+        }
+        let temp_schema = *session.temp_schema
+            .get_or_insert_with(|| self.temp_schemas.fetch_add(1, Ordering::SeqCst) + 1);
+        let stored_name = temp::stored_name(temp_schema, &table_name);
+
+        let options = planner::extract_create_table_options(ct)?;
+        let checks = planner::extract_create_table_checks(ct, &table_name, &schema)?;
+        // Foreign keys may reference the session's temporary tables, this one
+        // included, by the names the session gave them
+        let foreign_keys = planner::extract_create_table_foreign_keys(ct, &table_name)?
+            .into_iter()
+            .map(|mut foreign_key| {
+                if foreign_key.referenced_table == table_name {
+                    foreign_key.referenced_table = stored_name.clone();
+                } else if let Some(referenced) = session.temp_tables.get(&foreign_key.referenced_table) {
+                    foreign_key.referenced_table = referenced.clone();
+                }
+                foreign_key
+            })
+            .collect();
+        let serials = planner::extract_create_table_serials(ct)?;
+        self.db.write()
+            .create_temp_table(stored_name.clone(), schema, options, checks, foreign_keys, serials)
+            .map_err(ExecutorError::Execution)?;
+        debug!(table = %table_name, stored_name = %stored_name, "temporary table created");
+        session.temp_tables.insert(table_name, stored_name);
+        Ok(())
+    }
+
+    /// Check a requested transaction access mode (Some(true) for READ ONLY)
+    /// against the server's; a read-only server has no READ WRITE transactions
+    fn check_access_mode(&self, read_only: Option<bool>) -> Result<Option<bool>> {
+        if self.read_only.load(Ordering::SeqCst) && read_only == Some(false) {
+            return Err(ExecutorError::ReadOnly(
+                "cannot set transaction read-write mode while the server is read-only".to_string(),
+            ));
+        }
+        Ok(read_only)
+    }
+
+    /// Position in the schema of each column an INSERT's values go to, in
+    /// order: the named columns, or every column when none are named
+    fn insert_targets(table_name: &str, schema: &Schema, columns: &[String]) -> Result<Vec<usize>> {
+        if columns.is_empty() {
+            return Ok((0..schema.len()).collect());
+        }
+        let mut targets: Vec<usize> = Vec::with_capacity(columns.len());
+        for column in columns {
+            let target = schema.get_column_index(column).ok_or_else(|| ExecutorError::Execution(format!(
+                "column \"{}\" of relation \"{}\" does not exist",
+                column, table_name
+            )))?;
+            if targets.contains(&target) {
+                return Err(ExecutorError::Execution(format!("column \"{}\" specified more than once", column)));
+            }
+            targets.push(target);
+        }
+        Ok(targets)
+    }
+
+    /// CHECK constraints of a table, parsed, with their names
+    fn table_checks(db: &Database, table_name: &str) -> Result<Vec<(String, sqlparser::ast::Expr)>> {
+        db.table_checks(table_name)
+            .map_err(ExecutorError::Execution)?
+            .into_iter()
+            .map(|check| Ok((check.name, parser::parse_expr(&check.expr)?)))
+            .collect()
+    }
+
+    /// Reject a row that one of its table's CHECK constraints evaluates to
+    /// false for; as in Postgres, a constraint that evaluates to NULL passes
+    fn check_row(table_name: &str, checks: &[(String, sqlparser::ast::Expr)], row: &Row, schema: &Schema) -> Result<()> {
+        for (name, expr) in checks {
+            match evaluator::eval_expr(expr, row, schema)? {
+                Value::Bool(true) | Value::Null => {}
+                Value::Bool(false) => return Err(ExecutorError::CheckViolation(format!(
+                    "new row for relation \"{}\" violates check constraint \"{}\"",
+                    table_name, name
+                ))),
+                other => return Err(ExecutorError::Execution(format!(
+                    "check constraint \"{}\" must evaluate to a boolean, got {:?}",
+                    name, other
+                ))),
+            }
+        }
+        Ok(())
+    }
+
+    /// EXPLAIN: the plan of a statement with each node's estimated rows and
+    /// cost, one line per row of a QUERY PLAN column as in Postgres
+    /// The statement is planned but not run, so ANALYZE is not supported;
+    /// COSTS OFF leaves out the estimates
+    fn explain(
+        &self,
+        statement: &Statement,
+        analyze: bool,
+        format: Option<&sqlparser::ast::AnalyzeFormatKind>,
+        options: Option<&[sqlparser::ast::UtilityOption]>,
+        result_format: Option<&Format>,
+    ) -> Result<Response> {
+        if analyze {
+            return Err(ExecutorError::UnsupportedStatement("EXPLAIN ANALYZE is not supported".to_string()));
+        }
+        if let Some(format) = format {
+            return Err(ExecutorError::UnsupportedStatement(format!("EXPLAIN {} is not supported", format)));
+        }
+        let mut costs = true;
+        for option in options.unwrap_or_default() {
+            let enabled = || match &option.arg {
+                None => Ok(true),
+                Some(sqlparser::ast::Expr::Value(value)) => match &value.value {
+                    sqlparser::ast::Value::Boolean(enabled) => Ok(*enabled),
+                    sqlparser::ast::Value::Number(n, _) if n == "0" || n == "1" => Ok(n == "1"),
+                    _ => Err(()),
+                },
+                Some(sqlparser::ast::Expr::Identifier(ident)) => match ident.value.to_lowercase().as_str() {
+                    "on" | "true" => Ok(true),
+                    "off" | "false" => Ok(false),
+                    _ => Err(()),
+                },
+                Some(_) => Err(()),
+            }.map_err(|()| ExecutorError::Parse(format!("EXPLAIN option {} requires a Boolean value", option.name)));
+            match option.name.value.to_lowercase().as_str() {
+                "costs" => costs = enabled()?,
+                // Nothing more is known about a plan to show
+                "verbose" | "summary" => {
+                    enabled()?;
+                }
+                "analyze" if enabled()? => {
+                    return Err(ExecutorError::UnsupportedStatement("EXPLAIN ANALYZE is not supported".to_string()));
+                }
+                "analyze" => {}
+                "format" if matches!(&option.arg, Some(sqlparser::ast::Expr::Identifier(ident)) if ident.value.eq_ignore_ascii_case("text")) => {}
+                _ => {
+                    return Err(ExecutorError::UnsupportedStatement(format!("EXPLAIN option {} is not supported", option)));
+                }
+            }
         }
+
+        let plan = planner::plan(statement)?;
+        let lines = planner::cost::estimate(&plan, &*self.db.read()).explain_lines(costs);
+        let rows = lines.into_iter().map(|line| Row::new(vec![Value::String(line)])).collect();
+        let schema = Schema::new(vec![crate::types::Column {
+            name: "QUERY PLAN".to_string(),
+            data_type: DataType::String,
+            is_primary_key: false,
+            typmod: None,
+        }]);
+        self.rows_to_response(rows, Some(schema), result_format)
     }
 
-    pub fn execute(&self, query: &str) -> Result<Vec<Response>> {
-        debug!("parsing query");
-        let stmts = parser::parse(query)?;
+    /// Run a stored procedure's statements in order with its parameters bound
+    /// to the call's arguments
+    /// The first failing statement aborts the call and the rest are skipped;
+    /// storage has no rollback yet, so statements before it keep their effects
+    fn call_procedure(&self, func: &sqlparser::ast::Function, session: &mut Session, call_depth: usize) -> Result<()> {
+        let procedure_name = func.name.0.iter()
+            .filter_map(|part| part.as_ident())
+            .map(|ident| ident.value.clone())
+            .collect::<Vec<_>>()
+            .join(".");
+        let procedure_meta = self.db.read().get_procedure(&procedure_name)
+            .ok_or_else(|| ExecutorError::Execution(format!("Procedure not found: {}", procedure_name)))?;
+        if call_depth >= MAX_CALL_DEPTH {
+            return Err(ExecutorError::Execution(format!(
+                "Procedure {} nests deeper than {} calls; is it recursive?",
+                procedure_name, MAX_CALL_DEPTH
+            )));
+        }
 
-        if stmts.is_empty() {
-            debug!("empty query");
-            return Ok(vec![Response::EmptyQuery]);
+        // Arguments are evaluated once, before the body runs
+        let mut args = Vec::new();
+        for arg in evaluator::function_args(func)? {
+            let arg = self.inline_sql_functions(arg)?;
+            let value = match self.eval_sequence_function(&arg)? {
+                Some(value) => value,
+                None => evaluator::eval_expr(&arg, &Row::new(vec![]), &Schema::new(Vec::new()))?,
+            };
+            args.push(value);
+        }
+        if args.len() != procedure_meta.params.len() {
+            return Err(ExecutorError::Execution(format!(
+                "Procedure {} takes {} argument(s), got {}",
+                procedure_name,
+                procedure_meta.params.len(),
+                args.len()
+            )));
+        }
+        let mut bindings = Vec::new();
+        for ((param_name, data_type), value) in procedure_meta.params.iter().zip(args) {
+            let literal = match (data_type, &value) {
+                (_, Value::Null) => sqlparser::ast::Value::Null,
+                (crate::types::DataType::Int, Value::Int(n)) => sqlparser::ast::Value::Number(n.to_string(), false),
+                (crate::types::DataType::Float, Value::Int(n)) => sqlparser::ast::Value::Number(n.to_string(), false),
+                (crate::types::DataType::Float, Value::Float(f)) => sqlparser::ast::Value::Number(f.to_string(), false),
+                (crate::types::DataType::String, Value::String(text)) => sqlparser::ast::Value::SingleQuotedString(text.clone()),
+                (crate::types::DataType::Bool, Value::Bool(b)) => sqlparser::ast::Value::Boolean(*b),
+                _ => return Err(ExecutorError::Execution(format!(
+                    "Argument {} of procedure {} must be {:?}, got {:?}",
+                    param_name, procedure_name, data_type, value
+                ))),
+            };
+            bindings.push((param_name.clone(), sqlparser::ast::Expr::value(literal)));
         }
 
-        info!(statement_count = stmts.len(), "parsed statements");
+        for (idx, sql) in procedure_meta.body.iter().enumerate() {
+            for mut stmt in parser::parse(sql)? {
+                // Parameters are referenced by name or as $n
+                let _ = sqlparser::ast::visit_expressions_mut(&mut stmt, |expr| {
+                    let binding = match &*expr {
+                        sqlparser::ast::Expr::Identifier(ident) => bindings.iter()
+                            .find(|(param_name, _)| *param_name == ident.value),
+                        sqlparser::ast::Expr::Value(val) => match &val.value {
+                            sqlparser::ast::Value::Placeholder(placeholder) => placeholder.strip_prefix('$')
+                                .and_then(|position| position.parse::<usize>().ok())
+                                .and_then(|position| position.checked_sub(1))
+                                .and_then(|idx| bindings.get(idx)),
+                            _ => None,
+                        },
+                        _ => None,
+                    };
+                    if let Some((_, literal)) = binding {
+                        *expr = literal.clone();
+                    }
+                    std::ops::ControlFlow::<()>::Continue(())
+                });
 
-        let mut responses = Vec::new();
-        for (idx, stmt) in stmts.iter().enumerate() {
-            debug!(statement_idx = idx, "planning statement");
+                debug!(procedure = %procedure_name, statement_idx = idx, "executing procedure statement");
+                self.execute_statement(&stmt, session, call_depth + 1)?;
+            }
+        }
 
-            // Handle DDL/DML/transactions directly (not via planner)
-            let response = match stmt {
-                Statement::StartTransaction { .. } => {
-                    debug!("executing: start transaction");
-                    Ok(Response::TransactionStart(Tag::new("BEGIN")))
-                }
-                Statement::Rollback { .. } => {
-                    debug!("executing: rollback");
-                    Ok(Response::TransactionEnd(Tag::new("ROLLBACK")))
-                }
-                Statement::Commit { .. } => {
-                    debug!("executing: commit");
-                    Ok(Response::TransactionEnd(Tag::new("COMMIT")))
-                }
-                Statement::CreateTable(ct) => {
-                    debug!("executing: create table");
-                    let (table_name, schema, _primary_key_col) = planner::extract_create_table(ct)?;
-                    let mut db = self.db.write();
-                    db.create_table(table_name.clone(), schema)
-                        .map_err(|e| ExecutorError::Execution(e))?;
-                    debug!(table = %table_name, "table created");
-                    Ok(Response::EmptyQuery)
-                }
-                Statement::Insert(ins) => {
-                    debug!("executing: insert");
-                    let (table_name, row_exprs) = planner::extract_insert(ins)?;
+        info!(procedure = %procedure_name, statements = procedure_meta.body.len(), "procedure call complete");
+        Ok(())
+    }
 
-                    // Get the schema from the table
-                    let db = self.db.read();
-                    let schema = db.get_schema(&table_name)
-                        .map_err(|e| ExecutorError::Execution(e))?;
-                    drop(db);
+    fn execute_plan(&self, plan: Operator, result_format: Option<&Format>) -> Result<Response> {
+        if let Operator::Update { table, assignments, selection } = plan {
+            let updated = self.execute_update(&table, &assignments, selection.as_ref())?;
+            return Ok(Response::Execution(Tag::new("UPDATE").with_rows(updated)));
+        }
+        if let Operator::Delete { table, selection } = plan {
+            let deleted = self.execute_delete(&table, selection.as_ref())?;
+            return Ok(Response::Execution(Tag::new("DELETE").with_rows(deleted)));
+        }
 
-                    // Evaluate each row of expressions
-                    let mut rows_to_insert = Vec::new();
-                    for row_exprs_for_row in row_exprs {
-                        let mut values = Vec::new();
-                        // Create an empty row for schema context (INSERT doesn't reference existing columns)
-                        let empty_row = Row::new(vec![]);
-                        for expr in &row_exprs_for_row {
-                            let val = evaluator::eval_expr(expr, &empty_row, &schema)?;
-                            values.push(val);
-                        }
-                        rows_to_insert.push(Row::new(values));
-                    }
+        // Evaluate plan tree to get rows, then convert to Response
+        let (rows, schema) = self.execute_plan_with_schema(plan)?;
+        self.rows_to_response(rows, schema, result_format)
+    }
+
+    /// Encode rows as a query response, each column in the format asked for
+    /// in `result_format` (see `Session::result_format`)
+    fn rows_to_response(&self, rows: Vec<Row>, schema: Option<Schema>, result_format: Option<&Format>) -> Result<Response> {
+        // The extended query protocol describes the columns before the
+        // statement runs, so its results have them even when there are no rows
+        if rows.is_empty() && (result_format.is_none() || schema.is_none()) {
+            return Ok(Response::EmptyQuery);
+        }
+
+        let field_infos = match &schema {
+            Some(schema) => self.field_infos(schema, result_format),
+            // Fall back to generic names if no schema available
+            None => (0..rows[0].len())
+                .map(|i| FieldInfo::new(
+                    format!("?column?{}", i).into(),
+                    None,
+                    None,
+                    Type::INT8,
+                    wire::field_format(result_format, i),
+                ))
+                .collect(),
+        };
+        let schema = Arc::new(field_infos);
+        #[cfg(feature = "extensions")]
+        let type_registry = self.db.read().type_registry.clone();
 
-                    // Insert the rows
-                    let mut db = self.db.write();
-                    for row in rows_to_insert {
-                        db.insert_row(&table_name, row)
-                            .map_err(|e| ExecutorError::Execution(e))?;
+        // Encode rows, spooling them to disk if there are too many to hold
+        slowlog::count_rows(rows.len());
+        let mut encoded_rows = ResultSpool::new(&self.spool);
+        for row in rows {
+            let mut encoder = DataRowEncoder::new(schema.clone());
+            for (value, field) in row.values.iter().zip(schema.iter()) {
+                match value {
+                    #[cfg(feature = "extensions")]
+                    Value::Extension { type_oid, data } => {
+                        let type_ext = type_registry.get_by_oid(*type_oid)
+                            .ok_or_else(|| ExecutorError::Execution(format!("type with OID {} is not registered", type_oid)))?;
+                        let encoded = if field.format() == FieldFormat::Binary {
+                            let bytes = type_ext.send(data.as_ref())
+                                .map_err(ExecutorError::Execution)?;
+                            encoder.encode_field_with_type_and_format(&bytes, field.datatype(), FieldFormat::Binary, &Default::default())
+                        } else {
+                            encoder.encode_field(&type_ext.output(data.as_ref()))
+                        };
+                        encoded.map_err(|e| ExecutorError::Execution(format!("Encoding error: {:?}", e)))?;
                     }
-                    debug!(table = %table_name, "rows inserted");
-                    Ok(Response::EmptyQuery)
-                }
-                Statement::CreateIndex(ci) => {
-                    debug!("executing: create index");
-                    let (table_name, column_name, index_type) = planner::extract_create_index(ci)?;
-
-                    // Extract index name from the CREATE INDEX statement
-                    let index_name = ci.name.as_ref()
-                        .map(|name| name.0.iter()
-                            .filter_map(|part| part.as_ident())
-                            .map(|ident| ident.value.clone())
-                            .collect::<Vec<_>>()
-                            .join("."))
-                        .unwrap_or_else(|| format!("idx_{}", table_name));
-
-                    // Call database to create the secondary index
-                    self.db.write()
-                        .create_secondary_index(
-                            index_name.clone(),
-                            table_name.clone(),
-                            column_name.clone(), 
-                            index_type.clone(),
-                        )
-                        .map_err(|e| ExecutorError::Execution(e))?;
-
-                    debug!(table = %table_name, column = %column_name, index_type = %index_type, index_name = %index_name, "secondary index created");
-                    Ok(Response::EmptyQuery)
-                }
-                _ => {
-                    let plan = planner::plan(stmt)?;
-                    debug!(statement_idx = idx, plan = ?plan, "executing plan");
-                    self.execute_plan(plan)
+                    value => wire::encode_field(&mut encoder, value, field)?,
                 }
-            };
+            }
+            let encoded_row = encoder.finish()
+                .map_err(|e| ExecutorError::Execution(format!("Encoding error: {:?}", e)))?;
+            encoded_rows.push(encoded_row)
+                .map_err(|e| ExecutorError::Execution(format!("Failed to spool result: {}", e)))?;
+        }
+
+        let encoded_rows = encoded_rows.finish()
+            .map_err(|e| ExecutorError::Execution(format!("Failed to spool result: {}", e)))?;
+        let data_row_stream = stream::iter(encoded_rows);
+        Ok(Response::Query(QueryResponse::new(schema, data_row_stream)))
+    }
 
-            responses.push(response?);
+    /// Description of the columns of a result: each column's name and type,
+    /// and the format it is sent in
+    fn field_infos(&self, schema: &Schema, result_format: Option<&Format>) -> Vec<FieldInfo> {
+        schema.columns.iter().enumerate()
+            .map(|(idx, col)| {
+                let pgwire_type = match &col.data_type {
+                    DataType::Int => Type::INT8,
+                    DataType::Float => Type::FLOAT8,
+                    DataType::String => Type::VARCHAR,
+                    DataType::Bool => Type::BOOL,
+                    DataType::Null => Type::UNKNOWN,
+                    #[cfg(feature = "extensions")]
+                    DataType::Extension { type_oid, .. } => self.db.read().type_registry.get_by_oid(*type_oid)
+                        .map(|type_ext| type_ext.to_pgwire_type())
+                        .unwrap_or(Type::UNKNOWN),
+                    #[cfg(not(feature = "extensions"))]
+                    DataType::Extension { .. } => Type::UNKNOWN,
+                };
+                FieldInfo::new(col.name.clone().into(), None, None, pgwire_type, wire::field_format(result_format, idx))
+            })
+            .collect()
+    }
+
+    /// Apply an UPDATE: evaluate the predicate and assignments against each row
+    /// of the table, then write the changed rows back as one batch
+    /// The write lock is held from the scan to the write so no row changes in between
+    /// Returns the number of rows updated
+    fn execute_update(
+        &self,
+        table: &str,
+        assignments: &[(String, sqlparser::ast::Expr)],
+        selection: Option<&sqlparser::ast::Expr>,
+    ) -> Result<usize> {
+        debug!(table = %table, "executing update");
+        let schema = self.db.read().get_schema(table)
+            .map_err(ExecutorError::Execution)?;
+
+        let assignments = assignments.iter()
+            .map(|(column, expr)| {
+                let col_idx = schema.get_column_index(column)
+                    .ok_or_else(|| ExecutorError::Execution(format!("Column not found: {}", column)))?;
+                Ok((col_idx, self.inline_sql_functions(expr)?))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        let selection = selection.map(|predicate| self.inline_sql_functions(predicate)).transpose()?;
+
+        let mut db = self.db.write();
+        let checks = Self::table_checks(&db, table)?;
+        let tuples = Self::matching_tuples(&db, table, selection.as_ref(), &schema)?;
+
+        let mut tuple_ptrs = Vec::new();
+        let mut changes = Vec::new();
+        for (tuple_ptr, row) in tuples {
+            // Every assignment sees the row as it was before the update
+            let mut values = row.values.clone();
+            for (col_idx, expr) in &assignments {
+                values[*col_idx] = evaluator::eval_expr(expr, &row, &schema)?;
+            }
+            let mut updated_row = Row::new(values);
+            typmod::apply(&db, &schema, &mut updated_row)?;
+            Self::check_row(table, &checks, &updated_row, &schema)?;
+            changes.push((row, updated_row));
+            tuple_ptrs.push(tuple_ptr);
         }
+        let new_rows: Vec<Row> = changes.iter().map(|(_, new_row)| new_row.clone()).collect();
+        referential::check_references(&db, table, &schema, &new_rows)?;
+        referential::check_key_changes(&db, table, &schema, &changes)?;
+        let updates = tuple_ptrs.into_iter().zip(new_rows).collect();
 
-        info!(response_count = responses.len(), "execution complete");
-        Ok(responses)
+        let updated = db.update_rows(table, updates)
+            .map_err(ExecutorError::Execution)?;
+        debug!(table = %table, rows = updated, "rows updated");
+        Ok(updated)
     }
 
-    fn execute_plan(&self, plan: Operator) -> Result<Response> {
-        // Extract table name if available for schema lookup
-        let table_name = self.extract_table_name(&plan);
+    /// Inline the SQL functions of an ON CONFLICT DO UPDATE's SET list and WHERE
+    fn inline_on_conflict(&self, on_conflict: planner::OnConflict) -> Result<planner::OnConflict> {
+        let action = match on_conflict.action {
+            planner::ConflictAction::DoUpdate { assignments, selection } => planner::ConflictAction::DoUpdate {
+                assignments: assignments.into_iter()
+                    .map(|(column, expr)| Ok((column, self.inline_sql_functions(&expr)?)))
+                    .collect::<Result<Vec<_>>>()?,
+                selection: selection.map(|predicate| self.inline_sql_functions(&predicate).map(Box::new)).transpose()?,
+            },
+            action => action,
+        };
+        Ok(planner::OnConflict { action, ..on_conflict })
+    }
 
-        // Evaluate plan tree to get rows, then convert to Response
-        let rows = self.execute_plan_rows(plan, table_name.clone())?;
+    /// Apply a DELETE: remove every row of the table the predicate matches
+    /// Returns the number of rows deleted
+    fn execute_delete(&self, table: &str, selection: Option<&sqlparser::ast::Expr>) -> Result<usize> {
+        debug!(table = %table, "executing delete");
+        let schema = self.db.read().get_schema(table)
+            .map_err(ExecutorError::Execution)?;
+        let selection = selection.map(|predicate| self.inline_sql_functions(predicate)).transpose()?;
+
+        let mut db = self.db.write();
+        let tuples = Self::matching_tuples(&db, table, selection.as_ref(), &schema)?;
+
+        // Rows cascaded to are deleted with the matched rows, but only the
+        // matched rows are counted, as in Postgres
+        let mut deleted = 0;
+        for (delete_table, tuple_ptrs) in referential::plan_delete(&db, table, tuples)? {
+            let rows = db.delete_rows(&delete_table, &tuple_ptrs)
+                .map_err(ExecutorError::Execution)?;
+            if delete_table == table {
+                deleted = rows;
+            } else {
+                info!(table = %delete_table, rows, "rows deleted by cascade");
+            }
+        }
+        debug!(table = %table, rows = deleted, "rows deleted");
+        Ok(deleted)
+    }
+
+    /// Rows of a table, with where each is stored, that an UPDATE or DELETE
+    /// predicate selects; rows are matched the same way a SELECT's WHERE filters them
+    fn matching_tuples(
+        db: &Database,
+        table: &str,
+        selection: Option<&sqlparser::ast::Expr>,
+        schema: &Schema,
+    ) -> Result<Vec<(TuplePointer, Row)>> {
+        let tuples = db.scan_table_tuples(table)
+            .map_err(ExecutorError::Execution)?;
+        Ok(tuples.into_iter()
+            .filter(|(_, row)| match selection {
+                Some(predicate) => matches!(evaluator::eval_expr(predicate, row, schema), Ok(Value::Bool(true))),
+                None => true,
+            })
+            .collect())
+    }
 
+    /// Evaluate a plan tree into its rows and, when known, the schema of those rows
+    fn execute_plan_with_schema(&self, plan: Operator) -> Result<(Vec<Row>, Option<Schema>)> {
         // Get the actual schema for proper column naming
-        let schema = if let Some(table_name) = table_name {
-            let db = self.db.read();
-            db.get_schema(&table_name).ok()
-        } else {
-            None
-        };
+        let schema = self.output_schema(&plan);
+        let budget = MemoryBudget::new(self.work_mem);
+        let rows = self.execute_plan_rows(plan, &budget)?;
+        debug!(bytes = budget.used(), "query memory charged");
+        Ok((rows, schema))
+    }
 
-        rows_to_response(rows, schema)
+    /// Schema of the rows a plan produces, named by its projection if it has one
+    fn output_schema(&self, plan: &Operator) -> Option<Schema> {
+        match plan {
+            Operator::Project { input, columns, names } => {
+                let source_schema = self.source_schema(input).unwrap_or_else(|| Schema::new(Vec::new()));
+                let db = self.db.read();
+                let output_columns = expand_projection(columns, names, &source_schema)
+                    .into_iter()
+                    .map(|(col_expr, name)| crate::types::Column {
+                        data_type: typing::infer_type(&col_expr, &source_schema, &db),
+                        name,
+                        is_primary_key: false,
+                        typmod: None,
+                    })
+                    .collect();
+                Some(Schema::new(output_columns))
+            }
+            Operator::Filter { input, .. } => self.output_schema(input),
+            Operator::Sort { input, .. } => self.output_schema(input),
+            Operator::Limit { input, .. } => self.output_schema(input),
+            _ => self.source_schema(plan),
+        }
     }
 
-    fn extract_table_name(&self, plan: &Operator) -> Option<String> {
+    /// Schema of the rows a plan reads: the table's, or the columns of a VALUES list
+    fn source_schema(&self, plan: &Operator) -> Option<Schema> {
         match plan {
-            Operator::TableScan { table } if table != "__constant__" => Some(table.clone()),
-            Operator::IndexScan { table, .. } => Some(table.clone()),
-            Operator::Filter { input, .. } => self.extract_table_name(input),
-            Operator::Project { input, .. } => self.extract_table_name(input),
-            Operator::Limit { input, .. } => self.extract_table_name(input),
+            Operator::TableScan { table } if table != "__constant__" => self.db.read().get_schema(table).ok(),
+            Operator::IndexScan { table, .. }
+            | Operator::IndexPrefixScan { table, .. }
+            | Operator::IndexRangeScan { table, .. }
+            | Operator::IndexOperatorScan { table, .. } => self.db.read().get_schema(table).ok(),
+            Operator::Values { schema, .. } => Some(schema.clone()),
+            Operator::Join { left, right, left_qualifier, right_qualifier, .. } => {
+                self.join_schema(left, right, left_qualifier.as_deref(), right_qualifier.as_deref())
+            }
+            Operator::Filter { input, .. } => self.source_schema(input),
+            Operator::Sort { input, .. } => self.source_schema(input),
+            Operator::Project { input, .. } => self.source_schema(input),
+            Operator::Aggregate { input, group_by, aggregates } => {
+                // An aggregated row holds the group keys, then each aggregate's result
+                let input_schema = self.source_schema(input).unwrap_or_else(|| Schema::new(Vec::new()));
+                let db = self.db.read();
+                let columns = group_by.iter()
+                    .chain(aggregates)
+                    .map(|expr| crate::types::Column {
+                        name: planner::aggregate_column(expr),
+                        data_type: typing::infer_type(expr, &input_schema, &db),
+                        is_primary_key: false,
+                        typmod: None,
+                    })
+                    .collect();
+                Some(Schema::new(columns))
+            }
+            Operator::Limit { input, .. } => self.source_schema(input),
             _ => None,
         }
     }
 
-    fn execute_plan_rows(&self, plan: Operator, table_name: Option<String>) -> Result<Vec<Row>> {
+    /// Whether a value has the type of a column's values, so that its index
+    /// key is comparable to theirs
+    fn has_key_type(value: &Value, data_type: &DataType) -> bool {
+        matches!(
+            (value, data_type),
+            (Value::Int(_), DataType::Int)
+                | (Value::Float(_), DataType::Float)
+                | (Value::String(_), DataType::String)
+                | (Value::Bool(_), DataType::Bool)
+        )
+    }
+
+    /// Schema of the rows a Join produces: the left input's columns, then the
+    /// right input's, each qualified by its side's qualifier if it has one
+    fn join_schema(
+        &self,
+        left: &Operator,
+        right: &Operator,
+        left_qualifier: Option<&str>,
+        right_qualifier: Option<&str>,
+    ) -> Option<Schema> {
+        let mut columns = Vec::new();
+        for (input, qualifier) in [(left, left_qualifier), (right, right_qualifier)] {
+            columns.extend(self.source_schema(input)?.columns.into_iter().map(|column| crate::types::Column {
+                name: match qualifier {
+                    Some(qualifier) => format!("{}.{}", qualifier, column.name),
+                    None => column.name,
+                },
+                ..column
+            }));
+        }
+        Some(Schema::new(columns))
+    }
+
+    fn execute_plan_rows(&self, plan: Operator, budget: &MemoryBudget) -> Result<Vec<Row>> {
         match plan {
             Operator::TableScan { table } if table == "__constant__" => {
                 // Constant expression like SELECT 1
                 debug!("executing constant scan");
                 Ok(vec![Row::new(vec![Value::Int(1)])])
             }
-            Operator::IndexScan { table, column, value } => {
-                debug!(table = %table, column = %column, "executing index scan");
+            Operator::IndexScan { table, column, values } => {
+                debug!(table = %table, column = %column, lookups = values.len(), "executing index scan");
+                let values = values.iter()
+                    .map(|value| self.inline_sql_functions(value))
+                    .collect::<Result<Vec<_>>>()?;
                 let db = self.db.read();
 
-                // Evaluate the value expression
+                // Evaluate the value expressions; NULL never matches, so it is
+                // never looked up
                 let schema = db.get_schema(&table)
                     .map_err(|e| ExecutorError::Execution(e))?;
                 let empty_row = Row::new(vec![]);
-                let lookup_val = evaluator::eval_expr(&value, &empty_row, &schema)?;
-
-                // Convert value to u64 key for index lookup
-                let key = match lookup_val {
-                    Value::Int(n) => n as u64,
-                    Value::Float(f) => f.to_bits(),
-                    Value::String(s) => {
-                        use std::collections::hash_map::DefaultHasher;
-                        use std::hash::{Hash, Hasher};
-                        let mut hasher = DefaultHasher::new();
-                        s.hash(&mut hasher);
-                        hasher.finish()
+                let mut lookup_vals = Vec::with_capacity(values.len());
+                for value in &values {
+                    let lookup_val = evaluator::eval_expr(value, &empty_row, &schema)?;
+                    if !matches!(lookup_val, Value::Null) {
+                        lookup_vals.push(lookup_val);
+                    }
+                }
+
+                let col_idx = schema.get_column_index(&column)
+                    .ok_or_else(|| ExecutorError::Execution(format!("Column not found: {}", column)))?;
+                let matches = |row: &Row, lookup_val: &Value| row.get(col_idx)
+                    .is_some_and(|v| crate::storage::index::key::key_values_equal(v, lookup_val));
+
+                // Other columns are looked up through a secondary index that
+                // leads with them; without one, the table is scanned and compared
+                if schema.primary_key_index() != Some(col_idx) {
+                    let index = db.lookup_index(&table, &[schema.columns[col_idx].name.as_str()])
+                        .map_err(ExecutorError::Execution)?;
+                    let Some((index_name, _)) = index else {
+                        debug!(column = %column, "no usable index on column, falling back to table scan");
+                        let rows = db.scan_table(&table)
+                            .map_err(ExecutorError::Execution)?;
+                        return Ok(rows.into_iter()
+                            .filter(|row| lookup_vals.iter().any(|lookup_val| matches(row, lookup_val)))
+                            .collect());
+                    };
+                    debug!(index = %index_name, "looking up rows through secondary index");
+                    let mut rows = Vec::new();
+                    let mut seen = HashSet::new();
+                    for lookup_val in &lookup_vals {
+                        let hits = db.search_index_prefix(&table, &index_name, std::slice::from_ref(lookup_val))
+                            .map_err(ExecutorError::Execution)?;
+                        // Keys don't keep whole values, so entries may belong to others
+                        rows.extend(hits.into_iter()
+                            .filter(|(tuple_ptr, row)| seen.insert(*tuple_ptr) && matches(row, lookup_val))
+                            .map(|(_, row)| row));
+                    }
+                    return Ok(rows);
+                }
+
+                let mut rows = Vec::new();
+                let mut seen = HashSet::new();
+                for lookup_val in &lookup_vals {
+                    let Some((tuple_ptr, row)) = db.get_by_key(&table, lookup_val)
+                        .map_err(ExecutorError::Execution)?
+                    else {
+                        debug!("key not found in primary index");
+                        continue;
+                    };
+                    // A value listed twice still yields its row once
+                    if seen.insert(tuple_ptr) {
+                        rows.push(row);
+                    }
+                }
+                Ok(rows)
+            }
+            Operator::IndexPrefixScan { table, keys } => {
+                debug!(table = %table, columns = keys.len(), "executing index prefix scan");
+                let keys = keys.iter()
+                    .map(|(column, value)| Ok((column.clone(), self.inline_sql_functions(value)?)))
+                    .collect::<Result<Vec<_>>>()?;
+                let db = self.db.read();
+
+                // Only a value of the column's own type has a key the way the
+                // column's values do, so the others are left to the Filter above
+                let schema = db.get_schema(&table)
+                    .map_err(ExecutorError::Execution)?;
+                let empty_row = Row::new(vec![]);
+                let mut lookups: Vec<(&str, Value)> = Vec::new();
+                for (column, value) in &keys {
+                    let col_idx = schema.get_column_index(column)
+                        .ok_or_else(|| ExecutorError::Execution(format!("Column not found: {}", column)))?;
+                    let value = evaluator::eval_expr(value, &empty_row, &schema)?;
+                    let column = &schema.columns[col_idx];
+                    if Self::has_key_type(&value, &column.data_type) {
+                        lookups.push((column.name.as_str(), value));
                     }
-                    _ => return Err(ExecutorError::Execution("Cannot use NULL/Bool as index key".to_string())),
+                }
+
+                let primary_key = schema.primary_key_index().map(|idx| schema.columns[idx].name.as_str());
+                if let Some((_, value)) = lookups.iter().find(|(column, _)| Some(*column) == primary_key) {
+                    debug!("looking up row through primary index");
+                    let row = db.get_by_key(&table, value)
+                        .map_err(ExecutorError::Execution)?;
+                    return Ok(row.into_iter().map(|(_, row)| row).collect());
+                }
+                let columns: Vec<&str> = lookups.iter().map(|(column, _)| *column).collect();
+                let index = db.lookup_index(&table, &columns)
+                    .map_err(ExecutorError::Execution)?;
+                let Some((index_name, prefix_columns)) = index else {
+                    debug!("no usable index on columns, falling back to table scan");
+                    return db.scan_table(&table)
+                        .map_err(ExecutorError::Execution);
                 };
+                debug!(index = %index_name, prefix = prefix_columns.len(), "looking up rows through secondary index");
+                let prefix: Vec<Value> = prefix_columns.iter()
+                    .filter_map(|index_column| lookups.iter().find(|(column, _)| column == index_column))
+                    .map(|(_, value)| value.clone())
+                    .collect();
+                let hits = db.search_index_prefix(&table, &index_name, &prefix)
+                    .map_err(ExecutorError::Execution)?;
+                Ok(hits.into_iter().map(|(_, row)| row).collect())
+            }
+            Operator::IndexRangeScan { table, column, low, high, order, limit } => {
+                debug!(table = %table, column = %column, "executing index range scan");
+                let low = low.map(|low| self.inline_sql_functions(&low)).transpose()?;
+                let high = high.map(|high| self.inline_sql_functions(&high)).transpose()?;
+                let limit = self.eval_row_count(limit.as_deref(), "LIMIT")?;
+                let db = self.db.read();
 
-                // Try to find a secondary index for this column, fall back to primary
-                let result = db.search_secondary_index(&table, &column, key)
-                    .or_else(|_| {
-                        // If secondary index search fails, try primary index
-                        debug!(column = %column, "secondary index not found or search failed, falling back to primary");
-                        db.get_by_key(&table, key)
-                    })
-                    .map_err(|e| ExecutorError::Execution(e))?;
+                let schema = db.get_schema(&table)
+                    .map_err(ExecutorError::Execution)?;
+                let empty_row = Row::new(vec![]);
+                let low_val = low.as_ref().map(|low| evaluator::eval_expr(low, &empty_row, &schema)).transpose()?;
+                let high_val = high.as_ref().map(|high| evaluator::eval_expr(high, &empty_row, &schema)).transpose()?;
 
-                // Fetch the row using the pointer if found
-                match result {
-                    Some(tuple_ptr) => {
-                        let seg_id = tuple_ptr.segment_id;
-                        let block_id = tuple_ptr.block_id;
-                        let slot_id = tuple_ptr.slot_id;
-
-                        // Read the block and extract the row
-                        let block = db.read_block(seg_id, block_id)
-                            .map_err(|e| ExecutorError::Execution(e))?;
-
-                        if let Some(tuple_bytes) = block.read_tuple(slot_id) {
-                            let (row, _): (Row, usize) = bincode::decode_from_slice(tuple_bytes, bincode::config::standard())
-                                .map_err(|e| ExecutorError::Execution(format!("Deserialization error: {}", e)))?;
-                            Ok(vec![row])
-                        } else {
-                            Ok(Vec::new())
+                let col_idx = schema.get_column_index(&column)
+                    .ok_or_else(|| ExecutorError::Execution(format!("Column not found: {}", column)))?;
+                let in_range = |row: &Row| match (&low_val, &high_val) {
+                    (Some(low), Some(high)) => row.get(col_idx).is_some_and(|v| {
+                        matches!(evaluator::eval_between(v, low, high, false), Ok(Value::Bool(true)))
+                    }),
+                    _ => true,
+                };
+
+                // Keys of different types are encoded differently, so only bounds
+                // of the key's own type can be turned into a key range
+                let key_type = &schema.columns[col_idx].data_type;
+                let is_key = |bound: &Option<Value>| bound.as_ref().is_none_or(|bound| matches!(
+                    (key_type, bound),
+                    (DataType::Int, Value::Int(_)) | (DataType::Float, Value::Float(_))
+                        | (DataType::String, Value::String(_)) | (DataType::Bool, Value::Bool(_))
+                ));
+                let indexed = schema.primary_key_index() == Some(col_idx) && is_key(&low_val) && is_key(&high_val);
+                // String keys are prefixes, so only an exact order can come
+                // from the index; otherwise the rows are sorted like ORDER BY
+                if let Some(direction) = order
+                    && (!indexed || matches!(key_type, DataType::String))
+                {
+                    drop(db);
+                    let descending = direction == ScanDirection::Backward;
+                    let unordered = Operator::IndexRangeScan {
+                        table,
+                        column: column.clone(),
+                        low: low.map(Box::new),
+                        high: high.map(Box::new),
+                        order: None,
+                        limit: None,
+                    };
+                    let keys = vec![SortKey {
+                        expr: sqlparser::ast::Expr::Identifier(sqlparser::ast::Ident::new(column)),
+                        descending,
+                        nulls_first: descending,
+                    }];
+                    return self.execute_plan_rows(Operator::Sort { input: Box::new(unordered), keys }, budget);
+                }
+                if !indexed {
+                    debug!(column = %column, "no usable index for range, falling back to table scan");
+                    let rows = db.scan_table(&table)
+                        .map_err(ExecutorError::Execution)?;
+                    return Ok(rows.into_iter().filter(|row| in_range(row)).collect());
+                }
+                if let (Some(low), Some(high)) = (&low_val, &high_val)
+                    && evaluator::compare_values(low, high)? == std::cmp::Ordering::Greater
+                {
+                    return Ok(Vec::new());
+                }
+
+                // String keys are prefixes, so the range may take in keys just
+                // outside the bounds, and the scan can't stop at the LIMIT
+                // before they are filtered out
+                let limit = limit.filter(|_| !matches!(key_type, DataType::String));
+                let direction = order.unwrap_or(ScanDirection::Forward);
+                let hits = db.range_scan_index(&table, low_val.as_ref(), high_val.as_ref(), direction, limit)
+                    .map_err(ExecutorError::Execution)?;
+                Ok(hits.into_iter().map(|(_, row)| row).filter(|row| in_range(row)).collect())
+            }
+            Operator::IndexOperatorScan { table, column, operator, value } => {
+                debug!(table = %table, column = %column, operator = %operator, "executing index operator scan");
+                let value = self.inline_sql_functions(&value)?;
+                let db = self.db.read();
+
+                let schema = db.get_schema(&table)
+                    .map_err(ExecutorError::Execution)?;
+                let col_idx = schema.get_column_index(&column)
+                    .ok_or_else(|| ExecutorError::Execution(format!("Column not found: {}", column)))?;
+                let column = &schema.columns[col_idx];
+                let operand = evaluator::eval_expr(&value, &Row::new(vec![]), &schema)?;
+                let operand_type = typing::infer_type(&value, &schema, &db);
+                let symbol = operator.to_string();
+                let ext = db.operator_registry.find(&symbol, &column.data_type, &operand_type)
+                    .ok_or_else(|| ExecutorError::Execution(format!("Unsupported binary operator: {:?}", operator)))?;
+                // Operators are strict: a NULL on either side holds for no row
+                if matches!(operand, Value::Null) {
+                    return Ok(Vec::new());
+                }
+                let holds = |row: &Row| -> Result<bool> {
+                    match row.get(col_idx) {
+                        None | Some(Value::Null) => Ok(false),
+                        Some(v) => Ok(matches!(ext.execute(v, &operand).map_err(ExecutorError::Execution)?, Value::Bool(true))),
+                    }
+                };
+
+                let index = db.operator_index(&table, column, &symbol, &operand_type)
+                    .map_err(ExecutorError::Execution)?;
+                let hits = match &index {
+                    Some((index_name, strategy)) => db.search_operator_index(&table, index_name, *strategy, &operand)
+                        .map_err(ExecutorError::Execution)?,
+                    None => None,
+                };
+                let Some(hits) = hits else {
+                    debug!(column = %column.name, operator = %symbol, "no index serves the operator, falling back to table scan");
+                    let rows = db.scan_table(&table)
+                        .map_err(ExecutorError::Execution)?;
+                    let mut matching = Vec::new();
+                    for row in rows {
+                        if holds(&row)? {
+                            matching.push(row);
                         }
                     }
-                    None => {
-                        debug!("key not found in any index");
-                        Ok(Vec::new())
+                    return Ok(matching);
+                };
+                debug!(index = ?index.map(|(index_name, _)| index_name), candidates = hits.len(), "looking up rows through operator class");
+                let mut rows = Vec::with_capacity(hits.len());
+                for (_, row) in hits {
+                    // The index only narrows the rows; the operator decides
+                    if holds(&row)? {
+                        rows.push(row);
                     }
                 }
+                Ok(rows)
             }
             Operator::TableScan { table } => {
                 debug!(table = %table, "executing table scan");
@@ -241,10 +2221,31 @@ impl Executor {
                 // in Project when needed via the actual table schema from DB
                 Ok(rows)
             }
+            Operator::Values { schema: _, rows } => {
+                debug!(rows = rows.len(), "executing values list");
+                let empty_row = Row::new(vec![]);
+                let empty_schema = Schema::new(Vec::new());
+                rows.iter()
+                    .map(|row_exprs| {
+                        let values = row_exprs.iter()
+                            .map(|expr| {
+                                let expr = self.inline_sql_functions(expr)?;
+                                match self.eval_sequence_function(&expr)? {
+                                    Some(val) => Ok(val),
+                                    None => evaluator::eval_expr(&expr, &empty_row, &empty_schema),
+                                }
+                            })
+                            .collect::<Result<Vec<_>>>()?;
+                        Ok(Row::new(values))
+                    })
+                    .collect()
+            }
             Operator::Filter { input, predicate } => {
                 debug!("executing filter");
-                let rows = self.execute_plan_rows(*input, table_name)?;
-                let schema = self.infer_schema(&rows);
+                let source_schema = self.source_schema(&input);
+                let rows = self.execute_plan_rows(*input, budget)?;
+                let schema = source_schema.unwrap_or_else(|| self.infer_schema(&rows));
+                let predicate = self.inline_sql_functions(&predicate)?;
 
                 let filtered = rows
                     .into_iter()
@@ -259,38 +2260,104 @@ impl Executor {
                     .collect();
                 Ok(filtered)
             }
-            Operator::Project { input, columns } => {
+            Operator::Sort { input, keys } => {
+                debug!("executing sort on {} keys", keys.len());
+                let source_schema = self.source_schema(&input);
+                let rows = self.execute_plan_rows(*input, budget)?;
+                budget.charge_rows(&rows, "ORDER BY")?;
+                let schema = source_schema.unwrap_or_else(|| self.infer_schema(&rows));
+                let key_exprs = keys.iter()
+                    .map(|key| match &key.expr {
+                        // Position of an input column
+                        sqlparser::ast::Expr::Value(val) if matches!(val.value, sqlparser::ast::Value::Number(_, _)) => {
+                            let position = val.value.to_string();
+                            position.parse::<usize>().ok()
+                                .and_then(|position| position.checked_sub(1))
+                                .and_then(|idx| schema.columns.get(idx))
+                                .map(|column| sqlparser::ast::Expr::Identifier(sqlparser::ast::Ident::new(&column.name)))
+                                .ok_or_else(|| ExecutorError::Execution(format!(
+                                    "ORDER BY position {} is not in select list",
+                                    position
+                                )))
+                        }
+                        expr => self.inline_sql_functions(expr),
+                    })
+                    .collect::<Result<Vec<_>>>()?;
+
+                // Evaluate every row's keys once up front
+                let mut keyed = rows.into_iter()
+                    .map(|row| {
+                        let values = key_exprs.iter()
+                            .map(|expr| evaluator::eval_expr(expr, &row, &schema))
+                            .collect::<Result<Vec<_>>>()?;
+                        Ok((values, row))
+                    })
+                    .collect::<Result<Vec<_>>>()?;
+
+                // The sort is stable, so rows with equal keys keep their scan order
+                let mut error = None;
+                keyed.sort_by(|(left, _), (right, _)| {
+                    for ((left, right), key) in left.iter().zip(right).zip(&keys) {
+                        let ordering = match (left, right) {
+                            (Value::Null, Value::Null) => std::cmp::Ordering::Equal,
+                            (Value::Null, _) if key.nulls_first => std::cmp::Ordering::Less,
+                            (Value::Null, _) => std::cmp::Ordering::Greater,
+                            (_, Value::Null) if key.nulls_first => std::cmp::Ordering::Greater,
+                            (_, Value::Null) => std::cmp::Ordering::Less,
+                            _ => match evaluator::compare_values(left, right) {
+                                Ok(ordering) if key.descending => ordering.reverse(),
+                                Ok(ordering) => ordering,
+                                Err(e) => {
+                                    error.get_or_insert(e);
+                                    std::cmp::Ordering::Equal
+                                }
+                            },
+                        };
+                        if ordering.is_ne() {
+                            return ordering;
+                        }
+                    }
+                    std::cmp::Ordering::Equal
+                });
+                if let Some(e) = error {
+                    return Err(e);
+                }
+                Ok(keyed.into_iter().map(|(_, row)| row).collect())
+            }
+            Operator::Project { input, columns, names } => {
                 debug!("executing projection with {} columns", columns.len());
-                let rows = self.execute_plan_rows(*input, table_name.clone())?;
-                // Try to use actual table schema if available
-                let schema = if let Some(table_name) = &table_name {
-                    let db = self.db.read();
-                    db.get_schema(table_name).unwrap_or_else(|_| self.infer_schema(&rows))
-                } else {
-                    self.infer_schema(&rows)
-                };
+                // Try to use actual source schema if available
+                let source_schema = self.source_schema(&input);
+                let rows = self.execute_plan_rows(*input, budget)?;
+                let schema = source_schema.unwrap_or_else(|| self.infer_schema(&rows));
 
                 // Expand wildcards to actual column names
-                let expanded_columns = columns.iter()
-                    .flat_map(|col_expr| {
-                        match col_expr {
-                            sqlparser::ast::Expr::Identifier(ident) if &ident.value == "*" => {
-                                // Replace wildcard with actual column expressions
-                                schema.columns.iter()
-                                    .map(|col| sqlparser::ast::Expr::Identifier(sqlparser::ast::Ident::new(&col.name)))
-                                    .collect::<Vec<_>>()
-                            }
-                            _ => vec![col_expr.clone()]
-                        }
+                let expanded_columns = expand_projection(&columns, &names, &schema)
+                    .iter()
+                    .map(|(col_expr, _)| self.inline_sql_functions(col_expr))
+                    .collect::<Result<Vec<_>>>()?;
+
+                // Statistics and admin functions don't depend on the row, evaluate them once
+                let constants = expanded_columns.iter()
+                    .map(|col_expr| match self.eval_stats_function(col_expr)? {
+                        Some(val) => Ok(Some(val)),
+                        None => self.eval_admin_function(col_expr),
                     })
-                    .collect::<Vec<_>>();
+                    .collect::<Result<Vec<_>>>()?;
 
                 let projected: Result<Vec<Row>> = rows
                     .iter()
                     .map(|row| {
                         let mut new_values = Vec::new();
-                        for col_expr in &expanded_columns {
-                            let val = evaluator::eval_expr(col_expr, row, &schema)?;
+                        for (col_expr, constant) in expanded_columns.iter().zip(&constants) {
+                            let val = match constant {
+                                Some(val) => val.clone(),
+                                // Sequence functions advance once per row
+                                None => match self.eval_sequence_function(col_expr)? {
+                                    Some(val) => val,
+                                    None => evaluator::eval_expr(col_expr, row, &schema)?,
+                                },
+                            };
                             new_values.push(val);
                         }
                         Ok(Row::new(new_values))
@@ -298,22 +2365,423 @@ impl Executor {
                     .collect();
                 projected
             }
-            Operator::Aggregate { input, group_by: _, aggregates: _ } => {
-                debug!("executing aggregate");
-                let _rows = self.execute_plan_rows(*input, table_name)?;
-                // TODO: Implement aggregation
-                Ok(Vec::new())
+            Operator::Join { left, right, left_qualifier, right_qualifier, condition } => {
+                debug!("executing nested loop join");
+                let schema = self.join_schema(&left, &right, left_qualifier.as_deref(), right_qualifier.as_deref())
+                    .ok_or_else(|| ExecutorError::Execution("Cannot determine the columns of a join input".to_string()))?;
+                let condition = condition.map(|expr| self.inline_sql_functions(&expr)).transpose()?;
+                let left_width = self.source_schema(&left).map_or(0, |left_schema| left_schema.columns.len());
+                // Estimated before the inputs run, as they take the lock themselves
+                let estimated_pairs = {
+                    let db = self.db.read();
+                    planner::cost::estimate(&left, &*db).estimate.rows * planner::cost::estimate(&right, &*db).estimate.rows
+                };
+                let left_rows = self.execute_plan_rows(*left, budget)?;
+                let right_rows = self.execute_plan_rows(*right, budget)?;
+
+                let actual_pairs = left_rows.len() * right_rows.len();
+                if adaptive::is_misestimated(estimated_pairs, actual_pairs)
+                    && let Some(condition) = &condition
+                    && let Some(key) = adaptive::equi_join_key(condition, &schema, left_width)
+                {
+                    info!(
+                        estimated_pairs = estimated_pairs.round() as u64,
+                        actual_pairs,
+                        condition = %condition,
+                        "join inputs far exceed their estimates, switching to hash join"
+                    );
+                    return adaptive::hash_join(&left_rows, &right_rows, key, condition, &schema, budget);
+                }
+
+                let mut joined = Vec::new();
+                for left_row in &left_rows {
+                    for right_row in &right_rows {
+                        let row = Row::new(left_row.values.iter().chain(&right_row.values).cloned().collect());
+                        let keep = match &condition {
+                            Some(condition) => matches!(evaluator::eval_expr(condition, &row, &schema)?, Value::Bool(true)),
+                            None => true,
+                        };
+                        if keep {
+                            budget.charge(memory::row_size(&row), "JOIN")?;
+                            joined.push(row);
+                        }
+                    }
+                }
+                Ok(joined)
+            }
+            Operator::Aggregate { input, group_by, aggregates } => {
+                debug!("executing aggregate of {} calls over {} group keys", aggregates.len(), group_by.len());
+                let source_schema = self.source_schema(&input);
+                let rows = self.execute_plan_rows(*input, budget)?;
+                let schema = source_schema.unwrap_or_else(|| self.infer_schema(&rows));
+
+                let group_by = group_by.iter()
+                    .map(|expr| self.inline_sql_functions(expr))
+                    .collect::<Result<Vec<_>>>()?;
+                let calls = aggregates.iter()
+                    .map(|expr| aggregate::AggregateCall::parse(&self.inline_sql_functions(expr)?))
+                    .collect::<Result<Vec<_>>>()?;
+
+                // Groups keep the order their first row was seen in
+                let mut groups: Vec<(Vec<Value>, Vec<aggregate::Accumulator>)> = Vec::new();
+                let mut group_index: HashMap<Vec<u8>, usize> = HashMap::new();
+                if group_by.is_empty() {
+                    // Without GROUP BY the whole input is one group, even when empty
+                    groups.push((Vec::new(), calls.iter().map(|call| call.accumulator()).collect()));
+                }
+                for row in &rows {
+                    let idx = if group_by.is_empty() {
+                        0
+                    } else {
+                        let keys = group_by.iter()
+                            .map(|expr| evaluator::eval_expr(expr, row, &schema))
+                            .collect::<Result<Vec<_>>>()?;
+                        let group_key = aggregate::group_key(&keys)?;
+                        match group_index.get(&group_key) {
+                            Some(&idx) => idx,
+                            None => {
+                                let accumulators = calls.iter().map(|call| call.accumulator()).collect::<Vec<_>>();
+                                budget.charge(
+                                    group_key.len() + memory::values_size(&keys)
+                                        + accumulators.len() * std::mem::size_of::<aggregate::Accumulator>(),
+                                    "GROUP BY",
+                                )?;
+                                groups.push((keys, accumulators));
+                                group_index.insert(group_key, groups.len() - 1);
+                                groups.len() - 1
+                            }
+                        }
+                    };
+                    for (call, accumulator) in calls.iter().zip(&mut groups[idx].1) {
+                        let value = match &call.arg {
+                            Some(arg) => Some(evaluator::eval_expr(arg, row, &schema)?),
+                            None => None,
+                        };
+                        accumulator.update(value)?;
+                    }
+                }
+
+                Ok(groups.into_iter()
+                    .map(|(keys, accumulators)| {
+                        let values = keys.into_iter()
+                            .chain(accumulators.into_iter().map(|accumulator| accumulator.finish()))
+                            .collect();
+                        Row::new(values)
+                    })
+                    .collect())
             }
             Operator::Limit { input, limit, offset } => {
-                debug!("executing limit {} offset {:?}", limit, offset);
-                let rows = self.execute_plan_rows(*input, table_name)?;
-                let skip = offset.unwrap_or(0) as usize;
+                let limit = self.eval_row_count(limit.as_deref(), "LIMIT")?;
+                let offset = self.eval_row_count(offset.as_deref(), "OFFSET")?;
+                debug!("executing limit {:?} offset {:?}", limit, offset);
+                let rows = self.execute_plan_rows(*input, budget)?;
                 Ok(rows.into_iter()
-                    .skip(skip)
-                    .take(limit as usize)
+                    .skip(offset.unwrap_or(0))
+                    .take(limit.unwrap_or(usize::MAX))
                     .collect())
             }
+            Operator::Update { .. } | Operator::Delete { .. } => Err(ExecutorError::Execution(
+                "UPDATE and DELETE do not produce rows".to_string(),
+            )),
+        }
+    }
+
+    /// Evaluate a LIMIT or OFFSET count, which may be any constant expression
+    /// Returns None when there is no count or it is NULL
+    fn eval_row_count(&self, expr: Option<&sqlparser::ast::Expr>, clause: &str) -> Result<Option<usize>> {
+        let Some(expr) = expr else {
+            return Ok(None);
+        };
+        let expr = self.inline_sql_functions(expr)?;
+        match evaluator::eval_expr(&expr, &Row::new(vec![]), &Schema::new(Vec::new()))? {
+            Value::Null => Ok(None),
+            Value::Int(n) if n < 0 => Err(ExecutorError::Execution(format!("{} must not be negative", clause))),
+            Value::Int(n) => Ok(Some(n as usize)),
+            other => Err(ExecutorError::Execution(format!(
+                "argument of {} must be an integer, got {:?}",
+                clause, other
+            ))),
+        }
+    }
+
+    /// Evaluate a table statistics function, answered from storage metadata:
+    /// flint_table_size('t') (bytes), flint_row_count('t') (exact, scans) and
+    /// flint_approx_row_count('t') (maintained incrementally, no scan)
+    /// Returns None for any other expression
+    fn eval_stats_function(&self, expr: &sqlparser::ast::Expr) -> Result<Option<Value>> {
+        let sqlparser::ast::Expr::Function(func) = expr else {
+            return Ok(None);
+        };
+        let name = func.name.to_string().to_lowercase();
+        if !matches!(name.as_str(), "flint_table_size" | "flint_row_count" | "flint_approx_row_count") {
+            return Ok(None);
+        }
+
+        let table_name = match Self::function_args(func)?.as_slice() {
+            [Value::String(table_name)] => table_name.clone(),
+            [other] => return Err(ExecutorError::Execution(format!(
+                "{}() expects a table name, got {:?}",
+                name, other
+            ))),
+            _ => return Err(ExecutorError::Execution(format!("{}() takes exactly one argument", name))),
+        };
+
+        let db = self.db.read();
+        let value = match name.as_str() {
+            "flint_table_size" => db.table_size_bytes(&table_name),
+            "flint_row_count" => db.row_count(&table_name),
+            _ => db.approx_row_count(&table_name),
+        }
+        .map_err(ExecutorError::Execution)?;
+        debug!(function = %name, table = %table_name, value, "evaluated statistics function");
+
+        Ok(Some(Value::Int(value as i64)))
+    }
+
+    /// Evaluate a server administration function: flint_promote(),
+    /// flint_backup('location' [, 'base location']) returning the bytes
+    /// copied, or flint_verify_backup('location'), which fails listing any
+    /// damage it finds
+    /// Returns None for anything else
+    fn eval_admin_function(&self, expr: &sqlparser::ast::Expr) -> Result<Option<Value>> {
+        let sqlparser::ast::Expr::Function(func) = expr else {
+            return Ok(None);
+        };
+        let name = func.name.to_string().to_lowercase();
+        match name.as_str() {
+            "flint_promote" => {
+                if !evaluator::function_args(func)?.is_empty() {
+                    return Err(ExecutorError::Execution("flint_promote() takes no arguments".to_string()));
+                }
+                self.promote()?;
+                Ok(Some(Value::Bool(true)))
+            }
+            "flint_backup" => {
+                let (dest, base) = match Self::function_args(func)?.as_slice() {
+                    [Value::String(dest)] => (dest.clone(), None),
+                    [Value::String(dest), Value::String(base)] => (dest.clone(), Some(base.clone())),
+                    _ => return Err(ExecutorError::Execution(
+                        "flint_backup() expects a backup location and optionally a base backup location".to_string(),
+                    )),
+                };
+                let dest = self.backup_location(&dest)?;
+                let base = base.as_deref().map(|base| self.backup_location(base)).transpose()?;
+                // The read lock keeps writers out for the whole copy
+                let stats = self.db.read()
+                    .backup(&dest, base.as_deref())
+                    .map_err(ExecutorError::Execution)?;
+                debug!(dest = %dest, files = stats.files, bytes = stats.bytes, "backed up database");
+                Ok(Some(Value::Int(stats.bytes as i64)))
+            }
+            "flint_verify_backup" => {
+                let args = Self::function_args(func)?;
+                let [Value::String(dir)] = args.as_slice() else {
+                    return Err(ExecutorError::Execution(
+                        "flint_verify_backup() expects a backup location".to_string(),
+                    ));
+                };
+                let dir = self.backup_location(dir)?;
+                let files = archive::open(&dir)
+                    .and_then(|backup| backup::verify(backup.as_ref()))
+                    .map_err(ExecutorError::Execution)?;
+                debug!(dir = %dir, files, "verified backup");
+                Ok(Some(Value::Bool(true)))
+            }
+            _ => Ok(None),
+        }
+    }
+
+    /// Where a backup function argument points: URLs are left to
+    /// `archive::open`, and a directory must lie inside --backup-dir, which
+    /// relative paths are taken from
+    fn backup_location(&self, location: &str) -> Result<String> {
+        if location.contains("://") {
+            return Ok(location.to_string());
+        }
+        let Some(backup_dir) = &self.backup_dir else {
+            return Err(ExecutorError::Execution(format!(
+                "cannot use backup location {}: the server was started without --backup-dir",
+                location
+            )));
+        };
+        let path = Path::new(location);
+        // Checked by its components, since the directory may not exist yet
+        if path.components().any(|component| matches!(component, Component::ParentDir)) {
+            return Err(ExecutorError::Execution(format!("backup location {} must not contain \"..\"", location)));
+        }
+        let path = backup_dir.join(path);
+        if !path.starts_with(backup_dir) {
+            return Err(ExecutorError::Execution(format!(
+                "backup location {} is outside the backup directory {}",
+                location,
+                backup_dir.display()
+            )));
+        }
+        Ok(path.to_string_lossy().into_owned())
+    }
+
+    /// Replace the advisory lock function calls of a statement with their
+    /// results: pg_advisory_lock(key) waits for the lock, pg_try_advisory_lock(key)
+    /// returns whether it got it, pg_advisory_unlock(key) whether the session
+    /// held it, and pg_advisory_unlock_all() releases all of them; a key is a
+    /// bigint or a pair of integers
+    /// Locks belong to the session, which plans don't see, so each call is
+    /// evaluated once per statement rather than once per row
+    fn eval_advisory_lock_functions<'a>(&self, stmt: &'a Statement, session: &Session) -> Result<Cow<'a, Statement>> {
+        let is_lock_function = |expr: &sqlparser::ast::Expr| matches!(
+            expr,
+            sqlparser::ast::Expr::Function(func) if ADVISORY_LOCK_FUNCTIONS.contains(&func.name.to_string().to_lowercase().as_str())
+        );
+        let calls_lock_function = sqlparser::ast::visit_expressions(stmt, |expr| {
+            if is_lock_function(expr) {
+                return std::ops::ControlFlow::Break(());
+            }
+            std::ops::ControlFlow::Continue(())
+        })
+        .is_break();
+        if !calls_lock_function {
+            return Ok(Cow::Borrowed(stmt));
+        }
+
+        let mut stmt = stmt.clone();
+        // Select list items keep the function's name as their column name
+        if let Statement::Query(query) = &mut stmt
+            && let sqlparser::ast::SetExpr::Select(select) = &mut *query.body
+        {
+            for item in &mut select.projection {
+                if let sqlparser::ast::SelectItem::UnnamedExpr(expr) = item
+                    && is_lock_function(expr)
+                    && let sqlparser::ast::Expr::Function(func) = &*expr
+                {
+                    let alias = sqlparser::ast::Ident::new(func.name.to_string().to_lowercase());
+                    *item = sqlparser::ast::SelectItem::ExprWithAlias { expr: expr.clone(), alias };
+                }
+            }
+        }
+
+        let mut error = None;
+        let _ = sqlparser::ast::visit_expressions_mut(&mut stmt, |expr| {
+            let sqlparser::ast::Expr::Function(func) = &*expr else {
+                return std::ops::ControlFlow::Continue(());
+            };
+            let name = func.name.to_string().to_lowercase();
+            if !ADVISORY_LOCK_FUNCTIONS.contains(&name.as_str()) {
+                return std::ops::ControlFlow::Continue(());
+            }
+            match self.eval_advisory_lock_function(&name, func, session) {
+                Ok(value) => {
+                    *expr = sqlparser::ast::Expr::value(value);
+                    std::ops::ControlFlow::Continue(())
+                }
+                Err(e) => {
+                    error = Some(e);
+                    std::ops::ControlFlow::Break(())
+                }
+            }
+        });
+        match error {
+            Some(e) => Err(e),
+            None => Ok(Cow::Owned(stmt)),
+        }
+    }
+
+    fn eval_advisory_lock_function(
+        &self,
+        name: &str,
+        func: &sqlparser::ast::Function,
+        session: &Session,
+    ) -> Result<sqlparser::ast::Value> {
+        let args = Self::function_args(func)?;
+        if name == "pg_advisory_unlock_all" {
+            if !args.is_empty() {
+                return Err(ExecutorError::Execution("pg_advisory_unlock_all() takes no arguments".to_string()));
+            }
+            let released = self.locks.unlock_advisory(session.id);
+            debug!(locks = released, "released advisory locks");
+            return Ok(sqlparser::ast::Value::Null);
+        }
+
+        let key = match args.as_slice() {
+            [Value::Int(key)] => LockKey::Single(*key),
+            [Value::Int(high), Value::Int(low)] => match (i32::try_from(*high), i32::try_from(*low)) {
+                (Ok(high), Ok(low)) => LockKey::Pair(high, low),
+                _ => return Err(ExecutorError::Execution(format!(
+                    "{}() keys given as a pair must each fit in an integer",
+                    name
+                ))),
+            },
+            _ => return Err(ExecutorError::Execution(format!(
+                "{}() expects a bigint key or two integer keys",
+                name
+            ))),
+        };
+        let value = match name {
+            "pg_advisory_lock" => {
+                self.wait_for_lock(Lock::Advisory(key), session)?;
+                sqlparser::ast::Value::Null
+            }
+            "pg_try_advisory_lock" => sqlparser::ast::Value::Boolean(self.locks.try_lock(Lock::Advisory(key), Some(session.id))),
+            _ => {
+                let released = self.locks.unlock(Lock::Advisory(key), Some(session.id));
+                if !released {
+                    warn!(key = ?key, "you don't own a lock of type ExclusiveLock");
+                }
+                sqlparser::ast::Value::Boolean(released)
+            }
+        };
+        debug!(function = %name, key = ?key, "evaluated advisory lock function");
+        Ok(value)
+    }
+
+    /// Replace calls to SQL-defined functions with their bodies
+    fn inline_sql_functions(&self, expr: &sqlparser::ast::Expr) -> Result<sqlparser::ast::Expr> {
+        let db = self.db.read();
+        if !db.has_functions() {
+            return Ok(expr.clone());
+        }
+        functions::inline_functions(expr, &|name| db.get_function(name))
+    }
+
+    /// Evaluate nextval('s') or setval('s', value [, is_called])
+    /// Returns None for any other expression
+    fn eval_sequence_function(&self, expr: &sqlparser::ast::Expr) -> Result<Option<Value>> {
+        let sqlparser::ast::Expr::Function(func) = expr else {
+            return Ok(None);
+        };
+        let name = func.name.to_string().to_lowercase();
+        if !matches!(name.as_str(), "nextval" | "setval") {
+            return Ok(None);
         }
+
+        let args = Self::function_args(func)?;
+        let mut db = self.db.write();
+        let value = match (name.as_str(), args.as_slice()) {
+            ("nextval", [Value::String(sequence)]) => db.nextval(sequence),
+            ("setval", [Value::String(sequence), Value::Int(value)]) => db.setval(sequence, *value, true),
+            ("setval", [Value::String(sequence), Value::Int(value), Value::Bool(is_called)]) => {
+                db.setval(sequence, *value, *is_called)
+            }
+            ("nextval", _) => return Err(ExecutorError::Execution(
+                "nextval() expects a sequence name".to_string(),
+            )),
+            _ => return Err(ExecutorError::Execution(
+                "setval() expects a sequence name, a value and optionally is_called".to_string(),
+            )),
+        }
+        .map_err(ExecutorError::Execution)?;
+        debug!(function = %name, value, "evaluated sequence function");
+
+        Ok(Some(Value::Int(value)))
+    }
+
+    /// Evaluate the arguments of a function call, which may not reference columns
+    fn function_args(func: &sqlparser::ast::Function) -> Result<Vec<Value>> {
+        let empty_row = Row::new(vec![]);
+        let empty_schema = Schema::new(Vec::new());
+        evaluator::function_args(func)?
+            .into_iter()
+            .map(|arg| evaluator::eval_expr(arg, &empty_row, &empty_schema))
+            .collect()
     }
 
     fn infer_schema(&self, rows: &[Row]) -> Schema {
@@ -329,95 +2797,34 @@ impl Executor {
                 name: format!("col{}", i),
                 data_type: crate::types::DataType::Int,
                 is_primary_key: false,
+                typmod: None,
             });
         }
         Schema::new(columns)
     }
 }
 
-fn rows_to_response(rows: Vec<Row>, schema: Option<Schema>) -> Result<Response> {
-    // Convert Row data to pgwire Response
-    if rows.is_empty() {
-        return Ok(Response::EmptyQuery);
-    }
-
-    // Build column metadata for pgwire response
-    let row_len = rows[0].len();
-    let mut field_infos = Vec::new();
-
-    if let Some(schema) = &schema {
-        // Use actual column names from schema
-        for col in &schema.columns {
-            let pgwire_type = match col.data_type {
-                crate::types::DataType::Int => Type::INT4,
-                crate::types::DataType::Float => Type::FLOAT8,
-                crate::types::DataType::String => Type::VARCHAR,
-                crate::types::DataType::Bool => Type::BOOL,
-                crate::types::DataType::Null => Type::UNKNOWN,
-                crate::types::DataType::Extension { .. } => Type::UNKNOWN,
-            };
-            field_infos.push(FieldInfo::new(
-                col.name.clone().into(),
-                None,
-                None,
-                pgwire_type,
-                FieldFormat::Text,
-            ));
-        }
-    } else {
-        // Fall back to generic names if no schema available
-        for i in 0..row_len {
-            field_infos.push(FieldInfo::new(
-                format!("?column?{}", i).into(),
-                None,
-                None,
-                Type::INT4,
-                FieldFormat::Text,
-            ));
-        }
-    }
-
-    let schema = Arc::new(field_infos);
-    let schema_ref = schema.clone();
-
-    // Encode rows
-    let mut encoded_rows = Vec::new();
-    for row in rows {
-        let mut encoder = DataRowEncoder::new(schema_ref.clone());
-        for value in &row.values {
-            match value {
-                Value::Int(n) => {
-                    encoder.encode_field(&(*n as i32))
-                        .map_err(|e| ExecutorError::Execution(format!("Encoding error: {:?}", e)))?;
-                }
-                Value::Float(f) => {
-                    encoder.encode_field(f)
-                        .map_err(|e| ExecutorError::Execution(format!("Encoding error: {:?}", e)))?;
-                }
-                Value::String(s) => {
-                    encoder.encode_field(s)
-                        .map_err(|e| ExecutorError::Execution(format!("Encoding error: {:?}", e)))?;
-                }
-                Value::Bool(b) => {
-                    encoder.encode_field(b)
-                        .map_err(|e| ExecutorError::Execution(format!("Encoding error: {:?}", e)))?;
-                }
-                Value::Null => {
-                    encoder.encode_field(&None::<i32>)
-                        .map_err(|e| ExecutorError::Execution(format!("Encoding error: {:?}", e)))?;
-                }
-                Value::Extension { type_oid, .. } => {
-                    // Extension values cannot be directly serialized to pgwire
-                    // They require the TypeExtension trait for proper encoding
-                    debug!("skipping extension value (type_oid: {})", type_oid);
-                    encoder.encode_field(&None::<i32>)
-                        .map_err(|e| ExecutorError::Execution(format!("Encoding error: {:?}", e)))?;
-                }
+/// Pair each projected expression with its output column name, expanding
+/// wildcards into the source's columns
+fn expand_projection(
+    columns: &[sqlparser::ast::Expr],
+    names: &[String],
+    schema: &Schema,
+) -> Vec<(sqlparser::ast::Expr, String)> {
+    columns.iter()
+        .zip(names)
+        .flat_map(|(col_expr, name)| match col_expr {
+            sqlparser::ast::Expr::Identifier(ident) if ident.value == "*" => {
+                // Replace wildcard with actual column expressions
+                schema.columns.iter()
+                    .map(|col| {
+                        // Joined columns are shown without their table, as in Postgres
+                        let name = col.name.rsplit('.').next().unwrap_or(&col.name).to_string();
+                        (sqlparser::ast::Expr::Identifier(sqlparser::ast::Ident::new(&col.name)), name)
+                    })
+                    .collect::<Vec<_>>()
             }
-        }
-        encoded_rows.push(encoder.finish());
-    }
-
-    let data_row_stream = stream::iter(encoded_rows);
-    Ok(Response::Query(QueryResponse::new(schema, data_row_stream)))
+            _ => vec![(col_expr.clone(), name.clone())],
+        })
+        .collect()
 }
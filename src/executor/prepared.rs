@@ -0,0 +1,154 @@
+//! Prepared statements (PREPARE, EXECUTE, DEALLOCATE) kept by a session
+//!
+//! A statement is parsed once at PREPARE and kept with its `$n` parameters
+//! unbound; EXECUTE substitutes the arguments and runs it like any other
+//! statement. A pooled connection can live for days and prepare statements
+//! without ever deallocating them, so each session keeps at most
+//! `--max-prepared-statements` of them holding `--prepared-statement-mem`
+//! bytes of SQL, evicting the least recently executed ones to make room.
+
+use std::collections::HashMap;
+
+use sqlparser::ast::Statement;
+
+use crate::executor::error::ExecutorError;
+use crate::types::Value;
+
+/// Caps on the statements one session keeps prepared
+#[derive(Debug, Clone, Copy)]
+pub struct PreparedLimits {
+    pub max_statements: usize,
+    /// Bytes of statement text
+    pub max_bytes: usize,
+}
+
+#[derive(Debug)]
+struct PreparedStatement {
+    statement: Statement,
+    /// Number of `$n` parameters EXECUTE must bind
+    param_count: usize,
+    /// Length of the statement's text, counted against `max_bytes`
+    size: usize,
+    /// Tick of the last PREPARE or EXECUTE, for eviction
+    last_used: u64,
+}
+
+/// A session's prepared statements, by name
+#[derive(Debug, Default)]
+pub struct PreparedStatements {
+    statements: HashMap<String, PreparedStatement>,
+    bytes: usize,
+    tick: u64,
+}
+
+impl PreparedStatements {
+    /// Prepare `statement` under `name`, evicting the least recently used
+    /// statements until it fits within `limits`
+    /// Returns the names of the evicted statements
+    pub fn prepare(
+        &mut self,
+        name: &str,
+        statement: Statement,
+        param_count: usize,
+        limits: PreparedLimits,
+    ) -> Result<Vec<String>, String> {
+        if self.statements.contains_key(name) {
+            return Err(format!("prepared statement \"{}\" already exists", name));
+        }
+        let size = statement.to_string().len();
+        if size > limits.max_bytes || limits.max_statements == 0 {
+            return Err(format!(
+                "prepared statement \"{}\" is {} bytes, more than the {} bytes a session may keep prepared",
+                name, size, limits.max_bytes
+            ));
+        }
+
+        let mut evicted = Vec::new();
+        while self.statements.len() >= limits.max_statements || self.bytes + size > limits.max_bytes {
+            let Some(oldest) = self.statements.iter()
+                .min_by_key(|(_, prepared)| prepared.last_used)
+                .map(|(name, _)| name.clone())
+            else {
+                break;
+            };
+            self.deallocate(&oldest);
+            evicted.push(oldest);
+        }
+
+        self.tick += 1;
+        self.bytes += size;
+        self.statements.insert(name.to_string(), PreparedStatement {
+            statement,
+            param_count,
+            size,
+            last_used: self.tick,
+        });
+        Ok(evicted)
+    }
+
+    /// The statement prepared under `name` and its parameter count, marking it
+    /// as just used
+    pub fn get(&mut self, name: &str) -> Option<(Statement, usize)> {
+        self.tick += 1;
+        let prepared = self.statements.get_mut(name)?;
+        prepared.last_used = self.tick;
+        Some((prepared.statement.clone(), prepared.param_count))
+    }
+
+    /// Forget the statement prepared under `name`
+    /// Returns false if there is none
+    pub fn deallocate(&mut self, name: &str) -> bool {
+        match self.statements.remove(name) {
+            Some(prepared) => {
+                self.bytes -= prepared.size;
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn deallocate_all(&mut self) {
+        self.statements.clear();
+        self.bytes = 0;
+    }
+
+    pub fn len(&self) -> usize {
+        self.statements.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.statements.is_empty()
+    }
+}
+
+/// Substitute `values` for the `$n` placeholders of `statement`, as literals
+pub fn bind_parameters(statement: &mut Statement, values: Vec<Value>) -> Result<(), ExecutorError> {
+    let mut bindings = Vec::with_capacity(values.len());
+    for value in values {
+        let literal = match value {
+            Value::Null => sqlparser::ast::Value::Null,
+            Value::Int(n) => sqlparser::ast::Value::Number(n.to_string(), false),
+            Value::Float(f) => sqlparser::ast::Value::Number(f.to_string(), false),
+            Value::String(text) => sqlparser::ast::Value::SingleQuotedString(text),
+            Value::Bool(b) => sqlparser::ast::Value::Boolean(b),
+            other => return Err(ExecutorError::Execution(format!(
+                "Cannot bind {:?} as a prepared statement parameter",
+                other
+            ))),
+        };
+        bindings.push(sqlparser::ast::Expr::value(literal));
+    }
+    let _ = sqlparser::ast::visit_expressions_mut(statement, |expr| {
+        if let sqlparser::ast::Expr::Value(val) = &*expr
+            && let sqlparser::ast::Value::Placeholder(placeholder) = &val.value
+            && let Some(binding) = placeholder.strip_prefix('$')
+                .and_then(|position| position.parse::<usize>().ok())
+                .and_then(|position| position.checked_sub(1))
+                .and_then(|idx| bindings.get(idx))
+        {
+            *expr = binding.clone();
+        }
+        std::ops::ControlFlow::<()>::Continue(())
+    });
+    Ok(())
+}
@@ -0,0 +1,683 @@
+//! Catalog queries of psql's meta-commands
+//!
+//! `\d`, `\dt` and tab completion read pg_catalog with outer joins, regular
+//! expression matches, UNION and correlated subqueries, none of which the
+//! planner supports. A query over the catalogs that uses any of them is
+//! evaluated here instead, directly over the system views, by a small
+//! interpreter of the constructs psql's queries are built from.
+//!
+//! Catalogs flint keeps nothing in (pg_inherits, pg_policy, pg_am, ...) have
+//! no rows, and functions flint doesn't know evaluate to NULL, which psql
+//! reads as "none" or false. A query reading user tables, or using a construct
+//! the interpreter lacks (aggregates, INTERSECT, EXCEPT), is not answered,
+//! so the planner's error stands.
+
+use std::cmp::Ordering;
+use std::ops::ControlFlow;
+
+use regex::Regex;
+use sqlparser::ast::{
+    BinaryOperator, Distinct, Expr, FunctionArg, FunctionArgExpr, FunctionArguments, GroupByExpr, Ident,
+    JoinConstraint, JoinOperator, LimitClause, ObjectName, OrderByKind, Query, Select, SelectItem, SetExpr,
+    SetOperator, SetQuantifier, TableFactor, TableWithJoins, UnaryOperator, visit_expressions, visit_relations,
+};
+
+use crate::executor::builtins;
+use crate::executor::evaluator;
+use crate::storage::Database;
+use crate::storage::system::{self, SystemView};
+use crate::types::{Column, DataType, Row, Schema, Value};
+
+/// A row of a FROM clause: the value of each column, keyed `alias.column`,
+/// followed by the columns of the enclosing query's row a subquery may refer to
+type Record = Vec<(String, Value)>;
+
+/// Rows a query produced, each with the record it was projected from so
+/// ORDER BY can refer to columns the query doesn't select
+type Output = Vec<(Record, Vec<Value>)>;
+
+/// Answer a query over the catalogs the planner can't run, or None if it
+/// isn't one or this can't answer it either
+pub fn answer(query: &Query, db: &Database) -> Option<(Schema, Vec<Row>)> {
+    if !beyond_planner(query, db) {
+        return None;
+    }
+    let mut interpreter = Interpreter { db, reads_catalog: false };
+    let (names, rows) = interpreter.query(query, &Vec::new())?;
+    if !interpreter.reads_catalog {
+        return None;
+    }
+
+    let columns = names.into_iter()
+        .enumerate()
+        .map(|(position, name)| Column {
+            name,
+            data_type: rows.iter()
+                .map(|(_, values)| &values[position])
+                .find(|value| !matches!(value, Value::Null))
+                .map_or(DataType::String, data_type_of),
+            is_primary_key: false,
+            typmod: None,
+        })
+        .collect();
+    Some((Schema::new(columns), rows.into_iter().map(|(_, values)| Row::new(values)).collect()))
+}
+
+struct Interpreter<'a> {
+    db: &'a Database,
+    /// Whether any relation read so far was a catalog
+    reads_catalog: bool,
+}
+
+impl Interpreter<'_> {
+    /// Column names and rows of a query; `outer` is the row of the query a
+    /// subquery is evaluated for
+    fn query(&mut self, query: &Query, outer: &Record) -> Option<(Vec<String>, Output)> {
+        if query.with.is_some() {
+            return None;
+        }
+        let (names, mut rows) = self.set_expr(&query.body, outer)?;
+
+        if let Some(order_by) = &query.order_by {
+            let OrderByKind::Expressions(order_exprs) = &order_by.kind else {
+                return None;
+            };
+            let mut keyed = Vec::with_capacity(rows.len());
+            for (record, values) in rows {
+                let keys: Vec<Value> = order_exprs.iter()
+                    .map(|order_expr| self.sort_key(&order_expr.expr, &names, &record, &values))
+                    .collect::<Option<_>>()?;
+                keyed.push((keys, (record, values)));
+            }
+            keyed.sort_by(|(a, _), (b, _)| {
+                order_exprs.iter().zip(a.iter().zip(b))
+                    .map(|(order_expr, (a, b))| {
+                        // NULLs sort last ascending and first descending
+                        let ordering = match (a, b) {
+                            (Value::Null, Value::Null) => Ordering::Equal,
+                            (Value::Null, _) => Ordering::Greater,
+                            (_, Value::Null) => Ordering::Less,
+                            (a, b) => compare(a, b).unwrap_or(Ordering::Equal),
+                        };
+                        if order_expr.options.asc == Some(false) { ordering.reverse() } else { ordering }
+                    })
+                    .find(|ordering| ordering.is_ne())
+                    .unwrap_or(Ordering::Equal)
+            });
+            rows = keyed.into_iter().map(|(_, row)| row).collect();
+        }
+
+        match &query.limit_clause {
+            None => {}
+            Some(LimitClause::LimitOffset { limit, offset, limit_by }) if limit_by.is_empty() => {
+                let offset = match offset {
+                    Some(offset) => count(&self.eval(&offset.value, outer))?,
+                    None => 0,
+                };
+                rows.drain(..offset.min(rows.len()));
+                if let Some(limit) = limit {
+                    rows.truncate(count(&self.eval(limit, outer))?);
+                }
+            }
+            Some(_) => return None,
+        }
+        Some((names, rows))
+    }
+
+    /// Value an ORDER BY item sorts a row by: a selected column by position
+    /// or name, or an expression over the row's record
+    fn sort_key(&mut self, expr: &Expr, names: &[String], record: &Record, values: &[Value]) -> Option<Value> {
+        if let Expr::Value(value) = expr
+            && let sqlparser::ast::Value::Number(position, _) = &value.value
+        {
+            let position: usize = position.parse().ok()?;
+            return values.get(position.checked_sub(1)?).cloned();
+        }
+        if let Expr::Identifier(ident) = expr
+            && let Some(position) = names.iter().position(|name| *name == ident.value)
+        {
+            return Some(values[position].clone());
+        }
+        Some(self.eval(expr, record))
+    }
+
+    fn set_expr(&mut self, body: &SetExpr, outer: &Record) -> Option<(Vec<String>, Output)> {
+        match body {
+            SetExpr::Select(select) => self.select(select, outer),
+            SetExpr::Query(query) => self.query(query, outer),
+            SetExpr::SetOperation { op: SetOperator::Union, set_quantifier, left, right } => {
+                let (names, left_rows) = self.set_expr(left, outer)?;
+                let (right_names, right_rows) = self.set_expr(right, outer)?;
+                if right_names.len() != names.len() {
+                    return None;
+                }
+                let distinct = match set_quantifier {
+                    SetQuantifier::All => false,
+                    SetQuantifier::None | SetQuantifier::Distinct => true,
+                    _ => return None,
+                };
+                // ORDER BY after a set operation sees only the output columns
+                let mut rows: Output = Vec::with_capacity(left_rows.len() + right_rows.len());
+                for (_, values) in left_rows.into_iter().chain(right_rows) {
+                    if !distinct || !rows.iter().any(|(_, row)| same_row(row, &values)) {
+                        rows.push((Vec::new(), values));
+                    }
+                }
+                Some((names, rows))
+            }
+            _ => None,
+        }
+    }
+
+    fn select(&mut self, select: &Select, outer: &Record) -> Option<(Vec<String>, Output)> {
+        let grouped = match &select.group_by {
+            GroupByExpr::Expressions(exprs, modifiers) => !exprs.is_empty() || !modifiers.is_empty(),
+            GroupByExpr::All(_) => true,
+        };
+        if grouped || select.having.is_some() || matches!(select.distinct, Some(Distinct::On(_))) {
+            return None;
+        }
+
+        // The FROM items' rows, each item joined to the ones before it
+        let mut records: Vec<Record> = vec![Vec::new()];
+        for item in &select.from {
+            let item_records = self.table_with_joins(item)?;
+            records = records.iter()
+                .flat_map(|left| item_records.iter().map(move |right| [left.clone(), right.clone()].concat()))
+                .collect();
+        }
+        for record in &mut records {
+            record.extend(outer.iter().cloned());
+        }
+        if let Some(selection) = &select.selection {
+            let mut kept = Vec::with_capacity(records.len());
+            for record in records {
+                if is_true(&self.eval(selection, &record)) {
+                    kept.push(record);
+                }
+            }
+            records = kept;
+        }
+
+        let mut names = Vec::with_capacity(select.projection.len());
+        for item in &select.projection {
+            names.push(match item {
+                SelectItem::UnnamedExpr(expr) => column_name(expr),
+                SelectItem::ExprWithAlias { alias, .. } => alias.value.clone(),
+                _ => return None,
+            });
+        }
+        let mut rows: Output = Vec::with_capacity(records.len());
+        for record in records {
+            let values = select.projection.iter()
+                .map(|item| match item {
+                    SelectItem::UnnamedExpr(expr) | SelectItem::ExprWithAlias { expr, .. } => self.eval(expr, &record),
+                    _ => Value::Null,
+                })
+                .collect::<Vec<_>>();
+            if select.distinct.is_some() && rows.iter().any(|(_, row)| same_row(row, &values)) {
+                continue;
+            }
+            rows.push((record, values));
+        }
+        Some((names, rows))
+    }
+
+    /// Records of a FROM item: a relation and the relations joined to it
+    fn table_with_joins(&mut self, item: &TableWithJoins) -> Option<Vec<Record>> {
+        let mut records = self.relation(&item.relation)?;
+        for join in &item.joins {
+            let right = self.relation(&join.relation)?;
+            let (condition, outer_join) = match &join.join_operator {
+                JoinOperator::Join(constraint) | JoinOperator::Inner(constraint) => (on_condition(constraint)?, false),
+                JoinOperator::Left(constraint) | JoinOperator::LeftOuter(constraint) => (on_condition(constraint)?, true),
+                JoinOperator::CrossJoin(JoinConstraint::None) => (None, false),
+                _ => return None,
+            };
+
+            let mut joined = Vec::new();
+            for left in &records {
+                let mut matched = false;
+                for right in &right {
+                    let record = [left.clone(), right.clone()].concat();
+                    if condition.is_none_or(|condition| is_true(&self.eval(condition, &record))) {
+                        joined.push(record);
+                        matched = true;
+                    }
+                }
+                // A column of a relation left out reads as NULL
+                if outer_join && !matched {
+                    joined.push(left.clone());
+                }
+            }
+            records = joined;
+        }
+        Some(records)
+    }
+
+    /// Records of one relation: a system view, an empty catalog, or a subquery
+    fn relation(&mut self, factor: &TableFactor) -> Option<Vec<Record>> {
+        match factor {
+            TableFactor::Table { name, alias, args: None, .. } => {
+                let table_name = object_name(name);
+                let qualifier = alias.as_ref()
+                    .map(|alias| alias.name.value.to_lowercase())
+                    .unwrap_or_else(|| table_name.rsplit('.').next().unwrap_or_default().to_string());
+                if is_empty_catalog(&table_name, self.db) {
+                    self.reads_catalog = true;
+                    return Some(Vec::new());
+                }
+                // A user table isn't read here
+                SystemView::from_name(&table_name)?;
+                self.reads_catalog = true;
+
+                let schema = self.db.get_schema(&table_name).ok()?;
+                let rows = self.db.scan_table(&table_name).ok()?;
+                Some(rows.into_iter()
+                    .map(|row| schema.columns.iter()
+                        .map(|column| format!("{}.{}", qualifier, column.name))
+                        .zip(row.values)
+                        .collect())
+                    .collect())
+            }
+            TableFactor::Derived { subquery, alias: Some(alias), .. } => {
+                let (names, rows) = self.query(subquery, &Vec::new())?;
+                let qualifier = alias.name.value.to_lowercase();
+                Some(rows.into_iter()
+                    .map(|(_, values)| names.iter()
+                        .map(|name| format!("{}.{}", qualifier, name))
+                        .zip(values)
+                        .collect())
+                    .collect())
+            }
+            _ => None,
+        }
+    }
+
+    /// Value of an expression for a record
+    /// What the interpreter can't evaluate is NULL, as psql's queries only
+    /// need what they read from flint's catalogs to be right
+    fn eval(&mut self, expr: &Expr, record: &Record) -> Value {
+        match expr {
+            Expr::Identifier(ident) => lookup(record, None, ident),
+            Expr::CompoundIdentifier(parts) => match parts.as_slice() {
+                [.., qualifier, column] => lookup(record, Some(qualifier), column),
+                [column] => lookup(record, None, column),
+                [] => Value::Null,
+            },
+            Expr::Value(value) => match &value.value {
+                sqlparser::ast::Value::Number(number, _) => number.parse::<i64>().map(Value::Int)
+                    .or_else(|_| number.parse::<f64>().map(Value::Float))
+                    .unwrap_or(Value::Null),
+                sqlparser::ast::Value::SingleQuotedString(text)
+                | sqlparser::ast::Value::EscapedStringLiteral(text) => Value::String(text.clone()),
+                sqlparser::ast::Value::Boolean(b) => Value::Bool(*b),
+                _ => Value::Null,
+            },
+            // Casts to the object id types only change how values print
+            Expr::Nested(inner) | Expr::Collate { expr: inner, .. } | Expr::Cast { expr: inner, .. } => {
+                self.eval(inner, record)
+            }
+            Expr::UnaryOp { op: UnaryOperator::Not, expr } => match self.eval(expr, record) {
+                Value::Bool(b) => Value::Bool(!b),
+                _ => Value::Null,
+            },
+            Expr::IsNull(inner) => Value::Bool(matches!(self.eval(inner, record), Value::Null)),
+            Expr::IsNotNull(inner) => Value::Bool(!matches!(self.eval(inner, record), Value::Null)),
+            Expr::InList { expr, list, negated } => {
+                let value = self.eval(expr, record);
+                let items: Vec<Value> = list.iter().map(|item| self.eval(item, record)).collect();
+                in_values(&value, &items, *negated)
+            }
+            Expr::InSubquery { expr, subquery, negated } => {
+                let value = self.eval(expr, record);
+                let Some((_, rows)) = self.query(subquery, record) else {
+                    return Value::Null;
+                };
+                let items: Vec<Value> = rows.into_iter()
+                    .filter_map(|(_, values)| values.into_iter().next())
+                    .collect();
+                in_values(&value, &items, *negated)
+            }
+            Expr::Like { negated, expr, pattern, escape_char, any: false } => {
+                self.like(expr, pattern, escape_char.as_ref(), false, *negated, record)
+            }
+            Expr::ILike { negated, expr, pattern, escape_char, any: false } => {
+                self.like(expr, pattern, escape_char.as_ref(), true, *negated, record)
+            }
+            Expr::BinaryOp { left, op, right } => self.binary_op(left, op, right, record),
+            Expr::Case { operand, conditions, else_result, .. } => {
+                let operand = operand.as_ref().map(|operand| self.eval(operand, record));
+                for when in conditions {
+                    let condition = self.eval(&when.condition, record);
+                    let holds = match &operand {
+                        Some(operand) => compare(operand, &condition) == Some(Ordering::Equal),
+                        None => is_true(&condition),
+                    };
+                    if holds {
+                        return self.eval(&when.result, record);
+                    }
+                }
+                else_result.as_ref().map_or(Value::Null, |result| self.eval(result, record))
+            }
+            Expr::Subquery(subquery) => self.query(subquery, record)
+                .and_then(|(_, rows)| rows.into_iter().next())
+                .and_then(|(_, values)| values.into_iter().next())
+                .unwrap_or(Value::Null),
+            Expr::Exists { subquery, negated } => match self.query(subquery, record) {
+                Some((_, rows)) => Value::Bool(rows.is_empty() == *negated),
+                None => Value::Null,
+            },
+            Expr::Function(func) => {
+                let args: Vec<Value> = match &func.args {
+                    FunctionArguments::None => Vec::new(),
+                    FunctionArguments::List(list) => list.args.iter()
+                        .map(|arg| match arg {
+                            FunctionArg::Unnamed(FunctionArgExpr::Expr(arg)) => self.eval(arg, record),
+                            _ => Value::Null,
+                        })
+                        .collect(),
+                    FunctionArguments::Subquery(_) => return Value::Null,
+                };
+                self.function(&object_name(&func.name), &args)
+            }
+            _ => Value::Null,
+        }
+    }
+
+    /// `expr [NOT] [I]LIKE pattern [ESCAPE c]`
+    fn like(&mut self, expr: &Expr, pattern: &Expr, escape_char: Option<&sqlparser::ast::Value>, case_insensitive: bool, negated: bool, record: &Record) -> Value {
+        let (Value::String(text), Value::String(pattern)) = (self.eval(expr, record), self.eval(pattern, record)) else {
+            return Value::Null;
+        };
+        let escape = match escape_char {
+            None => Some('\\'),
+            Some(sqlparser::ast::Value::SingleQuotedString(escape)) => escape.chars().next(),
+            Some(_) => return Value::Null,
+        };
+
+        let mut regex = String::from(if case_insensitive { "(?si)^" } else { "(?s)^" });
+        let mut chars = pattern.chars();
+        while let Some(c) = chars.next() {
+            match c {
+                c if Some(c) == escape => match chars.next() {
+                    Some(escaped) => regex.push_str(&regex::escape(&escaped.to_string())),
+                    None => return Value::Null,
+                },
+                '%' => regex.push_str(".*"),
+                '_' => regex.push('.'),
+                c => regex.push_str(&regex::escape(&c.to_string())),
+            }
+        }
+        regex.push('$');
+        match Regex::new(&regex) {
+            Ok(regex) => Value::Bool(regex.is_match(&text) != negated),
+            Err(_) => Value::Null,
+        }
+    }
+
+    fn binary_op(&mut self, left: &Expr, op: &BinaryOperator, right: &Expr, record: &Record) -> Value {
+        let left = self.eval(left, record);
+        let right = self.eval(right, record);
+        match op {
+            // Three-valued logic: NULL only where the known side can't decide
+            BinaryOperator::And => match (&left, &right) {
+                (Value::Bool(false), _) | (_, Value::Bool(false)) => Value::Bool(false),
+                (Value::Bool(true), Value::Bool(true)) => Value::Bool(true),
+                _ => Value::Null,
+            },
+            BinaryOperator::Or => match (&left, &right) {
+                (Value::Bool(true), _) | (_, Value::Bool(true)) => Value::Bool(true),
+                (Value::Bool(false), Value::Bool(false)) => Value::Bool(false),
+                _ => Value::Null,
+            },
+            BinaryOperator::Eq
+            | BinaryOperator::NotEq
+            | BinaryOperator::Lt
+            | BinaryOperator::LtEq
+            | BinaryOperator::Gt
+            | BinaryOperator::GtEq => match compare(&left, &right) {
+                Some(ordering) => Value::Bool(match op {
+                    BinaryOperator::Eq => ordering.is_eq(),
+                    BinaryOperator::NotEq => ordering.is_ne(),
+                    BinaryOperator::Lt => ordering.is_lt(),
+                    BinaryOperator::LtEq => ordering.is_le(),
+                    BinaryOperator::Gt => ordering.is_gt(),
+                    _ => ordering.is_ge(),
+                }),
+                None => Value::Null,
+            },
+            BinaryOperator::StringConcat => match (left, right) {
+                (Value::Null, _) | (_, Value::Null) => Value::Null,
+                (left, right) => Value::String(format!("{}{}", text_of(&left), text_of(&right))),
+            },
+            BinaryOperator::PGRegexMatch => regex_match(&left, &right, false, false),
+            BinaryOperator::PGRegexIMatch => regex_match(&left, &right, true, false),
+            BinaryOperator::PGRegexNotMatch => regex_match(&left, &right, false, true),
+            BinaryOperator::PGRegexNotIMatch => regex_match(&left, &right, true, true),
+            // OPERATOR(pg_catalog.~), as psql writes the operators it uses
+            BinaryOperator::PGCustomBinaryOperator(parts) => match parts.last().map(String::as_str) {
+                Some("~") => regex_match(&left, &right, false, false),
+                Some("~*") => regex_match(&left, &right, true, false),
+                Some("!~") => regex_match(&left, &right, false, true),
+                Some("!~*") => regex_match(&left, &right, true, true),
+                Some("=") => Value::Bool(compare(&left, &right) == Some(Ordering::Equal)),
+                _ => Value::Null,
+            },
+            _ => Value::Null,
+        }
+    }
+
+    /// Functions of psql's queries; any other is NULL
+    fn function(&mut self, name: &str, args: &[Value]) -> Value {
+        let name = name.strip_prefix("pg_catalog.").unwrap_or(name);
+        match (name, args) {
+            // Every table is visible: flint has a single schema besides the
+            // temporary ones
+            ("pg_table_is_visible", _) => Value::Bool(true),
+            ("pg_get_userbyid", _) => Value::String(system::OWNER_NAME.to_string()),
+            ("format_type", [Value::Int(type_oid), typmod]) => {
+                let typmod = match typmod {
+                    Value::Int(typmod) => *typmod,
+                    _ => -1,
+                };
+                let type_name = self.rows_of("pg_type")
+                    .into_iter()
+                    .find(|values| matches!(values.first(), Some(Value::Int(oid)) if oid == type_oid))
+                    .and_then(|values| match values.into_iter().nth(1) {
+                        Some(Value::String(name)) => Some(name),
+                        _ => None,
+                    });
+                system::format_type(*type_oid, typmod, type_name.as_deref())
+                    .map_or(Value::Null, Value::String)
+            }
+            ("obj_description", [oid, ..]) => self.description(oid, 0),
+            ("col_description", [oid, Value::Int(column)]) => self.description(oid, *column),
+            (name, args) => match builtins::lookup(name) {
+                Some(builtin) if builtin.name != "pg_sleep" => builtin.call(args).unwrap_or(Value::Null),
+                _ => Value::Null,
+            },
+        }
+    }
+
+    /// Comment on a table (column 0) or on one of its columns
+    fn description(&mut self, oid: &Value, column: i64) -> Value {
+        self.rows_of("pg_description")
+            .into_iter()
+            .find(|values| compare(&values[0], oid) == Some(Ordering::Equal) && matches!(values[2], Value::Int(n) if n == column))
+            .map_or(Value::Null, |values| values[3].clone())
+    }
+
+    /// Rows of a system view
+    fn rows_of(&mut self, view: &str) -> Vec<Vec<Value>> {
+        self.db.scan_table(view)
+            .map(|rows| rows.into_iter().map(|row| row.values).collect())
+            .unwrap_or_default()
+    }
+}
+
+/// Whether a query uses what only the interpreter supports: catalogs flint
+/// has no view of, outer joins, UNION, subquery expressions, regular
+/// expression matches, COLLATE or pg_catalog-qualified functions
+fn beyond_planner(query: &Query, db: &Database) -> bool {
+    fn set_expr_beyond(body: &SetExpr) -> bool {
+        match body {
+            SetExpr::Select(select) => select.from.iter()
+                .flat_map(|item| &item.joins)
+                .any(|join| matches!(join.join_operator, JoinOperator::Left(_) | JoinOperator::LeftOuter(_))),
+            SetExpr::Query(query) => set_expr_beyond(&query.body),
+            _ => true,
+        }
+    }
+
+    set_expr_beyond(&query.body)
+        || visit_relations(query, |relation| {
+            if is_empty_catalog(&object_name(relation), db) {
+                ControlFlow::Break(())
+            } else {
+                ControlFlow::Continue(())
+            }
+        }).is_break()
+        || visit_expressions(query, |expr| {
+            let beyond = match expr {
+                Expr::Subquery(_) | Expr::Exists { .. } | Expr::InSubquery { .. } | Expr::Collate { .. } => true,
+                Expr::BinaryOp { op, .. } => matches!(op,
+                    BinaryOperator::PGRegexMatch
+                    | BinaryOperator::PGRegexIMatch
+                    | BinaryOperator::PGRegexNotMatch
+                    | BinaryOperator::PGRegexNotIMatch
+                    | BinaryOperator::PGCustomBinaryOperator(_)
+                ),
+                Expr::Function(func) => object_name(&func.name).starts_with("pg_catalog."),
+                _ => false,
+            };
+            if beyond { ControlFlow::Break(()) } else { ControlFlow::Continue(()) }
+        }).is_break()
+}
+
+/// Whether a relation is a catalog flint keeps nothing in: a pg_catalog
+/// relation that is not a system view, where no table of that name hides it
+/// Temporary tables live in `pg_temp_<n>`, not pg_catalog
+fn is_empty_catalog(name: &str, db: &Database) -> bool {
+    let in_catalog = name.starts_with("pg_catalog.") || (name.starts_with("pg_") && !name.contains('.'));
+    in_catalog && SystemView::from_name(name).is_none() && db.get_schema(name).is_err()
+}
+
+/// The expression of an ON join constraint, None for a cross join
+fn on_condition(constraint: &JoinConstraint) -> Option<Option<&Expr>> {
+    match constraint {
+        JoinConstraint::On(condition) => Some(Some(condition)),
+        JoinConstraint::None => Some(None),
+        _ => None,
+    }
+}
+
+/// Value of a column in a record: the first of that name, or of that name
+/// under the qualifier, so a subquery's own columns hide the outer query's
+/// A column not in the record is NULL: it belongs to a catalog with no rows,
+/// or to the missing side of an outer join
+fn lookup(record: &Record, qualifier: Option<&Ident>, column: &Ident) -> Value {
+    let column = ident_name(column);
+    let key = qualifier.map(|qualifier| format!("{}.{}", ident_name(qualifier), column));
+    record.iter()
+        .find(|(name, _)| match &key {
+            Some(key) => name == key,
+            None => name.rsplit_once('.').is_some_and(|(_, name)| name == column),
+        })
+        .map_or(Value::Null, |(_, value)| value.clone())
+}
+
+/// Name an identifier refers to: as written if quoted, otherwise lowercased
+fn ident_name(ident: &Ident) -> String {
+    if ident.quote_style.is_some() { ident.value.clone() } else { ident.value.to_lowercase() }
+}
+
+fn object_name(name: &ObjectName) -> String {
+    name.0.iter()
+        .filter_map(|part| part.as_ident())
+        .map(ident_name)
+        .collect::<Vec<_>>()
+        .join(".")
+}
+
+/// Name of an unaliased result column, as Postgres names it
+fn column_name(expr: &Expr) -> String {
+    match expr {
+        Expr::Identifier(ident) => ident.value.clone(),
+        Expr::CompoundIdentifier(parts) => parts.last().map_or_else(String::new, |part| part.value.clone()),
+        Expr::Function(func) => func.name.0.last()
+            .and_then(|part| part.as_ident())
+            .map_or_else(|| "?column?".to_string(), |ident| ident.value.clone()),
+        Expr::Nested(inner) | Expr::Collate { expr: inner, .. } => column_name(inner),
+        _ => "?column?".to_string(),
+    }
+}
+
+/// Compare two values, reading a string compared with a number as the number,
+/// as Postgres reads `c.oid = '16384'`; None if either is NULL
+fn compare(left: &Value, right: &Value) -> Option<Ordering> {
+    match (left, right) {
+        (Value::Null, _) | (_, Value::Null) => None,
+        (Value::Int(left), Value::String(right)) => right.parse::<i64>().ok().map(|right| left.cmp(&right)),
+        (Value::String(left), Value::Int(right)) => left.parse::<i64>().ok().map(|left| left.cmp(right)),
+        (left, right) => evaluator::compare_values(left, right).ok(),
+    }
+}
+
+fn in_values(value: &Value, items: &[Value], negated: bool) -> Value {
+    if matches!(value, Value::Null) {
+        return Value::Null;
+    }
+    let found = items.iter().any(|item| compare(value, item) == Some(Ordering::Equal));
+    Value::Bool(found != negated)
+}
+
+/// `text ~ pattern` and its case-insensitive and negated forms
+fn regex_match(text: &Value, pattern: &Value, case_insensitive: bool, negated: bool) -> Value {
+    let (Value::String(text), Value::String(pattern)) = (text, pattern) else {
+        return Value::Null;
+    };
+    let pattern = if case_insensitive { format!("(?i){}", pattern) } else { pattern.clone() };
+    match Regex::new(&pattern) {
+        Ok(regex) => Value::Bool(regex.is_match(text) != negated),
+        Err(_) => Value::Null,
+    }
+}
+
+fn is_true(value: &Value) -> bool {
+    matches!(value, Value::Bool(true))
+}
+
+/// A LIMIT or OFFSET count
+fn count(value: &Value) -> Option<usize> {
+    match value {
+        Value::Int(count) => usize::try_from(*count).ok(),
+        _ => None,
+    }
+}
+
+fn same_row(a: &[Value], b: &[Value]) -> bool {
+    a.iter().zip(b).all(|(a, b)| match (a, b) {
+        (Value::Null, Value::Null) => true,
+        (a, b) => compare(a, b) == Some(Ordering::Equal),
+    })
+}
+
+fn text_of(value: &Value) -> String {
+    match value {
+        Value::String(text) => text.clone(),
+        Value::Int(n) => n.to_string(),
+        Value::Float(f) => f.to_string(),
+        Value::Bool(b) => if *b { "t" } else { "f" }.to_string(),
+        _ => String::new(),
+    }
+}
+
+fn data_type_of(value: &Value) -> DataType {
+    match value {
+        Value::Int(_) => DataType::Int,
+        Value::Float(_) => DataType::Float,
+        Value::Bool(_) => DataType::Bool,
+        _ => DataType::String,
+    }
+}
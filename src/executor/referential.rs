@@ -0,0 +1,229 @@
+//! FOREIGN KEY enforcement
+//!
+//! A row written to a table with foreign keys has to refer to an existing row
+//! of each referenced table, found with a lookup in that table's primary index.
+//! A row other rows refer to can't be deleted or have its key changed, except
+//! that ON DELETE CASCADE deletes the referring rows along with it. A DELETE is
+//! worked out in full before anything is written: the cascade is followed
+//! through every table it reaches, each row visited once so cycles end, and
+//! only then are the remaining references checked. A reference found that way
+//! leaves every table as it was.
+//!
+//! Referring rows are found through a B-tree index led by the referencing
+//! column when the referring table has one, and with a scan of it otherwise.
+
+use std::collections::{HashMap, HashSet};
+
+use tracing::debug;
+
+use crate::executor::error::ExecutorError;
+use crate::storage::catalog::{ForeignKey, ReferentialAction};
+use crate::storage::index::key::key_values_equal;
+use crate::storage::{Database, TuplePointer};
+use crate::types::{Row, Schema, Value};
+
+pub type Result<T> = std::result::Result<T, ExecutorError>;
+
+/// Rows a DELETE removes, by table: the rows it matched and the rows its
+/// cascades reached
+pub type DeletePlan = Vec<(String, Vec<TuplePointer>)>;
+
+/// Check that each row about to be written to `table_name` refers to existing
+/// rows through every foreign key of the table; NULL refers to nothing
+pub fn check_references(db: &Database, table_name: &str, schema: &Schema, rows: &[Row]) -> Result<()> {
+    let foreign_keys = db.table_foreign_keys(table_name)
+        .map_err(ExecutorError::Execution)?;
+    for foreign_key in &foreign_keys {
+        let column_idx = column_index(schema, table_name, &foreign_key.column)?;
+        let referenced_schema = db.get_schema(&foreign_key.referenced_table)
+            .map_err(ExecutorError::Execution)?;
+        let referenced_idx = column_index(&referenced_schema, &foreign_key.referenced_table, &foreign_key.referenced_column)?;
+
+        for row in rows {
+            let value = &row.values[column_idx];
+            if matches!(value, Value::Null) {
+                continue;
+            }
+            // A row of a self-referencing table may refer to a row written with it
+            if foreign_key.referenced_table == table_name
+                && rows.iter().any(|other| key_values_equal(&other.values[referenced_idx], value))
+            {
+                continue;
+            }
+            if !referenced_row_exists(db, &foreign_key.referenced_table, value)? {
+                return Err(ExecutorError::ForeignKeyViolation(format!(
+                    "insert or update on table \"{}\" violates foreign key constraint \"{}\": Key ({})=({}) is not present in table \"{}\"",
+                    table_name, foreign_key.name, foreign_key.column, value.as_string(), foreign_key.referenced_table
+                )));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Check that an UPDATE of `table_name` doesn't take away a key other rows
+/// refer to; `changes` holds each updated row before and after
+/// A key that another updated row takes on is not taken away
+pub fn check_key_changes(db: &Database, table_name: &str, schema: &Schema, changes: &[(Row, Row)]) -> Result<()> {
+    for (referencing_table, foreign_key) in db.referencing_foreign_keys(table_name) {
+        let referenced_idx = column_index(schema, table_name, &foreign_key.referenced_column)?;
+        let removed: Vec<Value> = changes.iter()
+            .map(|(old_row, _)| &old_row.values[referenced_idx])
+            .filter(|old| !matches!(old, Value::Null))
+            .filter(|old| !changes.iter().any(|(_, new_row)| key_values_equal(&new_row.values[referenced_idx], old)))
+            .cloned()
+            .collect();
+        if removed.is_empty() {
+            continue;
+        }
+        if let Some((_, row)) = referencing_rows(db, &referencing_table, &foreign_key, &removed)?.first() {
+            return Err(still_referenced(table_name, &referencing_table, &foreign_key, row, db)?);
+        }
+    }
+    Ok(())
+}
+
+/// Work out everything a DELETE of `rows` from `table_name` removes, failing if
+/// a row left behind would still refer to one of them
+pub fn plan_delete(db: &Database, table_name: &str, rows: Vec<(TuplePointer, Row)>) -> Result<DeletePlan> {
+    // Follow the cascades first: the set of rows deleted is only known once
+    // every table they reach has been visited
+    let mut deleted: HashMap<String, Vec<(TuplePointer, Row)>> = HashMap::new();
+    let mut visited: HashSet<(String, TuplePointer)> = rows.iter()
+        .map(|(tuple_ptr, _)| (table_name.to_string(), *tuple_ptr))
+        .collect();
+    let mut pending = vec![(table_name.to_string(), rows)];
+    while let Some((table, rows)) = pending.pop() {
+        if rows.is_empty() {
+            continue;
+        }
+        let schema = db.get_schema(&table)
+            .map_err(ExecutorError::Execution)?;
+        for (referencing_table, foreign_key) in db.referencing_foreign_keys(&table) {
+            if foreign_key.on_delete != ReferentialAction::Cascade {
+                continue;
+            }
+            let keys = referenced_keys(&schema, &table, &foreign_key, &rows)?;
+            let cascaded: Vec<(TuplePointer, Row)> = referencing_rows(db, &referencing_table, &foreign_key, &keys)?
+                .into_iter()
+                .filter(|(tuple_ptr, _)| visited.insert((referencing_table.clone(), *tuple_ptr)))
+                .collect();
+            if !cascaded.is_empty() {
+                debug!(table = %referencing_table, constraint = %foreign_key.name, rows = cascaded.len(), "cascading delete");
+                pending.push((referencing_table, cascaded));
+            }
+        }
+        deleted.entry(table).or_default().extend(rows);
+    }
+
+    // Then any other reference to a deleted row has to come from a row that is
+    // deleted too
+    for (table, rows) in &deleted {
+        let schema = db.get_schema(table)
+            .map_err(ExecutorError::Execution)?;
+        for (referencing_table, foreign_key) in db.referencing_foreign_keys(table) {
+            if foreign_key.on_delete == ReferentialAction::Cascade {
+                continue;
+            }
+            let keys = referenced_keys(&schema, table, &foreign_key, rows)?;
+            let remaining = referencing_rows(db, &referencing_table, &foreign_key, &keys)?
+                .into_iter()
+                .find(|(tuple_ptr, _)| !visited.contains(&(referencing_table.clone(), *tuple_ptr)));
+            if let Some((_, row)) = remaining {
+                return Err(still_referenced(table, &referencing_table, &foreign_key, &row, db)?);
+            }
+        }
+    }
+
+    let mut plan: DeletePlan = deleted.into_iter()
+        .map(|(table, rows)| (table, rows.into_iter().map(|(tuple_ptr, _)| tuple_ptr).collect()))
+        .collect();
+    plan.sort_by(|a, b| a.0.cmp(&b.0));
+    Ok(plan)
+}
+
+/// Check that TRUNCATE of `tables` leaves no table referring to them
+pub fn check_truncate(db: &Database, tables: &[String]) -> Result<()> {
+    for table in tables {
+        if let Some((referencing_table, foreign_key)) = db.referencing_foreign_keys(table)
+            .into_iter()
+            .find(|(referencing_table, _)| !tables.contains(referencing_table))
+        {
+            return Err(ExecutorError::UnsupportedStatement(format!(
+                "cannot truncate a table referenced in a foreign key constraint: table \"{}\" references \"{}\" through \"{}\"",
+                referencing_table, table, foreign_key.name
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// Values of the referenced column among rows of the referenced table
+fn referenced_keys(schema: &Schema, table_name: &str, foreign_key: &ForeignKey, rows: &[(TuplePointer, Row)]) -> Result<Vec<Value>> {
+    let referenced_idx = column_index(schema, table_name, &foreign_key.referenced_column)?;
+    Ok(rows.iter()
+        .map(|(_, row)| row.values[referenced_idx].clone())
+        .filter(|value| !matches!(value, Value::Null))
+        .collect())
+}
+
+/// Whether a row of `table_name` has `value` as its primary key
+fn referenced_row_exists(db: &Database, table_name: &str, value: &Value) -> Result<bool> {
+    let row = db.get_by_key(table_name, value)
+        .map_err(ExecutorError::Execution)?;
+    Ok(row.is_some())
+}
+
+/// Rows of `table_name` whose foreign key column holds one of `keys`
+fn referencing_rows(db: &Database, table_name: &str, foreign_key: &ForeignKey, keys: &[Value]) -> Result<Vec<(TuplePointer, Row)>> {
+    if keys.is_empty() {
+        return Ok(Vec::new());
+    }
+    let schema = db.get_schema(table_name)
+        .map_err(ExecutorError::Execution)?;
+    let column_idx = column_index(&schema, table_name, &foreign_key.column)?;
+    let refers = |row: &Row| keys.iter().any(|key| key_values_equal(&row.values[column_idx], key));
+
+    // Index keys don't keep every detail of their values, so each row found
+    // is still compared
+    if let Some((index_name, _)) = db.lookup_index(table_name, &[foreign_key.column.as_str()])
+        .map_err(ExecutorError::Execution)?
+    {
+        let mut seen = HashSet::new();
+        let mut rows = Vec::new();
+        for key in keys {
+            for (tuple_ptr, row) in db.search_index_prefix(table_name, &index_name, std::slice::from_ref(key))
+                .map_err(ExecutorError::Execution)?
+            {
+                if refers(&row) && seen.insert(tuple_ptr) {
+                    rows.push((tuple_ptr, row));
+                }
+            }
+        }
+        debug!(table = %table_name, index = %index_name, rows = rows.len(), "found referring rows through index");
+        return Ok(rows);
+    }
+
+    Ok(db.scan_table_tuples(table_name)
+        .map_err(ExecutorError::Execution)?
+        .into_iter()
+        .filter(|(_, row)| refers(row))
+        .collect())
+}
+
+/// Error for a row of `table_name` that `row` of `referencing_table` still refers to
+fn still_referenced(table_name: &str, referencing_table: &str, foreign_key: &ForeignKey, row: &Row, db: &Database) -> Result<ExecutorError> {
+    let schema = db.get_schema(referencing_table)
+        .map_err(ExecutorError::Execution)?;
+    let column_idx = column_index(&schema, referencing_table, &foreign_key.column)?;
+    Ok(ExecutorError::ForeignKeyViolation(format!(
+        "update or delete on table \"{}\" violates foreign key constraint \"{}\" on table \"{}\": Key ({})=({}) is still referenced from table \"{}\"",
+        table_name, foreign_key.name, referencing_table, foreign_key.referenced_column,
+        row.values[column_idx].as_string(), referencing_table
+    )))
+}
+
+fn column_index(schema: &Schema, table_name: &str, column: &str) -> Result<usize> {
+    schema.get_column_index(column)
+        .ok_or_else(|| ExecutorError::Execution(format!("Column not found: {}.{}", table_name, column)))
+}
@@ -0,0 +1,206 @@
+//! Schemas (CREATE SCHEMA) and the names of the tables in them
+//!
+//! A table of `public` is stored under its own name, as every table was
+//! before there were other schemas, and a table of a schema made by CREATE
+//! SCHEMA is stored as `<schema>.<table>`. Before a statement runs, each of
+//! its table names is rewritten to the name the table is stored under: a name
+//! qualified with `public` loses the schema, one qualified with another
+//! schema keeps it once the schema is known to exist, and an unqualified name
+//! is looked up in the schemas of the session's search_path in order, as in
+//! Postgres. A CREATE TABLE of an unqualified name makes the table in the
+//! first schema of the search path that exists. Names in the system schemas
+//! are left to the system views, and those in temporary schemas to `temp`.
+
+use std::ops::ControlFlow;
+
+use sqlparser::ast::{CommentObject, CopySource, Ident, ObjectName, Statement, TableAlias, TableFactor, VisitMut, VisitorMut};
+
+use crate::executor::error::ExecutorError;
+use crate::storage::system::PUBLIC_SCHEMA;
+use crate::storage::Database;
+
+pub type Result<T> = std::result::Result<T, ExecutorError>;
+
+/// Schemas whose names the system views answer to
+const SYSTEM_SCHEMAS: &[&str] = &["pg_catalog", "information_schema"];
+
+/// Whether `schema` is a system schema or a temporary one, which no table
+/// made by CREATE TABLE can be in
+pub fn is_reserved(schema: &str) -> bool {
+    SYSTEM_SCHEMAS.contains(&schema) || schema.starts_with("pg_")
+}
+
+/// The statement with each table name replaced by the name the table is
+/// stored under, for a session of database `database` with search path
+/// `search_path`
+pub fn resolve(stmt: &Statement, db: &Database, search_path: &[String], database: &str) -> Result<Statement> {
+    let mut stmt = stmt.clone();
+    let mut names = SchemaNames { db, search_path, database };
+    let flow = match &mut stmt {
+        // The name of a table being created isn't looked up but placed
+        Statement::CreateTable(ct) => {
+            if !ct.temporary {
+                names.place(&mut ct.name)?;
+            }
+            match &mut ct.query {
+                Some(query) => query.visit(&mut names),
+                None => ControlFlow::Continue(()),
+            }
+        }
+        stmt => stmt.visit(&mut names),
+    };
+    match flow {
+        ControlFlow::Break(e) => Err(e),
+        ControlFlow::Continue(()) => Ok(stmt),
+    }
+}
+
+struct SchemaNames<'a> {
+    db: &'a Database,
+    search_path: &'a [String],
+    database: &'a str,
+}
+
+impl SchemaNames<'_> {
+    /// Parts of `name` after the current database, if it names one
+    fn parts(&self, name: &ObjectName) -> Result<Option<Vec<Ident>>> {
+        let Some(mut idents) = name.0.iter().map(|part| part.as_ident().cloned()).collect::<Option<Vec<_>>>() else {
+            return Ok(None);
+        };
+        if idents.len() == 3 {
+            if idents[0].value != self.database {
+                return Err(ExecutorError::UnsupportedStatement(format!(
+                    "cross-database references are not implemented: {}",
+                    name
+                )));
+            }
+            idents.remove(0);
+        }
+        Ok(Some(idents))
+    }
+
+    /// Check that `schema` can hold tables
+    fn check_schema(&self, schema: &str) -> Result<()> {
+        if !self.db.schema_exists(schema) {
+            return Err(ExecutorError::InvalidSchema(format!("schema \"{}\" does not exist", schema)));
+        }
+        Ok(())
+    }
+
+    /// Rewrite `name` to the name its table is stored under, returning the
+    /// table's own name if that is now qualified by a schema
+    fn rewrite(&self, name: &mut ObjectName) -> Result<Option<Ident>> {
+        let Some(idents) = self.parts(name)? else {
+            return Ok(None);
+        };
+        match idents.as_slice() {
+            [schema, _] if is_reserved(&schema.value) => Ok(None),
+            [schema, table_name] if schema.value == PUBLIC_SCHEMA => {
+                *name = ObjectName::from(vec![table_name.clone()]);
+                Ok(None)
+            }
+            [schema, table_name] => {
+                self.check_schema(&schema.value)?;
+                *name = ObjectName::from(idents.clone());
+                Ok(Some(table_name.clone()))
+            }
+            [table_name] => {
+                for schema in self.search_path {
+                    if schema == PUBLIC_SCHEMA {
+                        break;
+                    }
+                    if self.db.schema_exists(schema)
+                        && self.db.get_table(&format!("{}.{}", schema, table_name.value)).is_ok()
+                    {
+                        *name = ObjectName::from(vec![Ident::new(schema), table_name.clone()]);
+                        return Ok(Some(table_name.clone()));
+                    }
+                }
+                Ok(None)
+            }
+            _ => Ok(None),
+        }
+    }
+
+    /// Rewrite the name of a table being created to the name it is stored
+    /// under: in the schema given, or else the first of the search path
+    fn place(&self, name: &mut ObjectName) -> Result<()> {
+        let Some(idents) = self.parts(name)? else {
+            return Ok(());
+        };
+        match idents.as_slice() {
+            [schema, _] if schema.value.starts_with("pg_temp") => {}
+            [schema, _] if is_reserved(&schema.value) => {
+                return Err(ExecutorError::UnsupportedStatement(format!(
+                    "cannot create tables in system schema \"{}\"",
+                    schema.value
+                )));
+            }
+            [schema, table_name] if schema.value == PUBLIC_SCHEMA => *name = ObjectName::from(vec![table_name.clone()]),
+            [schema, _] => {
+                self.check_schema(&schema.value)?;
+                *name = ObjectName::from(idents.clone());
+            }
+            [table_name] => {
+                let schema = self.search_path.iter()
+                    .find(|schema| self.db.schema_exists(schema))
+                    .ok_or_else(|| ExecutorError::InvalidSchema("no schema has been selected to create in".to_string()))?;
+                if schema != PUBLIC_SCHEMA {
+                    *name = ObjectName::from(vec![Ident::new(schema), table_name.clone()]);
+                }
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+}
+
+impl VisitorMut for SchemaNames<'_> {
+    type Break = ExecutorError;
+
+    fn pre_visit_table_factor(&mut self, table_factor: &mut TableFactor) -> ControlFlow<ExecutorError> {
+        // Columns keep being qualified by the name the query gave the table
+        if let TableFactor::Table { name, alias: alias @ None, .. } = table_factor {
+            match self.rewrite(name) {
+                Ok(Some(table_name)) => *alias = Some(TableAlias { name: table_name, columns: Vec::new() }),
+                Ok(None) => {}
+                Err(e) => return ControlFlow::Break(e),
+            }
+        }
+        ControlFlow::Continue(())
+    }
+
+    fn pre_visit_relation(&mut self, relation: &mut ObjectName) -> ControlFlow<ExecutorError> {
+        match self.rewrite(relation) {
+            Ok(_) => ControlFlow::Continue(()),
+            Err(e) => ControlFlow::Break(e),
+        }
+    }
+
+    fn pre_visit_statement(&mut self, statement: &mut Statement) -> ControlFlow<ExecutorError> {
+        // Table names the visitor doesn't see as relations
+        let rewritten = match statement {
+            Statement::Comment { object_type: CommentObject::Table, object_name, .. } => self.rewrite(object_name),
+            Statement::Comment { object_type: CommentObject::Column, object_name, .. } => {
+                match object_name.0.pop() {
+                    Some(column) => {
+                        let rewritten = self.rewrite(object_name);
+                        object_name.0.push(column);
+                        rewritten
+                    }
+                    None => Ok(None),
+                }
+            }
+            Statement::Vacuum(vacuum) => match &mut vacuum.table_name {
+                Some(table_name) => self.rewrite(table_name),
+                None => Ok(None),
+            },
+            Statement::Copy { source: CopySource::Table { table_name, .. }, .. } => self.rewrite(table_name),
+            _ => Ok(None),
+        };
+        match rewritten {
+            Ok(_) => ControlFlow::Continue(()),
+            Err(e) => ControlFlow::Break(e),
+        }
+    }
+}
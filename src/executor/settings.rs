@@ -0,0 +1,283 @@
+//! Run-time parameters of a session (SET, SHOW and RESET)
+//!
+//! Clients set parameters such as application_name or client_encoding when
+//! they connect, and read them back with SHOW. Each session keeps the values
+//! it set over the defaults below; flint only remembers most of them, as
+//! nothing it does depends on them, but lock_timeout limits the session's
+//! waits on the write lock and advisory locks (see `transaction`). Some describe the server and cannot be
+//! set, and client_encoding only takes UTF8, the one encoding flint speaks.
+//! As in Postgres, a name with a dot (`myapp.tenant`) is a placeholder any
+//! session may set, and SET LOCAL lasts until the transaction block ends.
+//! workload_class is kept on the session itself (see `workload`).
+
+use std::time::Duration;
+
+use sqlparser::ast::{ContextModifier, Expr, Ident, ObjectName};
+
+use crate::executor::cursor::Session;
+use crate::executor::error::ExecutorError;
+use crate::storage::system::OWNER_NAME;
+use crate::types::{Column, DataType, Row, Schema, Value};
+
+pub type Result<T> = std::result::Result<T, ExecutorError>;
+
+/// Name SET and RESET take to mean every parameter, as SHOW ALL does
+const ALL: &str = "all";
+
+/// Name of the parameter kept as the session's workload class
+const WORKLOAD_CLASS: &str = "workload_class";
+
+/// Name of the parameter limiting the session's lock waits
+const LOCK_TIMEOUT: &str = "lock_timeout";
+
+struct Parameter {
+    name: &'static str,
+    default: &'static str,
+    /// Whether a session can change it
+    settable: bool,
+    description: &'static str,
+}
+
+const fn parameter(name: &'static str, default: &'static str, settable: bool, description: &'static str) -> Parameter {
+    Parameter { name, default, settable, description }
+}
+
+static PARAMETERS: &[Parameter] = &[
+    parameter("application_name", "", true, "Sets the application name to be reported in statistics and logs."),
+    parameter("client_encoding", "UTF8", true, "Sets the client's character set encoding."),
+    parameter("client_min_messages", "notice", true, "Sets the message levels that are sent to the client."),
+    parameter("DateStyle", "ISO, MDY", true, "Sets the display format for date and time values."),
+    parameter("default_transaction_isolation", "read committed", true, "Sets the transaction isolation level of each new transaction."),
+    parameter("extra_float_digits", "1", true, "Sets the number of digits displayed for floating-point values."),
+    parameter("integer_datetimes", "on", false, "Shows whether datetimes are integer based."),
+    parameter("IntervalStyle", "postgres", true, "Sets the display format for interval values."),
+    parameter("lock_timeout", "0", true, "Sets the maximum allowed duration of any wait for a lock."),
+    parameter("search_path", "\"$user\", public", true, "Sets the schema search order for names that are not schema-qualified."),
+    parameter("server_encoding", "UTF8", false, "Shows the server (database) character set encoding."),
+    parameter("server_version", crate::executor::builtins::POSTGRES_VERSION, false, "Shows the server version."),
+    parameter("standard_conforming_strings", "on", true, "Causes '...' strings to treat backslashes literally."),
+    parameter("statement_timeout", "0", true, "Sets the maximum allowed duration of any statement."),
+    parameter("TimeZone", "UTC", true, "Sets the time zone for displaying and interpreting time stamps."),
+];
+
+/// The parameter a name refers to, in any case
+fn lookup(name: &str) -> Option<&'static Parameter> {
+    PARAMETERS.iter().find(|parameter| parameter.name.eq_ignore_ascii_case(name))
+}
+
+/// Name of the parameter of a SHOW; SHOW TIME ZONE is SHOW TimeZone, as
+/// SET TIME ZONE is SET TimeZone
+pub fn show_name(variable: &[Ident]) -> String {
+    let name = variable.iter().map(|word| word.value.to_lowercase()).collect::<Vec<_>>().join(" ");
+    if name == "time zone" { "timezone".to_string() } else { name }
+}
+
+/// Name of the parameter of a SET
+pub fn set_name(variable: &ObjectName) -> String {
+    variable.0.iter()
+        .filter_map(|part| part.as_ident())
+        .map(|ident| ident.value.to_lowercase())
+        .collect::<Vec<_>>()
+        .join(".")
+}
+
+/// Text of the value given to SET, or None for DEFAULT
+/// Unquoted words are folded to lower case, and a list is separated by
+/// commas, as Postgres does
+pub fn value_text(values: &[Expr]) -> Option<String> {
+    if let [Expr::Identifier(ident)] = values
+        && ident.quote_style.is_none()
+        && ident.value.eq_ignore_ascii_case("default")
+    {
+        return None;
+    }
+    let text = |value: &Expr| match value {
+        Expr::Identifier(ident) if ident.quote_style.is_none() => ident.value.to_lowercase(),
+        Expr::Identifier(ident) => ident.value.clone(),
+        Expr::Value(value) => match &value.value {
+            sqlparser::ast::Value::SingleQuotedString(text) => text.clone(),
+            _ => value.to_string(),
+        },
+        _ => value.to_string(),
+    };
+    Some(values.iter().map(text).collect::<Vec<_>>().join(", "))
+}
+
+/// Set a parameter for the session, or back to its default if `value` is
+/// None; the name `all` resets every parameter
+pub fn set(session: &mut Session, scope: Option<&ContextModifier>, name: &str, value: Option<String>) -> Result<()> {
+    let local = match scope {
+        None | Some(ContextModifier::Session) => false,
+        Some(ContextModifier::Local) => true,
+        Some(ContextModifier::Global) => {
+            return Err(ExecutorError::UnsupportedStatement("SET GLOBAL is not supported".to_string()));
+        }
+    };
+
+    if name == ALL && value.is_none() && !local {
+        session.settings.clear();
+        session.local_settings.clear();
+        session.workload_class = None;
+        return Ok(());
+    }
+
+    let (key, value) = match lookup(name) {
+        Some(parameter) if !parameter.settable => {
+            return Err(ExecutorError::Execution(format!("parameter \"{}\" cannot be changed", parameter.name)));
+        }
+        Some(parameter) => {
+            let value = match value {
+                Some(value) => checked_value(parameter, value)?,
+                None => parameter.default.to_string(),
+            };
+            (parameter.name.to_string(), value)
+        }
+        // Placeholders have no default; DEFAULT leaves them empty
+        None if name.contains('.') => (name.to_string(), value.unwrap_or_default()),
+        None => return Err(unrecognized(name)),
+    };
+
+    // As in Postgres, SET LOCAL outside a transaction block does nothing,
+    // and SET replaces a SET LOCAL of the transaction
+    if local {
+        if session.in_transaction {
+            session.local_settings.insert(key, value);
+        }
+    } else {
+        session.local_settings.remove(&key);
+        match lookup(&key) {
+            Some(parameter) if parameter.default == value => session.settings.remove(&key),
+            _ => session.settings.insert(key, value),
+        };
+    }
+    Ok(())
+}
+
+/// The value given for a parameter, in the form SHOW reports it
+fn checked_value(parameter: &Parameter, value: String) -> Result<String> {
+    if parameter.name == "client_encoding" {
+        return match value.to_lowercase().as_str() {
+            "utf8" | "utf-8" | "unicode" => Ok("UTF8".to_string()),
+            _ => Err(ExecutorError::Execution(format!(
+                "invalid value for parameter \"client_encoding\": \"{}\" (only UTF8 is supported)",
+                value
+            ))),
+        };
+    }
+    if parameter.name == LOCK_TIMEOUT && parse_milliseconds(&value).is_none() {
+        return Err(ExecutorError::Execution(format!(
+            "invalid value for parameter \"{}\": \"{}\"",
+            parameter.name, value
+        )));
+    }
+    Ok(value)
+}
+
+/// Milliseconds of a time parameter: a number of milliseconds, or a number
+/// with a unit of ms, s, min or h
+fn parse_milliseconds(value: &str) -> Option<u64> {
+    let value = value.trim();
+    let digits = value.find(|c: char| !c.is_ascii_digit()).unwrap_or(value.len());
+    let number: u64 = value[..digits].parse().ok()?;
+    let unit = match value[digits..].trim() {
+        "" | "ms" => 1,
+        "s" => 1000,
+        "min" => 60 * 1000,
+        "h" => 60 * 60 * 1000,
+        _ => return None,
+    };
+    number.checked_mul(unit)
+}
+
+fn unrecognized(name: &str) -> ExecutorError {
+    ExecutorError::Execution(format!("unrecognized configuration parameter \"{}\"", name))
+}
+
+/// Value of a parameter in the session, under the name SHOW gives it
+fn current(session: &Session, name: &str) -> Result<(String, String)> {
+    if name == WORKLOAD_CLASS {
+        let class = session.workload_class.map_or("default", |class| class.name());
+        return Ok((WORKLOAD_CLASS.to_string(), class.to_string()));
+    }
+    let key = lookup(name).map_or(name, |parameter| parameter.name);
+    let value = session.local_settings.get(key)
+        .or_else(|| session.settings.get(key))
+        .cloned()
+        .or_else(|| lookup(key).map(|parameter| parameter.default.to_string()))
+        .ok_or_else(|| unrecognized(name))?;
+    Ok((key.to_string(), value))
+}
+
+/// Schemas of the session's search_path, in order, with `$user` naming the
+/// schema of the user every session runs as
+pub fn search_path(session: &Session) -> Vec<String> {
+    let (_, value) = current(session, "search_path").unwrap_or_default();
+    value.split(',')
+        .map(|schema| schema.trim())
+        .filter(|schema| !schema.is_empty())
+        .map(|schema| match schema.strip_prefix('"').and_then(|schema| schema.strip_suffix('"')) {
+            Some("$user") => OWNER_NAME.to_string(),
+            Some(quoted) => quoted.to_string(),
+            None if schema == "$user" => OWNER_NAME.to_string(),
+            None => schema.to_lowercase(),
+        })
+        .collect()
+}
+
+/// Longest the session waits for a lock before its statement fails, or None
+/// to wait as long as it takes, as lock_timeout's default of 0 means
+pub fn lock_timeout(session: &Session) -> Option<Duration> {
+    let (_, value) = current(session, LOCK_TIMEOUT).unwrap_or_default();
+    parse_milliseconds(&value)
+        .filter(|&milliseconds| milliseconds > 0)
+        .map(Duration::from_millis)
+}
+
+fn text_column(name: &str) -> Column {
+    Column { name: name.to_string(), data_type: DataType::String, is_primary_key: false, typmod: None }
+}
+
+/// Columns of the result of SHOW for a parameter, or of SHOW ALL
+pub fn show_schema(name: &str) -> Result<Schema> {
+    if name == ALL {
+        return Ok(Schema::new(vec![text_column("name"), text_column("setting"), text_column("description")]));
+    }
+    let name = match lookup(name) {
+        Some(parameter) => parameter.name,
+        None if name == WORKLOAD_CLASS || name.contains('.') => name,
+        None => return Err(unrecognized(name)),
+    };
+    Ok(Schema::new(vec![text_column(name)]))
+}
+
+/// Result of SHOW for a parameter: one row with its value in a column named
+/// after it; SHOW ALL lists every parameter with its description
+pub fn show(session: &Session, name: &str) -> Result<(Schema, Vec<Row>)> {
+    if name != ALL {
+        let (name, value) = current(session, name)?;
+        return Ok((Schema::new(vec![text_column(&name)]), vec![Row::new(vec![Value::String(value)])]));
+    }
+
+    let mut rows: Vec<(String, String, String)> = PARAMETERS.iter()
+        .map(|parameter| {
+            let (_, value) = current(session, parameter.name)?;
+            Ok((parameter.name.to_string(), value, parameter.description.to_string()))
+        })
+        .collect::<Result<_>>()?;
+    let (_, class) = current(session, WORKLOAD_CLASS)?;
+    rows.push((WORKLOAD_CLASS.to_string(), class, "Sets the workload class of the session's queries.".to_string()));
+    for name in session.settings.keys().chain(session.local_settings.keys()) {
+        if lookup(name).is_none() && !rows.iter().any(|(shown, _, _)| shown == name) {
+            let (name, value) = current(session, name)?;
+            rows.push((name, value, String::new()));
+        }
+    }
+    rows.sort_by_key(|(name, _, _)| name.to_lowercase());
+
+    let rows = rows.into_iter()
+        .map(|(name, value, description)| {
+            Row::new(vec![Value::String(name), Value::String(value), Value::String(description)])
+        })
+        .collect();
+    Ok((show_schema(ALL)?, rows))
+}
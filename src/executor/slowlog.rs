@@ -0,0 +1,46 @@
+//! The slow query log (--log-min-duration-statement=MS)
+//!
+//! The handler times every query; one that runs for at least the threshold
+//! is logged at warn level with its duration, the rows it returned and the
+//! estimated plan of each statement that reads or writes rows, as
+//! auto_explain does for Postgres. Rows are counted as results are encoded,
+//! on the thread the query runs on, so the handler reads them back on that
+//! same thread once the query is done. The plan is made again after the
+//! query ran, only for queries that are logged.
+
+use std::cell::Cell;
+use std::time::Duration;
+
+use tracing::warn;
+
+thread_local! {
+    /// Rows returned by the query running on this thread
+    static ROWS_RETURNED: Cell<u64> = const { Cell::new(0) };
+}
+
+/// Start counting the rows of a query
+pub fn start_query() {
+    ROWS_RETURNED.with(|rows| rows.set(0));
+}
+
+/// Count rows sent back by a statement of the running query
+pub fn count_rows(count: usize) {
+    ROWS_RETURNED.with(|rows| rows.set(rows.get() + count as u64));
+}
+
+/// Rows the last query on this thread returned
+pub fn rows_returned() -> u64 {
+    ROWS_RETURNED.with(Cell::get)
+}
+
+/// Log a query that ran for at least the threshold, with the lines of its
+/// plan
+pub fn log(query: &str, elapsed: Duration, plan: &[String]) {
+    warn!(
+        duration_ms = elapsed.as_secs_f64() * 1000.0,
+        rows = rows_returned(),
+        query = %query,
+        plan = %plan.join("\n"),
+        "slow query"
+    );
+}
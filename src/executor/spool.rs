@@ -0,0 +1,140 @@
+//! Spooling of large query results to disk
+//!
+//! A response is sent as encoded DataRows, built from the query's rows before
+//! the first one goes out. Holding all of them in memory next to the rows
+//! they were encoded from doubles what a large SELECT costs, so once the
+//! encoded rows of a response pass the spool size they are moved to a
+//! temporary file, along with every row encoded after them, and the response
+//! streams them back from the file as the client reads. The file is unlinked
+//! as soon as it is created, so it goes away with the response, or with the
+//! server if it crashes mid-query.
+//!
+//! Layout of a spooled row, integers little endian: i16 field count, u32
+//! length, then the encoded fields.
+
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter, Read, Seek, SeekFrom, Write};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use bytes::BytesMut;
+use pgwire::error::{PgWireError, PgWireResult};
+use pgwire::messages::data::DataRow;
+use tracing::info;
+
+/// Where and past how many bytes responses are spooled
+pub struct SpoolConfig {
+    /// Directory the spool files are created in
+    pub dir: PathBuf,
+    /// Bytes of encoded rows a response keeps in memory
+    pub max_memory: usize,
+}
+
+/// Names spool files apart within the server
+static NEXT_SPOOL: AtomicU64 = AtomicU64::new(0);
+
+/// Encoded rows of one response, in memory until they outgrow it
+pub struct ResultSpool<'a> {
+    config: &'a SpoolConfig,
+    rows: Vec<DataRow>,
+    bytes: usize,
+    file: Option<BufWriter<File>>,
+}
+
+impl<'a> ResultSpool<'a> {
+    pub fn new(config: &'a SpoolConfig) -> Self {
+        ResultSpool {
+            config,
+            rows: Vec::new(),
+            bytes: 0,
+            file: None,
+        }
+    }
+
+    /// Add the next row of the response
+    pub fn push(&mut self, row: DataRow) -> io::Result<()> {
+        if let Some(file) = &mut self.file {
+            return Self::write_row(file, &row);
+        }
+        self.bytes += row.data.len();
+        self.rows.push(row);
+        if self.bytes > self.config.max_memory {
+            let mut file = BufWriter::new(self.create_file()?);
+            for row in self.rows.drain(..) {
+                Self::write_row(&mut file, &row)?;
+            }
+            self.file = Some(file);
+        }
+        Ok(())
+    }
+
+    /// The rows in order, read back from the spool file if they went to one
+    pub fn finish(self) -> io::Result<Box<dyn Iterator<Item = PgWireResult<DataRow>> + Send>> {
+        let Some(file) = self.file else {
+            return Ok(Box::new(self.rows.into_iter().map(Ok)));
+        };
+        let mut file = file.into_inner().map_err(|e| e.into_error())?;
+        file.seek(SeekFrom::Start(0))?;
+        Ok(Box::new(SpooledRows { reader: Some(BufReader::new(file)) }))
+    }
+
+    /// An unlinked file in the spool directory
+    fn create_file(&self) -> io::Result<File> {
+        fs::create_dir_all(&self.config.dir)?;
+        let path = self.config.dir.join(format!(
+            "result-{}-{}",
+            std::process::id(),
+            NEXT_SPOOL.fetch_add(1, Ordering::Relaxed)
+        ));
+        let file = fs::OpenOptions::new().read(true).write(true).create_new(true).open(&path)?;
+        fs::remove_file(&path)?;
+        info!(rows = self.rows.len(), bytes = self.bytes, "spooling result to disk");
+        Ok(file)
+    }
+
+    fn write_row(file: &mut impl Write, row: &DataRow) -> io::Result<()> {
+        file.write_all(&row.field_count.to_le_bytes())?;
+        file.write_all(&(row.data.len() as u32).to_le_bytes())?;
+        file.write_all(&row.data)
+    }
+}
+
+/// Rows streamed back from a spool file
+struct SpooledRows {
+    /// None once the file is read to its end or has failed
+    reader: Option<BufReader<File>>,
+}
+
+impl SpooledRows {
+    fn read_row(reader: &mut BufReader<File>) -> io::Result<Option<DataRow>> {
+        let mut field_count = [0u8; 2];
+        match reader.read_exact(&mut field_count) {
+            Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+            result => result?,
+        }
+        let mut len = [0u8; 4];
+        reader.read_exact(&mut len)?;
+        let mut data = BytesMut::zeroed(u32::from_le_bytes(len) as usize);
+        reader.read_exact(&mut data)?;
+        Ok(Some(DataRow::new(data, i16::from_le_bytes(field_count))))
+    }
+}
+
+impl Iterator for SpooledRows {
+    type Item = PgWireResult<DataRow>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let reader = self.reader.as_mut()?;
+        match Self::read_row(reader) {
+            Ok(Some(row)) => Some(Ok(row)),
+            Ok(None) => {
+                self.reader = None;
+                None
+            }
+            Err(e) => {
+                self.reader = None;
+                Some(Err(PgWireError::IoError(e)))
+            }
+        }
+    }
+}
@@ -0,0 +1,117 @@
+//! Temporary tables (CREATE TEMP TABLE)
+//!
+//! A temporary table belongs to the session that created it and is dropped
+//! when that session ends. It is stored as `pg_temp_<n>.<name>`, where `n` is
+//! the session's own temporary schema, so sessions can each have a temporary
+//! table of the same name. Before a statement of the session runs, the names
+//! of its temporary tables are rewritten to the stored ones; as in Postgres,
+//! where pg_temp comes first in the search path, a temporary table hides a
+//! permanent table of the same name. `pg_temp.<name>` names it explicitly.
+
+use std::collections::HashMap;
+use std::ops::ControlFlow;
+
+use sqlparser::ast::{CommentObject, CopySource, Ident, ObjectName, Statement, TableAlias, TableFactor, VisitMut, VisitorMut};
+
+/// Schema name that always means the session's own temporary schema
+const TEMP_SCHEMA: &str = "pg_temp";
+
+/// Name of a table being created in `pg_temp`, without the schema, or None
+/// if it is created elsewhere
+pub fn in_temp_schema(table_name: &str) -> Option<&str> {
+    table_name.strip_prefix(TEMP_SCHEMA)?.strip_prefix('.')
+}
+
+/// Name a session's temporary table is stored under
+pub fn stored_name(session_schema: u32, table_name: &str) -> String {
+    format!("{}_{}.{}", TEMP_SCHEMA, session_schema, table_name)
+}
+
+/// The statement with each name of one of the session's temporary tables
+/// (`temp_tables`, by the name the session gave it) replaced by the name it is
+/// stored under
+/// The table a CREATE TABLE creates keeps its name: only a CREATE TEMP TABLE
+/// creates a temporary table, and that is handled before this
+pub fn resolve(stmt: &Statement, temp_tables: &HashMap<String, String>) -> Statement {
+    let mut stmt = stmt.clone();
+    let mut names = TempTableNames { temp_tables };
+    match &mut stmt {
+        Statement::CreateTable(ct) => {
+            if let Some(query) = &mut ct.query {
+                let _ = query.visit(&mut names);
+            }
+        }
+        stmt => {
+            let _ = stmt.visit(&mut names);
+        }
+    }
+    stmt
+}
+
+struct TempTableNames<'a> {
+    temp_tables: &'a HashMap<String, String>,
+}
+
+impl TempTableNames<'_> {
+    /// The name the session gave the temporary table `name` refers to
+    fn temp_table<'n>(&self, name: &'n [Ident]) -> Option<&'n str> {
+        let table_name = match name {
+            [table_name] => table_name,
+            [schema, table_name] if schema.value == TEMP_SCHEMA => table_name,
+            _ => return None,
+        };
+        self.temp_tables.contains_key(&table_name.value).then_some(table_name.value.as_str())
+    }
+
+    /// Replace the parts of `name` naming a temporary table with its stored name
+    fn rewrite(&self, name: &mut ObjectName) {
+        let Some(idents) = name.0.iter().map(|part| part.as_ident().cloned()).collect::<Option<Vec<_>>>() else {
+            return;
+        };
+        if let Some(table_name) = self.temp_table(&idents) {
+            let stored = &self.temp_tables[table_name];
+            *name = ObjectName::from(stored.split('.').map(Ident::new).collect::<Vec<_>>());
+        }
+    }
+}
+
+impl VisitorMut for TempTableNames<'_> {
+    type Break = ();
+
+    fn pre_visit_table_factor(&mut self, table_factor: &mut TableFactor) -> ControlFlow<()> {
+        // Columns keep being qualified by the name the query gave the table
+        if let TableFactor::Table { name, alias: alias @ None, .. } = table_factor
+            && let Some(idents) = name.0.iter().map(|part| part.as_ident().cloned()).collect::<Option<Vec<_>>>()
+            && let Some(table_name) = self.temp_table(&idents)
+        {
+            *alias = Some(TableAlias { name: Ident::new(table_name), columns: Vec::new() });
+        }
+        ControlFlow::Continue(())
+    }
+
+    fn pre_visit_relation(&mut self, relation: &mut ObjectName) -> ControlFlow<()> {
+        self.rewrite(relation);
+        ControlFlow::Continue(())
+    }
+
+    fn pre_visit_statement(&mut self, statement: &mut Statement) -> ControlFlow<()> {
+        // Table names the visitor doesn't see as relations
+        match statement {
+            Statement::Comment { object_type: CommentObject::Table, object_name, .. } => self.rewrite(object_name),
+            Statement::Comment { object_type: CommentObject::Column, object_name, .. } => {
+                if let Some(column) = object_name.0.pop() {
+                    self.rewrite(object_name);
+                    object_name.0.push(column);
+                }
+            }
+            Statement::Vacuum(vacuum) => {
+                if let Some(table_name) = &mut vacuum.table_name {
+                    self.rewrite(table_name);
+                }
+            }
+            Statement::Copy { source: CopySource::Table { table_name, .. }, .. } => self.rewrite(table_name),
+            _ => {}
+        }
+        ControlFlow::Continue(())
+    }
+}
@@ -0,0 +1,183 @@
+//! Transactions of sessions' writes (see `storage::mvcc`)
+//!
+//! A statement that writes to tables runs in a transaction: inside a
+//! transaction block, the block's, begun by its first such statement; outside
+//! one, a transaction of its own that commits when the statement succeeds and
+//! rolls back when it fails. COMMIT makes a block's writes visible to other
+//! sessions, which see the rows as they were until then, and ROLLBACK undoes
+//! them. As in Postgres, a statement failing inside a block rolls back the
+//! block's writes, and the block then refuses every statement until it ends.
+//!
+//! Transactions write one at a time: the first write of a transaction waits
+//! for the write lock, and the transaction keeps it until it commits or rolls
+//! back, so a session idle inside a block holds up every other session's
+//! writes until it ends the block or disconnects. Reads don't take the lock,
+//! though they still wait for admission (see `workload`) and for a statement
+//! changing the tables at that moment to finish.
+//!
+//! The write lock and advisory locks are kept in one table, so a session
+//! about to wait for a lock is refused with a deadlock error if the session
+//! holding it is itself waiting, directly or not, for a lock the first holds.
+//! A query gives up its place in its workload class while it waits, so the
+//! queries it waits for can be admitted, and a wait ends with an error once
+//! the session's lock_timeout passes.
+//!
+//! ROLLBACK only undoes changes to rows, so TRUNCATE, VACUUM and changes to
+//! definitions are refused inside a transaction block. Sequences advance at
+//! once, and ROLLBACK does not undo them, as in Postgres.
+
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+use parking_lot::{Condvar, Mutex};
+
+use crate::executor::advisory::LockKey;
+use crate::executor::cursor::SessionId;
+use crate::executor::error::ExecutorError;
+
+/// A lock sessions take
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Lock {
+    /// The write lock, held by the transaction writing
+    Write,
+    /// An advisory lock
+    Advisory(LockKey),
+}
+
+impl Lock {
+    fn name(&self) -> String {
+        match self {
+            Lock::Write => "the write lock".to_string(),
+            Lock::Advisory(LockKey::Single(key)) => format!("advisory lock {}", key),
+            Lock::Advisory(LockKey::Pair(high, low)) => format!("advisory lock ({}, {})", high, low),
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct Locks {
+    state: Mutex<LockState>,
+    /// Notified whenever a lock is released
+    released: Condvar,
+}
+
+#[derive(Default)]
+struct LockState {
+    /// Holder of each taken lock and how many times it has taken it; the
+    /// write lock is held by no session while the server itself writes
+    held: HashMap<Lock, (Option<SessionId>, usize)>,
+    /// Lock each waiting session waits for
+    waiting: HashMap<SessionId, Lock>,
+}
+
+impl LockState {
+    /// Take a lock for `holder` if no one else holds it
+    fn take(&mut self, lock: Lock, holder: Option<SessionId>) -> bool {
+        match self.held.get_mut(&lock) {
+            Some((current, count)) if holder.is_some() && *current == holder => {
+                *count += 1;
+                true
+            }
+            Some(_) => false,
+            None => {
+                self.held.insert(lock, (holder, 1));
+                true
+            }
+        }
+    }
+
+    /// Whether `session_id` waiting for `lock` would wait forever: its holder
+    /// waits for a lock whose holder waits in turn, and so on, back to
+    /// `session_id`
+    fn would_deadlock(&self, lock: Lock, session_id: SessionId) -> bool {
+        let mut lock = lock;
+        // A session waits for one lock at a time, so a chain of waits is no
+        // longer than the waiting sessions
+        for _ in 0..=self.waiting.len() {
+            let Some((Some(holder), _)) = self.held.get(&lock) else {
+                return false;
+            };
+            if *holder == session_id {
+                return true;
+            }
+            let Some(next) = self.waiting.get(holder) else {
+                return false;
+            };
+            lock = *next;
+        }
+        false
+    }
+}
+
+impl Locks {
+    /// Take a lock for a session, waiting for its holder to release it
+    /// Fails instead of waiting if the wait would never end, and once
+    /// `timeout` passes
+    pub fn lock(&self, lock: Lock, session_id: SessionId, timeout: Option<Duration>) -> Result<(), ExecutorError> {
+        let deadline = timeout.map(|timeout| Instant::now() + timeout);
+        let mut state = self.state.lock();
+        if state.take(lock, Some(session_id)) {
+            return Ok(());
+        }
+        // Waits only form a cycle as a session starts waiting, so checking
+        // then is enough
+        if state.would_deadlock(lock, session_id) {
+            return Err(ExecutorError::Deadlock(format!("deadlock detected waiting for {}", lock.name())));
+        }
+        state.waiting.insert(session_id, lock);
+        let result = loop {
+            let timed_out = match deadline {
+                Some(deadline) => self.released.wait_until(&mut state, deadline).timed_out(),
+                None => {
+                    self.released.wait(&mut state);
+                    false
+                }
+            };
+            if state.take(lock, Some(session_id)) {
+                break Ok(());
+            }
+            if timed_out {
+                break Err(ExecutorError::LockNotAvailable("canceling statement due to lock timeout".to_string()));
+            }
+        };
+        state.waiting.remove(&session_id);
+        result
+    }
+
+    /// Take a lock if no one else holds it; a holder of None is the server
+    /// Returns whether the lock was taken
+    pub fn try_lock(&self, lock: Lock, holder: Option<SessionId>) -> bool {
+        self.state.lock().take(lock, holder)
+    }
+
+    /// Release one hold of a lock
+    /// Returns false if `holder` does not hold it
+    pub fn unlock(&self, lock: Lock, holder: Option<SessionId>) -> bool {
+        let mut state = self.state.lock();
+        let Some((current, count)) = state.held.get_mut(&lock) else {
+            return false;
+        };
+        if *current != holder {
+            return false;
+        }
+        *count -= 1;
+        if *count == 0 {
+            state.held.remove(&lock);
+            self.released.notify_all();
+        }
+        true
+    }
+
+    /// Release every advisory lock a session holds
+    /// Returns the number of locks released
+    pub fn unlock_advisory(&self, session_id: SessionId) -> usize {
+        let mut state = self.state.lock();
+        let before = state.held.len();
+        state.held.retain(|lock, (holder, _)| matches!(lock, Lock::Write) || *holder != Some(session_id));
+        let released = before - state.held.len();
+        if released > 0 {
+            self.released.notify_all();
+        }
+        released
+    }
+}
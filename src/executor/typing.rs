@@ -0,0 +1,134 @@
+//! Result type inference for projected expressions
+//!
+//! Types are worked out from the expression alone, before any row is
+//! evaluated, so a result is described to the client the same way whether or
+//! not it has rows. Anything whose type cannot be determined is DataType::Null,
+//! which is reported as unknown.
+
+use sqlparser::ast::{BinaryOperator, Expr, UnaryOperator};
+
+use crate::executor::builtins;
+use crate::executor::cast;
+use crate::executor::evaluator;
+use crate::storage::Database;
+use crate::types::{DataType, Schema};
+
+/// Type of the value `expr` evaluates to against rows of `schema`
+pub fn infer_type(expr: &Expr, schema: &Schema, db: &Database) -> DataType {
+    let infer = |expr: &Expr| infer_type(expr, schema, db);
+
+    match expr {
+        Expr::Value(val) => match &val.value {
+            sqlparser::ast::Value::Number(n, _) if n.parse::<i64>().is_ok() => DataType::Int,
+            sqlparser::ast::Value::Number(_, _) => DataType::Float,
+            sqlparser::ast::Value::SingleQuotedString(_) => DataType::String,
+            sqlparser::ast::Value::Boolean(_) => DataType::Bool,
+            _ => DataType::Null,
+        },
+        Expr::Identifier(ident) => evaluator::resolve_column(schema, &ident.value)
+            .map_or(DataType::Null, |idx| schema.columns[idx].data_type.clone()),
+        Expr::CompoundIdentifier(parts) => {
+            let name = parts.iter().map(|part| part.value.as_str()).collect::<Vec<_>>().join(".");
+            evaluator::resolve_column(schema, &name)
+                .map_or(DataType::Null, |idx| schema.columns[idx].data_type.clone())
+        }
+        Expr::Nested(inner) => infer(inner),
+        Expr::UnaryOp { op: UnaryOperator::Not, .. }
+        | Expr::InList { .. }
+        | Expr::Between { .. }
+        | Expr::IsNull(_)
+        | Expr::IsNotNull(_) => DataType::Bool,
+        Expr::UnaryOp { expr, .. } => infer(expr),
+        Expr::Cast { data_type, .. } | Expr::TypedString(sqlparser::ast::TypedString { data_type, .. }) => {
+            cast::CastTarget::from_sql(data_type).map_or(DataType::Null, cast::CastTarget::data_type)
+        }
+        Expr::BinaryOp { left, op, right } => {
+            let left_type = infer(left);
+            let right_type = infer(right);
+            binary_op_type(&left_type, op, &right_type, db)
+        }
+        Expr::Function(func) => {
+            let name = func.name.0.iter()
+                .filter_map(|part| part.as_ident())
+                .map(|ident| ident.value.clone())
+                .collect::<Vec<_>>()
+                .join(".");
+            function_type(&name, func, schema, db)
+        }
+        _ => DataType::Null,
+    }
+}
+
+/// Result type of a binary operator; extension operators report their own
+fn binary_op_type(left: &DataType, op: &BinaryOperator, right: &DataType, db: &Database) -> DataType {
+    use BinaryOperator::*;
+
+    match op {
+        Eq | NotEq | Lt | LtEq | Gt | GtEq | And | Or => DataType::Bool,
+        // Integer results that may overflow into floats are described as floats
+        Plus | Minus | Multiply | Divide | Modulo => match (left, right) {
+            (DataType::Int, DataType::Int) if evaluator::promotes_integer_overflow() => DataType::Float,
+            (DataType::Int, DataType::Int) => DataType::Int,
+            (DataType::Int | DataType::Float, DataType::Int | DataType::Float) => DataType::Float,
+            _ => DataType::Null,
+        },
+        PGExp => DataType::Float,
+        BitwiseAnd | BitwiseOr | PGBitwiseXor | PGBitwiseShiftLeft | PGBitwiseShiftRight => DataType::Int,
+        _ => db.operator_registry
+            .find(&op.to_string(), left, right)
+            .map_or(DataType::Null, |ext| ext.return_type(left, right)),
+    }
+}
+
+/// Result type of a function call: the executor's own functions, SQL functions
+/// from the catalog, then extension functions
+fn function_type(name: &str, func: &sqlparser::ast::Function, schema: &Schema, db: &Database) -> DataType {
+    match name.to_lowercase().as_str() {
+        "flint_table_size" | "flint_row_count" | "flint_approx_row_count" | "nextval" | "setval" | "count" => {
+            return DataType::Int;
+        }
+        "avg" => return DataType::Float,
+        "flint_promote" | "flint_verify_backup" | "pg_try_advisory_lock" | "pg_advisory_unlock" => return DataType::Bool,
+        "flint_backup" => return DataType::Int,
+        // SUM keeps its argument's numeric type, unless an integer sum may
+        // overflow into a float; MIN and MAX pick one of its values
+        "sum" | "min" | "max" => {
+            let arg_type = match &func.args {
+                sqlparser::ast::FunctionArguments::List(list) => match list.args.as_slice() {
+                    [sqlparser::ast::FunctionArg::Unnamed(sqlparser::ast::FunctionArgExpr::Expr(arg))] => {
+                        infer_type(arg, schema, db)
+                    }
+                    _ => DataType::Null,
+                },
+                _ => DataType::Null,
+            };
+            if name.eq_ignore_ascii_case("sum") && arg_type == DataType::Int && evaluator::promotes_integer_overflow() {
+                return DataType::Float;
+            }
+            return arg_type;
+        }
+        _ => {}
+    }
+    if let Some(builtin) = builtins::lookup(name) {
+        return builtin.return_type.clone();
+    }
+    if let Some(function_meta) = db.get_function(name) {
+        return function_meta.return_type;
+    }
+
+    let Some(ext) = db.function_registry.get(name) else {
+        return DataType::Null;
+    };
+    let arg_types = match &func.args {
+        sqlparser::ast::FunctionArguments::List(list) => list.args.iter()
+            .map(|arg| match arg {
+                sqlparser::ast::FunctionArg::Unnamed(sqlparser::ast::FunctionArgExpr::Expr(arg)) => {
+                    infer_type(arg, schema, db)
+                }
+                _ => DataType::Null,
+            })
+            .collect(),
+        _ => Vec::new(),
+    };
+    ext.return_type(&arg_types).unwrap_or(DataType::Null)
+}
@@ -0,0 +1,47 @@
+//! Type modifier enforcement
+//!
+//! A column declared with a type modifier, such as the length of VARCHAR(n),
+//! only takes values that fit it. As in Postgres, a string longer than its
+//! column is an error unless the excess is all spaces, which are dropped. A
+//! value of an extension type is checked by the type's `check_typmod`.
+
+use crate::executor::error::ExecutorError;
+use crate::storage::Database;
+use crate::types::{DataType, Row, Schema, Value};
+
+pub type Result<T> = std::result::Result<T, ExecutorError>;
+
+/// Fit each value of a row about to be written to its column's type modifier,
+/// truncating trailing spaces or rejecting the row
+#[cfg_attr(not(feature = "extensions"), allow(unused_variables))]
+pub fn apply(db: &Database, schema: &Schema, row: &mut Row) -> Result<()> {
+    for (column, value) in schema.columns.iter().zip(row.values.iter_mut()) {
+        let Some(typmod) = column.typmod else {
+            continue;
+        };
+        match (&column.data_type, value) {
+            (DataType::String, Value::String(s)) => {
+                let limit = typmod as usize;
+                let Some((end, _)) = s.char_indices().nth(limit) else {
+                    continue;
+                };
+                if !s[end..].chars().all(|c| c == ' ') {
+                    return Err(ExecutorError::ValueTooLong(format!(
+                        "value too long for type character varying({})",
+                        typmod
+                    )));
+                }
+                s.truncate(end);
+            }
+            #[cfg(feature = "extensions")]
+            (DataType::Extension { type_name, .. }, Value::Extension { type_oid, data }) => {
+                let type_ext = db.type_registry.get_by_oid(*type_oid)
+                    .ok_or_else(|| ExecutorError::Execution(format!("type \"{}\" is not registered", type_name)))?;
+                type_ext.check_typmod(data.as_ref(), typmod)
+                    .map_err(ExecutorError::ValueTooLong)?;
+            }
+            _ => {}
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,139 @@
+//! INSERT ... ON CONFLICT
+//!
+//! A row whose primary key is already taken, by a row of the table or by a row
+//! the same INSERT wrote before it, conflicts. DO NOTHING skips it. DO UPDATE
+//! rewrites the row holding the key instead: its SET list and WHERE see that
+//! row, qualified by the table's name or alias, next to the row proposed for
+//! insertion as `excluded`. A row DO UPDATE would reach twice is an error, as
+//! the outcome would depend on the order of the VALUES.
+//!
+//! The primary key is the only constraint a row can conflict on; there are no
+//! UNIQUE constraints or unique indexes yet.
+
+use std::collections::HashMap;
+
+use tracing::debug;
+
+use crate::executor::error::ExecutorError;
+use crate::executor::evaluator;
+use crate::planner::{ConflictAction, ConflictTarget, OnConflict};
+use crate::storage::index::key::{encode_key, key_values_equal};
+use crate::storage::{Database, TuplePointer};
+use crate::types::{Column, Row, Schema, Value};
+
+pub type Result<T> = std::result::Result<T, ExecutorError>;
+
+/// What an INSERT writes once its conflicts are resolved
+pub struct Resolution {
+    /// Rows to insert
+    pub inserts: Vec<Row>,
+    /// Rows updated instead: where each is stored, the row, and what it becomes
+    pub updates: Vec<(TuplePointer, Row, Row)>,
+}
+
+/// Split the rows an INSERT proposes into the rows it inserts and the
+/// existing rows it updates instead
+pub fn resolve(
+    db: &Database,
+    table_name: &str,
+    alias: Option<&str>,
+    schema: &Schema,
+    on_conflict: &OnConflict,
+    rows: Vec<Row>,
+) -> Result<Resolution> {
+    let pk_column = check_target(table_name, schema, on_conflict.target.as_ref())?;
+    let combined = combined_schema(alias.unwrap_or(table_name), schema);
+
+    let mut resolution = Resolution { inserts: Vec::new(), updates: Vec::new() };
+    // Keys this INSERT has written so far, with the values written under them
+    let mut written: HashMap<u64, Vec<Value>> = HashMap::new();
+    for row in rows {
+        let value = &row.values[pk_column];
+        // A NULL key is refused when the row is inserted
+        if matches!(value, Value::Null) {
+            resolution.inserts.push(row);
+            continue;
+        }
+        let key = encode_key(value).map_err(ExecutorError::Execution)?;
+
+        if written.get(&key).is_some_and(|earlier| earlier.iter().any(|earlier| key_values_equal(earlier, value))) {
+            match &on_conflict.action {
+                ConflictAction::DoNothing => continue,
+                ConflictAction::DoUpdate { .. } => {
+                    return Err(ExecutorError::Execution(
+                        "ON CONFLICT DO UPDATE command cannot affect row a second time: \
+                         rows proposed for insertion by the same command have duplicate constrained values".to_string(),
+                    ));
+                }
+            }
+        }
+
+        let existing = db.get_by_key(table_name, value).map_err(ExecutorError::Execution)?;
+        written.entry(key).or_default().push(value.clone());
+        let Some((tuple_ptr, existing)) = existing else {
+            resolution.inserts.push(row);
+            continue;
+        };
+
+        let ConflictAction::DoUpdate { assignments, selection } = &on_conflict.action else {
+            debug!(key = %value.as_string(), "conflicting row skipped");
+            continue;
+        };
+        let both = Row::new(existing.values.iter().chain(&row.values).cloned().collect());
+        if let Some(selection) = selection
+            && !matches!(evaluator::eval_expr(selection, &both, &combined)?, Value::Bool(true))
+        {
+            continue;
+        }
+        // Every assignment sees the row as it was before the update
+        let mut values = existing.values.clone();
+        for (column, expr) in assignments {
+            let col_idx = schema.get_column_index(column)
+                .ok_or_else(|| ExecutorError::Execution(format!("Column not found: {}", column)))?;
+            values[col_idx] = evaluator::eval_expr(expr, &both, &combined)?;
+        }
+        resolution.updates.push((tuple_ptr, existing, Row::new(values)));
+    }
+    debug!(inserts = resolution.inserts.len(), updates = resolution.updates.len(), "conflicts resolved");
+    Ok(resolution)
+}
+
+/// Check that an ON CONFLICT target names the primary key
+/// Returns the primary key's column
+fn check_target(table_name: &str, schema: &Schema, target: Option<&ConflictTarget>) -> Result<usize> {
+    let pk_column = schema.primary_key_index()
+        .ok_or_else(|| ExecutorError::Execution(format!("Table {} has no primary key", table_name)))?;
+    let matches = match target {
+        None => true,
+        Some(ConflictTarget::Columns(columns)) => {
+            matches!(columns.as_slice(), [column] if column.eq_ignore_ascii_case(&schema.columns[pk_column].name))
+        }
+        Some(ConflictTarget::Constraint(name)) => {
+            if *name != format!("{}_pkey", table_name) {
+                return Err(ExecutorError::Execution(format!(
+                    "constraint \"{}\" for table \"{}\" does not exist",
+                    name, table_name
+                )));
+            }
+            true
+        }
+    };
+    if !matches {
+        return Err(ExecutorError::Execution(
+            "there is no unique or exclusion constraint matching the ON CONFLICT specification".to_string(),
+        ));
+    }
+    Ok(pk_column)
+}
+
+/// Schema of an existing row followed by the row proposed for insertion, the
+/// first qualified by `qualifier` and the second by `excluded`
+fn combined_schema(qualifier: &str, schema: &Schema) -> Schema {
+    let columns = [qualifier, "excluded"].into_iter()
+        .flat_map(|qualifier| schema.columns.iter().map(move |column| Column {
+            name: format!("{}.{}", qualifier, column.name),
+            ..column.clone()
+        }))
+        .collect();
+    Schema::new(columns)
+}
@@ -0,0 +1,121 @@
+//! Values in the formats of the extended query protocol
+//!
+//! With the extended query protocol a client chooses, for each parameter it
+//! binds and each column of the result, between the text format the simple
+//! query protocol always uses and Postgres's binary format, which most drivers
+//! ask for since it saves parsing numbers. In binary an integer is a
+//! big-endian int8, a float an IEEE 754 float8, a boolean one byte and a
+//! string its UTF-8 bytes; a value of an extension type is sent as its type's
+//! `TypeExtension::send` writes it.
+
+use pgwire::api::portal::Format;
+use pgwire::api::results::{DataRowEncoder, FieldFormat, FieldInfo};
+use pgwire::api::Type;
+
+use crate::executor::error::ExecutorError;
+use crate::executor::Result;
+use crate::types::Value;
+
+/// Format of parameter or result column `idx`; `format` is None for the
+/// simple query protocol, which only uses text
+pub fn field_format(format: Option<&Format>, idx: usize) -> FieldFormat {
+    match format {
+        // A client may give fewer codes than there are fields; the rest are text
+        Some(Format::Individual(codes)) if idx >= codes.len() => FieldFormat::Text,
+        Some(format) => format.format_for(idx),
+        None => FieldFormat::Text,
+    }
+}
+
+/// Value of a bound parameter of type `param_type`, sent in `format`; None
+/// is a NULL
+/// A parameter whose type the client left unspecified is taken as text
+pub fn decode_parameter(value: Option<&[u8]>, param_type: &Type, format: FieldFormat) -> Result<Value> {
+    let Some(bytes) = value else {
+        return Ok(Value::Null);
+    };
+    if format == FieldFormat::Binary {
+        return decode_binary(bytes, param_type);
+    }
+
+    let text = std::str::from_utf8(bytes)
+        .map_err(|_| ExecutorError::Execution("invalid UTF-8 in text parameter".to_string()))?;
+    let invalid = || ExecutorError::Execution(format!(
+        "invalid input syntax for type {}: \"{}\"",
+        param_type.name(),
+        text
+    ));
+    match *param_type {
+        Type::INT2 | Type::INT4 | Type::INT8 => text.trim().parse().map(Value::Int).map_err(|_| invalid()),
+        Type::FLOAT4 | Type::FLOAT8 | Type::NUMERIC => text.trim().parse().map(Value::Float).map_err(|_| invalid()),
+        Type::BOOL => match text.trim().to_lowercase().as_str() {
+            "t" | "true" | "y" | "yes" | "on" | "1" => Ok(Value::Bool(true)),
+            "f" | "false" | "n" | "no" | "off" | "0" => Ok(Value::Bool(false)),
+            _ => Err(invalid()),
+        },
+        _ => Ok(Value::String(text.to_string())),
+    }
+}
+
+fn decode_binary(bytes: &[u8], param_type: &Type) -> Result<Value> {
+    let wrong_length = || ExecutorError::Execution(format!(
+        "incorrect binary data format in parameter of type {}",
+        param_type.name()
+    ));
+    match *param_type {
+        Type::INT2 => Ok(Value::Int(i16::from_be_bytes(bytes.try_into().map_err(|_| wrong_length())?) as i64)),
+        Type::INT4 => Ok(Value::Int(i32::from_be_bytes(bytes.try_into().map_err(|_| wrong_length())?) as i64)),
+        Type::INT8 => Ok(Value::Int(i64::from_be_bytes(bytes.try_into().map_err(|_| wrong_length())?))),
+        Type::FLOAT4 => Ok(Value::Float(f32::from_be_bytes(bytes.try_into().map_err(|_| wrong_length())?) as f64)),
+        Type::FLOAT8 => Ok(Value::Float(f64::from_be_bytes(bytes.try_into().map_err(|_| wrong_length())?))),
+        Type::BOOL => match bytes {
+            [b] => Ok(Value::Bool(*b != 0)),
+            _ => Err(wrong_length()),
+        },
+        Type::TEXT | Type::VARCHAR | Type::BPCHAR | Type::NAME | Type::UNKNOWN => String::from_utf8(bytes.to_vec())
+            .map(Value::String)
+            .map_err(|_| ExecutorError::Execution("invalid UTF-8 in binary parameter".to_string())),
+        _ => Err(ExecutorError::UnsupportedStatement(format!(
+            "binary parameters of type {} are not supported",
+            param_type.name()
+        ))),
+    }
+}
+
+/// Encode `value` as the next field of a row, in the type and format of `field`
+/// Extension values are encoded by the executor, which has their types
+pub fn encode_field(encoder: &mut DataRowEncoder, value: &Value, field: &FieldInfo) -> Result<()> {
+    let binary = field.format() == FieldFormat::Binary;
+    let data_type = field.datatype();
+    // The binary forms of the types differ, so in binary a value must be
+    // sent as the type the column was described with
+    if binary && !sends_as(value, data_type) {
+        return Err(ExecutorError::Execution(format!(
+            "cannot send {:?} in binary format as type {}",
+            value,
+            data_type.name()
+        )));
+    }
+    match value {
+        // An integer in a float column, e.g. from a CASE mixing both
+        Value::Int(n) if binary && *data_type == Type::FLOAT8 => encoder.encode_field(&(*n as f64)),
+        Value::Int(n) => encoder.encode_field(n),
+        Value::Float(f) => encoder.encode_field(f),
+        Value::String(s) => encoder.encode_field(s),
+        Value::Bool(b) => encoder.encode_field(b),
+        Value::Null | Value::Extension { .. } => encoder.encode_field(&None::<i32>),
+    }
+    .map_err(|e| ExecutorError::Execution(format!("Encoding error: {:?}", e)))
+}
+
+/// Whether `value` has a binary form as `data_type`
+fn sends_as(value: &Value, data_type: &Type) -> bool {
+    match value {
+        Value::Null => true,
+        Value::Int(_) => matches!(*data_type, Type::INT8 | Type::FLOAT8),
+        Value::Float(_) => *data_type == Type::FLOAT8,
+        Value::Bool(_) => *data_type == Type::BOOL,
+        Value::String(_) => matches!(*data_type, Type::VARCHAR | Type::TEXT | Type::UNKNOWN),
+        Value::Extension { .. } => false,
+    }
+}
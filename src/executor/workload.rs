@@ -0,0 +1,216 @@
+//! Workload classes and query admission
+//!
+//! Every query is admitted under a workload class before any of its
+//! statements run, and each class has its own limit on the queries it runs at
+//! once. A query over its class's limit waits in line, first come first
+//! served, until one of the running queries finishes. Bulk work is batch
+//! (CREATE INDEX, VACUUM, COPY FROM and INSERTs of many rows) and everything
+//! else is interactive, so a few bulk loads can't take every worker from the
+//! point lookups queued behind them. A session can put all of its queries in one
+//! class with `SET workload_class = batch | interactive | DEFAULT`.
+//!
+//! Once the server starts shutting down, admission is closed: queries waiting
+//! in line and queries arriving are refused, and the shutdown waits for the
+//! running ones to finish.
+//!
+//! A query gives up its place while it waits for the write lock or an
+//! advisory lock, so the query that would release the lock can be admitted
+//! even when every place is taken by queries waiting for it. It takes the
+//! place back once it has the lock, ahead of the queries waiting in line.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+
+use parking_lot::{Condvar, Mutex};
+use sqlparser::ast::{SetExpr, Statement};
+use tracing::{debug, info};
+
+use crate::executor::error::ExecutorError;
+
+/// INSERTs of at least this many rows are bulk loads
+const BATCH_INSERT_ROWS: usize = 1000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WorkloadClass {
+    /// Short, latency-sensitive queries
+    Interactive,
+    /// Bulk loads and maintenance
+    Batch,
+}
+
+impl WorkloadClass {
+    pub fn name(&self) -> &'static str {
+        match self {
+            WorkloadClass::Interactive => "interactive",
+            WorkloadClass::Batch => "batch",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_lowercase().as_str() {
+            "interactive" => Some(WorkloadClass::Interactive),
+            "batch" => Some(WorkloadClass::Batch),
+            _ => None,
+        }
+    }
+
+    /// Class a query's statements belong to by their work: batch if any of
+    /// them is bulk work
+    pub fn of_statements<'a>(stmts: impl IntoIterator<Item = &'a Statement>) -> Self {
+        let is_bulk = |stmt: &Statement| match stmt {
+            Statement::CreateIndex(_) | Statement::Vacuum(_) | Statement::Copy { to: false, .. } => true,
+            Statement::Insert(ins) => ins.source.as_ref().is_some_and(|source| match &*source.body {
+                SetExpr::Values(values) => values.rows.len() >= BATCH_INSERT_ROWS,
+                _ => false,
+            }),
+            _ => false,
+        };
+        if stmts.into_iter().any(is_bulk) {
+            WorkloadClass::Batch
+        } else {
+            WorkloadClass::Interactive
+        }
+    }
+
+    fn slot(&self) -> usize {
+        match self {
+            WorkloadClass::Interactive => 0,
+            WorkloadClass::Batch => 1,
+        }
+    }
+}
+
+/// Queries each class may run at once (None for no limit)
+#[derive(Debug, Clone, Copy)]
+pub struct WorkloadLimits {
+    pub interactive: Option<usize>,
+    pub batch: Option<usize>,
+}
+
+impl WorkloadLimits {
+    fn of(&self, class: WorkloadClass) -> Option<usize> {
+        match class {
+            WorkloadClass::Interactive => self.interactive,
+            WorkloadClass::Batch => self.batch,
+        }
+    }
+}
+
+/// Running and queued queries of one class
+#[derive(Default)]
+struct ClassQueue {
+    running: usize,
+    /// Ticket the next query to arrive takes
+    next_ticket: u64,
+    /// Ticket of the query at the head of the line
+    next_admitted: u64,
+}
+
+pub struct Admission {
+    limits: WorkloadLimits,
+    queues: Mutex<[ClassQueue; 2]>,
+    /// Notified whenever a query is admitted or finishes, or admission closes
+    changed: Condvar,
+    /// Set once the server is shutting down; only changed with `queues` locked
+    closed: AtomicBool,
+}
+
+impl Admission {
+    pub fn new(limits: WorkloadLimits) -> Self {
+        Admission {
+            limits,
+            queues: Mutex::new(Default::default()),
+            changed: Condvar::new(),
+            closed: AtomicBool::new(false),
+        }
+    }
+
+    /// Wait for a query of `class` to be let in; it runs until the returned
+    /// permit is dropped
+    /// Fails once admission is closed, including for a query waiting in line
+    pub fn admit(&self, class: WorkloadClass) -> Result<Permit<'_>, ExecutorError> {
+        let limit = self.limits.of(class);
+        let mut queues = self.queues.lock();
+        if self.closed.load(Ordering::SeqCst) {
+            return Err(shutting_down());
+        }
+        let queue = &mut queues[class.slot()];
+        let ticket = queue.next_ticket;
+        queue.next_ticket += 1;
+
+        let is_admissible = |queue: &ClassQueue| {
+            queue.next_admitted == ticket && limit.is_none_or(|limit| queue.running < limit)
+        };
+        if !is_admissible(&queues[class.slot()]) {
+            let queue = &queues[class.slot()];
+            debug!(class = class.name(), running = queue.running, ahead = ticket - queue.next_admitted, "query queued");
+            let queued_at = Instant::now();
+            while !is_admissible(&queues[class.slot()]) {
+                self.changed.wait(&mut queues);
+                if self.closed.load(Ordering::SeqCst) {
+                    return Err(shutting_down());
+                }
+            }
+            info!(class = class.name(), waited_ms = queued_at.elapsed().as_millis() as u64, "queued query admitted");
+        }
+        let queue = &mut queues[class.slot()];
+        queue.running += 1;
+        queue.next_admitted += 1;
+        // The next in line may fit too
+        self.changed.notify_all();
+        Ok(Permit { admission: self, class })
+    }
+
+    /// Refuse every query from now on, then wait up to `timeout` for the
+    /// running ones to finish; returns how many are still running
+    pub fn close(&self, timeout: Duration) -> usize {
+        let deadline = Instant::now() + timeout;
+        let mut queues = self.queues.lock();
+        self.closed.store(true, Ordering::SeqCst);
+        self.changed.notify_all();
+
+        let running = |queues: &[ClassQueue; 2]| queues.iter().map(|queue| queue.running).sum::<usize>();
+        while running(&queues) > 0 {
+            info!(running = running(&queues), "waiting for running queries to finish");
+            if self.changed.wait_until(&mut queues, deadline).timed_out() {
+                break;
+            }
+        }
+        running(&queues)
+    }
+
+    /// Run `wait`, a running query's wait for a lock, with its place in
+    /// `class` given up until the wait ends
+    pub fn give_way<T>(&self, class: WorkloadClass, wait: impl FnOnce() -> T) -> T {
+        self.release(class);
+        let result = wait();
+        let limit = self.limits.of(class);
+        let mut queues = self.queues.lock();
+        while limit.is_some_and(|limit| queues[class.slot()].running >= limit) {
+            self.changed.wait(&mut queues);
+        }
+        queues[class.slot()].running += 1;
+        result
+    }
+
+    fn release(&self, class: WorkloadClass) {
+        self.queues.lock()[class.slot()].running -= 1;
+        self.changed.notify_all();
+    }
+}
+
+fn shutting_down() -> ExecutorError {
+    ExecutorError::Shutdown("terminating connection due to administrator command".to_string())
+}
+
+/// A running query's place in its class
+pub struct Permit<'a> {
+    admission: &'a Admission,
+    class: WorkloadClass,
+}
+
+impl Drop for Permit<'_> {
+    fn drop(&mut self) {
+        self.admission.release(self.class);
+    }
+}
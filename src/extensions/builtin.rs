@@ -5,6 +5,7 @@ use crate::storage::index::{IndexBuilder, Index};
 use crate::storage::PageId;
 use crate::storage::index::btree::BTree;
 use crate::storage::index::hash::HashIndex;
+use crate::storage::index::gin::GinIndex;
 
 /// Built-in Int type extension
 pub struct IntType;
@@ -207,6 +208,20 @@ impl IndexBuilder for HashIndexBuilder {
     }
 }
 
+/// Built-in GIN (inverted) index builder
+pub struct GinIndexBuilder;
+
+impl IndexBuilder for GinIndexBuilder {
+    fn create(&self, _root_page_id: Option<PageId>) -> Box<dyn Index> {
+        // Posting lists are allocated on demand, so the root page goes unused
+        Box::new(GinIndex::new())
+    }
+
+    fn type_name(&self) -> &str {
+        "gin"
+    }
+}
+
 /// Register all built-in type extensions
 pub fn register_builtin_types(registry: &mut super::registry::TypeRegistry) {
     registry.register(Box::new(IntType));
@@ -220,4 +235,5 @@ pub fn register_builtin_types(registry: &mut super::registry::TypeRegistry) {
 pub fn register_builtin_indexes(registry: &mut crate::storage::index::IndexBuilderRegistry) {
     registry.register("btree", Box::new(BTreeBuilder));
     registry.register("hash", Box::new(HashIndexBuilder));
+    registry.register("gin", Box::new(GinIndexBuilder));
 }
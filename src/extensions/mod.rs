@@ -37,6 +37,78 @@ pub trait TypeExtension: Send + Sync {
 
     /// Convert to PostgreSQL type for protocol
     fn to_pgwire_type(&self) -> pgwire::api::Type;
+
+    /// Text form of a value sent to a client, like a Postgres type's output
+    /// function
+    /// Default: None, the value is sent as NULL
+    fn output(&self, _value: &dyn Any) -> Option<String> {
+        None
+    }
+
+    /// Binary form of a value sent to a client that asked for binary results,
+    /// like a Postgres type's send function
+    /// Default: the type has no binary form
+    fn send(&self, _value: &dyn Any) -> Result<Vec<u8>, String> {
+        Err(format!("no binary output function available for type {}", self.type_name()))
+    }
+
+    /// Elements a GIN index stores a value under, e.g. the items of an array
+    /// or the lexemes of a tsvector
+    /// Default: None, the type is not multi-valued and is indexed whole
+    fn index_elements(&self, _value: &dyn Any) -> Option<Vec<Value>> {
+        None
+    }
+
+    /// Type modifier of a column declared with modifiers, e.g. 3 for the
+    /// `(3)` of `vector(3)`; it is stored with the column and passed back to
+    /// `check_typmod`
+    /// Default: the type takes no modifiers
+    fn typmod_in(&self, modifiers: &[String]) -> Result<Option<u32>, String> {
+        if modifiers.is_empty() {
+            Ok(None)
+        } else {
+            Err(format!("type modifier is not allowed for type \"{}\"", self.type_name()))
+        }
+    }
+
+    /// Check a value written to a column declared with a type modifier, e.g.
+    /// that a vector has the column's dimensions
+    /// Default: every value fits
+    fn check_typmod(&self, _value: &dyn Any, _typmod: u32) -> Result<(), String> {
+        Ok(())
+    }
+}
+
+/// Part an operator plays for an index type, like a strategy of a Postgres
+/// operator class
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IndexStrategy {
+    /// `a op b` holds when a equals b: the rows indexed under b's key
+    Equal,
+    /// `a op b` holds when a contains every element of b: the rows an
+    /// inverted index lists under all of b's elements
+    Contains,
+    /// `a op b` holds when a shares an element with b: the rows an inverted
+    /// index lists under any of b's elements
+    Overlaps,
+    /// `a op b` is a distance, so `ORDER BY a op b LIMIT k` asks for the k
+    /// nearest rows: an index's nearest-neighbour search (e.g. HNSW)
+    Distance,
+}
+
+impl IndexStrategy {
+    /// Whether the strategy narrows a WHERE clause, rather than ordering rows
+    pub fn is_search(self) -> bool {
+        !matches!(self, IndexStrategy::Distance)
+    }
+}
+
+/// An operator's entry in an index type's operator class
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OperatorClass {
+    /// Index type name (e.g., "gin", "hnsw")
+    pub index_type: &'static str,
+    pub strategy: IndexStrategy,
 }
 
 /// Extension trait for custom operators
@@ -52,6 +124,13 @@ pub trait OperatorExtension: Send + Sync {
 
     /// Return type given input types
     fn return_type(&self, left_type: &DataType, right_type: &DataType) -> DataType;
+
+    /// Index types that can serve this operator, and the strategy each serves
+    /// it with, so the planner need not know which index suits which operator
+    /// Default: none, the operator is evaluated row by row
+    fn operator_classes(&self) -> &[OperatorClass] {
+        &[]
+    }
 }
 
 /// Extension trait for scalar functions
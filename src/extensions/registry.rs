@@ -1,4 +1,4 @@
-use super::{TypeExtension, OperatorExtension, FunctionExtension, IndexExtension};
+use super::{TypeExtension, OperatorExtension, FunctionExtension, IndexExtension, IndexStrategy};
 use crate::types::DataType;
 use std::collections::HashMap;
 
@@ -62,6 +62,22 @@ impl OperatorRegistry {
             .find(|op| op.operator_symbol() == symbol && op.can_handle(left, right))
             .map(|b| &**b)
     }
+
+    /// Strategy an index type serves an operator with, None if the operator
+    /// isn't in the index type's operator class
+    pub fn index_strategy(
+        &self,
+        symbol: &str,
+        left: &DataType,
+        right: &DataType,
+        index_type: &str,
+    ) -> Option<IndexStrategy> {
+        self.find(symbol, left, right)?
+            .operator_classes()
+            .iter()
+            .find(|class| class.index_type == index_type)
+            .map(|class| class.strategy)
+    }
 }
 
 /// Registry for function extensions
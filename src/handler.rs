@@ -1,17 +1,32 @@
 use std::fmt::Debug;
+use std::net::SocketAddr;
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 
 use async_trait::async_trait;
-use futures::Sink;
-use pgwire::api::{ClientInfo, ClientPortalStore, NoopHandler, PgWireServerHandlers};
-use pgwire::api::query::SimpleQueryHandler;
-use pgwire::api::results::Response;
-use pgwire::error::PgWireResult;
-use pgwire::messages::PgWireBackendMessage;
+use futures::{Sink, SinkExt};
+use pgwire::api::auth::{finish_authentication, protocol_negotiation, save_startup_parameters_to_metadata, DefaultServerParameterProvider, StartupHandler};
+use pgwire::api::copy::{send_copy_in_response, send_copy_out_response, CopyHandler};
+use pgwire::api::{ClientInfo, ClientPortalStore, PgWireConnectionState, PgWireServerHandlers, Type, METADATA_DATABASE, METADATA_USER};
+use pgwire::api::portal::Portal;
+use pgwire::api::query::{send_execution_response, send_query_response, send_ready_for_query, ExtendedQueryHandler, SimpleQueryHandler};
+use pgwire::api::results::{CopyResponse, DescribePortalResponse, DescribeResponse, DescribeStatementResponse, Response, Tag};
+use pgwire::api::stmt::{QueryParser, StoredStatement};
+use pgwire::api::store::PortalStore;
+use pgwire::error::{ErrorInfo, PgWireError, PgWireResult};
+use pgwire::messages::copy::{CopyData, CopyDone, CopyFail};
+use pgwire::messages::response::EmptyQueryResponse;
+use pgwire::messages::simplequery::Query;
+use pgwire::messages::{PgWireBackendMessage, PgWireFrontendMessage};
 use tracing::{info, span, Level};
 use ulid::Ulid;
 
-use crate::executor::Executor;
+use crate::executor::cluster::{Cluster, DEFAULT_DATABASE};
+use crate::executor::error::ExecutorError;
+use crate::executor::{wire, Executor};
+use crate::metrics;
+use crate::parser;
+use crate::types::Value;
 
 use crate::config::Config;
 
@@ -20,11 +35,40 @@ pub(crate) struct HandlerFactory {
 }
 
 impl HandlerFactory {
-    pub fn new(config: &Config) -> Self {
-        let executor = Arc::new(Executor::new(config));
-        HandlerFactory {
-            handler: Arc::new(Handler { executor })
-        }
+    pub fn new(config: &Config) -> Result<Self, String> {
+        let cluster = Cluster::new(config).map_err(|e| e.into_error_info().message)?;
+        Ok(HandlerFactory {
+            handler: Arc::new(Handler {
+                cluster,
+                log_min_duration_statement: config.log_min_duration_statement,
+            })
+        })
+    }
+
+    /// Promote a read-only server to accept writes, in every database
+    pub fn promote(&self) -> Result<(), String> {
+        let executor = self.handler.cluster.executors().into_iter().next()
+            .ok_or_else(|| "the server has no databases".to_string())?;
+        executor.promote().map_err(|e| e.into_error_info().message)
+    }
+
+    /// Delete the rows past their table's TTL in every database, returning
+    /// how many
+    pub fn expire_rows(&self) -> Result<usize, String> {
+        self.handler.cluster.executors().iter()
+            .map(|executor| executor.expire_rows().map_err(|e| e.into_error_info().message))
+            .sum()
+    }
+
+    /// Refuse further queries and checkpoint every database once the running
+    /// queries finish or `timeout` passes
+    pub fn shutdown(&self, timeout: Duration) -> Result<(), String> {
+        self.handler.cluster.shutdown(timeout).map_err(|e| e.into_error_info().message)
+    }
+
+    /// Drop the state a closed connection left behind, such as its cursors
+    pub fn end_session(&self, client_addr: SocketAddr) {
+        self.handler.cluster.end_session(client_addr);
     }
 }
 
@@ -33,17 +77,139 @@ impl PgWireServerHandlers for HandlerFactory {
         self.handler.clone()
     }
 
+    fn extended_query_handler(&self) -> Arc<impl ExtendedQueryHandler> {
+        self.handler.clone()
+    }
+
+    fn copy_handler(&self) -> Arc<impl CopyHandler> {
+        self.handler.clone()
+    }
+
     fn startup_handler(&self) -> Arc<impl pgwire::api::auth::StartupHandler> {
-        Arc::new(NoopHandler)
+        self.handler.clone()
+    }
+}
+
+/// Handlers for a connection over the server's max_connections, which is
+/// refused at startup as Postgres refuses it
+pub(crate) struct TooManyClients;
+
+impl PgWireServerHandlers for TooManyClients {
+    fn startup_handler(&self) -> Arc<impl StartupHandler> {
+        Arc::new(TooManyClients)
+    }
+}
+
+#[async_trait]
+impl StartupHandler for TooManyClients {
+    async fn on_startup<C>(&self, _client: &mut C, _message: PgWireFrontendMessage) -> PgWireResult<()>
+    where
+        C: ClientInfo + Sink<PgWireBackendMessage> + Unpin + Send + Sync,
+        C::Error: Debug,
+        PgWireError: From<<C as Sink<PgWireBackendMessage>>::Error>,
+    {
+        Err(PgWireError::UserError(Box::new(ErrorInfo::new(
+            "FATAL".to_string(),
+            "53300".to_string(), // too_many_connections
+            "sorry, too many clients already".to_string(),
+        ))))
     }
 }
 
 struct Handler {
-    executor: Arc<Executor>,
+    cluster: Arc<Cluster>,
+    /// Threshold of the slow query log, if it is on
+    log_min_duration_statement: Option<Duration>,
+}
+
+#[async_trait]
+impl StartupHandler for Handler {
+    /// pgwire's startup without authentication, except that a connection to
+    /// a database that doesn't exist is refused
+    async fn on_startup<C>(&self, client: &mut C, message: PgWireFrontendMessage) -> PgWireResult<()>
+    where
+        C: ClientInfo + Sink<PgWireBackendMessage> + Unpin + Send + Sync,
+        C::Error: Debug,
+        PgWireError: From<<C as Sink<PgWireBackendMessage>>::Error>,
+    {
+        if let PgWireFrontendMessage::Startup(startup) = message {
+            protocol_negotiation(client, &startup).await?;
+            save_startup_parameters_to_metadata(client, &startup);
+            // As in Postgres, the database is named after the user by default
+            let database = startup.parameters.get(METADATA_DATABASE)
+                .or_else(|| startup.parameters.get(METADATA_USER))
+                .map_or(DEFAULT_DATABASE, String::as_str);
+            if let Err(e) = self.cluster.connect(client.socket_addr(), database) {
+                let mut error = e.into_error_info();
+                error.severity = "FATAL".to_string();
+                return Err(PgWireError::UserError(Box::new(error)));
+            }
+            info!(database = %database, "connected to database");
+            finish_authentication(client, &DefaultServerParameterProvider::default()).await?;
+        }
+        Ok(())
+    }
 }
 
 #[async_trait]
 impl SimpleQueryHandler for Handler {
+    /// pgwire's handling of a query, except that the rows of a COPY TO STDOUT
+    /// are sent after its CopyOutResponse, which pgwire leaves to the handler
+    async fn on_query<C>(&self, client: &mut C, query: Query) -> PgWireResult<()>
+    where
+        C: ClientInfo + ClientPortalStore + Sink<PgWireBackendMessage> + Unpin + Send + Sync,
+        C::Error: Debug,
+        PgWireError: From<<C as Sink<PgWireBackendMessage>>::Error>,
+    {
+        if !matches!(client.state(), PgWireConnectionState::ReadyForQuery) {
+            return Err(PgWireError::NotReadyForQuery);
+        }
+        let mut transaction_status = client.transaction_status();
+        client.set_state(PgWireConnectionState::QueryInProgress);
+
+        for response in SimpleQueryHandler::do_query(self, client, &query.query).await? {
+            match response {
+                Response::EmptyQuery => {
+                    client.feed(PgWireBackendMessage::EmptyQueryResponse(EmptyQueryResponse::new())).await?;
+                }
+                Response::Query(mut results) => send_query_response(client, &mut results, true).await?,
+                Response::Execution(tag) => send_execution_response(client, tag).await?,
+                Response::TransactionStart(tag) => {
+                    send_execution_response(client, tag).await?;
+                    transaction_status = transaction_status.to_in_transaction_state();
+                }
+                Response::TransactionEnd(tag) => {
+                    send_execution_response(client, tag).await?;
+                    transaction_status = transaction_status.to_idle_state();
+                }
+                Response::Error(e) => {
+                    client.feed(PgWireBackendMessage::ErrorResponse((*e).into())).await?;
+                    transaction_status = transaction_status.to_error_state();
+                }
+                Response::CopyIn(copy) => {
+                    send_copy_in_response(client, copy).await?;
+                    client.set_state(PgWireConnectionState::CopyInProgress(false));
+                }
+                Response::CopyOut(copy) => {
+                    let tag = self.send_copy_out(client, copy).await?;
+                    send_execution_response(client, tag).await?;
+                }
+                Response::CopyBoth(_) => {
+                    return Err(ExecutorError::UnsupportedStatement("COPY BOTH is not supported".to_string()).into());
+                }
+            }
+        }
+
+        // A COPY FROM STDIN ends with the client's CopyDone, which is
+        // answered with ReadyForQuery instead
+        if !matches!(client.state(), PgWireConnectionState::CopyInProgress(_)) {
+            client.set_state(PgWireConnectionState::ReadyForQuery);
+            client.set_transaction_status(transaction_status);
+            send_ready_for_query(client, transaction_status).await?;
+        }
+        Ok(())
+    }
+
     async fn do_query<C>(&self, client: &mut C, query: &str) -> PgWireResult<Vec<Response>>
     where
         C: ClientInfo + ClientPortalStore + Sink<PgWireBackendMessage> + Unpin + Send + Sync,
@@ -53,9 +219,237 @@ impl SimpleQueryHandler for Handler {
         let query_id = Ulid::new();
         let client_addr = client.socket_addr();
         let span = span!(Level::INFO, "query", query_id = %query_id, client_addr = %client_addr);
-        let _enter = span.enter();
+        span.in_scope(|| info!(query = %query, "received query"));
+
+        // Execution does synchronous disk I/O, so run it on the blocking pool
+        // instead of stalling the reactor that drives every other connection
+        let executor = self.cluster.executor(client_addr);
+        let slow_query_threshold = self.log_min_duration_statement;
+        let query = query.to_string();
+        tokio::task::spawn_blocking(move || span.in_scope(|| {
+            let started = Instant::now();
+            let responses = executor.execute(client_addr, &query);
+            let failed = responses.as_ref().map_or(true, |responses| responses.iter().any(is_error));
+            finish_query(&executor, slow_query_threshold, &query, started, failed);
+            responses
+        }))
+            .await
+            .map_err(|e| PgWireError::ApiError(Box::new(e)))?
+            .map_err(|e| e.into())
+    }
+}
+
+/// Whether a response reports a failed statement, for the query metrics
+fn is_error(response: &Response) -> bool {
+    matches!(response, Response::Error(_))
+}
+
+/// Count a query that ran from `started` in the metrics, and log it if it
+/// ran past the slow query threshold
+/// Runs on the query's own thread, where the rows it returned are counted
+fn finish_query(executor: &Executor, slow_query_threshold: Option<Duration>, query: &str, started: Instant, failed: bool) {
+    let elapsed = started.elapsed();
+    metrics::record_query(elapsed, failed);
+    if slow_query_threshold.is_some_and(|threshold| elapsed >= threshold) {
+        executor.log_slow_query(query, elapsed);
+    }
+}
+
+/// Checks the SQL of a Parse message, which is kept as text and parsed again
+/// when its portals run
+struct SqlParser;
+
+#[async_trait]
+impl QueryParser for SqlParser {
+    type Statement = String;
+
+    async fn parse_sql<C>(&self, _client: &C, sql: &str, _types: &[Type]) -> PgWireResult<String>
+    where
+        C: ClientInfo + Unpin + Send + Sync,
+    {
+        if parser::parse(sql)?.len() > 1 {
+            return Err(ExecutorError::Parse("cannot insert multiple commands into a prepared statement".to_string()).into());
+        }
+        Ok(sql.to_string())
+    }
+}
+
+/// The extended query protocol: a statement is parsed with `$n` placeholders,
+/// bound to parameters as a portal, then run, each in its own message, and
+/// parameters and result columns may each be sent in text or binary
+/// Describing a statement or portal plans it without running it
+#[async_trait]
+impl ExtendedQueryHandler for Handler {
+    type Statement = String;
+    type QueryParser = SqlParser;
+
+    fn query_parser(&self) -> Arc<SqlParser> {
+        Arc::new(SqlParser)
+    }
+
+    async fn do_describe_statement<C>(
+        &self,
+        client: &mut C,
+        target: &StoredStatement<String>,
+    ) -> PgWireResult<DescribeStatementResponse>
+    where
+        C: ClientInfo + ClientPortalStore + Sink<PgWireBackendMessage> + Unpin + Send + Sync,
+        C::PortalStore: PortalStore<Statement = String>,
+        C::Error: Debug,
+        PgWireError: From<<C as Sink<PgWireBackendMessage>>::Error>,
+    {
+        let client_addr = client.socket_addr();
+        let executor = self.cluster.executor(client_addr);
+        let (param_count, fields) = executor.describe(client_addr, &target.statement, None, &Default::default())?;
+        // Parameters of unspecified type are bound as text
+        let parameters = (0..param_count)
+            .map(|idx| match target.parameter_types.get(idx) {
+                Some(param_type) if *param_type != Type::UNKNOWN => param_type.clone(),
+                _ => Type::TEXT,
+            })
+            .collect();
+        Ok(DescribeStatementResponse::new(parameters, fields.unwrap_or_default()))
+    }
+
+    async fn do_describe_portal<C>(&self, client: &mut C, target: &Portal<String>) -> PgWireResult<DescribePortalResponse>
+    where
+        C: ClientInfo + ClientPortalStore + Sink<PgWireBackendMessage> + Unpin + Send + Sync,
+        C::PortalStore: PortalStore<Statement = String>,
+        C::Error: Debug,
+        PgWireError: From<<C as Sink<PgWireBackendMessage>>::Error>,
+    {
+        let params = portal_params(target)?;
+        let client_addr = client.socket_addr();
+        let (_, fields) = self.cluster.executor(client_addr).describe(
+            client_addr,
+            &target.statement.statement,
+            Some(params),
+            &target.result_column_format,
+        )?;
+        Ok(fields.map(DescribePortalResponse::new).unwrap_or_else(DescribePortalResponse::no_data))
+    }
+
+    async fn do_query<C>(&self, client: &mut C, portal: &Portal<String>, _max_rows: usize) -> PgWireResult<Response>
+    where
+        C: ClientInfo + ClientPortalStore + Sink<PgWireBackendMessage> + Unpin + Send + Sync,
+        C::PortalStore: PortalStore<Statement = String>,
+        C::Error: Debug,
+        PgWireError: From<<C as Sink<PgWireBackendMessage>>::Error>,
+    {
+        let query_id = Ulid::new();
+        let client_addr = client.socket_addr();
+        let span = span!(Level::INFO, "query", query_id = %query_id, client_addr = %client_addr);
+        let query = portal.statement.statement.clone();
+        span.in_scope(|| info!(query = %query, params = portal.parameters.len(), "received extended query"));
+        let params = portal_params(portal)?;
+        let result_format = portal.result_column_format.clone();
+
+        let executor = self.cluster.executor(client_addr);
+        let slow_query_threshold = self.log_min_duration_statement;
+        let response = tokio::task::spawn_blocking(move || {
+            span.in_scope(|| {
+                let started = Instant::now();
+                let response = executor.execute_portal(client_addr, &query, params, &result_format);
+                let failed = response.as_ref().map_or(true, is_error);
+                finish_query(&executor, slow_query_threshold, &query, started, failed);
+                response
+            })
+        })
+            .await
+            .map_err(|e| PgWireError::ApiError(Box::new(e)))??;
+        match response {
+            Response::CopyOut(copy) => Ok(Response::Execution(self.send_copy_out(client, copy).await?)),
+            response => Ok(response),
+        }
+    }
+}
+
+impl Handler {
+    /// Send a COPY TO STDOUT: the CopyOutResponse, its rows in CopyData
+    /// messages of a batch each, and CopyDone; returns the tag that
+    /// completes it
+    async fn send_copy_out<C>(&self, client: &mut C, copy: CopyResponse) -> PgWireResult<Tag>
+    where
+        C: ClientInfo + Sink<PgWireBackendMessage> + Unpin + Send + Sync,
+        C::Error: Debug,
+        PgWireError: From<<C as Sink<PgWireBackendMessage>>::Error>,
+    {
+        let client_addr = client.socket_addr();
+        send_copy_out_response(client, copy).await?;
+        loop {
+            let executor = self.cluster.executor(client_addr);
+            let data = tokio::task::spawn_blocking(move || executor.copy_out_data(client_addr))
+                .await
+                .map_err(|e| PgWireError::ApiError(Box::new(e)))??;
+            let Some(data) = data else {
+                break;
+            };
+            client.send(PgWireBackendMessage::CopyData(CopyData::new(data.into()))).await?;
+        }
+        client.send(PgWireBackendMessage::CopyDone(CopyDone::new())).await?;
+        let copied = self.cluster.executor(client_addr).copy_out_done(client_addr)?;
+        Ok(Tag::new("COPY").with_rows(copied))
+    }
+}
+
+/// The data of a COPY FROM STDIN, which the client sends after the COPY
+/// statement in CopyData messages and ends with CopyDone, or CopyFail to give up
+#[async_trait]
+impl CopyHandler for Handler {
+    async fn on_copy_data<C>(&self, client: &mut C, copy_data: CopyData) -> PgWireResult<()>
+    where
+        C: ClientInfo + Sink<PgWireBackendMessage> + Unpin + Send + Sync,
+        C::Error: Debug,
+        PgWireError: From<<C as Sink<PgWireBackendMessage>>::Error>,
+    {
+        let client_addr = client.socket_addr();
+        let executor = self.cluster.executor(client_addr);
+        tokio::task::spawn_blocking(move || executor.copy_data(client_addr, &copy_data.data))
+            .await
+            .map_err(|e| PgWireError::ApiError(Box::new(e)))?
+            .map_err(|e| e.into())
+    }
 
-        info!(query = %query, "received query");
-        self.executor.execute(query).map_err(|e| e.into())
+    async fn on_copy_done<C>(&self, client: &mut C, _done: CopyDone) -> PgWireResult<()>
+    where
+        C: ClientInfo + Sink<PgWireBackendMessage> + Unpin + Send + Sync,
+        C::Error: Debug,
+        PgWireError: From<<C as Sink<PgWireBackendMessage>>::Error>,
+    {
+        let client_addr = client.socket_addr();
+        let executor = self.cluster.executor(client_addr);
+        let copied = tokio::task::spawn_blocking(move || executor.copy_done(client_addr))
+            .await
+            .map_err(|e| PgWireError::ApiError(Box::new(e)))??;
+        // pgwire only follows a successful copy with ReadyForQuery
+        client
+            .send(PgWireBackendMessage::CommandComplete(Tag::new("COPY").with_rows(copied).into()))
+            .await?;
+        Ok(())
     }
+
+    async fn on_copy_fail<C>(&self, client: &mut C, fail: CopyFail) -> PgWireError
+    where
+        C: ClientInfo + Sink<PgWireBackendMessage> + Unpin + Send + Sync,
+        C::Error: Debug,
+        PgWireError: From<<C as Sink<PgWireBackendMessage>>::Error>,
+    {
+        let client_addr = client.socket_addr();
+        self.cluster.executor(client_addr).copy_fail(client_addr);
+        PgWireError::UserError(Box::new(ErrorInfo::new(
+            "ERROR".to_string(),
+            "57014".to_string(), // query_canceled
+            format!("COPY from stdin failed: {}", fail.message),
+        )))
+    }
+}
+
+/// Values of a portal's parameters, each decoded from the format it was sent in
+fn portal_params(portal: &Portal<String>) -> Result<Vec<Value>, ExecutorError> {
+    portal.parameters.iter().enumerate()
+        .map(|(idx, value)| {
+            let param_type = portal.statement.parameter_types.get(idx).unwrap_or(&Type::UNKNOWN);
+            wire::decode_parameter(value.as_deref(), param_type, wire::field_format(Some(&portal.parameter_format), idx))
+        })
+        .collect()
 }
@@ -1,9 +1,15 @@
 pub mod server;
 pub mod config;
+pub mod commands;
+pub mod bench;
+#[cfg(feature = "repl")]
+pub mod repl;
+pub mod logging;
 pub mod types;
 #[cfg(feature = "extensions")]
 pub mod extensions;
 mod handler;
+mod metrics;
 mod executor;
 mod storage;
 mod parser;
@@ -13,4 +19,4 @@ mod planner;
 #[cfg(feature = "extensions")]
 pub use extensions::registry::{TypeRegistry, OperatorRegistry, FunctionRegistry, IndexBuilderRegistry};
 #[cfg(feature = "extensions")]
-pub use extensions::{TypeExtension, OperatorExtension, FunctionExtension, IndexExtension, TypeCategory};
\ No newline at end of file
+pub use extensions::{TypeExtension, OperatorExtension, FunctionExtension, IndexExtension, TypeCategory, IndexStrategy, OperatorClass};
\ No newline at end of file
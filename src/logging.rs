@@ -0,0 +1,109 @@
+//! Where the server's log goes and in what form
+//!
+//! Logs go to stderr by default. Given a log directory they go to files in it
+//! instead, started afresh every hour or day with the oldest removed past a
+//! limit, so a long-running server doesn't fill its disk. Either destination
+//! takes plain text or JSON, one object per line, for log collectors to parse.
+//! Verbosity is set by --log-level, or by RUST_LOG as before.
+
+use std::path::PathBuf;
+
+use tracing::Level;
+use tracing_appender::rolling::{RollingFileAppender, Rotation};
+use tracing_subscriber::EnvFilter;
+use tracing_subscriber::fmt::writer::BoxMakeWriter;
+
+use crate::config::Config;
+
+/// Filter used when RUST_LOG is not set
+const DEFAULT_FILTER: &str = "flintdb=info";
+
+/// Filter used when RUST_LOG is not set by commands whose output goes to the
+/// terminal, such as `flint repl`, so per-query logs don't bury it
+const QUIET_FILTER: &str = "flintdb=warn";
+
+/// Log files are named `flint.log.<date>`, or `flint.log` without rotation
+const LOG_FILE_PREFIX: &str = "flint.log";
+
+/// Format of each logged event (--log-format=text|json)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LogFormat {
+    Text,
+    Json,
+}
+
+/// How often a new log file is started (--log-rotation=hourly|daily|never)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LogRotation {
+    Hourly,
+    Daily,
+    Never,
+}
+
+#[derive(Debug, Clone)]
+pub struct LogConfig {
+    /// Verbosity of flint's own events (--log-level=LEVEL); None leaves it
+    /// to RUST_LOG
+    pub(crate) level: Option<Level>,
+    pub(crate) format: LogFormat,
+    /// Directory for log files (--log-directory=DIR); None logs to stderr
+    pub(crate) directory: Option<PathBuf>,
+    pub(crate) rotation: LogRotation,
+    /// Rotated files kept, the oldest removed first (--log-max-files=N);
+    /// None keeps them all
+    pub(crate) max_files: Option<usize>,
+}
+
+impl Default for LogConfig {
+    fn default() -> Self {
+        LogConfig {
+            level: None,
+            format: LogFormat::Text,
+            directory: None,
+            rotation: LogRotation::Daily,
+            max_files: None,
+        }
+    }
+}
+
+/// Install the process-wide subscriber as configured; `quiet` logs only
+/// warnings and errors unless --log-level or RUST_LOG says otherwise
+/// Call once, before anything is logged
+pub fn init(config: &Config, quiet: bool) -> Result<(), String> {
+    let log = &config.log;
+    let default_filter = if quiet { QUIET_FILTER } else { DEFAULT_FILTER };
+    let filter = match log.level {
+        Some(level) => EnvFilter::new(format!("flintdb={}", level)),
+        None => EnvFilter::try_from_default_env().unwrap_or_else(|_| default_filter.into()),
+    };
+
+    let (writer, ansi) = match &log.directory {
+        Some(directory) => (BoxMakeWriter::new(file_appender(log, directory)?), false),
+        None => (BoxMakeWriter::new(std::io::stderr), true),
+    };
+    let builder = tracing_subscriber::fmt()
+        .with_env_filter(filter)
+        .with_writer(writer)
+        .with_ansi(ansi);
+    let result = match log.format {
+        LogFormat::Text => builder.try_init(),
+        LogFormat::Json => builder.json().try_init(),
+    };
+    result.map_err(|e| format!("Failed to initialize logging: {}", e))
+}
+
+fn file_appender(log: &LogConfig, directory: &PathBuf) -> Result<RollingFileAppender, String> {
+    let rotation = match log.rotation {
+        LogRotation::Hourly => Rotation::HOURLY,
+        LogRotation::Daily => Rotation::DAILY,
+        LogRotation::Never => Rotation::NEVER,
+    };
+    let mut builder = RollingFileAppender::builder()
+        .rotation(rotation)
+        .filename_prefix(LOG_FILE_PREFIX);
+    if let Some(max_files) = log.max_files {
+        builder = builder.max_log_files(max_files);
+    }
+    builder.build(directory)
+        .map_err(|e| format!("Failed to open log directory {}: {}", directory.display(), e))
+}
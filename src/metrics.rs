@@ -0,0 +1,180 @@
+//! Server metrics for Prometheus to scrape (--metrics-addr=HOST:PORT)
+//!
+//! Queries are counted and timed as the handler runs them, connections as
+//! the server accepts them, and block I/O and index lookups come from the
+//! storage counters behind pg_stat_io. Everything is a process-wide counter
+//! from server start, so rates such as queries per second are left to
+//! Prometheus (`rate(flint_queries_total[1m])`). The endpoint is a bare
+//! HTTP/1.1 responder that serves `GET /metrics` in the text exposition
+//! format and nothing else; it is off unless an address is given.
+
+use std::fmt::Write;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tracing::{debug, warn};
+
+use crate::storage::stats;
+
+/// Upper bounds in seconds of the query latency histogram's buckets, below
+/// the implicit +Inf
+const LATENCY_BUCKETS: [f64; 11] = [0.001, 0.0025, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 1.0, 5.0, 30.0];
+
+/// Largest request head read before the client is answered
+const MAX_REQUEST_SIZE: usize = 8 * 1024;
+
+/// How long a scraper has to send its request
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+
+static QUERIES: AtomicU64 = AtomicU64::new(0);
+static QUERY_ERRORS: AtomicU64 = AtomicU64::new(0);
+/// Queries per bucket of LATENCY_BUCKETS, not cumulative; the last is +Inf
+static LATENCY_COUNTS: [AtomicU64; LATENCY_BUCKETS.len() + 1] = [const { AtomicU64::new(0) }; LATENCY_BUCKETS.len() + 1];
+static LATENCY_SUM_MICROS: AtomicU64 = AtomicU64::new(0);
+static CONNECTIONS_ACTIVE: AtomicU64 = AtomicU64::new(0);
+static CONNECTIONS: AtomicU64 = AtomicU64::new(0);
+static CONNECTIONS_REFUSED: AtomicU64 = AtomicU64::new(0);
+
+/// Count a query run over either protocol, and how long it took
+pub fn record_query(elapsed: Duration, failed: bool) {
+    QUERIES.fetch_add(1, Ordering::Relaxed);
+    if failed {
+        QUERY_ERRORS.fetch_add(1, Ordering::Relaxed);
+    }
+    let secs = elapsed.as_secs_f64();
+    let bucket = LATENCY_BUCKETS.iter().position(|&bound| secs <= bound).unwrap_or(LATENCY_BUCKETS.len());
+    LATENCY_COUNTS[bucket].fetch_add(1, Ordering::Relaxed);
+    LATENCY_SUM_MICROS.fetch_add(elapsed.as_micros() as u64, Ordering::Relaxed);
+}
+
+/// Count a client connection accepted
+pub fn connection_opened() {
+    CONNECTIONS.fetch_add(1, Ordering::Relaxed);
+    CONNECTIONS_ACTIVE.fetch_add(1, Ordering::Relaxed);
+}
+
+/// Count the end of a connection counted by `connection_opened`
+pub fn connection_closed() {
+    CONNECTIONS_ACTIVE.fetch_sub(1, Ordering::Relaxed);
+}
+
+/// Count a connection turned away past --max-connections
+pub fn connection_refused() {
+    CONNECTIONS_REFUSED.fetch_add(1, Ordering::Relaxed);
+}
+
+/// Write a metric's HELP and TYPE lines
+fn header(out: &mut String, name: &str, kind: &str, help: &str) {
+    let _ = writeln!(out, "# HELP {} {}", name, help);
+    let _ = writeln!(out, "# TYPE {} {}", name, kind);
+}
+
+/// Write a metric with a single sample
+fn single(out: &mut String, name: &str, kind: &str, help: &str, value: u64) {
+    header(out, name, kind, help);
+    let _ = writeln!(out, "{} {}", name, value);
+}
+
+/// Write a counter of the storage I/O stats, one sample per kind of object
+fn io_counter(out: &mut String, io: &[stats::IoStats], name: &str, help: &str, value: impl Fn(&stats::IoStats) -> u64) {
+    header(out, name, "counter", help);
+    for stats in io {
+        let _ = writeln!(out, "{}{{object=\"{}\"}} {}", name, stats.object.name(), value(stats));
+    }
+}
+
+/// Every metric in the Prometheus text exposition format, version 0.0.4
+pub fn render() -> String {
+    let mut out = String::new();
+
+    single(&mut out, "flint_queries_total", "counter", "Queries run, over either protocol.",
+        QUERIES.load(Ordering::Relaxed));
+    single(&mut out, "flint_query_errors_total", "counter", "Queries that returned an error.",
+        QUERY_ERRORS.load(Ordering::Relaxed));
+
+    header(&mut out, "flint_query_duration_seconds", "histogram", "Time taken to run a query.");
+    let mut cumulative = 0;
+    for (bound, count) in LATENCY_BUCKETS.iter().zip(&LATENCY_COUNTS) {
+        cumulative += count.load(Ordering::Relaxed);
+        let _ = writeln!(out, "flint_query_duration_seconds_bucket{{le=\"{}\"}} {}", bound, cumulative);
+    }
+    cumulative += LATENCY_COUNTS[LATENCY_BUCKETS.len()].load(Ordering::Relaxed);
+    let _ = writeln!(out, "flint_query_duration_seconds_bucket{{le=\"+Inf\"}} {}", cumulative);
+    let sum = LATENCY_SUM_MICROS.load(Ordering::Relaxed) as f64 / 1_000_000.0;
+    let _ = writeln!(out, "flint_query_duration_seconds_sum {}", sum);
+    let _ = writeln!(out, "flint_query_duration_seconds_count {}", cumulative);
+
+    single(&mut out, "flint_connections_active", "gauge", "Client connections open.",
+        CONNECTIONS_ACTIVE.load(Ordering::Relaxed));
+    single(&mut out, "flint_connections_total", "counter", "Client connections accepted.",
+        CONNECTIONS.load(Ordering::Relaxed));
+    single(&mut out, "flint_connections_refused_total", "counter", "Client connections refused past the connection limit.",
+        CONNECTIONS_REFUSED.load(Ordering::Relaxed));
+
+    let io = stats::io_stats();
+    io_counter(&mut out, &io, "flint_blocks_read_total", "Blocks read from disk.", |io| io.reads);
+    io_counter(&mut out, &io, "flint_read_bytes_total", "Bytes read from disk.", |io| io.read_bytes);
+    io_counter(&mut out, &io, "flint_blocks_written_total", "Blocks written to disk.", |io| io.writes);
+    io_counter(&mut out, &io, "flint_written_bytes_total", "Bytes written to disk.", |io| io.write_bytes);
+    io_counter(&mut out, &io, "flint_fsyncs_total", "Files flushed to stable storage.", |io| io.fsyncs);
+
+    single(&mut out, "flint_wal_records_total", "counter", "Records appended to the write-ahead log.",
+        stats::wal_stats().records);
+    single(&mut out, "flint_index_lookups_total", "counter", "Searches of primary and secondary indexes.",
+        stats::index_lookups());
+    out
+}
+
+/// Answer scrapes on `listener` until the server exits
+pub async fn serve(listener: TcpListener) {
+    loop {
+        let (socket, client_addr) = match listener.accept().await {
+            Ok(accepted) => accepted,
+            Err(e) => {
+                warn!(error = %e, "metrics connection failed");
+                continue;
+            }
+        };
+        tokio::spawn(async move {
+            if let Err(e) = respond(socket).await {
+                debug!(client_addr = %client_addr, error = %e, "metrics request failed");
+            }
+        });
+    }
+}
+
+/// Read one request and answer it, closing the connection after
+async fn respond(mut socket: TcpStream) -> std::io::Result<()> {
+    let mut request = Vec::new();
+    let mut buf = [0u8; 1024];
+    let read_head = async {
+        while !request.windows(4).any(|window| window == b"\r\n\r\n") && request.len() < MAX_REQUEST_SIZE {
+            let n = socket.read(&mut buf).await?;
+            if n == 0 {
+                break;
+            }
+            request.extend_from_slice(&buf[..n]);
+        }
+        Ok::<_, std::io::Error>(())
+    };
+    tokio::time::timeout(REQUEST_TIMEOUT, read_head).await
+        .map_err(|_| std::io::Error::new(std::io::ErrorKind::TimedOut, "request timed out"))??;
+
+    let request = String::from_utf8_lossy(&request);
+    let mut request_line = request.lines().next().unwrap_or_default().split_whitespace();
+    let (status, content_type, body) = match (request_line.next(), request_line.next()) {
+        (Some("GET"), Some(path)) if path == "/metrics" || path.starts_with("/metrics?") => {
+            ("200 OK", "text/plain; version=0.0.4; charset=utf-8", render())
+        }
+        (Some("GET"), Some(_)) => ("404 Not Found", "text/plain; charset=utf-8", "not found\n".to_string()),
+        _ => ("405 Method Not Allowed", "text/plain; charset=utf-8", "method not allowed\n".to_string()),
+    };
+    let response = format!(
+        "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status, content_type, body.len(), body
+    );
+    socket.write_all(response.as_bytes()).await?;
+    socket.shutdown().await
+}
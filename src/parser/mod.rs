@@ -1,19 +1,148 @@
-use sqlparser::ast::Statement;
+use std::borrow::Cow;
+
+use sqlparser::ast::{Expr, Ident, ObjectName, Set, Statement};
 use sqlparser::dialect::PostgreSqlDialect;
-use sqlparser::parser::Parser;
+use sqlparser::keywords::Keyword;
+use sqlparser::parser::{Parser, ParserError};
+use sqlparser::tokenizer::{Location, Token};
 use tracing::debug;
 
 use crate::executor::error::ExecutorError;
 
 pub fn parse(query: &str) -> Result<Vec<Statement>, ExecutorError> {
+    Ok(parse_with_locations(query)?.into_iter().map(|(stmt, _)| stmt).collect())
+}
+
+/// Parse a single expression, such as a CHECK constraint stored as SQL text
+pub fn parse_expr(text: &str) -> Result<Expr, ExecutorError> {
+    let dialect = PostgreSqlDialect {};
+    Parser::new(&dialect)
+        .try_with_sql(text)
+        .and_then(|mut parser| parser.parse_expr())
+        .map_err(|e| ExecutorError::Parse(format!("Parse error: {}", e)))
+}
+
+/// Parse a query into its statements, each with the location of its first token
+pub fn parse_with_locations(query: &str) -> Result<Vec<(Statement, Location)>, ExecutorError> {
     let dialect = PostgreSqlDialect {};
     debug!(query_len = query.len(), "parsing SQL");
 
-    Parser::parse_sql(&dialect, query)
-        .map_err(|e| {
-            debug!(error = %e, "parse failed");
-            ExecutorError::Parse(format!("Parse error: {}", e))
-        })
+    let parse_error = |e: ParserError| {
+        debug!(error = %e, "parse failed");
+        ExecutorError::Parse(format!("Parse error: {}", e))
+    };
+    let mut parser = Parser::new(&dialect).try_with_sql(&terminate_copy(query)).map_err(parse_error)?;
+
+    let mut stmts = Vec::new();
+    loop {
+        // Empty statements between successive semicolons are skipped
+        while parser.consume_token(&Token::SemiColon) {}
+        let next = parser.peek_token();
+        if next.token == Token::EOF {
+            break;
+        }
+
+        let stmt = parse_statement(&mut parser).map_err(parse_error)?;
+        stmts.push((stmt, next.span.start));
+
+        let after = parser.peek_token();
+        if after.token != Token::SemiColon && after.token != Token::EOF {
+            return Err(parse_error(ParserError::ParserError(format!(
+                "Expected: end of statement, found: {} at {}",
+                after.token, after.span.start
+            ))));
+        }
+    }
+    Ok(stmts)
+}
+
+/// Parse the statement at the parser's position
+/// RESET and SHOW are read here, as sqlparser doesn't read them as Postgres
+/// does: RESET name becomes SET name TO DEFAULT, and SHOW takes the words up
+/// to the end of the statement, with a dotted name as one word
+fn parse_statement(parser: &mut Parser) -> Result<Statement, ParserError> {
+    if parser.parse_keyword(Keyword::RESET) {
+        let words = parameter_words(parser)?;
+        let name = match words.as_slice() {
+            [word] => word.clone(),
+            [time, zone] if time.value.eq_ignore_ascii_case("time") && zone.value.eq_ignore_ascii_case("zone") => {
+                Ident::new("timezone")
+            }
+            _ => return Err(ParserError::ParserError("Expected: a parameter name after RESET".to_string())),
+        };
+        return Ok(Statement::Set(Set::SingleAssignment {
+            scope: None,
+            hivevar: false,
+            variable: ObjectName::from(vec![name]),
+            values: vec![Expr::Identifier(Ident::new("DEFAULT"))],
+        }));
+    }
+    if parser.parse_keyword(Keyword::SHOW) {
+        let variable = parameter_words(parser)?;
+        if variable.is_empty() {
+            return Err(ParserError::ParserError("Expected: a parameter name after SHOW".to_string()));
+        }
+        return Ok(Statement::ShowVariable { variable });
+    }
+    parser.parse_statement()
+}
+
+/// Words naming a run-time parameter, up to the end of the statement
+fn parameter_words(parser: &mut Parser) -> Result<Vec<Ident>, ParserError> {
+    let mut words: Vec<Ident> = Vec::new();
+    loop {
+        let token = parser.peek_token();
+        match token.token {
+            Token::SemiColon | Token::EOF => return Ok(words),
+            Token::Word(word) => {
+                let ident = Ident { value: word.value, quote_style: word.quote_style, span: token.span };
+                words.push(ident);
+            }
+            Token::Period if !words.is_empty() => {
+                parser.next_token();
+                let next = parser.next_token();
+                let Token::Word(word) = next.token else {
+                    return Err(ParserError::ParserError(format!("Expected: a name after '.', found: {}", next.token)));
+                };
+                let last = words.last_mut().expect("checked not empty");
+                last.value = format!("{}.{}", last.value, word.value);
+                continue;
+            }
+            other => {
+                return Err(ParserError::ParserError(format!(
+                    "Expected: a parameter name, found: {} at {}",
+                    other, token.span.start
+                )));
+            }
+        }
+        parser.next_token();
+    }
+}
+
+/// The query with a `;` after a COPY that doesn't end in one
+/// sqlparser reads the data of a COPY FROM STDIN inline, after the `;` it
+/// requires, but clients send the data separately and often no `;`
+fn terminate_copy(query: &str) -> Cow<'_, str> {
+    let trimmed = query.trim_end();
+    let is_copy = trimmed.trim_start().get(..4).is_some_and(|word| word.eq_ignore_ascii_case("copy"));
+    if is_copy && !trimmed.ends_with(';') {
+        Cow::Owned(format!("{};", trimmed))
+    } else {
+        Cow::Borrowed(query)
+    }
+}
+
+/// 1-based character position of a location in the query, as reported in
+/// the error position field
+pub fn char_position(query: &str, location: Location) -> Option<usize> {
+    if location.line == 0 {
+        return None;
+    }
+    let preceding_chars: usize = query.split('\n')
+        .take(location.line as usize - 1)
+        .map(|line| line.chars().count() + 1)
+        .sum();
+    Some(preceding_chars + location.column as usize)
 }
 
 // TODO room for future implementation
@@ -0,0 +1,549 @@
+//! Row and cost estimates for plans, shown by EXPLAIN
+//!
+//! Costs are in Postgres's units, where reading a page in sequence costs 1,
+//! so the numbers read the way EXPLAIN's do in Postgres. Table sizes come from
+//! storage: the row count kept up to date as rows are written, and the pages
+//! the table file spans. Nothing is known about the values in a column, so
+//! predicates get Postgres's default selectivities, except that an equality
+//! on a primary key matches at most one row.
+
+use sqlparser::ast::{BinaryOperator, Expr, UnaryOperator};
+
+use crate::executor::typing;
+use crate::planner::Operator;
+use crate::storage::index::ScanDirection;
+use crate::storage::Database;
+
+/// Cost of reading a page in sequence
+const SEQ_PAGE_COST: f64 = 1.0;
+/// Cost of reading a page out of sequence, as an index lookup does
+const RANDOM_PAGE_COST: f64 = 4.0;
+/// Cost of handling one row
+const CPU_TUPLE_COST: f64 = 0.01;
+/// Cost of handling one index entry
+const CPU_INDEX_TUPLE_COST: f64 = 0.005;
+/// Cost of evaluating one operator or function
+const CPU_OPERATOR_COST: f64 = 0.0025;
+
+/// Rows assumed for a table storage knows nothing about, such as a system view
+const DEFAULT_TABLE_ROWS: f64 = 1000.0;
+/// Groups assumed for a GROUP BY, without statistics on distinct values
+const DEFAULT_GROUPS: f64 = 200.0;
+
+/// Default selectivities, as Postgres uses without column statistics
+const DEFAULT_EQ_SEL: f64 = 0.005;
+const DEFAULT_INEQ_SEL: f64 = 1.0 / 3.0;
+const DEFAULT_RANGE_SEL: f64 = 0.005;
+const DEFAULT_MATCH_SEL: f64 = 0.005;
+const DEFAULT_NULL_SEL: f64 = 0.005;
+const DEFAULT_BOOL_SEL: f64 = 0.5;
+
+/// What the planner needs to know about tables to estimate a plan
+pub trait Statistics {
+    /// Estimated live rows of a table, None if unknown
+    fn row_count(&self, table: &str) -> Option<u64>;
+    /// Pages the table's data spans, None if unknown
+    fn page_count(&self, table: &str) -> Option<u64>;
+    /// Whether a column is its table's primary key
+    fn is_primary_key(&self, table: &str, column: &str) -> bool;
+    /// Leading columns of the secondary index a lookup by equalities on
+    /// `columns` would use, 0 if none has any
+    fn index_prefix(&self, table: &str, columns: &[&str]) -> usize;
+    /// Whether a secondary index narrows `column op value` for an extension
+    /// operator, going by the operator classes it declares
+    fn serves_operator(&self, table: &str, column: &str, op: &BinaryOperator, value: &Expr) -> bool;
+}
+
+impl Statistics for Database {
+    fn row_count(&self, table: &str) -> Option<u64> {
+        self.approx_row_count(table).ok()
+    }
+
+    fn page_count(&self, table: &str) -> Option<u64> {
+        self.table_pages(table).ok()
+    }
+
+    fn is_primary_key(&self, table: &str, column: &str) -> bool {
+        self.get_schema(table).ok()
+            .and_then(|schema| schema.primary_key_index().map(|idx| schema.columns[idx].name.clone()))
+            .is_some_and(|pk| pk.eq_ignore_ascii_case(column))
+    }
+
+    fn index_prefix(&self, table: &str, columns: &[&str]) -> usize {
+        self.lookup_index(table, columns).ok().flatten().map_or(0, |(_, prefix)| prefix.len())
+    }
+
+    fn serves_operator(&self, table: &str, column: &str, op: &BinaryOperator, value: &Expr) -> bool {
+        let Ok(schema) = self.get_schema(table) else {
+            return false;
+        };
+        let Some(column) = schema.get_column_index(column).map(|idx| &schema.columns[idx]) else {
+            return false;
+        };
+        let value_type = typing::infer_type(value, &schema, self);
+        self.operator_index(table, column, &op.to_string(), &value_type).ok().flatten().is_some()
+    }
+}
+
+/// Estimated size and cost of a plan node's output
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Estimate {
+    pub rows: f64,
+    /// Cost before the first row can be returned
+    pub startup_cost: f64,
+    /// Cost of returning every row
+    pub total_cost: f64,
+}
+
+/// A plan node with its estimate and the nodes it reads from
+#[derive(Debug)]
+pub struct EstimatedPlan {
+    /// Name of the node as EXPLAIN shows it, e.g. "Seq Scan on users"
+    pub node: String,
+    /// Conditions, keys and outputs of the node, e.g. "Filter: (age > 30)"
+    pub details: Vec<String>,
+    pub estimate: Estimate,
+    pub children: Vec<EstimatedPlan>,
+}
+
+impl EstimatedPlan {
+    fn leaf(node: String, details: Vec<String>, estimate: Estimate) -> Self {
+        EstimatedPlan { node, details, estimate, children: Vec::new() }
+    }
+
+    /// The plan as the lines of EXPLAIN's QUERY PLAN column, indented as in
+    /// Postgres; `costs` false leaves out the estimates (EXPLAIN (COSTS OFF))
+    pub fn explain_lines(&self, costs: bool) -> Vec<String> {
+        let mut lines = Vec::new();
+        self.push_lines(0, costs, &mut lines);
+        lines
+    }
+
+    fn push_lines(&self, depth: usize, costs: bool, lines: &mut Vec<String>) {
+        let (prefix, detail_indent) = match depth {
+            0 => (String::new(), 2),
+            _ => (format!("{}->  ", " ".repeat(6 * depth - 4)), 6 * depth + 2),
+        };
+        let estimate = if costs {
+            format!(
+                "  (cost={:.2}..{:.2} rows={:.0})",
+                self.estimate.startup_cost, self.estimate.total_cost, self.estimate.rows
+            )
+        } else {
+            String::new()
+        };
+        lines.push(format!("{}{}{}", prefix, self.node, estimate));
+        lines.extend(self.details.iter().map(|detail| format!("{}{}", " ".repeat(detail_indent), detail)));
+        for child in &self.children {
+            child.push_lines(depth + 1, costs, lines);
+        }
+    }
+}
+
+/// Estimate every node of a plan
+pub fn estimate(plan: &Operator, stats: &dyn Statistics) -> EstimatedPlan {
+    match plan {
+        Operator::TableScan { table } if table == "__constant__" => EstimatedPlan::leaf(
+            "Result".to_string(),
+            Vec::new(),
+            Estimate { rows: 1.0, startup_cost: 0.0, total_cost: CPU_TUPLE_COST },
+        ),
+        Operator::TableScan { table } => seq_scan(table, None, stats),
+        Operator::IndexScan { table, column, values } => {
+            let condition = match values.as_slice() {
+                [value] => format!("({} = {})", column, value),
+                values => format!("({} = ANY ({}))", column, join(values)),
+            };
+            let lookups = values.len() as f64;
+            if !stats.is_primary_key(table, column) {
+                if stats.index_prefix(table, &[column]) > 0 {
+                    let rows = clamp_rows(table_rows(table, stats) * (lookups * DEFAULT_EQ_SEL).min(1.0));
+                    return index_scan(table, condition, lookups, rows);
+                }
+                // Executed as a scan comparing every row
+                return seq_scan(table, Some((condition, lookups * DEFAULT_EQ_SEL)), stats);
+            }
+            let rows = lookups.min(table_rows(table, stats));
+            EstimatedPlan::leaf(
+                format!("Index Scan on {}", table),
+                vec![format!("Index Cond: {}", condition)],
+                Estimate {
+                    rows,
+                    startup_cost: 0.0,
+                    total_cost: lookups * (RANDOM_PAGE_COST + CPU_INDEX_TUPLE_COST) + rows * CPU_TUPLE_COST,
+                },
+            )
+        }
+        Operator::IndexPrefixScan { table, keys } => {
+            let condition = match keys.as_slice() {
+                [(column, value)] => format!("({} = {})", column, value),
+                keys => format!("({})", keys.iter()
+                    .map(|(column, value)| format!("({} = {})", column, value))
+                    .collect::<Vec<_>>()
+                    .join(" AND ")),
+            };
+            let columns: Vec<&str> = keys.iter().map(|(column, _)| column.as_str()).collect();
+            if columns.iter().any(|column| stats.is_primary_key(table, column)) {
+                return index_scan(table, condition, 1.0, table_rows(table, stats).min(1.0));
+            }
+            match stats.index_prefix(table, &columns) {
+                // Executed as a scan, the Filter above it comparing every row
+                0 => seq_scan(table, None, stats),
+                prefix => {
+                    let rows = clamp_rows(table_rows(table, stats) * DEFAULT_EQ_SEL.powi(prefix as i32));
+                    index_scan(table, condition, 1.0, rows)
+                }
+            }
+        }
+        Operator::IndexRangeScan { table, column, low, high, order, .. } => {
+            let condition = match (low, high) {
+                (Some(low), Some(high)) => Some(format!("({} BETWEEN {} AND {})", column, low, high)),
+                _ => None,
+            };
+            let selectivity = if condition.is_some() { DEFAULT_RANGE_SEL } else { 1.0 };
+            if !stats.is_primary_key(table, column) {
+                // Executed as a scan, sorted after when an order is asked for
+                let scan = seq_scan(table, condition.map(|condition| (condition, selectivity)), stats);
+                return match order {
+                    Some(ScanDirection::Backward) => sort(scan, format!("{} DESC", column)),
+                    Some(ScanDirection::Forward) => sort(scan, column.clone()),
+                    None => scan,
+                };
+            }
+            let rows = clamp_rows(table_rows(table, stats) * selectivity);
+            let pages = table_pages(table, stats) * selectivity;
+            let node = match order {
+                Some(ScanDirection::Backward) => format!("Index Scan Backward on {}", table),
+                _ => format!("Index Scan on {}", table),
+            };
+            EstimatedPlan::leaf(
+                node,
+                condition.map(|condition| format!("Index Cond: {}", condition)).into_iter().collect(),
+                Estimate {
+                    rows,
+                    startup_cost: RANDOM_PAGE_COST,
+                    total_cost: RANDOM_PAGE_COST
+                        + pages.ceil() * RANDOM_PAGE_COST
+                        + rows * (CPU_INDEX_TUPLE_COST + CPU_TUPLE_COST),
+                },
+            )
+        }
+        Operator::IndexOperatorScan { table, column, operator, value } => {
+            let condition = format!("({} {} {})", column, operator, value);
+            if !stats.serves_operator(table, column, operator, value) {
+                // Executed as a scan applying the operator to every row
+                return seq_scan(table, Some((condition, DEFAULT_MATCH_SEL)), stats);
+            }
+            let rows = clamp_rows(table_rows(table, stats) * DEFAULT_MATCH_SEL);
+            index_scan(table, condition, 1.0, rows)
+        }
+        Operator::Values { rows, .. } => {
+            let count = rows.len() as f64;
+            EstimatedPlan::leaf(
+                "Values Scan".to_string(),
+                Vec::new(),
+                Estimate { rows: count, startup_cost: 0.0, total_cost: count * CPU_TUPLE_COST },
+            )
+        }
+        Operator::Join { left, right, left_qualifier, right_qualifier, condition } => {
+            let left = aliased(estimate(left, stats), left, left_qualifier.as_deref());
+            let right = aliased(estimate(right, stats), right, right_qualifier.as_deref());
+            let pairs = left.estimate.rows * right.estimate.rows;
+            let (rows, details) = match condition {
+                Some(condition) => (
+                    clamp_rows(pairs * selectivity(condition, None, stats)),
+                    vec![format!("Join Filter: {}", parenthesized(condition))],
+                ),
+                None => (pairs, Vec::new()),
+            };
+            let estimate = Estimate {
+                rows,
+                startup_cost: left.estimate.startup_cost + right.estimate.startup_cost,
+                // The right side is read once and kept for every left row
+                total_cost: left.estimate.total_cost
+                    + right.estimate.total_cost
+                    + pairs * CPU_OPERATOR_COST
+                    + rows * CPU_TUPLE_COST,
+            };
+            EstimatedPlan { node: "Nested Loop".to_string(), details, estimate, children: vec![left, right] }
+        }
+        Operator::Filter { input, predicate } => {
+            let table = scanned_table(input);
+            // An index scan below has applied part of the predicate already;
+            // the rows passing all of it are a fraction of the whole table
+            let candidates = match &**input {
+                Operator::IndexPrefixScan { table, .. } => Some(table_rows(table, stats)),
+                _ => None,
+            };
+            let input = estimate(input, stats);
+            let estimate = Estimate {
+                rows: clamp_rows(candidates.unwrap_or(input.estimate.rows) * selectivity(predicate, table, stats)),
+                startup_cost: input.estimate.startup_cost,
+                total_cost: input.estimate.total_cost + input.estimate.rows * operator_cost(predicate),
+            };
+            EstimatedPlan {
+                node: "Filter".to_string(),
+                details: vec![format!("Filter: {}", parenthesized(predicate))],
+                estimate,
+                children: vec![input],
+            }
+        }
+        Operator::Sort { input, keys } => {
+            let keys = keys.iter()
+                .map(|key| if key.descending { format!("{} DESC", key.expr) } else { key.expr.to_string() })
+                .collect::<Vec<_>>()
+                .join(", ");
+            sort(estimate(input, stats), keys)
+        }
+        Operator::Project { input, columns, .. } => {
+            let input = estimate(input, stats);
+            let estimate = Estimate {
+                rows: input.estimate.rows,
+                startup_cost: input.estimate.startup_cost,
+                total_cost: input.estimate.total_cost
+                    + input.estimate.rows * columns.iter().map(operator_cost).sum::<f64>(),
+            };
+            EstimatedPlan {
+                node: "Project".to_string(),
+                details: vec![format!("Output: {}", join(columns))],
+                estimate,
+                children: vec![input],
+            }
+        }
+        Operator::Aggregate { input, group_by, aggregates } => {
+            let input = estimate(input, stats);
+            let per_row = group_by.iter().chain(aggregates).map(operator_cost).sum::<f64>();
+            let startup_cost = input.estimate.total_cost + input.estimate.rows * per_row;
+            let rows = if group_by.is_empty() {
+                1.0
+            } else {
+                clamp_rows(input.estimate.rows.min(DEFAULT_GROUPS))
+            };
+            let details = if group_by.is_empty() {
+                Vec::new()
+            } else {
+                vec![format!("Group Key: {}", join(group_by))]
+            };
+            EstimatedPlan {
+                node: "Aggregate".to_string(),
+                details,
+                estimate: Estimate { rows, startup_cost, total_cost: startup_cost + rows * CPU_TUPLE_COST },
+                children: vec![input],
+            }
+        }
+        Operator::Limit { input, limit, offset } => {
+            let input = estimate(input, stats);
+            let available = input.estimate.rows;
+            // Counts that are not literals could be anything; assume a tenth
+            // of the rows, as Postgres does for a LIMIT it can't evaluate
+            let offset = offset.as_deref().map_or(0.0, |offset| literal_count(offset).unwrap_or(available * 0.1));
+            let limit = limit.as_deref().map_or(available, |limit| literal_count(limit).unwrap_or(available * 0.1));
+            let rows = limit.min((available - offset).max(0.0));
+            let run_cost = input.estimate.total_cost - input.estimate.startup_cost;
+            let fraction = |count: f64| if available > 0.0 { (count / available).min(1.0) } else { 0.0 };
+            let estimate = Estimate {
+                rows,
+                startup_cost: input.estimate.startup_cost + run_cost * fraction(offset),
+                total_cost: input.estimate.startup_cost + run_cost * fraction(offset + rows),
+            };
+            EstimatedPlan { node: "Limit".to_string(), details: Vec::new(), estimate, children: vec![input] }
+        }
+        Operator::Update { table, selection, .. } => modify("Update", table, selection.as_ref(), stats),
+        Operator::Delete { table, selection } => modify("Delete", table, selection.as_ref(), stats),
+    }
+}
+
+/// A full scan of a table, comparing each row against a filter if given,
+/// with the fraction of rows expected to pass it
+fn seq_scan(table: &str, filter: Option<(String, f64)>, stats: &dyn Statistics) -> EstimatedPlan {
+    let rows = table_rows(table, stats);
+    let mut estimate = Estimate {
+        rows,
+        startup_cost: 0.0,
+        total_cost: table_pages(table, stats) * SEQ_PAGE_COST + rows * CPU_TUPLE_COST,
+    };
+    let details = match filter {
+        Some((filter, selectivity)) => {
+            estimate.rows = clamp_rows(rows * selectivity.min(1.0));
+            estimate.total_cost += rows * CPU_OPERATOR_COST;
+            vec![format!("Filter: {}", filter)]
+        }
+        None => Vec::new(),
+    };
+    EstimatedPlan::leaf(format!("Seq Scan on {}", table), details, estimate)
+}
+
+/// Sorting every row of `input` by the listed keys
+fn sort(input: EstimatedPlan, keys: String) -> EstimatedPlan {
+    let rows = input.estimate.rows;
+    let comparisons = if rows > 1.0 { rows * rows.log2() } else { 0.0 };
+    let startup_cost = input.estimate.total_cost + comparisons * 2.0 * CPU_OPERATOR_COST;
+    EstimatedPlan {
+        node: "Sort".to_string(),
+        details: vec![format!("Sort Key: {}", keys)],
+        estimate: Estimate { rows, startup_cost, total_cost: startup_cost + rows * CPU_OPERATOR_COST },
+        children: vec![input],
+    }
+}
+
+/// Lookups of `rows` rows in total through an index
+fn index_scan(table: &str, condition: String, lookups: f64, rows: f64) -> EstimatedPlan {
+    EstimatedPlan::leaf(
+        format!("Index Scan on {}", table),
+        vec![format!("Index Cond: {}", condition)],
+        Estimate {
+            rows,
+            startup_cost: 0.0,
+            total_cost: lookups * RANDOM_PAGE_COST + rows * (RANDOM_PAGE_COST + CPU_INDEX_TUPLE_COST + CPU_TUPLE_COST),
+        },
+    )
+}
+
+/// UPDATE or DELETE: a scan for the matching rows, each of which is rewritten
+fn modify(command: &str, table: &str, selection: Option<&Expr>, stats: &dyn Statistics) -> EstimatedPlan {
+    let mut scan = seq_scan(table, None, stats);
+    if let Some(selection) = selection {
+        scan.estimate.rows = clamp_rows(scan.estimate.rows * selectivity(selection, Some(table), stats));
+        scan.estimate.total_cost += table_rows(table, stats) * operator_cost(selection);
+        scan.details.push(format!("Filter: {}", parenthesized(selection)));
+    }
+    let estimate = Estimate {
+        // Like Postgres, the node itself returns no rows
+        rows: 0.0,
+        startup_cost: scan.estimate.startup_cost,
+        total_cost: scan.estimate.total_cost + scan.estimate.rows * CPU_TUPLE_COST,
+    };
+    EstimatedPlan {
+        node: format!("{} on {}", command, table),
+        details: Vec::new(),
+        estimate,
+        children: vec![scan],
+    }
+}
+
+/// Name a join's scan by the alias its columns are qualified with, when the
+/// table has one ("Seq Scan on users u")
+fn aliased(mut plan: EstimatedPlan, input: &Operator, qualifier: Option<&str>) -> EstimatedPlan {
+    let table = match input {
+        Operator::TableScan { table }
+        | Operator::IndexScan { table, .. }
+        | Operator::IndexPrefixScan { table, .. }
+        | Operator::IndexRangeScan { table, .. }
+        | Operator::IndexOperatorScan { table, .. } => table,
+        _ => return plan,
+    };
+    if let Some(alias) = qualifier.filter(|alias| *alias != table) {
+        plan.node = format!("{} {}", plan.node, alias);
+    }
+    plan
+}
+
+fn table_rows(table: &str, stats: &dyn Statistics) -> f64 {
+    stats.row_count(table).map_or(DEFAULT_TABLE_ROWS, |rows| rows as f64)
+}
+
+fn table_pages(table: &str, stats: &dyn Statistics) -> f64 {
+    stats.page_count(table).map_or(1.0, |pages| pages as f64)
+}
+
+/// Estimates never go below one row, as in Postgres, unless the input is empty
+fn clamp_rows(rows: f64) -> f64 {
+    if rows > 0.0 { rows.max(1.0).round() } else { 0.0 }
+}
+
+/// Table whose rows a filter's input reads, for recognizing its primary key
+fn scanned_table(input: &Operator) -> Option<&str> {
+    match input {
+        Operator::TableScan { table } | Operator::IndexPrefixScan { table, .. } if table != "__constant__" => Some(table),
+        _ => None,
+    }
+}
+
+/// Fraction of rows a predicate holds for
+fn selectivity(predicate: &Expr, table: Option<&str>, stats: &dyn Statistics) -> f64 {
+    let is_primary_key = |expr: &Expr| match (expr, table) {
+        (Expr::Identifier(ident), Some(table)) => stats.is_primary_key(table, &ident.value),
+        _ => false,
+    };
+    let eq_sel = |column: &Expr| match table {
+        Some(table) if is_primary_key(column) => 1.0 / table_rows(table, stats).max(1.0),
+        _ => DEFAULT_EQ_SEL,
+    };
+
+    let sel = match predicate {
+        Expr::Nested(inner) => selectivity(inner, table, stats),
+        Expr::BinaryOp { left, op: BinaryOperator::And, right } => {
+            selectivity(left, table, stats) * selectivity(right, table, stats)
+        }
+        Expr::BinaryOp { left, op: BinaryOperator::Or, right } => {
+            let (left, right) = (selectivity(left, table, stats), selectivity(right, table, stats));
+            left + right - left * right
+        }
+        Expr::UnaryOp { op: UnaryOperator::Not, expr } => 1.0 - selectivity(expr, table, stats),
+        Expr::BinaryOp { left, op: BinaryOperator::Eq, right } => {
+            if is_primary_key(right) { eq_sel(right) } else { eq_sel(left) }
+        }
+        Expr::BinaryOp { op: BinaryOperator::NotEq, left, .. } => 1.0 - eq_sel(left),
+        Expr::BinaryOp {
+            op: BinaryOperator::Lt | BinaryOperator::LtEq | BinaryOperator::Gt | BinaryOperator::GtEq,
+            ..
+        } => DEFAULT_INEQ_SEL,
+        Expr::Between { negated, .. } => negate(DEFAULT_RANGE_SEL, *negated),
+        Expr::InList { expr, list, negated } => negate(eq_sel(expr) * list.len() as f64, *negated),
+        Expr::Like { negated, .. } | Expr::ILike { negated, .. } => negate(DEFAULT_MATCH_SEL, *negated),
+        Expr::IsNull(_) => DEFAULT_NULL_SEL,
+        Expr::IsNotNull(_) => 1.0 - DEFAULT_NULL_SEL,
+        _ => DEFAULT_BOOL_SEL,
+    };
+    sel.clamp(0.0, 1.0)
+}
+
+fn negate(sel: f64, negated: bool) -> f64 {
+    if negated { 1.0 - sel } else { sel }
+}
+
+/// Cost of evaluating an expression once: one unit per operator or function
+fn operator_cost(expr: &Expr) -> f64 {
+    let mut operators = 0;
+    let _ = sqlparser::ast::visit_expressions(expr, |expr| {
+        if matches!(
+            expr,
+            Expr::BinaryOp { .. }
+                | Expr::UnaryOp { .. }
+                | Expr::Function(_)
+                | Expr::Between { .. }
+                | Expr::InList { .. }
+                | Expr::Like { .. }
+                | Expr::ILike { .. }
+                | Expr::IsNull(_)
+                | Expr::IsNotNull(_)
+                | Expr::Cast { .. }
+        ) {
+            operators += 1;
+        }
+        std::ops::ControlFlow::<()>::Continue(())
+    });
+    operators as f64 * CPU_OPERATOR_COST
+}
+
+/// A LIMIT or OFFSET count given as a literal
+fn literal_count(expr: &Expr) -> Option<f64> {
+    match expr {
+        Expr::Value(value) => match &value.value {
+            sqlparser::ast::Value::Number(n, _) => n.parse::<f64>().ok().map(|n| n.max(0.0)),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+fn parenthesized(expr: &Expr) -> String {
+    match expr {
+        Expr::Nested(_) => expr.to_string(),
+        _ => format!("({})", expr),
+    }
+}
+
+fn join(exprs: &[Expr]) -> String {
+    exprs.iter().map(Expr::to_string).collect::<Vec<_>>().join(", ")
+}
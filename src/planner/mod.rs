@@ -1,30 +1,114 @@
-use sqlparser::ast::{Statement, CreateTable, Insert, CreateIndex};
+pub mod cost;
+
+use sqlparser::ast::{Statement, CreateTable, Insert, CreateIndex, VacuumStatement, CommentObject, ObjectName, AlterTableOperation};
 use tracing::debug;
 
+use crate::executor::aggregate;
 use crate::executor::error::ExecutorError;
+use crate::executor::evaluator;
+use crate::executor::functions;
+use crate::executor::workload::WorkloadClass;
+use crate::storage::index::ScanDirection;
+use crate::storage::catalog::{CheckConstraint, ForeignKey, FunctionMetadata, ProcedureMetadata, ReferentialAction, TableOptions, TtlPolicy};
+use crate::storage::sequence::SequenceOptions;
 use crate::types::{Schema, Column, DataType};
 
+/// Longest IN list planned as point lookups; a longer list is cheaper to
+/// check with one pass over the table
+const MAX_INDEX_LOOKUPS: usize = 32;
+
+/// One key of an ORDER BY
+#[derive(Debug)]
+pub struct SortKey {
+    /// Evaluated against the input rows; a bare integer is the position of an
+    /// input column, left for execution when the select list is just *
+    pub expr: sqlparser::ast::Expr,
+    pub descending: bool,
+    /// NULLs sort before every other value; by default only when descending,
+    /// as in Postgres where NULL is larger than any value
+    pub nulls_first: bool,
+}
+
 #[derive(Debug)]
 pub enum Operator {
     /// Scan all rows from a table
     TableScan {
         table: String,
     },
-    /// Index scan for exact key lookup
+    /// Index scan for exact key lookups, one per value: `col = value` or a
+    /// short `col IN (...)` list
     IndexScan {
         table: String,
         column: String,
-        value: sqlparser::ast::Expr,
+        values: Vec<sqlparser::ast::Expr>,
+    },
+    /// Index scan for the rows matching equalities on several columns at once,
+    /// `a = 1 AND b = 2`, through the index with the most leading columns
+    /// among them; a Filter above it checks the rows it returns
+    IndexPrefixScan {
+        table: String,
+        /// Each column with the constant it equals
+        keys: Vec<(String, sqlparser::ast::Expr)>,
+    },
+    /// Index scan for the keys between two bounds, both inclusive:
+    /// `col BETWEEN low AND high`; without bounds it reads every key
+    IndexRangeScan {
+        table: String,
+        column: String,
+        low: Option<Box<sqlparser::ast::Expr>>,
+        high: Option<Box<sqlparser::ast::Expr>>,
+        /// Order the rows come back in, by the column, for an ORDER BY the
+        /// scan stands in for; None returns them in any order
+        order: Option<ScanDirection>,
+        /// Rows a LIMIT above the scan reads from it, counting those its
+        /// OFFSET skips, when only a projection lies between them; the scan
+        /// may stop once it has found that many
+        limit: Option<Box<sqlparser::ast::Expr>>,
+    },
+    /// Scan for the rows an extension operator holds for, `col @> value`,
+    /// through an index whose operator class has the operator, else over
+    /// the whole table; returns only the rows the operator holds for
+    IndexOperatorScan {
+        table: String,
+        column: String,
+        operator: sqlparser::ast::BinaryOperator,
+        value: Box<sqlparser::ast::Expr>,
+    },
+    /// Literal rows of a VALUES list, read as a table with the given schema
+    Values {
+        schema: Schema,
+        rows: Vec<Vec<sqlparser::ast::Expr>>,
+    },
+    /// Inner join of two inputs by nested loop: each left row is paired with
+    /// every right row and the pairs the condition holds for are kept, as the
+    /// left row's values followed by the right row's
+    Join {
+        left: Box<Operator>,
+        right: Box<Operator>,
+        /// Table name or alias each side's columns are qualified with in the
+        /// joined rows ("users.id"); None for a side whose columns keep their
+        /// names, such as a join, whose columns are qualified already
+        left_qualifier: Option<String>,
+        right_qualifier: Option<String>,
+        /// ON condition, evaluated against the joined row; None for a cross join
+        condition: Option<sqlparser::ast::Expr>,
     },
     /// Filter rows with a predicate
     Filter {
         input: Box<Operator>,
         predicate: sqlparser::ast::Expr,
     },
+    /// Sort rows by one or more keys, evaluated against the input rows
+    Sort {
+        input: Box<Operator>,
+        keys: Vec<SortKey>,
+    },
     /// Project columns from input
     Project {
         input: Box<Operator>,
         columns: Vec<sqlparser::ast::Expr>,
+        /// Output name of each column; a wildcard keeps the source's names
+        names: Vec<String>,
     },
     /// Aggregate with GROUP BY
     Aggregate {
@@ -33,10 +117,25 @@ pub enum Operator {
         aggregates: Vec<sqlparser::ast::Expr>,
     },
     /// Limit/offset rows
+    /// Both counts are expressions evaluated at execution; a missing (or NULL)
+    /// limit keeps every row past the offset
     Limit {
         input: Box<Operator>,
-        limit: u64,
-        offset: Option<u64>,
+        limit: Option<Box<sqlparser::ast::Expr>>,
+        offset: Option<Box<sqlparser::ast::Expr>>,
+    },
+    /// Rewrite the rows of a table matching an optional predicate
+    Update {
+        table: String,
+        /// Column name and the expression for its new value, evaluated
+        /// against the row being updated
+        assignments: Vec<(String, sqlparser::ast::Expr)>,
+        selection: Option<sqlparser::ast::Expr>,
+    },
+    /// Delete the rows of a table matching an optional predicate
+    Delete {
+        table: String,
+        selection: Option<sqlparser::ast::Expr>,
     },
 }
 
@@ -45,6 +144,15 @@ pub fn plan(stmt: &Statement) -> Result<Operator, ExecutorError> {
 
     match stmt {
         Statement::Query(query) => plan_select(query),
+        Statement::Update { table, assignments, from, selection, returning, limit, .. } => {
+            if from.is_some() || returning.is_some() || limit.is_some() {
+                return Err(ExecutorError::UnsupportedStatement(
+                    "UPDATE with FROM, RETURNING or LIMIT not supported".to_string(),
+                ));
+            }
+            plan_update(table, assignments, selection.as_ref())
+        }
+        Statement::Delete(delete) => plan_delete(delete),
         Statement::StartTransaction { .. } => {
             debug!("plan: start transaction (handled by executor)");
             Err(ExecutorError::UnsupportedStatement(
@@ -73,7 +181,90 @@ pub fn plan(stmt: &Statement) -> Result<Operator, ExecutorError> {
     }
 }
 
+fn plan_update(
+    table: &sqlparser::ast::TableWithJoins,
+    assignments: &[sqlparser::ast::Assignment],
+    selection: Option<&sqlparser::ast::Expr>,
+) -> Result<Operator, ExecutorError> {
+    if !table.joins.is_empty() {
+        return Err(ExecutorError::UnsupportedStatement(
+            "UPDATE with joins not supported".to_string(),
+        ));
+    }
+    let table_name = extract_table_name(table)?;
+    debug!(table = %table_name, "plan: update");
+
+    Ok(Operator::Update {
+        table: table_name,
+        assignments: extract_assignments(assignments)?,
+        selection: selection.cloned(),
+    })
+}
+
+/// Column name and value expression of each `column = value` of a SET list
+fn extract_assignments(assignments: &[sqlparser::ast::Assignment]) -> Result<Vec<(String, sqlparser::ast::Expr)>, ExecutorError> {
+    assignments.iter()
+        .map(|assignment| match &assignment.target {
+            sqlparser::ast::AssignmentTarget::ColumnName(name) => {
+                let column = name.0.last()
+                    .and_then(|part| part.as_ident())
+                    .map(|ident| ident.value.clone())
+                    .ok_or_else(|| ExecutorError::Execution("Column name is empty".to_string()))?;
+                Ok((column, assignment.value.clone()))
+            }
+            sqlparser::ast::AssignmentTarget::Tuple(_) => Err(ExecutorError::UnsupportedStatement(
+                "Assigning to a tuple of columns not supported".to_string(),
+            )),
+        })
+        .collect()
+}
+
+fn plan_delete(delete: &sqlparser::ast::Delete) -> Result<Operator, ExecutorError> {
+    if !delete.tables.is_empty() || delete.using.is_some() || delete.returning.is_some()
+        || !delete.order_by.is_empty() || delete.limit.is_some()
+    {
+        return Err(ExecutorError::UnsupportedStatement(
+            "DELETE with USING, RETURNING, ORDER BY or LIMIT not supported".to_string(),
+        ));
+    }
+    let tables = match &delete.from {
+        sqlparser::ast::FromTable::WithFromKeyword(tables) | sqlparser::ast::FromTable::WithoutKeyword(tables) => tables,
+    };
+    let [table] = tables.as_slice() else {
+        return Err(ExecutorError::UnsupportedStatement(
+            "DELETE from multiple tables not supported".to_string(),
+        ));
+    };
+    if !table.joins.is_empty() {
+        return Err(ExecutorError::UnsupportedStatement(
+            "DELETE with joins not supported".to_string(),
+        ));
+    }
+    let table_name = extract_table_name(table)?;
+    debug!(table = %table_name, "plan: delete");
+
+    Ok(Operator::Delete {
+        table: table_name,
+        selection: delete.selection.clone(),
+    })
+}
+
 fn plan_select(query: &sqlparser::ast::Query) -> Result<Operator, ExecutorError> {
+    if let sqlparser::ast::SetExpr::Values(values) = &*query.body {
+        debug!("plan: values list");
+        let plan = plan_values(values, None)?;
+        // Positions in ORDER BY refer to the list's columns
+        let names: Vec<String> = match &plan {
+            Operator::Values { schema, .. } => schema.columns.iter().map(|column| column.name.clone()).collect(),
+            _ => Vec::new(),
+        };
+        let columns: Vec<_> = names.iter()
+            .map(|name| sqlparser::ast::Expr::Identifier(sqlparser::ast::Ident::new(name)))
+            .collect();
+        let keys = sort_keys(query.order_by.as_ref(), &columns, &names)?;
+        return Ok(plan_limit(plan_sort(plan, keys), query.limit_clause.as_ref()));
+    }
+
     if let sqlparser::ast::SetExpr::Select(select) = &*query.body {
         // Start with TableScan if there's a FROM clause
         let (mut plan, table_name_opt) = if select.from.is_empty() {
@@ -82,26 +273,61 @@ fn plan_select(query: &sqlparser::ast::Query) -> Result<Operator, ExecutorError>
             (Operator::TableScan {
                 table: "__constant__".to_string(),
             }, None)
-        } else if select.from.len() == 1 {
-            let table_name = extract_table_name(&select.from[0])?;
-            debug!(table = %table_name, "plan: table scan");
-            (Operator::TableScan { table: table_name.clone() }, Some(table_name))
+        } else if let [sqlparser::ast::TableWithJoins { relation, joins }] = select.from.as_slice()
+            && joins.is_empty()
+        {
+            let (plan, _) = plan_relation(relation)?;
+            let table_name = match &plan {
+                Operator::TableScan { table } => Some(table.clone()),
+                _ => None,
+            };
+            (plan, table_name)
         } else {
-            return Err(ExecutorError::UnsupportedStatement(
-                "Multiple tables not yet supported".to_string(),
-            ));
+            (plan_joins(&select.from)?, None)
         };
 
-        // Try to use IndexScan for equality predicates on primary key
+        // Try to use IndexScan for equality and IN-list predicates, IndexRangeScan
+        // for BETWEEN, IndexPrefixScan for equalities ANDed with more terms, and
+        // IndexOperatorScan for extension operators
         if let Some(selection) = &select.selection {
             if let Some(table_name) = &table_name_opt {
-                // Check if selection is a simple equality (col = value)
-                if let Some((col_name, value_expr)) = try_extract_equality(selection) {
-                    debug!(column = %col_name, "plan: attempting index scan");
+                // Check if selection is a simple equality (col = value) or IN list
+                let lookup = try_extract_equality(selection)
+                    .map(|(col_name, value_expr)| (col_name, vec![value_expr]))
+                    .or_else(|| try_extract_in_list(selection));
+                if let Some((col_name, values)) = lookup {
+                    debug!(column = %col_name, lookups = values.len(), "plan: attempting index scan");
                     plan = Operator::IndexScan {
                         table: table_name.clone(),
                         column: col_name,
-                        value: value_expr,
+                        values,
+                    };
+                } else if let Some((col_name, low, high)) = try_extract_between(selection) {
+                    debug!(column = %col_name, "plan: attempting index range scan");
+                    plan = Operator::IndexRangeScan {
+                        table: table_name.clone(),
+                        column: col_name,
+                        low: Some(low),
+                        high: Some(high),
+                        order: None,
+                        limit: None,
+                    };
+                } else if let Some(keys) = try_extract_equalities(selection) {
+                    debug!(columns = keys.len(), "plan: attempting index prefix scan");
+                    plan = Operator::Filter {
+                        input: Box::new(Operator::IndexPrefixScan {
+                            table: table_name.clone(),
+                            keys,
+                        }),
+                        predicate: selection.clone(),
+                    };
+                } else if let Some((col_name, operator, value)) = try_extract_extension_operator(selection) {
+                    debug!(column = %col_name, operator = %operator, "plan: attempting index operator scan");
+                    plan = Operator::IndexOperatorScan {
+                        table: table_name.clone(),
+                        column: col_name,
+                        operator,
+                        value,
                     };
                 } else {
                     debug!("plan: adding filter (not index-able)");
@@ -119,65 +345,98 @@ fn plan_select(query: &sqlparser::ast::Query) -> Result<Operator, ExecutorError>
             }
         }
 
+        let (columns, names): (Vec<_>, Vec<_>) = select
+            .projection
+            .iter()
+            .map(|item| match item {
+                sqlparser::ast::SelectItem::UnnamedExpr(expr) => (expr.clone(), output_name(expr)),
+                sqlparser::ast::SelectItem::ExprWithAlias { expr, alias } => (expr.clone(), alias.value.clone()),
+                sqlparser::ast::SelectItem::QualifiedWildcard(_, _) => {
+                    // Placeholder for wildcard - will expand columns during execution
+                    (sqlparser::ast::Expr::Identifier(sqlparser::ast::Ident::new("*")), "*".to_string())
+                }
+                sqlparser::ast::SelectItem::Wildcard(_) => {
+                    (sqlparser::ast::Expr::Identifier(sqlparser::ast::Ident::new("*")), "*".to_string())
+                }
+            })
+            .unzip();
+
+        let group_by = match &select.group_by {
+            sqlparser::ast::GroupByExpr::Expressions(exprs, modifiers) if modifiers.is_empty() => exprs.iter()
+                .map(|expr| resolve_group_position(expr, &columns, &names))
+                .collect::<Result<Vec<_>, _>>()?,
+            _ => return Err(ExecutorError::UnsupportedStatement(
+                "GROUP BY ALL and grouping modifiers not supported".to_string(),
+            )),
+        };
+        if let Some(expr) = group_by.iter().find(|expr| contains_aggregate(expr)) {
+            return Err(ExecutorError::Execution(format!(
+                "aggregate functions are not allowed in GROUP BY: {}",
+                expr
+            )));
+        }
+        let mut keys = sort_keys(query.order_by.as_ref(), &columns, &names)?;
+
+        // Aggregates are computed first; the projection and sort then read the
+        // group keys and aggregate results as columns of the aggregated rows
+        let mut aggregates = Vec::new();
+        let mut columns: Vec<_> = columns.iter()
+            .map(|column| extract_aggregates(column, &mut aggregates))
+            .collect();
+        for key in &mut keys {
+            key.expr = extract_aggregates(&key.expr, &mut aggregates);
+        }
+        // HAVING may use aggregates that are not selected
+        let mut having = select.having.as_ref().map(|having| extract_aggregates(having, &mut aggregates));
+        if !aggregates.is_empty() || !group_by.is_empty() || having.is_some() {
+            columns = columns.iter().map(|column| replace_grouped(column, &group_by)).collect();
+            for key in &mut keys {
+                key.expr = replace_grouped(&key.expr, &group_by);
+            }
+            having = having.map(|having| replace_grouped(&having, &group_by));
+            let available: Vec<String> = group_by.iter().chain(&aggregates).map(aggregate_column).collect();
+            let ungrouped = columns.iter()
+                .chain(keys.iter().map(|key| &key.expr))
+                .chain(having.as_ref())
+                .find_map(|expr| ungrouped_column(expr, &available));
+            if let Some(column) = ungrouped {
+                return Err(ExecutorError::Execution(format!(
+                    "column \"{}\" must appear in the GROUP BY clause or be used in an aggregate function",
+                    column
+                )));
+            }
+            debug!(group_count = group_by.len(), aggregate_count = aggregates.len(), "plan: adding aggregate");
+            plan = Operator::Aggregate {
+                input: Box::new(plan),
+                group_by,
+                aggregates,
+            };
+            if let Some(having) = having {
+                debug!("plan: adding having filter");
+                plan = Operator::Filter {
+                    input: Box::new(plan),
+                    predicate: having,
+                };
+            }
+        }
+
+        // Sorting happens before projection, so ORDER BY may use columns that
+        // are not selected
+        if !order_scan(&mut plan, &keys) {
+            plan = plan_sort(plan, keys);
+        }
+
         // Add projection (SELECT columns)
-        if !select.projection.is_empty() {
-            let columns = select
-                .projection
-                .iter()
-                .map(|item| match item {
-                    sqlparser::ast::SelectItem::UnnamedExpr(expr) => expr.clone(),
-                    sqlparser::ast::SelectItem::ExprWithAlias { expr, .. } => expr.clone(),
-                    sqlparser::ast::SelectItem::QualifiedWildcard(_, _) => {
-                        // Placeholder for wildcard - will expand columns during execution
-                        sqlparser::ast::Expr::Identifier(sqlparser::ast::Ident::new("*"))
-                    }
-                    sqlparser::ast::SelectItem::Wildcard(_) => {
-                        sqlparser::ast::Expr::Identifier(sqlparser::ast::Ident::new("*"))
-                    }
-                })
-                .collect::<Vec<_>>();
+        if !columns.is_empty() {
             debug!(column_count = columns.len(), "plan: adding projection");
             plan = Operator::Project {
                 input: Box::new(plan),
                 columns,
+                names,
             };
         }
 
-        // Add LIMIT if present
-        if let Some(limit_clause) = &query.limit_clause {
-            if let sqlparser::ast::LimitClause::LimitOffset { limit: Some(limit_expr), offset, .. } = limit_clause {
-                // Extract limit value from expression
-                if let sqlparser::ast::Expr::Value(val) = limit_expr {
-                    if let sqlparser::ast::Value::Number(num_str, _) = &val.value {
-                        if let Ok(limit_val) = num_str.parse::<u64>() {
-                            let offset_val = if let Some(off) = offset {
-                                match off {
-                                    sqlparser::ast::Offset { value: sqlparser::ast::Expr::Value(v), .. } => {
-                                        if let sqlparser::ast::Value::Number(off_str, _) = &v.value {
-                                            off_str.parse::<u64>().ok()
-                                        } else {
-                                            None
-                                        }
-                                    }
-                                    _ => None
-                                }
-                            } else {
-                                None
-                            };
-
-                            debug!(limit = limit_val, offset = ?offset_val, "plan: adding limit");
-                            plan = Operator::Limit {
-                                input: Box::new(plan),
-                                limit: limit_val,
-                                offset: offset_val,
-                            };
-                        }
-                    }
-                }
-            }
-        }
-
-        Ok(plan)
+        Ok(push_limit(plan_limit(plan, query.limit_clause.as_ref())))
     } else {
         Err(ExecutorError::UnsupportedStatement(
             "Only SELECT queries supported".to_string(),
@@ -185,6 +444,447 @@ fn plan_select(query: &sqlparser::ast::Query) -> Result<Operator, ExecutorError>
     }
 }
 
+/// Name of the column a group key or aggregate call's result is read from,
+/// in the rows an Aggregate produces
+pub fn aggregate_column(call: &sqlparser::ast::Expr) -> String {
+    call.to_string()
+}
+
+/// Replace every aggregate call in `expr` with a reference to its result
+/// column, collecting the calls (once each) into `aggregates`
+fn extract_aggregates(expr: &sqlparser::ast::Expr, aggregates: &mut Vec<sqlparser::ast::Expr>) -> sqlparser::ast::Expr {
+    let mut expr = expr.clone();
+    let _ = sqlparser::ast::visit_expressions_mut(&mut expr, |expr| {
+        if aggregate::is_aggregate_call(expr) {
+            if !aggregates.contains(expr) {
+                aggregates.push(expr.clone());
+            }
+            *expr = sqlparser::ast::Expr::Identifier(sqlparser::ast::Ident::new(aggregate_column(expr)));
+        }
+        std::ops::ControlFlow::<()>::Continue(())
+    });
+    expr
+}
+
+/// Replace every occurrence of a GROUP BY expression in `expr` with a
+/// reference to the group key column holding its value
+fn replace_grouped(expr: &sqlparser::ast::Expr, group_by: &[sqlparser::ast::Expr]) -> sqlparser::ast::Expr {
+    let mut expr = expr.clone();
+    let _ = sqlparser::ast::visit_expressions_mut(&mut expr, |expr| {
+        if group_by.contains(expr) {
+            *expr = sqlparser::ast::Expr::Identifier(sqlparser::ast::Ident::new(aggregate_column(expr)));
+        }
+        std::ops::ControlFlow::<()>::Continue(())
+    });
+    expr
+}
+
+/// Whether an expression calls an aggregate function anywhere
+fn contains_aggregate(expr: &sqlparser::ast::Expr) -> bool {
+    sqlparser::ast::visit_expressions(expr, |expr| {
+        if aggregate::is_aggregate_call(expr) {
+            std::ops::ControlFlow::Break(())
+        } else {
+            std::ops::ControlFlow::Continue(())
+        }
+    }).is_break()
+}
+
+/// A GROUP BY entry that is a bare integer names a select list entry by
+/// position, as in ORDER BY
+fn resolve_group_position(
+    expr: &sqlparser::ast::Expr,
+    columns: &[sqlparser::ast::Expr],
+    names: &[String],
+) -> Result<sqlparser::ast::Expr, ExecutorError> {
+    let sqlparser::ast::Expr::Value(val) = expr else {
+        return Ok(expr.clone());
+    };
+    if !matches!(val.value, sqlparser::ast::Value::Number(_, _)) {
+        return Ok(expr.clone());
+    }
+    let position = val.value.to_string();
+    let idx = position.parse::<usize>().ok()
+        .and_then(|position| position.checked_sub(1));
+    if idx.is_some_and(|idx| names.iter().take(idx + 1).any(|name| name == "*")) {
+        return Err(ExecutorError::UnsupportedStatement(
+            "GROUP BY position at or past a * not supported".to_string(),
+        ));
+    }
+    idx.and_then(|idx| columns.get(idx))
+        .cloned()
+        .ok_or_else(|| ExecutorError::Execution(format!(
+            "GROUP BY position {} is not in select list",
+            position
+        )))
+}
+
+/// First column an aggregated select list entry reads from the input rows
+/// directly, rather than from the `available` columns of the aggregated row
+fn ungrouped_column(expr: &sqlparser::ast::Expr, available: &[String]) -> Option<String> {
+    let mut found = None;
+    let _ = sqlparser::ast::visit_expressions(expr, |expr| {
+        let column = match expr {
+            sqlparser::ast::Expr::Identifier(ident) => Some(ident.value.clone()),
+            sqlparser::ast::Expr::CompoundIdentifier(parts) => Some(parts.iter()
+                .map(|part| part.value.clone())
+                .collect::<Vec<_>>()
+                .join(".")),
+            _ => None,
+        };
+        match column {
+            Some(column) if !available.contains(&column) => {
+                found = Some(column);
+                std::ops::ControlFlow::Break(())
+            }
+            _ => std::ops::ControlFlow::Continue(()),
+        }
+    });
+    found
+}
+
+/// Resolve the keys of an ORDER BY, if the query has one
+/// `columns` and `names` are the select list: a key that is a position or an
+/// output column name sorts by that select list entry, anything else is
+/// evaluated against the input rows
+fn sort_keys(
+    order_by: Option<&sqlparser::ast::OrderBy>,
+    columns: &[sqlparser::ast::Expr],
+    names: &[String],
+) -> Result<Vec<SortKey>, ExecutorError> {
+    use sqlparser::ast::{Expr, OrderByKind, Value};
+
+    let Some(order_by) = order_by else {
+        return Ok(Vec::new());
+    };
+    let OrderByKind::Expressions(order_exprs) = &order_by.kind else {
+        return Err(ExecutorError::UnsupportedStatement("ORDER BY ALL not supported".to_string()));
+    };
+
+    let keys = order_exprs.iter()
+        .map(|order_expr| {
+            let expr = match &order_expr.expr {
+                Expr::Value(val) if matches!(val.value, Value::Number(_, _)) && names == ["*"] => {
+                    order_expr.expr.clone()
+                }
+                Expr::Value(val) if matches!(val.value, Value::Number(_, _)) => {
+                    let position = val.value.to_string();
+                    let idx = position.parse::<usize>().ok()
+                        .and_then(|position| position.checked_sub(1));
+                    // Where * expands to is only known at execution
+                    if idx.is_some_and(|idx| names.iter().take(idx + 1).any(|name| name == "*")) {
+                        return Err(ExecutorError::UnsupportedStatement(
+                            "ORDER BY position at or past a * alongside other columns not supported".to_string(),
+                        ));
+                    }
+                    idx.and_then(|idx| columns.get(idx))
+                        .cloned()
+                        .ok_or_else(|| ExecutorError::Execution(format!(
+                            "ORDER BY position {} is not in select list",
+                            position
+                        )))?
+                }
+                Expr::Identifier(ident) => names.iter()
+                    .position(|name| name != "*" && *name == ident.value)
+                    .map_or_else(|| order_expr.expr.clone(), |idx| columns[idx].clone()),
+                expr => expr.clone(),
+            };
+            let descending = order_expr.options.asc == Some(false);
+            Ok(SortKey {
+                expr,
+                descending,
+                nulls_first: order_expr.options.nulls_first.unwrap_or(descending),
+            })
+        })
+        .collect::<Result<Vec<_>, ExecutorError>>()?;
+    Ok(keys)
+}
+
+/// Wrap a plan in a Sort when there are ORDER BY keys
+fn plan_sort(plan: Operator, keys: Vec<SortKey>) -> Operator {
+    if keys.is_empty() {
+        return plan;
+    }
+    debug!(key_count = keys.len(), "plan: adding sort");
+    Operator::Sort {
+        input: Box::new(plan),
+        keys,
+    }
+}
+
+/// Have a table scan, or a range scan over the sort column, return its rows
+/// in the order of a single-column ORDER BY, so no Sort is needed; NULLs must
+/// sort where the index keeps them, last ascending and first descending.
+/// Returns whether it did
+fn order_scan(plan: &mut Operator, keys: &[SortKey]) -> bool {
+    let [SortKey { expr: sqlparser::ast::Expr::Identifier(ident), descending, nulls_first }] = keys else {
+        return false;
+    };
+    if nulls_first != descending {
+        return false;
+    }
+    let direction = if *descending { ScanDirection::Backward } else { ScanDirection::Forward };
+    match plan {
+        Operator::TableScan { table } if table != "__constant__" => {
+            debug!(column = %ident.value, "plan: ordering by an index scan instead of sorting");
+            *plan = Operator::IndexRangeScan {
+                table: table.clone(),
+                column: ident.value.clone(),
+                low: None,
+                high: None,
+                order: Some(direction),
+                limit: None,
+            };
+            true
+        }
+        Operator::IndexRangeScan { column, order, .. } if *column == ident.value => {
+            debug!(column = %column, "plan: ordering the index range scan instead of sorting");
+            *order = Some(direction);
+            true
+        }
+        _ => false,
+    }
+}
+
+/// Wrap a plan in LIMIT/OFFSET when the query has either
+fn plan_limit(plan: Operator, limit_clause: Option<&sqlparser::ast::LimitClause>) -> Operator {
+    let (limit, offset) = match limit_clause {
+        None => return plan,
+        Some(sqlparser::ast::LimitClause::LimitOffset { limit, offset, .. }) => {
+            (limit.clone().map(Box::new), offset.as_ref().map(|offset| Box::new(offset.value.clone())))
+        }
+        Some(sqlparser::ast::LimitClause::OffsetCommaLimit { offset, limit }) => {
+            (Some(Box::new(limit.clone())), Some(Box::new(offset.clone())))
+        }
+    };
+    if limit.is_none() && offset.is_none() {
+        // LIMIT ALL
+        return plan;
+    }
+
+    debug!(limit = ?limit, offset = ?offset, "plan: adding limit");
+    Operator::Limit {
+        input: Box::new(plan),
+        limit,
+        offset,
+    }
+}
+
+/// Let an index range scan stop at the rows a LIMIT over it keeps, when only
+/// a projection lies between them: a Sort, Filter or Aggregate needs every row
+fn push_limit(mut plan: Operator) -> Operator {
+    let Operator::Limit { input, limit: Some(limit), offset } = &mut plan else {
+        return plan;
+    };
+    let scan = match &mut **input {
+        Operator::Project { input, .. } => &mut **input,
+        other => other,
+    };
+    if let Operator::IndexRangeScan { limit: scan_limit, .. } = scan {
+        debug!("plan: pushing limit into index range scan");
+        *scan_limit = Some(match offset {
+            Some(offset) => Box::new(sqlparser::ast::Expr::BinaryOp {
+                left: limit.clone(),
+                op: sqlparser::ast::BinaryOperator::Plus,
+                right: offset.clone(),
+            }),
+            None => limit.clone(),
+        });
+    }
+    plan
+}
+
+/// Output column name of an unaliased select expression, named as Postgres
+/// names it: a column keeps its name, a function call takes the function's
+/// name, a cast that of what it casts or else its type's, and anything else
+/// is ?column?
+fn output_name(expr: &sqlparser::ast::Expr) -> String {
+    use sqlparser::ast::Expr;
+
+    match expr {
+        Expr::Cast { expr, data_type, .. } => match output_name(expr) {
+            name if name == "?column?" => cast_type_name(data_type),
+            name => name,
+        },
+        Expr::TypedString(typed) => cast_type_name(&typed.data_type),
+        Expr::Identifier(ident) => ident.value.clone(),
+        Expr::CompoundIdentifier(parts) => parts.last()
+            .map_or_else(|| "?column?".to_string(), |ident| ident.value.clone()),
+        Expr::Function(func) => func.name.0.last()
+            .and_then(|part| part.as_ident())
+            .map_or_else(|| "?column?".to_string(), |ident| ident.value.clone()),
+        Expr::Nested(inner) => output_name(inner),
+        _ => "?column?".to_string(),
+    }
+}
+
+/// Postgres' internal name for a type, which names a cast's output column
+fn cast_type_name(data_type: &sqlparser::ast::DataType) -> String {
+    use sqlparser::ast::{DataType as SqlDataType, TimezoneInfo};
+
+    let name = match data_type {
+        SqlDataType::SmallInt(_) | SqlDataType::Int2(_) => "int2",
+        SqlDataType::Int(_) | SqlDataType::Integer(_) | SqlDataType::Int4(_) => "int4",
+        SqlDataType::BigInt(_) | SqlDataType::Int8(_) => "int8",
+        SqlDataType::Real | SqlDataType::Float4 => "float4",
+        SqlDataType::Float(_)
+        | SqlDataType::Float8
+        | SqlDataType::Float64
+        | SqlDataType::Double(_)
+        | SqlDataType::DoublePrecision => "float8",
+        SqlDataType::Numeric(_) | SqlDataType::Decimal(_) => "numeric",
+        SqlDataType::Text | SqlDataType::String(_) => "text",
+        SqlDataType::Varchar(_) | SqlDataType::CharacterVarying(_) => "varchar",
+        SqlDataType::Char(_) | SqlDataType::Character(_) => "bpchar",
+        SqlDataType::Bool | SqlDataType::Boolean => "bool",
+        SqlDataType::Date => "date",
+        SqlDataType::Timestamp(_, TimezoneInfo::WithTimeZone | TimezoneInfo::Tz) => "timestamptz",
+        SqlDataType::Timestamp(..) => "timestamp",
+        _ => "?column?",
+    };
+    name.to_string()
+}
+
+/// Plan a VALUES list as a row source
+/// Columns are named by the table alias, if any, then column1, column2, ... as
+/// in Postgres; each column's type is that of its first literal value
+fn plan_values(values: &sqlparser::ast::Values, alias: Option<&sqlparser::ast::TableAlias>) -> Result<Operator, ExecutorError> {
+    let width = values.rows.first().map_or(0, |row| row.len());
+    if values.rows.iter().any(|row| row.len() != width) {
+        return Err(ExecutorError::Execution("VALUES lists must all be the same length".to_string()));
+    }
+
+    let alias_columns = alias.map_or(&[][..], |alias| alias.columns.as_slice());
+    if alias_columns.len() > width {
+        return Err(ExecutorError::Execution(format!(
+            "Table alias names {} columns, but VALUES has {}",
+            alias_columns.len(),
+            width
+        )));
+    }
+
+    let columns = (0..width)
+        .map(|idx| Column {
+            name: alias_columns.get(idx)
+                .map(|column| column.name.value.clone())
+                .unwrap_or_else(|| format!("column{}", idx + 1)),
+            data_type: values.rows.iter()
+                .find_map(|row| literal_type(&row[idx]))
+                .unwrap_or(DataType::Null),
+            is_primary_key: false,
+            typmod: None,
+        })
+        .collect();
+
+    Ok(Operator::Values {
+        schema: Schema::new(columns),
+        rows: values.rows.clone(),
+    })
+}
+
+/// Type of a literal expression; None for NULL and anything that is not a literal
+fn literal_type(expr: &sqlparser::ast::Expr) -> Option<DataType> {
+    use sqlparser::ast::{Expr, UnaryOperator, Value};
+
+    match expr {
+        Expr::Value(val) => match &val.value {
+            Value::Number(n, _) if n.parse::<i64>().is_ok() => Some(DataType::Int),
+            Value::Number(_, _) => Some(DataType::Float),
+            Value::SingleQuotedString(_) => Some(DataType::String),
+            Value::Boolean(_) => Some(DataType::Bool),
+            _ => None,
+        },
+        Expr::UnaryOp { op: UnaryOperator::Minus | UnaryOperator::Plus, expr } => literal_type(expr),
+        Expr::Nested(inner) => literal_type(inner),
+        _ => None,
+    }
+}
+
+/// Plan one entry of a FROM clause, returning the name its columns are
+/// qualified with when joined: the alias if given, otherwise the table name
+fn plan_relation(relation: &sqlparser::ast::TableFactor) -> Result<(Operator, Option<String>), ExecutorError> {
+    match relation {
+        sqlparser::ast::TableFactor::Table { name, alias, args: None, .. } => {
+            let table_name = name.0.iter()
+                .filter_map(|part| part.as_ident())
+                .map(|ident| ident.value.clone())
+                .collect::<Vec<_>>()
+                .join(".");
+            debug!(table = %table_name, "plan: table scan");
+            let qualifier = alias.as_ref().map_or_else(|| table_name.clone(), |alias| alias.name.value.clone());
+            Ok((Operator::TableScan { table: table_name }, Some(qualifier)))
+        }
+        sqlparser::ast::TableFactor::Derived { lateral: false, subquery, alias } => {
+            // FROM (VALUES ...) AS t(a, b) reads the list like a table
+            let sqlparser::ast::SetExpr::Values(values) = &*subquery.body else {
+                return Err(ExecutorError::UnsupportedStatement(
+                    "Subqueries in FROM are only supported for VALUES lists".to_string(),
+                ));
+            };
+            debug!("plan: values list as table source");
+            let qualifier = alias.as_ref().map(|alias| alias.name.value.clone());
+            Ok((plan_values(values, alias.as_ref())?, qualifier))
+        }
+        _ => Err(ExecutorError::UnsupportedStatement(
+            "Only simple table scans supported".to_string(),
+        )),
+    }
+}
+
+/// Plan a FROM clause of several tables or with joins as a left-deep tree of
+/// Joins; tables listed with commas are cross joined
+fn plan_joins(from: &[sqlparser::ast::TableWithJoins]) -> Result<Operator, ExecutorError> {
+    use sqlparser::ast::{JoinConstraint, JoinOperator};
+
+    let mut plan: Option<(Operator, Option<String>)> = None;
+    let mut qualifiers = Vec::new();
+    for table in from {
+        let relations = std::iter::once((&table.relation, None))
+            .chain(table.joins.iter().map(|join| (&join.relation, Some(&join.join_operator))));
+        for (relation, join_operator) in relations {
+            let condition = match join_operator {
+                None | Some(JoinOperator::CrossJoin(JoinConstraint::None)) => None,
+                Some(JoinOperator::Join(constraint) | JoinOperator::Inner(constraint)) => match constraint {
+                    JoinConstraint::On(expr) => Some(expr.clone()),
+                    JoinConstraint::None => None,
+                    _ => return Err(ExecutorError::UnsupportedStatement(
+                        "JOIN with USING or NATURAL not supported".to_string(),
+                    )),
+                },
+                Some(_) => return Err(ExecutorError::UnsupportedStatement(
+                    "Only inner and cross joins are supported".to_string(),
+                )),
+            };
+
+            let (right, right_qualifier) = plan_relation(relation)?;
+            if let Some(qualifier) = &right_qualifier {
+                if qualifiers.contains(qualifier) {
+                    return Err(ExecutorError::Execution(format!(
+                        "table name \"{}\" specified more than once",
+                        qualifier
+                    )));
+                }
+                qualifiers.push(qualifier.clone());
+            }
+            plan = Some(match plan {
+                None => (right, right_qualifier),
+                Some((left, left_qualifier)) => {
+                    debug!(condition = ?condition, "plan: adding join");
+                    (Operator::Join {
+                        left: Box::new(left),
+                        right: Box::new(right),
+                        left_qualifier,
+                        right_qualifier,
+                        condition,
+                    }, None)
+                }
+            });
+        }
+    }
+    plan.map(|(plan, _)| plan)
+        .ok_or_else(|| ExecutorError::Execution("FROM clause is empty".to_string()))
+}
+
 fn extract_table_name(table_with_joins: &sqlparser::ast::TableWithJoins) -> Result<String, ExecutorError> {
     match &table_with_joins.relation {
         sqlparser::ast::TableFactor::Table { name, .. } => {
@@ -220,12 +920,16 @@ pub fn extract_create_table(stmt: &CreateTable) -> Result<(String, Schema, Strin
     let mut columns = Vec::new();
     for col_def in &stmt.columns {
         let col_name = col_def.name.value.clone();
-        let data_type = sql_type_to_data_type(&col_def.data_type)?;
+        let data_type = match serial_type_max(&col_def.data_type) {
+            Some(_) => DataType::Int,
+            None => sql_type_to_data_type(&col_def.data_type)?,
+        };
 
         columns.push(Column {
             name: col_name,
             data_type,
             is_primary_key: false,
+            typmod: sql_type_modifier(&col_def.data_type)?,
         });
     }
 
@@ -235,11 +939,47 @@ pub fn extract_create_table(stmt: &CreateTable) -> Result<(String, Schema, Strin
         ));
     }
 
-    // Extract PRIMARY KEY constraint
+    // Extract PRIMARY KEY constraint, given on a column or on the table
     let mut primary_key_col = None;
+    for col_def in &stmt.columns {
+        use sqlparser::ast::ColumnOption;
+        for option_def in &col_def.options {
+            let ColumnOption::Unique { is_primary: true, characteristics } = &option_def.option else {
+                continue;
+            };
+            if characteristics.is_some_and(|characteristics| characteristics.deferrable == Some(true)) {
+                return Err(ExecutorError::UnsupportedStatement(
+                    "DEFERRABLE constraints are not supported".to_string(),
+                ));
+            }
+            if primary_key_col.is_some() {
+                return Err(ExecutorError::Execution(format!(
+                    "multiple primary keys for table \"{}\" are not allowed",
+                    table_name
+                )));
+            }
+            if let Some(col) = columns.iter_mut().find(|c| c.name == col_def.name.value) {
+                col.is_primary_key = true;
+            }
+            primary_key_col = Some(col_def.name.value.clone());
+        }
+    }
     for constraint in &stmt.constraints {
         use sqlparser::ast::TableConstraint;
-        if let TableConstraint::PrimaryKey { columns: pk_cols, .. } = constraint {
+        if let TableConstraint::PrimaryKey { columns: pk_cols, characteristics, .. } = constraint {
+            if primary_key_col.is_some() {
+                return Err(ExecutorError::Execution(format!(
+                    "multiple primary keys for table \"{}\" are not allowed",
+                    table_name
+                )));
+            }
+            // Keys are checked as each row is written; nothing holds the check
+            // until COMMIT yet
+            if characteristics.is_some_and(|characteristics| characteristics.deferrable == Some(true)) {
+                return Err(ExecutorError::UnsupportedStatement(
+                    "DEFERRABLE constraints are not supported".to_string(),
+                ));
+            }
             if pk_cols.is_empty() {
                 return Err(ExecutorError::Execution(
                     "PRIMARY KEY constraint requires at least one column".to_string(),
@@ -282,45 +1022,396 @@ pub fn extract_create_table(stmt: &CreateTable) -> Result<(String, Schema, Strin
     Ok((table_name, Schema::new(columns), primary_key_col))
 }
 
-pub fn extract_insert(stmt: &Insert) -> Result<(String, Vec<Vec<sqlparser::ast::Expr>>), ExecutorError> {
-    debug!("extracting insert statement");
+/// Extract the SERIAL columns of a CREATE TABLE, each with the options of the
+/// sequence that fills it: counting up from 1 within the range of its type
+pub fn extract_create_table_serials(stmt: &CreateTable) -> Result<Vec<(String, SequenceOptions)>, ExecutorError> {
+    let serials: Vec<(String, SequenceOptions)> = stmt.columns.iter()
+        .filter_map(|col_def| {
+            let max = serial_type_max(&col_def.data_type)?;
+            Some((col_def.name.value.clone(), SequenceOptions { type_bounds: (1, max), ..Default::default() }))
+        })
+        .collect();
+    if !serials.is_empty() {
+        debug!(columns = ?serials.iter().map(|(column, _)| column).collect::<Vec<_>>(), "extracted serial columns");
+    }
+    Ok(serials)
+}
 
-    // Extract table name from TableObject
-    let table_name = match &stmt.table {
-        sqlparser::ast::TableObject::TableName(name) => {
-            name.0.iter()
-                .filter_map(|part| part.as_ident())
-                .map(|ident| ident.value.clone())
-                .collect::<Vec<_>>()
-                .join(".")
-        }
-        sqlparser::ast::TableObject::TableFunction(_) => {
-            return Err(ExecutorError::UnsupportedStatement(
-                "Table functions in INSERT not supported".to_string(),
-            ));
-        }
+/// Largest value of a SERIAL type, None for any other type
+/// SMALLSERIAL, SERIAL and BIGSERIAL are integers filled from a sequence, as
+/// in Postgres
+fn serial_type_max(data_type: &sqlparser::ast::DataType) -> Option<i64> {
+    let sqlparser::ast::DataType::Custom(name, modifiers) = data_type else {
+        return None;
     };
-
-    if table_name.is_empty() {
-        return Err(ExecutorError::Execution("Table name is empty".to_string()));
+    if !modifiers.is_empty() {
+        return None;
     }
+    match name.to_string().to_lowercase().as_str() {
+        "smallserial" | "serial2" => Some(i16::MAX as i64),
+        "serial" | "serial4" => Some(i32::MAX as i64),
+        "bigserial" | "serial8" => Some(i64::MAX),
+        _ => None,
+    }
+}
 
-    debug!(table = %table_name, "extracting insert rows");
-
-    // Extract rows from INSERT ... VALUES (...)
-    let mut rows = Vec::new();
+/// Extract the CHECK constraints of a CREATE TABLE, both those on a column and
+/// those on the table
+/// Unnamed constraints are named as Postgres names them: `<table>_<column>_check`
+/// when the expression refers to one column, `<table>_check` otherwise, with a
+/// number appended if that name is taken
+pub fn extract_create_table_checks(stmt: &CreateTable, table_name: &str, schema: &Schema) -> Result<Vec<CheckConstraint>, ExecutorError> {
+    use sqlparser::ast::{ColumnOption, TableConstraint};
 
-    if let Some(source) = &stmt.source {
-        // The source is a Query, extract VALUES from it
-        if let sqlparser::ast::SetExpr::Values(values) = &*source.body {
-            for row in &values.rows {
-                rows.push(row.clone());
+    let column_checks = stmt.columns.iter()
+        .flat_map(|col_def| &col_def.options)
+        .filter_map(|option_def| match &option_def.option {
+            ColumnOption::Check(expr) => Some((option_def.name.as_ref(), expr)),
+            _ => None,
+        });
+    let mut table_checks = Vec::new();
+    for constraint in &stmt.constraints {
+        if let TableConstraint::Check { name, expr, enforced } = constraint {
+            if *enforced == Some(false) {
+                return Err(ExecutorError::UnsupportedStatement(
+                    "NOT ENFORCED CHECK constraints are not supported".to_string(),
+                ));
             }
-        } else {
-            return Err(ExecutorError::Execution(
-                "INSERT with SELECT not yet supported".to_string(),
-            ));
+            table_checks.push((name.as_ref(), &**expr));
         }
+    }
+
+    let mut checks: Vec<CheckConstraint> = Vec::new();
+    for (name, expr) in column_checks.chain(table_checks) {
+        let columns = check_columns(expr, table_name, schema)?;
+        let name = match name {
+            Some(name) => {
+                if checks.iter().any(|check| check.name == name.value) {
+                    return Err(ExecutorError::Execution(format!(
+                        "constraint \"{}\" for relation \"{}\" already exists",
+                        name.value, table_name
+                    )));
+                }
+                name.value.clone()
+            }
+            None => {
+                let base = match columns.as_slice() {
+                    [column] => format!("{}_{}_check", table_name, column),
+                    _ => format!("{}_check", table_name),
+                };
+                let mut name = base.clone();
+                let mut suffix = 0;
+                while checks.iter().any(|check| check.name == name) {
+                    suffix += 1;
+                    name = format!("{}{}", base, suffix);
+                }
+                name
+            }
+        };
+        checks.push(CheckConstraint { name, expr: expr.to_string() });
+    }
+
+    debug!(table = %table_name, checks = checks.len(), "extracted check constraints");
+    Ok(checks)
+}
+
+/// Extract the FOREIGN KEY constraints of a CREATE TABLE, both `REFERENCES`
+/// on a column and `FOREIGN KEY (...) REFERENCES` on the table
+/// Unnamed constraints are named `<table>_<column>_fkey`, as in Postgres
+/// The referenced column is left empty when not given, for the referenced
+/// table's primary key; storage checks the rest against the referenced table
+pub fn extract_create_table_foreign_keys(stmt: &CreateTable, table_name: &str) -> Result<Vec<ForeignKey>, ExecutorError> {
+    use sqlparser::ast::{ColumnOption, TableConstraint};
+
+    let mut definitions = Vec::new();
+    for col_def in &stmt.columns {
+        for option_def in &col_def.options {
+            if let ColumnOption::ForeignKey { foreign_table, referred_columns, on_delete, on_update, characteristics } = &option_def.option {
+                definitions.push((
+                    option_def.name.as_ref(),
+                    std::slice::from_ref(&col_def.name),
+                    foreign_table,
+                    referred_columns.as_slice(),
+                    (*on_delete, *on_update, *characteristics),
+                ));
+            }
+        }
+    }
+    for constraint in &stmt.constraints {
+        if let TableConstraint::ForeignKey { name, columns, foreign_table, referred_columns, on_delete, on_update, characteristics, .. } = constraint {
+            definitions.push((name.as_ref(), columns.as_slice(), foreign_table, referred_columns.as_slice(), (*on_delete, *on_update, *characteristics)));
+        }
+    }
+
+    let mut foreign_keys: Vec<ForeignKey> = Vec::new();
+    for (name, columns, foreign_table, referred_columns, (on_delete, on_update, characteristics)) in definitions {
+        let ([column], [] | [_]) = (columns, referred_columns) else {
+            return Err(ExecutorError::UnsupportedStatement(
+                "Composite foreign keys not yet supported".to_string(),
+            ));
+        };
+        if characteristics.is_some_and(|characteristics| characteristics.deferrable == Some(true)) {
+            return Err(ExecutorError::UnsupportedStatement(
+                "DEFERRABLE constraints are not supported".to_string(),
+            ));
+        }
+        let on_delete = match on_delete {
+            None | Some(sqlparser::ast::ReferentialAction::NoAction) => ReferentialAction::NoAction,
+            Some(sqlparser::ast::ReferentialAction::Restrict) => ReferentialAction::Restrict,
+            Some(sqlparser::ast::ReferentialAction::Cascade) => ReferentialAction::Cascade,
+            Some(other) => return Err(ExecutorError::UnsupportedStatement(format!("ON DELETE {} is not supported", other))),
+        };
+        // Keys referenced by other rows can't change, which is what these ask for
+        if let Some(action) = on_update
+            && !matches!(action, sqlparser::ast::ReferentialAction::NoAction | sqlparser::ast::ReferentialAction::Restrict)
+        {
+            return Err(ExecutorError::UnsupportedStatement(format!("ON UPDATE {} is not supported", action)));
+        }
+
+        let name = match name {
+            Some(name) => name.value.clone(),
+            None => {
+                let base = format!("{}_{}_fkey", table_name, column.value);
+                let mut name = base.clone();
+                let mut suffix = 0;
+                while foreign_keys.iter().any(|foreign_key| foreign_key.name == name) {
+                    suffix += 1;
+                    name = format!("{}{}", base, suffix);
+                }
+                name
+            }
+        };
+        if foreign_keys.iter().any(|foreign_key| foreign_key.name == name) {
+            return Err(ExecutorError::Execution(format!(
+                "constraint \"{}\" for relation \"{}\" already exists",
+                name, table_name
+            )));
+        }
+        foreign_keys.push(ForeignKey {
+            name,
+            column: column.value.clone(),
+            referenced_table: foreign_table.0.iter()
+                .filter_map(|part| part.as_ident())
+                .map(|ident| ident.value.clone())
+                .collect::<Vec<_>>()
+                .join("."),
+            referenced_column: referred_columns.first().map(|ident| ident.value.clone()).unwrap_or_default(),
+            on_delete,
+        });
+    }
+
+    debug!(table = %table_name, foreign_keys = foreign_keys.len(), "extracted foreign keys");
+    Ok(foreign_keys)
+}
+
+/// Distinct columns a CHECK expression refers to, in the schema's spelling
+/// A reference to a column the table doesn't have is an error
+fn check_columns(expr: &sqlparser::ast::Expr, table_name: &str, schema: &Schema) -> Result<Vec<String>, ExecutorError> {
+    let mut columns: Vec<String> = Vec::new();
+    let result = sqlparser::ast::visit_expressions(expr, |expr| {
+        let name = match expr {
+            sqlparser::ast::Expr::Identifier(ident) => &ident.value,
+            sqlparser::ast::Expr::CompoundIdentifier(parts) => match parts.last() {
+                Some(ident) => &ident.value,
+                None => return std::ops::ControlFlow::Continue(()),
+            },
+            _ => return std::ops::ControlFlow::Continue(()),
+        };
+        let Some(column_idx) = schema.get_column_index(name) else {
+            return std::ops::ControlFlow::Break(name.clone());
+        };
+        let column = &schema.columns[column_idx].name;
+        if !columns.contains(column) {
+            columns.push(column.clone());
+        }
+        std::ops::ControlFlow::Continue(())
+    });
+    if let std::ops::ControlFlow::Break(name) = result {
+        return Err(ExecutorError::Execution(format!(
+            "column \"{}\" of relation \"{}\" does not exist",
+            name, table_name
+        )));
+    }
+    Ok(columns)
+}
+
+/// Rewrite CHECK constraints for a column being renamed from `old` to `new`
+pub fn rename_check_column(checks: &[CheckConstraint], old: &str, new: &str) -> Result<Vec<CheckConstraint>, ExecutorError> {
+    checks.iter()
+        .map(|check| {
+            let mut expr = crate::parser::parse_expr(&check.expr)?;
+            let _ = sqlparser::ast::visit_expressions_mut(&mut expr, |expr| {
+                let ident = match expr {
+                    sqlparser::ast::Expr::Identifier(ident) => Some(ident),
+                    sqlparser::ast::Expr::CompoundIdentifier(parts) => parts.last_mut(),
+                    _ => None,
+                };
+                if let Some(ident) = ident
+                    && ident.value.eq_ignore_ascii_case(old)
+                {
+                    *ident = sqlparser::ast::Ident::with_quote('"', new);
+                }
+                std::ops::ControlFlow::<()>::Continue(())
+            });
+            Ok(CheckConstraint { name: check.name.clone(), expr: expr.to_string() })
+        })
+        .collect()
+}
+
+/// Extract the storage options from the WITH options of a CREATE TABLE
+pub fn extract_create_table_options(stmt: &CreateTable) -> Result<TableOptions, ExecutorError> {
+    match &stmt.table_options {
+        sqlparser::ast::CreateTableOptions::With(options) => extract_table_options(options),
+        _ => Ok(TableOptions::default()),
+    }
+}
+
+/// Extract storage options such as `(fillfactor = 80, ttl = '7 days')`; the
+/// retention policy is only set if one of its options is given
+fn extract_table_options(options: &[sqlparser::ast::SqlOption]) -> Result<TableOptions, ExecutorError> {
+    let mut table_options = TableOptions::default();
+    let mut ttl_options = Vec::new();
+    for option in options {
+        let sqlparser::ast::SqlOption::KeyValue { key, value } = option else {
+            return Err(ExecutorError::UnsupportedStatement(format!("Unsupported table option: {}", option)));
+        };
+        match key.value.to_lowercase().as_str() {
+            "ttl" | "ttl_column" => ttl_options.push(option.clone()),
+            "fillfactor" => table_options.fillfactor = Some(match value {
+                sqlparser::ast::Expr::Value(val) => match &val.value {
+                    sqlparser::ast::Value::Number(text, _) | sqlparser::ast::Value::SingleQuotedString(text) => {
+                        text.trim().parse()
+                            .map_err(|_| ExecutorError::Execution(format!("invalid value for integer option \"fillfactor\": {}", text)))?
+                    }
+                    other => return Err(ExecutorError::Execution(format!("invalid value for integer option \"fillfactor\": {}", other))),
+                },
+                other => return Err(ExecutorError::Execution(format!("invalid value for integer option \"fillfactor\": {}", other))),
+            }),
+            _ => return Err(ExecutorError::UnsupportedStatement(format!("Unsupported table option: {}", key.value))),
+        }
+    }
+    if !ttl_options.is_empty() {
+        table_options.ttl = Some(extract_ttl_options(&ttl_options)?);
+    }
+    Ok(table_options)
+}
+
+/// Extract a retention policy from storage options such as
+/// `(ttl_column = created_at, ttl = '7 days')`
+/// `ttl = 'off'` removes the policy, which comes back as None
+fn extract_ttl_options(options: &[sqlparser::ast::SqlOption]) -> Result<Option<TtlPolicy>, ExecutorError> {
+    let mut column = None;
+    let mut ttl = None;
+    for option in options {
+        let sqlparser::ast::SqlOption::KeyValue { key, value } = option else {
+            return Err(ExecutorError::UnsupportedStatement(format!("Unsupported table option: {}", option)));
+        };
+        match key.value.to_lowercase().as_str() {
+            "ttl_column" => column = Some(match value {
+                sqlparser::ast::Expr::Identifier(ident) => ident.value.clone(),
+                sqlparser::ast::Expr::Value(val) => match &val.value {
+                    sqlparser::ast::Value::SingleQuotedString(name) => name.clone(),
+                    other => return Err(ExecutorError::Execution(format!("Invalid ttl_column: {}", other))),
+                },
+                other => return Err(ExecutorError::Execution(format!("Invalid ttl_column: {}", other))),
+            }),
+            "ttl" => ttl = Some(match value {
+                sqlparser::ast::Expr::Value(val) => match &val.value {
+                    sqlparser::ast::Value::SingleQuotedString(text) if text.trim().eq_ignore_ascii_case("off") => None,
+                    sqlparser::ast::Value::SingleQuotedString(text) | sqlparser::ast::Value::Number(text, _) => {
+                        Some(parse_interval_seconds(text)
+                            .ok_or_else(|| ExecutorError::Execution(format!("Invalid ttl: {}", text)))?)
+                    }
+                    other => return Err(ExecutorError::Execution(format!("Invalid ttl: {}", other))),
+                },
+                other => return Err(ExecutorError::Execution(format!("Invalid ttl: {}", other))),
+            }),
+            _ => return Err(ExecutorError::UnsupportedStatement(format!("Unsupported table option: {}", key.value))),
+        }
+    }
+
+    match (ttl, column) {
+        (None, None) => Ok(None),
+        (None, Some(_)) => Err(ExecutorError::Execution("ttl_column requires a ttl".to_string())),
+        (Some(None), _) => Ok(None),
+        (Some(Some(_)), None) => Err(ExecutorError::Execution("ttl requires a ttl_column".to_string())),
+        (Some(Some(seconds)), Some(column)) => Ok(Some(TtlPolicy { column, seconds })),
+    }
+}
+
+/// Number of seconds in an interval such as `90`, `30 minutes` or `7 days`
+fn parse_interval_seconds(text: &str) -> Option<u64> {
+    let mut parts = text.split_whitespace();
+    let count: u64 = parts.next()?.parse().ok()?;
+    let unit_seconds = match parts.next().map(|unit| unit.to_lowercase()) {
+        None => 1,
+        Some(unit) => match unit.trim_end_matches('s') {
+            "second" | "sec" => 1,
+            "minute" | "min" => 60,
+            "hour" => 3600,
+            "day" => 86400,
+            "week" => 7 * 86400,
+            _ => return None,
+        },
+    };
+    if parts.next().is_some() {
+        return None;
+    }
+    count.checked_mul(unit_seconds)
+}
+
+/// Rows of an INSERT, a DEFAULT in a row being None
+pub type InsertRows = Vec<Vec<Option<sqlparser::ast::Expr>>>;
+
+/// Extract the target table, the columns named (empty when none are) and the
+/// rows of an INSERT
+/// DEFAULT VALUES is a single row giving no values
+pub fn extract_insert(stmt: &Insert) -> Result<(String, Vec<String>, InsertRows), ExecutorError> {
+    debug!("extracting insert statement");
+
+    // Extract table name from TableObject
+    let table_name = match &stmt.table {
+        sqlparser::ast::TableObject::TableName(name) => {
+            name.0.iter()
+                .filter_map(|part| part.as_ident())
+                .map(|ident| ident.value.clone())
+                .collect::<Vec<_>>()
+                .join(".")
+        }
+        sqlparser::ast::TableObject::TableFunction(_) => {
+            return Err(ExecutorError::UnsupportedStatement(
+                "Table functions in INSERT not supported".to_string(),
+            ));
+        }
+    };
+
+    if table_name.is_empty() {
+        return Err(ExecutorError::Execution("Table name is empty".to_string()));
+    }
+
+    debug!(table = %table_name, "extracting insert rows");
+
+    let columns: Vec<String> = stmt.columns.iter().map(|ident| ident.value.clone()).collect();
+
+    // Extract rows from INSERT ... VALUES (...)
+    let mut rows = Vec::new();
+
+    if let Some(source) = &stmt.source {
+        // The source is a Query, extract VALUES from it
+        if let sqlparser::ast::SetExpr::Values(values) = &*source.body {
+            for row in &values.rows {
+                rows.push(row.iter()
+                    .map(|expr| (!is_default_keyword(expr)).then(|| expr.clone()))
+                    .collect());
+            }
+        } else {
+            return Err(ExecutorError::Execution(
+                "INSERT with SELECT not yet supported".to_string(),
+            ));
+        }
+    } else if columns.is_empty() && stmt.assignments.is_empty() {
+        // INSERT ... DEFAULT VALUES
+        rows.push(Vec::new());
     } else {
         return Err(ExecutorError::Execution(
             "INSERT without VALUES not yet supported".to_string(),
@@ -331,10 +1422,733 @@ pub fn extract_insert(stmt: &Insert) -> Result<(String, Vec<Vec<sqlparser::ast::
         return Err(ExecutorError::Execution("INSERT requires at least one row".to_string()));
     }
 
-    Ok((table_name, rows))
+    Ok((table_name, columns, rows))
+}
+
+/// What an INSERT does with a row whose key is already taken
+#[derive(Debug, Clone)]
+pub enum ConflictAction {
+    /// Skip the row
+    DoNothing,
+    /// Update the row holding the key instead; the assignments and predicate
+    /// see the row proposed for insertion as `excluded`
+    DoUpdate {
+        assignments: Vec<(String, sqlparser::ast::Expr)>,
+        selection: Option<Box<sqlparser::ast::Expr>>,
+    },
+}
+
+/// Constraint an ON CONFLICT clause names
+#[derive(Debug, Clone, PartialEq)]
+pub enum ConflictTarget {
+    /// The constraint over exactly these columns
+    Columns(Vec<String>),
+    /// The constraint with this name
+    Constraint(String),
+}
+
+/// An INSERT's ON CONFLICT clause
+#[derive(Debug, Clone)]
+pub struct OnConflict {
+    /// None (DO NOTHING only) for a conflict with any constraint
+    pub target: Option<ConflictTarget>,
+    pub action: ConflictAction,
+}
+
+/// Extract the ON CONFLICT clause of an INSERT, if it has one
+pub fn extract_on_conflict(stmt: &Insert) -> Result<Option<OnConflict>, ExecutorError> {
+    let on_conflict = match &stmt.on {
+        None => return Ok(None),
+        Some(sqlparser::ast::OnInsert::OnConflict(on_conflict)) => on_conflict,
+        Some(_) => {
+            return Err(ExecutorError::UnsupportedStatement(
+                "ON DUPLICATE KEY UPDATE not supported, use ON CONFLICT".to_string(),
+            ));
+        }
+    };
+    let target = on_conflict.conflict_target.as_ref().map(|target| match target {
+        sqlparser::ast::ConflictTarget::Columns(columns) => {
+            ConflictTarget::Columns(columns.iter().map(|ident| ident.value.clone()).collect())
+        }
+        sqlparser::ast::ConflictTarget::OnConstraint(name) => ConflictTarget::Constraint(name.to_string()),
+    });
+    let action = match &on_conflict.action {
+        sqlparser::ast::OnConflictAction::DoNothing => ConflictAction::DoNothing,
+        sqlparser::ast::OnConflictAction::DoUpdate(do_update) => {
+            if target.is_none() {
+                return Err(ExecutorError::Parse(
+                    "ON CONFLICT DO UPDATE requires inference specification or constraint name".to_string(),
+                ));
+            }
+            ConflictAction::DoUpdate {
+                assignments: extract_assignments(&do_update.assignments)?,
+                selection: do_update.selection.clone().map(Box::new),
+            }
+        }
+    };
+    Ok(Some(OnConflict { target, action }))
+}
+
+/// Whether a VALUES item is the DEFAULT keyword, which the parser reads as an
+/// unquoted identifier
+fn is_default_keyword(expr: &sqlparser::ast::Expr) -> bool {
+    matches!(expr, sqlparser::ast::Expr::Identifier(ident)
+        if ident.quote_style.is_none() && ident.value.eq_ignore_ascii_case("default"))
+}
+
+/// Extract the target table from a VACUUM statement (None means every table)
+pub fn extract_vacuum(stmt: &VacuumStatement) -> Result<Option<String>, ExecutorError> {
+    debug!("extracting vacuum");
+
+    if stmt.sort_only || stmt.delete_only || stmt.reindex || stmt.recluster || stmt.threshold.is_some() || stmt.boost {
+        return Err(ExecutorError::UnsupportedStatement(
+            "VACUUM options other than FULL are not supported".to_string(),
+        ));
+    }
+
+    Ok(stmt.table_name.as_ref().map(|name| {
+        name.0.iter()
+            .filter_map(|part| part.as_ident())
+            .map(|ident| ident.value.clone())
+            .collect::<Vec<_>>()
+            .join(".")
+    }))
+}
+
+/// Extract the tables a TRUNCATE empties, in the order given
+/// No sequence is owned by a column and no foreign key refers to a table, so
+/// RESTART IDENTITY and CASCADE have nothing more to act on
+pub fn extract_truncate(
+    table_names: &[sqlparser::ast::TruncateTableTarget],
+    partitions: Option<&[sqlparser::ast::Expr]>,
+) -> Result<Vec<String>, ExecutorError> {
+    debug!("extracting truncate");
+
+    if partitions.is_some() {
+        return Err(ExecutorError::UnsupportedStatement("TRUNCATE of partitions is not supported".to_string()));
+    }
+
+    let mut tables: Vec<String> = Vec::new();
+    for target in table_names {
+        let name = target.name.0.iter()
+            .filter_map(|part| part.as_ident())
+            .map(|ident| ident.value.clone())
+            .collect::<Vec<_>>()
+            .join(".");
+        // A table named twice is emptied once, as in Postgres
+        if !tables.contains(&name) {
+            tables.push(name);
+        }
+    }
+    Ok(tables)
+}
+
+/// What a COPY reads from or writes to
+#[derive(Debug, Clone)]
+pub enum CopyRelation {
+    /// Columns of a table, every column if none are named
+    Table { name: String, columns: Vec<String> },
+    /// Rows of a query, for COPY TO
+    Query(Box<sqlparser::ast::Query>),
+}
+
+/// Extract what a COPY FROM STDIN loads or a COPY TO STDOUT dumps
+pub fn extract_copy(
+    source: &sqlparser::ast::CopySource,
+    to: bool,
+    target: &sqlparser::ast::CopyTarget,
+) -> Result<CopyRelation, ExecutorError> {
+    debug!("extracting copy");
+
+    match (to, target) {
+        (false, sqlparser::ast::CopyTarget::Stdin) | (true, sqlparser::ast::CopyTarget::Stdout) => {}
+        (false, _) => return Err(ExecutorError::UnsupportedStatement(
+            "COPY FROM a file or program is not supported, only COPY FROM STDIN".to_string(),
+        )),
+        (true, _) => return Err(ExecutorError::UnsupportedStatement(
+            "COPY TO a file or program is not supported, only COPY TO STDOUT".to_string(),
+        )),
+    }
+    match source {
+        sqlparser::ast::CopySource::Table { table_name, columns } => {
+            let name = table_name.0.iter()
+                .filter_map(|part| part.as_ident())
+                .map(|ident| ident.value.clone())
+                .collect::<Vec<_>>()
+                .join(".");
+            Ok(CopyRelation::Table { name, columns: columns.iter().map(|ident| ident.value.clone()).collect() })
+        }
+        sqlparser::ast::CopySource::Query(query) if to => Ok(CopyRelation::Query(query.clone())),
+        sqlparser::ast::CopySource::Query(_) => Err(ExecutorError::Parse("COPY FROM requires a table".to_string())),
+    }
+}
+
+/// The command a statement would run if it writes anything, for the error a
+/// read-only server or transaction gives; None for statements that only read
+pub fn write_command(stmt: &Statement) -> Option<String> {
+    let command = match stmt {
+        Statement::Insert(_) => "INSERT",
+        Statement::Update { .. } => "UPDATE",
+        Statement::Delete(_) => "DELETE",
+        Statement::CreateTable(_) => "CREATE TABLE",
+        Statement::CreateIndex(_) => "CREATE INDEX",
+        Statement::AlterTable { .. } => "ALTER TABLE",
+        Statement::Comment { .. } => "COMMENT",
+        Statement::CreateSequence { .. } => "CREATE SEQUENCE",
+        Statement::CreateDatabase { .. } => "CREATE DATABASE",
+        Statement::CreateSchema { .. } => "CREATE SCHEMA",
+        Statement::Drop { .. } => "DROP",
+        Statement::CreateFunction(_) => "CREATE FUNCTION",
+        Statement::DropFunction { .. } => "DROP FUNCTION",
+        Statement::CreateProcedure { .. } => "CREATE PROCEDURE",
+        Statement::DropProcedure { .. } => "DROP PROCEDURE",
+        Statement::Vacuum(_) => "VACUUM",
+        Statement::Truncate { .. } => "TRUNCATE",
+        Statement::Copy { to: false, .. } => "COPY FROM",
+        _ => {
+            // Queries write only by advancing a sequence
+            let mut command = None;
+            let _ = sqlparser::ast::visit_expressions(stmt, |expr| {
+                if let sqlparser::ast::Expr::Function(func) = expr {
+                    let name = func.name.to_string().to_lowercase();
+                    if name == "nextval" || name == "setval" {
+                        command = Some(format!("{}()", name));
+                        return std::ops::ControlFlow::Break(());
+                    }
+                }
+                std::ops::ControlFlow::Continue(())
+            });
+            return command;
+        }
+    };
+    Some(command.to_string())
+}
+
+/// Extract the access mode from BEGIN or SET TRANSACTION modes: Some(true) for
+/// READ ONLY, Some(false) for READ WRITE, None if not given
+/// Isolation levels are accepted and ignored, since every statement already
+/// sees the latest committed data
+pub fn extract_transaction_read_only(modes: &[sqlparser::ast::TransactionMode]) -> Option<bool> {
+    modes.iter().rev().find_map(|mode| match mode {
+        sqlparser::ast::TransactionMode::AccessMode(access) => {
+            Some(*access == sqlparser::ast::TransactionAccessMode::ReadOnly)
+        }
+        sqlparser::ast::TransactionMode::IsolationLevel(_) => None,
+    })
+}
+
+/// Extract the class `SET workload_class` puts a session's queries in: None
+/// for DEFAULT, which classes each query by its work
+pub fn extract_workload_class(
+    scope: Option<&sqlparser::ast::ContextModifier>,
+    values: &[sqlparser::ast::Expr],
+) -> Result<Option<WorkloadClass>, ExecutorError> {
+    if matches!(scope, Some(sqlparser::ast::ContextModifier::Local | sqlparser::ast::ContextModifier::Global)) {
+        return Err(ExecutorError::UnsupportedStatement(
+            "workload_class can only be set for the session".to_string(),
+        ));
+    }
+    let name = match values {
+        [sqlparser::ast::Expr::Identifier(ident)] => ident.value.clone(),
+        [sqlparser::ast::Expr::Value(value)] => match &value.value {
+            sqlparser::ast::Value::SingleQuotedString(name) => name.clone(),
+            _ => String::new(),
+        },
+        _ => String::new(),
+    };
+    if name.eq_ignore_ascii_case("default") {
+        return Ok(None);
+    }
+    WorkloadClass::from_name(&name).map(Some).ok_or_else(|| ExecutorError::Execution(format!(
+        "invalid value for parameter \"workload_class\": \"{}\" (expected interactive, batch or DEFAULT)",
+        values.iter().map(|value| value.to_string()).collect::<Vec<_>>().join(", "),
+    )))
 }
 
-pub fn extract_create_index(stmt: &CreateIndex) -> Result<(String, String, String), ExecutorError> {
+/// Change requested by an ALTER TABLE statement
+#[derive(Debug, PartialEq)]
+pub enum AlterTableAction {
+    RenameTable(String),
+    RenameColumn { old: String, new: String },
+    /// Change the storage options that are given
+    SetOptions(TableOptions),
+}
+
+/// Extract the target table and action from an ALTER TABLE statement
+pub fn extract_alter_table(name: &ObjectName, operations: &[AlterTableOperation]) -> Result<(String, AlterTableAction), ExecutorError> {
+    debug!("extracting alter table");
+
+    let table_name = name.0.iter()
+        .filter_map(|part| part.as_ident())
+        .map(|ident| ident.value.clone())
+        .collect::<Vec<_>>()
+        .join(".");
+
+    if table_name.is_empty() {
+        return Err(ExecutorError::Execution("Table name is empty".to_string()));
+    }
+
+    let [operation] = operations else {
+        return Err(ExecutorError::UnsupportedStatement(
+            "ALTER TABLE supports exactly one action".to_string(),
+        ));
+    };
+
+    let action = match operation {
+        AlterTableOperation::RenameTable { table_name: new_name } => {
+            let (sqlparser::ast::RenameTableNameKind::To(new_name) | sqlparser::ast::RenameTableNameKind::As(new_name)) = new_name;
+            let new_name = new_name.0.iter()
+                .filter_map(|part| part.as_ident())
+                .map(|ident| ident.value.clone())
+                .collect::<Vec<_>>()
+                .join(".");
+            if new_name.is_empty() {
+                return Err(ExecutorError::Execution("New table name is empty".to_string()));
+            }
+            AlterTableAction::RenameTable(new_name)
+        }
+        AlterTableOperation::RenameColumn { old_column_name, new_column_name } => AlterTableAction::RenameColumn {
+            old: old_column_name.value.clone(),
+            new: new_column_name.value.clone(),
+        },
+        AlterTableOperation::SetOptionsParens { options } => AlterTableAction::SetOptions(extract_table_options(options)?),
+        other => {
+            return Err(ExecutorError::UnsupportedStatement(format!(
+                "Unsupported ALTER TABLE action: {}",
+                other
+            )));
+        }
+    };
+
+    debug!(table = %table_name, action = ?action, "extracted alter table");
+
+    Ok((table_name, action))
+}
+
+/// Object a COMMENT ON statement applies to
+#[derive(Debug, PartialEq)]
+pub enum CommentTarget {
+    Table(String),
+    Column { table: String, column: String },
+}
+
+/// Extract the target of a COMMENT ON statement
+/// `COMMENT ON ... IS NULL` clears the comment, so the text is optional
+pub fn extract_comment(object_type: &CommentObject, object_name: &ObjectName) -> Result<CommentTarget, ExecutorError> {
+    debug!("extracting comment");
+
+    let mut parts = object_name.0.iter()
+        .filter_map(|part| part.as_ident())
+        .map(|ident| ident.value.clone())
+        .collect::<Vec<_>>();
+
+    match object_type {
+        CommentObject::Table => {
+            if parts.is_empty() {
+                return Err(ExecutorError::Execution("Table name is empty".to_string()));
+            }
+            Ok(CommentTarget::Table(parts.join(".")))
+        }
+        CommentObject::Column => {
+            if parts.len() < 2 {
+                return Err(ExecutorError::Execution(
+                    "COMMENT ON COLUMN requires a table-qualified column name".to_string(),
+                ));
+            }
+            let column = parts.pop().unwrap_or_default();
+            Ok(CommentTarget::Column { table: parts.join("."), column })
+        }
+        other => Err(ExecutorError::UnsupportedStatement(format!(
+            "COMMENT ON {} not supported",
+            other
+        ))),
+    }
+}
+
+/// Extract the name and options of a CREATE SEQUENCE statement
+pub fn extract_create_sequence(
+    name: &ObjectName,
+    data_type: Option<&sqlparser::ast::DataType>,
+    options: &[sqlparser::ast::SequenceOptions],
+    temporary: bool,
+    owned_by: Option<&ObjectName>,
+) -> Result<(String, SequenceOptions), ExecutorError> {
+    use sqlparser::ast::SequenceOptions as Option_;
+
+    debug!("extracting create sequence");
+
+    if temporary {
+        return Err(ExecutorError::UnsupportedStatement("Temporary sequences are not supported".to_string()));
+    }
+    if owned_by.is_some() {
+        return Err(ExecutorError::UnsupportedStatement("OWNED BY is not supported".to_string()));
+    }
+
+    let sequence_name = name.0.iter()
+        .filter_map(|part| part.as_ident())
+        .map(|ident| ident.value.clone())
+        .collect::<Vec<_>>()
+        .join(".");
+    if sequence_name.is_empty() {
+        return Err(ExecutorError::Execution("Sequence name is empty".to_string()));
+    }
+
+    let type_bounds = match data_type {
+        None | Some(sqlparser::ast::DataType::BigInt(_)) | Some(sqlparser::ast::DataType::Int8(_)) => (i64::MIN, i64::MAX),
+        Some(sqlparser::ast::DataType::Int(_)) | Some(sqlparser::ast::DataType::Integer(_)) | Some(sqlparser::ast::DataType::Int4(_)) => {
+            (i32::MIN as i64, i32::MAX as i64)
+        }
+        Some(sqlparser::ast::DataType::SmallInt(_)) | Some(sqlparser::ast::DataType::Int2(_)) => (i16::MIN as i64, i16::MAX as i64),
+        Some(other) => {
+            return Err(ExecutorError::Execution(format!(
+                "Sequence type must be smallint, integer, or bigint, got {}",
+                other
+            )));
+        }
+    };
+
+    let mut sequence_options = SequenceOptions { type_bounds, ..Default::default() };
+    for option in options {
+        match option {
+            Option_::IncrementBy(expr, _) => sequence_options.increment = Some(sequence_option_value(expr)?),
+            Option_::MinValue(expr) => sequence_options.min_value = expr.as_ref().map(sequence_option_value).transpose()?,
+            Option_::MaxValue(expr) => sequence_options.max_value = expr.as_ref().map(sequence_option_value).transpose()?,
+            Option_::StartWith(expr, _) => sequence_options.start = Some(sequence_option_value(expr)?),
+            // Values are already cached in batches, see `storage::sequence`
+            Option_::Cache(expr) => {
+                sequence_option_value(expr)?;
+            }
+            Option_::Cycle(no_cycle) => sequence_options.cycle = !no_cycle,
+        }
+    }
+
+    debug!(sequence = %sequence_name, options = ?sequence_options, "extracted create sequence");
+
+    Ok((sequence_name, sequence_options))
+}
+
+/// Integer value of a CREATE SEQUENCE option, which may be negative
+fn sequence_option_value(expr: &sqlparser::ast::Expr) -> Result<i64, ExecutorError> {
+    match expr {
+        sqlparser::ast::Expr::Value(val) => match &val.value {
+            sqlparser::ast::Value::Number(num_str, _) => num_str.parse::<i64>()
+                .map_err(|_| ExecutorError::Execution(format!("Invalid sequence option value: {}", num_str))),
+            other => Err(ExecutorError::Execution(format!("Invalid sequence option value: {}", other))),
+        },
+        sqlparser::ast::Expr::UnaryOp { op: sqlparser::ast::UnaryOperator::Minus, expr } => {
+            sequence_option_value(expr)?.checked_neg()
+                .ok_or_else(|| ExecutorError::Execution(format!("Invalid sequence option value: -{}", expr)))
+        }
+        other => Err(ExecutorError::Execution(format!("Invalid sequence option value: {}", other))),
+    }
+}
+
+/// Extract the table names of a DROP TABLE statement
+pub fn extract_drop_table(names: &[ObjectName]) -> Result<Vec<String>, ExecutorError> {
+    debug!("extracting drop table");
+
+    names.iter()
+        .map(|name| {
+            let table_name = name.0.iter()
+                .filter_map(|part| part.as_ident())
+                .map(|ident| ident.value.clone())
+                .collect::<Vec<_>>()
+                .join(".");
+            if table_name.is_empty() {
+                return Err(ExecutorError::Execution("Table name is empty".to_string()));
+            }
+            Ok(table_name)
+        })
+        .collect()
+}
+
+/// Extract the sequence names of a DROP SEQUENCE statement
+pub fn extract_drop_sequence(names: &[ObjectName]) -> Result<Vec<String>, ExecutorError> {
+    debug!("extracting drop sequence");
+
+    names.iter()
+        .map(|name| {
+            let sequence_name = name.0.iter()
+                .filter_map(|part| part.as_ident())
+                .map(|ident| ident.value.clone())
+                .collect::<Vec<_>>()
+                .join(".");
+            if sequence_name.is_empty() {
+                return Err(ExecutorError::Execution("Sequence name is empty".to_string()));
+            }
+            Ok(sequence_name)
+        })
+        .collect()
+}
+
+/// Extract a SQL function definition from a CREATE FUNCTION statement
+/// Returns the definition (its oid is assigned by storage) and whether OR
+/// REPLACE was given
+pub fn extract_create_function(stmt: &sqlparser::ast::CreateFunction) -> Result<(FunctionMetadata, bool), ExecutorError> {
+    use sqlparser::ast::CreateFunctionBody;
+
+    debug!("extracting create function");
+
+    if stmt.temporary || stmt.or_alter {
+        return Err(ExecutorError::UnsupportedStatement(
+            "Only CREATE [OR REPLACE] FUNCTION is supported".to_string(),
+        ));
+    }
+    if let Some(language) = &stmt.language
+        && !language.value.eq_ignore_ascii_case("sql")
+    {
+        return Err(ExecutorError::UnsupportedStatement(format!(
+            "Unsupported function language: {}; only LANGUAGE SQL is supported",
+            language.value
+        )));
+    }
+
+    let function_name = stmt.name.0.iter()
+        .filter_map(|part| part.as_ident())
+        .map(|ident| ident.value.clone())
+        .collect::<Vec<_>>()
+        .join(".");
+    if function_name.is_empty() {
+        return Err(ExecutorError::Execution("Function name is empty".to_string()));
+    }
+
+    let mut args = Vec::new();
+    for arg in stmt.args.iter().flatten() {
+        if !matches!(arg.mode, None | Some(sqlparser::ast::ArgMode::In)) || arg.default_expr.is_some() {
+            return Err(ExecutorError::UnsupportedStatement(
+                "Function parameters must be plain IN parameters without defaults".to_string(),
+            ));
+        }
+        let arg_name = arg.name.as_ref().map(|ident| ident.value.clone()).unwrap_or_default();
+        args.push((arg_name, sql_type_to_data_type(&arg.data_type)?));
+    }
+
+    let return_type = stmt.return_type.as_ref()
+        .ok_or_else(|| ExecutorError::Execution("CREATE FUNCTION requires a RETURNS clause".to_string()))
+        .and_then(sql_type_to_data_type)?;
+
+    let body = match &stmt.function_body {
+        Some(CreateFunctionBody::Return(expr)) => expr.clone(),
+        Some(CreateFunctionBody::AsBeforeOptions(expr) | CreateFunctionBody::AsAfterOptions(expr)) => {
+            let sql = match expr {
+                sqlparser::ast::Expr::Value(val) => match &val.value {
+                    sqlparser::ast::Value::SingleQuotedString(sql) => sql.clone(),
+                    sqlparser::ast::Value::DollarQuotedString(sql) => sql.value.clone(),
+                    other => return Err(ExecutorError::Execution(format!("Invalid function body: {}", other))),
+                },
+                other => return Err(ExecutorError::Execution(format!("Invalid function body: {}", other))),
+            };
+            functions::parse_function_body(sql.trim().trim_end_matches(';'))?
+        }
+        _ => {
+            return Err(ExecutorError::UnsupportedStatement(
+                "Function body must be AS 'SELECT <expression>' or RETURN <expression>".to_string(),
+            ));
+        }
+    };
+
+    // Resolve parameter references now, so a bad body fails at definition time
+    let params: Vec<String> = args.iter().map(|(name, _)| name.clone()).collect();
+    let placeholders = vec![sqlparser::ast::Expr::value(sqlparser::ast::Value::Null); args.len()];
+    functions::substitute_params(&body, &params, &placeholders)?;
+
+    let function_meta = FunctionMetadata {
+        name: function_name,
+        oid: 0,
+        args,
+        return_type,
+        body: body.to_string(),
+    };
+    debug!(function = %function_meta.name, body = %function_meta.body, "extracted create function");
+
+    Ok((function_meta, stmt.or_replace))
+}
+
+/// Extract the function names of a DROP FUNCTION statement
+/// Functions cannot be overloaded, so argument lists are ignored
+pub fn extract_drop_function(func_desc: &[sqlparser::ast::FunctionDesc]) -> Result<Vec<String>, ExecutorError> {
+    debug!("extracting drop function");
+    routine_names(func_desc, "Function")
+}
+
+/// Extract a procedure definition from a CREATE PROCEDURE statement
+/// sqlparser drops OR REPLACE for procedures, so OR ALTER is what replaces an
+/// existing definition; the returned flag says whether it was given
+pub fn extract_create_procedure(
+    or_alter: bool,
+    name: &ObjectName,
+    params: Option<&[sqlparser::ast::ProcedureParam]>,
+    language: Option<&sqlparser::ast::Ident>,
+    body: &sqlparser::ast::ConditionalStatements,
+) -> Result<(ProcedureMetadata, bool), ExecutorError> {
+    debug!("extracting create procedure");
+
+    if let Some(language) = language
+        && !language.value.eq_ignore_ascii_case("sql")
+    {
+        return Err(ExecutorError::UnsupportedStatement(format!(
+            "Unsupported procedure language: {}; only LANGUAGE SQL is supported",
+            language.value
+        )));
+    }
+
+    let procedure_name = name.0.iter()
+        .filter_map(|part| part.as_ident())
+        .map(|ident| ident.value.clone())
+        .collect::<Vec<_>>()
+        .join(".");
+    if procedure_name.is_empty() {
+        return Err(ExecutorError::Execution("Procedure name is empty".to_string()));
+    }
+
+    let mut procedure_params = Vec::new();
+    for param in params.unwrap_or_default() {
+        if !matches!(param.mode, None | Some(sqlparser::ast::ArgMode::In)) {
+            return Err(ExecutorError::UnsupportedStatement(
+                "Procedure parameters must be IN parameters".to_string(),
+            ));
+        }
+        procedure_params.push((param.name.value.clone(), sql_type_to_data_type(&param.data_type)?));
+    }
+
+    // The whole body runs as one unit, so it cannot manage transactions itself
+    let statements = body.statements();
+    if let Some(stmt) = statements.iter().find(|stmt| matches!(
+        stmt,
+        Statement::StartTransaction { .. } | Statement::Commit { .. } | Statement::Rollback { .. }
+    )) {
+        return Err(ExecutorError::UnsupportedStatement(format!(
+            "Transaction control is not allowed in a procedure body: {}",
+            stmt
+        )));
+    }
+    if statements.is_empty() {
+        return Err(ExecutorError::Execution("Procedure body is empty".to_string()));
+    }
+
+    let procedure_meta = ProcedureMetadata {
+        name: procedure_name,
+        oid: 0,
+        params: procedure_params,
+        body: statements.iter().map(|stmt| stmt.to_string()).collect(),
+    };
+    debug!(procedure = %procedure_meta.name, statements = procedure_meta.body.len(), "extracted create procedure");
+
+    Ok((procedure_meta, or_alter))
+}
+
+/// Extract the procedure names of a DROP PROCEDURE statement
+/// Procedures cannot be overloaded, so argument lists are ignored
+pub fn extract_drop_procedure(proc_desc: &[sqlparser::ast::FunctionDesc]) -> Result<Vec<String>, ExecutorError> {
+    debug!("extracting drop procedure");
+    routine_names(proc_desc, "Procedure")
+}
+
+/// Returns (cursor name, query, WITH HOLD) of a DECLARE ... CURSOR FOR statement
+pub fn extract_declare_cursor(stmts: &[sqlparser::ast::Declare]) -> Result<(String, Box<sqlparser::ast::Query>, bool), ExecutorError> {
+    debug!("extracting declare cursor");
+
+    let [declare] = stmts else {
+        return Err(ExecutorError::Execution("DECLARE must declare exactly one cursor".to_string()));
+    };
+    let (Some(sqlparser::ast::DeclareType::Cursor), [name], Some(query)) =
+        (&declare.declare_type, declare.names.as_slice(), &declare.for_query)
+    else {
+        return Err(ExecutorError::Execution(format!("Only DECLARE name CURSOR FOR query is supported: {}", declare)));
+    };
+    if declare.binary == Some(true) || declare.scroll == Some(true) {
+        return Err(ExecutorError::Execution("BINARY and SCROLL cursors are not supported".to_string()));
+    }
+
+    Ok((name.value.clone(), query.clone(), declare.hold == Some(true)))
+}
+
+/// Check that a statement can be prepared and count the `$n` parameters it
+/// takes: the highest one referenced, or more if PREPARE lists more types
+pub fn extract_prepare(data_types: &[sqlparser::ast::DataType], statement: &Statement) -> Result<usize, ExecutorError> {
+    if !matches!(statement, Statement::Query(_) | Statement::Insert(_) | Statement::Update { .. } | Statement::Delete(_)) {
+        return Err(ExecutorError::UnsupportedStatement(
+            "Only SELECT, INSERT, UPDATE and DELETE can be prepared".to_string(),
+        ));
+    }
+
+    Ok(data_types.len().max(count_placeholders(statement)))
+}
+
+/// Number of parameters a statement's `$n` placeholders refer to: the highest n
+pub fn count_placeholders(statement: &Statement) -> usize {
+    let mut param_count = 0;
+    let _ = sqlparser::ast::visit_expressions(statement, |expr| {
+        if let sqlparser::ast::Expr::Value(val) = expr
+            && let sqlparser::ast::Value::Placeholder(placeholder) = &val.value
+            && let Some(position) = placeholder.strip_prefix('$').and_then(|position| position.parse::<usize>().ok())
+        {
+            param_count = param_count.max(position);
+        }
+        std::ops::ControlFlow::<()>::Continue(())
+    });
+    param_count
+}
+
+/// Extract the prepared statement name and argument expressions of an EXECUTE
+pub fn extract_execute(
+    name: Option<&ObjectName>,
+    parameters: &[sqlparser::ast::Expr],
+    immediate: bool,
+    into: &[sqlparser::ast::Ident],
+    using: &[sqlparser::ast::ExprWithAlias],
+) -> Result<(String, Vec<sqlparser::ast::Expr>), ExecutorError> {
+    let Some(name) = name.filter(|_| !immediate && into.is_empty() && using.is_empty()) else {
+        return Err(ExecutorError::UnsupportedStatement(
+            "Only EXECUTE name [(arguments)] is supported".to_string(),
+        ));
+    };
+    let statement_name = name.0.iter()
+        .filter_map(|part| part.as_ident())
+        .map(|ident| ident.value.clone())
+        .collect::<Vec<_>>()
+        .join(".");
+    Ok((statement_name, parameters.to_vec()))
+}
+
+/// Number of rows a FETCH asks for, or None for all remaining rows
+/// Cursors only move forward, so backward and absolute positioning are rejected
+pub fn extract_fetch_count(direction: &sqlparser::ast::FetchDirection) -> Result<Option<usize>, ExecutorError> {
+    use sqlparser::ast::FetchDirection;
+
+    let count = |limit: &sqlparser::ast::Value| match limit {
+        sqlparser::ast::Value::Number(n, _) => n.parse::<usize>()
+            .map_err(|_| ExecutorError::Execution(format!("Invalid FETCH count: {}", n))),
+        other => Err(ExecutorError::Execution(format!("Invalid FETCH count: {}", other))),
+    };
+
+    match direction {
+        FetchDirection::Next | FetchDirection::Forward { limit: None } => Ok(Some(1)),
+        FetchDirection::Count { limit } | FetchDirection::Forward { limit: Some(limit) } => Ok(Some(count(limit)?)),
+        FetchDirection::All | FetchDirection::ForwardAll => Ok(None),
+        other => Err(ExecutorError::Execution(format!("Cursor can only scan forward, FETCH {} is not supported", other))),
+    }
+}
+
+/// Names of the functions or procedures in a DROP statement
+fn routine_names(descs: &[sqlparser::ast::FunctionDesc], kind: &str) -> Result<Vec<String>, ExecutorError> {
+    descs.iter()
+        .map(|desc| {
+            let routine_name = desc.name.0.iter()
+                .filter_map(|part| part.as_ident())
+                .map(|ident| ident.value.clone())
+                .collect::<Vec<_>>()
+                .join(".");
+            if routine_name.is_empty() {
+                return Err(ExecutorError::Execution(format!("{} name is empty", kind)));
+            }
+            Ok(routine_name)
+        })
+        .collect()
+}
+
+/// Returns (table, columns, index type, descending)
+pub fn extract_create_index(stmt: &CreateIndex) -> Result<(String, Vec<String>, String, bool), ExecutorError> {
     debug!("extracting create index");
 
     // Extract index name (required)
@@ -366,26 +2180,34 @@ pub fn extract_create_index(stmt: &CreateIndex) -> Result<(String, String, Strin
 
     debug!(index = %index_name, table = %table_name, "extracting index columns");
 
-    // Extract column name (only support single column for now)
     if stmt.columns.is_empty() {
         return Err(ExecutorError::Execution(
             "CREATE INDEX requires at least one column".to_string(),
         ));
     }
+    if stmt.columns.len() > crate::storage::index::key::MAX_KEY_COLUMNS {
+        return Err(ExecutorError::Execution(format!(
+            "cannot use more than {} columns in an index",
+            crate::storage::index::key::MAX_KEY_COLUMNS
+        )));
+    }
 
-    if stmt.columns.len() > 1 {
+    // IndexColumn has a `column` field which is an OrderByExpr
+    // Keys run in one direction, so every column has to share it
+    let descending = stmt.columns[0].column.options.asc == Some(false);
+    if stmt.columns.iter().any(|column| (column.column.options.asc == Some(false)) != descending) {
         return Err(ExecutorError::UnsupportedStatement(
-            "Multi-column indexes not yet supported".to_string(),
+            "Indexes with columns in both ASC and DESC order are not supported".to_string(),
         ));
     }
-
-    // IndexColumn has a `column` field which is an OrderByExpr
-    let column_name = match &stmt.columns[0].column.expr {
-        sqlparser::ast::Expr::Identifier(ident) => ident.value.clone(),
-        _ => return Err(ExecutorError::Execution(
-            "Index column must be an identifier".to_string(),
-        )),
-    };
+    let column_names = stmt.columns.iter()
+        .map(|column| match &column.column.expr {
+            sqlparser::ast::Expr::Identifier(ident) => Ok(ident.value.clone()),
+            _ => Err(ExecutorError::Execution(
+                "Index column must be an identifier".to_string(),
+            )),
+        })
+        .collect::<Result<Vec<_>, _>>()?;
 
     // Extract index type from USING clause (defaults to "btree")
     let index_type = if let Some(using) = &stmt.using {
@@ -403,10 +2225,17 @@ pub fn extract_create_index(stmt: &CreateIndex) -> Result<(String, String, Strin
     } else {
         "btree".to_string()
     };
+    // Only a B-tree's keys are built from several columns
+    if column_names.len() > 1 && index_type != "btree" {
+        return Err(ExecutorError::UnsupportedStatement(format!(
+            "access method \"{}\" does not support multicolumn indexes",
+            index_type
+        )));
+    }
 
-    debug!(index = %index_name, table = %table_name, column = %column_name, index_type = %index_type, "extracted create index");
+    debug!(index = %index_name, table = %table_name, columns = ?column_names, index_type = %index_type, descending, "extracted create index");
 
-    Ok((table_name, column_name, index_type))
+    Ok((table_name, column_names, index_type, descending))
 }
 
 fn sql_type_to_data_type(data_type: &sqlparser::ast::DataType) -> Result<DataType, ExecutorError> {
@@ -440,8 +2269,135 @@ fn sql_type_to_data_type(data_type: &sqlparser::ast::DataType) -> Result<DataTyp
     }
 }
 
+/// Type modifier of a declared column type: the length of VARCHAR(n) or
+/// CHAR(n), None for a type declared without one
+fn sql_type_modifier(data_type: &sqlparser::ast::DataType) -> Result<Option<u32>, ExecutorError> {
+    use sqlparser::ast::{CharacterLength, DataType as SqlDataType};
+
+    let (type_name, length) = match data_type {
+        SqlDataType::Varchar(length) => ("varchar", length),
+        SqlDataType::Char(length) => ("char", length),
+        _ => return Ok(None),
+    };
+    match length {
+        Some(CharacterLength::IntegerLength { length, .. }) => {
+            if *length < 1 {
+                return Err(ExecutorError::Execution(format!(
+                    "length for type {} must be at least 1",
+                    type_name
+                )));
+            }
+            u32::try_from(*length)
+                .map(Some)
+                .map_err(|_| ExecutorError::Execution(format!(
+                    "length for type {} cannot exceed {}",
+                    type_name,
+                    u32::MAX
+                )))
+        }
+        Some(CharacterLength::Max) | None => Ok(None),
+    }
+}
+
 /// Try to extract a simple equality predicate (col = value) from a WHERE clause
 /// Returns Some((column_name, value_expr)) if matched, None otherwise
+/// Extract `col IN (v1, v2, ...)` with few enough constant values to look up
+/// one at a time
+fn try_extract_in_list(expr: &sqlparser::ast::Expr) -> Option<(String, Vec<sqlparser::ast::Expr>)> {
+    use sqlparser::ast::Expr;
+
+    match expr {
+        Expr::InList { expr, list, negated: false } => {
+            let Expr::Identifier(ident) = &**expr else {
+                return None;
+            };
+            let constant = |item: &Expr| !matches!(item, Expr::Identifier(_) | Expr::CompoundIdentifier(_));
+            if list.is_empty() || list.len() > MAX_INDEX_LOOKUPS || !list.iter().all(constant) {
+                return None;
+            }
+            Some((ident.value.clone(), list.clone()))
+        }
+        Expr::Nested(inner) => try_extract_in_list(inner),
+        _ => None,
+    }
+}
+
+/// Extract the `col = constant` terms of a conjunction of several terms, the
+/// first for each column
+fn try_extract_equalities(expr: &sqlparser::ast::Expr) -> Option<Vec<(String, sqlparser::ast::Expr)>> {
+    use sqlparser::ast::{BinaryOperator, Expr};
+
+    fn conjuncts<'a>(expr: &'a Expr, terms: &mut Vec<&'a Expr>) {
+        match expr {
+            Expr::BinaryOp { left, op: BinaryOperator::And, right } => {
+                conjuncts(left, terms);
+                conjuncts(right, terms);
+            }
+            Expr::Nested(inner) => conjuncts(inner, terms),
+            _ => terms.push(expr),
+        }
+    }
+    let mut terms = Vec::new();
+    conjuncts(expr, &mut terms);
+    if terms.len() < 2 {
+        return None;
+    }
+
+    let constant = |expr: &Expr| !matches!(expr, Expr::Identifier(_) | Expr::CompoundIdentifier(_));
+    let mut keys: Vec<(String, Expr)> = Vec::new();
+    for term in terms {
+        if let Some((column, value)) = try_extract_equality(term)
+            && constant(&value)
+            && !keys.iter().any(|(key_column, _)| *key_column == column)
+        {
+            keys.push((column, value));
+        }
+    }
+    (!keys.is_empty()).then_some(keys)
+}
+
+/// Extract `col BETWEEN low AND high` with constant bounds
+fn try_extract_between(expr: &sqlparser::ast::Expr) -> Option<(String, Box<sqlparser::ast::Expr>, Box<sqlparser::ast::Expr>)> {
+    use sqlparser::ast::Expr;
+
+    match expr {
+        Expr::Between { expr, negated: false, low, high } => {
+            let Expr::Identifier(ident) = &**expr else {
+                return None;
+            };
+            let constant = |bound: &Expr| !matches!(bound, Expr::Identifier(_) | Expr::CompoundIdentifier(_));
+            if !constant(low) || !constant(high) {
+                return None;
+            }
+            Some((ident.value.clone(), low.clone(), high.clone()))
+        }
+        Expr::Nested(inner) => try_extract_between(inner),
+        _ => None,
+    }
+}
+
+/// Extract `col op value` with an operator only an extension implements and a
+/// constant right operand
+fn try_extract_extension_operator(
+    expr: &sqlparser::ast::Expr,
+) -> Option<(String, sqlparser::ast::BinaryOperator, Box<sqlparser::ast::Expr>)> {
+    use sqlparser::ast::Expr;
+
+    match expr {
+        Expr::BinaryOp { left, op, right } if !evaluator::is_builtin_operator(op) => {
+            let Expr::Identifier(ident) = &**left else {
+                return None;
+            };
+            if matches!(&**right, Expr::Identifier(_) | Expr::CompoundIdentifier(_)) {
+                return None;
+            }
+            Some((ident.value.clone(), op.clone(), right.clone()))
+        }
+        Expr::Nested(inner) => try_extract_extension_operator(inner),
+        _ => None,
+    }
+}
+
 fn try_extract_equality(expr: &sqlparser::ast::Expr) -> Option<(String, sqlparser::ast::Expr)> {
     use sqlparser::ast::{BinaryOperator, Expr};
 
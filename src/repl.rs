@@ -0,0 +1,265 @@
+//! `flint repl`: an interactive SQL shell that opens the database in the data
+//! directory directly, without starting the server
+//!
+//! Lines are read with readline editing and history, and gathered until a
+//! statement ends with a semicolon, so statements can span lines. Results are
+//! printed as psql prints them. Like the other offline commands it takes the
+//! data directory lock, so it refuses to run while a server is using it.
+//! When stdin is not a terminal, statements are read from it without prompts.
+
+use std::io::IsTerminal;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+
+use futures::StreamExt;
+use pgwire::api::results::{QueryResponse, Response, Tag};
+use pgwire::api::Type;
+use pgwire::messages::response::CommandComplete;
+use rustyline::error::ReadlineError;
+use rustyline::DefaultEditor;
+
+use crate::config::Config;
+use crate::executor::Executor;
+use crate::storage;
+
+const PROMPT: &str = "flint=> ";
+/// Prompt for the lines of a statement after its first
+const CONTINUATION_PROMPT: &str = "flint-> ";
+const HISTORY_FILE: &str = ".flint_history";
+
+/// Run the shell until `\q` or end of input
+pub fn run(config: &Config, args: &[String]) -> Result<String, String> {
+    if !args.is_empty() {
+        return Err("usage: flint repl".to_string());
+    }
+    let _data_dir_lock = storage::lock_data_dir(&config.data_dir)?;
+    let executor = Executor::new(config).map_err(|e| e.into_error_info().message)?;
+    // The shell is a single session, so cursors and prepared statements
+    // live until it exits
+    let session_id = SocketAddr::from(([127, 0, 0, 1], 0));
+
+    let mut editor = DefaultEditor::new().map_err(|e| format!("Failed to start line editor: {}", e))?;
+    let history = std::env::var_os("HOME").map(|home| PathBuf::from(home).join(HISTORY_FILE));
+    if let Some(history) = &history {
+        // There is no history yet on the first run
+        let _ = editor.load_history(history);
+    }
+    if std::io::stdin().is_terminal() {
+        println!(
+            "flint {} on {}\nEnd statements with ; and type \\q to quit.\n",
+            env!("CARGO_PKG_VERSION"),
+            config.data_dir.display()
+        );
+    }
+
+    let mut buffer = String::new();
+    loop {
+        let prompt = if buffer.is_empty() { PROMPT } else { CONTINUATION_PROMPT };
+        let line = match editor.readline(prompt) {
+            Ok(line) => line,
+            // Ctrl-C discards the statement being typed, as in psql
+            Err(ReadlineError::Interrupted) => {
+                buffer.clear();
+                continue;
+            }
+            Err(ReadlineError::Eof) => break,
+            Err(e) => return Err(format!("Failed to read input: {}", e)),
+        };
+
+        if buffer.is_empty() && line.trim_start().starts_with('\\') {
+            let _ = editor.add_history_entry(line.as_str());
+            match line.trim() {
+                "\\q" => break,
+                "\\dt" => print_responses(executor.execute(session_id, "SELECT table_name FROM information_schema.tables;")),
+                "\\?" => println!("\\dt  list tables\n\\q   quit\n"),
+                other => eprintln!("invalid command {}\nTry \\? for help.", other),
+            }
+            continue;
+        }
+
+        if !buffer.is_empty() {
+            buffer.push('\n');
+        }
+        buffer.push_str(&line);
+        if buffer.trim().is_empty() {
+            buffer.clear();
+            continue;
+        }
+        if !statement_complete(&buffer) {
+            continue;
+        }
+
+        let _ = editor.add_history_entry(buffer.as_str());
+        print_responses(executor.execute(session_id, &buffer));
+        buffer.clear();
+    }
+
+    if let Some(history) = &history {
+        let _ = editor.save_history(history);
+    }
+    executor.end_session(session_id);
+    Ok(String::new())
+}
+
+/// Whether the text typed so far ends with a semicolon that isn't inside a
+/// string, quoted identifier, dollar-quoted body or comment
+fn statement_complete(sql: &str) -> bool {
+    let chars: Vec<char> = sql.chars().collect();
+    let mut complete = false;
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        match c {
+            '\'' | '"' => {
+                // A doubled quote inside the quotes is an escaped one
+                i += 1;
+                loop {
+                    match chars.get(i) {
+                        None => return false,
+                        Some(&q) if q == c && chars.get(i + 1) == Some(&c) => i += 2,
+                        Some(&q) if q == c => break,
+                        Some(_) => i += 1,
+                    }
+                }
+                complete = false;
+            }
+            '-' if chars.get(i + 1) == Some(&'-') => {
+                while i < chars.len() && chars[i] != '\n' {
+                    i += 1;
+                }
+            }
+            '/' if chars.get(i + 1) == Some(&'*') => {
+                i += 2;
+                while i < chars.len() && !(chars[i] == '*' && chars.get(i + 1) == Some(&'/')) {
+                    i += 1;
+                }
+                if i >= chars.len() {
+                    return false;
+                }
+                i += 1;
+            }
+            '$' => {
+                // $tag$ ... $tag$, where the tag may be empty
+                let tag_end = chars[i + 1..].iter()
+                    .position(|&t| !(t.is_alphanumeric() || t == '_'))
+                    .map(|offset| i + 1 + offset);
+                if let Some(tag_end) = tag_end
+                    && chars[tag_end] == '$'
+                {
+                    let delimiter: String = chars[i..=tag_end].iter().collect();
+                    let rest: String = chars[tag_end + 1..].iter().collect();
+                    let Some(close) = rest.find(&delimiter) else {
+                        return false;
+                    };
+                    i = tag_end + 1 + rest[..close].chars().count() + delimiter.chars().count() - 1;
+                }
+                complete = false;
+            }
+            ';' => complete = true,
+            c if c.is_whitespace() => {}
+            _ => complete = false,
+        }
+        i += 1;
+    }
+    complete
+}
+
+/// Print the results of a query as psql does, and any error after them
+fn print_responses(responses: Result<Vec<Response>, crate::executor::error::ExecutorError>) {
+    let responses = match responses {
+        Ok(responses) => responses,
+        Err(e) => {
+            eprintln!("ERROR:  {}", e.into_error_info().message);
+            return;
+        }
+    };
+    for response in responses {
+        match response {
+            Response::Query(query) => match format_query(query) {
+                Ok(table) => println!("{}", table),
+                Err(e) => eprintln!("ERROR:  {}", e),
+            },
+            Response::Execution(tag) | Response::TransactionStart(tag) | Response::TransactionEnd(tag) => {
+                println!("{}", command_tag(tag));
+            }
+            Response::Error(error_info) => eprintln!("{}:  {}", error_info.severity, error_info.message),
+            // Empty results, and INSERTs, print nothing
+            _ => {}
+        }
+    }
+}
+
+/// Text of a command tag, such as `UPDATE 3`
+fn command_tag(tag: Tag) -> String {
+    CommandComplete::from(tag).tag
+}
+
+/// A result set as an aligned table with a row count footer
+fn format_query(mut query: QueryResponse) -> Result<String, String> {
+    let fields = query.row_schema();
+    let right_aligned: Vec<bool> = fields.iter()
+        .map(|field| matches!(*field.datatype(), Type::INT2 | Type::INT4 | Type::INT8 | Type::FLOAT4 | Type::FLOAT8))
+        .collect();
+
+    let mut rows = Vec::new();
+    let data_rows = query.data_rows();
+    while let Some(data_row) = futures::executor::block_on(data_rows.next()) {
+        let data_row = data_row.map_err(|e| e.to_string())?;
+        rows.push(decode_text_fields(&data_row.data, data_row.field_count as usize)?);
+    }
+
+    let headers: Vec<&str> = fields.iter().map(|field| field.name()).collect();
+    let widths: Vec<usize> = headers.iter().enumerate()
+        .map(|(col, header)| {
+            rows.iter()
+                .map(|row: &Vec<String>| row[col].chars().count())
+                .chain(std::iter::once(header.chars().count()))
+                .max()
+                .unwrap_or(0)
+        })
+        .collect();
+
+    let line = |cells: Vec<String>| cells.join("|").trim_end().to_string();
+    let mut lines = Vec::with_capacity(rows.len() + 3);
+    lines.push(line(headers.iter().zip(&widths)
+        .map(|(header, &width)| {
+            let pad = width - header.chars().count();
+            format!(" {}{}{} ", " ".repeat(pad / 2), header, " ".repeat(pad - pad / 2))
+        })
+        .collect()));
+    lines.push(widths.iter().map(|&width| "-".repeat(width + 2)).collect::<Vec<_>>().join("+"));
+    for row in &rows {
+        lines.push(line(row.iter().zip(&widths).zip(&right_aligned)
+            .map(|((value, &width), &right)| if right {
+                format!(" {:>width$} ", value, width = width)
+            } else {
+                format!(" {:<width$} ", value, width = width)
+            })
+            .collect()));
+    }
+    lines.push(format!("({} {})\n", rows.len(), if rows.len() == 1 { "row" } else { "rows" }));
+    Ok(lines.join("\n"))
+}
+
+/// Fields of a text-format DataRow: each a 4-byte length, -1 for NULL,
+/// followed by that many bytes of text
+fn decode_text_fields(data: &[u8], field_count: usize) -> Result<Vec<String>, String> {
+    let mut fields = Vec::with_capacity(field_count);
+    let mut rest = data;
+    for _ in 0..field_count {
+        let (len, tail) = rest.split_first_chunk::<4>()
+            .ok_or_else(|| "Truncated data row".to_string())?;
+        let len = i32::from_be_bytes(*len);
+        if len < 0 {
+            // NULL prints as an empty cell
+            fields.push(String::new());
+            rest = tail;
+            continue;
+        }
+        let (value, tail) = tail.split_at_checked(len as usize)
+            .ok_or_else(|| "Truncated data row".to_string())?;
+        fields.push(String::from_utf8_lossy(value).into_owned());
+        rest = tail;
+    }
+    Ok(fields)
+}
@@ -1,11 +1,23 @@
+use std::path::PathBuf;
 use std::sync::Arc;
+use std::time::Duration;
 
-use pgwire::tokio::process_socket;
+use pgwire::tokio::tokio_rustls::rustls::{self, ServerConfig};
+use pgwire::tokio::tokio_rustls::rustls::pki_types::pem::PemObject;
+use pgwire::tokio::tokio_rustls::rustls::pki_types::{CertificateDer, PrivateKeyDer};
+use pgwire::tokio::{process_socket, TlsAcceptor};
 use tokio::net::TcpListener;
-use tracing::{debug, error, info, span, Level};
+use tokio::signal::unix::{signal, SignalKind};
+use tokio::sync::Semaphore;
+use tracing::{debug, error, info, span, warn, Level};
 
-use crate::config::Config;
-use crate::handler::HandlerFactory;
+use crate::config::{Config, TlsConfig};
+use crate::handler::{HandlerFactory, TooManyClients};
+use crate::metrics;
+use crate::storage::{self, archive, backup};
+
+/// How often a read-only server checks for its promotion trigger file
+const PROMOTE_TRIGGER_POLL: Duration = Duration::from_millis(500);
 
 pub struct Server {
     config: Config,
@@ -17,29 +29,191 @@ impl Server {
     }
 
     pub async fn start(&self) {
-        let factory = Arc::new(HandlerFactory::new(&self.config));
+        // Held for as long as the server runs
+        let _data_dir_lock = match storage::lock_data_dir(&self.config.data_dir) {
+            Ok(lock) => lock,
+            Err(e) => {
+                error!(error = %e, "cannot start");
+                return;
+            }
+        };
+
+        if let Some(location) = &self.config.restore_from
+            && let Err(e) = archive::open(location)
+                .and_then(|backup| backup::restore(backup.as_ref(), &self.config.data_dir))
+        {
+            error!(error = %e, backup = %location, "restore failed");
+            return;
+        }
+
+        let tls_acceptor = match self.config.tls.as_ref().map(tls_acceptor).transpose() {
+            Ok(acceptor) => acceptor,
+            Err(e) => {
+                error!(error = %e, "cannot start");
+                return;
+            }
+        };
+
+        let factory = match HandlerFactory::new(&self.config) {
+            Ok(factory) => Arc::new(factory),
+            Err(e) => {
+                error!(error = %e, "cannot start");
+                return;
+            }
+        };
 
         let server_addr = format!("{}:{}", self.config.bind_addr, self.config.port);
         let listener = TcpListener::bind(&server_addr).await.unwrap();
 
-        info!(addr = %server_addr, "server listening");
+        info!(addr = %server_addr, tls = tls_acceptor.is_some(), "server listening");
+
+        if let Some(metrics_addr) = &self.config.metrics_addr {
+            match TcpListener::bind(metrics_addr).await {
+                Ok(listener) => {
+                    info!(addr = %metrics_addr, "serving metrics");
+                    tokio::spawn(metrics::serve(listener));
+                }
+                Err(e) => {
+                    error!(addr = %metrics_addr, error = %e, "cannot start");
+                    return;
+                }
+            }
+        }
+
+        if self.config.read_only
+            && let Some(trigger_file) = &self.config.promote_trigger_file
+        {
+            tokio::spawn(watch_promote_trigger(trigger_file.clone(), factory.clone()));
+        }
+        tokio::spawn(expire_rows_periodically(self.config.ttl_check_interval, factory.clone()));
+
+        // Each open connection holds a permit until it closes
+        let connections = Arc::new(Semaphore::new(self.config.max_connections));
+
+        let shutdown = shutdown_signal();
+        tokio::pin!(shutdown);
 
         loop {
-            let incoming_socket = listener.accept().await.unwrap();
+            let incoming_socket = tokio::select! {
+                accepted = listener.accept() => accepted.unwrap(),
+                _ = &mut shutdown => break,
+            };
             let client_addr = incoming_socket.1;
 
+            let tls_acceptor = tls_acceptor.clone();
+            let Ok(permit) = connections.clone().try_acquire_owned() else {
+                // The client is still answered, so it sees why it was refused
+                metrics::connection_refused();
+                tokio::spawn(async move {
+                    warn!(client_addr = %client_addr, "connection refused: too many clients");
+                    if let Err(e) = process_socket(incoming_socket.0, tls_acceptor, TooManyClients).await {
+                        debug!(client_addr = %client_addr, error = %e, "refused connection error");
+                    }
+                });
+                continue;
+            };
             let factory_ref = factory.clone();
             tokio::spawn(async move {
+                let _permit = permit;
                 let span = span!(Level::INFO, "connection", client_addr = %client_addr);
                 let _enter = span.enter();
 
                 info!("new connection");
+                metrics::connection_opened();
 
-                match process_socket(incoming_socket.0, None, factory_ref).await {
+                match process_socket(incoming_socket.0, tls_acceptor, factory_ref.clone()).await {
                     Ok(_) => debug!("connection closed"),
                     Err(e) => error!(error = %e, "connection error"),
                 }
+                // Rolling back an open transaction holds the database lock, so
+                // keep it off the async workers
+                if let Err(e) = tokio::task::spawn_blocking(move || factory_ref.end_session(client_addr)).await {
+                    error!(error = %e, "session cleanup task failed");
+                }
+                metrics::connection_closed();
             });
         }
+
+        // New connections are refused from here on; open ones are dropped as
+        // the process exits, once the database is checkpointed
+        drop(listener);
+        let timeout = self.config.shutdown_timeout;
+        info!(timeout_secs = timeout.as_secs(), "shutting down");
+        match tokio::task::spawn_blocking(move || factory.shutdown(timeout)).await {
+            Ok(Ok(())) => info!("shutdown complete"),
+            Ok(Err(e)) => error!(error = %e, "shutdown failed"),
+            Err(e) => error!(error = %e, "shutdown task failed"),
+        }
+    }
+}
+
+/// Wait for SIGTERM or SIGINT, either of which shuts the server down
+async fn shutdown_signal() {
+    let mut terminate = signal(SignalKind::terminate()).expect("failed to listen for SIGTERM");
+    tokio::select! {
+        _ = terminate.recv() => info!("received SIGTERM"),
+        _ = tokio::signal::ctrl_c() => info!("received SIGINT"),
+    }
+}
+
+/// Acceptor for the TLS connections clients ask for with an SSLRequest,
+/// from the configured certificate chain and private key
+fn tls_acceptor(tls: &TlsConfig) -> Result<TlsAcceptor, String> {
+    let certs = CertificateDer::pem_file_iter(&tls.cert_path)
+        .and_then(|certs| certs.collect::<Result<Vec<_>, _>>())
+        .map_err(|e| format!("cannot read TLS certificate {}: {}", tls.cert_path.display(), e))?;
+    if certs.is_empty() {
+        return Err(format!("no certificate in {}", tls.cert_path.display()));
+    }
+    let key = PrivateKeyDer::from_pem_file(&tls.key_path)
+        .map_err(|e| format!("cannot read TLS private key {}: {}", tls.key_path.display(), e))?;
+
+    // Both of rustls's crypto providers are built in, so name the one to use
+    let provider = Arc::new(rustls::crypto::aws_lc_rs::default_provider());
+    let mut config = ServerConfig::builder_with_provider(provider)
+        .with_safe_default_protocol_versions()
+        .and_then(|builder| builder.with_no_client_auth().with_single_cert(certs, key))
+        .map_err(|e| format!("invalid TLS certificate or key: {}", e))?;
+    // Clients connecting with sslnegotiation=direct name the protocol
+    config.alpn_protocols = vec![b"postgresql".to_vec()];
+    Ok(TlsAcceptor::from(Arc::new(config)))
+}
+
+/// Poll for the promotion trigger file, promoting the server when it appears
+async fn watch_promote_trigger(trigger_file: PathBuf, factory: Arc<HandlerFactory>) {
+    info!(path = %trigger_file.display(), "watching for promotion trigger file");
+    let mut interval = tokio::time::interval(PROMOTE_TRIGGER_POLL);
+    loop {
+        interval.tick().await;
+        if !tokio::fs::try_exists(&trigger_file).await.unwrap_or(false) {
+            continue;
+        }
+        info!(path = %trigger_file.display(), "promotion trigger file found");
+        if let Err(e) = tokio::fs::remove_file(&trigger_file).await {
+            error!(error = %e, "failed to remove promotion trigger file");
+        }
+        // Promotion is one-way, so there is nothing left to watch for
+        if let Err(e) = factory.promote() {
+            error!(error = %e, "promotion failed");
+        }
+        return;
+    }
+}
+
+/// Delete rows past their table's TTL every `period`
+async fn expire_rows_periodically(period: Duration, factory: Arc<HandlerFactory>) {
+    let mut interval = tokio::time::interval(period);
+    // The first tick completes immediately; the first pass waits a full period
+    interval.tick().await;
+    loop {
+        interval.tick().await;
+        // Deleting holds the database lock, so keep it off the async workers
+        let factory = factory.clone();
+        match tokio::task::spawn_blocking(move || factory.expire_rows()).await {
+            Ok(Ok(0)) => {}
+            Ok(Ok(rows)) => info!(rows, "expired rows past their TTL"),
+            Ok(Err(e)) => error!(error = %e, "row expiry failed"),
+            Err(e) => error!(error = %e, "row expiry task failed"),
+        }
     }
 }
@@ -0,0 +1,107 @@
+//! Archive targets: where backups are written to and restored from
+//!
+//! A target stores named blobs under one location. A location is either a
+//! directory path or, with the `s3` feature, an `s3://bucket/prefix` URL for
+//! any S3-compatible object store. Backups only ever write whole files and
+//! write their manifest last, so a target needs no more than put, get and an
+//! emptiness check.
+
+#[cfg(feature = "s3")]
+pub mod s3;
+
+use std::fs;
+use std::io::Write;
+use std::path::PathBuf;
+
+pub type Result<T> = std::result::Result<T, String>;
+
+/// Somewhere to store the files of one backup
+pub trait ArchiveTarget: Send + Sync {
+    /// Location that `open` turns back into this target
+    fn location(&self) -> String;
+
+    /// Store `data` under `name`, replacing any earlier contents; readers see
+    /// either the old contents or all of the new
+    fn put(&self, name: &str, data: &[u8]) -> Result<()>;
+
+    fn get(&self, name: &str) -> Result<Vec<u8>>;
+
+    /// Whether nothing has been stored at this location
+    fn is_empty(&self) -> Result<bool>;
+}
+
+/// A borrowed target, so one passed in can sit alongside opened ones
+impl<T: ArchiveTarget + ?Sized> ArchiveTarget for &T {
+    fn location(&self) -> String {
+        (**self).location()
+    }
+
+    fn put(&self, name: &str, data: &[u8]) -> Result<()> {
+        (**self).put(name, data)
+    }
+
+    fn get(&self, name: &str) -> Result<Vec<u8>> {
+        (**self).get(name)
+    }
+
+    fn is_empty(&self) -> Result<bool> {
+        (**self).is_empty()
+    }
+}
+
+/// Open the target a location names
+pub fn open(location: &str) -> Result<Box<dyn ArchiveTarget>> {
+    if location.starts_with("s3://") {
+        #[cfg(feature = "s3")]
+        return Ok(Box::new(s3::S3Target::from_url(location)?));
+        #[cfg(not(feature = "s3"))]
+        return Err(format!("Cannot use {}: flint was built without the s3 feature", location));
+    }
+    if location.contains("://") {
+        return Err(format!("Unsupported archive location: {}", location));
+    }
+    Ok(Box::new(DirectoryTarget::new(PathBuf::from(location))))
+}
+
+/// A directory on a local or mounted filesystem
+pub struct DirectoryTarget {
+    dir: PathBuf,
+}
+
+impl DirectoryTarget {
+    pub fn new(dir: PathBuf) -> Self {
+        DirectoryTarget { dir }
+    }
+}
+
+impl ArchiveTarget for DirectoryTarget {
+    fn location(&self) -> String {
+        // Absolute, so a backup that names this one as its base still finds
+        // it from another working directory
+        self.dir.canonicalize().unwrap_or_else(|_| self.dir.clone()).display().to_string()
+    }
+
+    fn put(&self, name: &str, data: &[u8]) -> Result<()> {
+        fs::create_dir_all(&self.dir)
+            .map_err(|e| format!("Failed to create backup directory {}: {}", self.dir.display(), e))?;
+        let path = self.dir.join(name);
+        let temp_path = self.dir.join(format!("{}.tmp", name));
+        fs::File::create(&temp_path)
+            .and_then(|mut file| file.write_all(data).and_then(|_| file.sync_all()))
+            .and_then(|_| fs::rename(&temp_path, &path))
+            .map_err(|e| format!("Failed to write {}: {}", path.display(), e))
+    }
+
+    fn get(&self, name: &str) -> Result<Vec<u8>> {
+        let path = self.dir.join(name);
+        fs::read(&path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))
+    }
+
+    fn is_empty(&self) -> Result<bool> {
+        match fs::read_dir(&self.dir) {
+            Ok(mut entries) => Ok(entries.next().is_none()),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(true),
+            Err(e) => Err(format!("Failed to read backup directory {}: {}", self.dir.display(), e)),
+        }
+    }
+}
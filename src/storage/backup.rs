@@ -0,0 +1,490 @@
+//! Full and incremental backups of the data directory, and restoring them
+//!
+//! A backup is a location (see `archive`) holding a copy of each database
+//! file and a manifest listing the files with their size, checksum and the checksum of
+//! every 64KB block. A full backup copies each file whole. An incremental
+//! backup is taken against an earlier backup (full or incremental) and
+//! compares block checksums with that backup's manifest: files it has not
+//! seen are copied whole, and for the rest only the blocks that changed are
+//! stored, in `<file>.delta`. The WAL is one of the files, so its new entries
+//! are picked up the same way.
+//!
+//! Restoring an incremental backup first restores the backup it was taken
+//! against, then applies its own files over the result, so a chain of
+//! incrementals is applied in order back to its full backup. The manifest is
+//! written last; a backup directory without one is incomplete.
+//!
+//! Verifying a backup rebuilds each file through the chain in memory and
+//! checks it against the manifest, without touching a data directory. The
+//! manifest ends with a checksum of its own contents, and records the
+//! checksum of each stored copy so damage is pinned to the file that has it.
+//! Restore verifies the backup before writing anything.
+
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+
+use tracing::{debug, info, warn};
+
+use crate::storage::archive::{self, ArchiveTarget};
+use crate::storage::base::BLOCK_SIZE;
+use crate::storage::wal::compute_crc32;
+
+pub type Result<T> = std::result::Result<T, String>;
+
+/// File in a backup listing its contents
+pub const MANIFEST_FILE: &str = "backup.manifest";
+
+/// First line of a manifest, naming its format version
+const MANIFEST_HEADER: &str = "flint backup manifest 1";
+
+/// How a file is stored in a backup
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Stored {
+    /// The whole file, under its own name
+    Full,
+    /// Only the blocks changed since the base backup, in `<name>.delta`
+    Delta,
+}
+
+/// A database file as recorded in a manifest
+#[derive(Debug, Clone, PartialEq)]
+pub struct FileEntry {
+    pub name: String,
+    pub size: u64,
+    /// CRC32 of the whole file
+    pub checksum: u32,
+    pub stored: Stored,
+    /// CRC32 of the copy in the backup (the file or its delta)
+    pub stored_checksum: u32,
+    /// CRC32 of each BLOCK_SIZE block of the file, the last one possibly short
+    pub block_checksums: Vec<u32>,
+}
+
+impl FileEntry {
+    /// Name of the copy in the backup
+    fn stored_name(&self) -> String {
+        match self.stored {
+            Stored::Full => self.name.clone(),
+            Stored::Delta => format!("{}.delta", self.name),
+        }
+    }
+}
+
+/// Contents of a backup
+#[derive(Debug, Clone, PartialEq)]
+pub struct Manifest {
+    /// Location of the backup this one was taken against; None for a full backup
+    pub base: Option<String>,
+    pub files: Vec<FileEntry>,
+}
+
+impl Manifest {
+    pub fn read(backup: &dyn ArchiveTarget) -> Result<Self> {
+        let path = format!("{}/{}", backup.location(), MANIFEST_FILE);
+        let text = String::from_utf8(backup.get(MANIFEST_FILE)?)
+            .map_err(|_| format!("{} is not a flint backup manifest", path))?;
+        if !text.starts_with(&format!("{}\n", MANIFEST_HEADER)) {
+            return Err(format!("{} is not a flint backup manifest", path));
+        }
+
+        // The last line checksums everything before it
+        let body_end = text.trim_end_matches('\n').rfind('\n').map_or(0, |idx| idx + 1);
+        let (body, trailer) = text.split_at(body_end);
+        let checksum = trailer.trim_end().strip_prefix("end ")
+            .and_then(|checksum| u32::from_str_radix(checksum, 16).ok())
+            .ok_or_else(|| format!("Backup manifest {} is truncated", path))?;
+        if checksum != compute_crc32(body.as_bytes()) {
+            return Err(format!("Backup manifest {} is corrupt: checksum mismatch", path));
+        }
+
+        let mut manifest = Manifest { base: None, files: Vec::new() };
+        for line in body.lines().skip(1) {
+            let invalid = || format!("Invalid line in backup manifest {}: {}", path, line);
+            if let Some(base) = line.strip_prefix("base ") {
+                manifest.base = Some(base.to_string());
+                continue;
+            }
+            // file <full|delta> <size> <checksum> <stored checksum> <block checksums> <name>,
+            // the name running to the end of the line
+            let fields: Vec<&str> = line.strip_prefix("file ").ok_or_else(invalid)?.splitn(6, ' ').collect();
+            let [stored, size, checksum, stored_checksum, block_checksums, name] = fields[..] else {
+                return Err(invalid());
+            };
+            let stored = match stored {
+                "full" => Stored::Full,
+                "delta" => Stored::Delta,
+                _ => return Err(invalid()),
+            };
+            let parse_checksum = |checksum: &str| u32::from_str_radix(checksum, 16).map_err(|_| invalid());
+            let block_checksums = match block_checksums {
+                "-" => Vec::new(),
+                checksums => checksums.split(',').map(parse_checksum).collect::<Result<Vec<_>>>()?,
+            };
+            manifest.files.push(FileEntry {
+                name: name.to_string(),
+                size: size.parse().map_err(|_| invalid())?,
+                checksum: parse_checksum(checksum)?,
+                stored,
+                stored_checksum: parse_checksum(stored_checksum)?,
+                block_checksums,
+            });
+        }
+        Ok(manifest)
+    }
+
+    /// Write the manifest into a backup, replacing it atomically
+    fn write(&self, backup: &dyn ArchiveTarget) -> Result<()> {
+        let mut text = format!("{}\n", MANIFEST_HEADER);
+        if let Some(base) = &self.base {
+            text.push_str(&format!("base {}\n", base));
+        }
+        for file in &self.files {
+            let block_checksums = if file.block_checksums.is_empty() {
+                "-".to_string()
+            } else {
+                file.block_checksums.iter()
+                    .map(|checksum| format!("{:08x}", checksum))
+                    .collect::<Vec<_>>()
+                    .join(",")
+            };
+            let stored = match file.stored {
+                Stored::Full => "full",
+                Stored::Delta => "delta",
+            };
+            text.push_str(&format!(
+                "file {} {} {:08x} {:08x} {} {}\n",
+                stored, file.size, file.checksum, file.stored_checksum, block_checksums, file.name
+            ));
+        }
+        text.push_str(&format!("end {:08x}\n", compute_crc32(text.as_bytes())));
+        backup.put(MANIFEST_FILE, text.as_bytes())
+    }
+
+    fn file(&self, name: &str) -> Option<&FileEntry> {
+        self.files.iter().find(|file| file.name == name)
+    }
+}
+
+/// What a backup wrote
+#[derive(Debug, Default)]
+pub struct BackupStats {
+    pub files: usize,
+    /// Bytes of file contents copied into the backup
+    pub bytes: u64,
+}
+
+/// Back up the named files of `data_dir` into `dest`, which must be empty;
+/// with a `base` backup, only what changed since it is stored
+pub fn take_backup(
+    data_dir: &Path,
+    file_names: &[String],
+    dest: &dyn ArchiveTarget,
+    base: Option<&dyn ArchiveTarget>,
+) -> Result<BackupStats> {
+    let base_manifest = base.map(Manifest::read).transpose()?;
+    if !dest.is_empty()? {
+        return Err(format!("Backup location {} is not empty", dest.location()));
+    }
+
+    let mut manifest = Manifest {
+        base: base.map(|base| base.location()),
+        files: Vec::new(),
+    };
+    let mut stats = BackupStats::default();
+    for name in file_names {
+        let data = fs::read(data_dir.join(name))
+            .map_err(|e| format!("Failed to read {} for backup: {}", name, e))?;
+        let block_checksums: Vec<u32> = data.chunks(BLOCK_SIZE).map(compute_crc32).collect();
+
+        let previous = base_manifest.as_ref().and_then(|manifest| manifest.file(name));
+        let (stored, copy) = match previous {
+            None => {
+                stats.bytes += data.len() as u64;
+                (Stored::Full, data.clone())
+            }
+            Some(previous) => {
+                // Each changed block is stored as its index followed by its contents
+                let mut delta = Vec::new();
+                for (idx, (block, checksum)) in data.chunks(BLOCK_SIZE).zip(&block_checksums).enumerate() {
+                    if previous.block_checksums.get(idx) != Some(checksum) {
+                        delta.extend_from_slice(&(idx as u64).to_le_bytes());
+                        delta.extend_from_slice(block);
+                        stats.bytes += block.len() as u64;
+                    }
+                }
+                (Stored::Delta, delta)
+            }
+        };
+        let entry = FileEntry {
+            name: name.clone(),
+            size: data.len() as u64,
+            checksum: compute_crc32(&data),
+            stored,
+            stored_checksum: compute_crc32(&copy),
+            block_checksums,
+        };
+        dest.put(&entry.stored_name(), &copy)?;
+        debug!(file = %name, size = data.len(), ?stored, "backed up file");
+        stats.files += 1;
+        manifest.files.push(entry);
+    }
+
+    manifest.write(dest)?;
+    info!(dest = %dest.location(), files = stats.files, bytes = stats.bytes, incremental = base.is_some(), "backup complete");
+    Ok(stats)
+}
+
+/// Check that a backup, and every backup it was taken against, restores to
+/// exactly the files its manifest lists
+/// Returns the number of files checked, or every problem found
+pub fn verify(backup: &dyn ArchiveTarget) -> Result<usize> {
+    let chain = read_chain(backup)?;
+    let mut problems = Vec::new();
+
+    // Damaged or missing copies, in any backup of the chain
+    for (target, manifest) in &chain {
+        for file in &manifest.files {
+            match target.get(&file.stored_name()) {
+                Ok(copy) if compute_crc32(&copy) == file.stored_checksum => {}
+                Ok(_) => problems.push(format!("{} in {} is corrupt", file.stored_name(), target.location())),
+                Err(e) => problems.push(e),
+            }
+        }
+    }
+
+    // Each file as a restore would rebuild it
+    if problems.is_empty() {
+        let (_, newest) = &chain[0];
+        for file in &newest.files {
+            match rebuild_file(&chain, &file.name) {
+                Ok(data) if data.len() as u64 == file.size && compute_crc32(&data) == file.checksum => {}
+                Ok(_) => problems.push(format!("{} does not restore to its recorded contents", file.name)),
+                Err(e) => problems.push(e),
+            }
+        }
+    }
+
+    if !problems.is_empty() {
+        warn!(backup = %backup.location(), problems = problems.len(), "backup failed verification");
+        return Err(format!("Backup {} failed verification: {}", backup.location(), problems.join("; ")));
+    }
+    info!(backup = %backup.location(), backups = chain.len(), files = chain[0].1.files.len(), "backup verified");
+    Ok(chain[0].1.files.len())
+}
+
+/// Restore a backup, and every backup it was taken against, into `data_dir`,
+/// which must not hold a database already
+pub fn restore(backup: &dyn ArchiveTarget, data_dir: &Path) -> Result<()> {
+    if crate::storage::holds_database(data_dir) {
+        return Err(format!("Data directory {} already holds a database", data_dir.display()));
+    }
+    verify(backup)?;
+    fs::create_dir_all(data_dir)
+        .map_err(|e| format!("Failed to create data directory {}: {}", data_dir.display(), e))?;
+
+    let chain = read_chain(backup)?;
+    let (_, newest) = &chain[0];
+    for file in &newest.files {
+        write_file(&data_dir.join(&file.name), &rebuild_file(&chain, &file.name)?)?;
+    }
+    info!(backup = %backup.location(), files = newest.files.len(), "backup restored");
+    Ok(())
+}
+
+/// Longest chain of incremental backups restore follows, which also stops a
+/// manifest that names itself as its base
+const MAX_CHAIN_LENGTH: usize = 1000;
+
+/// A backup and the backups it was taken against, newest first
+type Chain<'a> = Vec<(Box<dyn ArchiveTarget + 'a>, Manifest)>;
+
+fn read_chain(backup: &dyn ArchiveTarget) -> Result<Chain<'_>> {
+    let mut chain: Chain = vec![(Box::new(backup), Manifest::read(backup)?)];
+    while let Some(base) = chain.last().and_then(|(_, manifest)| manifest.base.clone()) {
+        if chain.len() >= MAX_CHAIN_LENGTH {
+            return Err(format!("Backup chain through {} is too long or circular", backup.location()));
+        }
+        let base = archive::open(&base)?;
+        let manifest = Manifest::read(base.as_ref())?;
+        chain.push((base, manifest));
+    }
+    Ok(chain)
+}
+
+/// Contents of a file as of the newest backup in `chain`: its last full copy
+/// with the deltas of later backups applied in order
+fn rebuild_file(chain: &Chain, name: &str) -> Result<Vec<u8>> {
+    let mut deltas = Vec::new();
+    for (target, manifest) in chain {
+        let file = manifest.file(name).ok_or_else(|| {
+            format!("{} has a delta in a later backup but is missing from {}", name, target.location())
+        })?;
+        match file.stored {
+            Stored::Delta => deltas.push((target, file)),
+            Stored::Full => {
+                let mut data = target.get(name)?;
+                for (target, file) in deltas.into_iter().rev() {
+                    let delta = target.get(&file.stored_name())?;
+                    apply_delta(&mut data, &delta, file.size)
+                        .map_err(|e| format!("{} in {}: {}", file.stored_name(), target.location(), e))?;
+                }
+                return Ok(data);
+            }
+        }
+    }
+    Err(format!("{} has no full copy in the backup chain", name))
+}
+
+/// Write the changed blocks of a delta over a file's earlier contents and cut
+/// it to its size at backup time
+fn apply_delta(data: &mut Vec<u8>, delta: &[u8], size: u64) -> Result<()> {
+    data.resize(size as usize, 0);
+    let mut rest = delta;
+    while !rest.is_empty() {
+        let Some((idx, after)) = rest.split_first_chunk::<8>() else {
+            return Err("delta is truncated".to_string());
+        };
+        let offset = u64::from_le_bytes(*idx).saturating_mul(BLOCK_SIZE as u64);
+        let len = size.saturating_sub(offset).min(BLOCK_SIZE as u64) as usize;
+        if len == 0 || after.len() < len {
+            return Err("delta does not match its manifest".to_string());
+        }
+        data[offset as usize..offset as usize + len].copy_from_slice(&after[..len]);
+        rest = &after[len..];
+    }
+    Ok(())
+}
+
+fn write_file(path: &Path, data: &[u8]) -> Result<()> {
+    fs::File::create(path)
+        .and_then(|mut file| file.write_all(data).and_then(|_| file.sync_all()))
+        .map_err(|e| format!("Failed to write {}: {}", path.display(), e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+    use crate::storage::archive::DirectoryTarget;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("flint-backup-test-{}-{}", std::process::id(), name));
+        let _ = fs::remove_dir_all(&dir);
+        dir
+    }
+
+    fn target(dir: &Path) -> DirectoryTarget {
+        DirectoryTarget::new(dir.to_path_buf())
+    }
+
+    #[test]
+    fn test_incremental_backup_and_restore() {
+        let data_dir = temp_dir("data");
+        fs::create_dir_all(&data_dir).unwrap();
+        let mut table = vec![1u8; BLOCK_SIZE * 3];
+        fs::write(data_dir.join("catalog_0.db"), b"catalog v1").unwrap();
+        fs::write(data_dir.join("table_a.tbl"), &table).unwrap();
+        fs::write(data_dir.join("table_b.tbl"), b"dropped later").unwrap();
+
+        let full = temp_dir("full");
+        let names = ["catalog_0.db", "table_a.tbl", "table_b.tbl"].map(String::from);
+        let stats = take_backup(&data_dir, &names, &target(&full), None).unwrap();
+        assert_eq!(stats.files, 3);
+
+        // Change one block, grow the file by a partial block, drop a file
+        table[BLOCK_SIZE + 7] = 9;
+        table.extend_from_slice(&[2u8; 100]);
+        fs::write(data_dir.join("table_a.tbl"), &table).unwrap();
+        fs::write(data_dir.join("catalog_0.db"), b"catalog v2").unwrap();
+        fs::write(data_dir.join("table_c.tbl"), b"new table").unwrap();
+
+        let incremental = temp_dir("incremental");
+        let names = ["catalog_0.db", "table_a.tbl", "table_c.tbl"].map(String::from);
+        let stats = take_backup(&data_dir, &names, &target(&incremental), Some(&target(&full))).unwrap();
+        assert_eq!(stats.bytes, (BLOCK_SIZE + 100 + b"catalog v2".len() + b"new table".len()) as u64,
+            "only changed blocks and new files should be copied");
+
+        let manifest = Manifest::read(&target(&incremental)).unwrap();
+        assert_eq!(manifest.file("table_a.tbl").unwrap().stored, Stored::Delta);
+        assert_eq!(manifest.file("table_c.tbl").unwrap().stored, Stored::Full);
+
+        let restored = temp_dir("restored");
+        restore(&target(&incremental), &restored).unwrap();
+        assert_eq!(fs::read(restored.join("table_a.tbl")).unwrap(), table);
+        assert_eq!(fs::read(restored.join("catalog_0.db")).unwrap(), b"catalog v2");
+        assert_eq!(fs::read(restored.join("table_c.tbl")).unwrap(), b"new table");
+        assert!(!restored.join("table_b.tbl").exists(), "dropped file should not be restored");
+
+        // Restoring over an existing database is refused
+        assert!(restore(&target(&incremental), &restored).is_err());
+        // So is backing up into a used directory
+        assert!(take_backup(&data_dir, &names, &target(&full), None).is_err());
+
+        for dir in [data_dir, full, incremental, restored] {
+            let _ = fs::remove_dir_all(dir);
+        }
+    }
+
+    #[test]
+    fn test_shrunk_file_restores_to_its_size() {
+        let data_dir = temp_dir("shrink-data");
+        fs::create_dir_all(&data_dir).unwrap();
+        fs::write(data_dir.join("flint.wal"), vec![5u8; BLOCK_SIZE + 10]).unwrap();
+        let names = ["flint.wal".to_string()];
+
+        let full = temp_dir("shrink-full");
+        take_backup(&data_dir, &names, &target(&full), None).unwrap();
+        fs::write(data_dir.join("flint.wal"), b"").unwrap();
+        let incremental = temp_dir("shrink-incremental");
+        let stats = take_backup(&data_dir, &names, &target(&incremental), Some(&target(&full))).unwrap();
+        assert_eq!(stats.bytes, 0);
+
+        let restored = temp_dir("shrink-restored");
+        restore(&target(&incremental), &restored).unwrap();
+        assert_eq!(fs::read(restored.join("flint.wal")).unwrap(), b"");
+
+        for dir in [data_dir, full, incremental, restored] {
+            let _ = fs::remove_dir_all(dir);
+        }
+    }
+
+    #[test]
+    fn test_verify_finds_damage() {
+        let data_dir = temp_dir("verify-data");
+        fs::create_dir_all(&data_dir).unwrap();
+        fs::write(data_dir.join("table_a.tbl"), vec![3u8; BLOCK_SIZE * 2]).unwrap();
+        fs::write(data_dir.join("table_b.tbl"), b"unchanged").unwrap();
+        let names = ["table_a.tbl", "table_b.tbl"].map(String::from);
+
+        let full = temp_dir("verify-full");
+        take_backup(&data_dir, &names, &target(&full), None).unwrap();
+        fs::write(data_dir.join("table_a.tbl"), vec![4u8; BLOCK_SIZE * 2]).unwrap();
+        let incremental = temp_dir("verify-incremental");
+        take_backup(&data_dir, &names, &target(&incremental), Some(&target(&full))).unwrap();
+        assert_eq!(verify(&target(&incremental)).unwrap(), 2);
+
+        // A damaged base breaks every backup taken against it
+        let mut copy = fs::read(full.join("table_b.tbl")).unwrap();
+        copy[0] ^= 1;
+        fs::write(full.join("table_b.tbl"), &copy).unwrap();
+        let err = verify(&target(&incremental)).unwrap_err();
+        assert!(err.contains("table_b.tbl") && err.contains("corrupt"), "unexpected error: {}", err);
+        let restored = temp_dir("verify-restored");
+        assert!(restore(&target(&incremental), &restored).is_err(), "a damaged backup should not be restored");
+        assert!(!restored.join("table_a.tbl").exists(), "nothing should be written from a damaged backup");
+
+        // So does any edit to a manifest
+        let manifest_path = incremental.join(MANIFEST_FILE);
+        let manifest = fs::read_to_string(&manifest_path).unwrap();
+        fs::write(&manifest_path, manifest.replacen("file delta 131072", "file delta 131071", 1)).unwrap();
+        let err = verify(&target(&incremental)).unwrap_err();
+        assert!(err.contains("checksum mismatch"), "unexpected error: {}", err);
+        fs::write(&manifest_path, &manifest[..manifest.len() / 2]).unwrap();
+        assert!(verify(&target(&incremental)).is_err(), "a truncated manifest should fail verification");
+
+        for dir in [data_dir, full, incremental, restored] {
+            let _ = fs::remove_dir_all(dir);
+        }
+    }
+}
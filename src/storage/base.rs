@@ -51,18 +51,35 @@ impl TuplePointer {
     }
 }
 
-/// MVCC metadata for each tuple
-#[derive(Debug, Clone, Copy)]
+/// Transaction ID stamped on tuples written outside a tracked transaction
+/// (visible to every snapshot)
+pub const FROZEN_TXID: TxId = 1;
+
+/// First transaction ID handed out to a transaction (see `mvcc`)
+pub const FIRST_TXID: TxId = FROZEN_TXID + 1;
+
+/// MVCC metadata for each tuple, stored inline in front of the tuple data
+/// zerocopy-verified safe layout: IntoBytes + FromBytes guarantee no padding between fields
+#[derive(Debug, Clone, Copy, IntoBytes, FromBytes, Immutable, KnownLayout)]
+#[repr(C)]
 pub struct TupleMeta {
     /// Transaction ID that created this tuple
     pub xmin: TxId,
     /// Transaction ID that deleted this tuple (0 if not deleted)
     pub xmax: TxId,
+    /// Per-tuple flags (none defined yet)
+    pub flags: u16,
+    /// Reserved for future use
+    pub reserved: [u8; 6],
 }
 
+/// Size of the per-tuple header preceding every tuple's data
+pub const TUPLE_HEADER_SIZE: usize = 24;
+const _: () = assert!(size_of::<TupleMeta>() == TUPLE_HEADER_SIZE);
+
 impl TupleMeta {
     pub fn new(xmin: TxId) -> Self {
-        TupleMeta { xmin, xmax: 0 }
+        TupleMeta { xmin, xmax: 0, flags: 0, reserved: [0; 6] }
     }
 
     pub fn is_deleted(&self) -> bool {
@@ -121,6 +138,16 @@ impl SegmentHeader {
             self.blocks_used -= 1;
         }
     }
+
+    /// Find the first free block at or after `start`
+    pub fn first_free_block(&self, start: BlockId) -> Option<BlockId> {
+        (start..BLOCKS_PER_UNCOMPRESSED_SEGMENT as u8).find(|&id| self.is_block_free(id))
+    }
+
+    /// Find the highest-numbered used block at or after `start` (the current insert target)
+    pub fn last_used_block(&self, start: BlockId) -> Option<BlockId> {
+        (start..BLOCKS_PER_UNCOMPRESSED_SEGMENT as u8).rev().find(|&id| !self.is_block_free(id))
+    }
 }
 
 /// Block header for slotted page
@@ -136,10 +163,16 @@ pub struct BlockHeader {
     pub free_start: u32,
     /// Offset to end of free space (grows backward from end)
     pub free_end: u32,
+    /// On-disk block format version
+    pub version: u16,
     /// Reserved for future use
-    pub reserved: [u8; 4],
+    pub reserved: [u8; 2],
 }
 
+/// Current block format version
+/// Version 1: every tuple is prefixed with a TupleMeta header
+pub const BLOCK_FORMAT_VERSION: u16 = 1;
+
 const BLOCK_HEADER_SIZE: usize = 16;
 const _: () = assert!(size_of::<BlockHeader>() == BLOCK_HEADER_SIZE);
 
@@ -150,7 +183,8 @@ impl BlockHeader {
             flags: 0,
             free_start: BLOCK_HEADER_SIZE as u32,
             free_end: BLOCK_SIZE as u32,
-            reserved: [0; 4],
+            version: BLOCK_FORMAT_VERSION,
+            reserved: [0; 2],
         }
     }
 
@@ -173,6 +207,9 @@ pub struct SlotEntry {
 const SLOT_ENTRY_SIZE: usize = 4;
 const _: () = assert!(size_of::<SlotEntry>() == SLOT_ENTRY_SIZE);
 
+/// Largest tuple that fits in an empty block (one slot entry + tuple header + data)
+pub const MAX_TUPLE_SIZE: usize = BLOCK_SIZE - BLOCK_HEADER_SIZE - SLOT_ENTRY_SIZE - TUPLE_HEADER_SIZE;
+
 impl SlotEntry {
     pub fn new(offset: u16, length: u16) -> Self {
         SlotEntry { offset, length }
@@ -255,50 +292,258 @@ impl Block {
             .expect("Block alignment guaranteed by Vec<u32>")
     }
 
-    /// Read tuple data at slot
+    /// Check the header and slot directory against the block's bounds
+    /// Reading a slot or tuple past the block's end panics, so a block read
+    /// from disk must pass this before its contents are trusted
+    pub fn validate(&self) -> std::io::Result<()> {
+        let invalid = |message: String| std::io::Error::new(std::io::ErrorKind::InvalidData, message);
+        let header = self.header();
+        if header.version != BLOCK_FORMAT_VERSION {
+            return Err(invalid(format!(
+                "unsupported block format version {} (expected {})",
+                header.version, BLOCK_FORMAT_VERSION
+            )));
+        }
+        let slots_end = BLOCK_HEADER_SIZE + header.slot_count as usize * SLOT_ENTRY_SIZE;
+        if slots_end > header.free_start as usize
+            || header.free_start > header.free_end
+            || header.free_end as usize > BLOCK_SIZE
+        {
+            return Err(invalid(format!(
+                "free space {}..{} does not fit {} slots in the block",
+                header.free_start, header.free_end, header.slot_count
+            )));
+        }
+        for slot_id in 0..header.slot_count {
+            let slot = self.slot(slot_id);
+            if slot.is_empty() {
+                continue;
+            }
+            let (start, end) = (slot.offset as usize, slot.offset as usize + slot.length as usize);
+            if start < header.free_end as usize || end > BLOCK_SIZE || (slot.length as usize) < TUPLE_HEADER_SIZE {
+                return Err(invalid(format!("slot {} points outside the tuple data", slot_id)));
+            }
+        }
+        Ok(())
+    }
+
+    /// Read tuple data at slot (without the tuple header)
     pub fn read_tuple(&self, slot_id: SlotId) -> Option<&[u8]> {
         let slot = self.slot(slot_id);
         if slot.is_empty() {
             return None;
         }
         let bytes = self.as_bytes();
-        let start = slot.offset as usize;
-        let end = start + slot.length as usize;
+        let start = slot.offset as usize + TUPLE_HEADER_SIZE;
+        let end = slot.offset as usize + slot.length as usize;
         Some(&bytes[start..end])
     }
 
-    /// Append tuple data to block (allocates new slot)
-    pub fn append_tuple(&mut self, data: &[u8]) -> Option<SlotId> {
+    /// Read the MVCC header of the tuple at slot
+    /// Tuples are not 8-byte aligned within the block, so the header is copied out
+    pub fn tuple_meta(&self, slot_id: SlotId) -> Option<TupleMeta> {
+        let slot = self.slot(slot_id);
+        if slot.is_empty() {
+            return None;
+        }
+        let start = slot.offset as usize;
+        TupleMeta::read_from_bytes(&self.as_bytes()[start..start + TUPLE_HEADER_SIZE]).ok()
+    }
+
+    /// Overwrite the MVCC header of the tuple at slot, leaving its data untouched
+    pub fn set_tuple_meta(&mut self, slot_id: SlotId, meta: &TupleMeta) -> bool {
+        let slot = self.slot(slot_id);
+        if slot.is_empty() {
+            return false;
+        }
+        let start = slot.offset as usize;
+        self.as_bytes_mut()[start..start + TUPLE_HEADER_SIZE].copy_from_slice(meta.as_bytes());
+        true
+    }
+
+    /// Append tuple data to block
+    /// Reuses a tombstoned slot if one exists, otherwise allocates a new slot
+    pub fn append_tuple(&mut self, meta: &TupleMeta, data: &[u8]) -> Option<SlotId> {
         // Get values from header first
-        let slot_id = self.header().slot_count;
         let free_end = self.header().free_end;
         let free_space = self.header().free_space();
+        let reused_slot = self.first_free_slot();
 
-        // Check space for slot entry + data
-        let slot_space = SLOT_ENTRY_SIZE;
-        let data_space = data.len();
+        // Check space for slot entry (unless reusing one) + tuple header + data
+        let slot_space = if reused_slot.is_some() { 0 } else { SLOT_ENTRY_SIZE };
+        let data_space = TUPLE_HEADER_SIZE + data.len();
         let total_space = slot_space + data_space;
 
         if free_space < total_space {
-            return None;
+            // Space freed by deletes is fragmented; only coalesce it when doing so
+            // would actually make this tuple fit
+            if free_space + self.reclaimable_space() < total_space {
+                return None;
+            }
+            self.compact();
+            return self.append_tuple(meta, data);
         }
 
-        // Allocate from end (tuple data)
+        // Allocate from end (tuple header followed by tuple data)
         let new_free_end = free_end - data_space as u32;
         let bytes = self.as_bytes_mut();
-        bytes[new_free_end as usize..free_end as usize].copy_from_slice(data);
+        let start = new_free_end as usize;
+        bytes[start..start + TUPLE_HEADER_SIZE].copy_from_slice(meta.as_bytes());
+        bytes[start + TUPLE_HEADER_SIZE..free_end as usize].copy_from_slice(data);
 
-        // Create slot entry
-        *self.slot_mut(slot_id) = SlotEntry::new(new_free_end as u16, data.len() as u16);
+        let slot_id = reused_slot.unwrap_or(self.header().slot_count);
+        *self.slot_mut(slot_id) = SlotEntry::new(new_free_end as u16, data_space as u16);
 
         // Update header
         let header = self.header_mut();
-        header.slot_count += 1;
-        header.free_start += SLOT_ENTRY_SIZE as u32;
+        if reused_slot.is_none() {
+            header.slot_count += 1;
+            header.free_start += SLOT_ENTRY_SIZE as u32;
+        }
         header.free_end = new_free_end;
 
         Some(slot_id)
     }
+
+    /// Append tuple data only if the block keeps at least `reserve` bytes free
+    /// afterwards, the room a fillfactor below 100 leaves for rows' later versions
+    /// A block without live tuples takes any tuple that fits, so a tuple larger
+    /// than the fill limit still finds a block
+    pub fn append_tuple_reserving(&mut self, meta: &TupleMeta, data: &[u8], reserve: usize) -> Option<SlotId> {
+        let has_live_tuples = (0..self.header().slot_count).any(|slot_id| !self.slot(slot_id).is_empty());
+        if has_live_tuples {
+            let available = self.header().free_space() + self.reclaimable_space();
+            let needed = SLOT_ENTRY_SIZE + TUPLE_HEADER_SIZE + data.len();
+            if available < needed + reserve {
+                return None;
+            }
+        }
+        self.append_tuple(meta, data)
+    }
+
+    /// Write a tuple into a given slot, as WAL replay redoes a logged insert
+    /// The slot directory grows to reach the slot if needed, and whatever the
+    /// slot held is replaced. Returns false if the block has no room for the tuple
+    pub fn put_tuple(&mut self, slot_id: SlotId, meta: &TupleMeta, data: &[u8]) -> bool {
+        if slot_id < self.header().slot_count {
+            if self.read_tuple(slot_id) == Some(data) {
+                return self.set_tuple_meta(slot_id, meta);
+            }
+            // The old contents become space `compact` can reclaim
+            *self.slot_mut(slot_id) = SlotEntry::new(0, 0);
+        }
+
+        let data_space = TUPLE_HEADER_SIZE + data.len();
+        let needed = |block: &Block| {
+            let new_slots = (slot_id as usize + 1).saturating_sub(block.header().slot_count as usize);
+            new_slots * SLOT_ENTRY_SIZE + data_space
+        };
+        if self.header().free_space() < needed(self) {
+            self.compact();
+            if self.header().free_space() < needed(self) {
+                return false;
+            }
+        }
+
+        let slot_count = self.header().slot_count;
+        if slot_id >= slot_count {
+            for new_slot in slot_count..slot_id {
+                *self.slot_mut(new_slot) = SlotEntry::new(0, 0);
+            }
+            let header = self.header_mut();
+            header.slot_count = slot_id + 1;
+            header.free_start += (slot_id + 1 - slot_count) as u32 * SLOT_ENTRY_SIZE as u32;
+        }
+
+        let free_end = self.header().free_end;
+        let new_free_end = free_end - data_space as u32;
+        let bytes = self.as_bytes_mut();
+        let start = new_free_end as usize;
+        bytes[start..start + TUPLE_HEADER_SIZE].copy_from_slice(meta.as_bytes());
+        bytes[start + TUPLE_HEADER_SIZE..free_end as usize].copy_from_slice(data);
+        *self.slot_mut(slot_id) = SlotEntry::new(new_free_end as u16, data_space as u16);
+        self.header_mut().free_end = new_free_end;
+        true
+    }
+
+    /// Tombstone the tuple at slot
+    /// The slot id becomes reusable immediately; the tuple's data space is only
+    /// reclaimed by `compact`
+    pub fn delete_tuple(&mut self, slot_id: SlotId) -> bool {
+        if slot_id >= self.header().slot_count || self.slot(slot_id).is_empty() {
+            return false;
+        }
+        *self.slot_mut(slot_id) = SlotEntry::new(0, 0);
+        true
+    }
+
+    /// First tombstoned slot, if any
+    fn first_free_slot(&self) -> Option<SlotId> {
+        (0..self.header().slot_count).find(|&slot_id| self.slot(slot_id).is_empty())
+    }
+
+    /// Bytes `compact` would return to the contiguous free region: data of
+    /// deleted tuples plus tombstoned slot entries at the end of the directory
+    pub fn reclaimable_space(&self) -> usize {
+        let header = self.header();
+        let live_bytes: usize = (0..header.slot_count)
+            .map(|slot_id| self.slot(slot_id).length as usize)
+            .sum();
+        let dead_bytes = (BLOCK_SIZE - header.free_end as usize) - live_bytes;
+        dead_bytes + self.trailing_free_slots() as usize * SLOT_ENTRY_SIZE
+    }
+
+    /// Number of tombstoned slots at the end of the slot directory
+    fn trailing_free_slots(&self) -> SlotId {
+        let slot_count = self.header().slot_count;
+        (0..slot_count)
+            .rev()
+            .take_while(|&slot_id| self.slot(slot_id).is_empty())
+            .count() as SlotId
+    }
+
+    /// Rewrite live tuples contiguously at the end of the block and drop trailing
+    /// tombstones from the slot directory, coalescing all free space between
+    /// free_start and free_end. Ids of live slots are preserved.
+    /// Returns the number of bytes reclaimed
+    pub fn compact(&mut self) -> usize {
+        let old_free_space = self.header().free_space();
+        let slot_count = self.header().slot_count - self.trailing_free_slots();
+        let old_free_end = self.header().free_end;
+
+        let live: Vec<(SlotId, Vec<u8>)> = (0..slot_count)
+            .filter_map(|slot_id| {
+                let slot = self.slot(slot_id);
+                if slot.is_empty() {
+                    return None;
+                }
+                let start = slot.offset as usize;
+                let end = start + slot.length as usize;
+                Some((slot_id, self.as_bytes()[start..end].to_vec()))
+            })
+            .collect();
+
+        let mut free_end = BLOCK_SIZE;
+        for (slot_id, tuple) in &live {
+            let start = free_end - tuple.len();
+            self.as_bytes_mut()[start..free_end].copy_from_slice(tuple);
+            *self.slot_mut(*slot_id) = SlotEntry::new(start as u16, tuple.len() as u16);
+            free_end = start;
+        }
+
+        // Zero the reclaimed regions so stale bytes never leak into later reads
+        let free_start = BLOCK_HEADER_SIZE + slot_count as usize * SLOT_ENTRY_SIZE;
+        let old_free_start = self.header().free_start as usize;
+        self.as_bytes_mut()[free_start..old_free_start].fill(0);
+        self.as_bytes_mut()[old_free_end as usize..free_end].fill(0);
+
+        let header = self.header_mut();
+        header.slot_count = slot_count;
+        header.free_start = free_start as u32;
+        header.free_end = free_end as u32;
+
+        header.free_space() - old_free_space
+    }
 }
 
 /// Page identifier for index pages (4KB)
@@ -326,4 +571,130 @@ impl PageId {
     pub fn raw(&self) -> u32 {
         self.0
     }
-}
\ No newline at end of file
+
+    /// Rebuild a PageId from its raw u32 value
+    pub fn from_raw(raw: u32) -> Self {
+        PageId(raw)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_delete_compact_and_slot_reuse() {
+        let meta = TupleMeta::new(FROZEN_TXID);
+        let mut block = Block::new();
+        let a = block.append_tuple(&meta, b"aaaa").unwrap();
+        let b = block.append_tuple(&meta, b"bbbbbbbb").unwrap();
+        let c = block.append_tuple(&meta, b"cc").unwrap();
+        let free_before = block.header().free_space();
+
+        assert!(block.delete_tuple(b));
+        assert!(!block.delete_tuple(b));
+        assert_eq!(block.read_tuple(b), None);
+
+        // Deleting alone does not return data space
+        assert_eq!(block.header().free_space(), free_before);
+
+        let reclaimed = block.compact();
+        assert_eq!(reclaimed, TUPLE_HEADER_SIZE + 8);
+        assert_eq!(block.header().free_space(), free_before + reclaimed);
+        assert_eq!(block.read_tuple(a), Some(&b"aaaa"[..]));
+        assert_eq!(block.read_tuple(c), Some(&b"cc"[..]));
+
+        // The tombstoned slot is handed out again without growing the directory
+        let d = block.append_tuple(&meta, b"dd").unwrap();
+        assert_eq!(d, b);
+        assert_eq!(block.header().slot_count, 3);
+        assert_eq!(block.read_tuple(d), Some(&b"dd"[..]));
+    }
+
+    #[test]
+    fn test_insert_defragments_lazily() {
+        let meta = TupleMeta::new(FROZEN_TXID);
+        let mut block = Block::new();
+        let tuple = [7u8; 1000];
+        let mut slots = Vec::new();
+        while let Some(slot_id) = block.append_tuple(&meta, &tuple) {
+            slots.push(slot_id);
+        }
+
+        // Free every other tuple: plenty of space overall, none of it contiguous
+        for slot_id in slots.iter().step_by(2) {
+            assert!(block.delete_tuple(*slot_id));
+        }
+        let big = [9u8; 3000];
+        assert!(block.header().free_space() < big.len());
+
+        let slot_id = block.append_tuple(&meta, &big).expect("insert should compact the block");
+        assert_eq!(block.read_tuple(slot_id), Some(&big[..]));
+        for slot_id in slots.iter().skip(1).step_by(2) {
+            assert_eq!(block.read_tuple(*slot_id), Some(&tuple[..]));
+        }
+        assert_eq!(block.reclaimable_space(), 0);
+    }
+
+    #[test]
+    fn test_put_tuple_redoes_writes() {
+        let meta = TupleMeta::new(FROZEN_TXID);
+        let mut block = Block::new();
+
+        // Slots past the end of the directory are reached through empty ones
+        assert!(block.put_tuple(2, &meta, b"cc"));
+        assert_eq!(block.header().slot_count, 3);
+        assert_eq!(block.read_tuple(0), None);
+        assert_eq!(block.read_tuple(2), Some(&b"cc"[..]));
+        assert!(block.validate().is_ok());
+
+        // Redoing a write that already happened changes nothing
+        let free_space = block.header().free_space();
+        assert!(block.put_tuple(2, &meta, b"cc"));
+        assert_eq!(block.header().free_space(), free_space);
+
+        // A slot holding something else takes the logged tuple instead
+        assert!(block.put_tuple(2, &meta, b"dddd"));
+        assert_eq!(block.read_tuple(2), Some(&b"dddd"[..]));
+        assert_eq!(block.append_tuple(&meta, b"aa"), Some(0));
+
+        let big = [5u8; MAX_TUPLE_SIZE];
+        assert!(!block.put_tuple(1, &meta, &big));
+        assert_eq!(block.read_tuple(0), Some(&b"aa"[..]));
+    }
+
+    #[test]
+    fn test_compact_trims_trailing_tombstones() {
+        let meta = TupleMeta::new(FROZEN_TXID);
+        let mut block = Block::new();
+        let a = block.append_tuple(&meta, b"a").unwrap();
+        let b = block.append_tuple(&meta, b"b").unwrap();
+        let c = block.append_tuple(&meta, b"c").unwrap();
+        block.delete_tuple(b);
+        block.delete_tuple(c);
+
+        block.compact();
+        assert_eq!(block.header().slot_count, 1);
+        assert_eq!(block.header().free_space(), Block::new().header().free_space() - SLOT_ENTRY_SIZE - TUPLE_HEADER_SIZE - 1);
+        assert_eq!(block.read_tuple(a), Some(&b"a"[..]));
+    }
+
+    #[test]
+    fn test_append_reserving_leaves_room() {
+        let meta = TupleMeta::new(FROZEN_TXID);
+        let mut block = Block::new();
+        let tuple = [7u8; 1000];
+        let reserve = BLOCK_SIZE / 5;
+        while block.append_tuple_reserving(&meta, &tuple, reserve).is_some() {}
+        assert!(block.header().free_space() >= reserve);
+        assert!(block.header().free_space() < reserve + SLOT_ENTRY_SIZE + TUPLE_HEADER_SIZE + tuple.len());
+
+        // The reserved room is still there for plain appends
+        assert!(block.append_tuple(&meta, &tuple).is_some());
+
+        // A block without live tuples takes a tuple larger than its fill limit
+        let mut empty = Block::new();
+        let big = [9u8; 2000];
+        assert!(empty.append_tuple_reserving(&meta, &big, BLOCK_SIZE).is_some());
+    }
+}
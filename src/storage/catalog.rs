@@ -1,9 +1,11 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::io::{self, Result};
 use std::sync::atomic::{AtomicU8, Ordering};
 use serde::{Serialize, Deserialize};
 use bincode::{Encode, Decode};
-use crate::types::Schema;
+use crate::storage::base::{TxId, FIRST_TXID};
+use crate::storage::migrate;
+use crate::types::{DataType, Schema};
 
 /// Metadata about a single index file
 #[derive(Debug, Clone, Serialize, Deserialize, Encode, Decode)]
@@ -18,6 +20,8 @@ pub struct IndexFileMetadata {
     pub root_page_segment: u16,
     /// Root page offset
     pub root_page_offset: u16,
+    /// Allocation high-water mark: number of pages handed out so far
+    pub next_page_id: u32,
 }
 
 /// Metadata about a single table file
@@ -35,8 +39,209 @@ pub struct TableFileMetadata {
     pub primary_index: Option<IndexFileMetadata>,
     /// Secondary indexes
     pub secondary_indexes: Vec<IndexFileMetadata>,
+    /// Storage version of the table and index files (see `migrate::STORAGE_VERSION`)
+    pub storage_version: u32,
+    /// Object id exposed through the pg_catalog views
+    pub oid: u32,
+    /// COMMENT ON TABLE text
+    pub comment: Option<String>,
+    /// COMMENT ON COLUMN text, keyed by column name
+    pub column_comments: Vec<(String, String)>,
+    /// Live row count as of the last catalog write (None if never counted)
+    /// Only rewritten once the count drifts by a tenth, so after a crash this
+    /// can lag behind the table by up to that much
+    pub row_count_estimate: Option<u64>,
+    /// Retention policy enforced by the expiry worker (None keeps rows forever)
+    pub ttl: Option<TtlPolicy>,
+    /// CHECK constraints every inserted or updated row must satisfy
+    pub checks: Vec<CheckConstraint>,
+    /// FOREIGN KEY constraints from this table's columns to other tables' keys
+    pub foreign_keys: Vec<ForeignKey>,
+    /// SERIAL columns and the sequences that fill them, keyed by column name
+    pub serial_columns: Vec<(String, String)>,
+    /// Percentage of each block INSERTs fill, leaving the rest for the rows'
+    /// later versions (see `FILLFACTOR_DEFAULT`)
+    pub fillfactor: u8,
 }
 
+/// Fillfactor of a table created without one: blocks are packed full
+pub const FILLFACTOR_DEFAULT: u8 = 100;
+
+/// Lowest fillfactor a table accepts
+pub const FILLFACTOR_MIN: u8 = 10;
+
+/// Row retention policy of a table
+/// Rows whose `column` holds an epoch second older than `seconds` ago are
+/// deleted; rows where it is NULL are kept
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Encode, Decode)]
+pub struct TtlPolicy {
+    /// Integer column holding the row's timestamp in seconds since the epoch
+    pub column: String,
+    /// How long rows are kept
+    pub seconds: u64,
+}
+
+/// Storage options of a table, as given by CREATE TABLE ... WITH (...) or
+/// ALTER TABLE ... SET (...)
+/// An option left out (None) keeps its current value, or its default for a
+/// new table
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TableOptions {
+    /// Retention policy; Some(None) removes it
+    pub ttl: Option<Option<TtlPolicy>>,
+    /// Percentage of each block INSERTs fill, from `FILLFACTOR_MIN` to 100
+    pub fillfactor: Option<i64>,
+}
+
+/// CHECK constraint of a table
+/// A row violates it when the expression evaluates to false; NULL passes
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Encode, Decode)]
+pub struct CheckConstraint {
+    pub name: String,
+    /// Boolean expression over the table's columns, as SQL text
+    pub expr: String,
+}
+
+/// FOREIGN KEY constraint: every non-NULL value of `column` must be the
+/// primary key of a row of `referenced_table`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Encode, Decode)]
+pub struct ForeignKey {
+    pub name: String,
+    pub column: String,
+    pub referenced_table: String,
+    /// Primary key column of the referenced table
+    pub referenced_column: String,
+    /// What deleting a referenced row does to the rows referring to it
+    pub on_delete: ReferentialAction,
+}
+
+/// ON DELETE action of a foreign key
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Encode, Decode)]
+pub enum ReferentialAction {
+    /// Refuse the delete while the row is referenced; the default
+    NoAction,
+    /// As NoAction; the two only differ once checks can be deferred
+    Restrict,
+    /// Delete the referring rows too
+    Cascade,
+}
+
+impl TableFileMetadata {
+    /// Comment on a column, if any
+    pub fn column_comment(&self, column: &str) -> Option<&str> {
+        self.column_comments.iter()
+            .find(|(name, _)| name == column)
+            .map(|(_, comment)| comment.as_str())
+    }
+
+    /// Set or clear the comment on a column
+    pub fn set_column_comment(&mut self, column: &str, comment: Option<String>) {
+        self.column_comments.retain(|(name, _)| name != column);
+        if let Some(comment) = comment {
+            self.column_comments.push((column.to_string(), comment));
+        }
+    }
+}
+
+/// Metadata and durable state of a sequence
+#[derive(Debug, Clone, Serialize, Deserialize, Encode, Decode)]
+pub struct SequenceMetadata {
+    /// Sequence name
+    pub name: String,
+    /// Object id exposed through the pg_catalog views
+    pub oid: u32,
+    /// Amount added by each nextval (negative for descending sequences)
+    pub increment: i64,
+    /// Smallest value the sequence can return
+    pub min_value: i64,
+    /// Largest value the sequence can return
+    pub max_value: i64,
+    /// Value returned by the first nextval
+    pub start_value: i64,
+    /// Wrap around instead of failing once a bound is reached
+    pub cycle: bool,
+    /// Last value handed out, or the next one if `is_called` is false
+    /// Values reserved through the WAL count as handed out, see `sequence`
+    pub last_value: i64,
+    /// Whether `last_value` has been returned by nextval
+    pub is_called: bool,
+}
+
+/// A SQL-bodied scalar function (CREATE FUNCTION ... LANGUAGE SQL)
+#[derive(Debug, Clone, Serialize, Deserialize, Encode, Decode)]
+pub struct FunctionMetadata {
+    /// Function name
+    pub name: String,
+    /// Object id, shared with tables and sequences
+    pub oid: u32,
+    /// Parameter names (empty if only referenced as $n) and types
+    pub args: Vec<(String, DataType)>,
+    /// Declared result type
+    pub return_type: DataType,
+    /// Body expression as SQL text, referring to parameters by name or $n
+    pub body: String,
+}
+
+/// A stored procedure (CREATE PROCEDURE), run by CALL
+#[derive(Debug, Clone, Serialize, Deserialize, Encode, Decode)]
+pub struct ProcedureMetadata {
+    /// Procedure name
+    pub name: String,
+    /// Object id, shared with tables, sequences and functions
+    pub oid: u32,
+    /// Parameter names and types
+    pub params: Vec<(String, DataType)>,
+    /// Body statements as SQL text, referring to parameters by name or $n
+    pub body: Vec<String>,
+}
+
+/// A schema (CREATE SCHEMA) other than `public`, whose tables are stored as
+/// `<schema>.<table>`
+#[derive(Debug, Clone, Serialize, Deserialize, Encode, Decode)]
+pub struct SchemaMetadata {
+    /// Schema name
+    pub name: String,
+    /// Object id, shared with tables, sequences, functions and procedures
+    pub oid: u32,
+}
+
+/// Transaction ID state (see `mvcc::Transactions`)
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Encode, Decode)]
+pub struct TransactionState {
+    /// Next transaction ID to hand out
+    pub next_txid: TxId,
+    /// Transactions that ended without committing, whose tuples are never visible
+    pub aborted: Vec<TxId>,
+}
+
+impl Default for TransactionState {
+    fn default() -> Self {
+        TransactionState { next_txid: FIRST_TXID, aborted: Vec::new() }
+    }
+}
+
+/// Current catalog format version
+/// Version 2: IndexFileMetadata records the page allocation high-water mark
+/// Version 3: TableFileMetadata records the storage version of its files
+/// Version 4: TableFileMetadata records an object id and comments
+/// Version 5: TableFileMetadata records a row count estimate
+/// Version 6: sequences follow the table records
+/// Version 7: SQL functions follow the sequences
+/// Version 8: procedures follow the functions
+/// Version 9: TableFileMetadata records a TTL policy
+/// Version 10: TableFileMetadata records CHECK constraints
+/// Version 11: TableFileMetadata records FOREIGN KEY constraints
+/// Version 12: TableFileMetadata records the sequences of SERIAL columns
+/// Version 13: columns record their type modifier
+/// Version 14: TableFileMetadata records a fillfactor
+/// Version 15: schemas follow the procedures
+/// Version 16: the transaction ID state follows the schemas
+/// Older versions are upgraded on load by `migrate::decode_legacy_table`
+pub const CATALOG_VERSION: u32 = 16;
+
+/// First object id handed out to tables (Postgres' FirstNormalObjectId)
+pub const FIRST_TABLE_OID: u32 = 16384;
+
 /// Global catalog header
 #[derive(Debug, Clone, Serialize, Deserialize, Encode, Decode)]
 pub struct CatalogHeader {
@@ -51,7 +256,7 @@ pub struct CatalogHeader {
 impl CatalogHeader {
     pub fn new() -> Self {
         CatalogHeader {
-            version: 1,
+            version: CATALOG_VERSION,
             num_tables: 0,
             checksum: 0,
         }
@@ -65,6 +270,20 @@ pub struct Catalog {
     active_segment: AtomicU8,
     /// All table metadata indexed by name
     tables: HashMap<String, TableFileMetadata>,
+    /// All sequences indexed by name
+    sequences: HashMap<String, SequenceMetadata>,
+    /// All SQL functions indexed by name
+    functions: HashMap<String, FunctionMetadata>,
+    /// All procedures indexed by name
+    procedures: HashMap<String, ProcedureMetadata>,
+    /// All schemas other than `public` indexed by name
+    schemas: HashMap<String, SchemaMetadata>,
+    /// Transaction IDs handed out, as of the last save
+    transactions: TransactionState,
+    /// Catalog version this catalog was decoded from, if older than the current one
+    upgraded_from: Option<u32>,
+    /// Temporary tables, which belong to one session and are never saved
+    temporary: HashSet<String>,
 }
 
 impl Catalog {
@@ -73,9 +292,22 @@ impl Catalog {
         Catalog {
             active_segment: AtomicU8::new(0),
             tables: HashMap::new(),
+            sequences: HashMap::new(),
+            functions: HashMap::new(),
+            procedures: HashMap::new(),
+            schemas: HashMap::new(),
+            transactions: TransactionState::default(),
+            upgraded_from: None,
+            temporary: HashSet::new(),
         }
     }
 
+    /// Older catalog version this catalog was upgraded from when it was loaded
+    /// The upgrade only becomes permanent once the catalog is saved again
+    pub fn upgraded_from(&self) -> Option<u32> {
+        self.upgraded_from
+    }
+
     /// Get the active metadata segment (0 or 1)
     pub fn active_segment(&self) -> u8 {
         self.active_segment.load(Ordering::SeqCst)
@@ -87,6 +319,11 @@ impl Catalog {
         1 - active
     }
 
+    /// Set the active metadata segment (when loading from disk)
+    pub fn set_active_segment(&self, segment: u8) {
+        self.active_segment.store(segment, Ordering::SeqCst);
+    }
+
     /// Flip to use the other metadata segment
     pub fn flip_segment(&self) {
         let current = self.active_segment.load(Ordering::SeqCst);
@@ -104,29 +341,185 @@ impl Catalog {
         Ok(self.tables.get(name))
     }
 
+    /// Get mutable table metadata by name
+    pub fn get_table_mut(&mut self, name: &str) -> Option<&mut TableFileMetadata> {
+        self.tables.get_mut(name)
+    }
+
+    /// Object id for the next table, sequence, function or procedure
+    pub fn next_oid(&self) -> u32 {
+        self.tables.values()
+            .map(|table_meta| table_meta.oid + 1)
+            .chain(self.sequences.values().map(|sequence_meta| sequence_meta.oid + 1))
+            .chain(self.functions.values().map(|function_meta| function_meta.oid + 1))
+            .chain(self.procedures.values().map(|procedure_meta| procedure_meta.oid + 1))
+            .chain(self.schemas.values().map(|schema_meta| schema_meta.oid + 1))
+            .max()
+            .unwrap_or(FIRST_TABLE_OID)
+            .max(FIRST_TABLE_OID)
+    }
+
     /// Get all tables
     pub fn all_tables(&self) -> Vec<&TableFileMetadata> {
         self.tables.values().collect()
     }
 
+    /// Get all tables, mutably
+    pub fn all_tables_mut(&mut self) -> Vec<&mut TableFileMetadata> {
+        self.tables.values_mut().collect()
+    }
+
     /// Remove a table from the catalog
     pub fn remove_table(&mut self, name: &str) -> Result<Option<TableFileMetadata>> {
+        self.temporary.remove(name);
         Ok(self.tables.remove(name))
     }
 
+    /// Mark a table, before it is added, as temporary: left out when the
+    /// catalog is saved, along with the sequences of its SERIAL columns
+    pub fn set_temporary(&mut self, name: &str) {
+        self.temporary.insert(name.to_string());
+    }
+
+    /// Whether a table is temporary
+    pub fn is_temporary(&self, name: &str) -> bool {
+        self.temporary.contains(name)
+    }
+
+    /// Register a new sequence in the catalog
+    pub fn add_sequence(&mut self, metadata: SequenceMetadata) {
+        self.sequences.insert(metadata.name.clone(), metadata);
+    }
+
+    /// Get sequence metadata by name
+    pub fn get_sequence(&self, name: &str) -> Option<&SequenceMetadata> {
+        self.sequences.get(name)
+    }
+
+    /// Get mutable sequence metadata by name
+    pub fn get_sequence_mut(&mut self, name: &str) -> Option<&mut SequenceMetadata> {
+        self.sequences.get_mut(name)
+    }
+
+    /// Get all sequences
+    pub fn all_sequences(&self) -> Vec<&SequenceMetadata> {
+        self.sequences.values().collect()
+    }
+
+    /// Remove a sequence from the catalog
+    pub fn remove_sequence(&mut self, name: &str) -> Option<SequenceMetadata> {
+        self.sequences.remove(name)
+    }
+
+    /// Register a function, replacing any function of the same name
+    pub fn add_function(&mut self, metadata: FunctionMetadata) -> Option<FunctionMetadata> {
+        self.functions.insert(metadata.name.clone(), metadata)
+    }
+
+    /// Get function metadata by name
+    pub fn get_function(&self, name: &str) -> Option<&FunctionMetadata> {
+        self.functions.get(name)
+    }
+
+    /// Whether any functions are defined
+    pub fn has_functions(&self) -> bool {
+        !self.functions.is_empty()
+    }
+
+    /// Remove a function from the catalog
+    pub fn remove_function(&mut self, name: &str) -> Option<FunctionMetadata> {
+        self.functions.remove(name)
+    }
+
+    /// Register a procedure, replacing any procedure of the same name
+    pub fn add_procedure(&mut self, metadata: ProcedureMetadata) -> Option<ProcedureMetadata> {
+        self.procedures.insert(metadata.name.clone(), metadata)
+    }
+
+    /// Get procedure metadata by name
+    pub fn get_procedure(&self, name: &str) -> Option<&ProcedureMetadata> {
+        self.procedures.get(name)
+    }
+
+    /// Remove a procedure from the catalog
+    pub fn remove_procedure(&mut self, name: &str) -> Option<ProcedureMetadata> {
+        self.procedures.remove(name)
+    }
+
+    /// Register a new schema in the catalog
+    pub fn add_schema(&mut self, metadata: SchemaMetadata) {
+        self.schemas.insert(metadata.name.clone(), metadata);
+    }
+
+    /// Get schema metadata by name
+    pub fn get_schema(&self, name: &str) -> Option<&SchemaMetadata> {
+        self.schemas.get(name)
+    }
+
+    /// Get all schemas other than `public`
+    pub fn all_schemas(&self) -> Vec<&SchemaMetadata> {
+        self.schemas.values().collect()
+    }
+
+    /// Remove a schema from the catalog
+    pub fn remove_schema(&mut self, name: &str) -> Option<SchemaMetadata> {
+        self.schemas.remove(name)
+    }
+
+    /// Transaction ID state as of the last save
+    pub fn transactions(&self) -> &TransactionState {
+        &self.transactions
+    }
+
+    /// Record the transaction ID state, to be saved with the catalog
+    pub fn set_transactions(&mut self, state: TransactionState) {
+        self.transactions = state;
+    }
+
     /// Serialize catalog to bytes for persistence
     pub fn serialize(&self) -> Result<Vec<u8>> {
+        let tables: Vec<&TableFileMetadata> = self.tables.values()
+            .filter(|table_meta| !self.temporary.contains(&table_meta.name))
+            .collect();
+        let temporary_sequences: HashSet<&String> = self.temporary.iter()
+            .filter_map(|name| self.tables.get(name))
+            .flat_map(|table_meta| table_meta.serial_columns.iter().map(|(_, sequence)| sequence))
+            .collect();
         let mut header = CatalogHeader::new();
-        header.num_tables = self.tables.len() as u32;
+        header.num_tables = tables.len() as u32;
 
         // Serialize all table metadata
         let mut table_bytes = Vec::new();
-        for table_meta in self.tables.values() {
+        for table_meta in tables {
             let encoded = bincode::encode_to_vec(table_meta, bincode::config::standard())
                 .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e.to_string()))?;
             table_bytes.extend_from_slice(&encoded);
         }
 
+        // Sequences, functions, procedures, schemas and the transaction ID
+        // state follow the tables and share their checksum
+        let sequences: Vec<&SequenceMetadata> = self.sequences.values()
+            .filter(|sequence_meta| !temporary_sequences.contains(&sequence_meta.name))
+            .collect();
+        let encoded = bincode::encode_to_vec(&sequences, bincode::config::standard())
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e.to_string()))?;
+        table_bytes.extend_from_slice(&encoded);
+        let functions: Vec<&FunctionMetadata> = self.functions.values().collect();
+        let encoded = bincode::encode_to_vec(&functions, bincode::config::standard())
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e.to_string()))?;
+        table_bytes.extend_from_slice(&encoded);
+        let procedures: Vec<&ProcedureMetadata> = self.procedures.values().collect();
+        let encoded = bincode::encode_to_vec(&procedures, bincode::config::standard())
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e.to_string()))?;
+        table_bytes.extend_from_slice(&encoded);
+        let schemas: Vec<&SchemaMetadata> = self.schemas.values().collect();
+        let encoded = bincode::encode_to_vec(&schemas, bincode::config::standard())
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e.to_string()))?;
+        table_bytes.extend_from_slice(&encoded);
+        let encoded = bincode::encode_to_vec(&self.transactions, bincode::config::standard())
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e.to_string()))?;
+        table_bytes.extend_from_slice(&encoded);
+
         // Compute checksum
         header.checksum = compute_checksum(&table_bytes);
 
@@ -144,6 +537,13 @@ impl Catalog {
             bincode::decode_from_slice(data, bincode::config::standard())
                 .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e.to_string()))?;
 
+        if header.version > CATALOG_VERSION {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("Catalog version {} is newer than supported version {}", header.version, CATALOG_VERSION),
+            ));
+        }
+
         // Verify checksum
         let table_bytes = &data[bytes_read..];
         let expected_checksum = compute_checksum(table_bytes);
@@ -158,12 +558,74 @@ impl Catalog {
         let mut catalog = Catalog::new();
         let mut offset = 0;
         for _ in 0..header.num_tables {
-            let (metadata, bytes_read): (TableFileMetadata, usize) =
+            // Catalog v15 and v16 only added sections after the procedures
+            let (metadata, bytes_read): (TableFileMetadata, usize) = if header.version >= 14 {
                 bincode::decode_from_slice(&table_bytes[offset..], bincode::config::standard())
-                    .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e.to_string()))?;
+                    .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e.to_string()))?
+            } else {
+                migrate::decode_legacy_table(header.version, &table_bytes[offset..])?
+            };
             catalog.tables.insert(metadata.name.clone(), metadata);
             offset += bytes_read;
         }
+        if header.version >= 6 {
+            let (sequences, bytes_read): (Vec<SequenceMetadata>, usize) =
+                bincode::decode_from_slice(&table_bytes[offset..], bincode::config::standard())
+                    .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e.to_string()))?;
+            for sequence_meta in sequences {
+                catalog.sequences.insert(sequence_meta.name.clone(), sequence_meta);
+            }
+            offset += bytes_read;
+        }
+        if header.version >= 7 {
+            let (functions, bytes_read): (Vec<FunctionMetadata>, usize) =
+                bincode::decode_from_slice(&table_bytes[offset..], bincode::config::standard())
+                    .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e.to_string()))?;
+            for function_meta in functions {
+                catalog.functions.insert(function_meta.name.clone(), function_meta);
+            }
+            offset += bytes_read;
+        }
+        if header.version >= 8 {
+            let (procedures, bytes_read): (Vec<ProcedureMetadata>, usize) =
+                bincode::decode_from_slice(&table_bytes[offset..], bincode::config::standard())
+                    .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e.to_string()))?;
+            for procedure_meta in procedures {
+                catalog.procedures.insert(procedure_meta.name.clone(), procedure_meta);
+            }
+            offset += bytes_read;
+        }
+        if header.version >= 15 {
+            let (schemas, bytes_read): (Vec<SchemaMetadata>, usize) =
+                bincode::decode_from_slice(&table_bytes[offset..], bincode::config::standard())
+                    .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e.to_string()))?;
+            for schema_meta in schemas {
+                catalog.schemas.insert(schema_meta.name.clone(), schema_meta);
+            }
+            offset += bytes_read;
+        }
+        if header.version >= 16 {
+            let (transactions, _): (TransactionState, usize) =
+                bincode::decode_from_slice(&table_bytes[offset..], bincode::config::standard())
+                    .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e.to_string()))?;
+            catalog.transactions = transactions;
+        }
+        if header.version < CATALOG_VERSION {
+            catalog.upgraded_from = Some(header.version);
+
+            // Tables from before object ids existed get them in name order
+            let mut unnumbered: Vec<String> = catalog.tables.values()
+                .filter(|table_meta| table_meta.oid == 0)
+                .map(|table_meta| table_meta.name.clone())
+                .collect();
+            unnumbered.sort();
+            for name in unnumbered {
+                let oid = catalog.next_oid();
+                if let Some(table_meta) = catalog.tables.get_mut(&name) {
+                    table_meta.oid = oid;
+                }
+            }
+        }
 
         Ok(catalog)
     }
@@ -1,8 +1,9 @@
 use std::io::{self, Result};
 use std::path::{Path, PathBuf};
 use std::sync::Mutex;
-use crate::storage::base::{Block, BlockHeader, SegmentHeader, SEGMENT_SIZE, SEGMENT_HEADER_SIZE, BLOCK_SIZE, BLOCKS_PER_UNCOMPRESSED_SEGMENT};
+use crate::storage::base::{Block, BlockHeader, SegmentHeader, BLOCK_FORMAT_VERSION, SEGMENT_SIZE, SEGMENT_HEADER_SIZE, BLOCK_SIZE, BLOCKS_PER_UNCOMPRESSED_SEGMENT};
 use crate::storage::io::{Disk, alloc_aligned};
+use crate::storage::stats::IoObject;
 use crate::storage::base::PageId;
 use zerocopy::{IntoBytes, FromBytes};
 
@@ -19,14 +20,21 @@ pub struct TableFile {
 
 impl TableFile {
     /// Open or create a table file
+    /// The allocation counter resumes after the last segment present in the file
     pub fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
-        let disk = Disk::open(&path)?;
+        let disk = Disk::open(&path, IoObject::Relation)?;
         let path = path.as_ref().to_path_buf();
 
+        let segment_count = disk.len()?.div_ceil(SEGMENT_SIZE as u64);
+        let next_segment_id = u32::try_from(segment_count).map_err(|_| io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("table file {} has too many segments", path.display()),
+        ))?;
+
         Ok(TableFile {
             disk,
             path,
-            next_segment_id: Mutex::new(0),
+            next_segment_id: Mutex::new(next_segment_id),
         })
     }
 
@@ -80,6 +88,23 @@ impl TableFile {
 
     /// Read block (64KB) - atomic read unit
     pub fn read_block(&self, segment_id: u32, block_id: u8) -> Result<Block> {
+        let block = self.read_block_any_version(segment_id, block_id)?;
+        let version = block.header().version;
+        if version != BLOCK_FORMAT_VERSION {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "unsupported block format version {} (expected {})",
+                    version, BLOCK_FORMAT_VERSION
+                ),
+            ));
+        }
+
+        Ok(block)
+    }
+
+    /// Read block without checking its format version, for upgrades
+    pub fn read_block_any_version(&self, segment_id: u32, block_id: u8) -> Result<Block> {
         if block_id >= BLOCKS_PER_UNCOMPRESSED_SEGMENT as u8 {
             return Err(io::Error::new(
                 io::ErrorKind::InvalidInput,
@@ -88,11 +113,14 @@ impl TableFile {
         }
 
         let offset = Self::block_offset(segment_id, block_id);
-        // Allocate as Vec<u32> to ensure 4-byte alignment for zerocopy
+        // Direct I/O needs a page-aligned buffer; copy into the Vec<u32> afterwards
+        // so the block keeps the 4-byte alignment zerocopy relies on
+        let mut buf = alloc_aligned(BLOCK_SIZE);
+        self.disk.read_at(offset, &mut buf)?;
+
         let num_u32s = BLOCK_SIZE / std::mem::size_of::<u32>();
         let mut data = vec![0u32; num_u32s];
-        let data_bytes = data.as_mut_bytes();
-        self.disk.read_at(offset, data_bytes)?;
+        data.as_mut_bytes().copy_from_slice(&buf);
 
         Ok(Block { data })
     }
@@ -107,7 +135,9 @@ impl TableFile {
         }
 
         let offset = Self::block_offset(segment_id, block_id);
-        self.disk.write_at(offset, block.as_bytes())?;
+        let mut buf = alloc_aligned(BLOCK_SIZE);
+        buf.copy_from_slice(block.as_bytes());
+        self.disk.write_at(offset, &buf)?;
         Ok(())
     }
 
@@ -132,29 +162,35 @@ impl TableFile {
         self.write_segment_header(segment_id, &header)
     }
 
-    /// Allocate a free block in segment
+    /// First block usable for tuple data in a segment
     /// Note: segment 0 block 0 is reserved for table header
+    pub fn first_data_block(segment_id: u32) -> u8 {
+        if segment_id == 0 { 1 } else { 0 }
+    }
+
+    /// Allocate a free block in segment
     pub fn allocate_block(&self, segment_id: u32) -> Result<Option<u8>> {
         let mut header = self.read_segment_header(segment_id)?;
+        let block_id = Self::allocate_block_in(segment_id, &mut header);
 
-        // If this is segment 0, skip block 0 (reserved for table header)
-        let start_block = if segment_id == 0 { 1 } else { 0 };
-
-        // Find first free block
-        for block_id in start_block..BLOCKS_PER_UNCOMPRESSED_SEGMENT as u8 {
-            if header.is_block_free(block_id) {
-                header.mark_block_used(block_id);
-                self.write_segment_header(segment_id, &header)?;
-
-                // Initialize the block on disk with valid header
-                let initialized_block = Self::create_initialized_block();
-                self.write_block(segment_id, block_id, &initialized_block)?;
+        if let Some(block_id) = block_id {
+            self.write_segment_header(segment_id, &header)?;
 
-                return Ok(Some(block_id));
-            }
+            // Initialize the block on disk with valid header
+            let initialized_block = Self::create_initialized_block();
+            self.write_block(segment_id, block_id, &initialized_block)?;
         }
 
-        Ok(None) // Segment full
+        Ok(block_id)
+    }
+
+    /// Allocate a free block against an in-memory segment header
+    /// Neither the header nor the block is written; batched inserts write both
+    /// once they have filled the block
+    pub fn allocate_block_in(segment_id: u32, header: &mut SegmentHeader) -> Option<u8> {
+        let block_id = header.first_free_block(Self::first_data_block(segment_id))?;
+        header.mark_block_used(block_id);
+        Some(block_id)
     }
 
     /// Allocate a new segment
@@ -182,6 +218,36 @@ impl TableFile {
         Ok(())
     }
 
+    /// Size of the file on disk in bytes
+    pub fn size_bytes(&self) -> Result<u64> {
+        self.disk.len()
+    }
+
+    /// Number of segments present in the file, derived from its length
+    pub fn segment_count(&self) -> Result<u32> {
+        Ok(self.disk.len()?.div_ceil(SEGMENT_SIZE as u64) as u32)
+    }
+
+    /// Whether a block lies entirely within the file
+    /// A block can be marked used in its segment header without ever having
+    /// been written if the process stopped in between
+    pub fn contains_block(&self, segment_id: u32, block_id: u8) -> Result<bool> {
+        Ok(Self::block_offset(segment_id, block_id) + BLOCK_SIZE as u64 <= self.disk.len()?)
+    }
+
+    /// Truncate the file so that `block_id` of `segment_id` is its last block
+    /// Pass None to keep only the header of segment 0
+    pub fn truncate_after(&self, last_block: Option<(u32, u8)>) -> Result<()> {
+        let len = match last_block {
+            Some((segment_id, block_id)) => Self::block_offset(segment_id, block_id) + BLOCK_SIZE as u64,
+            None => SEGMENT_HEADER_SIZE as u64,
+        };
+        if len < self.disk.len()? {
+            self.disk.set_len(len)?;
+        }
+        Ok(())
+    }
+
     /// Free a block in segment
     pub fn free_block(&self, segment_id: u32, block_id: u8) -> Result<()> {
         let mut header = self.read_segment_header(segment_id)?;
@@ -190,6 +256,11 @@ impl TableFile {
         Ok(())
     }
 
+    /// Flush the file's writes to stable storage
+    pub fn sync(&self) -> Result<()> {
+        self.disk.sync()
+    }
+
     /// Get file path
     pub fn path(&self) -> &Path {
         &self.path
@@ -197,7 +268,8 @@ impl TableFile {
 }
 
 /// IndexFile manages per-index data storage in .idx files
-/// Uses 4KB page-based storage (no segment wrapping)
+/// Uses 4KB page-based storage; a PageId's raw value is its page number in the
+/// file, so its segment part covers 65536 pages (256MB) each
 pub struct IndexFile {
     disk: Disk,
     path: PathBuf,
@@ -207,14 +279,21 @@ pub struct IndexFile {
 
 impl IndexFile {
     /// Open or create an index file
+    /// The allocation counter resumes after the last page present in the file
     pub fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
-        let disk = Disk::open(&path)?;
+        let disk = Disk::open(&path, IoObject::Index)?;
         let path = path.as_ref().to_path_buf();
 
+        let page_count = disk.len()?.div_ceil(PAGE_SIZE as u64);
+        let next_page_id = u32::try_from(page_count).map_err(|_| io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("index file {} has too many pages", path.display()),
+        ))?;
+
         Ok(IndexFile {
             disk,
             path,
-            next_page_id: Mutex::new(0),
+            next_page_id: Mutex::new(next_page_id),
         })
     }
 
@@ -241,20 +320,40 @@ impl IndexFile {
         }
 
         let offset = Self::page_offset(page_id.raw());
-        self.disk.write_at(offset, data)?;
+        let mut buf = alloc_aligned(PAGE_SIZE);
+        buf.copy_from_slice(data);
+        self.disk.write_at(offset, &buf)?;
         Ok(())
     }
 
     /// Allocate a new page ID
+    /// Pages are numbered linearly through the file; once a segment's 65536
+    /// pages are used the PageId simply carries over into the next segment
     pub fn allocate_page(&self) -> Result<PageId> {
         let page_id = {
             let mut next_id = self.next_page_id.lock().unwrap();
             let id = *next_id;
-            *next_id += 1;
+            *next_id = id.checked_add(1).ok_or_else(|| io::Error::new(
+                io::ErrorKind::StorageFull,
+                "index file has no page ids left",
+            ))?;
             id
         };
 
-        Ok(PageId::new(0, (page_id & 0xFFFF) as u16))
+        Ok(PageId::from_raw(page_id))
+    }
+
+    /// Size of the file on disk in bytes
+    pub fn size_bytes(&self) -> Result<u64> {
+        self.disk.len()
+    }
+
+    /// Discard every page so the index can be rebuilt from scratch
+    pub fn reset(&self) -> Result<()> {
+        let mut next_id = self.next_page_id.lock().unwrap();
+        self.disk.set_len(0)?;
+        *next_id = 0;
+        Ok(())
     }
 
     /// Get the next page ID that would be allocated
@@ -269,6 +368,11 @@ impl IndexFile {
         Ok(())
     }
 
+    /// Flush the file's writes to stable storage
+    pub fn sync(&self) -> Result<()> {
+        self.disk.sync()
+    }
+
     /// Get file path
     pub fn path(&self) -> &Path {
         &self.path
@@ -279,6 +383,7 @@ impl IndexFile {
 mod tests {
     use super::*;
     use std::fs;
+    use crate::storage::base::{TupleMeta, FROZEN_TXID};
 
     #[test]
     fn test_table_file_creation() {
@@ -304,4 +409,125 @@ mod tests {
 
         let _ = fs::remove_file(path);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_batched_block_allocation() {
+        let path = "test_batched_alloc.tbl";
+        let _ = fs::remove_file(path);
+
+        let table_file = TableFile::open(path).expect("Failed to create table file");
+        let seg_id = table_file.allocate_segment().expect("Failed to allocate segment");
+
+        // Allocate two blocks against the in-memory header, then write everything once
+        let mut header = table_file.read_segment_header(seg_id).expect("Failed to read header");
+        let first = TableFile::allocate_block_in(seg_id, &mut header).expect("No free block");
+        let second = TableFile::allocate_block_in(seg_id, &mut header).expect("No free block");
+        assert_eq!((first, second), (1, 2));
+
+        let mut block = Block::new();
+        let slot = block.append_tuple(&TupleMeta::new(FROZEN_TXID), b"hello").expect("Block full");
+        table_file.write_block(seg_id, first, &block).expect("Failed to write block");
+
+        // Nothing is persisted until the header is written back
+        let on_disk = table_file.read_segment_header(seg_id).expect("Failed to read header");
+        assert_eq!(on_disk.last_used_block(1), None);
+
+        table_file.write_segment_header(seg_id, &header).expect("Failed to write header");
+        let on_disk = table_file.read_segment_header(seg_id).expect("Failed to read header");
+        assert_eq!(on_disk.last_used_block(1), Some(second));
+
+        let read_back = table_file.read_block(seg_id, first).expect("Failed to read block");
+        assert_eq!(read_back.read_tuple(slot), Some(&b"hello"[..]));
+        let meta = read_back.tuple_meta(slot).expect("Missing tuple header");
+        assert_eq!(meta.xmin, FROZEN_TXID);
+        assert!(!meta.is_deleted());
+
+        let _ = fs::remove_file(path);
+    }
+
+    #[test]
+    fn test_table_file_reopen_resumes_allocation() {
+        let path = "test_table_reopen.tbl";
+        let _ = fs::remove_file(path);
+
+        {
+            let table_file = TableFile::open(path).expect("Failed to create table file");
+            table_file.allocate_segment().expect("Failed to allocate segment");
+            let seg_id = table_file.allocate_segment().expect("Failed to allocate segment");
+            table_file.allocate_block(seg_id).expect("Failed to allocate block");
+        }
+
+        let table_file = TableFile::open(path).expect("Failed to reopen table file");
+        assert_eq!(table_file.next_segment_id(), 2);
+        assert_eq!(table_file.allocate_segment().unwrap(), 2);
+
+        let _ = fs::remove_file(path);
+    }
+
+    #[test]
+    fn test_truncate_after() {
+        let path = "test_truncate_after.tbl";
+        let _ = fs::remove_file(path);
+
+        let table_file = TableFile::open(path).expect("Failed to create table file");
+        table_file.allocate_segment().expect("Failed to allocate segment");
+        let seg_id = table_file.allocate_segment().expect("Failed to allocate segment");
+        table_file.allocate_block(seg_id).expect("Failed to allocate block");
+        assert_eq!(table_file.segment_count().unwrap(), 2);
+
+        // Cutting back to block 1 of segment 0 drops segment 1 entirely
+        table_file.truncate_after(Some((0, 1))).expect("Failed to truncate");
+        assert_eq!(table_file.segment_count().unwrap(), 1);
+        assert_eq!(fs::metadata(path).unwrap().len(), (SEGMENT_HEADER_SIZE + 2 * BLOCK_SIZE) as u64);
+
+        // Truncating never grows the file
+        table_file.truncate_after(Some((0, 20))).expect("Failed to truncate");
+        assert_eq!(fs::metadata(path).unwrap().len(), (SEGMENT_HEADER_SIZE + 2 * BLOCK_SIZE) as u64);
+
+        let _ = fs::remove_file(path);
+    }
+
+    #[test]
+    fn test_index_pages_span_segments() {
+        let path = "test_index_segments.idx";
+        let _ = fs::remove_file(path);
+
+        let index_file = IndexFile::open(path).expect("Failed to create index file");
+        index_file.set_next_page_id(0xFFFF).unwrap();
+        let last = index_file.allocate_page().unwrap();
+        let first_of_next = index_file.allocate_page().unwrap();
+        assert_eq!((last.segment_id(), last.page_offset()), (0, 0xFFFF));
+        assert_eq!((first_of_next.segment_id(), first_of_next.page_offset()), (1, 0));
+
+        // Pages in the second segment are distinct from the start of the file
+        let mut page = vec![0u8; PAGE_SIZE];
+        page[0] = 42;
+        index_file.write_page(first_of_next, &page).expect("Failed to write page");
+        index_file.write_page(PageId::new(0, 0), &vec![0u8; PAGE_SIZE]).expect("Failed to write page");
+        assert_eq!(index_file.read_page(first_of_next).unwrap()[0], 42);
+
+        let _ = fs::remove_file(path);
+    }
+
+    #[test]
+    fn test_index_file_reopen_resumes_allocation() {
+        let path = "test_index_reopen.idx";
+        let _ = fs::remove_file(path);
+
+        {
+            let index_file = IndexFile::open(path).expect("Failed to create index file");
+            assert_eq!(index_file.next_page_id(), 0);
+            for _ in 0..3 {
+                let page_id = index_file.allocate_page().unwrap();
+                index_file.write_page(page_id, &vec![1u8; PAGE_SIZE]).expect("Failed to write page");
+            }
+        }
+
+        // Reopening must not hand out pages that already hold data
+        let index_file = IndexFile::open(path).expect("Failed to reopen index file");
+        assert_eq!(index_file.next_page_id(), 3);
+        assert_eq!(index_file.allocate_page().unwrap().raw(), 3);
+
+        let _ = fs::remove_file(path);
+    }
+}
@@ -3,6 +3,7 @@ use crate::storage::base::TuplePointer;
 use crate::storage::files::IndexFile;
 use crate::storage::base::PageId;
 use super::page::{IndexEntry, IndexPage, IndexPageHeader, NodeType};
+use super::{IndexCursor, ScanDirection};
 
 /// Represents a split result when a node overflows
 #[derive(Debug)]
@@ -13,6 +14,89 @@ pub struct SplitResult {
     pub right_page: IndexPage,
 }
 
+/// Internal pages visited on the way to a leaf, each with the child position
+/// taken in it, followed by the leaf's id and contents
+type LeafPath = (Vec<(PageId, usize)>, PageId, IndexPage);
+
+/// Lazy range scan over B+ tree leaves, holding one leaf page at a time and
+/// following sibling pointers as it goes
+pub struct BTreeCursor<'a> {
+    disk_mgr: &'a IndexFile,
+    /// Current leaf (None once the scan is exhausted)
+    leaf: Option<IndexPage>,
+    /// Forward: index of the next entry to yield
+    /// Backward: number of entries left to yield in this leaf
+    pos: usize,
+    start_key: u64,
+    end_key: u64,
+    direction: ScanDirection,
+}
+
+impl BTreeCursor<'_> {
+    fn step(&mut self) -> IoResult<Option<(u64, TuplePointer)>> {
+        loop {
+            let Some(leaf) = &self.leaf else { return Ok(None) };
+            let num_keys = leaf.header()?.num_keys as usize;
+
+            let entry = match self.direction {
+                ScanDirection::Forward if self.pos < num_keys => {
+                    self.pos += 1;
+                    leaf.get_entry(self.pos - 1)?
+                }
+                ScanDirection::Backward if self.pos > 0 => {
+                    self.pos -= 1;
+                    leaf.get_entry(self.pos)?
+                }
+                // Leaf exhausted: move to its sibling
+                ScanDirection::Forward => {
+                    self.leaf = match leaf.next_sibling()? {
+                        Some(next_id) => Some(IndexPage { data: self.disk_mgr.read_page(next_id)? }),
+                        None => None,
+                    };
+                    self.pos = 0;
+                    continue;
+                }
+                ScanDirection::Backward => {
+                    self.leaf = match leaf.prev_sibling()? {
+                        Some(prev_id) => Some(IndexPage { data: self.disk_mgr.read_page(prev_id)? }),
+                        None => None,
+                    };
+                    self.pos = match &self.leaf {
+                        Some(page) => page.header()?.num_keys as usize,
+                        None => 0,
+                    };
+                    continue;
+                }
+            };
+
+            let past_end = match self.direction {
+                ScanDirection::Forward => entry.key > self.end_key,
+                ScanDirection::Backward => entry.key < self.start_key,
+            };
+            if past_end {
+                self.leaf = None;
+                return Ok(None);
+            }
+            if entry.key >= self.start_key && entry.key <= self.end_key {
+                return Ok(Some((entry.key, entry.as_tuple_pointer())));
+            }
+        }
+    }
+}
+
+impl Iterator for BTreeCursor<'_> {
+    type Item = IoResult<(u64, TuplePointer)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let result = self.step();
+        if result.is_err() {
+            // Don't keep yielding after an I/O error
+            self.leaf = None;
+        }
+        result.transpose()
+    }
+}
+
 /// B+ Tree with root page tracking
 /// Stores root page ID and loads/saves pages via IndexDiskManager
 #[derive(Debug, Clone)]
@@ -58,7 +142,15 @@ impl BTree {
         }
 
         // Try to insert at position
-        let entry = IndexEntry::new(key, tuple_ptr);
+        Self::insert_entry_at(page, pos, IndexEntry::new(key, tuple_ptr))
+    }
+
+    /// Insert an entry at a position, splitting the page if it is full
+    fn insert_entry_at(
+        page: &mut IndexPage,
+        pos: usize,
+        entry: IndexEntry,
+    ) -> IoResult<Option<SplitResult>> {
         match page.insert_at(pos, entry) {
             Ok(()) => Ok(None),
             Err(e) if e.kind() == io::ErrorKind::Other => {
@@ -69,6 +161,11 @@ impl BTree {
         }
     }
 
+    /// Decode a raw sibling pointer (0 = no sibling)
+    fn sibling(raw: u32) -> Option<PageId> {
+        (raw != 0).then(|| PageId::from_raw(raw))
+    }
+
     /// Split a full page into two pages
     /// Returns the promoted key and the right sibling page
     fn split_page(
@@ -85,6 +182,7 @@ impl BTree {
         // Get page info
         let header = page.header()?;
         let is_leaf = header.is_leaf();
+        let (prev_page_id, next_page_id) = (header.prev_page_id, header.next_page_id);
 
         // Calculate split point (roughly middle)
         let split_point = entries.len() / 2;
@@ -102,6 +200,15 @@ impl BTree {
         let mut right_page = IndexPage::new(node_type);
         right_page.set_entries(node_type, right_entries)?;
 
+        // set_entries resets the header; keep the left page in the leaf chain and
+        // hand its old next pointer to the right page. The caller links the two
+        // halves once the right page has an id
+        if is_leaf {
+            page.set_prev_sibling(Self::sibling(prev_page_id))?;
+            page.set_next_sibling(Self::sibling(next_page_id))?;
+            right_page.set_next_sibling(Self::sibling(next_page_id))?;
+        }
+
         Ok(Some(SplitResult {
             promoted_key,
             right_page,
@@ -148,49 +255,173 @@ impl BTree {
             .collect())
     }
 
-    /// Find the leaf page containing a given key by traversing internal nodes
-    fn find_leaf_page(
+    /// Child to follow in an internal node
+    /// Each internal entry holds the smallest key of its child's subtree when
+    /// it was split off; the first child also receives every key below its
+    /// separator. A key repeated across a split sits on both sides of the
+    /// separator, so the last child whose separator is at most `key` is where
+    /// it goes, and the last one whose separator is below `key` is where its
+    /// run of repeats begins
+    fn child_position(page: &IndexPage, key: u64, first_of_run: bool) -> IoResult<usize> {
+        let header = page.header()?;
+        if header.num_keys == 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "Internal node has no keys",
+            ));
+        }
+        let bound = if first_of_run { page.lower_bound(key)? } else { page.upper_bound(key)? };
+        Ok(bound.saturating_sub(1))
+    }
+
+    /// Descend to the leaf that holds (or would hold) `key`, the last of its
+    /// repeats if it has any
+    fn find_leaf_path(
         &self,
         key: u64,
         disk_mgr: &IndexFile,
-    ) -> IoResult<IndexPage> {
+    ) -> IoResult<LeafPath> {
+        self.descend(key, false, disk_mgr)
+    }
+
+    /// Descend to the leaf holding the first of `key`'s repeats, or where
+    /// the keys from `key` up begin
+    fn find_first_leaf(
+        &self,
+        key: u64,
+        disk_mgr: &IndexFile,
+    ) -> IoResult<(PageId, IndexPage)> {
+        self.descend(key, true, disk_mgr).map(|(_, leaf_id, leaf)| (leaf_id, leaf))
+    }
+
+    fn descend(
+        &self,
+        key: u64,
+        first_of_run: bool,
+        disk_mgr: &IndexFile,
+    ) -> IoResult<LeafPath> {
         let mut current_page_id = match self.root_page_id {
             None => return Err(io::Error::new(io::ErrorKind::NotFound, "No root page")),
             Some(id) => id,
         };
+        let mut path = Vec::new();
 
         loop {
             let page_data = disk_mgr.read_page(current_page_id)?;
             let current_page = IndexPage { data: page_data };
-            let header = current_page.header()?;
 
-            if header.is_leaf() {
-                return Ok(current_page);
+            if current_page.header()?.is_leaf() {
+                return Ok((path, current_page_id, current_page));
+            }
+
+            let pos = Self::child_position(&current_page, key, first_of_run)?;
+            path.push((current_page_id, pos));
+            current_page_id = current_page.get_entry(pos)?.as_child_page_id();
+        }
+    }
+
+    /// Find the leaf page containing a given key by traversing internal nodes
+    fn find_leaf_page(
+        &self,
+        key: u64,
+        disk_mgr: &IndexFile,
+    ) -> IoResult<IndexPage> {
+        self.find_leaf_path(key, disk_mgr).map(|(_, _, leaf)| leaf)
+    }
+
+    /// Insert an entry, pushing splits up to the root
+    /// With `replace`, an entry already under the key is overwritten; otherwise
+    /// the new one is added after it
+    fn insert_into_tree(
+        &mut self,
+        key: u64,
+        pointer: TuplePointer,
+        replace: bool,
+        disk_mgr: &IndexFile,
+    ) -> IoResult<Option<super::IndexSplit>> {
+        let (path, leaf_id, mut leaf) = self.find_leaf_path(key, disk_mgr)?;
+
+        let inserted = if replace {
+            Self::insert_into_page(&mut leaf, key, pointer)?
+        } else {
+            let pos = leaf.upper_bound(key)?;
+            Self::insert_entry_at(&mut leaf, pos, IndexEntry::new(key, pointer))?
+        };
+        let split = match inserted {
+            None => {
+                // No split, just write back
+                disk_mgr.write_page(leaf_id, &leaf.data)?;
+                return Ok(None);
             }
+            Some(split) => split,
+        };
 
-            // Internal node: find child to traverse
-            let (found, pos) = current_page.binary_search(key)?;
-
-            // For internal nodes, position tells us which child to follow
-            // If found, child is at pos; if not found, child is at pos
-            let child_index = if found { pos } else { pos };
-
-            // Get the entry at child_index to find next page
-            let entry = if child_index < header.num_keys as usize {
-                current_page.get_entry(child_index)?
-            } else {
-                // Key is greater than all keys in this node, use rightmost child
-                if header.num_keys == 0 {
-                    return Err(io::Error::new(
-                        io::ErrorKind::InvalidData,
-                        "Internal node has no keys",
-                    ));
+        // Push separators up until a parent absorbs one without splitting
+        let mut pending = self.write_split(leaf_id, leaf, split, disk_mgr)?;
+        for (parent_id, child_pos) in path.into_iter().rev() {
+            let Some((separator, right_id)) = pending else { break };
+            let mut parent = IndexPage { data: disk_mgr.read_page(parent_id)? };
+            let entry = IndexEntry::new_internal(separator, right_id);
+            pending = match Self::insert_entry_at(&mut parent, child_pos + 1, entry)? {
+                None => {
+                    disk_mgr.write_page(parent_id, &parent.data)?;
+                    None
                 }
-                current_page.get_entry((header.num_keys - 1) as usize)?
+                Some(split) => self.write_split(parent_id, parent, split, disk_mgr)?,
             };
+        }
+
+        // Splits are fully absorbed by the tree, including root growth
+        Ok(None)
+    }
+
+    /// Write both halves of a split page and link them into the tree
+    /// Returns the separator and page id the parent must add, or None if the
+    /// split page was the root (which is grown in place so its id never changes)
+    fn write_split(
+        &self,
+        page_id: PageId,
+        mut left: IndexPage,
+        split: SplitResult,
+        disk_mgr: &IndexFile,
+    ) -> IoResult<Option<(u64, PageId)>> {
+        let SplitResult { promoted_key, right_page: mut right } = split;
+        let is_leaf = left.header()?.is_leaf();
+        let right_id = disk_mgr.allocate_page()?;
+
+        if Some(page_id) == self.root_page_id {
+            // Move the left half out of the root, then turn the root into an
+            // internal node over both halves
+            let left_id = disk_mgr.allocate_page()?;
+            if is_leaf {
+                left.set_next_sibling(Some(right_id))?;
+                right.set_prev_sibling(Some(left_id))?;
+            }
+            let left_first_key = left.get_entry(0)?.key;
+            disk_mgr.write_page(left_id, &left.data)?;
+            disk_mgr.write_page(right_id, &right.data)?;
+
+            let mut root = IndexPage::new(NodeType::Internal);
+            root.set_entries(NodeType::Internal, vec![
+                IndexEntry::new_internal(left_first_key, left_id),
+                IndexEntry::new_internal(promoted_key, right_id),
+            ])?;
+            disk_mgr.write_page(page_id, &root.data)?;
+            return Ok(None);
+        }
 
-            current_page_id = entry.as_child_page_id();
+        if is_leaf {
+            left.set_next_sibling(Some(right_id))?;
+            right.set_prev_sibling(Some(page_id))?;
+            if let Some(next_id) = right.next_sibling()? {
+                let mut next = IndexPage { data: disk_mgr.read_page(next_id)? };
+                next.set_prev_sibling(Some(right_id))?;
+                disk_mgr.write_page(next_id, &next.data)?;
+            }
         }
+        disk_mgr.write_page(right_id, &right.data)?;
+        disk_mgr.write_page(page_id, &left.data)?;
+        Ok(Some((promoted_key, right_id)))
     }
 }
 
@@ -199,41 +430,31 @@ impl super::Index for BTree {
         "btree"
     }
 
+    fn capability(&self) -> super::IndexCapability {
+        super::IndexCapability::Ordered
+    }
+
+    fn as_ordered(&self) -> Option<&dyn super::OrderedIndex> {
+        Some(self)
+    }
+
     fn insert(
         &mut self,
         key: u64,
         pointer: TuplePointer,
         disk_mgr: &IndexFile,
     ) -> IoResult<Option<super::IndexSplit>> {
-        // Read root page
-        let root_id = self.root_page_id.unwrap();
-        let page_data = disk_mgr.read_page(root_id)?;
-        let mut root_page = super::page::IndexPage { data: page_data };
+        self.insert_into_tree(key, pointer, true, disk_mgr)
+    }
 
-        // Insert into root
-        match Self::insert_into_page(&mut root_page, key, pointer)? {
-            None => {
-                // No split, just write back
-                disk_mgr.write_page(root_id, &root_page.data)?;
-                Ok(None)
-            }
-            Some(split) => {
-                // Root split - create new root
-                // Write left page (current root becomes left child)
-                disk_mgr.write_page(root_id, &root_page.data)?;
-
-                // Allocate right sibling
-                let right_id = disk_mgr.allocate_page()?;
-                disk_mgr.write_page(right_id, &split.right_page.data)?;
-
-                // For now, signal split to caller
-                // Full B+ tree would create new parent here
-                Ok(Some(super::IndexSplit {
-                    promoted_key: split.promoted_key,
-                    right_sibling_data: split.right_page.data.to_vec(),
-                }))
-            }
-        }
+    /// Add the entry after any others under the same key
+    fn insert_entry(
+        &mut self,
+        key: u64,
+        pointer: TuplePointer,
+        disk_mgr: &IndexFile,
+    ) -> IoResult<Option<super::IndexSplit>> {
+        self.insert_into_tree(key, pointer, false, disk_mgr)
     }
 
     fn search(
@@ -245,6 +466,86 @@ impl super::Index for BTree {
         let leaf_page = self.find_leaf_page(key, disk_mgr)?;
         Self::search_page(&leaf_page, key)
     }
+
+    fn delete(
+        &mut self,
+        key: u64,
+        disk_mgr: &IndexFile,
+    ) -> IoResult<bool> {
+        // Leaves are left underfull rather than merged; separators above them
+        // stay valid bounds, and scans step over empty leaves
+        let (_, leaf_id, mut leaf) = self.find_leaf_path(key, disk_mgr)?;
+        let (found, pos) = leaf.binary_search(key)?;
+        if !found {
+            return Ok(false);
+        }
+        leaf.remove_at(pos)?;
+        disk_mgr.write_page(leaf_id, &leaf.data)?;
+        Ok(true)
+    }
+
+    /// Remove the entry among the key's repeats that points at `pointer`
+    fn delete_entry(
+        &mut self,
+        key: u64,
+        pointer: TuplePointer,
+        disk_mgr: &IndexFile,
+    ) -> IoResult<bool> {
+        let (mut leaf_id, mut leaf) = self.find_first_leaf(key, disk_mgr)?;
+        let mut pos = leaf.lower_bound(key)?;
+        loop {
+            if pos == leaf.header()?.num_keys as usize {
+                let Some(next_id) = leaf.next_sibling()? else { return Ok(false) };
+                leaf_id = next_id;
+                leaf = IndexPage { data: disk_mgr.read_page(next_id)? };
+                pos = 0;
+                continue;
+            }
+            let entry = leaf.get_entry(pos)?;
+            if entry.key > key {
+                return Ok(false);
+            }
+            if entry.key == key && entry.as_tuple_pointer() == pointer {
+                leaf.remove_at(pos)?;
+                disk_mgr.write_page(leaf_id, &leaf.data)?;
+                return Ok(true);
+            }
+            pos += 1;
+        }
+    }
+
+    fn search_all(
+        &self,
+        key: u64,
+        disk_mgr: &IndexFile,
+    ) -> IoResult<Vec<TuplePointer>> {
+        <Self as super::OrderedIndex>::range_scan(self, key, key, disk_mgr)
+            .map(|entries| entries.into_iter().map(|(_, pointer)| pointer).collect())
+    }
+
+    // Callers only hold a `dyn Index`, so route range and full scans to the
+    // ordered implementation instead of the empty defaults
+    fn range_scan(
+        &self,
+        start_key: u64,
+        end_key: u64,
+        disk_mgr: &IndexFile,
+    ) -> IoResult<Vec<(u64, TuplePointer)>> {
+        <Self as super::OrderedIndex>::range_scan(self, start_key, end_key, disk_mgr)
+    }
+
+    fn range_scan_rev(
+        &self,
+        start_key: u64,
+        end_key: u64,
+        disk_mgr: &IndexFile,
+    ) -> IoResult<Vec<(u64, TuplePointer)>> {
+        <Self as super::OrderedIndex>::range_scan_rev(self, start_key, end_key, disk_mgr)
+    }
+
+    fn full_scan(&self, disk_mgr: &IndexFile) -> IoResult<Vec<(u64, TuplePointer)>> {
+        <Self as super::OrderedIndex>::full_scan(self, disk_mgr)
+    }
 }
 
 impl super::OrderedIndex for BTree {
@@ -254,17 +555,52 @@ impl super::OrderedIndex for BTree {
         end_key: u64,
         disk_mgr: &IndexFile,
     ) -> IoResult<Vec<(u64, TuplePointer)>> {
-        // Find the leftmost leaf containing start_key
-        let leaf_page = self.find_leaf_page(start_key, disk_mgr)?;
-        Self::range_scan_page(&leaf_page, start_key, end_key)
+        self.cursor(start_key, end_key, ScanDirection::Forward, disk_mgr)?.collect()
+    }
+
+    fn range_scan_rev(
+        &self,
+        start_key: u64,
+        end_key: u64,
+        disk_mgr: &IndexFile,
+    ) -> IoResult<Vec<(u64, TuplePointer)>> {
+        self.cursor(start_key, end_key, ScanDirection::Backward, disk_mgr)?.collect()
     }
 
     fn full_scan(&self, disk_mgr: &IndexFile) -> IoResult<Vec<(u64, TuplePointer)>> {
-        // Find the leftmost leaf by searching for key 0
-        let leaf_page = self.find_leaf_page(0, disk_mgr)?;
-        Self::scan_page(&leaf_page)
-        // NOTE: Without sibling pointers, we only scan the first leaf found.
-        // Full implementation would need B+ tree sibling links to scan all leaves.
+        <Self as super::OrderedIndex>::range_scan(self, u64::MIN, u64::MAX, disk_mgr)
+    }
+
+    fn cursor<'a>(
+        &self,
+        start_key: u64,
+        end_key: u64,
+        direction: ScanDirection,
+        disk_mgr: &'a IndexFile,
+    ) -> IoResult<IndexCursor<'a>> {
+        // Position on the leaf holding the first key to yield, the first of
+        // its repeats going forward and the last going backward
+        let (leaf, pos) = match direction {
+            ScanDirection::Forward => {
+                let (_, leaf) = self.find_first_leaf(start_key, disk_mgr)?;
+                let pos = leaf.lower_bound(start_key)?;
+                (leaf, pos)
+            }
+            ScanDirection::Backward => {
+                let leaf = self.find_leaf_page(end_key, disk_mgr)?;
+                let pos = leaf.upper_bound(end_key)?;
+                (leaf, pos)
+            }
+        };
+
+        Ok(Box::new(BTreeCursor {
+            disk_mgr,
+            leaf: Some(leaf),
+            pos,
+            start_key,
+            end_key,
+            direction,
+        }))
     }
 }
 
@@ -284,4 +620,134 @@ mod tests {
         let btree = BTree::new(Some(page_id));
         assert_eq!(btree.root_page_id(), Some(page_id));
     }
-}
\ No newline at end of file
+
+    fn test_tree(path: &str) -> (BTree, IndexFile) {
+        let _ = std::fs::remove_file(path);
+        let index_file = IndexFile::open(path).expect("Failed to create index file");
+        let root_id = index_file.allocate_page().expect("Failed to allocate root");
+        index_file.write_page(root_id, &IndexPage::new(NodeType::Leaf).data).expect("Failed to write root");
+        (BTree::new(Some(root_id)), index_file)
+    }
+
+    #[test]
+    fn test_btree_splits_and_scans() {
+        use crate::storage::index::{Index, OrderedIndex};
+
+        let path = "test_btree_splits.idx";
+        let (mut btree, index_file) = test_tree(path);
+        let root_id = btree.root_page_id();
+
+        // Enough keys for several levels of splits, inserted out of order
+        let n = 20_000u64;
+        for i in 0..n {
+            let key = (i * 7919) % n;
+            let ptr = TuplePointer::new(key as u32, 0, 0);
+            Index::insert(&mut btree, key, ptr, &index_file).expect("insert failed");
+        }
+        assert_eq!(btree.root_page_id(), root_id, "root page must stay put");
+
+        for key in [0, 1, 251, 252, 4096, n - 1] {
+            let found = btree.search(key, &index_file).expect("search failed");
+            assert_eq!(found.map(|p| p.segment_id as u64), Some(key));
+        }
+        assert_eq!(btree.search(n, &index_file).unwrap(), None);
+
+        let keys: Vec<u64> = OrderedIndex::full_scan(&btree, &index_file).unwrap()
+            .into_iter().map(|(k, _)| k).collect();
+        assert_eq!(keys, (0..n).collect::<Vec<_>>());
+
+        let forward: Vec<u64> = OrderedIndex::range_scan(&btree, 500, 1500, &index_file).unwrap()
+            .into_iter().map(|(k, _)| k).collect();
+        assert_eq!(forward, (500..=1500).collect::<Vec<_>>());
+
+        let reverse: Vec<u64> = OrderedIndex::range_scan_rev(&btree, 500, 1500, &index_file).unwrap()
+            .into_iter().map(|(k, _)| k).collect();
+        assert_eq!(reverse, (500..=1500).rev().collect::<Vec<_>>());
+
+        // Cursors are lazy: taking a few entries stops without walking the range
+        let ordered = Index::as_ordered(&btree).expect("btree is ordered");
+        let first: Vec<u64> = ordered.cursor(100, u64::MAX, ScanDirection::Forward, &index_file).unwrap()
+            .take(3).map(|r| r.unwrap().0).collect();
+        assert_eq!(first, vec![100, 101, 102]);
+        let last: Vec<u64> = ordered.cursor(0, u64::MAX, ScanDirection::Backward, &index_file).unwrap()
+            .take(2).map(|r| r.unwrap().0).collect();
+        assert_eq!(last, vec![n - 1, n - 2]);
+        assert_eq!(ordered.cursor(n, u64::MAX, ScanDirection::Forward, &index_file).unwrap().count(), 0);
+
+        let _ = std::fs::remove_file(path);
+    }
+
+    #[test]
+    fn test_btree_delete() {
+        use crate::storage::index::{Index, OrderedIndex};
+
+        let path = "test_btree_delete.idx";
+        let (mut btree, index_file) = test_tree(path);
+
+        let n = 2_000u64;
+        for key in 0..n {
+            Index::insert(&mut btree, key, TuplePointer::new(key as u32, 0, 0), &index_file).expect("insert failed");
+        }
+
+        // Empty a whole leaf's worth of keys plus every other key elsewhere
+        for key in (0..300).chain((300..n).step_by(2)) {
+            assert!(btree.delete(key, &index_file).expect("delete failed"));
+        }
+        assert!(!btree.delete(0, &index_file).unwrap(), "key already deleted");
+        assert!(!btree.delete(n, &index_file).unwrap(), "key never inserted");
+
+        assert_eq!(btree.search(10, &index_file).unwrap(), None);
+        assert_eq!(btree.search(301, &index_file).unwrap().map(|p| p.segment_id), Some(301));
+
+        let keys: Vec<u64> = OrderedIndex::full_scan(&btree, &index_file).unwrap()
+            .into_iter().map(|(k, _)| k).collect();
+        assert_eq!(keys, (301..n).step_by(2).collect::<Vec<_>>());
+        let reverse: Vec<u64> = OrderedIndex::range_scan_rev(&btree, 0, 400, &index_file).unwrap()
+            .into_iter().map(|(k, _)| k).collect();
+        assert_eq!(reverse, (301..=399).rev().step_by(2).collect::<Vec<_>>());
+
+        // Deleted keys can be inserted again
+        Index::insert(&mut btree, 10, TuplePointer::new(10, 0, 0), &index_file).unwrap();
+        assert_eq!(btree.search(10, &index_file).unwrap().map(|p| p.segment_id), Some(10));
+
+        let _ = std::fs::remove_file(path);
+    }
+
+    #[test]
+    fn test_btree_repeated_keys() {
+        use crate::storage::index::{Index, OrderedIndex};
+
+        let path = "test_btree_repeated.idx";
+        let (mut btree, index_file) = test_tree(path);
+
+        // Runs of repeats long enough to span several leaves, interleaved so
+        // splits land inside the runs
+        let n = 3_000u32;
+        for i in 0..n {
+            let key = (i % 3) as u64 * 10;
+            Index::insert_entry(&mut btree, key, TuplePointer::new(i, 0, 0), &index_file).expect("insert failed");
+        }
+
+        for key in [0, 10, 20] {
+            let mut found: Vec<u32> = btree.search_all(key, &index_file).unwrap()
+                .into_iter().map(|p| p.segment_id).collect();
+            found.sort_unstable();
+            let expected: Vec<u32> = (0..n).filter(|i| (i % 3) as u64 * 10 == key).collect();
+            assert_eq!(found, expected, "wrong pointers for key {}", key);
+        }
+        assert!(btree.search_all(5, &index_file).unwrap().is_empty());
+        assert_eq!(OrderedIndex::range_scan(&btree, 10, 20, &index_file).unwrap().len(), 2_000);
+        assert_eq!(OrderedIndex::range_scan_rev(&btree, 0, 10, &index_file).unwrap().len(), 2_000);
+
+        // Removing one entry of a run leaves the rest in place
+        for i in (0..n).step_by(3) {
+            assert!(btree.delete_entry(0, TuplePointer::new(i, 0, 0), &index_file).expect("delete failed"));
+        }
+        assert!(!btree.delete_entry(0, TuplePointer::new(0, 0, 0), &index_file).unwrap(), "entry already deleted");
+        assert!(!btree.delete_entry(10, TuplePointer::new(2, 0, 0), &index_file).unwrap(), "pointer is under another key");
+        assert!(btree.search_all(0, &index_file).unwrap().is_empty());
+        assert_eq!(btree.search_all(10, &index_file).unwrap().len(), 1_000);
+
+        let _ = std::fs::remove_file(path);
+    }
+}
@@ -0,0 +1,266 @@
+use std::io::Result as IoResult;
+use std::collections::HashMap;
+use crate::storage::base::TuplePointer;
+use crate::storage::files::IndexFile;
+use crate::storage::base::PageId;
+use super::page::{IndexEntry, IndexPage, NodeType};
+
+/// Inverted (GIN-style) index: each key maps to a posting list of every tuple
+/// that contains it, rather than to a single tuple
+/// A multi-valued column (an array, a JSONB document, a tsvector) is indexed
+/// under each of its elements, so a lookup by element finds all the rows that
+/// contain it
+/// Posting lists are chains of leaf pages, allocated on demand like hash
+/// buckets; pages emptied by deletes stay linked and are refilled by inserts
+#[derive(Debug, Clone)]
+pub struct GinIndex {
+    /// Map from key -> first page of its posting list
+    posting_pages: HashMap<u64, PageId>,
+}
+
+impl GinIndex {
+    pub fn new() -> Self {
+        GinIndex {
+            posting_pages: HashMap::new(),
+        }
+    }
+
+    /// Position of a pointer within a posting page
+    fn find_in_page(page: &IndexPage, pointer: TuplePointer) -> IoResult<Option<usize>> {
+        let header = page.header()?;
+        for i in 0..header.num_keys as usize {
+            if page.get_entry(i)?.as_tuple_pointer() == pointer {
+                return Ok(Some(i));
+            }
+        }
+        Ok(None)
+    }
+}
+
+impl super::Index for GinIndex {
+    fn index_type(&self) -> &str {
+        "gin"
+    }
+
+    fn is_inverted(&self) -> bool {
+        true
+    }
+
+    /// Add a pointer to the key's posting list; a pointer already listed is
+    /// not added twice
+    fn insert(
+        &mut self,
+        key: u64,
+        pointer: TuplePointer,
+        disk_mgr: &IndexFile,
+    ) -> IoResult<Option<super::IndexSplit>> {
+        let first_page_id = match self.posting_pages.get(&key) {
+            Some(&page_id) => page_id,
+            None => {
+                let page_id = disk_mgr.allocate_page()?;
+                disk_mgr.write_page(page_id, &IndexPage::new(NodeType::Leaf).data)?;
+                self.posting_pages.insert(key, page_id);
+                page_id
+            }
+        };
+
+        // Look for the pointer in the whole list first, remembering the first
+        // page with room for it
+        let mut free_page = None;
+        let mut current_id = first_page_id;
+        let last_id = loop {
+            let page = IndexPage { data: disk_mgr.read_page(current_id)? };
+            if Self::find_in_page(&page, pointer)?.is_some() {
+                return Ok(None);
+            }
+            let next_id = page.next_sibling()?;
+            if free_page.is_none() && (page.header()?.num_keys as usize) < IndexPage::max_entries() {
+                free_page = Some((current_id, page));
+            }
+            match next_id {
+                Some(next_id) => current_id = next_id,
+                None => break current_id,
+            }
+        };
+
+        let entry = IndexEntry::new(key, pointer);
+        match free_page {
+            Some((page_id, mut page)) => {
+                let pos = page.header()?.num_keys as usize;
+                page.insert_at(pos, entry)?;
+                disk_mgr.write_page(page_id, &page.data)?;
+            }
+            None => {
+                let overflow_id = disk_mgr.allocate_page()?;
+                let mut overflow_page = IndexPage::new(NodeType::Leaf);
+                overflow_page.insert_at(0, entry)?;
+                disk_mgr.write_page(overflow_id, &overflow_page.data)?;
+
+                let mut last_page = IndexPage { data: disk_mgr.read_page(last_id)? };
+                last_page.set_next_sibling(Some(overflow_id))?;
+                disk_mgr.write_page(last_id, &last_page.data)?;
+            }
+        }
+        Ok(None)
+    }
+
+    /// The first pointer in the key's posting list
+    fn search(
+        &self,
+        key: u64,
+        disk_mgr: &IndexFile,
+    ) -> IoResult<Option<TuplePointer>> {
+        let Some(&first_page_id) = self.posting_pages.get(&key) else {
+            return Ok(None);
+        };
+
+        let mut current_id = first_page_id;
+        loop {
+            let page = IndexPage { data: disk_mgr.read_page(current_id)? };
+            if page.header()?.num_keys > 0 {
+                return Ok(Some(page.get_entry(0)?.as_tuple_pointer()));
+            }
+            match page.next_sibling()? {
+                Some(next_id) => current_id = next_id,
+                None => return Ok(None),
+            }
+        }
+    }
+
+    fn search_all(
+        &self,
+        key: u64,
+        disk_mgr: &IndexFile,
+    ) -> IoResult<Vec<TuplePointer>> {
+        let mut pointers = Vec::new();
+        let Some(&first_page_id) = self.posting_pages.get(&key) else {
+            return Ok(pointers);
+        };
+
+        let mut current_id = first_page_id;
+        loop {
+            let page = IndexPage { data: disk_mgr.read_page(current_id)? };
+            for i in 0..page.header()?.num_keys as usize {
+                pointers.push(page.get_entry(i)?.as_tuple_pointer());
+            }
+            match page.next_sibling()? {
+                Some(next_id) => current_id = next_id,
+                None => return Ok(pointers),
+            }
+        }
+    }
+
+    /// Drop the key's whole posting list
+    fn delete(
+        &mut self,
+        key: u64,
+        disk_mgr: &IndexFile,
+    ) -> IoResult<bool> {
+        let found = !self.search_all(key, disk_mgr)?.is_empty();
+        self.posting_pages.remove(&key);
+        Ok(found)
+    }
+
+    fn delete_entry(
+        &mut self,
+        key: u64,
+        pointer: TuplePointer,
+        disk_mgr: &IndexFile,
+    ) -> IoResult<bool> {
+        let Some(&first_page_id) = self.posting_pages.get(&key) else {
+            return Ok(false);
+        };
+
+        let mut current_id = first_page_id;
+        loop {
+            let mut page = IndexPage { data: disk_mgr.read_page(current_id)? };
+            if let Some(pos) = Self::find_in_page(&page, pointer)? {
+                page.remove_at(pos)?;
+                disk_mgr.write_page(current_id, &page.data)?;
+                return Ok(true);
+            }
+            match page.next_sibling()? {
+                Some(next_id) => current_id = next_id,
+                None => return Ok(false),
+            }
+        }
+    }
+
+    fn full_scan(&self, disk_mgr: &IndexFile) -> IoResult<Vec<(u64, TuplePointer)>> {
+        let mut entries = Vec::new();
+        for &key in self.posting_pages.keys() {
+            entries.extend(self.search_all(key, disk_mgr)?.into_iter().map(|pointer| (key, pointer)));
+        }
+        Ok(entries)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::index::Index;
+
+    fn test_index(path: &str) -> (GinIndex, IndexFile) {
+        let _ = std::fs::remove_file(path);
+        let index_file = IndexFile::open(path).expect("Failed to create index file");
+        (GinIndex::new(), index_file)
+    }
+
+    #[test]
+    fn test_gin_posting_lists() {
+        let path = "test_gin_posting_lists.idx";
+        let (mut gin, index_file) = test_index(path);
+
+        // Enough pointers under one key to spill over several pages
+        let n = 1_000u32;
+        for i in 0..n {
+            gin.insert(7, TuplePointer::new(i, 0, 0), &index_file).expect("insert failed");
+            if i % 2 == 0 {
+                gin.insert(8, TuplePointer::new(i, 0, 0), &index_file).expect("insert failed");
+            }
+        }
+        // Listing a pointer twice keeps one entry
+        gin.insert(7, TuplePointer::new(0, 0, 0), &index_file).unwrap();
+
+        let sevens: Vec<u32> = gin.search_all(7, &index_file).unwrap().iter().map(|p| p.segment_id).collect();
+        assert_eq!(sevens, (0..n).collect::<Vec<_>>());
+        assert_eq!(gin.search_all(8, &index_file).unwrap().len(), n as usize / 2);
+        assert_eq!(gin.search(7, &index_file).unwrap().map(|p| p.segment_id), Some(0));
+        assert!(gin.search_all(9, &index_file).unwrap().is_empty());
+        assert_eq!(gin.full_scan(&index_file).unwrap().len(), n as usize * 3 / 2);
+
+        let _ = std::fs::remove_file(path);
+    }
+
+    #[test]
+    fn test_gin_delete() {
+        let path = "test_gin_delete.idx";
+        let (mut gin, index_file) = test_index(path);
+
+        for i in 0..600u32 {
+            gin.insert(1, TuplePointer::new(i, 0, 0), &index_file).expect("insert failed");
+            gin.insert(2, TuplePointer::new(i, 0, 0), &index_file).expect("insert failed");
+        }
+
+        // Removing one pointer leaves the rest of the posting list
+        for i in (0..600u32).step_by(3) {
+            assert!(gin.delete_entry(1, TuplePointer::new(i, 0, 0), &index_file).expect("delete failed"));
+        }
+        assert!(!gin.delete_entry(1, TuplePointer::new(0, 0, 0), &index_file).unwrap(), "pointer already deleted");
+        assert!(!gin.delete_entry(3, TuplePointer::new(1, 0, 0), &index_file).unwrap(), "key never inserted");
+        assert_eq!(gin.search_all(1, &index_file).unwrap().len(), 400);
+        assert_eq!(gin.search_all(2, &index_file).unwrap().len(), 600);
+
+        // Freed room is reused
+        gin.insert(1, TuplePointer::new(0, 0, 0), &index_file).unwrap();
+        assert_eq!(gin.search_all(1, &index_file).unwrap().len(), 401);
+
+        // Deleting the key drops its whole list
+        assert!(gin.delete(2, &index_file).unwrap());
+        assert!(gin.search_all(2, &index_file).unwrap().is_empty());
+        assert!(!gin.delete(2, &index_file).unwrap());
+
+        let _ = std::fs::remove_file(path);
+    }
+}
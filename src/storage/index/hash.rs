@@ -219,6 +219,36 @@ impl super::Index for HashIndex {
         }
     }
 
+    fn delete(
+        &mut self,
+        key: u64,
+        disk_mgr: &IndexFile,
+    ) -> IoResult<bool> {
+        let bucket_hash = self.hash_key(key);
+        let first_page_id = match self.bucket_pages.get(&bucket_hash) {
+            Some(&page_id) => page_id,
+            None => return Ok(false),
+        };
+
+        // Overflow pages stay linked even if emptied; inserts refill them
+        let mut current_id = first_page_id;
+        loop {
+            let page_data = disk_mgr.read_page(current_id)?;
+            let mut current_page = IndexPage { data: page_data };
+
+            if let Some(pos) = Self::search_in_page(&current_page, key)? {
+                current_page.remove_at(pos)?;
+                disk_mgr.write_page(current_id, &current_page.data)?;
+                return Ok(true);
+            }
+
+            match current_page.next_sibling()? {
+                Some(next_id) => current_id = next_id,
+                None => return Ok(false),
+            }
+        }
+    }
+
     fn search(
         &self,
         key: u64,
@@ -0,0 +1,186 @@
+//! Order-preserving encoding of column values into the u64 keys stored in indexes
+//!
+//! Strings keep only their first `STRING_KEY_PREFIX` bytes, so distinct strings can
+//! share a key. Any tuple found through a string key must have its full value
+//! checked with `key_values_equal` before it is treated as a match.
+
+use crate::types::Value;
+
+/// Number of leading string bytes that participate in an index key
+pub const STRING_KEY_PREFIX: usize = 8;
+
+/// Encode a value as an index key such that `a < b` implies `encode(a) <= encode(b)`
+pub fn encode_key(value: &Value) -> Result<u64, String> {
+    match value {
+        Value::Int(n) => Ok(encode_int(*n)),
+        Value::Float(f) => Ok(encode_float(*f)),
+        Value::String(s) => Ok(encode_string(s)),
+        Value::Bool(b) => Ok(*b as u64),
+        Value::Null => Err("NULL cannot be used as an index key".to_string()),
+        Value::Extension { type_oid, .. } => Err(format!(
+            "Extension type {} cannot be used as an index key",
+            type_oid
+        )),
+    }
+}
+
+/// Flip the sign bit so negative numbers sort below positive ones
+fn encode_int(n: i64) -> u64 {
+    (n as u64) ^ (1 << 63)
+}
+
+/// IEEE 754 total order: set the sign bit of positives, invert every bit of
+/// negatives (so larger magnitudes sort lower)
+fn encode_float(f: f64) -> u64 {
+    // -0.0 == 0.0, so both must map to the same key
+    let bits = if f == 0.0 { 0 } else { f.to_bits() };
+    if bits & (1 << 63) != 0 {
+        !bits
+    } else {
+        bits | (1 << 63)
+    }
+}
+
+/// Big-endian, zero-padded string prefix: byte-wise order matches integer order
+fn encode_string(s: &str) -> u64 {
+    let bytes = s.as_bytes();
+    let len = bytes.len().min(STRING_KEY_PREFIX);
+    let mut prefix = [0u8; STRING_KEY_PREFIX];
+    prefix[..len].copy_from_slice(&bytes[..len]);
+    u64::from_be_bytes(prefix)
+}
+
+/// Columns an index key can span; each keeps 8 bits of its value at the least
+pub const MAX_KEY_COLUMNS: usize = 8;
+
+/// Encode the values of a multi-column index key: the 64 bits are shared out
+/// evenly between the columns, so composite keys sort by the first column,
+/// then the second, ... Each column's part keeps the order of its values but
+/// not all of their detail: integers beyond what the part can hold are
+/// clamped to its ends, floats and strings keep their leading bits, and NULL
+/// takes the lowest part. Values can share a key, so every match found
+/// through a composite key must be checked against the full values
+pub fn encode_composite_key(values: &[Value]) -> Result<u64, String> {
+    pack(values, composite_bits(values.len())?)
+}
+
+/// Range of the composite keys of a `columns`-column index whose leading
+/// columns hold `prefix`
+pub fn composite_prefix_range(prefix: &[Value], columns: usize) -> Result<(u64, u64), String> {
+    let bits = composite_bits(columns)?;
+    if prefix.is_empty() || prefix.len() > columns {
+        return Err(format!("A prefix of {} values does not fit an index on {} columns", prefix.len(), columns));
+    }
+    let rest_bits = (columns - prefix.len()) as u32 * bits;
+    let low = pack(prefix, bits)?.checked_shl(rest_bits).unwrap_or(0);
+    let rest = 1u64.checked_shl(rest_bits).map_or(u64::MAX, |bound| bound - 1);
+    Ok((low, low | rest))
+}
+
+/// Bits of a composite key each of `columns` columns gets
+fn composite_bits(columns: usize) -> Result<u32, String> {
+    match columns {
+        0 => Err("An index key needs at least one column".to_string()),
+        columns if columns > MAX_KEY_COLUMNS => Err(format!("cannot use more than {} columns in an index", MAX_KEY_COLUMNS)),
+        columns => Ok(64 / columns as u32),
+    }
+}
+
+/// Concatenate the `bits`-bit parts of each value
+fn pack(values: &[Value], bits: u32) -> Result<u64, String> {
+    values.iter().try_fold(0u64, |key, value| {
+        Ok(key.checked_shl(bits).unwrap_or(0) | encode_key_part(value, bits)?)
+    })
+}
+
+/// Order-preserving key of a value in `bits` bits
+fn encode_key_part(value: &Value, bits: u32) -> Result<u64, String> {
+    if bits == 64 {
+        return encode_key(value);
+    }
+    match value {
+        Value::Null => Ok(0),
+        // Small integers are the common ones, so their low bits are kept
+        Value::Int(n) => {
+            let max = (1i64 << (bits - 1)) - 1;
+            let clamped = (*n).clamp(-max - 1, max) as u64;
+            Ok((clamped & ((1 << bits) - 1)) ^ (1 << (bits - 1)))
+        }
+        Value::Bool(b) => Ok(*b as u64),
+        value => Ok(encode_key(value)? >> (64 - bits)),
+    }
+}
+
+/// Whether two key values are equal in full (not just in their encoded form)
+pub fn key_values_equal(a: &Value, b: &Value) -> bool {
+    match (a, b) {
+        (Value::Int(a), Value::Int(b)) => a == b,
+        (Value::Float(a), Value::Float(b)) => a == b,
+        (Value::String(a), Value::String(b)) => a == b,
+        (Value::Bool(a), Value::Bool(b)) => a == b,
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn string_key(s: &str) -> u64 {
+        encode_key(&Value::String(s.to_string())).unwrap()
+    }
+
+    #[test]
+    fn test_string_keys_preserve_order() {
+        let mut words = vec!["pear", "apple", "", "app", "banana", "b", "zz", "Zebra"];
+        let keys: Vec<u64> = {
+            words.sort();
+            words.iter().map(|w| string_key(w)).collect()
+        };
+        assert!(keys.windows(2).all(|pair| pair[0] < pair[1]), "keys out of order: {:?}", words);
+    }
+
+    #[test]
+    fn test_signed_keys_preserve_order() {
+        let ints = [i64::MIN, -1000, -1, 0, 1, 42, i64::MAX];
+        let keys: Vec<u64> = ints.iter().map(|n| encode_key(&Value::Int(*n)).unwrap()).collect();
+        assert!(keys.windows(2).all(|pair| pair[0] < pair[1]));
+
+        let floats = [f64::NEG_INFINITY, -1e300, -2.5, -f64::MIN_POSITIVE, 0.0, f64::MIN_POSITIVE, 0.5, 2.5, f64::INFINITY];
+        let keys: Vec<u64> = floats.iter().map(|f| encode_key(&Value::Float(*f)).unwrap()).collect();
+        assert!(keys.windows(2).all(|pair| pair[0] < pair[1]));
+
+        assert_eq!(encode_key(&Value::Float(-0.0)), encode_key(&Value::Float(0.0)));
+    }
+
+    #[test]
+    fn test_string_keys_share_long_prefixes() {
+        assert_eq!(string_key("abcdefgh-one"), string_key("abcdefgh-two"));
+        assert!(!key_values_equal(
+            &Value::String("abcdefgh-one".to_string()),
+            &Value::String("abcdefgh-two".to_string())
+        ));
+        assert!(encode_key(&Value::Null).is_err());
+    }
+
+    #[test]
+    fn test_composite_keys_sort_by_leading_column() {
+        let key = |a: i64, b: &str| encode_composite_key(&[Value::Int(a), Value::String(b.to_string())]).unwrap();
+        assert!(key(-5, "zz") < key(1, "aa"));
+        assert!(key(1, "aa") < key(1, "ab"));
+        assert!(key(1, "ab") < key(2, ""));
+        assert!(key(i64::MIN, "") < key(-1_000_000, "") && key(i64::MAX, "") > key(1_000_000, ""));
+        assert_eq!(key(i64::MAX, "a"), key(i64::MAX - 1, "a"), "large integers are clamped");
+        assert!(encode_composite_key(&[Value::Null, Value::String(String::new())]).unwrap() <= key(i64::MIN, ""));
+
+        // A prefix covers every key starting with it, and a full key only itself
+        let (low, high) = composite_prefix_range(&[Value::Int(1)], 2).unwrap();
+        assert!(low <= key(1, "") && key(1, "zzzz") <= high);
+        assert!(key(0, "zzzz") < low && high < key(2, ""));
+        let full = [Value::Int(1), Value::String("ab".to_string())];
+        assert_eq!(composite_prefix_range(&full, 2).unwrap(), (key(1, "ab"), key(1, "ab")));
+
+        assert!(encode_composite_key(&vec![Value::Int(1); MAX_KEY_COLUMNS + 1]).is_err());
+        assert!(composite_prefix_range(&full, 1).is_err());
+    }
+}
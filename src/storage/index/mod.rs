@@ -5,6 +5,8 @@ use crate::storage::files::IndexFile;
 pub mod page;
 pub mod btree;
 pub mod hash;
+pub mod gin;
+pub mod key;
 
 /// Index capability classification
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -15,6 +17,18 @@ pub enum IndexCapability {
     Ordered,
 }
 
+/// Direction of an ordered index scan
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScanDirection {
+    /// Lowest key first
+    Forward,
+    /// Highest key first
+    Backward,
+}
+
+/// Lazily evaluated (key, pointer) entries of an ordered index scan
+pub type IndexCursor<'a> = Box<dyn Iterator<Item = io::Result<(u64, TuplePointer)>> + 'a>;
+
 /// Represents a split result when a node overflows
 #[derive(Debug, Clone)]
 pub struct IndexSplit {
@@ -35,6 +49,19 @@ pub trait Index: Send + Sync {
         IndexCapability::PointOnly
     }
 
+    /// View this index as an OrderedIndex if it supports ordered operations
+    /// Default: None (ordered indexes return Some(self))
+    fn as_ordered(&self) -> Option<&dyn OrderedIndex> {
+        None
+    }
+
+    /// Whether each key maps to a posting list of pointers rather than one
+    /// pointer, so a multi-valued value is indexed under each of its elements
+    /// Default: false
+    fn is_inverted(&self) -> bool {
+        false
+    }
+
     /// Insert a key-value pair into the index
     /// Returns None if no split occurred, Some(IndexSplit) if the index node split
     fn insert(&mut self, key: u64, pointer: TuplePointer, disk_mgr: &IndexFile) -> io::Result<Option<IndexSplit>>;
@@ -42,12 +69,46 @@ pub trait Index: Send + Sync {
     /// Search for a value by key
     fn search(&self, key: u64, disk_mgr: &IndexFile) -> io::Result<Option<TuplePointer>>;
 
+    /// Remove a key from the index
+    /// Returns whether the key was present
+    fn delete(&mut self, key: u64, disk_mgr: &IndexFile) -> io::Result<bool>;
+
+    /// Add a key-value pair, keeping any values already stored under the key
+    /// Default: `insert`, for indexes that keep one value per key
+    fn insert_entry(&mut self, key: u64, pointer: TuplePointer, disk_mgr: &IndexFile) -> io::Result<Option<IndexSplit>> {
+        self.insert(key, pointer, disk_mgr)
+    }
+
+    /// Search for every value stored under a key
+    /// Default: the single value `search` finds (inverted indexes override)
+    fn search_all(&self, key: u64, disk_mgr: &IndexFile) -> io::Result<Vec<TuplePointer>> {
+        Ok(self.search(key, disk_mgr)?.into_iter().collect())
+    }
+
+    /// Remove one key-value pair, leaving the key's other values in place
+    /// Returns whether the pair was present
+    /// Default: removes the key only while it still points at `pointer`
+    fn delete_entry(&mut self, key: u64, pointer: TuplePointer, disk_mgr: &IndexFile) -> io::Result<bool> {
+        if self.search(key, disk_mgr)? == Some(pointer) {
+            self.delete(key, disk_mgr)
+        } else {
+            Ok(false)
+        }
+    }
+
     /// Range scan - return all entries in [start_key, end_key] inclusive
     /// Default implementation: returns empty vec (override for ordered indexes)
     fn range_scan(&self, _start_key: u64, _end_key: u64, _disk_mgr: &IndexFile) -> io::Result<Vec<(u64, TuplePointer)>> {
         Ok(Vec::new())
     }
 
+    /// Reverse range scan - return all entries in [start_key, end_key] inclusive,
+    /// highest key first
+    /// Default implementation: returns empty vec (override for ordered indexes)
+    fn range_scan_rev(&self, _start_key: u64, _end_key: u64, _disk_mgr: &IndexFile) -> io::Result<Vec<(u64, TuplePointer)>> {
+        Ok(Vec::new())
+    }
+
     /// Full scan - return all entries in the index
     /// Default implementation: returns empty vec (override for ordered indexes)
     fn full_scan(&self, _disk_mgr: &IndexFile) -> io::Result<Vec<(u64, TuplePointer)>> {
@@ -66,8 +127,17 @@ pub trait OrderedIndex: Index {
     /// Range scan - return all entries in [start_key, end_key] inclusive
     fn range_scan(&self, start_key: u64, end_key: u64, disk_mgr: &IndexFile) -> io::Result<Vec<(u64, TuplePointer)>>;
 
+    /// Reverse range scan - return all entries in [start_key, end_key] inclusive,
+    /// highest key first
+    fn range_scan_rev(&self, start_key: u64, end_key: u64, disk_mgr: &IndexFile) -> io::Result<Vec<(u64, TuplePointer)>>;
+
     /// Full scan - return all entries in the index
     fn full_scan(&self, disk_mgr: &IndexFile) -> io::Result<Vec<(u64, TuplePointer)>>;
+
+    /// Cursor over entries in [start_key, end_key] inclusive, in the given direction
+    /// Entries are read one page at a time as the cursor advances, so callers can
+    /// stop early without materializing the whole range
+    fn cursor<'a>(&self, start_key: u64, end_key: u64, direction: ScanDirection, disk_mgr: &'a IndexFile) -> io::Result<IndexCursor<'a>>;
 }
 
 /// Factory trait for creating index instances
@@ -7,6 +7,11 @@ use zerocopy::{IntoBytes, TryFromBytes, Immutable};
 /// Index page size (4KB)
 pub const INDEX_PAGE_SIZE: usize = 4096;
 
+/// Current index page format version
+/// Version 0 pages were written before the field existed and share the layout
+/// of version 1, so they are read as is and stamped when next rewritten
+pub const INDEX_PAGE_FORMAT_VERSION: u16 = 1;
+
 /// B-tree node type
 #[repr(u8)]
 #[derive(IntoBytes, TryFromBytes, Immutable, Debug, Clone, Copy, PartialEq, Eq, Encode, Decode)]
@@ -34,8 +39,10 @@ pub struct IndexPageHeader {
     /// 0 means no sibling (first/last leaf)
     pub prev_page_id: u32,
     pub next_page_id: u32,
+    /// On-disk page format version
+    pub version: u16,
     /// Padding to reach 64 bytes
-    pub _reserved: [u8; 48],
+    pub _reserved: [u8; 46],
 }
 
 impl IndexPageHeader {
@@ -49,7 +56,8 @@ impl IndexPageHeader {
             num_keys: 0,
             prev_page_id: 0,
             next_page_id: 0,
-            _reserved: [0; 48],
+            version: INDEX_PAGE_FORMAT_VERSION,
+            _reserved: [0; 46],
         }
     }
 
@@ -64,6 +72,15 @@ impl IndexPageHeader {
                 "Invalid index page magic",
             ));
         }
+        if self.version > INDEX_PAGE_FORMAT_VERSION {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "unsupported index page format version {} (expected at most {})",
+                    self.version, INDEX_PAGE_FORMAT_VERSION
+                ),
+            ));
+        }
         Ok(())
     }
 }
@@ -114,11 +131,8 @@ impl IndexEntry {
 
     /// Extract child page ID from internal node entry
     pub fn as_child_page_id(&self) -> crate::storage::base::PageId {
-        // Decode u32 stored in segment_id back to (segment, offset)
-        let raw = self.segment_id;
-        let segment = (raw >> 16) as u16;
-        let offset = (raw & 0xFFFF) as u16;
-        crate::storage::base::PageId::new(segment, offset)
+        // The whole u32 PageId is stored in segment_id
+        crate::storage::base::PageId::from_raw(self.segment_id)
     }
 }
 
@@ -164,11 +178,12 @@ impl IndexPage {
         Ok(header)
     }
 
-    /// Write header to page
+    /// Write header to page, stamping it with the current format version
     fn write_header(&mut self, header: &IndexPageHeader) -> io::Result<()> {
+        let header = IndexPageHeader { version: INDEX_PAGE_FORMAT_VERSION, ..*header };
         let header_bytes = unsafe {
             std::slice::from_raw_parts(
-                header as *const IndexPageHeader as *const u8,
+                &header as *const IndexPageHeader as *const u8,
                 std::mem::size_of::<IndexPageHeader>(),
             )
         };
@@ -179,14 +194,19 @@ impl IndexPage {
     /// Get next sibling page ID (0 if no sibling)
     pub fn next_sibling(&self) -> io::Result<Option<crate::storage::base::PageId>> {
         let header = self.header()?;
-        if header.next_page_id == 0 {
-            Ok(None)
-        } else {
-            let raw = header.next_page_id;
-            let segment = (raw >> 16) as u16;
-            let offset = (raw & 0xFFFF) as u16;
-            Ok(Some(crate::storage::base::PageId::new(segment, offset)))
-        }
+        Ok(match header.next_page_id {
+            0 => None,
+            raw => Some(crate::storage::base::PageId::from_raw(raw)),
+        })
+    }
+
+    /// Get prev sibling page ID (0 if no sibling)
+    pub fn prev_sibling(&self) -> io::Result<Option<crate::storage::base::PageId>> {
+        let header = self.header()?;
+        Ok(match header.prev_page_id {
+            0 => None,
+            raw => Some(crate::storage::base::PageId::from_raw(raw)),
+        })
     }
 
     /// Set next sibling page ID
@@ -262,6 +282,33 @@ impl IndexPage {
         Ok((false, left))
     }
 
+    /// Position of the first entry whose key is not below `key`
+    /// Unlike `binary_search`, stays well defined when keys repeat
+    pub fn lower_bound(&self, key: u64) -> io::Result<usize> {
+        self.partition_point(|entry_key| entry_key < key)
+    }
+
+    /// Position of the first entry whose key is above `key`
+    pub fn upper_bound(&self, key: u64) -> io::Result<usize> {
+        self.partition_point(|entry_key| entry_key <= key)
+    }
+
+    /// Number of leading entries whose keys satisfy `pred`, for a predicate
+    /// that holds for a prefix of the (sorted) entries
+    fn partition_point(&self, pred: impl Fn(u64) -> bool) -> io::Result<usize> {
+        let mut left = 0;
+        let mut right = self.header()?.num_keys as usize;
+        while left < right {
+            let mid = (left + right) / 2;
+            if pred(self.get_entry(mid)?.key) {
+                left = mid + 1;
+            } else {
+                right = mid;
+            }
+        }
+        Ok(left)
+    }
+
     /// Insert entry at position (shifts others right)
     /// Returns error if page is full
     pub fn insert_at(&mut self, pos: usize, entry: IndexEntry) -> io::Result<()> {
@@ -310,6 +357,26 @@ impl IndexPage {
         Ok(())
     }
 
+    /// Remove entry at position (shifts others left)
+    pub fn remove_at(&mut self, pos: usize) -> io::Result<IndexEntry> {
+        let entry = self.get_entry(pos)?;
+        let mut header = self.header()?;
+
+        let header_size = std::mem::size_of::<IndexPageHeader>();
+        let entry_size = std::mem::size_of::<IndexEntry>();
+        let count = header.num_keys as usize;
+
+        let start = header_size + (pos + 1) * entry_size;
+        let end = header_size + count * entry_size;
+        self.data.copy_within(start..end, start - entry_size);
+        self.data[end - entry_size..end].fill(0);
+
+        header.num_keys -= 1;
+        self.write_header(&header)?;
+
+        Ok(entry)
+    }
+
     /// Get all entries (for splitting)
     pub fn entries(&self) -> io::Result<Vec<IndexEntry>> {
         let header = self.header()?;
@@ -3,6 +3,7 @@ use std::path::Path;
 
 use crate::storage::base::*;
 use crate::storage::io::{Disk, alloc_aligned};
+use crate::storage::stats::IoObject;
 use zerocopy::{IntoBytes, FromBytes};
 
 /// Page size (4KB) - buffer pool granularity
@@ -19,7 +20,7 @@ pub struct DatabaseFile {
 impl DatabaseFile {
     /// Open or create database file
     pub fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
-        let disk = Disk::open(path)?;
+        let disk = Disk::open(path, IoObject::Relation)?;
         Ok(DatabaseFile { disk })
     }
 
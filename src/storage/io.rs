@@ -4,15 +4,19 @@ use std::os::unix::fs::FileExt;
 use std::os::unix::io::AsRawFd;
 use std::path::Path;
 
+use crate::storage::stats::{self, IoObject};
+
 /// Alignment requirement for Direct I/O (4KB on most systems)
 pub const ALIGNMENT: usize = 4096;
 
 pub struct Disk {
     file: File,
+    /// What the file holds, for the I/O statistics
+    object: IoObject,
 }
 
 impl Disk {
-    pub fn open<P: AsRef<Path>>(path: P) -> Result<Disk> {
+    pub fn open<P: AsRef<Path>>(path: P, object: IoObject) -> Result<Disk> {
         let file = OpenOptions::new()
             .read(true)
             .write(true)
@@ -45,7 +49,7 @@ impl Disk {
             tracing::warn!("Direct I/O not supported on this platform, using buffered I/O");
         }
 
-        Ok(Disk { file })
+        Ok(Disk { file, object })
     }
 
     /// Read aligned data at a specific offset
@@ -73,7 +77,9 @@ impl Disk {
             ));
         }
 
-        self.file.read_at(buf, offset)
+        let read = self.file.read_at(buf, offset)?;
+        stats::record_read(self.object, read);
+        Ok(read)
     }
 
     /// Write aligned data at a specific offset
@@ -101,7 +107,29 @@ impl Disk {
             ));
         }
 
-        self.file.write_at(buf, offset)
+        let written = self.file.write_at(buf, offset)?;
+        stats::record_write(self.object, written);
+        Ok(written)
+    }
+
+    /// Current file length in bytes
+    pub fn len(&self) -> Result<u64> {
+        Ok(self.file.metadata()?.len())
+    }
+
+    /// Flush written data to stable storage
+    pub fn sync(&self) -> Result<()> {
+        self.file.sync_data()?;
+        stats::record_fsync(self.object);
+        Ok(())
+    }
+
+    /// Truncate or extend the file to exactly `len` bytes
+    pub fn set_len(&self, len: u64) -> Result<()> {
+        self.file.set_len(len)?;
+        self.file.sync_all()?;
+        stats::record_fsync(self.object);
+        Ok(())
     }
 }
 
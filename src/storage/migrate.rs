@@ -0,0 +1,598 @@
+//! On-disk format versions and the upgrades between them
+//!
+//! Every persistent structure carries its own format version:
+//! - catalog files: `CatalogHeader::version` (see `catalog::CATALOG_VERSION`)
+//! - table blocks: `BlockHeader::version` (see `base::BLOCK_FORMAT_VERSION`)
+//! - index pages: `IndexPageHeader::version` (see `page::INDEX_PAGE_FORMAT_VERSION`)
+//!
+//! On top of that each catalog table entry records the storage version of its
+//! table and index files as a whole, so that changes which cannot be upgraded
+//! one page at a time (such as a new index key encoding) are rebuilt on open.
+//!
+//! Older catalogs are decoded with the legacy record layouts below and upgraded
+//! in memory; the caller persists the result. Data newer than this build is
+//! rejected rather than guessed at.
+
+use std::io::{self, Result};
+use bincode::Decode;
+use crate::storage::base::{Block, TupleMeta, FROZEN_TXID};
+use crate::storage::catalog::{CheckConstraint, ForeignKey, IndexFileMetadata, TableFileMetadata, TtlPolicy, FILLFACTOR_DEFAULT};
+use crate::types::{Column, DataType, Schema};
+
+/// Current storage version of a table's files
+/// Version 0: written before storage versions existed; blocks may predate tuple
+/// headers and index keys may use the old integer encoding
+/// Version 1: versioned blocks with tuple headers, order-preserving index keys
+pub const STORAGE_VERSION: u32 = 1;
+
+/// Catalog version 1 to 12 column: no type modifier
+#[derive(Decode)]
+struct ColumnV12 {
+    name: String,
+    data_type: DataType,
+    is_primary_key: bool,
+}
+
+/// Catalog version 1 to 12 table schema
+#[derive(Decode)]
+struct SchemaV12 {
+    columns: Vec<ColumnV12>,
+}
+
+impl From<SchemaV12> for Schema {
+    fn from(v12: SchemaV12) -> Self {
+        Schema::new(v12.columns.into_iter()
+            .map(|column| Column {
+                name: column.name,
+                data_type: column.data_type,
+                is_primary_key: column.is_primary_key,
+                typmod: None,
+            })
+            .collect())
+    }
+}
+
+/// Catalog version 1 index record: no allocation high-water mark
+#[derive(Decode)]
+struct IndexFileMetadataV1 {
+    name: String,
+    index_type: String,
+    file_path: String,
+    root_page_segment: u16,
+    root_page_offset: u16,
+}
+
+/// Catalog version 1 table record
+#[derive(Decode)]
+struct TableFileMetadataV1 {
+    name: String,
+    file_path: String,
+    schema: SchemaV12,
+    next_segment_id: u32,
+    primary_index: Option<IndexFileMetadataV1>,
+    secondary_indexes: Vec<IndexFileMetadataV1>,
+}
+
+/// Catalog version 2 table record: no storage version
+#[derive(Decode)]
+struct TableFileMetadataV2 {
+    name: String,
+    file_path: String,
+    schema: SchemaV12,
+    next_segment_id: u32,
+    primary_index: Option<IndexFileMetadata>,
+    secondary_indexes: Vec<IndexFileMetadata>,
+}
+
+impl From<IndexFileMetadataV1> for IndexFileMetadata {
+    fn from(v1: IndexFileMetadataV1) -> Self {
+        IndexFileMetadata {
+            name: v1.name,
+            index_type: v1.index_type,
+            file_path: v1.file_path,
+            root_page_segment: v1.root_page_segment,
+            root_page_offset: v1.root_page_offset,
+            // Recovery derives the real value from the index file size
+            next_page_id: 0,
+        }
+    }
+}
+
+impl From<TableFileMetadataV1> for TableFileMetadataV2 {
+    fn from(v1: TableFileMetadataV1) -> Self {
+        TableFileMetadataV2 {
+            name: v1.name,
+            file_path: v1.file_path,
+            schema: v1.schema,
+            next_segment_id: v1.next_segment_id,
+            primary_index: v1.primary_index.map(Into::into),
+            secondary_indexes: v1.secondary_indexes.into_iter().map(Into::into).collect(),
+        }
+    }
+}
+
+/// Catalog version 3 table record: no object id or comments
+#[derive(Decode)]
+struct TableFileMetadataV3 {
+    name: String,
+    file_path: String,
+    schema: SchemaV12,
+    next_segment_id: u32,
+    primary_index: Option<IndexFileMetadata>,
+    secondary_indexes: Vec<IndexFileMetadata>,
+    storage_version: u32,
+}
+
+impl TableFileMetadataV2 {
+    fn upgrade(self, storage_version: u32) -> TableFileMetadataV3 {
+        TableFileMetadataV3 {
+            name: self.name,
+            file_path: self.file_path,
+            schema: self.schema,
+            next_segment_id: self.next_segment_id,
+            primary_index: self.primary_index,
+            secondary_indexes: self.secondary_indexes,
+            storage_version,
+        }
+    }
+}
+
+/// Catalog version 4 table record: no row count estimate
+#[derive(Decode)]
+struct TableFileMetadataV4 {
+    name: String,
+    file_path: String,
+    schema: SchemaV12,
+    next_segment_id: u32,
+    primary_index: Option<IndexFileMetadata>,
+    secondary_indexes: Vec<IndexFileMetadata>,
+    storage_version: u32,
+    oid: u32,
+    comment: Option<String>,
+    column_comments: Vec<(String, String)>,
+}
+
+impl From<TableFileMetadataV3> for TableFileMetadataV4 {
+    fn from(v3: TableFileMetadataV3) -> Self {
+        TableFileMetadataV4 {
+            name: v3.name,
+            file_path: v3.file_path,
+            schema: v3.schema,
+            next_segment_id: v3.next_segment_id,
+            primary_index: v3.primary_index,
+            secondary_indexes: v3.secondary_indexes,
+            storage_version: v3.storage_version,
+            // Assigned by the catalog once every record is loaded
+            oid: 0,
+            comment: None,
+            column_comments: Vec::new(),
+        }
+    }
+}
+
+/// Catalog version 5 to 8 table record: no TTL policy
+#[derive(Decode)]
+struct TableFileMetadataV5 {
+    name: String,
+    file_path: String,
+    schema: SchemaV12,
+    next_segment_id: u32,
+    primary_index: Option<IndexFileMetadata>,
+    secondary_indexes: Vec<IndexFileMetadata>,
+    storage_version: u32,
+    oid: u32,
+    comment: Option<String>,
+    column_comments: Vec<(String, String)>,
+    row_count_estimate: Option<u64>,
+}
+
+impl From<TableFileMetadataV4> for TableFileMetadataV5 {
+    fn from(v4: TableFileMetadataV4) -> Self {
+        TableFileMetadataV5 {
+            name: v4.name,
+            file_path: v4.file_path,
+            schema: v4.schema,
+            next_segment_id: v4.next_segment_id,
+            primary_index: v4.primary_index,
+            secondary_indexes: v4.secondary_indexes,
+            storage_version: v4.storage_version,
+            oid: v4.oid,
+            comment: v4.comment,
+            column_comments: v4.column_comments,
+            // Recounted by recovery, which scans tables without an estimate
+            row_count_estimate: None,
+        }
+    }
+}
+
+/// Catalog version 9 table record: no CHECK constraints
+#[derive(Decode)]
+struct TableFileMetadataV9 {
+    name: String,
+    file_path: String,
+    schema: SchemaV12,
+    next_segment_id: u32,
+    primary_index: Option<IndexFileMetadata>,
+    secondary_indexes: Vec<IndexFileMetadata>,
+    storage_version: u32,
+    oid: u32,
+    comment: Option<String>,
+    column_comments: Vec<(String, String)>,
+    row_count_estimate: Option<u64>,
+    ttl: Option<TtlPolicy>,
+}
+
+impl From<TableFileMetadataV5> for TableFileMetadataV9 {
+    fn from(v5: TableFileMetadataV5) -> Self {
+        TableFileMetadataV9 {
+            name: v5.name,
+            file_path: v5.file_path,
+            schema: v5.schema,
+            next_segment_id: v5.next_segment_id,
+            primary_index: v5.primary_index,
+            secondary_indexes: v5.secondary_indexes,
+            storage_version: v5.storage_version,
+            oid: v5.oid,
+            comment: v5.comment,
+            column_comments: v5.column_comments,
+            row_count_estimate: v5.row_count_estimate,
+            ttl: None,
+        }
+    }
+}
+
+/// Catalog version 10 table record: no FOREIGN KEY constraints
+#[derive(Decode)]
+struct TableFileMetadataV10 {
+    name: String,
+    file_path: String,
+    schema: SchemaV12,
+    next_segment_id: u32,
+    primary_index: Option<IndexFileMetadata>,
+    secondary_indexes: Vec<IndexFileMetadata>,
+    storage_version: u32,
+    oid: u32,
+    comment: Option<String>,
+    column_comments: Vec<(String, String)>,
+    row_count_estimate: Option<u64>,
+    ttl: Option<TtlPolicy>,
+    checks: Vec<CheckConstraint>,
+}
+
+impl From<TableFileMetadataV9> for TableFileMetadataV10 {
+    fn from(v9: TableFileMetadataV9) -> Self {
+        TableFileMetadataV10 {
+            name: v9.name,
+            file_path: v9.file_path,
+            schema: v9.schema,
+            next_segment_id: v9.next_segment_id,
+            primary_index: v9.primary_index,
+            secondary_indexes: v9.secondary_indexes,
+            storage_version: v9.storage_version,
+            oid: v9.oid,
+            comment: v9.comment,
+            column_comments: v9.column_comments,
+            row_count_estimate: v9.row_count_estimate,
+            ttl: v9.ttl,
+            checks: Vec::new(),
+        }
+    }
+}
+
+/// Catalog version 11 table record: no SERIAL columns
+#[derive(Decode)]
+struct TableFileMetadataV11 {
+    name: String,
+    file_path: String,
+    schema: SchemaV12,
+    next_segment_id: u32,
+    primary_index: Option<IndexFileMetadata>,
+    secondary_indexes: Vec<IndexFileMetadata>,
+    storage_version: u32,
+    oid: u32,
+    comment: Option<String>,
+    column_comments: Vec<(String, String)>,
+    row_count_estimate: Option<u64>,
+    ttl: Option<TtlPolicy>,
+    checks: Vec<CheckConstraint>,
+    foreign_keys: Vec<ForeignKey>,
+}
+
+impl From<TableFileMetadataV10> for TableFileMetadataV11 {
+    fn from(v10: TableFileMetadataV10) -> Self {
+        TableFileMetadataV11 {
+            name: v10.name,
+            file_path: v10.file_path,
+            schema: v10.schema,
+            next_segment_id: v10.next_segment_id,
+            primary_index: v10.primary_index,
+            secondary_indexes: v10.secondary_indexes,
+            storage_version: v10.storage_version,
+            oid: v10.oid,
+            comment: v10.comment,
+            column_comments: v10.column_comments,
+            row_count_estimate: v10.row_count_estimate,
+            ttl: v10.ttl,
+            checks: v10.checks,
+            foreign_keys: Vec::new(),
+        }
+    }
+}
+
+/// Catalog version 12 table record: columns have no type modifiers
+#[derive(Decode)]
+struct TableFileMetadataV12 {
+    name: String,
+    file_path: String,
+    schema: SchemaV12,
+    next_segment_id: u32,
+    primary_index: Option<IndexFileMetadata>,
+    secondary_indexes: Vec<IndexFileMetadata>,
+    storage_version: u32,
+    oid: u32,
+    comment: Option<String>,
+    column_comments: Vec<(String, String)>,
+    row_count_estimate: Option<u64>,
+    ttl: Option<TtlPolicy>,
+    checks: Vec<CheckConstraint>,
+    foreign_keys: Vec<ForeignKey>,
+    serial_columns: Vec<(String, String)>,
+}
+
+impl From<TableFileMetadataV11> for TableFileMetadataV12 {
+    fn from(v11: TableFileMetadataV11) -> Self {
+        TableFileMetadataV12 {
+            name: v11.name,
+            file_path: v11.file_path,
+            schema: v11.schema,
+            next_segment_id: v11.next_segment_id,
+            primary_index: v11.primary_index,
+            secondary_indexes: v11.secondary_indexes,
+            storage_version: v11.storage_version,
+            oid: v11.oid,
+            comment: v11.comment,
+            column_comments: v11.column_comments,
+            row_count_estimate: v11.row_count_estimate,
+            ttl: v11.ttl,
+            checks: v11.checks,
+            foreign_keys: v11.foreign_keys,
+            serial_columns: Vec::new(),
+        }
+    }
+}
+
+/// Catalog version 13 table record: no fillfactor
+#[derive(Decode)]
+struct TableFileMetadataV13 {
+    name: String,
+    file_path: String,
+    schema: Schema,
+    next_segment_id: u32,
+    primary_index: Option<IndexFileMetadata>,
+    secondary_indexes: Vec<IndexFileMetadata>,
+    storage_version: u32,
+    oid: u32,
+    comment: Option<String>,
+    column_comments: Vec<(String, String)>,
+    row_count_estimate: Option<u64>,
+    ttl: Option<TtlPolicy>,
+    checks: Vec<CheckConstraint>,
+    foreign_keys: Vec<ForeignKey>,
+    serial_columns: Vec<(String, String)>,
+}
+
+impl From<TableFileMetadataV12> for TableFileMetadataV13 {
+    fn from(v12: TableFileMetadataV12) -> Self {
+        TableFileMetadataV13 {
+            name: v12.name,
+            file_path: v12.file_path,
+            schema: v12.schema.into(),
+            next_segment_id: v12.next_segment_id,
+            primary_index: v12.primary_index,
+            secondary_indexes: v12.secondary_indexes,
+            storage_version: v12.storage_version,
+            oid: v12.oid,
+            comment: v12.comment,
+            column_comments: v12.column_comments,
+            row_count_estimate: v12.row_count_estimate,
+            ttl: v12.ttl,
+            checks: v12.checks,
+            foreign_keys: v12.foreign_keys,
+            serial_columns: v12.serial_columns,
+        }
+    }
+}
+
+impl From<TableFileMetadataV13> for TableFileMetadata {
+    fn from(v13: TableFileMetadataV13) -> Self {
+        TableFileMetadata {
+            name: v13.name,
+            file_path: v13.file_path,
+            schema: v13.schema,
+            next_segment_id: v13.next_segment_id,
+            primary_index: v13.primary_index,
+            secondary_indexes: v13.secondary_indexes,
+            storage_version: v13.storage_version,
+            oid: v13.oid,
+            comment: v13.comment,
+            column_comments: v13.column_comments,
+            row_count_estimate: v13.row_count_estimate,
+            ttl: v13.ttl,
+            checks: v13.checks,
+            foreign_keys: v13.foreign_keys,
+            serial_columns: v13.serial_columns,
+            fillfactor: FILLFACTOR_DEFAULT,
+        }
+    }
+}
+
+fn decode<T: Decode<()>>(bytes: &[u8]) -> Result<(T, usize)> {
+    bincode::decode_from_slice(bytes, bincode::config::standard())
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e.to_string()))
+}
+
+/// Decode one table record written by an older catalog version and upgrade it
+/// to the current layout
+/// Returns the record and the number of bytes it occupied
+pub fn decode_legacy_table(version: u32, bytes: &[u8]) -> Result<(TableFileMetadata, usize)> {
+    match version {
+        // Catalog v1 predates block and key versioning entirely
+        1 => {
+            let (v1, read): (TableFileMetadataV1, usize) = decode(bytes)?;
+            Ok((TableFileMetadataV13::from(TableFileMetadataV12::from(TableFileMetadataV11::from(TableFileMetadataV10::from(TableFileMetadataV9::from(TableFileMetadataV5::from(TableFileMetadataV4::from(TableFileMetadataV2::from(v1).upgrade(0)))))))).into(), read))
+        }
+        // Catalog v2 was only ever written alongside storage version 1 files
+        2 => {
+            let (v2, read): (TableFileMetadataV2, usize) = decode(bytes)?;
+            Ok((TableFileMetadataV13::from(TableFileMetadataV12::from(TableFileMetadataV11::from(TableFileMetadataV10::from(TableFileMetadataV9::from(TableFileMetadataV5::from(TableFileMetadataV4::from(v2.upgrade(1)))))))).into(), read))
+        }
+        3 => {
+            let (v3, read): (TableFileMetadataV3, usize) = decode(bytes)?;
+            Ok((TableFileMetadataV13::from(TableFileMetadataV12::from(TableFileMetadataV11::from(TableFileMetadataV10::from(TableFileMetadataV9::from(TableFileMetadataV5::from(TableFileMetadataV4::from(v3))))))).into(), read))
+        }
+        4 => {
+            let (v4, read): (TableFileMetadataV4, usize) = decode(bytes)?;
+            Ok((TableFileMetadataV13::from(TableFileMetadataV12::from(TableFileMetadataV11::from(TableFileMetadataV10::from(TableFileMetadataV9::from(TableFileMetadataV5::from(v4)))))).into(), read))
+        }
+        // Catalogs v6 to v8 only added sequences, functions and procedures after
+        // the table records
+        5..=8 => {
+            let (v5, read): (TableFileMetadataV5, usize) = decode(bytes)?;
+            Ok((TableFileMetadataV13::from(TableFileMetadataV12::from(TableFileMetadataV11::from(TableFileMetadataV10::from(TableFileMetadataV9::from(v5))))).into(), read))
+        }
+        9 => {
+            let (v9, read): (TableFileMetadataV9, usize) = decode(bytes)?;
+            Ok((TableFileMetadataV13::from(TableFileMetadataV12::from(TableFileMetadataV11::from(TableFileMetadataV10::from(v9)))).into(), read))
+        }
+        10 => {
+            let (v10, read): (TableFileMetadataV10, usize) = decode(bytes)?;
+            Ok((TableFileMetadataV13::from(TableFileMetadataV12::from(TableFileMetadataV11::from(v10))).into(), read))
+        }
+        11 => {
+            let (v11, read): (TableFileMetadataV11, usize) = decode(bytes)?;
+            Ok((TableFileMetadataV13::from(TableFileMetadataV12::from(v11)).into(), read))
+        }
+        12 => {
+            let (v12, read): (TableFileMetadataV12, usize) = decode(bytes)?;
+            Ok((TableFileMetadataV13::from(v12).into(), read))
+        }
+        13 => {
+            let (v13, read): (TableFileMetadataV13, usize) = decode(bytes)?;
+            Ok((v13.into(), read))
+        }
+        _ => Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("No upgrade path from catalog version {}", version),
+        )),
+    }
+}
+
+/// Tuples of a block in any supported format with their MVCC headers,
+/// converted to the current tuple layout
+/// Version 0 blocks stored bare row bytes in each slot; they are treated as
+/// committed by every transaction
+pub fn block_tuples(block: &Block) -> Result<Vec<(TupleMeta, Vec<u8>)>> {
+    let header = block.header();
+    match header.version {
+        0 => {
+            let bytes = block.as_bytes();
+            let mut tuples = Vec::with_capacity(header.slot_count as usize);
+            for slot_id in 0..header.slot_count {
+                let slot = block.slot(slot_id);
+                if slot.is_empty() {
+                    continue;
+                }
+                let start = slot.offset as usize;
+                let end = start + slot.length as usize;
+                let data = bytes.get(start..end).ok_or_else(|| io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("Version 0 slot {} points outside the block", slot_id),
+                ))?;
+                tuples.push((TupleMeta::new(FROZEN_TXID), data.to_vec()));
+            }
+            Ok(tuples)
+        }
+        1 => Ok((0..header.slot_count)
+            .filter_map(|slot_id| match (block.tuple_meta(slot_id), block.read_tuple(slot_id)) {
+                (Some(meta), Some(data)) => Some((meta, data.to_vec())),
+                _ => None,
+            })
+            .collect()),
+        version => Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("Unsupported block format version {}", version),
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::base::SlotEntry;
+    use bincode::Encode;
+    use zerocopy::IntoBytes;
+
+    /// Encode a record the way catalog version 1 did, for upgrade tests
+    #[derive(Encode)]
+    struct TableFileMetadataV1Out<'a> {
+        name: &'a str,
+        file_path: &'a str,
+        /// Schema columns as (name, data type, is primary key)
+        schema: Vec<(&'a str, DataType, bool)>,
+        next_segment_id: u32,
+        primary_index: Option<(&'a str, &'a str, &'a str, u16, u16)>,
+        secondary_indexes: Vec<(&'a str, &'a str, &'a str, u16, u16)>,
+    }
+
+    #[test]
+    fn test_upgrade_v1_table_record() {
+        let v1 = TableFileMetadataV1Out {
+            name: "t",
+            file_path: "table_t.tbl",
+            schema: vec![("id", DataType::Int, true)],
+            next_segment_id: 1,
+            primary_index: Some(("pk", "btree", "index_t_pk.idx", 0, 0)),
+            secondary_indexes: Vec::new(),
+        };
+        let bytes = bincode::encode_to_vec(&v1, bincode::config::standard()).unwrap();
+
+        let (table, read) = decode_legacy_table(1, &bytes).expect("Failed to upgrade v1 record");
+        assert_eq!(read, bytes.len());
+        assert_eq!(table.name, "t");
+        assert_eq!(table.storage_version, 0);
+        assert_eq!(table.comment, None);
+        assert_eq!(table.schema.columns[0].name, "id");
+        assert!(table.schema.columns[0].is_primary_key);
+        assert_eq!(table.schema.columns[0].typmod, None);
+        let primary_index = table.primary_index.expect("Missing primary index");
+        assert_eq!(primary_index.file_path, "index_t_pk.idx");
+        assert_eq!(primary_index.next_page_id, 0);
+
+        assert!(decode_legacy_table(99, &bytes).is_err());
+    }
+
+    #[test]
+    fn test_version_0_block_tuples() {
+        // Lay out a block the way version 0 did: bare tuples, no tuple headers
+        let mut block = Block::new();
+        block.header_mut().version = 0;
+        let mut free_end = crate::storage::base::BLOCK_SIZE;
+        for (slot_id, data) in [&b"first"[..], b"", b"third"].iter().enumerate() {
+            let entry = if data.is_empty() {
+                SlotEntry { offset: 0, length: 0 }
+            } else {
+                free_end -= data.len();
+                block.as_bytes_mut()[free_end..free_end + data.len()].copy_from_slice(data);
+                SlotEntry { offset: free_end as u16, length: data.len() as u16 }
+            };
+            let offset = 16 + slot_id * 4;
+            block.as_bytes_mut()[offset..offset + 4].copy_from_slice(entry.as_bytes());
+        }
+        block.header_mut().slot_count = 3;
+
+        let tuples = block_tuples(&block).expect("Failed to read version 0 block");
+        let data: Vec<&[u8]> = tuples.iter().map(|(_, data)| data.as_slice()).collect();
+        assert_eq!(data, vec![&b"first"[..], b"third"]);
+        assert!(tuples.iter().all(|(meta, _)| meta.xmin == FROZEN_TXID && !meta.is_deleted()));
+    }
+}
@@ -1,32 +1,110 @@
 mod io;
+pub mod archive;
+pub mod backup;
+pub mod snapshot;
 pub mod base;
 mod internal;
 pub mod index;
 pub mod files;
 pub mod catalog;
+pub mod migrate;
+pub mod mvcc;
+pub mod progress;
+pub mod recovery;
+pub mod sequence;
+pub mod stats;
+pub mod system;
 pub mod wal;
 
 // Re-export for extension types
 pub use self::base::TuplePointer;
 pub use base::PageId;
 
-use std::collections::HashMap;
-use std::sync::{Arc, atomic::{AtomicU8, Ordering}};
-use std::path::PathBuf;
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::sync::{Arc, atomic::{AtomicU8, AtomicU64, Ordering}};
+use std::path::{Path, PathBuf};
 use parking_lot::{Mutex, RwLock};
 use serde::{Serialize, Deserialize};
 use bincode::{Encode, Decode};
-use tracing::debug;
+use tracing::{debug, info, warn};
 use crate::types::{Row, Schema};
 use crate::config::Config;
 #[cfg(feature = "extensions")]
 use crate::extensions::registry::{TypeRegistry, OperatorRegistry, FunctionRegistry};
+#[cfg(feature = "extensions")]
+use crate::extensions::IndexStrategy;
 use self::index::IndexBuilderRegistry;
+use self::progress::ProgressRegistry;
+use self::recovery::{QuarantinedBlock, RecoveryReport};
 use self::files::{TableFile, IndexFile};
-use self::catalog::Catalog;
+use self::catalog::{Catalog, CheckConstraint, ForeignKey, SchemaMetadata, TableOptions, TransactionState, TtlPolicy};
+use self::sequence::{SequenceCache, SequenceOptions, SequenceRecord};
+use self::system::PUBLIC_SCHEMA;
+use self::wal::{CommitRecord, DdlOperation, DdlRecord, TupleChange, TupleRecord, WalEnd, WalEntry, WalEntryType, WalFile};
 
 pub type Result<T> = std::result::Result<T, String>;
 
+/// Path in `data_dir` of a table or index file the catalog recorded as `path`
+fn rebase_path(data_dir: &Path, path: &str) -> String {
+    match Path::new(path).file_name() {
+        Some(file_name) => data_dir.join(file_name).to_string_lossy().to_string(),
+        None => path.to_string(),
+    }
+}
+
+/// File in the data directory naming the active catalog segment
+const CATALOG_MARKER_FILE: &str = "catalog.active";
+
+/// Write-ahead log in the data directory
+const WAL_FILE: &str = "flint.wal";
+
+/// Bytes of WAL past which the next logged change checkpoints first
+const MAX_WAL_SIZE: u64 = 64 * 1024 * 1024;
+
+/// File in the data directory locked by the process using it
+const LOCK_FILE: &str = "flint.lock";
+
+/// Directory inside the data directory holding the files of temporary tables
+const TEMP_TABLE_DIR: &str = "pg_temp";
+
+/// Rows an index build handles between progress updates
+const PROGRESS_BATCH_ROWS: usize = 1024;
+
+/// Appended to a table file's path for the copy VACUUM writes before it
+/// replaces the table file
+const COMPACTED_FILE_SUFFIX: &str = ".compact";
+
+/// Whether a data directory already holds a database, which restoring a
+/// backup or snapshot must not overwrite
+pub fn holds_database(data_dir: &Path) -> bool {
+    std::fs::read_dir(data_dir).is_ok_and(|entries| entries.flatten()
+        .any(|entry| entry.file_name().to_string_lossy().starts_with("catalog")))
+}
+
+/// Take the data directory for this process, failing if another flint
+/// process (a server or an offline command) has it
+/// The lock lasts as long as the returned file is open
+pub fn lock_data_dir(data_dir: &Path) -> Result<std::fs::File> {
+    use std::io::Write;
+    use std::os::unix::io::AsRawFd;
+
+    std::fs::create_dir_all(data_dir)
+        .map_err(|e| format!("Failed to create data directory {}: {}", data_dir.display(), e))?;
+    let path = data_dir.join(LOCK_FILE);
+    let mut file = std::fs::OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(false)
+        .open(&path)
+        .map_err(|e| format!("Failed to open {}: {}", path.display(), e))?;
+    if unsafe { libc::flock(file.as_raw_fd(), libc::LOCK_EX | libc::LOCK_NB) } != 0 {
+        return Err(format!("Data directory {} is in use by another flint process", data_dir.display()));
+    }
+    // The pid is only for whoever looks at the file
+    let _ = file.set_len(0).and_then(|_| writeln!(file, "{}", std::process::id()));
+    Ok(file)
+}
+
 /// Compute simple checksum for metadata validation
 fn compute_checksum(data: &[u8]) -> u64 {
     data.iter().fold(0u64, |acc, &byte| {
@@ -34,6 +112,13 @@ fn compute_checksum(data: &[u8]) -> u64 {
     })
 }
 
+/// Decode the payload of a WAL entry
+fn decode_wal_record<T: Decode<()>>(entry: &WalEntry) -> Result<T> {
+    bincode::decode_from_slice(&entry.payload, bincode::config::standard())
+        .map(|(record, _)| record)
+        .map_err(|e| format!("Failed to decode WAL {:?} record: {}", WalEntryType::from_u8(entry.header.entry_type), e))
+}
+
 /// Catalog header for metadata persistence
 #[derive(Debug, Clone, Serialize, Deserialize, Encode, Decode)]
 pub struct CatalogHeader {
@@ -84,13 +169,50 @@ impl MetadataManager {
 /// Index metadata - wraps the actual index instance
 pub struct IndexMetadata {
     pub name: String,
-    pub column: String,
+    /// Indexed columns, in key order
+    pub columns: Vec<String>,
     pub index_type: String,
+    /// Keys are stored in descending order (CREATE INDEX ... (col DESC))
+    pub descending: bool,
     /// The actual index instance (manages its own root page ID)
     /// TODO replace Mutex with lockless pattern
     pub index: Arc<Mutex<Box<dyn index::Index>>>,
 }
 
+impl IndexMetadata {
+    /// Index key for a column value, honouring the index's sort direction
+    /// Descending indexes store inverted keys, so a forward scan of the tree
+    /// yields values from highest to lowest
+    pub fn key_for(&self, value: &crate::types::Value) -> Result<u64> {
+        let key = index::key::encode_key(value)?;
+        Ok(if self.descending { !key } else { key })
+    }
+
+    /// Index key for the values of a multi-column index's columns
+    pub fn composite_key_for(&self, values: &[crate::types::Value]) -> Result<u64> {
+        let key = index::key::encode_composite_key(values)?;
+        Ok(if self.descending { !key } else { key })
+    }
+
+    /// Range of the keys whose leading columns hold `prefix`, both inclusive
+    pub fn prefix_range(&self, prefix: &[crate::types::Value]) -> Result<(u64, u64)> {
+        if let ([value], 1) = (prefix, self.columns.len()) {
+            let key = self.key_for(value)?;
+            return Ok((key, key));
+        }
+        let (low, high) = index::key::composite_prefix_range(prefix, self.columns.len())?;
+        Ok(if self.descending { (!high, !low) } else { (low, high) })
+    }
+}
+
+/// A secondary index built by `Database::build_secondary_index`, not yet
+/// added to its table
+pub struct BuiltIndex {
+    table_name: String,
+    index_meta: IndexMetadata,
+    index_file: IndexFile,
+}
+
 /// Runtime table metadata (file paths + schema)
 pub struct TableMetadata {
     pub name: String,
@@ -100,6 +222,99 @@ pub struct TableMetadata {
     pub primary_index: Option<IndexMetadata>,
     /// Secondary indexes
     pub secondary_indexes: Vec<IndexMetadata>,
+    /// Live row count, maintained incrementally; the catalog holds a lagging copy
+    pub row_count_estimate: AtomicU64,
+}
+
+/// Outcome of a table compaction
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CompactionStats {
+    /// Tuples carried over into the compacted layout
+    pub live_tuples: usize,
+    /// Data blocks in use before compaction
+    pub blocks_before: usize,
+    /// Data blocks in use after compaction
+    pub blocks_after: usize,
+}
+
+/// Tuples placed in a table's blocks but not yet written (see `place_tuples`)
+struct PlacedTuples {
+    segment_id: base::SegmentId,
+    /// The segment header, if blocks were allocated for the tuples
+    header: Option<base::SegmentHeader>,
+    /// Every block a tuple went into, with the tuples added
+    blocks: Vec<(base::BlockId, base::Block)>,
+    tuple_ptrs: Vec<TuplePointer>,
+}
+
+/// Blocks of one table brought up to date by WAL replay, held until every
+/// record is applied and then written back together
+struct RedoBlocks {
+    table_file: Arc<TableFile>,
+    headers: HashMap<base::SegmentId, base::SegmentHeader>,
+    blocks: HashMap<(base::SegmentId, base::BlockId), base::Block>,
+}
+
+impl RedoBlocks {
+    fn new(table_file: Arc<TableFile>) -> Self {
+        RedoBlocks { table_file, headers: HashMap::new(), blocks: HashMap::new() }
+    }
+
+    /// The block a tuple pointer refers to, read on first use
+    /// A block that was allocated but never written starts out empty when
+    /// `create` is set, as a logged insert is redone into it, and is None
+    /// otherwise, since there is nothing in it to delete
+    fn block_mut(&mut self, tuple_ptr: &TuplePointer, create: bool) -> Result<Option<&mut base::Block>> {
+        let (segment_id, block_id) = (tuple_ptr.segment_id, tuple_ptr.block_id);
+        if block_id < TableFile::first_data_block(segment_id) || block_id as usize >= base::BLOCKS_PER_UNCOMPRESSED_SEGMENT {
+            return Err(format!("block {}/{} is outside the segment", segment_id, block_id));
+        }
+        if !self.blocks.contains_key(&(segment_id, block_id)) {
+            let header = match self.headers.entry(segment_id) {
+                std::collections::hash_map::Entry::Occupied(entry) => entry.into_mut(),
+                std::collections::hash_map::Entry::Vacant(entry) => entry.insert(
+                    self.table_file.read_segment_header(segment_id)
+                        .map_err(|e| format!("segment {} header is unreadable: {}", segment_id, e))?,
+                ),
+            };
+            let written = self.table_file.contains_block(segment_id, block_id)
+                .map_err(|e| format!("Failed to read table file size: {}", e))?;
+            let block = if written && !header.is_block_free(block_id) {
+                let block = self.table_file.read_block(segment_id, block_id)
+                    .map_err(|e| format!("block {}/{} is unreadable: {}", segment_id, block_id, e))?;
+                if recovery::is_unwritten(&block) {
+                    None
+                } else {
+                    block.validate()
+                        .map_err(|e| format!("block {}/{} is corrupt: {}", segment_id, block_id, e))?;
+                    Some(block)
+                }
+            } else {
+                None
+            };
+            let block = match block {
+                Some(block) => block,
+                None if create => base::Block::new(),
+                None => return Ok(None),
+            };
+            header.mark_block_used(block_id);
+            self.blocks.insert((segment_id, block_id), block);
+        }
+        Ok(self.blocks.get_mut(&(segment_id, block_id)))
+    }
+
+    /// Write the blocks, then the segment headers that mark them used
+    fn write(self) -> Result<()> {
+        for ((segment_id, block_id), block) in &self.blocks {
+            self.table_file.write_block(*segment_id, *block_id, block)
+                .map_err(|e| format!("Failed to write block: {}", e))?;
+        }
+        for (segment_id, header) in &self.headers {
+            self.table_file.write_segment_header(*segment_id, header)
+                .map_err(|e| format!("Failed to write segment header: {}", e))?;
+        }
+        Ok(())
+    }
 }
 
 /// Database with per-table file storage
@@ -113,8 +328,25 @@ pub struct Database {
     tables: HashMap<String, Arc<RwLock<TableMetadata>>>,
     /// Global catalog metadata
     catalog: Catalog,
+    /// Directory holding the catalog, table and index files
+    data_dir: PathBuf,
+    /// In-memory sequence state, ahead of the catalog by the values handed out
+    /// from the current WAL-logged batch
+    sequences: HashMap<String, SequenceCache>,
+    /// Write-ahead log of changes since the catalog was last saved
+    /// None if it could not be opened, which leaves tables and sequences
+    /// read-only
+    wal: Option<WalFile>,
+    /// Whether WAL appends are flushed to stable storage before they return
+    synchronous_commit: bool,
+    /// Whether startup goes on when WAL records can't be replayed
+    allow_lossy_recovery: bool,
+    /// Transaction IDs handed out, deciding which tuples readers see
+    transactions: mvcc::Transactions,
     /// Index builder registry (always available with builtins)
     pub index_builder_registry: Arc<IndexBuilderRegistry>,
+    /// Long-running operations (index builds, compactions) and how far along they are
+    progress: Arc<ProgressRegistry>,
     /// Extension registries for types, operators, functions
     #[cfg(feature = "extensions")]
     pub type_registry: Arc<TypeRegistry>,
@@ -125,9 +357,30 @@ pub struct Database {
 }
 
 impl Database {
-    pub fn new(config: &Config) -> Self {
+    /// Open the database in the configured data directory, recovering it as
+    /// `load_catalog_from_disk` describes
+    pub fn new(config: &Config) -> Result<Self> {
         // Initialize global catalog from catalog.db or create new
         let catalog = Catalog::new();
+        let data_dir = config.data_dir.clone();
+        if let Err(e) = std::fs::create_dir_all(&data_dir) {
+            warn!(error = %e, path = %data_dir.display(), "failed to create data directory");
+        }
+        // Temporary tables end with their sessions, so any files of theirs
+        // still here were left by a crash
+        match std::fs::remove_dir_all(data_dir.join(TEMP_TABLE_DIR)) {
+            Ok(()) => info!("removed temporary table files left by an earlier run"),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => warn!(error = %e, "failed to remove leftover temporary table files"),
+        }
+
+        let wal = match WalFile::open(data_dir.join(WAL_FILE)) {
+            Ok(wal) => Some(wal),
+            Err(e) => {
+                warn!(error = %e, "failed to open WAL");
+                None
+            }
+        };
 
         // Always initialize index_builder_registry with builtins
         let mut index_builder_registry = IndexBuilderRegistry::new();
@@ -162,10 +415,17 @@ impl Database {
                 index_files: HashMap::new(),
                 tables: HashMap::new(),
                 catalog,
+                data_dir: data_dir.clone(),
+                sequences: HashMap::new(),
+                wal,
+                synchronous_commit: config.synchronous_commit,
+                allow_lossy_recovery: config.allow_lossy_recovery,
+                transactions: mvcc::Transactions::new(&TransactionState::default()),
                 type_registry: Arc::new(type_registry),
                 operator_registry: Arc::new(operator_registry),
                 function_registry: Arc::new(function_registry),
                 index_builder_registry: Arc::new(index_builder_registry),
+                progress: Arc::default(),
             }
         };
 
@@ -175,146 +435,684 @@ impl Database {
             index_files: HashMap::new(),
             tables: HashMap::new(),
             catalog,
+            data_dir: data_dir.clone(),
+            sequences: HashMap::new(),
+            wal,
+            synchronous_commit: config.synchronous_commit,
+            allow_lossy_recovery: config.allow_lossy_recovery,
+            transactions: mvcc::Transactions::new(&TransactionState::default()),
             index_builder_registry: Arc::new(index_builder_registry),
+            progress: Arc::default(),
         };
 
-        // Try to load catalog from disk (TODO: implement catalog.db disk I/O)
-        let _ = db.load_catalog_from_disk();
+        db.load_catalog_from_disk()?.log();
+        Ok(db)
+    }
+
+    /// Path of a file inside the data directory
+    fn data_path(&self, file_name: &str) -> PathBuf {
+        self.data_dir.join(file_name)
+    }
+
+    /// Directory for the files of a table: the data directory, or for a
+    /// temporary table the directory cleared at startup
+    fn table_dir(&self, table_name: &str) -> PathBuf {
+        if self.catalog.is_temporary(table_name) {
+            self.data_dir.join(TEMP_TABLE_DIR)
+        } else {
+            self.data_dir.clone()
+        }
+    }
+
+    /// Path in the table's directory for a new file, skipping any path the
+    /// catalog still refers to
+    /// Renamed tables keep their original files, so `table_<name>.tbl` may belong
+    /// to a table that used to be called `<name>`
+    fn unused_data_path(&self, table_name: &str, stem: &str, extension: &str) -> PathBuf {
+        let tables = self.catalog.all_tables();
+        let in_use = |path: &PathBuf| tables.iter().any(|table_meta| {
+            Path::new(&table_meta.file_path) == path
+                || table_meta.primary_index.iter()
+                    .chain(&table_meta.secondary_indexes)
+                    .any(|index_meta| Path::new(&index_meta.file_path) == path)
+        });
 
-        db
+        let dir = self.table_dir(table_name);
+        let mut path = dir.join(format!("{}.{}", stem, extension));
+        let mut suffix = 1;
+        while in_use(&path) {
+            path = dir.join(format!("{}_{}.{}", stem, suffix, extension));
+            suffix += 1;
+        }
+        path
     }
 
-    /// Load catalog from catalog.db file
-    fn load_catalog_from_disk(&mut self) -> Result<()> {
+    /// Load the catalog from the segment named by the active marker
+    /// Falls back to the other segment if the active one cannot be read
+    /// Every table is then checked and repaired as described in `recovery`
+    fn load_catalog_from_disk(&mut self) -> Result<RecoveryReport> {
         use std::fs;
 
-        // Try to load from active segment (0 or 1)
-        let active_seg = self.catalog.active_segment();
-        let catalog_path = format!("catalog_{}.db", active_seg);
-
-        let data = match fs::read(&catalog_path) {
-            Ok(data) => data,
-            Err(_) => return Ok(()), // No catalog file yet, start with empty
-        };
-
-        match catalog::Catalog::deserialize(&data) {
-            Ok(loaded_catalog) => {
-                // Replace catalog with loaded version
-                self.catalog = loaded_catalog;
-
-                // Reconstruct runtime metadata and indexes from catalog
-                for table_meta in self.catalog.all_tables() {
-                    // Open table file
-                    let table_path = PathBuf::from(&table_meta.file_path);
-                    let table_file = TableFile::open(&table_path)
-                        .map_err(|e| format!("Failed to open table file during recovery: {}", e))?;
-
-                    // Reconstruct primary index if it exists
-                    let primary_index = if let Some(index_meta) = &table_meta.primary_index {
-                        let index_path = PathBuf::from(&index_meta.file_path);
-                        let index_file = IndexFile::open(&index_path)
-                            .map_err(|e| format!("Failed to open index file during recovery: {}", e))?;
-
-                        let root_page_id = base::PageId::new(index_meta.root_page_segment, index_meta.root_page_offset);
-                        let index = self.index_builder_registry.create_index(&index_meta.index_type, Some(root_page_id))
-                            .ok_or_else(|| format!("Failed to create {} index during recovery", index_meta.index_type))?;
-
-                        self.index_files.insert(table_meta.name.clone(), Arc::new(index_file));
-
-                        // Get primary key column from schema
-                        let pk_column = table_meta.schema.columns.iter()
-                            .find(|col| col.is_primary_key)
-                            .or_else(|| table_meta.schema.columns.first())
-                            .map(|col| col.name.clone())
-                            .unwrap_or_else(|| "".to_string());
-
-                        Some(IndexMetadata {
-                            name: index_meta.name.clone(),
-                            column: pk_column,
-                            index_type: index_meta.index_type.clone(),
-                            index: Arc::new(Mutex::new(index)),
-                        })
-                    } else {
-                        None
-                    };
-
-                    // Build runtime table metadata
-                    let runtime_meta = TableMetadata {
-                        name: table_meta.name.clone(),
-                        file_path: table_path,
-                        schema: table_meta.schema.clone(),
-                        primary_index,
-                        secondary_indexes: Vec::new(),
-                    };
-
-                    self.tables.insert(table_meta.name.clone(), Arc::new(RwLock::new(runtime_meta)));
-                    self.table_files.insert(table_meta.name.clone(), Arc::new(table_file));
-                }
+        let mut report = RecoveryReport::default();
 
-                Ok(())
+        let marker_path = self.data_path(CATALOG_MARKER_FILE);
+        let active_seg = match fs::read_to_string(&marker_path) {
+            Ok(marker) => match marker.trim() {
+                "0" => 0,
+                "1" => 1,
+                other => return Err(format!("Invalid catalog marker {:?}", other)),
+            },
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                // Catalogs written before the marker existed: the most recently
+                // written segment is the active one
+                let modified = |segment: u8| fs::metadata(self.data_path(&format!("catalog_{}.db", segment)))
+                    .and_then(|meta| meta.modified())
+                    .ok();
+                match (modified(0), modified(1)) {
+                    // No catalog has ever been committed, start with empty
+                    (None, None) => return Ok(report),
+                    (Some(_), None) => 0,
+                    (None, Some(_)) => 1,
+                    (Some(first), Some(second)) => if second > first { 1 } else { 0 },
+                }
             }
-            Err(_) => {
+            Err(e) => return Err(format!("Failed to read catalog marker: {}", e)),
+        };
+
+        let read_segment = |segment: u8| -> Result<Catalog> {
+            let path = self.data_path(&format!("catalog_{}.db", segment));
+            let data = fs::read(&path)
+                .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+            Catalog::deserialize(&data)
+                .map_err(|e| format!("Failed to deserialize {}: {}", path.display(), e))
+        };
+
+        let (loaded_catalog, loaded_seg) = match read_segment(active_seg) {
+            Ok(loaded_catalog) => (loaded_catalog, active_seg),
+            Err(active_err) => {
                 // Corruption in active segment, try inactive
-                let inactive_seg = self.catalog.inactive_segment();
-                let fallback_path = format!("catalog_{}.db", inactive_seg);
+                warn!(error = %active_err, "active catalog segment unreadable, trying the other one");
+                let fallback_seg = 1 - active_seg;
+                let fallback_catalog = read_segment(fallback_seg)
+                    .map_err(|e| format!("Failed to load catalog from either segment: {}; {}", active_err, e))?;
+                report.catalog_fallback = true;
+                (fallback_catalog, fallback_seg)
+            }
+        };
+
+        // Replace catalog with loaded version
+        self.catalog = loaded_catalog;
+        self.catalog.set_active_segment(loaded_seg);
+        report.catalog_segment = Some(loaded_seg as u32);
+
+        // The catalog records where files were when they were made; they are
+        // wherever the data directory is now, as it may have been moved,
+        // restored elsewhere or opened from another working directory
+        for table_meta in self.catalog.all_tables_mut() {
+            table_meta.file_path = rebase_path(&self.data_dir, &table_meta.file_path);
+            for index_meta in table_meta.primary_index.iter_mut().chain(&mut table_meta.secondary_indexes) {
+                index_meta.file_path = rebase_path(&self.data_dir, &index_meta.file_path);
+            }
+        }
+
+        // Reconstruct runtime metadata and indexes from catalog
+        for table_meta in self.catalog.all_tables() {
+            // Open table file
+            let table_path = PathBuf::from(&table_meta.file_path);
+            if !table_path.exists() {
+                report.problems.push(format!("table {}: file {} is missing, opened empty", table_meta.name, table_path.display()));
+            }
+            let table_file = TableFile::open(&table_path)
+                .map_err(|e| format!("Failed to open table file during recovery: {}", e))?;
+            // The catalog may know of segments that were allocated but not yet
+            // written out, so never resume below its high-water mark
+            if table_meta.next_segment_id > table_file.next_segment_id() {
+                table_file.set_next_segment_id(table_meta.next_segment_id)
+                    .map_err(|e| format!("Failed to restore segment allocation state: {}", e))?;
+            }
+
+            // Reconstruct primary index if it exists
+            let primary_index = if let Some(index_meta) = &table_meta.primary_index {
+                let index_path = PathBuf::from(&index_meta.file_path);
+                let index_file = IndexFile::open(&index_path)
+                    .map_err(|e| format!("Failed to open index file during recovery: {}", e))?;
+                // Pages may have been allocated without ever being written, so
+                // the catalog's high-water mark can be ahead of the file size
+                if index_meta.next_page_id > index_file.next_page_id() {
+                    index_file.set_next_page_id(index_meta.next_page_id)
+                        .map_err(|e| format!("Failed to restore index allocation state: {}", e))?;
+                }
+
+                let root_page_id = base::PageId::new(index_meta.root_page_segment, index_meta.root_page_offset);
+                let index = self.index_builder_registry.create_index(&index_meta.index_type, Some(root_page_id))
+                    .ok_or_else(|| format!("Failed to create {} index during recovery", index_meta.index_type))?;
+
+                self.index_files.insert(table_meta.name.clone(), Arc::new(index_file));
+
+                // Get primary key column from schema
+                let pk_column = table_meta.schema.columns.iter()
+                    .find(|col| col.is_primary_key)
+                    .or_else(|| table_meta.schema.columns.first())
+                    .map(|col| col.name.clone())
+                    .unwrap_or_else(|| "".to_string());
+
+                Some(IndexMetadata {
+                    name: index_meta.name.clone(),
+                    columns: vec![pk_column],
+                    index_type: index_meta.index_type.clone(),
+                    descending: false,
+                    index: Arc::new(Mutex::new(index)),
+                })
+            } else {
+                None
+            };
+
+            // Build runtime table metadata
+            let runtime_meta = TableMetadata {
+                name: table_meta.name.clone(),
+                file_path: table_path,
+                schema: table_meta.schema.clone(),
+                primary_index,
+                secondary_indexes: Vec::new(),
+                row_count_estimate: AtomicU64::new(table_meta.row_count_estimate.unwrap_or(0)),
+            };
+
+            self.tables.insert(table_meta.name.clone(), Arc::new(RwLock::new(runtime_meta)));
+            self.table_files.insert(table_meta.name.clone(), Arc::new(table_file));
+        }
+
+        // Redo logged changes before anything reads the tables: the self-test
+        // then rebuilds indexes and row counts from what the log restored
+        self.transactions = mvcc::Transactions::new(self.catalog.transactions());
+        for sequence_meta in self.catalog.all_sequences() {
+            self.sequences.insert(sequence_meta.name.clone(), SequenceCache::new(sequence_meta));
+        }
+        self.replay_wal(&mut report)?;
+
+        // Bring tables written by older builds up to the current storage version
+        let outdated: Vec<String> = self.catalog.all_tables().into_iter()
+            .filter(|table_meta| table_meta.storage_version < migrate::STORAGE_VERSION)
+            .map(|table_meta| table_meta.name.clone())
+            .collect();
+        for table_name in &outdated {
+            self.upgrade_table_storage(table_name)?;
+        }
+
+        let table_names: Vec<String> = self.tables.keys().cloned().collect();
+        for table_name in &table_names {
+            self.check_table(table_name, &mut report)?;
+        }
+        report.tables_recovered = table_names.len();
+
+        // Tables from catalogs that predate row count estimates were counted
+        // by the self-test; the catalog gets their count once
+        let uncounted: Vec<String> = self.catalog.all_tables().into_iter()
+            .filter(|table_meta| table_meta.row_count_estimate.is_none())
+            .map(|table_meta| table_meta.name.clone())
+            .collect();
+        for table_name in &uncounted {
+            let row_count = self.approx_row_count(table_name)?;
+            if let Some(table_meta) = self.catalog.get_table_mut(table_name) {
+                table_meta.row_count_estimate = Some(row_count);
+            }
+        }
+
+        if let Some(version) = self.catalog.upgraded_from() {
+            info!(from = version, to = catalog::CATALOG_VERSION, "upgraded catalog");
+        }
+        if self.catalog.upgraded_from().is_some() || !outdated.is_empty() || !uncounted.is_empty()
+            || report.wal_records_replayed > 0 || !report.wal_end.is_clean() || !report.indexes_rebuilt.is_empty()
+        {
+            self.save_catalog_to_disk()?;
+        }
+
+        Ok(report)
+    }
+
+    /// Check a table's blocks and primary index, repairing what can be repaired:
+    /// blocks marked used but never written are released, blocks that can't be
+    /// read back as rows are quarantined, and a primary index that doesn't match
+    /// the rows left is rebuilt from them
+    /// The row count estimate is set from the rows found, so it is exact again
+    fn check_table(&mut self, table_name: &str, report: &mut RecoveryReport) -> Result<()> {
+        let table_file = self.table_files.get(table_name)
+            .ok_or_else(|| format!("Table not found: {}", table_name))?
+            .clone();
+        let segment_count = table_file.segment_count()
+            .map_err(|e| format!("Failed to read table file size: {}", e))?;
+
+        let mut quarantined = false;
+        for segment_id in 0..segment_count {
+            let mut header = match table_file.read_segment_header(segment_id) {
+                Ok(header) => header,
+                Err(e) => {
+                    report.problems.push(format!("table {}: segment {} header is unreadable: {}", table_name, segment_id, e));
+                    continue;
+                }
+            };
+            let mut header_changed = false;
+            for block_id in TableFile::first_data_block(segment_id)..base::BLOCKS_PER_UNCOMPRESSED_SEGMENT as u8 {
+                if header.is_block_free(block_id) {
+                    continue;
+                }
+                let written = table_file.contains_block(segment_id, block_id)
+                    .map_err(|e| format!("Failed to read table file size: {}", e))?;
+                let block = if written {
+                    match table_file.read_block_any_version(segment_id, block_id) {
+                        Ok(block) => Some(block),
+                        Err(e) => {
+                            report.problems.push(format!("table {}: block {}/{} is unreadable: {}", table_name, segment_id, block_id, e));
+                            continue;
+                        }
+                    }
+                } else {
+                    None
+                };
+
+                match block {
+                    // The process stopped between allocating the block and writing it
+                    None => report.blocks_released += 1,
+                    Some(block) if recovery::is_unwritten(&block) => report.blocks_released += 1,
+                    Some(block) => {
+                        let Err(reason) = recovery::check_block(&block) else {
+                            continue;
+                        };
+                        let path = recovery::quarantine_block(&self.data_dir, table_name, segment_id, block_id, &block)?;
+                        report.blocks_quarantined.push(QuarantinedBlock {
+                            table: table_name.to_string(),
+                            segment_id,
+                            block_id,
+                            reason,
+                            path,
+                        });
+                        quarantined = true;
+                    }
+                }
+                header.mark_block_free(block_id);
+                header_changed = true;
+            }
+            if header_changed {
+                table_file.write_segment_header(segment_id, &header)
+                    .map_err(|e| format!("Failed to write segment header: {}", e))?;
+            }
+        }
+
+        let rows = self.scan_table_tuples(table_name)?;
+        let metadata_arc = self.get_table(table_name)?;
+        let metadata = metadata_arc.read();
+        metadata.row_count_estimate.store(rows.len() as u64, Ordering::Relaxed);
+        let (Some(primary_index_meta), Some(index_file)) = (&metadata.primary_index, self.index_files.get(table_name)) else {
+            return Ok(());
+        };
+        let pk_column = metadata.schema.primary_key_index().unwrap_or(0);
+
+        // Entries for quarantined rows can only be dropped by starting over
+        if !quarantined && Self::index_matches(&**primary_index_meta.index.lock(), index_file, &rows, pk_column) {
+            return Ok(());
+        }
+        let root_page_id = self.reset_index(primary_index_meta, index_file)?;
+        let mut index_guard = primary_index_meta.index.lock();
+        for (tuple_ptr, row) in &rows {
+            index_guard.insert_entry(Self::primary_key(row, pk_column)?, *tuple_ptr, index_file)
+                .map_err(|e| format!("Failed to rebuild primary index: {}", e))?;
+        }
+        drop(index_guard);
+        drop(metadata);
+
+        if let Some(index_meta) = self.catalog.get_table_mut(table_name)
+            .and_then(|table_meta| table_meta.primary_index.as_mut())
+        {
+            index_meta.root_page_segment = root_page_id.segment_id();
+            index_meta.root_page_offset = root_page_id.page_offset();
+        }
+        report.indexes_rebuilt.push(table_name.to_string());
+        Ok(())
+    }
 
-                let fallback_data = fs::read(&fallback_path)
-                    .map_err(|_| "Failed to load catalog from either segment".to_string())?;
+    /// Whether a primary index holds exactly one entry per row, leading to it
+    fn index_matches(index: &dyn index::Index, index_file: &IndexFile, rows: &[(TuplePointer, Row)], pk_column: usize) -> bool {
+        let all_found = rows.iter().all(|(tuple_ptr, row)| {
+            Self::primary_key(row, pk_column)
+                .is_ok_and(|key| index.search_all(key, index_file).is_ok_and(|found| found.contains(tuple_ptr)))
+        });
+        // Entries left over for rows that are gone only show up in a full scan
+        all_found && index.as_ordered()
+            .is_none_or(|ordered| index::OrderedIndex::full_scan(ordered, index_file).is_ok_and(|entries| entries.len() == rows.len()))
+    }
+
+    /// Discard an index's pages and start it over from an empty root
+    /// Returns the new root page
+    fn reset_index(&self, index_meta: &IndexMetadata, index_file: &IndexFile) -> Result<PageId> {
+        index_file.reset()
+            .map_err(|e| format!("Failed to reset index file: {}", e))?;
+        let root_page_id = index_file.allocate_page()
+            .map_err(|e| format!("Failed to allocate index root page: {}", e))?;
+        Self::init_index_root(index_file, root_page_id)?;
+        *index_meta.index.lock() = self.index_builder_registry.create_index(&index_meta.index_type, Some(root_page_id))
+            .ok_or_else(|| format!("Failed to create {} index", index_meta.index_type))?;
+        Ok(root_page_id)
+    }
+
+    /// Rewrite a table's files in the current storage format
+    /// Every block is repacked in the current block format and the primary
+    /// index is rebuilt from scratch, since its keys may use an older encoding
+    fn upgrade_table_storage(&mut self, table_name: &str) -> Result<()> {
+        let from = self.catalog.get_table(table_name)
+            .map_err(|e| format!("Failed to read catalog: {}", e))?
+            .map(|table_meta| table_meta.storage_version)
+            .ok_or_else(|| format!("Table not found: {}", table_name))?;
 
-                let fallback_catalog = catalog::Catalog::deserialize(&fallback_data)
-                    .map_err(|e| format!("Failed to deserialize fallback catalog: {}", e))?;
+        if let Some(index_file) = self.index_files.get(table_name).cloned() {
+            index_file.reset()
+                .map_err(|e| format!("Failed to reset index file: {}", e))?;
+            let root_page_id = index_file.allocate_page()
+                .map_err(|e| format!("Failed to allocate index root page: {}", e))?;
+            Self::init_index_root(&index_file, root_page_id)?;
 
-                // Use fallback catalog and flip segment
-                self.catalog = fallback_catalog;
-                self.catalog.flip_segment();
+            let metadata_arc = self.get_table(table_name)?;
+            let mut metadata = metadata_arc.write();
+            if let Some(primary_index) = metadata.primary_index.as_mut() {
+                let index = self.index_builder_registry.create_index(&primary_index.index_type, Some(root_page_id))
+                    .ok_or_else(|| format!("Failed to create {} index", primary_index.index_type))?;
+                primary_index.index = Arc::new(Mutex::new(index));
+            }
+            if let Some(index_meta) = self.catalog.get_table_mut(table_name)
+                .and_then(|table_meta| table_meta.primary_index.as_mut())
+            {
+                index_meta.root_page_segment = root_page_id.segment_id();
+                index_meta.root_page_offset = root_page_id.page_offset();
+            }
+        }
+
+        // Compaction reads blocks of any supported format and writes them back in
+        // the current one, re-inserting every primary key as it goes
+        let stats = self.compact_table(table_name)?;
+
+        if let Some(table_meta) = self.catalog.get_table_mut(table_name) {
+            table_meta.storage_version = migrate::STORAGE_VERSION;
+        }
+        info!(table_name, from, to = migrate::STORAGE_VERSION, live_tuples = stats.live_tuples, "upgraded table storage");
+        Ok(())
+    }
 
-                // Recursively load with fallback catalog
-                self.load_catalog_from_disk()
+    /// Make everything written so far durable, as on a clean shutdown: every
+    /// table and index file is fsynced, then the catalog saved, which leaves
+    /// nothing in the WAL to replay
+    /// Sequences are saved at the last value handed out rather than the end
+    /// of their reserved batch, so a clean restart leaves no gap; the next
+    /// nextval reserves a new batch
+    pub fn checkpoint(&mut self) -> Result<()> {
+        for (name, cache) in self.sequences.iter_mut() {
+            if let Some(sequence_meta) = self.catalog.get_sequence_mut(name) {
+                sequence_meta.last_value = cache.last_value;
+                sequence_meta.is_called = cache.is_called;
             }
+            cache.log_cnt = 0;
+        }
+        self.sync_data_files()?;
+        self.save_catalog_to_disk()?;
+        info!(tables = self.table_files.len(), indexes = self.index_files.len(), "checkpoint complete");
+        Ok(())
+    }
+
+    /// Flush every table and index file to stable storage
+    fn sync_data_files(&self) -> Result<()> {
+        for table_file in self.table_files.values() {
+            table_file.sync()
+                .map_err(|e| format!("Failed to sync {}: {}", table_file.path().display(), e))?;
         }
+        for index_file in self.index_files.values() {
+            index_file.sync()
+                .map_err(|e| format!("Failed to sync {}: {}", index_file.path().display(), e))?;
+        }
+        Ok(())
     }
 
-    /// Save catalog to catalog.db file with atomic flip
+    /// Save catalog to the inactive segment and flip the active marker to it
+    /// Each step is written to a temp file, fsynced, renamed into place and the
+    /// directory fsynced, so a crash leaves the marker on a complete catalog
     fn save_catalog_to_disk(&mut self) -> Result<()> {
-        use std::fs;
-        use std::io::Write;
+        // The WAL is discarded once the catalog is saved, so the changes it
+        // logged have to be in the table and index files first
+        if self.wal.as_ref().is_some_and(|wal| wal.next_offset() > 0) {
+            self.sync_data_files()?;
+        }
 
         // Get inactive segment to write to
         let inactive_seg = self.catalog.inactive_segment();
-        let temp_path = format!("catalog_{}.tmp", inactive_seg);
-        let final_path = format!("catalog_{}.db", inactive_seg);
+        self.catalog.set_transactions(self.transactions.state());
 
         // Serialize catalog
         let data = self.catalog.serialize()
             .map_err(|e| format!("Failed to serialize catalog: {}", e))?;
 
-        // Write to temp file first
-        let mut temp_file = fs::File::create(&temp_path)
-            .map_err(|e| format!("Failed to create temp catalog file: {}", e))?;
+        self.write_durably(&format!("catalog_{}.db", inactive_seg), &data)?;
+
+        // The catalog only becomes active once the marker points at it
+        self.write_durably(CATALOG_MARKER_FILE, inactive_seg.to_string().as_bytes())?;
+
+        // Flip segment
+        self.catalog.flip_segment();
+
+        // Everything logged so far is now part of the catalog
+        if let Some(wal) = self.wal.as_mut()
+            && !wal.is_empty()
+        {
+            wal.reset()
+                .map_err(|e| format!("Failed to reset WAL: {}", e))?;
+        }
+
+        Ok(())
+    }
+
+    /// Apply WAL records written since the catalog was last saved
+    /// A torn entry ends the log, since nothing after it can have been
+    /// acknowledged
+    /// A corrupt entry with more of the log after it, or a record that can't
+    /// be redone, loses acknowledged changes, so it fails startup before
+    /// anything is written unless lossy recovery was allowed
+    /// The records applied, and how the log ended, go in the report
+    fn replay_wal(&mut self, report: &mut RecoveryReport) -> Result<()> {
+        let Some(wal) = self.wal.as_ref() else {
+            return Ok(());
+        };
+
+        let mut sequence_records = Vec::new();
+        let mut tuple_records: Vec<(WalEntryType, TupleRecord)> = Vec::new();
+        // Transactions with tuple changes logged and no commit yet
+        let mut uncommitted = BTreeSet::new();
+        let mut entries = wal.entries();
+        for entry in &mut entries {
+            match WalEntryType::from_u8(entry.header.entry_type) {
+                Some(WalEntryType::Sequence) => sequence_records.push(decode_wal_record::<SequenceRecord>(&entry)?),
+                Some(entry_type @ (WalEntryType::Insert | WalEntryType::Update | WalEntryType::Delete)) => {
+                    let record: TupleRecord = decode_wal_record(&entry)?;
+                    uncommitted.insert(record.txid);
+                    tuple_records.push((entry_type, record));
+                }
+                Some(WalEntryType::Commit) => {
+                    let record: CommitRecord = decode_wal_record(&entry)?;
+                    uncommitted.remove(&record.txid);
+                    self.transactions.replayed(record.txid, true);
+                }
+                Some(WalEntryType::Ddl) => {
+                    // Changes logged before a table was created, rewritten or
+                    // emptied don't describe the files it has since
+                    let record: DdlRecord = decode_wal_record(&entry)?;
+                    tuple_records.retain(|(_, tuple_record)| tuple_record.relid != record.relid);
+                }
+                Some(WalEntryType::Checkpoint) => tuple_records.clear(),
+                None => {}
+            }
+        }
+        report.wal_end = entries.end().cloned().unwrap_or_default();
+        report.wal_bytes_ignored = entries.bytes_ignored();
+        if matches!(report.wal_end, WalEnd::Corrupt(_) | WalEnd::Unreadable(_)) && !self.allow_lossy_recovery {
+            return Err(format!(
+                "WAL is {}, leaving {} bytes of it unreplayed; restart with --allow-lossy-recovery to discard them",
+                report.wal_end, report.wal_bytes_ignored
+            ));
+        }
+        // The rest were running when the log ended, so their changes are
+        // redone but stay invisible
+        for txid in uncommitted {
+            self.transactions.replayed(txid, false);
+        }
+
+        for record in &sequence_records {
+            if let Some(sequence_meta) = self.catalog.get_sequence_mut(&record.name) {
+                sequence_meta.last_value = record.last_value;
+                sequence_meta.is_called = record.is_called;
+                self.sequences.insert(record.name.clone(), SequenceCache::new(sequence_meta));
+            }
+        }
+
+        // Tables still in an older storage format were upgraded before this
+        // build wrote to them, so the log has nothing for them
+        let tables: HashMap<u32, String> = self.catalog.all_tables().into_iter()
+            .filter(|table_meta| table_meta.storage_version == migrate::STORAGE_VERSION)
+            .map(|table_meta| (table_meta.oid, table_meta.name.clone()))
+            .collect();
+        let mut redo: HashMap<&str, RedoBlocks> = HashMap::new();
+        let mut tuple_records_replayed = 0;
+        for (entry_type, record) in &tuple_records {
+            let Some(table_name) = tables.get(&record.relid) else {
+                continue;
+            };
+            let Some(table_file) = self.table_files.get(table_name) else {
+                continue;
+            };
+            let blocks = redo.entry(table_name).or_insert_with(|| RedoBlocks::new(table_file.clone()));
+            if let Err(e) = Self::redo_tuple_changes(blocks, *entry_type, record) {
+                if !self.allow_lossy_recovery {
+                    return Err(format!(
+                        "table {}: WAL record could not be replayed: {}; restart with --allow-lossy-recovery to discard it",
+                        table_name, e
+                    ));
+                }
+                report.problems.push(format!("table {}: WAL record could not be replayed: {}", table_name, e));
+                continue;
+            }
+            tuple_records_replayed += 1;
+        }
+        for blocks in redo.into_values() {
+            blocks.write()?;
+        }
+
+        let replayed = sequence_records.len() + tuple_records_replayed;
+        if replayed > 0 {
+            info!(records = replayed, "replayed WAL");
+        }
+        report.wal_records_replayed = replayed;
+        Ok(())
+    }
 
-        temp_file.write_all(&data)
-            .map_err(|e| format!("Failed to write catalog file: {}", e))?;
+    /// Redo one logged statement's changes to a table's blocks
+    /// Inserts go back into the exact slots they were logged at, replacing
+    /// whatever is there, and deletes stamp the statement's transaction on
+    /// the tuples they delete, so redoing a change that already reached the
+    /// table leaves it as it was
+    fn redo_tuple_changes(blocks: &mut RedoBlocks, entry_type: WalEntryType, record: &TupleRecord) -> Result<()> {
+        let meta = base::TupleMeta::new(record.txid);
+        for change in &record.changes {
+            let deleted = if entry_type == WalEntryType::Delete {
+                Some(&change.tuple_ptr)
+            } else {
+                let tuple_ptr = &change.tuple_ptr;
+                let block = blocks.block_mut(tuple_ptr, true)?
+                    .expect("blocks are created for inserts");
+                if !block.put_tuple(tuple_ptr.slot_id, &meta, &change.tuple) {
+                    return Err(format!("no room for tuple {:?}", tuple_ptr));
+                }
+                change.old_tuple_ptr.as_ref()
+            };
+            if let Some(tuple_ptr) = deleted
+                && let Some(block) = blocks.block_mut(tuple_ptr, false)?
+                && let Some(mut deleted_meta) = block.tuple_meta(tuple_ptr.slot_id)
+            {
+                deleted_meta.mark_deleted(record.txid);
+                block.set_tuple_meta(tuple_ptr.slot_id, &deleted_meta);
+            }
+        }
+        Ok(())
+    }
+
+    /// Atomically replace a file in the data directory
+    fn write_durably(&self, file_name: &str, data: &[u8]) -> Result<()> {
+        use std::fs;
+        use std::io::Write;
 
+        let final_path = self.data_path(file_name);
+        let temp_path = self.data_path(&format!("{}.tmp", file_name));
+
+        let mut temp_file = fs::File::create(&temp_path)
+            .map_err(|e| format!("Failed to create {}: {}", temp_path.display(), e))?;
+        temp_file.write_all(data)
+            .map_err(|e| format!("Failed to write {}: {}", temp_path.display(), e))?;
         temp_file.sync_all()
-            .map_err(|e| format!("Failed to sync catalog file: {}", e))?;
+            .map_err(|e| format!("Failed to sync {}: {}", temp_path.display(), e))?;
 
         // Atomic rename
         fs::rename(&temp_path, &final_path)
-            .map_err(|e| format!("Failed to rename catalog file: {}", e))?;
+            .map_err(|e| format!("Failed to rename {}: {}", temp_path.display(), e))?;
 
-        // Flip segment
-        self.catalog.flip_segment();
-
-        Ok(())
+        // The rename is only durable once the directory entry is
+        fs::File::open(&self.data_dir)
+            .and_then(|dir| dir.sync_all())
+            .map_err(|e| format!("Failed to sync data directory: {}", e))
     }
 
-    pub fn create_table(&mut self, name: String, schema: Schema) -> Result<()> {
+    pub fn create_table(
+        &mut self,
+        name: String,
+        schema: Schema,
+        options: TableOptions,
+        checks: Vec<CheckConstraint>,
+        foreign_keys: Vec<ForeignKey>,
+        serials: Vec<(String, SequenceOptions)>,
+    ) -> Result<()> {
         if self.tables.contains_key(&name) {
             return Err(format!("Table already exists: {}", name));
         }
+        if self.catalog.get_sequence(&name).is_some() {
+            return Err(format!("A sequence named {} already exists", name));
+        }
+        let ttl = options.ttl.flatten()
+            .map(|policy| Self::check_ttl(&name, &schema, policy))
+            .transpose()?;
+        let fillfactor = options.fillfactor
+            .map(Self::check_fillfactor)
+            .transpose()?
+            .unwrap_or(catalog::FILLFACTOR_DEFAULT);
+        let foreign_keys = foreign_keys.into_iter()
+            .map(|foreign_key| self.check_foreign_key(&name, &schema, foreign_key))
+            .collect::<Result<Vec<_>>>()?;
+
+        // Each SERIAL column gets a sequence of its own, named as Postgres
+        // names it, which is saved with the table
+        let mut serial_columns = Vec::with_capacity(serials.len());
+        let mut serial_sequences = Vec::with_capacity(serials.len());
+        for (column, options) in serials {
+            let sequence_name = self.serial_sequence_name(&name, &column, &serial_columns);
+            // Object ids are handed out once the table has taken its own
+            let sequence_meta = options.into_metadata(sequence_name.clone(), 0)?;
+            serial_sequences.push(sequence_meta);
+            serial_columns.push((column, sequence_name));
+        }
 
         // Create file path: table_<name>.tbl
-        let file_path = PathBuf::from(format!("table_{}.tbl", name));
+        let file_path = self.unused_data_path(&name, &format!("table_{}", name), "tbl");
+
+        // A file left behind by a table that is no longer in the catalog holds nothing
+        // we can use, and reopening it would resume allocation after its stale data
+        let index_file_path = self.unused_data_path(&name, &format!("index_{}_{}", name, "pk"), "idx");
+        for stale_path in [&file_path, &index_file_path] {
+            match std::fs::remove_file(stale_path) {
+                Ok(()) => debug!(path = %stale_path.display(), "removed stale file"),
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+                Err(e) => return Err(format!("Failed to remove stale file {}: {}", stale_path.display(), e)),
+            }
+        }
 
         // Open/create the per-table file
         let table_file = TableFile::open(&file_path)
@@ -325,13 +1123,13 @@ impl Database {
             .map_err(|e| format!("Failed to allocate segment: {}", e))?;
 
         // Create and initialize primary index
-        let index_file_path = PathBuf::from(format!("index_{}_{}.idx", name, "pk"));
         let index_file = IndexFile::open(&index_file_path)
             .map_err(|e| format!("Failed to open index file: {}", e))?;
 
         // Allocate root page for the primary index
         let root_page_id = index_file.allocate_page()
             .map_err(|e| format!("Failed to allocate index root page: {}", e))?;
+        Self::init_index_root(&index_file, root_page_id)?;
 
         // Create BTree index via registry
         let index = self.index_builder_registry.create_index("btree", Some(root_page_id))
@@ -339,8 +1137,9 @@ impl Database {
 
         let primary_index = Some(IndexMetadata {
             name: "pk".to_string(),
-            column: "".to_string(), // Primary key column determined by schema
+            columns: vec![String::new()], // Primary key column determined by schema
             index_type: "btree".to_string(),
+            descending: false,
             index: Arc::new(Mutex::new(index)),
         });
 
@@ -352,12 +1151,15 @@ impl Database {
             schema,
             primary_index,
             secondary_indexes: Vec::new(),
+            row_count_estimate: AtomicU64::new(0),
         };
 
         // Insert into runtime tables (wrapped in Arc<RwLock<>>)
         // TODO check or mutex to prevent duplicate tables
         self.tables.insert(name.clone(), Arc::new(RwLock::new(metadata)));
+        let table_file_next_segment_id = table_file.next_segment_id();
         self.table_files.insert(name.clone(), Arc::new(table_file));
+        let index_file_next_page_id = index_file.next_page_id();
         self.index_files.insert(name.clone(), Arc::new(index_file));
 
         // Build and save metadata to catalog
@@ -367,142 +1169,1910 @@ impl Database {
             file_path: index_file_path.to_string_lossy().to_string(),
             root_page_segment: root_page_id.segment_id(),
             root_page_offset: root_page_id.page_offset(),
+            next_page_id: index_file_next_page_id,
         };
 
         let table_meta = catalog::TableFileMetadata {
             name: name.clone(),
             file_path: file_path.to_string_lossy().to_string(),
             schema: metadata_schema,
-            next_segment_id: 1, // We allocated segment 0
+            next_segment_id: table_file_next_segment_id,
             primary_index: Some(primary_index_meta),
             secondary_indexes: Vec::new(),
+            storage_version: migrate::STORAGE_VERSION,
+            oid: self.catalog.next_oid(),
+            comment: None,
+            column_comments: Vec::new(),
+            row_count_estimate: Some(0),
+            ttl,
+            checks,
+            foreign_keys,
+            serial_columns,
+            fillfactor,
         };
 
         self.catalog.add_table(table_meta)
             .map_err(|e| format!("Failed to add table to catalog: {}", e))?;
+        for mut sequence_meta in serial_sequences {
+            sequence_meta.oid = self.catalog.next_oid();
+            debug!(table = %name, sequence = %sequence_meta.name, "serial sequence created");
+            self.sequences.insert(sequence_meta.name.clone(), SequenceCache::new(&sequence_meta));
+            self.catalog.add_sequence(sequence_meta);
+        }
 
-        self.save_catalog_to_disk()?;
+        // A temporary table is never saved, so creating one changes nothing on disk
+        if !self.catalog.is_temporary(&name) {
+            self.log_ddl(&name, DdlOperation::Create)?;
+            self.save_catalog_to_disk()?;
+        }
 
         Ok(())
     }
 
-    pub fn get_table(&self, name: &str) -> Result<Arc<RwLock<TableMetadata>>> {
-        self.tables
-            .get(name)
-            .cloned()
-            .ok_or_else(|| format!("Table not found: {}", name))
+    /// Create a temporary table: one the catalog never saves, with its files
+    /// in a directory cleared at startup, for a session to drop when it ends
+    pub fn create_temp_table(
+        &mut self,
+        name: String,
+        schema: Schema,
+        options: TableOptions,
+        checks: Vec<CheckConstraint>,
+        foreign_keys: Vec<ForeignKey>,
+        serials: Vec<(String, SequenceOptions)>,
+    ) -> Result<()> {
+        if self.tables.contains_key(&name) {
+            return Err(format!("Table already exists: {}", name));
+        }
+        let temp_dir = self.data_dir.join(TEMP_TABLE_DIR);
+        std::fs::create_dir_all(&temp_dir)
+            .map_err(|e| format!("Failed to create {}: {}", temp_dir.display(), e))?;
+        self.catalog.set_temporary(&name);
+        let result = self.create_table(name.clone(), schema, options, checks, foreign_keys, serials);
+        if result.is_err() && !self.tables.contains_key(&name) {
+            self.catalog.remove_table(&name)
+                .map_err(|e| format!("Failed to remove table from catalog: {}", e))?;
+        }
+        result
     }
 
-    pub fn insert_row(&mut self, table_name: &str, row: Row) -> Result<()> {
-        let table_file = self.table_files.get(table_name)
-            .ok_or_else(|| format!("Table not found: {}", table_name))?
-            .clone();
-
-        let metadata_arc = self.tables.get(table_name)
-            .ok_or_else(|| format!("Table not found: {}", table_name))?
-            .clone();
-        let metadata = metadata_arc.read();
-
-        // Validate row against schema
-        if row.len() != metadata.schema.len() {
+    /// Drop a temporary table with its indexes and SERIAL sequences, deleting
+    /// its files
+    pub fn drop_temp_table(&mut self, name: &str) -> Result<()> {
+        if !self.catalog.is_temporary(name) {
+            return Err(format!("Dropping table {} is not supported: only temporary tables can be dropped", name));
+        }
+        let dependent = self.catalog.all_tables().into_iter().find(|table_meta| {
+            table_meta.name != name
+                && table_meta.foreign_keys.iter().any(|foreign_key| foreign_key.referenced_table == name)
+        });
+        if let Some(dependent) = dependent {
             return Err(format!(
-                "Row has {} columns but schema expects {}",
-                row.len(),
-                metadata.schema.len()
+                "cannot drop table {} because table {} has a foreign key referencing it",
+                name, dependent.name
             ));
         }
 
-        // Serialize row to bytes
-        let row_bytes = bincode::encode_to_vec(&row, bincode::config::standard())
-            .map_err(|e| format!("Serialization error: {}", e))?;
+        let table_meta = self.catalog.remove_table(name)
+            .map_err(|e| format!("Failed to remove table from catalog: {}", e))?
+            .ok_or_else(|| format!("Table not found: {}", name))?;
+        for (_, sequence) in &table_meta.serial_columns {
+            self.catalog.remove_sequence(sequence);
+            self.sequences.remove(sequence);
+        }
+        self.tables.remove(name);
+        self.table_files.remove(name);
+        self.index_files.remove(name);
+        for index_meta in &table_meta.secondary_indexes {
+            self.index_files.remove(&format!("{}_{}", name, index_meta.name));
+        }
 
-        // Try to allocate block in segment 0 (first segment)
-        let segment_id = 0u32;
-        let block_id = table_file.allocate_block(segment_id)
-            .map_err(|e| format!("Failed to allocate block: {}", e))?
-            .ok_or_else(|| "Segment full - need to allocate new segment".to_string())?;
+        let paths = std::iter::once(&table_meta.file_path)
+            .chain(table_meta.primary_index.iter().chain(&table_meta.secondary_indexes)
+                .map(|index_meta| &index_meta.file_path));
+        for path in paths {
+            match std::fs::remove_file(path) {
+                Ok(()) => {}
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+                Err(e) => warn!(error = %e, path = %path, "failed to remove temporary table file"),
+            }
+        }
+        debug!(table = %name, "temporary table dropped");
+        Ok(())
+    }
 
-        // Read block, append tuple, write back
-        let mut block = table_file.read_block(segment_id, block_id)
-            .map_err(|e| format!("Failed to read block: {}", e))?;
+    /// Name for the sequence of a SERIAL column: `<table>_<column>_seq`, with a
+    /// number appended if that is taken by a relation or by a sequence of the
+    /// same table (`chosen`)
+    fn serial_sequence_name(&self, table_name: &str, column: &str, chosen: &[(String, String)]) -> String {
+        let base = format!("{}_{}_seq", table_name, column);
+        let taken = |name: &str| {
+            self.catalog.get_sequence(name).is_some()
+                || self.tables.contains_key(name)
+                || name == table_name
+                || chosen.iter().any(|(_, sequence)| sequence == name)
+        };
+        std::iter::once(base.clone())
+            .chain((1..).map(|n| format!("{}{}", base, n)))
+            .find(|name| !taken(name))
+            .expect("some numbered name is free")
+    }
 
-        let slot_id = block.append_tuple(&row_bytes)
-            .ok_or_else(|| "Block full".to_string())?;
+    /// Write an empty leaf page at a freshly allocated index root
+    fn init_index_root(index_file: &IndexFile, root_page_id: PageId) -> Result<()> {
+        let root_page = index::page::IndexPage::new(index::page::NodeType::Leaf);
+        index_file.write_page(root_page_id, &root_page.data)
+            .map_err(|e| format!("Failed to initialize index root page: {}", e))
+    }
 
-        table_file.write_block(segment_id, block_id, &block)
-            .map_err(|e| format!("Failed to write block: {}", e))?;
+    pub fn get_table(&self, name: &str) -> Result<Arc<RwLock<TableMetadata>>> {
+        self.tables
+            .get(name)
+            .cloned()
+            .ok_or_else(|| format!("Table not found: {}", name))
+    }
 
-        // Create tuple pointer for the inserted row
-        let tuple_ptr = TuplePointer::new(segment_id, block_id, slot_id);
+    /// Names of all tables in the database
+    pub fn table_names(&self) -> Vec<String> {
+        self.tables.keys().cloned().collect()
+    }
 
-        // Update primary key index if table has one
-        if let Some(primary_index_meta) = &metadata.primary_index {
-            // Extract primary key from first column (TODO: assume first column is PK)
-            let key_value = row.get(0)
-                .ok_or_else(|| "Row must have at least one column for primary key".to_string())?;
-
-            // Convert Value to u64 key (handle Int type)
-            let key = match key_value {
-                crate::types::Value::Int(n) => *n as u64,
-                crate::types::Value::Null => return Err("Primary key cannot be NULL".to_string()),
-                _ => return Err(format!("Primary key must be Int type, got {:?}", key_value)),
+    /// Run a write in this thread's transaction (see `mvcc::set_current`), or
+    /// else as a transaction of its own: committed if it succeeds, rolled
+    /// back if it fails, so tuples it had already written stay invisible
+    fn in_transaction<T>(&mut self, write: impl FnOnce(&mut Self, base::TxId) -> Result<T>) -> Result<T> {
+        if let Some(txid) = mvcc::current().filter(|txid| self.transactions.is_running(*txid)) {
+            return write(self, txid);
+        }
+        let txid = self.transactions.begin();
+        mvcc::set_current(Some(txid));
+        let result = write(self, txid);
+        mvcc::set_current(None);
+        match result {
+            Ok(value) => self.commit_transaction(txid).map(|()| value),
+            Err(e) => {
+                self.rollback_transaction(txid);
+                Err(e)
+            }
+        }
+    }
+
+    /// Start a transaction for a session's writes, which run in it while it
+    /// is set as their thread's (see `mvcc::set_current`) until it is
+    /// committed or rolled back
+    pub fn begin_transaction(&mut self) -> base::TxId {
+        self.transactions.begin()
+    }
+
+    /// Commit a transaction, making its writes visible to every reader
+    /// One that wrote is logged as committed, as WAL replay otherwise takes
+    /// its writes for those of a transaction cut short; if that fails it is
+    /// rolled back instead
+    /// Writes to temporary tables aren't logged, so neither is their commit
+    /// The index entries of the tuples it deleted go once it has committed
+    /// (see `remove_index_entries_of`)
+    pub fn commit_transaction(&mut self, txid: base::TxId) -> Result<()> {
+        let logged = self.transactions.written_tables(txid).any(|table_name| !self.catalog.is_temporary(table_name));
+        if logged
+            && let Err(e) = self.log_change(WalEntryType::Commit, &CommitRecord { txid })
+        {
+            self.rollback_transaction(txid);
+            return Err(e);
+        }
+        for (table_name, changes) in self.transactions.commit(txid) {
+            if !self.table_files.contains_key(&table_name) {
+                continue;
+            }
+            if let Err(e) = self.remove_index_entries_of(&table_name, &changes.deleted, |meta| meta.xmax == txid) {
+                warn!(txid, table = %table_name, error = %e, "failed to remove index entries of committed deletes");
+            }
+        }
+        Ok(())
+    }
+
+    /// Roll back a transaction: the tuples it wrote stay in their blocks,
+    /// never visible, and lose their index entries, while the ones it deleted
+    /// are visible again, their entries never having been removed
+    /// Tables dropped since are skipped, and an index that can't be put back
+    /// is only warned about, as startup checks rebuild indexes that don't
+    /// match their tables
+    pub fn rollback_transaction(&mut self, txid: base::TxId) {
+        for (table_name, changes) in self.transactions.abort(txid) {
+            if !self.table_files.contains_key(&table_name) {
+                continue;
+            }
+            if let Err(e) = self.undo_index_entries(txid, &table_name, &changes) {
+                warn!(txid, table = %table_name, error = %e, "failed to undo index entries of rolled back transaction");
+            }
+        }
+    }
+
+    /// Put a table's index entries back the way they were before transaction
+    /// `txid` wrote and deleted the tuples of `changes`
+    fn undo_index_entries(&self, txid: base::TxId, table_name: &str, changes: &mvcc::TableChanges) -> Result<()> {
+        let written = self.remove_index_entries_of(table_name, &changes.written, |meta| meta.xmin == txid)?;
+
+        // Tuples it wrote and deleted again were never counted
+        let table_file = self.table_files.get(table_name)
+            .ok_or_else(|| format!("Table not found: {}", table_name))?;
+        let restored = Self::read_tuples(table_file, &changes.deleted, |meta| meta.xmax == txid && meta.xmin != txid)?.len();
+        let discarded = written.iter().filter(|(tuple_ptr, _)| !changes.deleted.contains(tuple_ptr)).count();
+        let metadata_arc = self.get_table(table_name)?;
+        let _ = metadata_arc.read().row_count_estimate.fetch_update(Ordering::Relaxed, Ordering::Relaxed, |count| {
+            Some((count + restored as u64).saturating_sub(discarded as u64))
+        });
+        Ok(())
+    }
+
+    /// Remove the primary and secondary index entries of the tuples among
+    /// `tuple_ptrs` that pass `filter`
+    /// Deletes leave a tuple's entries in place until the delete commits, as
+    /// readers that don't see it yet still look it up through them
+    /// Returns the tuples whose entries were removed
+    fn remove_index_entries_of(
+        &self,
+        table_name: &str,
+        tuple_ptrs: &HashSet<TuplePointer>,
+        filter: impl Fn(&base::TupleMeta) -> bool,
+    ) -> Result<Vec<(TuplePointer, Row)>> {
+        let table_file = self.table_files.get(table_name)
+            .ok_or_else(|| format!("Table not found: {}", table_name))?;
+        let metadata_arc = self.get_table(table_name)?;
+        let metadata = metadata_arc.read();
+        let tuples = Self::read_tuples(table_file, tuple_ptrs, filter)?;
+
+        if let Some(primary_index_meta) = &metadata.primary_index {
+            let index_file = self.index_files.get(table_name)
+                .ok_or_else(|| format!("Index file not found for table: {}", table_name))?;
+            let pk_column = metadata.schema.primary_key_index().unwrap_or(0);
+            let mut index_guard = primary_index_meta.index.lock();
+            for (tuple_ptr, row) in &tuples {
+                index_guard.delete_entry(Self::primary_key(row, pk_column)?, *tuple_ptr, index_file)
+                    .map_err(|e| format!("Failed to delete from primary index: {}", e))?;
+            }
+        }
+        for (index_meta, index_file) in self.secondary_indexes(table_name, &metadata)? {
+            self.remove_index_entries(&metadata.schema, index_meta, index_file, &tuples)?;
+        }
+        Ok(tuples)
+    }
+
+    pub fn insert_row(&mut self, table_name: &str, row: Row) -> Result<()> {
+        self.insert_rows(table_name, vec![row]).map(|_| ())
+    }
+
+    /// Insert a batch of rows, grouping rows that land in the same block into a
+    /// single read-modify-write and updating the segment header once per batch
+    /// Returns the number of rows inserted
+    pub fn insert_rows(&mut self, table_name: &str, rows: Vec<Row>) -> Result<usize> {
+        self.in_transaction(|db, txid| db.insert_rows_in(txid, table_name, rows))
+    }
+
+    /// Insert rows as transaction `txid` (see `insert_rows`)
+    fn insert_rows_in(&mut self, txid: base::TxId, table_name: &str, rows: Vec<Row>) -> Result<usize> {
+        let table_file = self.table_files.get(table_name)
+            .ok_or_else(|| format!("Table not found: {}", table_name))?
+            .clone();
+
+        let metadata_arc = self.tables.get(table_name)
+            .ok_or_else(|| format!("Table not found: {}", table_name))?
+            .clone();
+        let metadata = metadata_arc.read();
+
+        let encoded_rows = Self::encode_rows(&metadata.schema, &rows)?;
+        if encoded_rows.is_empty() {
+            return Ok(0);
+        }
+
+        // Resolve primary keys and reject duplicates before anything touches disk
+        let primary_keys = match &metadata.primary_index {
+            Some(primary_index_meta) => {
+                let pk_column = metadata.schema.primary_key_index().unwrap_or(0);
+                let pk_name = &metadata.schema.columns[pk_column].name;
+                let index_guard = primary_index_meta.index.lock();
+
+                // Distinct values can share a key, so each key keeps every value
+                // taken under it
+                let mut batch_keys: HashMap<u64, Vec<&crate::types::Value>> = HashMap::with_capacity(rows.len());
+                let mut keys = Vec::with_capacity(rows.len());
+                for row in &rows {
+                    let key = Self::primary_key(row, pk_column)?;
+                    let value = &row.values[pk_column];
+
+                    let taken_in_batch = batch_keys.get(&key)
+                        .is_some_and(|values| values.iter().any(|other| index::key::key_values_equal(other, value)));
+                    if taken_in_batch || self.key_taken(table_name, &**index_guard, pk_column, value, &HashSet::new())? {
+                        return Err(Self::key_conflict(table_name, pk_name, value));
+                    }
+
+                    batch_keys.entry(key).or_default().push(value);
+                    keys.push(key);
+                }
+                Some(keys)
+            }
+            None => None,
+        };
+
+        let placed = Self::place_tuples(&table_file, &self.transactions, txid, &encoded_rows, self.fill_reserve(table_name))?;
+        let changes = placed.tuple_ptrs.iter().zip(&encoded_rows)
+            .map(|(tuple_ptr, tuple)| TupleChange { tuple_ptr: *tuple_ptr, old_tuple_ptr: None, tuple: tuple.clone() })
+            .collect();
+        self.log_tuples(table_name, txid, WalEntryType::Insert, changes)?;
+        let tuple_ptrs = Self::write_tuples(&table_file, placed)?;
+
+        // Update primary key index if table has one
+        if let (Some(primary_index_meta), Some(primary_keys)) = (&metadata.primary_index, primary_keys) {
+            // Get index file
+            let index_file = self.index_files.get(table_name)
+                .ok_or_else(|| format!("Index file not found for table: {}", table_name))?;
+
+            let mut index_guard = primary_index_meta.index.lock();
+            for (key, tuple_ptr) in primary_keys.into_iter().zip(&tuple_ptrs) {
+                index_guard.insert_entry(key, *tuple_ptr, index_file)
+                    .map_err(|e| format!("Failed to insert into primary index: {}", e))?;
+            }
+        }
+        let inserted: Vec<(TuplePointer, Row)> = tuple_ptrs.into_iter().zip(rows).collect();
+        for (index_meta, index_file) in self.secondary_indexes(table_name, &metadata)? {
+            self.add_index_entries(&metadata.schema, index_meta, index_file, &inserted)?;
+        }
+        metadata.row_count_estimate.fetch_add(encoded_rows.len() as u64, Ordering::Relaxed);
+        drop(metadata);
+        self.sync_table_state(table_name, false)?;
+
+        Ok(encoded_rows.len())
+    }
+
+    /// Replace rows with new versions: each new row is written as a new tuple,
+    /// the tuple it replaces is deleted, and the primary index follows any key
+    /// that changed
+    /// New tuples are written before the old ones are deleted, so a failed
+    /// write leaves the original rows intact
+    /// Returns the number of rows updated
+    pub fn update_rows(&mut self, table_name: &str, updates: Vec<(TuplePointer, Row)>) -> Result<usize> {
+        self.in_transaction(|db, txid| db.update_rows_in(txid, table_name, updates))
+    }
+
+    /// Update rows as transaction `txid` (see `update_rows`)
+    fn update_rows_in(&mut self, txid: base::TxId, table_name: &str, updates: Vec<(TuplePointer, Row)>) -> Result<usize> {
+        let table_file = self.table_files.get(table_name)
+            .ok_or_else(|| format!("Table not found: {}", table_name))?
+            .clone();
+
+        let metadata_arc = self.tables.get(table_name)
+            .ok_or_else(|| format!("Table not found: {}", table_name))?
+            .clone();
+        let metadata = metadata_arc.read();
+
+        let (old_ptrs, rows): (Vec<TuplePointer>, Vec<Row>) = updates.into_iter().unzip();
+        let encoded_rows = Self::encode_rows(&metadata.schema, &rows)?;
+        if encoded_rows.is_empty() {
+            return Ok(0);
+        }
+
+        // Resolve new primary keys; a new key may only be taken by a row that
+        // is itself being updated
+        let new_keys = match &metadata.primary_index {
+            Some(primary_index_meta) => {
+                let pk_column = metadata.schema.primary_key_index().unwrap_or(0);
+                let pk_name = &metadata.schema.columns[pk_column].name;
+                let index_guard = primary_index_meta.index.lock();
+
+                let updated: HashSet<TuplePointer> = old_ptrs.iter().copied().collect();
+                let mut batch_keys: HashMap<u64, Vec<&crate::types::Value>> = HashMap::with_capacity(rows.len());
+                let mut new_keys = Vec::with_capacity(rows.len());
+                for (row, old_ptr) in rows.iter().zip(&old_ptrs) {
+                    let key = Self::primary_key(row, pk_column)?;
+                    let value = &row.values[pk_column];
+
+                    let taken_in_batch = batch_keys.get(&key)
+                        .is_some_and(|values| values.iter().any(|other| index::key::key_values_equal(other, value)));
+                    if taken_in_batch || self.key_taken(table_name, &**index_guard, pk_column, value, &updated)? {
+                        return Err(Self::key_conflict(table_name, pk_name, value));
+                    }
+
+                    if Self::read_row(&table_file, self.transactions.snapshot(), *old_ptr)?.is_none() {
+                        return Err("Row to update no longer exists".to_string());
+                    }
+                    batch_keys.entry(key).or_default().push(value);
+                    new_keys.push(key);
+                }
+                Some(new_keys)
+            }
+            None => None,
+        };
+
+        // As in Postgres, new row versions may use the room a fillfactor keeps
+        let placed = Self::place_tuples(&table_file, &self.transactions, txid, &encoded_rows, 0)?;
+        let changes = placed.tuple_ptrs.iter().zip(&old_ptrs).zip(&encoded_rows)
+            .map(|((tuple_ptr, old_ptr), tuple)| TupleChange { tuple_ptr: *tuple_ptr, old_tuple_ptr: Some(*old_ptr), tuple: tuple.clone() })
+            .collect();
+        self.log_tuples(table_name, txid, WalEntryType::Update, changes)?;
+        let new_ptrs = Self::write_tuples(&table_file, placed)?;
+
+        Self::delete_tuples(&table_file, &self.transactions, txid, &old_ptrs)?;
+
+        // Every row moved to a new tuple, so it gets entries even when the key
+        // stayed the same; the old tuple's stay until the update commits
+        if let (Some(primary_index_meta), Some(new_keys)) = (&metadata.primary_index, new_keys) {
+            let index_file = self.index_files.get(table_name)
+                .ok_or_else(|| format!("Index file not found for table: {}", table_name))?;
+
+            let mut index_guard = primary_index_meta.index.lock();
+            for (new_key, new_ptr) in new_keys.into_iter().zip(&new_ptrs) {
+                index_guard.insert_entry(new_key, *new_ptr, index_file)
+                    .map_err(|e| format!("Failed to update primary index: {}", e))?;
+            }
+        }
+        let new_rows: Vec<(TuplePointer, Row)> = new_ptrs.into_iter().zip(rows).collect();
+        for (index_meta, index_file) in self.secondary_indexes(table_name, &metadata)? {
+            self.add_index_entries(&metadata.schema, index_meta, index_file, &new_rows)?;
+        }
+        drop(metadata);
+        self.sync_table_state(table_name, false)?;
+
+        Ok(encoded_rows.len())
+    }
+
+    /// Delete rows by marking their tuples deleted; the index entries that
+    /// point at them are removed once the delete commits
+    /// Pointers to tuples that are already gone are skipped
+    /// Returns the number of rows deleted
+    pub fn delete_rows(&mut self, table_name: &str, tuple_ptrs: &[TuplePointer]) -> Result<usize> {
+        self.in_transaction(|db, txid| db.delete_rows_in(txid, table_name, tuple_ptrs))
+    }
+
+    /// Delete rows as transaction `txid` (see `delete_rows`)
+    fn delete_rows_in(&mut self, txid: base::TxId, table_name: &str, tuple_ptrs: &[TuplePointer]) -> Result<usize> {
+        let table_file = self.table_files.get(table_name)
+            .ok_or_else(|| format!("Table not found: {}", table_name))?
+            .clone();
+
+        let metadata_arc = self.tables.get(table_name)
+            .ok_or_else(|| format!("Table not found: {}", table_name))?
+            .clone();
+        let metadata = metadata_arc.read();
+
+        if tuple_ptrs.is_empty() {
+            return Ok(0);
+        }
+        let changes = tuple_ptrs.iter()
+            .map(|tuple_ptr| TupleChange { tuple_ptr: *tuple_ptr, old_tuple_ptr: None, tuple: Vec::new() })
+            .collect();
+        self.log_tuples(table_name, txid, WalEntryType::Delete, changes)?;
+        let deleted = Self::delete_tuples(&table_file, &self.transactions, txid, tuple_ptrs)?;
+        if deleted.is_empty() {
+            return Ok(0);
+        }
+
+        let _ = metadata.row_count_estimate.fetch_update(Ordering::Relaxed, Ordering::Relaxed, |count| {
+            Some(count.saturating_sub(deleted.len() as u64))
+        });
+        drop(metadata);
+        self.sync_table_state(table_name, false)?;
+
+        Ok(deleted.len())
+    }
+
+    /// A table's secondary indexes, each with its index file
+    fn secondary_indexes<'a>(&'a self, table_name: &str, metadata: &'a TableMetadata) -> Result<Vec<(&'a IndexMetadata, &'a Arc<IndexFile>)>> {
+        metadata.secondary_indexes.iter()
+            .map(|index_meta| {
+                let index_file = self.index_files.get(&format!("{}_{}", table_name, index_meta.name))
+                    .ok_or_else(|| format!("Index file not found for secondary index {}", index_meta.name))?;
+                Ok((index_meta, index_file))
+            })
+            .collect()
+    }
+
+    /// Object id of a table, 0 if the catalog has no entry for it
+    fn relid(&self, table_name: &str) -> u32 {
+        self.catalog.get_table(table_name).ok().flatten().map_or(0, |table_meta| table_meta.oid)
+    }
+
+    /// Keys a column value is indexed under: none for NULL, one per element of
+    /// a multi-valued value in an inverted index, otherwise the value's own key
+    #[cfg_attr(not(feature = "extensions"), allow(unused_variables))]
+    fn index_keys(&self, index_meta: &IndexMetadata, inverted: bool, value: &crate::types::Value) -> Result<Vec<u64>> {
+        use crate::types::Value;
+
+        match value {
+            Value::Null => Ok(Vec::new()),
+            #[cfg(feature = "extensions")]
+            Value::Extension { type_oid, data } if inverted => {
+                let elements = self.type_registry.get_by_oid(*type_oid)
+                    .and_then(|type_ext| type_ext.index_elements(data.as_ref()));
+                let Some(elements) = elements else {
+                    return Ok(vec![index_meta.key_for(value)?]);
+                };
+                let mut keys = elements.iter()
+                    .filter(|element| !matches!(element, Value::Null))
+                    .map(|element| index_meta.key_for(element))
+                    .collect::<Result<Vec<u64>>>()?;
+                keys.sort_unstable();
+                keys.dedup();
+                Ok(keys)
+            }
+            _ => Ok(vec![index_meta.key_for(value)?]),
+        }
+    }
+
+    /// Keys a row is indexed under, given the positions of the index's columns
+    /// A multi-column index has one composite key per row, NULLs included, so
+    /// a lookup by its leading columns finds rows whatever the others hold
+    fn row_index_keys(&self, index_meta: &IndexMetadata, columns: &[usize], inverted: bool, row: &Row) -> Result<Vec<u64>> {
+        if let [column] = columns {
+            return match row.get(*column) {
+                Some(value) => self.index_keys(index_meta, inverted, value),
+                None => Ok(Vec::new()),
             };
+        }
+        let values: Vec<crate::types::Value> = columns.iter()
+            .map(|column| row.get(*column).cloned().unwrap_or(crate::types::Value::Null))
+            .collect();
+        Ok(vec![index_meta.composite_key_for(&values)?])
+    }
+
+    /// Positions of an index's columns in a schema, None if one is missing
+    fn index_columns(schema: &Schema, index_meta: &IndexMetadata) -> Option<Vec<usize>> {
+        index_meta.columns.iter().map(|column| schema.get_column_index(column)).collect()
+    }
+
+    /// Add the entries of newly written tuples to an index
+    fn add_index_entries(&self, schema: &Schema, index_meta: &IndexMetadata, index_file: &IndexFile, rows: &[(TuplePointer, Row)]) -> Result<()> {
+        let Some(columns) = Self::index_columns(schema, index_meta) else {
+            return Ok(());
+        };
+        let mut index_guard = index_meta.index.lock();
+        let inverted = index_guard.is_inverted();
+        for (tuple_ptr, row) in rows {
+            for key in self.row_index_keys(index_meta, &columns, inverted, row)? {
+                index_guard.insert_entry(key, *tuple_ptr, index_file)
+                    .map_err(|e| format!("Failed to insert into index {}: {}", index_meta.name, e))?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Remove the entries of tombstoned tuples from an index
+    /// An entry is only removed while it still points at the tuple
+    fn remove_index_entries(&self, schema: &Schema, index_meta: &IndexMetadata, index_file: &IndexFile, rows: &[(TuplePointer, Row)]) -> Result<()> {
+        let Some(columns) = Self::index_columns(schema, index_meta) else {
+            return Ok(());
+        };
+        let mut index_guard = index_meta.index.lock();
+        let inverted = index_guard.is_inverted();
+        for (tuple_ptr, row) in rows {
+            for key in self.row_index_keys(index_meta, &columns, inverted, row)? {
+                index_guard.delete_entry(key, *tuple_ptr, index_file)
+                    .map_err(|e| format!("Failed to delete from index {}: {}", index_meta.name, e))?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Mark tuples deleted by transaction `txid`, one read-modify-write per
+    /// block, releasing the slots of dead tuples in the blocks written
+    /// Returns the pointer and decoded row of every tuple that was still visible
+    fn delete_tuples(table_file: &TableFile, transactions: &mvcc::Transactions, txid: base::TxId, tuple_ptrs: &[TuplePointer]) -> Result<Vec<(TuplePointer, Row)>> {
+        let mut by_block: HashMap<(base::SegmentId, base::BlockId), Vec<base::SlotId>> = HashMap::new();
+        for tuple_ptr in tuple_ptrs {
+            by_block.entry((tuple_ptr.segment_id, tuple_ptr.block_id))
+                .or_default()
+                .push(tuple_ptr.slot_id);
+        }
+
+        let mut deleted = Vec::with_capacity(tuple_ptrs.len());
+        for ((segment_id, block_id), slot_ids) in by_block {
+            let mut block = table_file.read_block(segment_id, block_id)
+                .map_err(|e| format!("Failed to read block: {}", e))?;
+            let mut dirty = false;
+            for slot_id in slot_ids {
+                if slot_id >= block.header().slot_count {
+                    continue;
+                }
+                let Some(mut meta) = block.tuple_meta(slot_id) else { continue };
+                if !transactions.snapshot().sees(&meta) {
+                    continue;
+                }
+                // Another transaction deleted it first and may yet commit
+                if meta.is_deleted() && meta.xmax != txid && transactions.is_running(meta.xmax) {
+                    return Err("could not serialize access due to concurrent update".to_string());
+                }
+                let Some(tuple_bytes) = block.read_tuple(slot_id) else { continue };
+                let (row, _): (Row, usize) = bincode::decode_from_slice(tuple_bytes, bincode::config::standard())
+                    .map_err(|e| format!("Deserialization error: {}", e))?;
+                meta.mark_deleted(txid);
+                block.set_tuple_meta(slot_id, &meta);
+                dirty = true;
+                deleted.push((TuplePointer::new(segment_id, block_id, slot_id), row));
+            }
+            if dirty {
+                transactions.prune(&mut block);
+                table_file.write_block(segment_id, block_id, &block)
+                    .map_err(|e| format!("Failed to write block: {}", e))?;
+            }
+        }
+        Ok(deleted)
+    }
+
+    /// Validate and serialize every row up front so a bad row fails the whole batch
+    /// before anything touches disk
+    fn encode_rows(schema: &Schema, rows: &[Row]) -> Result<Vec<Vec<u8>>> {
+        let mut encoded_rows = Vec::with_capacity(rows.len());
+        for row in rows {
+            if row.len() != schema.len() {
+                return Err(format!(
+                    "Row has {} columns but schema expects {}",
+                    row.len(),
+                    schema.len()
+                ));
+            }
+
+            let row_bytes = bincode::encode_to_vec(row, bincode::config::standard())
+                .map_err(|e| format!("Serialization error: {}", e))?;
+            if row_bytes.len() > base::MAX_TUPLE_SIZE {
+                return Err(format!(
+                    "Row of {} bytes exceeds maximum tuple size of {} bytes",
+                    row_bytes.len(),
+                    base::MAX_TUPLE_SIZE
+                ));
+            }
+            encoded_rows.push(row_bytes);
+        }
+        Ok(encoded_rows)
+    }
+
+    /// Place encoded tuples written by transaction `txid` in the table's free
+    /// space, grouping tuples that land in the same block into a single
+    /// read-modify-write; nothing is written until `write_tuples`, so the
+    /// tuples can be logged where they will go
+    /// Blocks are filled until `reserve` bytes are left (see `fill_reserve`),
+    /// after the slots of dead tuples in them are released
+    fn place_tuples(table_file: &TableFile, transactions: &mvcc::Transactions, txid: base::TxId, encoded_rows: &[Vec<u8>], reserve: usize) -> Result<PlacedTuples> {
+        // Insert into segment 0 (first segment)
+        let segment_id = 0u32;
+        let mut header = table_file.read_segment_header(segment_id)
+            .map_err(|e| format!("Failed to read segment header: {}", e))?;
+        let mut header_dirty = false;
+
+        // Start from the most recently allocated block; it is the only one that may
+        // still have room since earlier blocks were filled before moving on
+        let mut current = match header.last_used_block(TableFile::first_data_block(segment_id)) {
+            Some(block_id) => {
+                let mut block = table_file.read_block(segment_id, block_id)
+                    .map_err(|e| format!("Failed to read block: {}", e))?;
+                let pruned = transactions.prune(&mut block);
+                Some((block_id, block, pruned))
+            }
+            None => None,
+        };
+
+        let meta = base::TupleMeta::new(txid);
+        let mut blocks = Vec::new();
+        let mut tuple_ptrs = Vec::with_capacity(encoded_rows.len());
+        for row_bytes in encoded_rows {
+            loop {
+                if let Some((block_id, block, dirty)) = current.as_mut()
+                    && let Some(slot_id) = block.append_tuple_reserving(&meta, row_bytes, reserve)
+                {
+                    *dirty = true;
+                    tuple_ptrs.push(TuplePointer::new(segment_id, *block_id, slot_id));
+                    break;
+                }
+
+                // Block full: keep it for writing before moving on to a fresh one
+                if let Some((block_id, block, true)) = current.take() {
+                    blocks.push((block_id, block));
+                }
+                let block_id = TableFile::allocate_block_in(segment_id, &mut header)
+                    .ok_or_else(|| "Segment full - need to allocate new segment".to_string())?;
+                header_dirty = true;
+                current = Some((block_id, base::Block::new(), false));
+            }
+        }
+        if let Some((block_id, block, true)) = current {
+            blocks.push((block_id, block));
+        }
+
+        Ok(PlacedTuples {
+            segment_id,
+            header: header_dirty.then_some(header),
+            blocks,
+            tuple_ptrs,
+        })
+    }
+
+    /// Write placed tuples' blocks, then the segment header that makes newly
+    /// allocated blocks visible
+    /// Returns where each tuple was written, in order
+    fn write_tuples(table_file: &TableFile, placed: PlacedTuples) -> Result<Vec<TuplePointer>> {
+        for (block_id, block) in &placed.blocks {
+            table_file.write_block(placed.segment_id, *block_id, block)
+                .map_err(|e| format!("Failed to write block: {}", e))?;
+        }
+        if let Some(header) = &placed.header {
+            table_file.write_segment_header(placed.segment_id, header)
+                .map_err(|e| format!("Failed to write segment header: {}", e))?;
+        }
+        Ok(placed.tuple_ptrs)
+    }
+
+    /// Index key for a row's primary key
+    fn primary_key(row: &Row, pk_column: usize) -> Result<u64> {
+        let key_value = row.get(pk_column)
+            .ok_or_else(|| "Row is missing its primary key column".to_string())?;
+        if matches!(key_value, crate::types::Value::Null) {
+            return Err("Primary key cannot be NULL".to_string());
+        }
+        index::key::encode_key(key_value)
+    }
+
+    /// Whether a visible row other than those in `skip` already has `value`
+    /// as its primary key
+    /// Distinct strings can share an index key, so the rows under it are told
+    /// apart by their full values
+    fn key_taken(
+        &self,
+        table_name: &str,
+        index: &dyn index::Index,
+        pk_column: usize,
+        value: &crate::types::Value,
+        skip: &HashSet<TuplePointer>,
+    ) -> Result<bool> {
+        let table_file = self.table_files.get(table_name)
+            .ok_or_else(|| format!("Table not found: {}", table_name))?;
+        let index_file = self.index_files.get(table_name)
+            .ok_or_else(|| format!("Index file not found for table: {}", table_name))?;
+        let tuple_ptrs = index.search_all(index::key::encode_key(value)?, index_file)
+            .map_err(|e| format!("Failed to search primary index: {}", e))?;
+        for tuple_ptr in tuple_ptrs.into_iter().filter(|tuple_ptr| !skip.contains(tuple_ptr)) {
+            if let Some(existing_row) = Self::read_row(table_file, self.transactions.snapshot(), tuple_ptr)?
+                && existing_row.get(pk_column).is_some_and(|existing| index::key::key_values_equal(existing, value))
+            {
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+
+    /// Error for a primary key value another row already has
+    fn key_conflict(table_name: &str, column: &str, value: &crate::types::Value) -> String {
+        format!(
+            "duplicate key value violates unique constraint \"{}_pkey\": Key ({})=({}) already exists",
+            table_name,
+            column,
+            value.as_string()
+        )
+    }
+
+    /// Bytes of each block an INSERT leaves free under the table's fillfactor
+    fn fill_reserve(&self, table_name: &str) -> usize {
+        let fillfactor = self.catalog.get_table(table_name).ok().flatten()
+            .map_or(catalog::FILLFACTOR_DEFAULT, |table_meta| table_meta.fillfactor);
+        base::BLOCK_SIZE * (100 - fillfactor as usize) / 100
+    }
+
+    /// Read and decode the row a tuple pointer refers to (None if empty or not
+    /// visible to the snapshot)
+    fn read_row(table_file: &TableFile, snapshot: mvcc::Snapshot, tuple_ptr: TuplePointer) -> Result<Option<Row>> {
+        let block = table_file.read_block(tuple_ptr.segment_id, tuple_ptr.block_id)
+            .map_err(|e| format!("Failed to read block: {}", e))?;
+        if tuple_ptr.slot_id >= block.header().slot_count
+            || block.tuple_meta(tuple_ptr.slot_id).is_some_and(|meta| !snapshot.sees(&meta))
+        {
+            return Ok(None);
+        }
+        match block.read_tuple(tuple_ptr.slot_id) {
+            Some(tuple_bytes) => {
+                let (row, _): (Row, usize) = bincode::decode_from_slice(tuple_bytes, bincode::config::standard())
+                    .map_err(|e| format!("Deserialization error: {}", e))?;
+                Ok(Some(row))
+            }
+            None => Ok(None),
+        }
+    }
+
+    /// Read and decode the tuples at `tuple_ptrs` whose headers pass `filter`,
+    /// whether or not they are visible, one read per block
+    fn read_tuples(table_file: &TableFile, tuple_ptrs: &HashSet<TuplePointer>, filter: impl Fn(&base::TupleMeta) -> bool) -> Result<Vec<(TuplePointer, Row)>> {
+        let mut by_block: HashMap<(base::SegmentId, base::BlockId), Vec<base::SlotId>> = HashMap::new();
+        for tuple_ptr in tuple_ptrs {
+            by_block.entry((tuple_ptr.segment_id, tuple_ptr.block_id))
+                .or_default()
+                .push(tuple_ptr.slot_id);
+        }
+
+        let mut tuples = Vec::with_capacity(tuple_ptrs.len());
+        for ((segment_id, block_id), slot_ids) in by_block {
+            let block = table_file.read_block(segment_id, block_id)
+                .map_err(|e| format!("Failed to read block: {}", e))?;
+            for slot_id in slot_ids {
+                if slot_id >= block.header().slot_count
+                    || !block.tuple_meta(slot_id).is_some_and(|meta| filter(&meta))
+                {
+                    continue;
+                }
+                let Some(tuple_bytes) = block.read_tuple(slot_id) else { continue };
+                let (row, _): (Row, usize) = bincode::decode_from_slice(tuple_bytes, bincode::config::standard())
+                    .map_err(|e| format!("Deserialization error: {}", e))?;
+                tuples.push((TuplePointer::new(segment_id, block_id, slot_id), row));
+            }
+        }
+        Ok(tuples)
+    }
+
+    /// Rewrite a table so its live tuples occupy as few blocks as possible, and
+    /// rebuild its indexes at the new tuple locations
+    /// The live tuples are copied block by block into a new file, which then
+    /// replaces the table file by a rename: a crash leaves either the old file
+    /// or the complete new one, and startup rebuilds a primary index that
+    /// doesn't match the rows it finds
+    /// Runs under the database write lock, so queries see either the old or the
+    /// new layout but never a mix
+    pub fn compact_table(&mut self, table_name: &str) -> Result<CompactionStats> {
+        let table_file = self.table_files.get(table_name)
+            .ok_or_else(|| format!("Table not found: {}", table_name))?
+            .clone();
+
+        let segment_count = table_file.segment_count()
+            .map_err(|e| format!("Failed to read table file size: {}", e))?
+            .max(1);
+
+        let mut progress = self.progress.start(progress::Command::Vacuum, self.relid(table_name), table_name, None);
+
+        let mut used_blocks = Vec::new();
+        for segment_id in 0..segment_count {
+            let header = table_file.read_segment_header(segment_id)
+                .map_err(|e| format!("Failed to read segment header: {}", e))?;
+            used_blocks.extend((TableFile::first_data_block(segment_id)..base::BLOCKS_PER_UNCOMPRESSED_SEGMENT as u8)
+                .filter(|&block_id| !header.is_block_free(block_id))
+                .map(|block_id| (segment_id, block_id)));
+        }
+        let blocks_before = used_blocks.len();
+
+        // A file left behind by a compaction that never finished is started over
+        let table_path = table_file.path().to_path_buf();
+        let mut compacted_path = table_path.clone().into_os_string();
+        compacted_path.push(COMPACTED_FILE_SUFFIX);
+        let compacted_path = PathBuf::from(compacted_path);
+        match std::fs::remove_file(&compacted_path) {
+            Ok(()) => debug!(path = %compacted_path.display(), "removed stale file"),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => return Err(format!("Failed to remove stale file {}: {}", compacted_path.display(), e)),
+        }
+        let compacted = TableFile::open(&compacted_path)
+            .map_err(|e| format!("Failed to create {}: {}", compacted_path.display(), e))?;
+
+        // Copy every visible tuple (header and data) in physical order, packed
+        // densely from the first data block of segment 0 up to the table's
+        // fillfactor; the rest are dead or were never committed
+        // Only the block being filled is held in memory
+        progress.set_phase("copying heap", blocks_before as u64);
+        let reserve = self.fill_reserve(table_name);
+        let snapshot = self.transactions.snapshot();
+        let (mut segment_id, mut block_id) = (0, TableFile::first_data_block(0));
+        let mut header = base::SegmentHeader::new(0);
+        let mut block = base::Block::new();
+        let mut live_tuples = 0;
+        let mut blocks_after = 0;
+        for (source_segment_id, source_block_id) in used_blocks {
+            progress.advance(1);
+            if !table_file.contains_block(source_segment_id, source_block_id)
+                .map_err(|e| format!("Failed to read table file size: {}", e))?
+            {
+                continue;
+            }
+            // Older block formats are converted here, which is how table
+            // upgrades rewrite them
+            let source = table_file.read_block_any_version(source_segment_id, source_block_id)
+                .map_err(|e| format!("Failed to read block: {}", e))?;
+            let tuples = migrate::block_tuples(&source)
+                .map_err(|e| format!("Failed to read block {}/{}: {}", source_segment_id, source_block_id, e))?;
+            for (meta, data) in tuples.iter().filter(|(meta, _)| snapshot.sees(meta)) {
+                // Only possible for tuples carried over from an older block format
+                if data.len() > base::MAX_TUPLE_SIZE {
+                    return Err(format!("Tuple of {} bytes no longer fits in a block", data.len()));
+                }
+                while block.append_tuple_reserving(meta, data, reserve).is_none() {
+                    compacted.write_block(segment_id, block_id, &block)
+                        .map_err(|e| format!("Failed to write block: {}", e))?;
+                    header.mark_block_used(block_id);
+                    blocks_after += 1;
+                    block = base::Block::new();
+                    block_id += 1;
+                    if block_id as usize == base::BLOCKS_PER_UNCOMPRESSED_SEGMENT {
+                        compacted.write_segment_header(segment_id, &header)
+                            .map_err(|e| format!("Failed to write segment header: {}", e))?;
+                        segment_id += 1;
+                        block_id = TableFile::first_data_block(segment_id);
+                        header = base::SegmentHeader::new(segment_id);
+                    }
+                }
+                live_tuples += 1;
+            }
+        }
+        if block.header().slot_count > 0 {
+            compacted.write_block(segment_id, block_id, &block)
+                .map_err(|e| format!("Failed to write block: {}", e))?;
+            header.mark_block_used(block_id);
+            blocks_after += 1;
+        }
+        compacted.write_segment_header(segment_id, &header)
+            .map_err(|e| format!("Failed to write segment header: {}", e))?;
+        compacted.sync()
+            .map_err(|e| format!("Failed to sync {}: {}", compacted_path.display(), e))?;
+        drop(compacted);
+
+        // Changes logged before the rename describe the old file, so they have
+        // to be on disk in it by the time the log stops covering them
+        table_file.sync()
+            .map_err(|e| format!("Failed to sync table file: {}", e))?;
+        self.log_ddl(table_name, DdlOperation::Compact)?;
+        std::fs::rename(&compacted_path, &table_path)
+            .map_err(|e| format!("Failed to rename {}: {}", compacted_path.display(), e))?;
+        let table_dir = table_path.parent().unwrap_or(&self.data_dir);
+        std::fs::File::open(table_dir)
+            .and_then(|dir| dir.sync_all())
+            .map_err(|e| format!("Failed to sync {}: {}", table_dir.display(), e))?;
+        let table_file = Arc::new(TableFile::open(&table_path)
+            .map_err(|e| format!("Failed to open table file: {}", e))?);
+        self.table_files.insert(table_name.to_string(), table_file.clone());
+
+        // Tuples moved, so every index starts over from the new layout; entries
+        // of dead tuples would lead to whatever now occupies their old locations
+        progress.set_phase("rebuilding indexes", blocks_after as u64);
+        let metadata_arc = self.get_table(table_name)?;
+        let metadata = metadata_arc.read();
+        let secondary_indexes = self.secondary_indexes(table_name, &metadata)?;
+        if let Some(primary_index_meta) = &metadata.primary_index {
+            let index_file = self.index_files.get(table_name)
+                .ok_or_else(|| format!("Index file not found for table: {}", table_name))?;
+            self.reset_index(primary_index_meta, index_file)?;
+        }
+        for (index_meta, index_file) in &secondary_indexes {
+            self.reset_index(index_meta, index_file)?;
+        }
+        for segment_id in 0..=segment_id {
+            let header = table_file.read_segment_header(segment_id)
+                .map_err(|e| format!("Failed to read segment header: {}", e))?;
+            for block_id in TableFile::first_data_block(segment_id)..base::BLOCKS_PER_UNCOMPRESSED_SEGMENT as u8 {
+                if header.is_block_free(block_id) {
+                    continue;
+                }
+                let block = table_file.read_block(segment_id, block_id)
+                    .map_err(|e| format!("Failed to read block: {}", e))?;
+                let mut rows = Vec::with_capacity(block.header().slot_count as usize);
+                for slot_id in 0..block.header().slot_count {
+                    let Some(data) = block.read_tuple(slot_id) else { continue };
+                    let (row, _): (Row, usize) = bincode::decode_from_slice(data, bincode::config::standard())
+                        .map_err(|e| format!("Deserialization error: {}", e))?;
+                    rows.push((TuplePointer::new(segment_id, block_id, slot_id), row));
+                }
+
+                if let Some(primary_index_meta) = &metadata.primary_index {
+                    let index_file = self.index_files.get(table_name)
+                        .ok_or_else(|| format!("Index file not found for table: {}", table_name))?;
+                    let pk_column = metadata.schema.primary_key_index().unwrap_or(0);
+                    let mut index_guard = primary_index_meta.index.lock();
+                    for (tuple_ptr, row) in &rows {
+                        index_guard.insert_entry(Self::primary_key(row, pk_column)?, *tuple_ptr, index_file)
+                            .map_err(|e| format!("Failed to update primary index: {}", e))?;
+                    }
+                }
+                for (index_meta, index_file) in &secondary_indexes {
+                    self.add_index_entries(&metadata.schema, index_meta, index_file, &rows)?;
+                }
+                progress.advance(1);
+            }
+        }
+
+        let stats = CompactionStats {
+            live_tuples,
+            blocks_before,
+            blocks_after,
+        };
+        // Compaction has just seen every live tuple, so the count is exact
+        metadata.row_count_estimate.store(live_tuples as u64, Ordering::Relaxed);
+        drop(progress);
+        drop(metadata);
+        self.sync_table_state(table_name, true)?;
+
+        debug!(table_name, ?stats, "compacted table");
+        Ok(stats)
+    }
+
+    /// Remove every row of a table by resetting its files rather than deleting
+    /// rows one by one: the table file is cut back to an empty segment 0 and
+    /// each index starts over from an empty root. The schema, the indexes'
+    /// definitions and the table's identity are kept.
+    pub fn truncate_table(&mut self, table_name: &str) -> Result<()> {
+        let table_file = self.table_files.get(table_name)
+            .ok_or_else(|| format!("Table not found: {}", table_name))?
+            .clone();
+        let metadata_arc = self.get_table(table_name)?;
+        let metadata = metadata_arc.read();
+
+        self.log_ddl(table_name, DdlOperation::Truncate)?;
+        // The emptied header goes first, so no block is reachable from it by
+        // the time the file is cut
+        table_file.write_segment_header(0, &base::SegmentHeader::new(0))
+            .map_err(|e| format!("Failed to write segment header: {}", e))?;
+        table_file.truncate_after(None)
+            .map_err(|e| format!("Failed to truncate table file: {}", e))?;
+        table_file.set_next_segment_id(1)
+            .map_err(|e| format!("Failed to update next segment id: {}", e))?;
+
+        let mut indexes = self.secondary_indexes(table_name, &metadata)?;
+        if let Some(primary_index_meta) = &metadata.primary_index {
+            let index_file = self.index_files.get(table_name)
+                .ok_or_else(|| format!("Index file not found for table: {}", table_name))?;
+            indexes.push((primary_index_meta, index_file));
+        }
+        for (index_meta, index_file) in indexes {
+            self.reset_index(index_meta, index_file)?;
+        }
+
+        metadata.row_count_estimate.store(0, Ordering::Relaxed);
+        drop(metadata);
+        self.sync_table_state(table_name, true)?;
+
+        debug!(table_name, "truncated table");
+        Ok(())
+    }
+
+    /// Record the table's segment and primary index page allocation high-water
+    /// marks, and its row count estimate, in the catalog
+    /// Unless forced, the catalog is only rewritten when an allocation counter
+    /// moved since the last save, so the persisted row count trails the table
+    fn sync_table_state(&mut self, table_name: &str, force: bool) -> Result<()> {
+        let next_segment_id = self.table_files.get(table_name)
+            .map(|table_file| table_file.next_segment_id());
+        let next_page_id = self.index_files.get(table_name)
+            .map(|index_file| index_file.next_page_id());
+        let row_count = self.tables.get(table_name)
+            .map(|metadata_arc| metadata_arc.read().row_count_estimate.load(Ordering::Relaxed));
+
+        let Some(table_meta) = self.catalog.get_table_mut(table_name) else {
+            return Ok(());
+        };
+        let mut changed = false;
+        if let Some(next_segment_id) = next_segment_id
+            && table_meta.next_segment_id != next_segment_id
+        {
+            table_meta.next_segment_id = next_segment_id;
+            changed = true;
+        }
+        if let (Some(next_page_id), Some(index_meta)) = (next_page_id, table_meta.primary_index.as_mut())
+            && index_meta.next_page_id != next_page_id
+        {
+            index_meta.next_page_id = next_page_id;
+            changed = true;
+        }
+
+        // Persist the row count once it drifts by a tenth from the saved value,
+        // which keeps catalog writes logarithmic in the table size
+        if let Some(row_count) = row_count {
+            let saved = table_meta.row_count_estimate.unwrap_or(0);
+            if row_count != saved && row_count.abs_diff(saved) * 10 >= saved {
+                changed = true;
+            }
+        }
+
+        if changed || force {
+            if row_count.is_some() {
+                table_meta.row_count_estimate = row_count;
+            }
+            self.save_catalog_to_disk()?;
+        }
+        Ok(())
+    }
+
+    /// System view a table name refers to, unless a user table shadows it
+    fn system_view(&self, table_name: &str) -> Option<system::SystemView> {
+        if self.tables.contains_key(table_name) {
+            return None;
+        }
+        system::SystemView::from_name(table_name)
+    }
+
+    pub fn scan_table(&self, table_name: &str) -> Result<Vec<Row>> {
+        if let Some(view) = self.system_view(table_name) {
+            return Ok(view.rows(&self.catalog, &self.progress));
+        }
+
+        Ok(self.scan_table_tuples(table_name)?
+            .into_iter()
+            .map(|(_, row)| row)
+            .collect())
+    }
+
+    /// Scan a table's visible rows along with where each is stored, so callers
+    /// can rewrite the rows they select
+    pub fn scan_table_tuples(&self, table_name: &str) -> Result<Vec<(TuplePointer, Row)>> {
+        let table_file = self.table_files.get(table_name)
+            .ok_or_else(|| format!("Table not found: {}", table_name))?;
+        let snapshot = self.transactions.snapshot();
+
+        let mut rows = Vec::new();
+
+        // Scan segment 0 (first segment allocated)
+        let segment_id = 0u32;
+        let header = table_file.read_segment_header(segment_id)
+            .map_err(|e| format!("Failed to read segment header: {}", e))?;
+
+        // Scan all used blocks
+        for block_id in 0..base::BLOCKS_PER_UNCOMPRESSED_SEGMENT as u8 {
+            if !header.is_block_free(block_id) {
+                let block = table_file.read_block(segment_id, block_id)
+                    .map_err(|e| format!("Failed to read block: {}", e))?;
+
+                // Read all slots in block
+                let slot_count = block.header().slot_count;
+                for slot_id in 0..slot_count {
+                    if block.tuple_meta(slot_id).is_some_and(|meta| !snapshot.sees(&meta)) {
+                        continue;
+                    }
+                    if let Some(tuple_bytes) = block.read_tuple(slot_id) {
+                        let (row, _): (Row, usize) = bincode::decode_from_slice(tuple_bytes, bincode::config::standard())
+                            .map_err(|e| format!("Deserialization error: {}", e))?;
+                        rows.push((TuplePointer::new(segment_id, block_id, slot_id), row));
+                    }
+                }
+            }
+        }
+
+        Ok(rows)
+    }
+
+    pub fn get_schema(&self, table_name: &str) -> Result<Schema> {
+        if let Some(view) = self.system_view(table_name) {
+            return Ok(view.schema());
+        }
+
+        let metadata_arc = self.get_table(table_name)?;
+        let metadata = metadata_arc.read();
+        Ok(metadata.schema.clone())
+    }
+
+    /// Rename a table
+    /// Files keep their names; the catalog's file paths map the new name to them
+    pub fn rename_table(&mut self, old_name: &str, new_name: &str) -> Result<()> {
+        if !self.tables.contains_key(old_name) {
+            return Err(format!("Table not found: {}", old_name));
+        }
+        if self.catalog.is_temporary(old_name) {
+            return Err(format!("Renaming temporary table {} is not supported", old_name));
+        }
+        if self.tables.contains_key(new_name) {
+            return Err(format!("Table already exists: {}", new_name));
+        }
+        if self.catalog.get_sequence(new_name).is_some() {
+            return Err(format!("A sequence named {} already exists", new_name));
+        }
+
+        self.log_ddl(old_name, DdlOperation::Rename)?;
+        self.rename_catalog_table(old_name, new_name)?;
+        if let Err(e) = self.save_catalog_to_disk() {
+            self.rename_catalog_table(new_name, old_name)?;
+            return Err(e);
+        }
+
+        // The catalog is committed; move the runtime state over to the new name
+        if let Some(metadata_arc) = self.tables.remove(old_name) {
+            let secondary_index_names: Vec<String> = {
+                let mut metadata = metadata_arc.write();
+                metadata.name = new_name.to_string();
+                metadata.secondary_indexes.iter().map(|index_meta| index_meta.name.clone()).collect()
+            };
+            for index_name in secondary_index_names {
+                if let Some(index_file) = self.index_files.remove(&format!("{}_{}", old_name, index_name)) {
+                    self.index_files.insert(format!("{}_{}", new_name, index_name), index_file);
+                }
+            }
+            self.tables.insert(new_name.to_string(), metadata_arc);
+        }
+        if let Some(table_file) = self.table_files.remove(old_name) {
+            self.table_files.insert(new_name.to_string(), table_file);
+        }
+        if let Some(index_file) = self.index_files.remove(old_name) {
+            self.index_files.insert(new_name.to_string(), index_file);
+        }
+
+        debug!(old_name, new_name, "renamed table");
+        Ok(())
+    }
+
+    fn rename_catalog_table(&mut self, old_name: &str, new_name: &str) -> Result<()> {
+        let mut table_meta = self.catalog.remove_table(old_name)
+            .map_err(|e| format!("Failed to update catalog: {}", e))?
+            .ok_or_else(|| format!("Table not found: {}", old_name))?;
+        table_meta.name = new_name.to_string();
+        self.catalog.add_table(table_meta)
+            .map_err(|e| format!("Failed to update catalog: {}", e))?;
+        for table_meta in self.catalog.all_tables_mut() {
+            for foreign_key in &mut table_meta.foreign_keys {
+                if foreign_key.referenced_table == old_name {
+                    foreign_key.referenced_table = new_name.to_string();
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Rename a column, carrying its comment and any index on it along
+    /// `checks` are the table's CHECK constraints rewritten to the new name,
+    /// saved with the rename
+    pub fn rename_column(&mut self, table_name: &str, old_column: &str, new_column: &str, checks: Vec<CheckConstraint>) -> Result<()> {
+        let metadata_arc = self.get_table(table_name)?;
+        let mut metadata = metadata_arc.write();
+
+        let column_idx = metadata.schema.get_column_index(old_column)
+            .ok_or_else(|| format!("Column not found: {}.{}", table_name, old_column))?;
+        if metadata.schema.get_column_index(new_column).is_some() {
+            return Err(format!("Column already exists: {}.{}", table_name, new_column));
+        }
+        let old_column = metadata.schema.columns[column_idx].name.clone();
+
+        let table_meta = self.catalog.get_table_mut(table_name)
+            .ok_or_else(|| format!("Table not found: {}", table_name))?;
+        Self::rename_catalog_column(table_meta, column_idx, new_column);
+        let old_checks = std::mem::replace(&mut table_meta.checks, checks);
+        self.rename_referenced_column(table_name, &old_column, new_column);
+        if let Err(e) = self.save_catalog_to_disk() {
+            if let Some(table_meta) = self.catalog.get_table_mut(table_name) {
+                Self::rename_catalog_column(table_meta, column_idx, &old_column);
+                table_meta.checks = old_checks;
+            }
+            self.rename_referenced_column(table_name, new_column, &old_column);
+            return Err(e);
+        }
+
+        let metadata = &mut *metadata;
+        metadata.schema.columns[column_idx].name = new_column.to_string();
+        let indexes = metadata.primary_index.iter_mut().chain(metadata.secondary_indexes.iter_mut());
+        for column in indexes.flat_map(|index_meta| &mut index_meta.columns) {
+            if column.eq_ignore_ascii_case(&old_column) {
+                *column = new_column.to_string();
+            }
+        }
+
+        debug!(table_name, old_column = %old_column, new_column, "renamed column");
+        Ok(())
+    }
+
+    fn rename_catalog_column(table_meta: &mut catalog::TableFileMetadata, column_idx: usize, new_column: &str) {
+        let column = &mut table_meta.schema.columns[column_idx];
+        let old_column = std::mem::replace(&mut column.name, new_column.to_string());
+        let comment = table_meta.column_comment(&old_column).map(str::to_string);
+        if comment.is_some() {
+            table_meta.set_column_comment(&old_column, None);
+            table_meta.set_column_comment(new_column, comment);
+        }
+        if let Some(ttl) = &mut table_meta.ttl
+            && ttl.column == old_column
+        {
+            ttl.column = new_column.to_string();
+        }
+        for foreign_key in table_meta.foreign_keys.iter_mut().filter(|foreign_key| foreign_key.column == old_column) {
+            foreign_key.column = new_column.to_string();
+        }
+        for (column, _) in table_meta.serial_columns.iter_mut().filter(|(column, _)| *column == old_column) {
+            *column = new_column.to_string();
+        }
+    }
 
-            // Get index file
-            let index_file = self.index_files.get(table_name)
-                .ok_or_else(|| format!("Index file not found for table: {}", table_name))?;
+    /// Point the foreign keys that reference a renamed column at its new name
+    fn rename_referenced_column(&mut self, table_name: &str, old_column: &str, new_column: &str) {
+        for table_meta in self.catalog.all_tables_mut() {
+            let foreign_keys = table_meta.foreign_keys.iter_mut()
+                .filter(|foreign_key| foreign_key.referenced_table == table_name && foreign_key.referenced_column == old_column);
+            for foreign_key in foreign_keys {
+                foreign_key.referenced_column = new_column.to_string();
+            }
+        }
+    }
 
-            // Lock index and insert
-            let mut index_guard = primary_index_meta.index.lock();
-            index_guard.insert(key, tuple_ptr, index_file)
-                .map_err(|e| format!("Failed to insert into primary index: {}", e))?;
+    /// Bytes on disk used by a table's data file and all of its index files
+    pub fn table_size_bytes(&self, table_name: &str) -> Result<u64> {
+        let metadata_arc = self.get_table(table_name)?;
+        let metadata = metadata_arc.read();
+
+        let mut files: Vec<&Arc<IndexFile>> = self.index_files.get(table_name).into_iter().collect();
+        for index_meta in &metadata.secondary_indexes {
+            files.extend(self.index_files.get(&format!("{}_{}", table_name, index_meta.name)));
         }
 
-        Ok(())
+        let table_file = self.table_files.get(table_name)
+            .ok_or_else(|| format!("Table not found: {}", table_name))?;
+        let mut total = table_file.size_bytes()
+            .map_err(|e| format!("Failed to read table file size: {}", e))?;
+        for index_file in files {
+            total += index_file.size_bytes()
+                .map_err(|e| format!("Failed to read index file size: {}", e))?;
+        }
+        Ok(total)
     }
 
-    pub fn scan_table(&self, table_name: &str) -> Result<Vec<Row>> {
+    /// Blocks spanned by a table's data file, for the planner's cost estimates
+    pub fn table_pages(&self, table_name: &str) -> Result<u64> {
         let table_file = self.table_files.get(table_name)
             .ok_or_else(|| format!("Table not found: {}", table_name))?;
+        let size = table_file.size_bytes()
+            .map_err(|e| format!("Failed to read table file size: {}", e))?;
+        Ok(size.div_ceil(base::BLOCK_SIZE as u64))
+    }
 
-        let mut rows = Vec::new();
+    /// Exact number of live rows, counted with a full scan
+    pub fn row_count(&self, table_name: &str) -> Result<u64> {
+        Ok(self.scan_table(table_name)?.len() as u64)
+    }
 
-        // Scan segment 0 (first segment allocated)
-        let segment_id = 0u32;
-        let header = table_file.read_segment_header(segment_id)
-            .map_err(|e| format!("Failed to read segment header: {}", e))?;
+    /// Row count maintained incrementally as rows are written, without a scan
+    /// Exact, since the startup self-test counts every table
+    pub fn approx_row_count(&self, table_name: &str) -> Result<u64> {
+        Ok(self.get_table(table_name)?.read().row_count_estimate.load(Ordering::Relaxed))
+    }
 
-        // Scan all used blocks
-        for block_id in 0..base::BLOCKS_PER_UNCOMPRESSED_SEGMENT as u8 {
-            if !header.is_block_free(block_id) {
-                let block = table_file.read_block(segment_id, block_id)
-                    .map_err(|e| format!("Failed to read block: {}", e))?;
+    /// Copy the database to the `dest` location, in full or, given an earlier
+    /// backup as `base`, only the blocks changed since that backup (see
+    /// `backup` and `archive`)
+    /// Callers must hold the database lock so no write lands mid-copy
+    pub fn backup(&self, dest: &str, base: Option<&str>) -> Result<backup::BackupStats> {
+        let dest = archive::open(dest)?;
+        let base = base.map(archive::open).transpose()?;
+        backup::take_backup(&self.data_dir, &self.data_file_names()?, dest.as_ref(), base.as_deref())
+    }
 
-                // Read all slots in block
-                let slot_count = block.header().slot_count;
-                for slot_id in 0..slot_count {
-                    if let Some(tuple_bytes) = block.read_tuple(slot_id) {
-                        let (row, _): (Row, usize) = bincode::decode_from_slice(tuple_bytes, bincode::config::standard())
-                            .map_err(|e| format!("Deserialization error: {}", e))?;
-                        rows.push(row);
-                    }
+    /// Write the whole database to a single snapshot file (see `snapshot`)
+    /// Callers must hold the database lock so no write lands mid-copy
+    pub fn snapshot(&self, out: &Path) -> Result<snapshot::SnapshotStats> {
+        snapshot::create(&self.data_dir, &self.data_file_names()?, out)
+    }
+
+    /// Names of the files in the data directory that make up the database:
+    /// the catalog, the WAL and every table and index file
+    fn data_file_names(&self) -> Result<Vec<String>> {
+        let mut file_names = vec![CATALOG_MARKER_FILE.to_string()];
+        file_names.extend((0..2u8).map(|segment| format!("catalog_{}.db", segment)));
+        file_names.push(WAL_FILE.to_string());
+        for table_meta in self.catalog.all_tables() {
+            if self.catalog.is_temporary(&table_meta.name) {
+                continue;
+            }
+            let paths = std::iter::once(&table_meta.file_path)
+                .chain(table_meta.primary_index.iter().chain(&table_meta.secondary_indexes)
+                    .map(|index_meta| &index_meta.file_path));
+            for path in paths {
+                let name = Path::new(path).file_name()
+                    .ok_or_else(|| format!("Invalid data file path: {}", path))?;
+                file_names.push(name.to_string_lossy().to_string());
+            }
+        }
+        file_names.retain(|name| self.data_path(name).exists());
+        Ok(file_names)
+    }
+
+    /// Set or clear (None) the comment on a table
+    pub fn set_table_comment(&mut self, table_name: &str, comment: Option<String>) -> Result<()> {
+        let table_meta = self.catalog.get_table_mut(table_name)
+            .ok_or_else(|| format!("Table not found: {}", table_name))?;
+        table_meta.comment = comment;
+        self.save_catalog_to_disk()
+    }
+
+    /// Change the storage options of a table that are given, leaving the rest
+    /// as they are
+    /// A new fillfactor applies to blocks filled from now on; blocks already
+    /// written keep the rows they have until VACUUM FULL repacks them
+    pub fn set_table_options(&mut self, table_name: &str, options: TableOptions) -> Result<()> {
+        let schema = self.get_schema(table_name)?;
+        let ttl = options.ttl
+            .map(|ttl| ttl.map(|policy| Self::check_ttl(table_name, &schema, policy)).transpose())
+            .transpose()?;
+        let fillfactor = options.fillfactor.map(Self::check_fillfactor).transpose()?;
+        let table_meta = self.catalog.get_table_mut(table_name)
+            .ok_or_else(|| format!("Table not found: {}", table_name))?;
+        if let Some(ttl) = ttl {
+            table_meta.ttl = ttl;
+        }
+        if let Some(fillfactor) = fillfactor {
+            table_meta.fillfactor = fillfactor;
+        }
+        self.save_catalog_to_disk()
+    }
+
+    /// Validate a fillfactor, a percentage from `catalog::FILLFACTOR_MIN` to 100
+    fn check_fillfactor(fillfactor: i64) -> Result<u8> {
+        u8::try_from(fillfactor).ok()
+            .filter(|fillfactor| (catalog::FILLFACTOR_MIN..=100).contains(fillfactor))
+            .ok_or_else(|| format!(
+                "value {} out of bounds for option \"fillfactor\": valid values are between \"{}\" and \"100\"",
+                fillfactor, catalog::FILLFACTOR_MIN
+            ))
+    }
+
+    /// Validate a retention policy against the table's schema, returning it
+    /// with the column named as the schema spells it
+    fn check_ttl(table_name: &str, schema: &Schema, mut policy: TtlPolicy) -> Result<TtlPolicy> {
+        let column_idx = schema.get_column_index(&policy.column)
+            .ok_or_else(|| format!("Column not found: {}.{}", table_name, policy.column))?;
+        let column = &schema.columns[column_idx];
+        if column.data_type != crate::types::DataType::Int {
+            return Err(format!(
+                "TTL column {}.{} must be an integer of seconds since the epoch, not {:?}",
+                table_name, column.name, column.data_type
+            ));
+        }
+        if policy.seconds == 0 {
+            return Err("TTL must be at least one second".to_string());
+        }
+        policy.column = column.name.clone();
+        Ok(policy)
+    }
+
+    /// CHECK constraints of a table
+    pub fn table_checks(&self, table_name: &str) -> Result<Vec<CheckConstraint>> {
+        self.catalog.get_table(table_name)
+            .map_err(|e| format!("Failed to read catalog: {}", e))?
+            .map(|table_meta| table_meta.checks.clone())
+            .ok_or_else(|| format!("Table not found: {}", table_name))
+    }
+
+    /// FOREIGN KEY constraints of a table
+    /// SERIAL columns of a table and the sequences that fill them
+    pub fn serial_columns(&self, table_name: &str) -> Result<Vec<(String, String)>> {
+        self.catalog.get_table(table_name)
+            .map_err(|e| format!("Failed to read catalog: {}", e))?
+            .map(|table_meta| table_meta.serial_columns.clone())
+            .ok_or_else(|| format!("Table not found: {}", table_name))
+    }
+
+    pub fn table_foreign_keys(&self, table_name: &str) -> Result<Vec<ForeignKey>> {
+        self.catalog.get_table(table_name)
+            .map_err(|e| format!("Failed to read catalog: {}", e))?
+            .map(|table_meta| table_meta.foreign_keys.clone())
+            .ok_or_else(|| format!("Table not found: {}", table_name))
+    }
+
+    /// FOREIGN KEY constraints referencing a table, each with the table it is on
+    pub fn referencing_foreign_keys(&self, table_name: &str) -> Vec<(String, ForeignKey)> {
+        let mut referencing: Vec<(String, ForeignKey)> = self.catalog.all_tables().into_iter()
+            .flat_map(|table_meta| table_meta.foreign_keys.iter()
+                .filter(|foreign_key| foreign_key.referenced_table == table_name)
+                .map(|foreign_key| (table_meta.name.clone(), foreign_key.clone())))
+            .collect();
+        // Catalog order is arbitrary; errors should name the same constraint every time
+        referencing.sort_by(|a, b| (&a.0, &a.1.name).cmp(&(&b.0, &b.1.name)));
+        referencing
+    }
+
+    /// Validate a foreign key of table `table_name` (with `schema`, which may
+    /// not be created yet), returning it with its columns named as the schemas
+    /// spell them
+    /// An empty referenced column means the referenced table's primary key
+    fn check_foreign_key(&self, table_name: &str, schema: &Schema, mut foreign_key: ForeignKey) -> Result<ForeignKey> {
+        let column_idx = schema.get_column_index(&foreign_key.column)
+            .ok_or_else(|| format!("Column not found: {}.{}", table_name, foreign_key.column))?;
+        if self.catalog.is_temporary(&foreign_key.referenced_table) && !self.catalog.is_temporary(table_name) {
+            return Err("constraints on permanent tables may reference only permanent tables".to_string());
+        }
+        let referenced_schema = if foreign_key.referenced_table == table_name {
+            schema.clone()
+        } else {
+            self.get_schema(&foreign_key.referenced_table)?
+        };
+        let referenced_idx = if foreign_key.referenced_column.is_empty() {
+            referenced_schema.primary_key_index()
+                .ok_or_else(|| format!("Table {} has no primary key to reference", foreign_key.referenced_table))?
+        } else {
+            referenced_schema.get_column_index(&foreign_key.referenced_column)
+                .ok_or_else(|| format!("Column not found: {}.{}", foreign_key.referenced_table, foreign_key.referenced_column))?
+        };
+        let column = &schema.columns[column_idx];
+        let referenced = &referenced_schema.columns[referenced_idx];
+        // Referenced rows are found through the primary index, the only
+        // constraint that keeps values unique
+        if !referenced.is_primary_key {
+            return Err(format!(
+                "there is no unique constraint matching given keys for referenced table \"{}\"",
+                foreign_key.referenced_table
+            ));
+        }
+        if column.data_type != referenced.data_type {
+            return Err(format!(
+                "foreign key constraint \"{}\" cannot be implemented: key columns \"{}\" and \"{}\" are of incompatible types: {:?} and {:?}",
+                foreign_key.name, column.name, referenced.name, column.data_type, referenced.data_type
+            ));
+        }
+        foreign_key.column = column.name.clone();
+        foreign_key.referenced_column = referenced.name.clone();
+        Ok(foreign_key)
+    }
+
+    /// Tables with a retention policy
+    pub fn ttl_tables(&self) -> Vec<(String, TtlPolicy)> {
+        self.catalog.all_tables().into_iter()
+            .filter_map(|table_meta| table_meta.ttl.clone().map(|ttl| (table_meta.name.clone(), ttl)))
+            .collect()
+    }
+
+    /// Delete the rows of a table that its retention policy has expired as of
+    /// `now` (seconds since the epoch)
+    /// Returns the number of rows deleted
+    pub fn expire_rows(&mut self, table_name: &str, now: i64) -> Result<usize> {
+        let Some(ttl) = self.table_catalog_entry(table_name)?.ttl else {
+            return Ok(0);
+        };
+        let schema = self.get_schema(table_name)?;
+        let column_idx = schema.get_column_index(&ttl.column)
+            .ok_or_else(|| format!("Column not found: {}.{}", table_name, ttl.column))?;
+        let cutoff = now.saturating_sub(i64::try_from(ttl.seconds).unwrap_or(i64::MAX));
+
+        let expired: Vec<TuplePointer> = self.scan_table_tuples(table_name)?
+            .into_iter()
+            .filter(|(_, row)| matches!(row.values.get(column_idx), Some(crate::types::Value::Int(ts)) if *ts < cutoff))
+            .map(|(tuple_ptr, _)| tuple_ptr)
+            .collect();
+        if expired.is_empty() {
+            return Ok(0);
+        }
+        let deleted = self.delete_rows(table_name, &expired)?;
+        info!(table = table_name, rows = deleted, "expired rows");
+        Ok(deleted)
+    }
+
+    /// Set or clear (None) the comment on a column
+    pub fn set_column_comment(&mut self, table_name: &str, column_name: &str, comment: Option<String>) -> Result<()> {
+        let table_meta = self.catalog.get_table_mut(table_name)
+            .ok_or_else(|| format!("Table not found: {}", table_name))?;
+        let column_idx = table_meta.schema.get_column_index(column_name)
+            .ok_or_else(|| format!("Column not found: {}.{}", table_name, column_name))?;
+        let column_name = table_meta.schema.columns[column_idx].name.clone();
+        table_meta.set_column_comment(&column_name, comment);
+        self.save_catalog_to_disk()
+    }
+
+    pub fn create_sequence(&mut self, name: String, options: SequenceOptions) -> Result<()> {
+        if self.catalog.get_sequence(&name).is_some() {
+            return Err(format!("Sequence already exists: {}", name));
+        }
+        if self.tables.contains_key(&name) {
+            return Err(format!("A table named {} already exists", name));
+        }
+
+        let sequence_meta = options.into_metadata(name.clone(), self.catalog.next_oid())?;
+        self.sequences.insert(name.clone(), SequenceCache::new(&sequence_meta));
+        self.catalog.add_sequence(sequence_meta);
+        if let Err(e) = self.save_catalog_to_disk() {
+            self.catalog.remove_sequence(&name);
+            self.sequences.remove(&name);
+            return Err(e);
+        }
+
+        debug!(sequence = %name, "sequence created");
+        Ok(())
+    }
+
+    pub fn drop_sequence(&mut self, name: &str) -> Result<()> {
+        // A SERIAL column's sequence lives as long as its table
+        let owner = self.catalog.all_tables().into_iter().find_map(|table_meta| {
+            table_meta.serial_columns.iter()
+                .find(|(_, sequence)| sequence == name)
+                .map(|(column, _)| format!("{}.{}", table_meta.name, column))
+        });
+        if let Some(owner) = owner {
+            return Err(format!(
+                "cannot drop sequence {} because column {} uses it",
+                name, owner
+            ));
+        }
+        let sequence_meta = self.catalog.remove_sequence(name)
+            .ok_or_else(|| format!("Sequence not found: {}", name))?;
+        if let Err(e) = self.save_catalog_to_disk() {
+            self.catalog.add_sequence(sequence_meta);
+            return Err(e);
+        }
+        self.sequences.remove(name);
+
+        debug!(sequence = %name, "sequence dropped");
+        Ok(())
+    }
+
+    pub fn sequence_exists(&self, name: &str) -> bool {
+        self.catalog.get_sequence(name).is_some()
+    }
+
+    pub fn create_schema(&mut self, name: String) -> Result<()> {
+        if self.schema_exists(&name) {
+            return Err(format!("schema \"{}\" already exists", name));
+        }
+
+        let schema_meta = SchemaMetadata { name: name.clone(), oid: self.catalog.next_oid() };
+        self.catalog.add_schema(schema_meta);
+        if let Err(e) = self.save_catalog_to_disk() {
+            self.catalog.remove_schema(&name);
+            return Err(e);
+        }
+
+        debug!(schema = %name, "schema created");
+        Ok(())
+    }
+
+    /// Drop an empty schema; its tables would have to be dropped first
+    pub fn drop_schema(&mut self, name: &str) -> Result<()> {
+        if !self.catalog.all_schemas().iter().any(|schema_meta| schema_meta.name == name) {
+            return Err(format!("schema \"{}\" does not exist", name));
+        }
+        let prefix = format!("{}.", name);
+        if self.catalog.all_tables().iter().any(|table_meta| table_meta.name.starts_with(&prefix)) {
+            return Err(format!("cannot drop schema \"{}\" because other objects depend on it", name));
+        }
+        let schema_meta = self.catalog.remove_schema(name)
+            .ok_or_else(|| format!("schema \"{}\" does not exist", name))?;
+        if let Err(e) = self.save_catalog_to_disk() {
+            self.catalog.add_schema(schema_meta);
+            return Err(e);
+        }
+
+        debug!(schema = %name, "schema dropped");
+        Ok(())
+    }
+
+    /// Whether `name` is `public` or a schema made by CREATE SCHEMA
+    pub fn schema_exists(&self, name: &str) -> bool {
+        name == PUBLIC_SCHEMA || self.catalog.get_schema(name).is_some()
+    }
+
+    /// Advance a sequence and return its new value
+    /// Only every `SEQUENCE_LOG_VALS`th call writes to the WAL
+    pub fn nextval(&mut self, name: &str) -> Result<i64> {
+        let sequence_meta = self.catalog.get_sequence(name)
+            .ok_or_else(|| format!("Sequence not found: {}", name))?;
+        let cache = self.sequences.get(name).copied()
+            .unwrap_or_else(|| SequenceCache::new(sequence_meta));
+
+        let next = if cache.is_called {
+            sequence::step(sequence_meta, cache.last_value).ok_or_else(|| {
+                let bound = if sequence_meta.increment > 0 { "maximum" } else { "minimum" };
+                let value = if sequence_meta.increment > 0 { sequence_meta.max_value } else { sequence_meta.min_value };
+                format!("nextval: reached {} value of sequence \"{}\" ({})", bound, name, value)
+            })?
+        } else {
+            cache.last_value
+        };
+
+        let mut log_cnt = cache.log_cnt;
+        if log_cnt == 0 {
+            // Reserve a batch by logging its last value; recovery resumes after it
+            let mut reserved = next;
+            log_cnt = 1;
+            while log_cnt < sequence::SEQUENCE_LOG_VALS {
+                match sequence::step(sequence_meta, reserved) {
+                    Some(value) => reserved = value,
+                    None => break,
                 }
+                log_cnt += 1;
             }
+            self.log_sequence(SequenceRecord {
+                name: name.to_string(),
+                last_value: reserved,
+                is_called: true,
+            })?;
         }
 
-        Ok(rows)
+        self.sequences.insert(name.to_string(), SequenceCache {
+            last_value: next,
+            is_called: true,
+            log_cnt: log_cnt - 1,
+        });
+        Ok(next)
     }
 
-    pub fn get_schema(&self, table_name: &str) -> Result<Schema> {
-        let metadata_arc = self.get_table(table_name)?;
-        let metadata = metadata_arc.read();
-        Ok(metadata.schema.clone())
+    /// Set a sequence's current value; with `is_called` false the next nextval
+    /// returns `value` itself
+    pub fn setval(&mut self, name: &str, value: i64, is_called: bool) -> Result<i64> {
+        let sequence_meta = self.catalog.get_sequence(name)
+            .ok_or_else(|| format!("Sequence not found: {}", name))?;
+        if value < sequence_meta.min_value || value > sequence_meta.max_value {
+            return Err(format!(
+                "setval: value {} is out of bounds for sequence \"{}\" ({}..{})",
+                value, name, sequence_meta.min_value, sequence_meta.max_value
+            ));
+        }
+
+        self.log_sequence(SequenceRecord {
+            name: name.to_string(),
+            last_value: value,
+            is_called,
+        })?;
+        self.sequences.insert(name.to_string(), SequenceCache {
+            last_value: value,
+            is_called,
+            log_cnt: 0,
+        });
+        Ok(value)
+    }
+
+    /// Durably log a sequence's state and apply it to the in-memory catalog,
+    /// so the next catalog save carries it and the WAL can be discarded
+    fn log_sequence(&mut self, record: SequenceRecord) -> Result<()> {
+        self.log_change(WalEntryType::Sequence, &record)?;
+
+        if let Some(sequence_meta) = self.catalog.get_sequence_mut(&record.name) {
+            sequence_meta.last_value = record.last_value;
+            sequence_meta.is_called = record.is_called;
+        }
+        Ok(())
+    }
+
+    /// Append a record to the WAL ahead of the change it describes, and flush
+    /// it unless commits are asynchronous (--synchronous-commit=off)
+    /// A WAL grown past `MAX_WAL_SIZE` is checkpointed before the record goes
+    /// in, which keeps it bounded between catalog saves
+    fn log_change<T: Encode>(&mut self, entry_type: WalEntryType, record: &T) -> Result<()> {
+        if self.wal.as_ref().is_some_and(|wal| wal.next_offset() >= MAX_WAL_SIZE) {
+            self.checkpoint()?;
+        }
+        let wal = self.wal.as_mut()
+            .ok_or_else(|| "WAL is unavailable, changes cannot be logged".to_string())?;
+        let payload = bincode::encode_to_vec(record, bincode::config::standard())
+            .map_err(|e| format!("Failed to encode WAL record: {}", e))?;
+        let lsn = wal.next_offset();
+        wal.append(&WalEntry::new(entry_type, payload, lsn))
+            .map_err(|e| format!("Failed to append to WAL: {}", e))?;
+        if self.synchronous_commit {
+            wal.sync()
+                .map_err(|e| format!("Failed to sync WAL: {}", e))?;
+        }
+        Ok(())
+    }
+
+    /// Log one statement's changes to a table's tuples before they are
+    /// written; temporary tables don't outlive the server, so aren't logged
+    fn log_tuples(&mut self, table_name: &str, txid: base::TxId, entry_type: WalEntryType, changes: Vec<TupleChange>) -> Result<()> {
+        let (written, deleted): (Vec<_>, Vec<_>) = match entry_type {
+            WalEntryType::Delete => (Vec::new(), changes.iter().map(|change| Some(change.tuple_ptr)).collect()),
+            _ => changes.iter().map(|change| (change.tuple_ptr, change.old_tuple_ptr)).unzip(),
+        };
+        self.transactions.wrote(txid, table_name, written, deleted.into_iter().flatten());
+        if self.catalog.is_temporary(table_name) {
+            return Ok(());
+        }
+        let record = TupleRecord { relid: self.relid(table_name), txid, changes };
+        self.log_change(entry_type, &record)
+    }
+
+    /// Log a change to a table's definition or files before it is made
+    fn log_ddl(&mut self, table_name: &str, operation: DdlOperation) -> Result<()> {
+        if self.catalog.is_temporary(table_name) {
+            return Ok(());
+        }
+        let record = DdlRecord { relid: self.relid(table_name), table: table_name.to_string(), operation };
+        self.log_change(WalEntryType::Ddl, &record)
+    }
+
+    /// Store a SQL function; with `or_replace` an existing definition is
+    /// replaced and keeps its object id
+    pub fn create_function(&mut self, mut function_meta: catalog::FunctionMetadata, or_replace: bool) -> Result<()> {
+        let name = function_meta.name.clone();
+        if self.catalog.get_procedure(&name).is_some() {
+            return Err(format!("A procedure named {} already exists", name));
+        }
+        function_meta.oid = match self.catalog.get_function(&name) {
+            Some(existing) if or_replace => existing.oid,
+            Some(_) => return Err(format!("Function already exists: {}", name)),
+            None => self.catalog.next_oid(),
+        };
+
+        let previous = self.catalog.add_function(function_meta);
+        if let Err(e) = self.save_catalog_to_disk() {
+            match previous {
+                Some(previous) => self.catalog.add_function(previous),
+                None => self.catalog.remove_function(&name),
+            };
+            return Err(e);
+        }
+
+        debug!(function = %name, replaced = previous.is_some(), "function created");
+        Ok(())
+    }
+
+    /// Store a procedure; with `or_replace` an existing definition is replaced
+    /// and keeps its object id
+    pub fn create_procedure(&mut self, mut procedure_meta: catalog::ProcedureMetadata, or_replace: bool) -> Result<()> {
+        let name = procedure_meta.name.clone();
+        if self.catalog.get_function(&name).is_some() {
+            return Err(format!("A function named {} already exists", name));
+        }
+        procedure_meta.oid = match self.catalog.get_procedure(&name) {
+            Some(existing) if or_replace => existing.oid,
+            Some(_) => return Err(format!("Procedure already exists: {}", name)),
+            None => self.catalog.next_oid(),
+        };
+
+        let previous = self.catalog.add_procedure(procedure_meta);
+        if let Err(e) = self.save_catalog_to_disk() {
+            match previous {
+                Some(previous) => self.catalog.add_procedure(previous),
+                None => self.catalog.remove_procedure(&name),
+            };
+            return Err(e);
+        }
+
+        debug!(procedure = %name, replaced = previous.is_some(), "procedure created");
+        Ok(())
+    }
+
+    pub fn drop_procedure(&mut self, name: &str) -> Result<()> {
+        let procedure_meta = self.catalog.remove_procedure(name)
+            .ok_or_else(|| format!("Procedure not found: {}", name))?;
+        if let Err(e) = self.save_catalog_to_disk() {
+            self.catalog.add_procedure(procedure_meta);
+            return Err(e);
+        }
+
+        debug!(procedure = %name, "procedure dropped");
+        Ok(())
+    }
+
+    pub fn get_procedure(&self, name: &str) -> Option<catalog::ProcedureMetadata> {
+        self.catalog.get_procedure(name).cloned()
+    }
+
+    pub fn drop_function(&mut self, name: &str) -> Result<()> {
+        let function_meta = self.catalog.remove_function(name)
+            .ok_or_else(|| format!("Function not found: {}", name))?;
+        if let Err(e) = self.save_catalog_to_disk() {
+            self.catalog.add_function(function_meta);
+            return Err(e);
+        }
+
+        debug!(function = %name, "function dropped");
+        Ok(())
+    }
+
+    pub fn get_function(&self, name: &str) -> Option<catalog::FunctionMetadata> {
+        self.catalog.get_function(name).cloned()
+    }
+
+    pub fn has_functions(&self) -> bool {
+        self.catalog.has_functions()
+    }
+
+    /// Catalog entry for a table: schema, comments and storage bookkeeping
+    pub fn table_catalog_entry(&self, table_name: &str) -> Result<catalog::TableFileMetadata> {
+        self.catalog.get_table(table_name)
+            .map_err(|e| format!("Failed to read catalog: {}", e))?
+            .cloned()
+            .ok_or_else(|| format!("Table not found: {}", table_name))
     }
 
-    /// Read a block from storage (for index/executor use)
-    pub fn read_block(&self, _segment_id: u32, _block_id: u8) -> Result<base::Block> {
-        // TODO: Implement per-table access to blocks
-        Err("read_block not yet implemented with per-file architecture".to_string())
+    /// Rows that index entries lead to, in the order of the entries
+    /// An entry only counts if it leads to a visible tuple that is still
+    /// `indexed` under the entry's key: a deleted tuple's slot is freed for
+    /// reuse while entries may still point at it
+    fn index_hits<K>(
+        &self,
+        table_name: &str,
+        entries: impl IntoIterator<Item = (K, TuplePointer)>,
+        indexed: impl Fn(&K, &Row) -> Result<bool>,
+    ) -> Result<Vec<(TuplePointer, Row)>> {
+        let table_file = self.table_files.get(table_name)
+            .ok_or_else(|| format!("Table not found: {}", table_name))?;
+        let snapshot = self.transactions.snapshot();
+        let mut seen = HashSet::new();
+        let mut hits = Vec::new();
+        for (key, tuple_ptr) in entries {
+            if !seen.insert(tuple_ptr) {
+                continue;
+            }
+            if let Some(row) = Self::read_row(table_file, snapshot, tuple_ptr)?
+                && indexed(&key, &row)?
+            {
+                hits.push((tuple_ptr, row));
+            }
+        }
+        Ok(hits)
     }
 
     /// Update primary key index when a row is inserted (STUB)
@@ -512,7 +3082,10 @@ impl Database {
     }
 
     /// Point lookup using primary index
-    pub fn get_by_key(&self, table_name: &str, key: u64) -> Result<Option<TuplePointer>> {
+    /// Returns the visible row whose primary key is `value`, with where it is
+    /// stored
+    pub fn get_by_key(&self, table_name: &str, value: &crate::types::Value) -> Result<Option<(TuplePointer, Row)>> {
+        let key = index::key::encode_key(value)?;
         let metadata_arc = self.get_table(table_name)?;
         let metadata = metadata_arc.read();
 
@@ -524,21 +3097,41 @@ impl Database {
                 return Ok(None)
             },
         };
+        let pk_column = metadata.schema.primary_key_index().unwrap_or(0);
 
         // Get index file
         let index_file = self.index_files.get(table_name)
             .ok_or_else(|| format!("Index file not found for table: {}", table_name))?;
 
         // Lock index and search
+        stats::record_index_lookup();
         let index_guard = primary_index_meta.index.lock();
-        index_guard.search(key, index_file)
-            .map_err(|e| format!("Failed to search primary index: {}", e))
+        let entries = index_guard.search_all(key, index_file)
+            .map_err(|e| format!("Failed to search primary index: {}", e))?;
+        drop(index_guard);
+        let hits = self.index_hits(table_name, entries.into_iter().map(|tuple_ptr| (key, tuple_ptr)), |key, row| Ok(Self::primary_key(row, pk_column)? == *key))?;
+        // String keys are prefixes, so the entries may belong to other values
+        Ok(hits.into_iter().find(|(_, row)| row.get(pk_column).is_some_and(|v| index::key::key_values_equal(v, value))))
     }
 
     /// Range scan using primary index
-    /// Returns all tuple pointers for keys in [start_key, end_key] inclusive
+    /// Returns tuple pointers for keys in [start, end] inclusive, either bound
+    /// open when None, in `direction` order, stopping after `limit` entries
+    /// when one is given
     /// Returns empty vec if table has no primary index or index doesn't support range scans
-    pub fn range_scan_index(&self, table_name: &str, start_key: u64, end_key: u64) -> Result<Vec<TuplePointer>> {
+    /// String keys are prefix-encoded, so for STRING primary keys the result is a
+    /// superset: callers must re-check each row's key against the bounds
+    pub fn range_scan_index(
+        &self,
+        table_name: &str,
+        start: Option<&crate::types::Value>,
+        end: Option<&crate::types::Value>,
+        direction: index::ScanDirection,
+        limit: Option<usize>,
+    ) -> Result<Vec<(TuplePointer, Row)>> {
+        let start_key = start.map(index::key::encode_key).transpose()?.unwrap_or(u64::MIN);
+        let end_key = end.map(index::key::encode_key).transpose()?.unwrap_or(u64::MAX);
+
         let metadata_arc = self.get_table(table_name)?;
         let metadata = metadata_arc.read();
 
@@ -551,21 +3144,32 @@ impl Database {
             },
         };
 
-        // Check if index supports range scans
-        if primary_index_meta.index.lock().capability() != index::IndexCapability::Ordered {
-            // TODO do we just proceed with a full table scan?
-            return Ok(Vec::new());
-        }
+        let pk_column = metadata.schema.primary_key_index().unwrap_or(0);
 
         // Get index file
         let index_file = self.index_files.get(table_name)
             .ok_or_else(|| format!("Index file not found for table: {}", table_name))?;
 
-        // Lock index and perform range scan
+        // Check if index supports range scans
         let index_guard = primary_index_meta.index.lock();
-        index_guard.range_scan(start_key, end_key, index_file)
-            .map(|results| results.into_iter().map(|(_, ptr)| ptr).collect())
-            .map_err(|e| format!("Failed to range scan primary index: {}", e))
+        let Some(ordered) = index_guard.as_ordered() else {
+            // TODO do we just proceed with a full table scan?
+            return Ok(Vec::new());
+        };
+
+        // Walk the range lazily so a LIMIT only reads the pages it needs,
+        // counting only the entries that lead to a row
+        stats::record_index_lookup();
+        let mut cursor = ordered.cursor(start_key, end_key, direction, index_file)
+            .map_err(|e| format!("Failed to range scan primary index: {}", e))?;
+        let limit = limit.unwrap_or(usize::MAX);
+        let mut rows = Vec::new();
+        while rows.len() < limit {
+            let Some(entry) = cursor.next() else { break };
+            let entry = entry.map_err(|e| format!("Failed to range scan primary index: {}", e))?;
+            rows.extend(self.index_hits(table_name, [entry], |key, row| Ok(Self::primary_key(row, pk_column)? == *key))?);
+        }
+        Ok(rows)
     }
 
     /// Find a secondary index by table name and column name
@@ -576,7 +3180,7 @@ impl Database {
 
         // Search secondary indexes for matching column
         for idx_meta in &metadata.secondary_indexes {
-            if idx_meta.column == column_name {
+            if idx_meta.columns == [column_name] {
                 return Ok(Some((idx_meta.name.clone(), idx_meta.index.clone())));
             }
         }
@@ -584,40 +3188,209 @@ impl Database {
         Ok(None)
     }
 
+    /// Whether a secondary index of this name exists on any table
+    /// Index names share one namespace across tables, as in Postgres
+    pub fn index_exists(&self, index_name: &str) -> bool {
+        self.tables.values().any(|metadata_arc| metadata_arc.read().secondary_indexes.iter()
+            .any(|index_meta| index_meta.name == index_name))
+    }
+
     /// Search a secondary index by table and column name
-    /// Returns Some(TuplePointer) if found, None if not found
-    pub fn search_secondary_index(&self, table_name: &str, column_name: &str, key: u64) -> Result<Option<TuplePointer>> {
+    /// Returns every tuple indexed under the value; for an inverted index, every
+    /// tuple whose column contains it as an element
+    /// String keys are prefixes, so callers must still compare the full values
+    pub fn search_secondary_index(&self, table_name: &str, column_name: &str, value: &crate::types::Value) -> Result<Vec<TuplePointer>> {
+        let metadata_arc = self.get_table(table_name)?;
+        let metadata = metadata_arc.read();
+
         // Find the secondary index
-        let index_opt = self.find_secondary_index(table_name, column_name)?;
-
-        if let Some((index_name, index_arc)) = index_opt {
-            // Get the index file using the table and index name
-            let index_file_key = format!("{}_{}", table_name, index_name);
-            let index_file = self.index_files.get(&index_file_key)
-                .ok_or_else(|| format!("Index file not found for secondary index {}", index_name))?;
-
-            // Search the index
-            let mut index = index_arc.lock();
-            index.search(key, index_file)
-                .map_err(|e| format!("Index search error: {}", e))
-        } else {
-            Ok(None)
+        let Some(idx_meta) = metadata.secondary_indexes.iter().find(|idx| idx.columns == [column_name]) else {
+            return Ok(Vec::new());
+        };
+
+        // Get the index file using the table and index name
+        let index_file_key = format!("{}_{}", table_name, idx_meta.name);
+        let index_file = self.index_files.get(&index_file_key)
+            .ok_or_else(|| format!("Index file not found for secondary index {}", idx_meta.name))?;
+
+        // Search the index
+        let key = idx_meta.key_for(value)?;
+        stats::record_index_lookup();
+        let index = idx_meta.index.lock();
+        index.search_all(key, index_file)
+            .map_err(|e| format!("Index search error: {}", e))
+    }
+
+    /// The secondary index that narrows a lookup by equalities on `columns`
+    /// the most: the one with the most leading columns among them
+    /// Only B-tree indexes list every row under a key, so only they are used
+    /// Returns the index's name and its leading columns that are given
+    pub fn lookup_index(&self, table_name: &str, columns: &[&str]) -> Result<Option<(String, Vec<String>)>> {
+        if self.system_view(table_name).is_some() {
+            return Ok(None);
+        }
+        let metadata_arc = self.get_table(table_name)?;
+        let metadata = metadata_arc.read();
+        Ok(metadata.secondary_indexes.iter()
+            .filter(|idx_meta| idx_meta.index_type == "btree")
+            .map(|idx_meta| {
+                let prefix = idx_meta.columns.iter().take_while(|column| columns.contains(&column.as_str())).count();
+                (idx_meta, prefix)
+            })
+            .filter(|(_, prefix)| *prefix > 0)
+            // The first index created wins a tie
+            .rev()
+            .max_by_key(|(_, prefix)| *prefix)
+            .map(|(idx_meta, prefix)| (idx_meta.name.clone(), idx_meta.columns[..prefix].to_vec())))
+    }
+
+    /// Search a secondary index for the tuples whose leading indexed columns
+    /// hold `prefix`, one value per column
+    /// Keys don't keep every detail of their values, so callers must still
+    /// compare the full values
+    pub fn search_index_prefix(&self, table_name: &str, index_name: &str, prefix: &[crate::types::Value]) -> Result<Vec<(TuplePointer, Row)>> {
+        let metadata_arc = self.get_table(table_name)?;
+        let metadata = metadata_arc.read();
+        let idx_meta = metadata.secondary_indexes.iter()
+            .find(|idx_meta| idx_meta.name == index_name)
+            .ok_or_else(|| format!("Index not found: {}", index_name))?;
+        let index_file = self.index_files.get(&format!("{}_{}", table_name, idx_meta.name))
+            .ok_or_else(|| format!("Index file not found for secondary index {}", idx_meta.name))?;
+
+        let (low, high) = idx_meta.prefix_range(prefix)?;
+        stats::record_index_lookup();
+        let index = idx_meta.index.lock();
+        let inverted = index.is_inverted();
+        let entries = index.range_scan(low, high, index_file)
+            .map_err(|e| format!("Index search error: {}", e))?;
+        drop(index);
+        let columns = Self::index_columns(&metadata.schema, idx_meta)
+            .ok_or_else(|| format!("Index {} refers to a missing column", idx_meta.name))?;
+        self.index_hits(table_name, entries, |key, row| Ok(self.row_index_keys(idx_meta, &columns, inverted, row)?.contains(key)))
+    }
+
+    /// The secondary index on `column` that can narrow `column op operand`,
+    /// going by the operator classes the operator declares
+    /// Returns the index's name and the strategy it serves the operator with
+    #[cfg(feature = "extensions")]
+    pub fn operator_index(
+        &self,
+        table_name: &str,
+        column: &crate::types::Column,
+        symbol: &str,
+        operand_type: &crate::types::DataType,
+    ) -> Result<Option<(String, IndexStrategy)>> {
+        if self.system_view(table_name).is_some() {
+            return Ok(None);
+        }
+        let metadata_arc = self.get_table(table_name)?;
+        let metadata = metadata_arc.read();
+        Ok(metadata.secondary_indexes.iter()
+            .filter(|idx_meta| idx_meta.columns == [column.name.as_str()])
+            .find_map(|idx_meta| {
+                self.operator_registry
+                    .index_strategy(symbol, &column.data_type, operand_type, &idx_meta.index_type)
+                    .filter(|strategy| strategy.is_search())
+                    .map(|strategy| (idx_meta.name.clone(), strategy))
+            }))
+    }
+
+    /// Search a secondary index for the tuples that may satisfy an operator
+    /// it serves with `strategy`, given the operator's right operand
+    /// Returns None if the index can't narrow the search, as when a value
+    /// with no elements is contained in every row
+    /// Keys don't keep every detail of their values, and an operator may hold
+    /// for fewer rows than its strategy suggests, so callers must still apply
+    /// the operator
+    #[cfg(feature = "extensions")]
+    pub fn search_operator_index(
+        &self,
+        table_name: &str,
+        index_name: &str,
+        strategy: IndexStrategy,
+        operand: &crate::types::Value,
+    ) -> Result<Option<Vec<(TuplePointer, Row)>>> {
+        let metadata_arc = self.get_table(table_name)?;
+        let metadata = metadata_arc.read();
+        let idx_meta = metadata.secondary_indexes.iter()
+            .find(|idx_meta| idx_meta.name == index_name)
+            .ok_or_else(|| format!("Index not found: {}", index_name))?;
+        let index_file = self.index_files.get(&format!("{}_{}", table_name, idx_meta.name))
+            .ok_or_else(|| format!("Index file not found for secondary index {}", idx_meta.name))?;
+
+        // Keys to look up: the operand's own for Equal, its elements otherwise
+        let keys = match strategy {
+            IndexStrategy::Equal => vec![idx_meta.key_for(operand)?],
+            IndexStrategy::Contains | IndexStrategy::Overlaps => self.index_keys(idx_meta, true, operand)?,
+            IndexStrategy::Distance => return Ok(None),
+        };
+        if keys.is_empty() && strategy == IndexStrategy::Contains {
+            return Ok(None);
+        }
+
+        stats::record_index_lookup();
+        let index = idx_meta.index.lock();
+        let inverted = index.is_inverted();
+        let mut matches: Option<HashSet<TuplePointer>> = None;
+        for &key in &keys {
+            let tuple_ptrs: HashSet<TuplePointer> = index.search_all(key, index_file)
+                .map_err(|e| format!("Index search error: {}", e))?
+                .into_iter()
+                .collect();
+            matches = Some(match (matches, strategy) {
+                (Some(found), IndexStrategy::Contains) => found.intersection(&tuple_ptrs).copied().collect(),
+                (Some(mut found), _) => {
+                    found.extend(tuple_ptrs);
+                    found
+                }
+                (None, _) => tuple_ptrs,
+            });
         }
+        drop(index);
+        let mut tuple_ptrs: Vec<TuplePointer> = matches.unwrap_or_default().into_iter().collect();
+        // In storage order, as a scan would return them
+        tuple_ptrs.sort_unstable_by_key(|tuple_ptr| (tuple_ptr.segment_id, tuple_ptr.block_id, tuple_ptr.slot_id));
+
+        // A tuple found is still indexed under one of the keys looked up
+        let columns = Self::index_columns(&metadata.schema, idx_meta)
+            .ok_or_else(|| format!("Index {} refers to a missing column", idx_meta.name))?;
+        let rows = self.index_hits(table_name, tuple_ptrs.into_iter().map(|tuple_ptr| ((), tuple_ptr)), |_, row| {
+            let row_keys = self.row_index_keys(idx_meta, &columns, inverted, row)?;
+            Ok(keys.iter().any(|key| row_keys.contains(key)))
+        })?;
+        Ok(Some(rows))
     }
 
-    /// Create a secondary index on a table
-    pub fn create_secondary_index(&mut self, index_name: String, table_name: String, column_name: String, index_type: String) -> Result<()> {
+    /// Build a secondary index on a table, filled with the rows it already holds
+    /// Only reads the database, so queries can run (and watch its progress in
+    /// pg_stat_progress_create_index) while it builds; callers must keep out
+    /// writers until the index is added with `add_secondary_index`
+    pub fn build_secondary_index(&self, index_name: String, table_name: String, columns: Vec<String>, index_type: String, descending: bool) -> Result<BuiltIndex> {
+        if self.index_exists(&index_name) {
+            return Err(format!("Index already exists: {}", index_name));
+        }
         // Get the table metadata
         let metadata_arc = self.get_table(&table_name)?;
+        let columns = {
+            let schema = &metadata_arc.read().schema;
+            columns.iter()
+                .map(|column| schema.get_column_index(column)
+                    .map(|idx| schema.columns[idx].name.clone())
+                    .ok_or_else(|| format!("Column not found: {}.{}", table_name, column)))
+                .collect::<Result<Vec<_>>>()?
+        };
+        let relid = self.relid(&table_name);
+        let mut progress = self.progress.start(progress::Command::CreateIndex, relid, &table_name, Some(&index_name));
 
         // Create index file
-        let index_file_path = PathBuf::from(format!("index_{}_{}_{}.idx", table_name, column_name, &index_name));
+        let index_file_path = self.table_dir(&table_name).join(format!("index_{}_{}_{}.idx", table_name, columns.join("_"), &index_name));
         let index_file = IndexFile::open(&index_file_path)
             .map_err(|e| format!("Failed to open index file: {}", e))?;
 
         // Allocate root page for the secondary index
         let root_page_id = index_file.allocate_page()
             .map_err(|e| format!("Failed to allocate index root page: {}", e))?;
+        Self::init_index_root(&index_file, root_page_id)?;
 
         // Create index instance via registry
         let index = self.index_builder_registry.create_index(&index_type, Some(root_page_id))
@@ -625,22 +3398,37 @@ impl Database {
 
         // Create index metadata
         let index_meta = IndexMetadata {
-            name: index_name.clone(),
-            column: column_name.clone(),
-            index_type: index_type.clone(),
+            name: index_name,
+            columns,
+            index_type,
+            descending,
             index: Arc::new(Mutex::new(index)),
         };
 
-        // Add to TableMetadata.secondary_indexes
-        {
-            let mut metadata = metadata_arc.write();
-            metadata.secondary_indexes.push(index_meta);
+        progress.set_phase("scanning table", 0);
+        let existing = self.scan_table_tuples(&table_name)?;
+        progress.set_phase("loading tuples", existing.len() as u64);
+        let schema = &metadata_arc.read().schema;
+        for batch in existing.chunks(PROGRESS_BATCH_ROWS) {
+            self.add_index_entries(schema, &index_meta, &index_file, batch)?;
+            progress.advance(batch.len() as u64);
         }
 
+        Ok(BuiltIndex { table_name, index_meta, index_file })
+    }
+
+    /// Add an index built by `build_secondary_index` to its table
+    pub fn add_secondary_index(&mut self, built: BuiltIndex) -> Result<()> {
+        let BuiltIndex { table_name, index_meta, index_file } = built;
+        let metadata_arc = self.get_table(&table_name)?;
+
         // Store index file for later access
-        let index_file_key = format!("{}_{}", table_name, index_name);
+        let index_file_key = format!("{}_{}", table_name, index_meta.name);
         self.index_files.insert(index_file_key, Arc::new(index_file));
 
+        // Add to TableMetadata.secondary_indexes
+        metadata_arc.write().secondary_indexes.push(index_meta);
+
         // TODO: Update catalog to persist secondary index metadata
         // catalog.add_secondary_index(...)?;
 
@@ -0,0 +1,296 @@
+//! Transaction ids and tuple visibility
+//!
+//! Every write runs as a transaction, with an id from a counter saved in the
+//! catalog. Tuples it creates carry the id as their xmin; tuples it deletes,
+//! including the versions an UPDATE replaces, get it as their xmax instead of
+//! being removed. A reader sees a tuple whose xmin committed and whose xmax,
+//! if any, did not, so it never sees a version that is uncommitted or already
+//! deleted. Once its xmax commits a tuple is dead: no reader can see it again,
+//! and its slot is reclaimed the next time its block is written to, or when
+//! the table is compacted.
+//!
+//! A write outside any transaction commits as soon as it is done. Writes made
+//! while a thread has a transaction set with `set_current` run in it instead,
+//! and that thread's reads see them, while every other reader sees the rows as
+//! they were until it commits. A transaction keeps the tuples it has written
+//! and deleted: the tuples it deleted keep their index entries, for readers
+//! that still see them, until it commits, and a rollback removes the entries
+//! of the tuples it wrote.
+//!
+//! Ids below `FIRST_TXID` are frozen, visible to every reader. Tuples written
+//! before transaction ids were handed out carry `FROZEN_TXID`.
+
+use std::cell::Cell;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+
+use crate::storage::base::{Block, TupleMeta, TuplePointer, TxId, FIRST_TXID};
+use crate::storage::catalog::TransactionState;
+
+thread_local! {
+    /// Transaction the statement running on this thread writes in; queries
+    /// run on a blocking thread of their own for their whole execution
+    static CURRENT: Cell<Option<TxId>> = const { Cell::new(None) };
+}
+
+/// Run this thread's writes in a transaction begun with
+/// `Database::begin_transaction`, or outside any with None
+pub fn set_current(txid: Option<TxId>) {
+    CURRENT.with(|current| current.set(txid));
+}
+
+/// Transaction this thread's writes run in, if any
+pub fn current() -> Option<TxId> {
+    CURRENT.with(Cell::get)
+}
+
+/// Tuples a transaction has written and deleted in one table
+#[derive(Debug, Default)]
+pub struct TableChanges {
+    pub written: HashSet<TuplePointer>,
+    pub deleted: HashSet<TuplePointer>,
+}
+
+/// Transaction ids handed out so far, and how each transaction ended
+#[derive(Debug)]
+pub struct Transactions {
+    /// Next id to hand out
+    next_txid: TxId,
+    /// Transactions begun and not yet ended, with the changes each has made
+    /// to each table
+    running: BTreeMap<TxId, HashMap<String, TableChanges>>,
+    /// Transactions that ended without committing; their tuples stay in their
+    /// blocks, never visible, and are not reclaimed
+    aborted: BTreeSet<TxId>,
+}
+
+impl Transactions {
+    /// Pick up where the state saved in the catalog left off
+    pub fn new(state: &TransactionState) -> Self {
+        Transactions {
+            next_txid: state.next_txid.max(FIRST_TXID),
+            running: BTreeMap::new(),
+            aborted: state.aborted.iter().copied().collect(),
+        }
+    }
+
+    /// State to save in the catalog
+    /// Running transactions that have written are saved as aborted, so their
+    /// tuples stay hidden if the server stops before they commit
+    pub fn state(&self) -> TransactionState {
+        let mut aborted = self.aborted.clone();
+        aborted.extend(self.running.iter()
+            .filter(|(_, tables)| !tables.is_empty())
+            .map(|(txid, _)| *txid));
+        TransactionState {
+            next_txid: self.next_txid,
+            aborted: aborted.into_iter().collect(),
+        }
+    }
+
+    /// Start a transaction, returning its id
+    pub fn begin(&mut self) -> TxId {
+        let txid = self.next_txid;
+        self.next_txid += 1;
+        self.running.insert(txid, HashMap::new());
+        txid
+    }
+
+    /// Whether a transaction has begun and not yet ended
+    pub fn is_running(&self, txid: TxId) -> bool {
+        self.running.contains_key(&txid)
+    }
+
+    /// Note the tuples a transaction is about to write and delete in a table
+    pub fn wrote(
+        &mut self,
+        txid: TxId,
+        table_name: &str,
+        written: impl IntoIterator<Item = TuplePointer>,
+        deleted: impl IntoIterator<Item = TuplePointer>,
+    ) {
+        if let Some(tables) = self.running.get_mut(&txid) {
+            let changes = tables.entry(table_name.to_string()).or_default();
+            changes.written.extend(written);
+            changes.deleted.extend(deleted);
+        }
+    }
+
+    /// Tables a running transaction has written to
+    pub fn written_tables(&self, txid: TxId) -> impl Iterator<Item = &str> {
+        self.running.get(&txid).into_iter().flat_map(|tables| tables.keys().map(String::as_str))
+    }
+
+    /// End a transaction, making its writes visible
+    /// Returns the changes it made, for the caller to remove the index
+    /// entries of the tuples it deleted
+    pub fn commit(&mut self, txid: TxId) -> HashMap<String, TableChanges> {
+        self.running.remove(&txid).unwrap_or_default()
+    }
+
+    /// End a transaction without making its writes visible
+    /// Returns the changes it made, for the caller to undo; its id is only
+    /// remembered as aborted if it made any, as otherwise there is nothing to
+    /// hide
+    pub fn abort(&mut self, txid: TxId) -> HashMap<String, TableChanges> {
+        let tables = self.running.remove(&txid).unwrap_or_default();
+        if !tables.is_empty() {
+            self.aborted.insert(txid);
+        }
+        tables
+    }
+
+    /// Take in how a transaction WAL replay finds writes of ended: committed
+    /// if the log has its commit, aborted otherwise; its id is never handed
+    /// out again
+    pub fn replayed(&mut self, txid: TxId, committed: bool) {
+        self.next_txid = self.next_txid.max(txid + 1);
+        // The catalog may have been saved while it was running, listing it
+        // as aborted
+        if committed {
+            self.aborted.remove(&txid);
+        } else {
+            self.aborted.insert(txid);
+        }
+    }
+
+    /// Whether a transaction committed
+    fn is_committed(&self, txid: TxId) -> bool {
+        txid < FIRST_TXID
+            || (txid < self.next_txid && !self.running.contains_key(&txid) && !self.aborted.contains(&txid))
+    }
+
+    /// What a reader on this thread sees right now: committed tuples and the
+    /// thread's own transaction's writes
+    pub fn snapshot(&self) -> Snapshot<'_> {
+        let own = current().filter(|txid| self.is_running(*txid));
+        Snapshot { transactions: self, own }
+    }
+
+    /// Whether no reader can ever see a tuple again, so its slot can be reused
+    pub fn is_dead(&self, meta: &TupleMeta) -> bool {
+        meta.is_deleted() && self.is_committed(meta.xmax)
+    }
+
+    /// Release the slots of a block's dead tuples
+    /// Returns whether any were released, so the block needs writing
+    pub fn prune(&self, block: &mut Block) -> bool {
+        let mut pruned = false;
+        for slot_id in 0..block.header().slot_count {
+            if block.tuple_meta(slot_id).is_some_and(|meta| self.is_dead(&meta)) {
+                pruned |= block.delete_tuple(slot_id);
+            }
+        }
+        pruned
+    }
+}
+
+/// The committed state of the database a reader sees
+#[derive(Debug, Clone, Copy)]
+pub struct Snapshot<'a> {
+    transactions: &'a Transactions,
+    /// Running transaction of the reader, whose writes it sees
+    own: Option<TxId>,
+}
+
+impl Snapshot<'_> {
+    /// Whether a tuple is visible: created by a committed transaction or the
+    /// reader's own, and not deleted by either
+    pub fn sees(&self, meta: &TupleMeta) -> bool {
+        let own = |txid: TxId| self.own == Some(txid);
+        (self.transactions.is_committed(meta.xmin) || own(meta.xmin))
+            && !self.transactions.is_dead(meta)
+            && !(meta.is_deleted() && own(meta.xmax))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::base::FROZEN_TXID;
+
+    fn meta(xmin: TxId, xmax: TxId) -> TupleMeta {
+        let mut meta = TupleMeta::new(xmin);
+        meta.xmax = xmax;
+        meta
+    }
+
+    #[test]
+    fn test_visibility() {
+        let mut transactions = Transactions::new(&TransactionState::default());
+        let committed = transactions.begin();
+        transactions.commit(committed);
+        let running = transactions.begin();
+        let aborted = transactions.begin();
+        transactions.wrote(aborted, "t", [TuplePointer::new(0, 1, 0)], []);
+        assert_eq!(transactions.abort(aborted)["t"].written.len(), 1);
+        let unused = transactions.begin();
+        assert!(transactions.abort(unused).is_empty());
+        assert!(transactions.aborted.contains(&aborted));
+        assert!(!transactions.aborted.contains(&unused), "a transaction that wrote nothing is not remembered");
+
+        let snapshot = transactions.snapshot();
+        assert!(snapshot.sees(&meta(FROZEN_TXID, 0)));
+        assert!(snapshot.sees(&meta(committed, 0)));
+        assert!(!snapshot.sees(&meta(running, 0)), "uncommitted tuples are invisible");
+        assert!(!snapshot.sees(&meta(aborted, 0)), "aborted tuples are invisible");
+        assert!(!snapshot.sees(&meta(running + 100, 0)), "ids not handed out yet are invisible");
+
+        assert!(!snapshot.sees(&meta(FROZEN_TXID, committed)), "deleted tuples are invisible");
+        assert!(snapshot.sees(&meta(committed, running)), "uncommitted deletes are invisible");
+        assert!(snapshot.sees(&meta(committed, aborted)), "aborted deletes are invisible");
+
+        assert!(transactions.is_dead(&meta(FROZEN_TXID, committed)));
+        assert!(!transactions.is_dead(&meta(committed, running)));
+        assert!(!transactions.is_dead(&meta(aborted, 0)), "aborted tuples are never reclaimed");
+    }
+
+    #[test]
+    fn test_prune() {
+        let mut transactions = Transactions::new(&TransactionState::default());
+        let deleter = transactions.begin();
+        let mut block = Block::new();
+        for xmax in [0, deleter] {
+            block.append_tuple(&meta(FROZEN_TXID, xmax), b"row").unwrap();
+        }
+        assert!(!transactions.prune(&mut block), "tuples whose delete is running are kept");
+        transactions.commit(deleter);
+        assert!(transactions.prune(&mut block));
+        assert!(block.tuple_meta(0).is_some());
+        assert!(block.tuple_meta(1).is_none());
+    }
+
+    #[test]
+    fn test_state_round_trip() {
+        let mut transactions = Transactions::new(&TransactionState::default());
+        let aborted = transactions.begin();
+        transactions.wrote(aborted, "t", [], []);
+        transactions.abort(aborted);
+        let running = transactions.begin();
+        transactions.wrote(running, "t", [], []);
+        transactions.replayed(aborted + 10, true);
+
+        let mut reloaded = Transactions::new(&transactions.state());
+        assert_eq!(reloaded.begin(), aborted + 11, "ids are never handed out twice");
+        assert!(!reloaded.snapshot().sees(&meta(aborted, 0)), "aborts outlive a restart");
+        assert!(!reloaded.snapshot().sees(&meta(running, 0)), "writes still running are saved as aborted");
+
+        // Replay finds the transaction committed after the catalog was saved
+        reloaded.replayed(running, true);
+        assert!(reloaded.snapshot().sees(&meta(running, 0)));
+    }
+
+    #[test]
+    fn test_own_writes() {
+        let mut transactions = Transactions::new(&TransactionState::default());
+        let writer = transactions.begin();
+        transactions.wrote(writer, "t", [], []);
+
+        assert!(!transactions.snapshot().sees(&meta(writer, 0)));
+        set_current(Some(writer));
+        assert!(transactions.snapshot().sees(&meta(writer, 0)), "a transaction sees its own writes");
+        assert!(!transactions.snapshot().sees(&meta(FROZEN_TXID, writer)), "and not the tuples it deleted");
+        transactions.abort(writer);
+        assert!(!transactions.snapshot().sees(&meta(writer, 0)), "once aborted its writes are hidden from it too");
+        set_current(None);
+    }
+}
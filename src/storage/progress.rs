@@ -0,0 +1,184 @@
+//! Progress of long-running operations (CREATE INDEX, VACUUM)
+//!
+//! An operation registers itself for as long as it runs, moving through
+//! phases and counting the work done in each against the total it expects.
+//! Running index builds are listed by pg_stat_progress_create_index; they
+//! only hold off writers, so the view can be queried while they run. VACUUM
+//! rewrites its table in place and so holds off every query, which leaves the
+//! log as the only way to follow it: any operation running longer than
+//! LOG_INTERVAL logs its percentage complete that often.
+
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+use parking_lot::Mutex;
+use tracing::{debug, info};
+
+/// How often a running operation logs its progress
+const LOG_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Kind of operation being tracked
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Command {
+    CreateIndex,
+    Vacuum,
+}
+
+impl Command {
+    pub fn name(self) -> &'static str {
+        match self {
+            Command::CreateIndex => "CREATE INDEX",
+            Command::Vacuum => "VACUUM",
+        }
+    }
+}
+
+/// State of one running operation
+#[derive(Debug, Clone)]
+pub struct OperationProgress {
+    pub command: Command,
+    /// Object id of the table the operation works on
+    pub relid: u32,
+    pub relname: String,
+    /// Index being built, for CREATE INDEX
+    pub index_name: Option<String>,
+    pub phase: &'static str,
+    /// Units of work (tuples or blocks, depending on the phase) done so far
+    pub done: u64,
+    /// Units of work the phase expects, 0 if unknown
+    pub total: u64,
+}
+
+impl OperationProgress {
+    /// Share of the current phase done, if its total is known
+    pub fn percent(&self) -> Option<f64> {
+        (self.total > 0).then(|| (self.done as f64 * 100.0 / self.total as f64).min(100.0))
+    }
+}
+
+/// Every operation currently running
+#[derive(Default)]
+pub struct ProgressRegistry {
+    operations: Mutex<BTreeMap<u64, OperationProgress>>,
+    next_id: AtomicU64,
+}
+
+impl ProgressRegistry {
+    /// Register an operation; it stays listed until the returned tracker is dropped
+    pub fn start(self: &Arc<Self>, command: Command, relid: u32, relname: &str, index_name: Option<&str>) -> ProgressTracker {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        self.operations.lock().insert(id, OperationProgress {
+            command,
+            relid,
+            relname: relname.to_string(),
+            index_name: index_name.map(str::to_string),
+            phase: "initializing",
+            done: 0,
+            total: 0,
+        });
+        debug!(command = command.name(), table = relname, "operation started");
+        let now = Instant::now();
+        ProgressTracker { registry: Arc::clone(self), id, started: now, last_logged: now }
+    }
+
+    /// Running operations, oldest first
+    pub fn operations(&self) -> Vec<OperationProgress> {
+        self.operations.lock().values().cloned().collect()
+    }
+}
+
+/// Handle through which a running operation reports its progress
+pub struct ProgressTracker {
+    registry: Arc<ProgressRegistry>,
+    id: u64,
+    started: Instant,
+    last_logged: Instant,
+}
+
+impl ProgressTracker {
+    /// Move on to a new phase expecting `total` units of work
+    pub fn set_phase(&mut self, phase: &'static str, total: u64) {
+        self.update(|operation| {
+            operation.phase = phase;
+            operation.done = 0;
+            operation.total = total;
+        });
+    }
+
+    /// Count `units` more units of work done in the current phase
+    pub fn advance(&mut self, units: u64) {
+        self.update(|operation| operation.done += units);
+    }
+
+    fn update(&mut self, change: impl FnOnce(&mut OperationProgress)) {
+        let mut operations = self.registry.operations.lock();
+        let Some(operation) = operations.get_mut(&self.id) else {
+            return;
+        };
+        change(operation);
+
+        if self.last_logged.elapsed() >= LOG_INTERVAL {
+            self.last_logged = Instant::now();
+            info!(
+                command = operation.command.name(),
+                table = %operation.relname,
+                phase = operation.phase,
+                done = operation.done,
+                total = operation.total,
+                percent = format_args!("{:.1}", operation.percent().unwrap_or(0.0)),
+                "operation in progress"
+            );
+        }
+    }
+}
+
+impl Drop for ProgressTracker {
+    fn drop(&mut self) {
+        if let Some(operation) = self.registry.operations.lock().remove(&self.id) {
+            debug!(
+                command = operation.command.name(),
+                table = %operation.relname,
+                elapsed_ms = self.started.elapsed().as_millis() as u64,
+                "operation finished"
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_progress_tracking() {
+        let registry = Arc::new(ProgressRegistry::default());
+        assert!(registry.operations().is_empty());
+
+        let mut build = registry.start(Command::CreateIndex, 16384, "docs", Some("docs_tag"));
+        let vacuum = registry.start(Command::Vacuum, 16385, "notes", None);
+        build.set_phase("loading tuples", 200);
+        build.advance(50);
+
+        let operations = registry.operations();
+        assert_eq!(operations.len(), 2);
+        assert_eq!(operations[0].command, Command::CreateIndex);
+        assert_eq!(operations[0].index_name.as_deref(), Some("docs_tag"));
+        assert_eq!((operations[0].phase, operations[0].done, operations[0].total), ("loading tuples", 50, 200));
+        assert_eq!(operations[0].percent(), Some(25.0));
+        assert_eq!(operations[1].relname, "notes");
+        assert_eq!(operations[1].percent(), None, "no total is known yet");
+
+        // A new phase starts from zero
+        build.set_phase("done", 10);
+        assert_eq!(registry.operations()[0].done, 0);
+
+        drop(build);
+        let operations = registry.operations();
+        assert_eq!(operations.len(), 1);
+        assert_eq!(operations[0].command, Command::Vacuum);
+        drop(vacuum);
+        assert!(registry.operations().is_empty());
+    }
+}
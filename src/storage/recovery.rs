@@ -0,0 +1,194 @@
+//! Startup self-test and the report of what it found
+//!
+//! Every time the database is opened, changes logged to the WAL since the
+//! catalog was last saved are first redone into the table files, so writes a
+//! crash kept from reaching them are not lost. A log that can't be fully
+//! redone, from an entry corrupted partway through it or a record that no
+//! longer applies, stops the database from opening, as going on would lose
+//! acknowledged writes; --allow-lossy-recovery opens it anyway, without
+//! them. Then each table in the catalog
+//! is read block by block and its primary index checked against the rows it
+//! holds. Blocks a crash left marked used but never written are released.
+//! Blocks that cannot be read back as rows are copied into the quarantine
+//! directory, then dropped from their table, so queries never trip over them.
+//! A primary index that doesn't match its table's rows is rebuilt from them.
+//! The whole outcome, together with what catalog loading and WAL replay did,
+//! is logged as one structured event. Operators can then tell a clean restart
+//! from one that had to give something up.
+
+use std::path::{Path, PathBuf};
+
+use tracing::{info, warn};
+
+use crate::storage::Result;
+use crate::storage::base::{Block, BlockId, SegmentId};
+use crate::storage::wal::WalEnd;
+use crate::types::Row;
+
+/// Directory in the data directory that quarantined blocks are copied to
+pub const QUARANTINE_DIR: &str = "quarantine";
+
+/// A block taken out of its table because its contents could not be read
+#[derive(Debug, Clone)]
+pub struct QuarantinedBlock {
+    pub table: String,
+    pub segment_id: SegmentId,
+    pub block_id: BlockId,
+    /// What was wrong with the block
+    pub reason: String,
+    /// Copy of the block's bytes, for inspection or manual salvage
+    pub path: PathBuf,
+}
+
+/// What opening the database found and repaired
+#[derive(Debug, Clone, Default)]
+pub struct RecoveryReport {
+    /// Catalog segment loaded, None for a new database
+    pub catalog_segment: Option<u32>,
+    /// Whether the active catalog segment was unreadable, so the other was loaded
+    pub catalog_fallback: bool,
+    pub tables_recovered: usize,
+    pub wal_records_replayed: usize,
+    /// How the WAL ended; anything after the last valid entry was ignored
+    pub wal_end: WalEnd,
+    /// Bytes of the WAL after its last valid entry
+    pub wal_bytes_ignored: u64,
+    /// Blocks marked used that were never written, released
+    pub blocks_released: usize,
+    /// Tables whose primary index was rebuilt
+    pub indexes_rebuilt: Vec<String>,
+    pub blocks_quarantined: Vec<QuarantinedBlock>,
+    /// Problems found that could not be repaired
+    pub problems: Vec<String>,
+}
+
+impl RecoveryReport {
+    /// Whether nothing had to be repaired or given up
+    pub fn is_clean(&self) -> bool {
+        !self.catalog_fallback
+            && self.wal_end.is_clean()
+            && self.blocks_released == 0
+            && self.indexes_rebuilt.is_empty()
+            && self.blocks_quarantined.is_empty()
+            && self.problems.is_empty()
+    }
+
+    /// Log the report: a warning for a WAL cut short, for each block
+    /// quarantined and for each problem left, then the summary
+    pub fn log(&self) {
+        for block in &self.blocks_quarantined {
+            warn!(
+                table = %block.table,
+                segment_id = block.segment_id,
+                block_id = block.block_id,
+                reason = %block.reason,
+                path = %block.path.display(),
+                "quarantined corrupt block"
+            );
+        }
+        if !self.wal_end.is_clean() {
+            warn!(
+                end = %self.wal_end,
+                bytes_ignored = self.wal_bytes_ignored,
+                records_replayed = self.wal_records_replayed,
+                "WAL does not end cleanly, ignoring the rest"
+            );
+        }
+        for problem in &self.problems {
+            warn!(problem = %problem, "self-test found a problem it could not repair");
+        }
+        info!(
+            clean = self.is_clean(),
+            catalog_segment = ?self.catalog_segment,
+            catalog_fallback = self.catalog_fallback,
+            tables_recovered = self.tables_recovered,
+            wal_records_replayed = self.wal_records_replayed,
+            wal_end = %self.wal_end,
+            wal_bytes_ignored = self.wal_bytes_ignored,
+            blocks_released = self.blocks_released,
+            indexes_rebuilt = ?self.indexes_rebuilt,
+            blocks_quarantined = self.blocks_quarantined.len(),
+            problems = self.problems.len(),
+            "recovery report"
+        );
+    }
+}
+
+/// Whether a block is all zeros, as a block allocated but never written reads
+pub fn is_unwritten(block: &Block) -> bool {
+    block.as_bytes().iter().all(|&byte| byte == 0)
+}
+
+/// Check that a block can be read back as rows
+/// The layout is checked first, so reading the block's slots can't run past
+/// its end; then every live tuple has to decode as a row
+pub fn check_block(block: &Block) -> Result<()> {
+    block.validate().map_err(|e| e.to_string())?;
+    for slot_id in 0..block.header().slot_count {
+        if block.tuple_meta(slot_id).is_some_and(|meta| meta.is_deleted()) {
+            continue;
+        }
+        if let Some(tuple_bytes) = block.read_tuple(slot_id) {
+            bincode::decode_from_slice::<Row, _>(tuple_bytes, bincode::config::standard())
+                .map_err(|e| format!("slot {} does not hold a row: {}", slot_id, e))?;
+        }
+    }
+    Ok(())
+}
+
+/// Copy a block's bytes into the quarantine directory, returning the copy's path
+pub fn quarantine_block(data_dir: &Path, table_name: &str, segment_id: SegmentId, block_id: BlockId, block: &Block) -> Result<PathBuf> {
+    let dir = data_dir.join(QUARANTINE_DIR);
+    std::fs::create_dir_all(&dir)
+        .map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+    let path = dir.join(format!("{}.{}.{}.blk", table_name, segment_id, block_id));
+    std::fs::write(&path, block.as_bytes())
+        .and_then(|_| std::fs::File::open(&path)?.sync_all())
+        .map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+    Ok(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::base::{FROZEN_TXID, TupleMeta};
+    use crate::types::Value;
+
+    fn block_with_row() -> Block {
+        let row = Row::new(vec![Value::Int(1), Value::String("one".to_string())]);
+        let data = bincode::encode_to_vec(&row, bincode::config::standard()).unwrap();
+        let mut block = Block::new();
+        block.append_tuple(&TupleMeta::new(FROZEN_TXID), &data).unwrap();
+        block
+    }
+
+    #[test]
+    fn test_check_block() {
+        assert!(check_block(&Block::new()).is_ok());
+        assert!(check_block(&block_with_row()).is_ok());
+        assert!(!is_unwritten(&Block::new()));
+        assert!(is_unwritten(&Block { data: vec![0; Block::new().data.len()] }));
+
+        // A slot pointing past the end of the block
+        let mut block = block_with_row();
+        block.slot_mut(0).length = u16::MAX;
+        assert!(check_block(&block).is_err());
+
+        // More slots than fit before the tuple data
+        let mut block = block_with_row();
+        block.header_mut().slot_count = 20_000;
+        assert!(check_block(&block).is_err());
+
+        // Tuple bytes that are not a row
+        let mut block = Block::new();
+        block.append_tuple(&TupleMeta::new(FROZEN_TXID), &[0xff; 16]).unwrap();
+        let err = check_block(&block).unwrap_err();
+        assert!(err.contains("slot 0"), "unexpected error: {}", err);
+
+        // Unless deleted, which nothing reads
+        let mut meta = TupleMeta::new(FROZEN_TXID);
+        meta.mark_deleted(7);
+        block.set_tuple_meta(0, &meta);
+        assert!(check_block(&block).is_ok());
+    }
+}
@@ -0,0 +1,238 @@
+//! Single-file snapshots of a database, for seeding other environments
+//!
+//! A snapshot packs every database file into one archive, leaving out the
+//! all-zero pages that preallocated table and index blocks are mostly made
+//! of, so a small database makes a small file. Unlike a backup it has no base
+//! and no manifest to keep next to it: it is meant to be copied around,
+//! checked into a fixtures directory or attached to a bug report.
+//!
+//! Layout, all integers little endian:
+//!
+//! ```text
+//! magic "FLSNAP01"
+//! u32 file count
+//! per file:
+//!   u16 name length, name
+//!   u64 size, u32 CRC32 of the whole file
+//!   u32 stored page count
+//!   per stored page: u32 page number, then the page (short if it is the last)
+//! u32 CRC32 of everything above
+//! ```
+
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+
+use tracing::info;
+
+use crate::storage::wal::compute_crc32;
+
+pub type Result<T> = std::result::Result<T, String>;
+
+const MAGIC: &[u8; 8] = b"FLSNAP01";
+
+/// Granularity at which runs of zeroes are left out
+const PAGE_SIZE: usize = 4096;
+
+/// What a snapshot holds
+#[derive(Debug, Default)]
+pub struct SnapshotStats {
+    pub files: usize,
+    /// Total size of the database files
+    pub bytes: u64,
+    /// Size of the snapshot file
+    pub snapshot_bytes: u64,
+}
+
+/// Pack the named files of `data_dir` into a snapshot at `out`, replacing
+/// any file there only once the snapshot is complete
+pub fn create(data_dir: &Path, file_names: &[String], out: &Path) -> Result<SnapshotStats> {
+    let mut archive = MAGIC.to_vec();
+    archive.extend_from_slice(&(file_names.len() as u32).to_le_bytes());
+    let mut stats = SnapshotStats::default();
+
+    for name in file_names {
+        let data = fs::read(data_dir.join(name))
+            .map_err(|e| format!("Failed to read {} for snapshot: {}", name, e))?;
+        let name_len = u16::try_from(name.len()).map_err(|_| format!("File name too long: {}", name))?;
+        archive.extend_from_slice(&name_len.to_le_bytes());
+        archive.extend_from_slice(name.as_bytes());
+        archive.extend_from_slice(&(data.len() as u64).to_le_bytes());
+        archive.extend_from_slice(&compute_crc32(&data).to_le_bytes());
+
+        let pages: Vec<(usize, &[u8])> = data.chunks(PAGE_SIZE)
+            .enumerate()
+            .filter(|(_, page)| page.iter().any(|&byte| byte != 0))
+            .collect();
+        archive.extend_from_slice(&(pages.len() as u32).to_le_bytes());
+        for (page_no, page) in pages {
+            archive.extend_from_slice(&(page_no as u32).to_le_bytes());
+            archive.extend_from_slice(page);
+        }
+        stats.files += 1;
+        stats.bytes += data.len() as u64;
+    }
+    archive.extend_from_slice(&compute_crc32(&archive).to_le_bytes());
+    stats.snapshot_bytes = archive.len() as u64;
+
+    let mut temp_path = out.as_os_str().to_owned();
+    temp_path.push(".tmp");
+    fs::File::create(&temp_path)
+        .and_then(|mut file| file.write_all(&archive).and_then(|_| file.sync_all()))
+        .and_then(|_| fs::rename(&temp_path, out))
+        .map_err(|e| format!("Failed to write snapshot {}: {}", out.display(), e))?;
+    info!(snapshot = %out.display(), files = stats.files, bytes = stats.bytes, snapshot_bytes = stats.snapshot_bytes, "snapshot created");
+    Ok(stats)
+}
+
+/// Unpack a snapshot into `data_dir`, which must not hold a database already
+/// The whole snapshot is checked before anything is written
+/// Returns the number of files restored
+pub fn restore(snapshot: &Path, data_dir: &Path) -> Result<usize> {
+    if crate::storage::holds_database(data_dir) {
+        return Err(format!("Data directory {} already holds a database", data_dir.display()));
+    }
+    let archive = fs::read(snapshot)
+        .map_err(|e| format!("Failed to read snapshot {}: {}", snapshot.display(), e))?;
+    let files = unpack(&archive).map_err(|e| format!("Snapshot {} is invalid: {}", snapshot.display(), e))?;
+
+    fs::create_dir_all(data_dir)
+        .map_err(|e| format!("Failed to create data directory {}: {}", data_dir.display(), e))?;
+    for (name, data) in &files {
+        let path = data_dir.join(name);
+        fs::File::create(&path)
+            .and_then(|mut file| file.write_all(data).and_then(|_| file.sync_all()))
+            .map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+    }
+    info!(snapshot = %snapshot.display(), files = files.len(), "snapshot restored");
+    Ok(files.len())
+}
+
+/// Files in a snapshot archive, each checked against its checksum
+fn unpack(archive: &[u8]) -> Result<Vec<(String, Vec<u8>)>> {
+    let body_len = archive.len().checked_sub(4).ok_or("truncated")?;
+    let (body, checksum) = archive.split_at(body_len);
+    if compute_crc32(body) != u32::from_le_bytes(checksum.try_into().unwrap()) {
+        return Err("checksum mismatch".to_string());
+    }
+    let mut reader = Reader { rest: body };
+    if reader.take(MAGIC.len())? != MAGIC {
+        return Err("not a flint snapshot".to_string());
+    }
+
+    let file_count = reader.u32()?;
+    let mut files = Vec::new();
+    for _ in 0..file_count {
+        let name_len = u16::from_le_bytes(reader.take(2)?.try_into().unwrap()) as usize;
+        let name = String::from_utf8(reader.take(name_len)?.to_vec()).map_err(|_| "invalid file name")?;
+        if name.contains('/') || name.starts_with('.') {
+            return Err(format!("invalid file name {}", name));
+        }
+        let size = u64::from_le_bytes(reader.take(8)?.try_into().unwrap()) as usize;
+        let file_checksum = reader.u32()?;
+
+        let mut data = vec![0; size];
+        for _ in 0..reader.u32()? {
+            let offset = (reader.u32()? as usize).saturating_mul(PAGE_SIZE);
+            if offset >= size {
+                return Err(format!("page past the end of {}", name));
+            }
+            let len = (size - offset).min(PAGE_SIZE);
+            data[offset..offset + len].copy_from_slice(reader.take(len)?);
+        }
+        if compute_crc32(&data) != file_checksum {
+            return Err(format!("{} does not match its checksum", name));
+        }
+        files.push((name, data));
+    }
+    if !reader.rest.is_empty() {
+        return Err("unexpected data after the last file".to_string());
+    }
+    Ok(files)
+}
+
+struct Reader<'a> {
+    rest: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8]> {
+        if self.rest.len() < len {
+            return Err("truncated".to_string());
+        }
+        let (taken, rest) = self.rest.split_at(len);
+        self.rest = rest;
+        Ok(taken)
+    }
+
+    fn u32(&mut self) -> Result<u32> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("flint-snapshot-test-{}-{}", std::process::id(), name));
+        let _ = fs::remove_dir_all(&dir);
+        dir
+    }
+
+    #[test]
+    fn test_snapshot_round_trip() {
+        let data_dir = temp_dir("data");
+        fs::create_dir_all(&data_dir).unwrap();
+        // A mostly empty preallocated file, as table files are
+        let mut table = vec![0u8; PAGE_SIZE * 48];
+        table[10] = 1;
+        table[PAGE_SIZE * 20 + 5] = 2;
+        fs::write(data_dir.join("table_a.tbl"), &table).unwrap();
+        fs::write(data_dir.join("catalog_0.db"), b"catalog").unwrap();
+        fs::write(data_dir.join("flint.wal"), b"").unwrap();
+
+        let out = temp_dir("out.snap");
+        let names = ["catalog_0.db", "table_a.tbl", "flint.wal"].map(String::from);
+        let stats = create(&data_dir, &names, &out).unwrap();
+        assert_eq!(stats.files, 3);
+        assert!(stats.snapshot_bytes < (PAGE_SIZE * 3) as u64, "zero pages should be left out: {:?}", stats);
+
+        let restored = temp_dir("restored");
+        assert_eq!(restore(&out, &restored).unwrap(), 3);
+        assert_eq!(fs::read(restored.join("table_a.tbl")).unwrap(), table);
+        assert_eq!(fs::read(restored.join("catalog_0.db")).unwrap(), b"catalog");
+        assert_eq!(fs::read(restored.join("flint.wal")).unwrap(), b"");
+        assert!(restore(&out, &restored).is_err(), "restoring over a database should fail");
+
+        for path in [&data_dir, &restored] {
+            let _ = fs::remove_dir_all(path);
+        }
+        let _ = fs::remove_file(&out);
+    }
+
+    #[test]
+    fn test_damaged_snapshot_is_rejected() {
+        let data_dir = temp_dir("damaged-data");
+        fs::create_dir_all(&data_dir).unwrap();
+        fs::write(data_dir.join("catalog_0.db"), b"catalog").unwrap();
+        let out = temp_dir("damaged.snap");
+        create(&data_dir, &["catalog_0.db".to_string()], &out).unwrap();
+
+        let mut archive = fs::read(&out).unwrap();
+        archive[MAGIC.len() + 8] ^= 1;
+        fs::write(&out, &archive).unwrap();
+        let restored = temp_dir("damaged-restored");
+        let err = restore(&out, &restored).unwrap_err();
+        assert!(err.contains("checksum mismatch"), "unexpected error: {}", err);
+        assert!(!restored.join("catalog_0.db").exists());
+
+        fs::write(&out, &archive[..archive.len() / 2]).unwrap();
+        assert!(restore(&out, &restored).is_err(), "a truncated snapshot should be rejected");
+
+        let _ = fs::remove_dir_all(&data_dir);
+        let _ = fs::remove_dir_all(&restored);
+        let _ = fs::remove_file(&out);
+    }
+}
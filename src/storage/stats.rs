@@ -0,0 +1,198 @@
+//! Counters of the I/O storage does, listed by pg_stat_io and pg_stat_wal
+//!
+//! Every file goes through a Disk, which counts its reads, writes and fsyncs
+//! under the kind of object the file holds. The counters are process-wide, as
+//! Postgres's are cluster-wide, and start from zero when the server starts.
+//! There is no buffer pool: files are opened for direct I/O and every block
+//! read goes to disk, so there are no hits or evictions to count yet.
+//! Searches of primary and secondary indexes are counted too, one per lookup
+//! however many entries it finds.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// Kind of object a file holds, the `object` column of pg_stat_io
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IoObject {
+    /// Table data
+    Relation,
+    /// Primary and secondary indexes
+    Index,
+    /// The write-ahead log
+    Wal,
+}
+
+impl IoObject {
+    const ALL: [IoObject; 3] = [IoObject::Relation, IoObject::Index, IoObject::Wal];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            IoObject::Relation => "relation",
+            IoObject::Index => "index",
+            IoObject::Wal => "wal",
+        }
+    }
+}
+
+struct IoCounters {
+    reads: AtomicU64,
+    read_bytes: AtomicU64,
+    writes: AtomicU64,
+    write_bytes: AtomicU64,
+    fsyncs: AtomicU64,
+}
+
+impl IoCounters {
+    const fn new() -> Self {
+        IoCounters {
+            reads: AtomicU64::new(0),
+            read_bytes: AtomicU64::new(0),
+            writes: AtomicU64::new(0),
+            write_bytes: AtomicU64::new(0),
+            fsyncs: AtomicU64::new(0),
+        }
+    }
+}
+
+/// One set of counters per IoObject, in the order of IoObject::ALL
+static IO_COUNTERS: [IoCounters; 3] = [IoCounters::new(), IoCounters::new(), IoCounters::new()];
+static WAL_RECORDS: AtomicU64 = AtomicU64::new(0);
+static INDEX_LOOKUPS: AtomicU64 = AtomicU64::new(0);
+
+fn counters(object: IoObject) -> &'static IoCounters {
+    &IO_COUNTERS[object as usize]
+}
+
+/// Count a read of `bytes` bytes
+pub fn record_read(object: IoObject, bytes: usize) {
+    let counters = counters(object);
+    counters.reads.fetch_add(1, Ordering::Relaxed);
+    counters.read_bytes.fetch_add(bytes as u64, Ordering::Relaxed);
+}
+
+/// Count a write of `bytes` bytes
+pub fn record_write(object: IoObject, bytes: usize) {
+    let counters = counters(object);
+    counters.writes.fetch_add(1, Ordering::Relaxed);
+    counters.write_bytes.fetch_add(bytes as u64, Ordering::Relaxed);
+}
+
+/// Count a flush of a file to stable storage
+pub fn record_fsync(object: IoObject) {
+    counters(object).fsyncs.fetch_add(1, Ordering::Relaxed);
+}
+
+/// Count a record appended to the WAL; its write is counted by the Disk
+pub fn record_wal_record() {
+    WAL_RECORDS.fetch_add(1, Ordering::Relaxed);
+}
+
+/// Count a search of an index, whether for one key or a range
+pub fn record_index_lookup() {
+    INDEX_LOOKUPS.fetch_add(1, Ordering::Relaxed);
+}
+
+/// Index searches since the server started
+pub fn index_lookups() -> u64 {
+    INDEX_LOOKUPS.load(Ordering::Relaxed)
+}
+
+/// I/O done on one kind of object since the server started
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IoStats {
+    pub object: IoObject,
+    pub reads: u64,
+    pub read_bytes: u64,
+    pub writes: u64,
+    pub write_bytes: u64,
+    /// Reads answered from memory; always 0 until there is a buffer pool
+    pub hits: u64,
+    /// Blocks dropped from memory to make room; always 0 until there is a buffer pool
+    pub evictions: u64,
+    pub fsyncs: u64,
+}
+
+impl IoStats {
+    /// Share of reads answered from memory, None before any read
+    pub fn hit_ratio(&self) -> Option<f64> {
+        let lookups = self.hits + self.reads;
+        (lookups > 0).then(|| self.hits as f64 / lookups as f64)
+    }
+}
+
+/// Current counters of every kind of object
+/// This is the one place the counters are read, so every report of them
+/// shows the same numbers
+pub fn io_stats() -> Vec<IoStats> {
+    IoObject::ALL.iter()
+        .map(|&object| {
+            let counters = counters(object);
+            IoStats {
+                object,
+                reads: counters.reads.load(Ordering::Relaxed),
+                read_bytes: counters.read_bytes.load(Ordering::Relaxed),
+                writes: counters.writes.load(Ordering::Relaxed),
+                write_bytes: counters.write_bytes.load(Ordering::Relaxed),
+                hits: 0,
+                evictions: 0,
+                fsyncs: counters.fsyncs.load(Ordering::Relaxed),
+            }
+        })
+        .collect()
+}
+
+/// WAL activity since the server started
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WalStats {
+    pub records: u64,
+    /// Bytes written to the WAL, entries padded to the direct I/O alignment
+    pub bytes: u64,
+    pub writes: u64,
+    pub syncs: u64,
+}
+
+/// Current WAL counters, consistent with the WAL row of `io_stats`
+pub fn wal_stats() -> WalStats {
+    let counters = counters(IoObject::Wal);
+    WalStats {
+        records: WAL_RECORDS.load(Ordering::Relaxed),
+        bytes: counters.write_bytes.load(Ordering::Relaxed),
+        writes: counters.writes.load(Ordering::Relaxed),
+        syncs: counters.fsyncs.load(Ordering::Relaxed),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stats_of(object: IoObject) -> IoStats {
+        io_stats().into_iter().find(|stats| stats.object == object).unwrap()
+    }
+
+    #[test]
+    fn test_io_counters() {
+        // Counters are shared with every other test, so only growth is checked
+        let before = stats_of(IoObject::Index);
+        let wal_before = wal_stats();
+
+        record_read(IoObject::Index, 4096);
+        record_read(IoObject::Index, 4096);
+        record_write(IoObject::Index, 4096);
+        record_fsync(IoObject::Index);
+        record_write(IoObject::Wal, 512);
+        record_wal_record();
+        let lookups_before = index_lookups();
+        record_index_lookup();
+
+        let after = stats_of(IoObject::Index);
+        assert!(after.reads >= before.reads + 2);
+        assert!(after.read_bytes >= before.read_bytes + 8192);
+        assert!(after.writes > before.writes && after.fsyncs > before.fsyncs);
+        assert_eq!(after.hit_ratio(), Some(0.0), "there is no buffer pool to hit");
+
+        let wal_after = wal_stats();
+        assert!(wal_after.records > wal_before.records);
+        assert!(wal_after.bytes >= wal_before.bytes + 512);
+        assert!(index_lookups() > lookups_before);
+    }
+}
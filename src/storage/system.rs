@@ -0,0 +1,501 @@
+//! Read-only system views derived from the catalog
+//! A small subset of information_schema and pg_catalog, enough for schema
+//! documentation tools to list tables, columns and their comments, and for
+//! psql's meta-commands to describe them (see `executor::psql`), plus the
+//! progress of running index builds and the I/O counters of `stats`
+
+use crate::storage::catalog::{Catalog, TableFileMetadata, FILLFACTOR_DEFAULT};
+use crate::storage::progress::{Command, ProgressRegistry};
+use crate::storage::stats;
+use crate::types::{Column, DataType, Row, Schema, Value};
+
+/// Schema of every table not made in another schema
+pub const PUBLIC_SCHEMA: &str = "public";
+
+/// Object id of pg_class, the classoid of table and column descriptions
+const PG_CLASS_OID: i64 = 1259;
+
+/// Object ids of the schemas every database has, as in Postgres
+const PG_CATALOG_NAMESPACE_OID: i64 = 11;
+const PUBLIC_NAMESPACE_OID: i64 = 2200;
+const INFORMATION_SCHEMA_NAMESPACE_OID: i64 = 13000;
+
+/// Object id of the `pg_temp_1` schema; `pg_temp_<n>` is this plus n - 1, all
+/// below the first object id of a table
+const FIRST_TEMP_NAMESPACE_OID: i64 = 15000;
+
+/// Object id of the bootstrap superuser, the owner of every object
+const OWNER_OID: i64 = 10;
+
+/// Name pg_get_userbyid() gives the owner of every object
+pub const OWNER_NAME: &str = "postgres";
+
+/// pg_type object ids of the built-in types
+const BOOL_OID: i64 = 16;
+const INT8_OID: i64 = 20;
+const TEXT_OID: i64 = 25;
+const FLOAT8_OID: i64 = 701;
+/// varchar's type modifier is its length plus 4, as in Postgres
+const VARCHAR_OID: i64 = 1043;
+
+/// The built-in types: object id, pg_type name, length (-1 for
+/// variable-length types) and the name format_type() gives them
+const BUILTIN_TYPES: &[(i64, &str, i64, &str)] = &[
+    (BOOL_OID, "bool", 1, "boolean"),
+    (INT8_OID, "int8", 8, "bigint"),
+    (TEXT_OID, "text", -1, "text"),
+    (FLOAT8_OID, "float8", 8, "double precision"),
+    (VARCHAR_OID, "varchar", -1, "character varying"),
+];
+
+/// A catalog-backed view that can be scanned like a table
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SystemView {
+    /// information_schema.tables
+    Tables,
+    /// information_schema.columns
+    Columns,
+    /// pg_catalog.pg_class
+    PgClass,
+    /// pg_catalog.pg_namespace
+    PgNamespace,
+    /// pg_catalog.pg_attribute
+    PgAttribute,
+    /// pg_catalog.pg_type
+    PgType,
+    /// pg_catalog.pg_description
+    PgDescription,
+    /// pg_catalog.pg_stat_progress_create_index
+    PgStatProgressCreateIndex,
+    /// pg_catalog.pg_stat_io
+    PgStatIo,
+    /// pg_catalog.pg_stat_wal
+    PgStatWal,
+}
+
+impl SystemView {
+    /// Resolve a (possibly schema-qualified) table name to a system view
+    /// pg_catalog views also resolve unqualified, as they do in Postgres
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "information_schema.tables" => Some(SystemView::Tables),
+            "information_schema.columns" => Some(SystemView::Columns),
+            "pg_catalog.pg_class" | "pg_class" => Some(SystemView::PgClass),
+            "pg_catalog.pg_namespace" | "pg_namespace" => Some(SystemView::PgNamespace),
+            "pg_catalog.pg_attribute" | "pg_attribute" => Some(SystemView::PgAttribute),
+            "pg_catalog.pg_type" | "pg_type" => Some(SystemView::PgType),
+            "pg_catalog.pg_description" | "pg_description" => Some(SystemView::PgDescription),
+            "pg_catalog.pg_stat_progress_create_index" | "pg_stat_progress_create_index" => {
+                Some(SystemView::PgStatProgressCreateIndex)
+            }
+            "pg_catalog.pg_stat_io" | "pg_stat_io" => Some(SystemView::PgStatIo),
+            "pg_catalog.pg_stat_wal" | "pg_stat_wal" => Some(SystemView::PgStatWal),
+            _ => None,
+        }
+    }
+
+    pub fn schema(&self) -> Schema {
+        let columns: &[(&str, DataType)] = match self {
+            SystemView::Tables => &[
+                ("table_schema", DataType::String),
+                ("table_name", DataType::String),
+                ("table_type", DataType::String),
+            ],
+            SystemView::Columns => &[
+                ("table_schema", DataType::String),
+                ("table_name", DataType::String),
+                ("column_name", DataType::String),
+                ("ordinal_position", DataType::Int),
+                ("data_type", DataType::String),
+                ("character_maximum_length", DataType::Int),
+            ],
+            SystemView::PgClass => &[
+                ("oid", DataType::Int),
+                ("relname", DataType::String),
+                ("relnamespace", DataType::Int),
+                ("relkind", DataType::String),
+                ("relowner", DataType::Int),
+                ("relhasindex", DataType::Bool),
+                ("reloptions", DataType::String),
+                ("relpersistence", DataType::String),
+                ("relreplident", DataType::String),
+            ],
+            SystemView::PgNamespace => &[
+                ("oid", DataType::Int),
+                ("nspname", DataType::String),
+                ("nspowner", DataType::Int),
+            ],
+            SystemView::PgAttribute => &[
+                ("attrelid", DataType::Int),
+                ("attname", DataType::String),
+                ("atttypid", DataType::Int),
+                ("atttypmod", DataType::Int),
+                ("attnum", DataType::Int),
+                ("attnotnull", DataType::Bool),
+                ("attisdropped", DataType::Bool),
+                ("attstorage", DataType::String),
+            ],
+            SystemView::PgType => &[
+                ("oid", DataType::Int),
+                ("typname", DataType::String),
+                ("typnamespace", DataType::Int),
+                ("typlen", DataType::Int),
+                ("typtype", DataType::String),
+            ],
+            SystemView::PgDescription => &[
+                ("objoid", DataType::Int),
+                ("classoid", DataType::Int),
+                ("objsubid", DataType::Int),
+                ("description", DataType::String),
+            ],
+            SystemView::PgStatProgressCreateIndex => &[
+                ("relid", DataType::Int),
+                ("relname", DataType::String),
+                ("index_name", DataType::String),
+                ("command", DataType::String),
+                ("phase", DataType::String),
+                ("tuples_total", DataType::Int),
+                ("tuples_done", DataType::Int),
+            ],
+            SystemView::PgStatIo => &[
+                ("backend_type", DataType::String),
+                ("object", DataType::String),
+                ("context", DataType::String),
+                ("reads", DataType::Int),
+                ("read_bytes", DataType::Int),
+                ("writes", DataType::Int),
+                ("write_bytes", DataType::Int),
+                ("hits", DataType::Int),
+                ("evictions", DataType::Int),
+                ("fsyncs", DataType::Int),
+                ("hit_ratio", DataType::Float),
+            ],
+            SystemView::PgStatWal => &[
+                ("wal_records", DataType::Int),
+                ("wal_bytes", DataType::Int),
+                ("wal_write", DataType::Int),
+                ("wal_sync", DataType::Int),
+            ],
+        };
+        Schema::new(columns.iter()
+            .map(|(name, data_type)| Column {
+                name: name.to_string(),
+                data_type: data_type.clone(),
+                is_primary_key: false,
+                typmod: None,
+            })
+            .collect())
+    }
+
+    /// Current contents of the view, ordered by table name
+    /// pg_class lists sequences after the tables
+    pub fn rows(&self, catalog: &Catalog, progress: &ProgressRegistry) -> Vec<Row> {
+        match self {
+            SystemView::PgNamespace => return namespace_rows(catalog),
+            SystemView::PgType => return type_rows(catalog),
+            SystemView::PgStatProgressCreateIndex => return progress_rows(progress),
+            SystemView::PgStatIo => return io_rows(),
+            SystemView::PgStatWal => return vec![wal_row()],
+            _ => {}
+        }
+
+        let mut tables = catalog.all_tables();
+        tables.sort_by(|a, b| a.name.cmp(&b.name));
+
+        let mut rows = Vec::new();
+        for table in tables {
+            // A temporary table is stored as `pg_temp_<n>.<name>`, a table
+            // of another schema as `<schema>.<name>`
+            let temporary = catalog.is_temporary(&table.name);
+            let (schema, name) = split_name(catalog, &table.name, temporary);
+            match self {
+                SystemView::Tables => rows.push(Row::new(vec![
+                    Value::String(schema.to_string()),
+                    Value::String(name.to_string()),
+                    Value::String(if temporary { "LOCAL TEMPORARY" } else { "BASE TABLE" }.to_string()),
+                ])),
+                SystemView::Columns => {
+                    for (position, column) in table.schema.columns.iter().enumerate() {
+                        rows.push(Row::new(vec![
+                            Value::String(schema.to_string()),
+                            Value::String(name.to_string()),
+                            Value::String(column.name.clone()),
+                            Value::Int(position as i64 + 1),
+                            Value::String(sql_type_name(&column.data_type).to_string()),
+                            match (&column.data_type, column.typmod) {
+                                (DataType::String, Some(length)) => Value::Int(length as i64),
+                                _ => Value::Null,
+                            },
+                        ]));
+                    }
+                }
+                SystemView::PgClass => rows.push(Row::new(vec![
+                    Value::Int(table.oid as i64),
+                    Value::String(name.to_string()),
+                    Value::Int(namespace_oid(catalog, schema)),
+                    Value::String("r".to_string()),
+                    Value::Int(OWNER_OID),
+                    Value::Bool(table.primary_index.is_some() || !table.secondary_indexes.is_empty()),
+                    reloptions(table),
+                    Value::String(if temporary { "t" } else { "p" }.to_string()),
+                    Value::String("d".to_string()),
+                ])),
+                SystemView::PgAttribute => {
+                    for (position, column) in table.schema.columns.iter().enumerate() {
+                        let (type_oid, typmod) = type_of(column);
+                        rows.push(Row::new(vec![
+                            Value::Int(table.oid as i64),
+                            Value::String(column.name.clone()),
+                            Value::Int(type_oid),
+                            Value::Int(typmod),
+                            Value::Int(position as i64 + 1),
+                            Value::Bool(column.is_primary_key),
+                            Value::Bool(false),
+                            Value::String(storage_of(type_oid).to_string()),
+                        ]));
+                    }
+                }
+                SystemView::PgDescription => rows.extend(descriptions(table)),
+                SystemView::PgNamespace
+                | SystemView::PgType
+                | SystemView::PgStatProgressCreateIndex
+                | SystemView::PgStatIo
+                | SystemView::PgStatWal => {}
+            }
+        }
+
+        if *self == SystemView::PgClass {
+            let mut sequences = catalog.all_sequences();
+            sequences.sort_by(|a, b| a.name.cmp(&b.name));
+            rows.extend(sequences.into_iter().map(|sequence| {
+                let (schema, name) = split_name(catalog, &sequence.name, false);
+                Row::new(vec![
+                    Value::Int(sequence.oid as i64),
+                    Value::String(name.to_string()),
+                    Value::Int(namespace_oid(catalog, schema)),
+                    Value::String("S".to_string()),
+                    Value::Int(OWNER_OID),
+                    Value::Bool(false),
+                    Value::Null,
+                    Value::String("p".to_string()),
+                    Value::String("n".to_string()),
+                ])
+            }));
+        }
+        rows
+    }
+}
+
+/// Schema and name of a table or sequence stored as `<schema>.<name>`; any
+/// other name, even with a dot in it, is in `public`
+fn split_name<'a>(catalog: &Catalog, stored_name: &'a str, temporary: bool) -> (&'a str, &'a str) {
+    stored_name.split_once('.')
+        .filter(|(schema, _)| temporary || catalog.get_schema(schema).is_some())
+        .unwrap_or((PUBLIC_SCHEMA, stored_name))
+}
+
+/// pg_class.reloptions of a table: the storage options it was given, as
+/// `{name=value,...}`, or NULL if it has none
+fn reloptions(table: &TableFileMetadata) -> Value {
+    let mut options = Vec::new();
+    if table.fillfactor != FILLFACTOR_DEFAULT {
+        options.push(format!("fillfactor={}", table.fillfactor));
+    }
+    if let Some(ttl) = &table.ttl {
+        options.push(format!("ttl_column={}", ttl.column));
+        options.push(format!("ttl={}", ttl.seconds));
+    }
+    if options.is_empty() {
+        return Value::Null;
+    }
+    Value::String(format!("{{{}}}", options.join(",")))
+}
+
+/// Object id of a schema: the fixed ones, one made by CREATE SCHEMA, or
+/// `pg_temp_<n>`
+fn namespace_oid(catalog: &Catalog, schema: &str) -> i64 {
+    match schema {
+        "pg_catalog" => PG_CATALOG_NAMESPACE_OID,
+        "information_schema" => INFORMATION_SCHEMA_NAMESPACE_OID,
+        _ if let Some(schema_meta) = catalog.get_schema(schema) => schema_meta.oid as i64,
+        _ => schema.strip_prefix("pg_temp_")
+            .and_then(|n| n.parse::<i64>().ok())
+            .map_or(PUBLIC_NAMESPACE_OID, |n| FIRST_TEMP_NAMESPACE_OID + n - 1),
+    }
+}
+
+/// pg_namespace rows: the schemas every database has, those made by CREATE
+/// SCHEMA, then the temporary schemas that have tables in them
+fn namespace_rows(catalog: &Catalog) -> Vec<Row> {
+    let mut schemas = vec!["pg_catalog".to_string(), PUBLIC_SCHEMA.to_string(), "information_schema".to_string()];
+    let mut created: Vec<String> = catalog.all_schemas().into_iter()
+        .map(|schema_meta| schema_meta.name.clone())
+        .collect();
+    created.sort();
+    schemas.extend(created);
+    let mut temporary: Vec<String> = catalog.all_tables().into_iter()
+        .filter(|table| catalog.is_temporary(&table.name))
+        .filter_map(|table| table.name.split_once('.').map(|(schema, _)| schema.to_string()))
+        .collect();
+    temporary.sort();
+    temporary.dedup();
+    schemas.extend(temporary);
+
+    schemas.into_iter()
+        .map(|schema| Row::new(vec![
+            Value::Int(namespace_oid(catalog, &schema)),
+            Value::String(schema),
+            Value::Int(OWNER_OID),
+        ]))
+        .collect()
+}
+
+/// pg_type rows: the built-in types, then the extension types columns use
+fn type_rows(catalog: &Catalog) -> Vec<Row> {
+    let mut types: Vec<(i64, String, i64)> = BUILTIN_TYPES.iter()
+        .map(|(oid, name, length, _)| (*oid, name.to_string(), *length))
+        .collect();
+    let mut extension_types: Vec<(i64, String, i64)> = catalog.all_tables().into_iter()
+        .flat_map(|table| table.schema.columns.iter())
+        .filter_map(|column| match &column.data_type {
+            DataType::Extension { type_oid, type_name } => Some((*type_oid as i64, type_name.clone(), -1)),
+            _ => None,
+        })
+        .collect();
+    extension_types.sort();
+    extension_types.dedup();
+    types.extend(extension_types);
+
+    types.into_iter()
+        .map(|(oid, name, length)| Row::new(vec![
+            Value::Int(oid),
+            Value::String(name),
+            Value::Int(PG_CATALOG_NAMESPACE_OID),
+            Value::Int(length),
+            Value::String("b".to_string()),
+        ]))
+        .collect()
+}
+
+/// pg_attribute's atttypid and atttypmod of a column: the object id of the
+/// type it is sent as, and its type modifier or -1
+fn type_of(column: &Column) -> (i64, i64) {
+    let type_oid = match &column.data_type {
+        DataType::Int => INT8_OID,
+        DataType::Float => FLOAT8_OID,
+        DataType::String => VARCHAR_OID,
+        DataType::Bool => BOOL_OID,
+        DataType::Null => TEXT_OID,
+        DataType::Extension { type_oid, .. } => *type_oid as i64,
+    };
+    let typmod = match (type_oid, column.typmod) {
+        (VARCHAR_OID, Some(length)) => length as i64 + 4,
+        (_, Some(typmod)) => typmod as i64,
+        (_, None) => -1,
+    };
+    (type_oid, typmod)
+}
+
+/// pg_attribute.attstorage of a column of a type: `p` (plain) for the
+/// fixed-length types, `x` (extended) for the others
+fn storage_of(type_oid: i64) -> &'static str {
+    match BUILTIN_TYPES.iter().find(|(oid, ..)| *oid == type_oid) {
+        Some((_, _, length, _)) if *length > 0 => "p",
+        _ => "x",
+    }
+}
+
+/// format_type(): the SQL name of a type with its modifier, given its
+/// object id and, for an extension type, its pg_type name
+pub fn format_type(type_oid: i64, typmod: i64, type_name: Option<&str>) -> Option<String> {
+    let name = BUILTIN_TYPES.iter()
+        .find(|(oid, ..)| *oid == type_oid)
+        .map(|(.., name)| *name)
+        .or(type_name)?;
+    Some(match (type_oid, typmod) {
+        (_, typmod) if typmod < 0 => name.to_string(),
+        (VARCHAR_OID, typmod) => format!("{}({})", name, typmod - 4),
+        (_, typmod) => format!("{}({})", name, typmod),
+    })
+}
+
+/// pg_description rows for a table: objsubid 0 is the table itself, otherwise
+/// the 1-based column number
+fn descriptions(table: &TableFileMetadata) -> Vec<Row> {
+    let description = |objsubid: i64, text: &str| Row::new(vec![
+        Value::Int(table.oid as i64),
+        Value::Int(PG_CLASS_OID),
+        Value::Int(objsubid),
+        Value::String(text.to_string()),
+    ]);
+
+    let mut rows = Vec::new();
+    if let Some(comment) = &table.comment {
+        rows.push(description(0, comment));
+    }
+    for (position, column) in table.schema.columns.iter().enumerate() {
+        if let Some(comment) = table.column_comment(&column.name) {
+            rows.push(description(position as i64 + 1, comment));
+        }
+    }
+    rows
+}
+
+/// pg_stat_progress_create_index rows: one per index build, oldest first
+fn progress_rows(progress: &ProgressRegistry) -> Vec<Row> {
+    progress.operations().into_iter()
+        .filter(|operation| operation.command == Command::CreateIndex)
+        .map(|operation| Row::new(vec![
+            Value::Int(operation.relid as i64),
+            Value::String(operation.relname),
+            operation.index_name.map_or(Value::Null, Value::String),
+            Value::String(operation.command.name().to_string()),
+            Value::String(operation.phase.to_string()),
+            Value::Int(operation.total as i64),
+            Value::Int(operation.done as i64),
+        ]))
+        .collect()
+}
+
+/// pg_stat_io rows: one per kind of object, all done by client backends in
+/// the normal context, as flint has no background processes of its own
+fn io_rows() -> Vec<Row> {
+    stats::io_stats().into_iter()
+        .map(|io| Row::new(vec![
+            Value::String("client backend".to_string()),
+            Value::String(io.object.name().to_string()),
+            Value::String("normal".to_string()),
+            Value::Int(io.reads as i64),
+            Value::Int(io.read_bytes as i64),
+            Value::Int(io.writes as i64),
+            Value::Int(io.write_bytes as i64),
+            Value::Int(io.hits as i64),
+            Value::Int(io.evictions as i64),
+            Value::Int(io.fsyncs as i64),
+            io.hit_ratio().map_or(Value::Null, Value::Float),
+        ]))
+        .collect()
+}
+
+/// The single pg_stat_wal row
+fn wal_row() -> Row {
+    let wal = stats::wal_stats();
+    Row::new(vec![
+        Value::Int(wal.records as i64),
+        Value::Int(wal.bytes as i64),
+        Value::Int(wal.writes as i64),
+        Value::Int(wal.syncs as i64),
+    ])
+}
+
+/// Name information_schema uses for a column type
+fn sql_type_name(data_type: &DataType) -> &str {
+    match data_type {
+        DataType::Int => "integer",
+        DataType::Float => "double precision",
+        DataType::String => "character varying",
+        DataType::Bool => "boolean",
+        DataType::Null => "unknown",
+        DataType::Extension { type_name, .. } => type_name,
+    }
+}
@@ -1,6 +1,9 @@
+use std::fmt;
 use std::io::{self, Result};
 use std::path::{Path, PathBuf};
-use crate::storage::io::{Disk, alloc_aligned};
+use crate::storage::base::{TuplePointer, TxId};
+use crate::storage::io::{Disk, alloc_aligned, ALIGNMENT};
+use crate::storage::stats::{self, IoObject};
 use bincode::{Encode, Decode};
 
 /// WAL entry type
@@ -16,6 +19,10 @@ pub enum WalEntryType {
     Ddl = 4,
     /// Checkpoint marker
     Checkpoint = 5,
+    /// Sequence state change (nextval batch or setval)
+    Sequence = 6,
+    /// Commit of a transaction that wrote tuples
+    Commit = 7,
 }
 
 impl WalEntryType {
@@ -26,12 +33,64 @@ impl WalEntryType {
             3 => Some(WalEntryType::Update),
             4 => Some(WalEntryType::Ddl),
             5 => Some(WalEntryType::Checkpoint),
+            6 => Some(WalEntryType::Sequence),
+            7 => Some(WalEntryType::Commit),
             _ => None,
         }
     }
 }
 
-/// WAL entry header (48 bytes)
+/// Payload of an Insert, Update or Delete entry: one statement's changes to
+/// the tuples of a table, logged before any of them reach its blocks
+#[derive(Debug, Clone, Encode, Decode)]
+pub struct TupleRecord {
+    /// Object id of the table
+    pub relid: u32,
+    /// Transaction that made the changes, stamped on the tuples it writes or deletes
+    pub txid: TxId,
+    pub changes: Vec<TupleChange>,
+}
+
+#[derive(Debug, Clone, Encode, Decode)]
+pub struct TupleChange {
+    /// Where the tuple is written, or for a Delete the tuple deleted
+    pub tuple_ptr: TuplePointer,
+    /// Version an Update replaces, deleted once the new one is written
+    pub old_tuple_ptr: Option<TuplePointer>,
+    /// Encoded row; empty for a Delete
+    pub tuple: Vec<u8>,
+}
+
+/// Payload of a Commit entry; a transaction whose tuple changes are logged
+/// without one never committed
+#[derive(Debug, Clone, Encode, Decode)]
+pub struct CommitRecord {
+    pub txid: TxId,
+}
+
+/// Change to a table's definition or files recorded by a Ddl entry
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Encode, Decode)]
+pub enum DdlOperation {
+    Create,
+    Rename,
+    Truncate,
+    Compact,
+}
+
+/// Payload of a Ddl entry; the change itself is committed by the catalog
+/// save that follows it, which also ends the log
+#[derive(Debug, Clone, Encode, Decode)]
+pub struct DdlRecord {
+    /// Object id of the table
+    pub relid: u32,
+    pub table: String,
+    pub operation: DdlOperation,
+}
+
+/// Sequence number of the first entry after the log is reset
+pub const FIRST_SEQ: u64 = 1;
+
+/// WAL entry header (56 bytes)
 #[derive(Debug, Clone, Copy, Encode, Decode)]
 #[repr(C)]
 pub struct WalEntryHeader {
@@ -43,10 +102,13 @@ pub struct WalEntryHeader {
     pub payload_len: u32,
     /// LSN (Log Sequence Number) / entry offset in log
     pub lsn: u64,
+    /// Position of the entry in the log, counting from `FIRST_SEQ`; set by
+    /// `WalFile::append`
+    pub seq: u64,
     /// CRC32 of entire entry (header + payload)
     pub crc32: u32,
-    /// Padding to reach 48 bytes
-    pub _reserved: [u8; 27],
+    /// Padding to reach 56 bytes
+    pub _reserved: [u8; 19],
 }
 
 impl WalEntryHeader {
@@ -58,8 +120,9 @@ impl WalEntryHeader {
             entry_type: entry_type as u8,
             payload_len,
             lsn,
+            seq: 0, // Will be set when writing
             crc32: 0, // Will be set when writing
-            _reserved: [0; 27],
+            _reserved: [0; 19],
         }
     }
 
@@ -97,35 +160,103 @@ impl WalEntry {
     }
 }
 
+/// Where a scan of the log stopped, and why
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum WalEnd {
+    /// The file ends right after the last entry
+    #[default]
+    Clean,
+    /// Only zeros follow the last entry, where the file was extended for a
+    /// write that never reached the disk
+    ZeroFilled,
+    /// The last entry was cut short by a crash: the file ends inside it, or
+    /// it fails validation with nothing written after it
+    Torn,
+    /// An entry fails validation with more of the log after it; the rest is
+    /// ignored too, as it can't be trusted to follow on from what came before
+    Corrupt(String),
+    /// The file could not be read
+    Unreadable(String),
+}
+
+impl WalEnd {
+    /// Whether the log ends where it was last written, with nothing after its
+    /// last entry to ignore
+    pub fn is_clean(&self) -> bool {
+        *self == WalEnd::Clean
+    }
+}
+
+impl fmt::Display for WalEnd {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            WalEnd::Clean => write!(f, "clean"),
+            WalEnd::ZeroFilled => write!(f, "zero-filled tail"),
+            WalEnd::Torn => write!(f, "torn last entry"),
+            WalEnd::Corrupt(reason) => write!(f, "corrupt: {}", reason),
+            WalEnd::Unreadable(error) => write!(f, "unreadable: {}", error),
+        }
+    }
+}
+
+/// What reading the log at an offset found
+enum ReadOutcome {
+    /// A valid entry, and the offset the next one starts at
+    Entry(WalEntry, u64),
+    End(WalEnd),
+}
+
+/// Size of the reads that check a zeroed tail runs to the end of the file
+const ZERO_CHECK_CHUNK: usize = 64 * 1024;
+
 /// WalFile manages append-only write-ahead log
-/// Writes are sequential and buffered for performance
+/// Writes are sequential; each entry starts on an ALIGNMENT boundary and is
+/// padded to a multiple of it, as Direct I/O requires
 pub struct WalFile {
     disk: Disk,
     path: PathBuf,
     /// Current write offset (next entry will be written here)
     next_offset: u64,
+    /// Sequence number of the next entry appended
+    next_seq: u64,
+    /// How the log ended when it was opened; anything but Clean leaves bytes
+    /// past `next_offset` until the log is reset
+    end: WalEnd,
 }
 
 impl WalFile {
     /// Open or create a WAL file
+    /// Appends go after the last valid entry, continuing its sequence, so a
+    /// log that doesn't end cleanly should be read and then reset first
     pub fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
-        let disk = Disk::open(&path)?;
+        let disk = Disk::open(&path, IoObject::Wal)?;
         let path = path.as_ref().to_path_buf();
-
-        // Get file size to determine next offset
-        let next_offset = std::fs::metadata(&path)
-            .ok()
-            .map(|m| m.len())
-            .unwrap_or(0);
-
-        Ok(WalFile {
+        let mut wal = WalFile {
             disk,
             path,
-            next_offset,
-        })
+            next_offset: 0,
+            next_seq: FIRST_SEQ,
+            end: WalEnd::Clean,
+        };
+
+        let mut entries = wal.entries();
+        let mut next_seq = FIRST_SEQ;
+        for entry in &mut entries {
+            next_seq = entry.header.seq + 1;
+        }
+        let next_offset = entries.offset();
+        let end = entries.end().cloned().unwrap_or_default();
+        if let WalEnd::Unreadable(e) = &end {
+            return Err(io::Error::other(format!("WAL is unreadable: {}", e)));
+        }
+
+        wal.next_offset = next_offset;
+        wal.next_seq = next_seq;
+        wal.end = end;
+        Ok(wal)
     }
 
-    /// Append a WAL entry to the log
+    /// Append a WAL entry to the log, assigning it the next sequence number
     pub fn append(&mut self, entry: &WalEntry) -> Result<u64> {
         let header_size = std::mem::size_of::<WalEntryHeader>();
         let total_size = header_size + entry.payload.len();
@@ -133,82 +264,157 @@ impl WalFile {
         // Allocate aligned buffer
         let mut buf = alloc_aligned(total_size);
 
-        // Write header
+        // Write header, with the CRC field zeroed until the CRC is known
+        let mut header = entry.header;
+        header.seq = self.next_seq;
         let header_bytes = unsafe {
             std::slice::from_raw_parts(
-                &entry.header as *const WalEntryHeader as *const u8,
+                &header as *const WalEntryHeader as *const u8,
                 header_size,
             )
         };
         buf[..header_size].copy_from_slice(header_bytes);
+        let crc_offset = std::mem::offset_of!(WalEntryHeader, crc32);
+        buf[crc_offset..crc_offset + 4].fill(0);
 
-        // Write payload
-        buf[header_size..].copy_from_slice(&entry.payload);
+        // Write payload, zeroing the padding after it
+        buf[header_size..total_size].copy_from_slice(&entry.payload);
+        buf[total_size..].fill(0);
 
         // Compute CRC32 (for integrity checking during recovery)
         let crc = compute_crc32(&buf[..total_size]);
 
-        // Update header with CRC (in-memory only)
-        let mut header_with_crc = entry.header;
-        header_with_crc.crc32 = crc;
-        let header_with_crc_bytes = unsafe {
-            std::slice::from_raw_parts(
-                &header_with_crc as *const WalEntryHeader as *const u8,
-                header_size,
-            )
-        };
-        buf[..header_size].copy_from_slice(header_with_crc_bytes);
+        // Fill in the CRC field
+        buf[crc_offset..crc_offset + 4].copy_from_slice(&crc.to_ne_bytes());
 
         // Write to disk at current offset
         self.disk.write_at(self.next_offset, &buf)?;
 
         let entry_offset = self.next_offset;
-        self.next_offset += total_size as u64;
+        self.next_offset += buf.len() as u64;
+        self.next_seq += 1;
+        stats::record_wal_record();
 
         Ok(entry_offset)
     }
 
-    /// Read a WAL entry at given offset
-    pub fn read_at(&self, offset: u64) -> Result<Option<WalEntry>> {
+    /// Flush appended entries to stable storage
+    pub fn sync(&self) -> Result<()> {
+        self.disk.sync()
+    }
+
+    /// Discard every entry, once their effects are persisted elsewhere,
+    /// along with anything unreadable after them
+    pub fn reset(&mut self) -> Result<()> {
+        self.disk.set_len(0)?;
+        self.next_offset = 0;
+        self.next_seq = FIRST_SEQ;
+        self.end = WalEnd::Clean;
+        Ok(())
+    }
+
+    /// Whether the file holds nothing, not even an unreadable tail
+    pub fn is_empty(&self) -> bool {
+        self.next_offset == 0 && self.end.is_clean()
+    }
+
+    /// Read the entry at offset, or find how the log ends there
+    /// `len` is the file's length, which tells a torn last entry apart from
+    /// corruption with more of the log after it
+    fn read_entry(&self, offset: u64, len: u64) -> Result<ReadOutcome> {
+        if offset >= len {
+            return Ok(ReadOutcome::End(WalEnd::Clean));
+        }
         let header_size = std::mem::size_of::<WalEntryHeader>();
-        let mut header_buf = alloc_aligned(header_size);
+        let mut buf = alloc_aligned(header_size);
+
+        // Read the first aligned chunk, which holds the header
+        let read = self.disk.read_at(offset, &mut buf)?;
 
-        // Read header
-        match self.disk.read_at(offset, &mut header_buf) {
-            Ok(_) => {}
-            Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
-            Err(e) => return Err(e),
+        // Zeros where an entry should start: either the rest of the file is
+        // zeros too, or a write in the middle of the log was lost
+        if buf[..read].iter().all(|&byte| byte == 0) {
+            let end = if self.is_zero_from(offset + read as u64, len)? {
+                WalEnd::ZeroFilled
+            } else {
+                WalEnd::Corrupt(format!("zeroed entry at offset {}", offset))
+            };
+            return Ok(ReadOutcome::End(end));
+        }
+        if read < header_size {
+            return Ok(ReadOutcome::End(WalEnd::Torn));
         }
 
-        let header = unsafe { std::ptr::read(header_buf.as_ptr() as *const WalEntryHeader) };
-        header.validate()?;
+        // An entry that fails validation is a torn write if nothing follows it
+        let invalid = |extent: u64, reason: String| {
+            if offset + extent >= len {
+                WalEnd::Torn
+            } else {
+                WalEnd::Corrupt(format!("{} at offset {}", reason, offset))
+            }
+        };
+
+        let header = unsafe { std::ptr::read(buf.as_ptr() as *const WalEntryHeader) };
+        if let Err(e) = header.validate() {
+            return Ok(ReadOutcome::End(invalid(buf.len() as u64, e.to_string())));
+        }
 
-        // Read payload
-        let payload_len = header.payload_len as usize;
-        let mut payload = alloc_aligned(payload_len);
-        self.disk.read_at(offset + header_size as u64, &mut payload)?;
+        // Re-read the whole entry if the payload runs past the first chunk
+        let total_size = header_size + header.payload_len as usize;
+        let padded_size = (total_size.div_ceil(ALIGNMENT) * ALIGNMENT) as u64;
+        if offset + total_size as u64 > len {
+            return Ok(ReadOutcome::End(WalEnd::Torn));
+        }
+        if total_size > buf.len() {
+            buf = alloc_aligned(total_size);
+            if self.disk.read_at(offset, &mut buf)? < total_size {
+                return Ok(ReadOutcome::End(WalEnd::Torn));
+            }
+        }
 
-        // Verify CRC
-        let mut verify_buf = alloc_aligned(header_size + payload_len);
-        verify_buf[..header_size].copy_from_slice(&header_buf);
-        verify_buf[header_size..].copy_from_slice(&payload[..payload_len]);
+        // Verify CRC, which was computed before the CRC field was filled in
+        let crc_offset = std::mem::offset_of!(WalEntryHeader, crc32);
+        buf[crc_offset..crc_offset + 4].fill(0);
 
-        let expected_crc = compute_crc32(&verify_buf[..header_size + payload_len]);
+        let expected_crc = compute_crc32(&buf[..total_size]);
         if header.crc32 != expected_crc {
-            return Err(io::Error::new(
-                io::ErrorKind::InvalidData,
-                format!("WAL entry CRC mismatch at offset {}", offset),
-            ));
+            return Ok(ReadOutcome::End(invalid(padded_size, "CRC mismatch".to_string())));
         }
 
-        Ok(Some(WalEntry { header, payload }))
+        let payload = buf[header_size..total_size].to_vec();
+        Ok(ReadOutcome::Entry(WalEntry { header, payload }, offset + padded_size))
+    }
+
+    /// Whether every byte from offset to len is zero
+    fn is_zero_from(&self, mut offset: u64, len: u64) -> Result<bool> {
+        let mut buf = alloc_aligned(ZERO_CHECK_CHUNK);
+        while offset < len {
+            let read = self.disk.read_at(offset, &mut buf)?;
+            if read == 0 {
+                break;
+            }
+            if buf[..read].iter().any(|&byte| byte != 0) {
+                return Ok(false);
+            }
+            offset += read as u64;
+        }
+        Ok(true)
     }
 
-    /// Iterate through all entries in the log starting from offset
-    pub fn iter_from(&self, start_offset: u64) -> WalIterator<'_> {
+    /// Iterate through the log's entries from its start
+    /// Iteration stops at the first entry that is torn, fails validation or
+    /// is out of sequence; `WalIterator::end` then tells which
+    pub fn entries(&self) -> WalIterator<'_> {
+        let (len, end) = match self.disk.len() {
+            Ok(len) => (len, None),
+            Err(e) => (0, Some(WalEnd::Unreadable(e.to_string()))),
+        };
         WalIterator {
             wal: self,
-            current_offset: start_offset,
+            len,
+            offset: 0,
+            next_seq: FIRST_SEQ,
+            end,
         }
     }
 
@@ -237,41 +443,67 @@ impl WalFile {
 /// Iterator for WAL entries
 pub struct WalIterator<'a> {
     wal: &'a WalFile,
-    current_offset: u64,
+    /// Length of the file when iteration started
+    len: u64,
+    /// Offset of the next entry
+    offset: u64,
+    /// Sequence number the next entry has to carry
+    next_seq: u64,
+    /// How the log ended, once iteration has stopped
+    end: Option<WalEnd>,
+}
+
+impl WalIterator<'_> {
+    /// Offset just past the last entry returned
+    pub fn offset(&self) -> u64 {
+        self.offset
+    }
+
+    /// How the log ended, once iteration has stopped
+    pub fn end(&self) -> Option<&WalEnd> {
+        self.end.as_ref()
+    }
+
+    /// Bytes after the last entry returned, which were ignored
+    pub fn bytes_ignored(&self) -> u64 {
+        self.len.saturating_sub(self.offset)
+    }
 }
 
-impl<'a> Iterator for WalIterator<'a> {
-    type Item = Result<WalEntry>;
+impl Iterator for WalIterator<'_> {
+    type Item = WalEntry;
 
     fn next(&mut self) -> Option<Self::Item> {
-        match self.wal.read_at(self.current_offset) {
-            Ok(Some(entry)) => {
-                let header_size = std::mem::size_of::<WalEntryHeader>();
-                self.current_offset += header_size as u64 + entry.payload.len() as u64;
-                Some(Ok(entry))
+        if self.end.is_some() {
+            return None;
+        }
+        let outcome = self.wal.read_entry(self.offset, self.len)
+            .unwrap_or_else(|e| ReadOutcome::End(WalEnd::Unreadable(e.to_string())));
+        match outcome {
+            // A valid entry out of order is left over from an earlier log
+            ReadOutcome::Entry(entry, _) if entry.header.seq != self.next_seq => {
+                self.end = Some(WalEnd::Corrupt(format!(
+                    "entry at offset {} has sequence number {}, expected {}",
+                    self.offset, entry.header.seq, self.next_seq
+                )));
+                None
+            }
+            ReadOutcome::Entry(entry, next_offset) => {
+                self.offset = next_offset;
+                self.next_seq += 1;
+                Some(entry)
+            }
+            ReadOutcome::End(end) => {
+                self.end = Some(end);
+                None
             }
-            Ok(None) => None,
-            Err(e) => Some(Err(e)),
         }
     }
 }
 
-/// Compute CRC32 checksum
-fn compute_crc32(data: &[u8]) -> u32 {
-    // Simple polynomial-based CRC (not cryptographically secure)
-    // For production, use crc32fast or similar
-    let mut crc = 0xFFFFFFFFu32;
-    for &byte in data {
-        crc ^= byte as u32;
-        for _ in 0..8 {
-            crc = if crc & 1 != 0 {
-                (crc >> 1) ^ 0xEDB88320
-            } else {
-                crc >> 1
-            };
-        }
-    }
-    crc ^ 0xFFFFFFFF
+/// Compute CRC32 checksum (CRC-32/ISO-HDLC, as zlib and gzip use)
+pub(crate) fn compute_crc32(data: &[u8]) -> u32 {
+    crc32fast::hash(data)
 }
 
 #[cfg(test)]
@@ -280,7 +512,6 @@ mod tests {
     use std::fs;
 
     #[test]
-    #[ignore]
     fn test_wal_file_creation() {
         let path = "test_wal.log";
         let _ = fs::remove_file(path);
@@ -292,7 +523,6 @@ mod tests {
     }
 
     #[test]
-    #[ignore]
     fn test_wal_append_and_read() {
         let path = "test_wal_write.log";
         let _ = fs::remove_file(path);
@@ -304,10 +534,7 @@ mod tests {
 
         assert_eq!(offset, 0);
 
-        let read_entry = wal
-            .read_at(offset)
-            .expect("Failed to read entry")
-            .expect("No entry found");
+        let read_entry = wal.entries().next().expect("No entry found");
         assert_eq!(read_entry.header.entry_type, WalEntryType::Insert as u8);
         assert_eq!(read_entry.payload, vec![1, 2, 3, 4, 5]);
 
@@ -315,7 +542,6 @@ mod tests {
     }
 
     #[test]
-    #[ignore]
     fn test_wal_iterator() {
         let path = "test_wal_iter.log";
         let _ = fs::remove_file(path);
@@ -332,15 +558,95 @@ mod tests {
             wal.append(entry).expect("Failed to append");
         }
 
-        let read_entries: Vec<_> = wal
-            .iter_from(0)
-            .map(|e| e.expect("Failed to read entry"))
-            .collect();
+        let read_entries: Vec<_> = wal.entries().collect();
 
         assert_eq!(read_entries.len(), 3);
         assert_eq!(read_entries[0].payload, vec![1]);
         assert_eq!(read_entries[1].payload, vec![2]);
         assert_eq!(read_entries[2].payload, vec![3]);
+        assert_eq!(read_entries[2].header.seq, FIRST_SEQ + 2);
+
+        let _ = fs::remove_file(path);
+    }
+
+    #[test]
+    fn test_wal_reopen_continues_sequence() {
+        let path = "test_wal_reopen.log";
+        let _ = fs::remove_file(path);
+
+        let mut wal = WalFile::open(path).expect("Failed to create WAL file");
+        wal.append(&WalEntry::new(WalEntryType::Insert, vec![1], 0)).expect("Failed to append");
+        drop(wal);
+
+        let mut wal = WalFile::open(path).expect("Failed to reopen WAL file");
+        assert_eq!(wal.end, WalEnd::Clean);
+        wal.append(&WalEntry::new(WalEntryType::Insert, vec![2], 0)).expect("Failed to append");
+        let seqs: Vec<u64> = wal.entries().map(|entry| entry.header.seq).collect();
+        assert_eq!(seqs, vec![FIRST_SEQ, FIRST_SEQ + 1]);
+
+        wal.reset().expect("Failed to reset");
+        assert!(wal.is_empty());
+        wal.append(&WalEntry::new(WalEntryType::Insert, vec![3], 0)).expect("Failed to append");
+        assert_eq!(wal.entries().next().expect("No entry found").header.seq, FIRST_SEQ);
+
+        let _ = fs::remove_file(path);
+    }
+
+    #[test]
+    fn test_wal_end_detection() {
+        use std::os::unix::fs::FileExt;
+
+        let path = "test_wal_end.log";
+        let _ = fs::remove_file(path);
+
+        let mut wal = WalFile::open(path).expect("Failed to create WAL file");
+        for payload in [vec![1], vec![2; 5000], vec![3]] {
+            wal.append(&WalEntry::new(WalEntryType::Insert, payload, 0)).expect("Failed to append");
+        }
+        let len = wal.next_offset();
+        let last = len - ALIGNMENT as u64;
+        drop(wal);
+        let file = fs::OpenOptions::new().write(true).open(path).expect("Failed to open WAL file");
+
+        let end_of = |path| {
+            let wal = WalFile::open(path).expect("Failed to open WAL file");
+            let mut entries = wal.entries();
+            let count = (&mut entries).count();
+            (count, entries.end().cloned().expect("iteration should have ended"), wal.is_empty())
+        };
+        assert_eq!(end_of(path), (3, WalEnd::Clean, false));
+
+        // Space the file system added for a write that never landed
+        file.set_len(len + 3 * ALIGNMENT as u64).expect("Failed to extend");
+        assert_eq!(end_of(path), (3, WalEnd::ZeroFilled, false));
+
+        // The last entry half written
+        file.write_all_at(&[0xab; 16], last + 40).expect("Failed to write");
+        file.set_len(len).expect("Failed to truncate");
+        assert_eq!(end_of(path), (2, WalEnd::Torn, false));
+
+        // The file ending inside an entry's payload
+        file.set_len(ALIGNMENT as u64 + 100).expect("Failed to truncate");
+        assert_eq!(end_of(path), (1, WalEnd::Torn, false));
+
+        // A damaged entry with the log going on after it
+        file.set_len(0).expect("Failed to truncate");
+        let mut wal = WalFile::open(path).expect("Failed to open WAL file");
+        for payload in [vec![1], vec![2], vec![3]] {
+            wal.append(&WalEntry::new(WalEntryType::Insert, payload, 0)).expect("Failed to append");
+        }
+        drop(wal);
+        file.write_all_at(&[0xff], ALIGNMENT as u64 + 56).expect("Failed to write");
+        let (count, end, _) = end_of(path);
+        assert_eq!(count, 1);
+        assert!(matches!(end, WalEnd::Corrupt(ref reason) if reason.contains("CRC")), "unexpected end: {}", end);
+
+        // A valid entry left over from an earlier log
+        let second = fs::read(path).expect("Failed to read WAL file")[..ALIGNMENT].to_vec();
+        file.write_all_at(&second, ALIGNMENT as u64).expect("Failed to write");
+        let (count, end, _) = end_of(path);
+        assert_eq!(count, 1);
+        assert!(matches!(end, WalEnd::Corrupt(ref reason) if reason.contains("sequence")), "unexpected end: {}", end);
 
         let _ = fs::remove_file(path);
     }
@@ -219,6 +219,9 @@ pub struct Column {
     pub name: String,
     pub data_type: DataType,
     pub is_primary_key: bool,
+    /// Type modifier, such as the length of VARCHAR(n) or the dimensions of
+    /// an extension's VECTOR(d); None if the type was declared without one
+    pub typmod: Option<u32>,
 }
 
 /// SQL data types
@@ -253,6 +256,11 @@ impl Schema {
             .position(|c| c.name.eq_ignore_ascii_case(name))
     }
 
+    /// Position of the primary key column, if the table has one
+    pub fn primary_key_index(&self) -> Option<usize> {
+        self.columns.iter().position(|c| c.is_primary_key)
+    }
+
     pub fn len(&self) -> usize {
         self.columns.len()
     }
@@ -0,0 +1,96 @@
+mod common;
+
+use std::io::Write;
+use std::thread;
+use std::time::Duration;
+
+use common::TestDb;
+use serial_test::serial;
+
+#[test]
+#[serial]
+fn test_advisory_locks_within_session() {
+    let db = TestDb::new();
+
+    // A session may take a lock it holds, and keeps it until unlocked as often
+    let result = db.execute_sql(
+        "SELECT pg_try_advisory_lock(1); SELECT pg_try_advisory_lock(1); \
+         SELECT pg_advisory_unlock(1); SELECT pg_advisory_unlock(1); SELECT pg_advisory_unlock(1);",
+    ).expect("advisory lock functions failed");
+    assert_eq!(result.matches(" t\n").count(), 4, "unexpected lock results: {}", result);
+    assert_eq!(result.matches(" f\n").count(), 1, "unlocking a lock not held should return false: {}", result);
+    assert!(result.contains("pg_try_advisory_lock"), "the column should be named after the function: {}", result);
+
+    // A pair of integers is a separate key from a bigint
+    let result = db.execute_sql("SELECT pg_advisory_lock(1, 2); SELECT pg_advisory_unlock(3); SELECT pg_advisory_unlock(1, 2);")
+        .expect("advisory lock functions failed");
+    assert!(result.contains(" f\n") && result.contains(" t\n"), "unexpected pair key results: {}", result);
+
+    let result = db.execute_sql("SELECT pg_advisory_lock(4); SELECT pg_advisory_lock(5); SELECT pg_advisory_unlock_all(); SELECT pg_advisory_unlock(4);")
+        .expect("pg_advisory_unlock_all failed");
+    assert!(result.contains(" f\n"), "pg_advisory_unlock_all should release every lock: {}", result);
+
+    let err = db.execute_sql("SELECT pg_advisory_lock('x');").expect_err("a non-integer key should be rejected");
+    assert!(err.contains("expects a bigint key"), "unexpected error: {}", err);
+    let err = db.execute_sql("SELECT pg_advisory_lock(1, 3000000000);").expect_err("an out of range pair key should be rejected");
+    assert!(err.contains("fit in an integer"), "unexpected error: {}", err);
+}
+
+#[test]
+#[serial]
+fn test_advisory_locks_across_sessions() {
+    let db = TestDb::new();
+
+    let mut holder = db.open_session();
+    let mut stdin = holder.stdin.take().expect("psql stdin");
+    stdin.write_all(b"SELECT pg_advisory_lock(42);\n").expect("write to psql failed");
+    stdin.flush().expect("flush to psql failed");
+    thread::sleep(Duration::from_millis(500));
+
+    let result = db.execute_sql("SELECT pg_try_advisory_lock(42);").expect("pg_try_advisory_lock failed");
+    assert!(result.contains(" f\n"), "a lock held by another session should not be taken: {}", result);
+    let result = db.execute_sql("SELECT pg_advisory_unlock(42);").expect("pg_advisory_unlock failed");
+    assert!(result.contains(" f\n"), "only the holder may unlock a lock: {}", result);
+
+    // Closing the holder's connection releases its locks
+    drop(stdin);
+    holder.wait().expect("psql did not exit");
+    thread::sleep(Duration::from_millis(200));
+    let result = db.execute_sql("SELECT pg_try_advisory_lock(42);").expect("pg_try_advisory_lock failed");
+    assert!(result.contains(" t\n"), "a closed session's locks should be released: {}", result);
+}
+
+#[test]
+#[serial]
+fn test_advisory_lock_deadlock() {
+    let db = TestDb::new();
+    db.execute_sql("CREATE TABLE jobs (id INT PRIMARY KEY);").expect("CREATE TABLE failed");
+
+    // One session's transaction holds the write lock...
+    let mut writer = db.open_session();
+    let mut writer_stdin = writer.stdin.take().expect("psql stdin");
+    writer_stdin.write_all(b"BEGIN;\nINSERT INTO jobs VALUES (1);\n").expect("write to psql failed");
+    writer_stdin.flush().expect("flush to psql failed");
+    thread::sleep(Duration::from_millis(500));
+
+    // ...while another holds an advisory lock and waits to write
+    let mut holder = db.open_session();
+    let mut holder_stdin = holder.stdin.take().expect("psql stdin");
+    holder_stdin.write_all(b"SELECT pg_advisory_lock(9);\nINSERT INTO jobs VALUES (2);\n").expect("write to psql failed");
+    holder_stdin.flush().expect("flush to psql failed");
+    thread::sleep(Duration::from_millis(500));
+
+    // Waiting for that advisory lock would never end, so it fails, rolling
+    // back the block and letting the other session write
+    writer_stdin.write_all(b"SELECT pg_advisory_lock(9);\nROLLBACK;\n").expect("write to psql failed");
+    drop(writer_stdin);
+    let output = writer.wait_with_output().expect("psql did not exit");
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("deadlock detected"), "the wait closing the cycle should fail: {}", stderr);
+
+    drop(holder_stdin);
+    let output = holder.wait_with_output().expect("psql did not exit");
+    assert!(output.status.success(), "the waiting write failed: {}", String::from_utf8_lossy(&output.stderr));
+    let result = db.execute_sql("SELECT id FROM jobs;").expect("SELECT failed");
+    assert!(result.contains("(1 row)") && result.contains(" 2\n"), "only the other session's write should be kept: {}", result);
+}
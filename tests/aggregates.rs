@@ -0,0 +1,134 @@
+mod common;
+
+use common::TestDb;
+use serial_test::serial;
+
+/// Cells of the single result row, trimmed
+fn row_cells(result: &str) -> Vec<String> {
+    result.lines()
+        .nth(2)
+        .unwrap_or_default()
+        .split('|')
+        .map(|cell| cell.trim().to_string())
+        .collect()
+}
+
+#[test]
+#[serial]
+fn test_aggregate_functions() {
+    let db = TestDb::new();
+
+    db.execute_sql("CREATE TABLE readings (id INT, site STRING, level INT, ratio FLOAT, PRIMARY KEY (id));")
+        .expect("CREATE TABLE failed");
+    db.execute_sql("INSERT INTO readings VALUES (1, 'b', 5, 1.5), (2, 'a', NULL, 2.5), (3, 'c', 5, NULL), (4, 'a', 9, 0.5);")
+        .expect("INSERT failed");
+
+    // NULLs are skipped by everything but COUNT(*)
+    let result = db.execute_sql(
+        "SELECT COUNT(*), COUNT(level), SUM(level), MIN(site), MAX(level), SUM(ratio) FROM readings;",
+    ).expect("aggregate SELECT failed");
+    assert_eq!(row_cells(&result), ["4", "3", "19", "a", "9", "4.5"], "wrong aggregates: {}", result);
+
+    let result = db.execute_sql("SELECT AVG(level) FROM readings WHERE id > 2;").expect("AVG failed");
+    assert_eq!(row_cells(&result), ["7.0"], "wrong average: {}", result);
+
+    // Aggregates can be combined in expressions and filtered by WHERE
+    let result = db.execute_sql("SELECT MAX(id) - MIN(id) AS spread, COUNT(*) AS n FROM readings WHERE level = 5;")
+        .expect("aggregate expression failed");
+    assert!(result.contains("spread") && result.contains(" n"), "aliases should name the columns: {}", result);
+    assert_eq!(row_cells(&result), ["2", "2"], "wrong aggregates: {}", result);
+}
+
+#[test]
+#[serial]
+fn test_aggregates_over_no_rows() {
+    let db = TestDb::new();
+
+    db.execute_sql("CREATE TABLE nothing (id INT, amount INT, PRIMARY KEY (id));")
+        .expect("CREATE TABLE failed");
+
+    // An aggregate without GROUP BY always produces one row
+    let result = db.execute_sql("SELECT COUNT(*), SUM(amount), AVG(amount), MAX(amount) FROM nothing;")
+        .expect("aggregate over empty table failed");
+    assert!(result.contains("(1 row)"), "expected a single row: {}", result);
+    assert_eq!(row_cells(&result), ["0", "", "", ""], "empty aggregates should be 0 or NULL: {}", result);
+
+    let result = db.execute_sql("SELECT id, COUNT(*) FROM nothing;");
+    assert!(result.is_err(), "ungrouped column alongside an aggregate should be rejected");
+
+    db.execute_sql("INSERT INTO nothing VALUES (1, 10);").expect("INSERT failed");
+    let result = db.execute_sql("SELECT SUM(id) FROM nothing WHERE amount > 100;").expect("SUM failed");
+    assert_eq!(row_cells(&result), [""], "sum over no rows should be NULL: {}", result);
+}
+
+#[test]
+#[serial]
+fn test_group_by() {
+    let db = TestDb::new();
+
+    db.execute_sql("CREATE TABLE sales (id INT, region STRING, amount INT, PRIMARY KEY (id));")
+        .expect("CREATE TABLE failed");
+    db.execute_sql("INSERT INTO sales VALUES (1, 'east', 10), (2, 'west', 5), (3, 'east', 7), (4, NULL, 1), (5, 'west', NULL), (6, NULL, 2);")
+        .expect("INSERT failed");
+
+    // One row per group, with NULL keys grouped together
+    let result = db.execute_sql("SELECT region, COUNT(*), SUM(amount) FROM sales GROUP BY region ORDER BY region;")
+        .expect("GROUP BY failed");
+    let rows: Vec<Vec<String>> = result.lines()
+        .skip(2)
+        .take_while(|line| !line.starts_with('('))
+        .map(|line| line.split('|').map(|cell| cell.trim().to_string()).collect())
+        .collect();
+    assert_eq!(rows, [["east", "2", "17"], ["west", "2", "5"], ["", "2", "3"]], "wrong groups: {}", result);
+
+    // Groups can be named by position and sorted by aggregates
+    let result = db.execute_sql("SELECT region FROM sales GROUP BY 1 ORDER BY MAX(amount) DESC NULLS LAST;")
+        .expect("GROUP BY position failed");
+    assert_eq!(row_cells(&result), ["east"], "largest group should come first: {}", result);
+    let result = db.execute_sql("SELECT id + 1 FROM sales WHERE id < 3 GROUP BY id ORDER BY 1;")
+        .expect("expression over a group key failed");
+    assert!(result.contains(" 2\n") && result.contains(" 3\n"), "wrong keys: {}", result);
+
+    // No input rows means no groups
+    let result = db.execute_sql("SELECT region, COUNT(*) FROM sales WHERE id > 100 GROUP BY region;")
+        .expect("GROUP BY over no rows failed");
+    assert!(!result.contains("row)"), "expected no groups: {}", result);
+
+    let result = db.execute_sql("SELECT region, amount FROM sales GROUP BY region;");
+    assert!(result.is_err(), "ungrouped column should be rejected");
+    let result = db.execute_sql("SELECT region FROM sales GROUP BY region ORDER BY amount;");
+    assert!(result.is_err(), "ungrouped ORDER BY key should be rejected");
+    let result = db.execute_sql("SELECT COUNT(*) FROM sales GROUP BY COUNT(*);");
+    assert!(result.is_err(), "aggregate in GROUP BY should be rejected");
+}
+
+#[test]
+#[serial]
+fn test_having() {
+    let db = TestDb::new();
+
+    db.execute_sql("CREATE TABLE sales (id INT, region STRING, amount INT, PRIMARY KEY (id));")
+        .expect("CREATE TABLE failed");
+    db.execute_sql("INSERT INTO sales VALUES (1, 'east', 10), (2, 'west', 5), (3, 'east', 7), (4, 'north', 1), (5, 'west', NULL);")
+        .expect("INSERT failed");
+
+    // Aggregates only in HAVING are computed but not returned
+    let result = db.execute_sql("SELECT region, SUM(amount) FROM sales GROUP BY region HAVING COUNT(*) > 1 ORDER BY region;")
+        .expect("HAVING failed");
+    assert!(result.contains("east") && result.contains("west") && !result.contains("north"),
+        "only groups of several rows should be kept: {}", result);
+    assert!(result.contains("(2 rows)"), "expected two groups: {}", result);
+
+    let result = db.execute_sql("SELECT region FROM sales GROUP BY region HAVING MAX(amount) > 6 AND region <> 'west';")
+        .expect("HAVING on a group key failed");
+    assert_eq!(row_cells(&result), ["east"], "wrong group: {}", result);
+
+    // Without GROUP BY the single group is kept or dropped as a whole
+    let result = db.execute_sql("SELECT COUNT(*) FROM sales HAVING SUM(amount) > 10;").expect("HAVING failed");
+    assert_eq!(row_cells(&result), ["5"], "group should be kept: {}", result);
+    let result = db.execute_sql("SELECT COUNT(*) FROM sales HAVING SUM(amount) > 100;").expect("HAVING failed");
+    assert!(!result.contains("row)"), "group should be dropped: {}", result);
+
+    let result = db.execute_sql("SELECT region FROM sales GROUP BY region HAVING amount > 1;");
+    assert!(result.is_err(), "ungrouped column in HAVING should be rejected");
+}
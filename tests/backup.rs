@@ -0,0 +1,127 @@
+mod common;
+
+use common::TestDb;
+use serial_test::serial;
+
+/// Bytes copied, as returned by flint_backup()
+fn backup_bytes(result: &str) -> u64 {
+    result.lines()
+        .nth(2)
+        .and_then(|line| line.trim().parse().ok())
+        .unwrap_or_else(|| panic!("unexpected flint_backup() result: {}", result))
+}
+
+#[test]
+#[serial]
+fn test_incremental_backup_and_restore() {
+    let mut db = TestDb::new();
+    let backup_root = std::env::temp_dir().join(format!("flint-backup-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&backup_root);
+    let full = backup_root.join("full");
+    let incremental = backup_root.join("incremental");
+    db.restart_with_args(&[&format!("--backup-dir={}", backup_root.display())]).expect("restart failed");
+
+    db.execute_sql("CREATE TABLE notes (id INT, body STRING, PRIMARY KEY (id)); CREATE SEQUENCE ids;")
+        .expect("CREATE failed");
+    // Enough rows to span many blocks, so a few changes touch only some of them
+    let body = "x".repeat(500);
+    for batch in 0..10 {
+        let values: Vec<String> = (1..=200).map(|id| format!("({}, '{}')", batch * 200 + id, body)).collect();
+        db.execute_sql(&format!("INSERT INTO notes VALUES {};", values.join(", "))).expect("INSERT failed");
+    }
+    db.execute_sql("SELECT nextval('ids');").expect("nextval failed");
+
+    let result = db.execute_sql(&format!("SELECT flint_backup('{}');", full.display()))
+        .expect("full backup failed");
+    let full_bytes = backup_bytes(&result);
+
+    // Changes after the full backup: only these should be copied next time
+    db.execute_sql("UPDATE notes SET body = 'changed' WHERE id = 7;").expect("UPDATE failed");
+    db.execute_sql("DELETE FROM notes WHERE id > 1990;").expect("DELETE failed");
+    db.execute_sql("CREATE TABLE tags (id INT, name STRING, PRIMARY KEY (id)); INSERT INTO tags VALUES (1, 'red');")
+        .expect("CREATE failed");
+    db.execute_sql("SELECT nextval('ids');").expect("nextval failed");
+
+    // Relative locations are inside the backup directory
+    let result = db.execute_sql("SELECT flint_backup('incremental', 'full');")
+        .expect("incremental backup failed");
+    let incremental_bytes = backup_bytes(&result);
+    assert!(incremental_bytes < full_bytes,
+        "incremental backup should copy less than the full one: {} vs {}", incremental_bytes, full_bytes);
+
+    let result = db.execute_sql(&format!("SELECT flint_backup('{}');", full.display()));
+    assert!(result.is_err(), "backing up over an existing backup should fail");
+
+    // Lose the database, then restore the chain
+    db.execute_sql("INSERT INTO notes VALUES (5000, 'after backup');").expect("INSERT failed");
+    db.stop_and_clear().expect("failed to clear database");
+    let restore_arg = format!("--restore-from={}", incremental.display());
+    db.restart_with_args(&[&restore_arg]).expect("restore failed");
+
+    let result = db.execute_sql("SELECT COUNT(*) FROM notes;").expect("SELECT failed");
+    assert!(result.contains(" 1990"), "rows as of the incremental backup should be restored: {}", result);
+    let result = db.execute_sql("SELECT body FROM notes WHERE id = 7;").expect("SELECT failed");
+    assert!(result.contains("changed"), "updated row should be restored: {}", result);
+    let result = db.execute_sql("SELECT name FROM tags;").expect("SELECT failed");
+    assert!(result.contains("red"), "table created after the full backup should be restored: {}", result);
+    let result = db.execute_sql("SELECT nextval('ids');").expect("nextval failed");
+    assert!(result.contains(" 3"), "sequence should continue where it was: {}", result);
+
+    let _ = std::fs::remove_dir_all(&backup_root);
+}
+
+#[test]
+#[serial]
+fn test_verify_backup() {
+    let mut db = TestDb::new();
+    let backup_root = std::env::temp_dir().join(format!("flint-verify-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&backup_root);
+    let backup_dir = backup_root.join("verified");
+
+    let err = db.execute_sql(&format!("SELECT flint_backup('{}');", backup_dir.display()))
+        .expect_err("backups to a directory should need --backup-dir");
+    assert!(err.contains("--backup-dir"), "unexpected error: {}", err);
+    db.restart_with_args(&[&format!("--backup-dir={}", backup_root.display())]).expect("restart failed");
+
+    db.execute_sql("CREATE TABLE notes (id INT, body STRING, PRIMARY KEY (id)); INSERT INTO notes VALUES (1, 'kept');")
+        .expect("CREATE failed");
+    db.execute_sql(&format!("SELECT flint_backup('{}');", backup_dir.display())).expect("backup failed");
+
+    let manifest = std::fs::read_to_string(backup_dir.join("backup.manifest")).expect("manifest missing");
+    assert!(manifest.lines().any(|line| line.starts_with("file ") && line.ends_with(".tbl")),
+        "manifest should list the table file: {}", manifest);
+
+    let result = db.execute_sql(&format!("SELECT flint_verify_backup('{}');", backup_dir.display()))
+        .expect("verification of an intact backup failed");
+    assert!(result.contains(" t"), "intact backup should verify: {}", result);
+
+    // Damage the table's copy
+    let table_copy = std::fs::read_dir(&backup_dir).expect("backup directory missing")
+        .flatten()
+        .map(|entry| entry.path())
+        .find(|path| path.extension().is_some_and(|ext| ext == "tbl"))
+        .expect("no table file in backup");
+    let mut data = std::fs::read(&table_copy).expect("failed to read table copy");
+    data.truncate(data.len() / 2);
+    std::fs::write(&table_copy, data).expect("failed to damage table copy");
+
+    let err = db.execute_sql(&format!("SELECT flint_verify_backup('{}');", backup_dir.display()))
+        .expect_err("damaged backup should fail verification");
+    assert!(err.contains("corrupt") && err.contains(".tbl"), "error should name the damaged file: {}", err);
+
+    let result = db.execute_sql(&format!("SELECT flint_verify_backup('{}');", backup_root.join("missing").display()));
+    assert!(result.is_err(), "a missing backup should fail verification");
+
+    // Nothing outside the backup directory can be written or read
+    let err = db.execute_sql("SELECT flint_backup('/tmp/flint-elsewhere');").expect_err("absolute path outside should fail");
+    assert!(err.contains("outside the backup directory"), "unexpected error: {}", err);
+    let err = db.execute_sql("SELECT flint_backup('verified/../../escaped');").expect_err(".. should fail");
+    assert!(err.contains("must not contain"), "unexpected error: {}", err);
+    let err = db.execute_sql("SELECT flint_verify_backup('/etc');").expect_err("verifying outside should fail");
+    assert!(err.contains("outside the backup directory"), "unexpected error: {}", err);
+    assert!(!std::path::Path::new("/tmp/flint-elsewhere").exists(), "nothing should be written outside");
+    let err = db.execute_sql("SELECT flint_backup('ftp://host/backups');").expect_err("ftp is not an archive target");
+    assert!(err.contains("Unsupported archive location"), "unexpected error: {}", err);
+
+    let _ = std::fs::remove_dir_all(&backup_root);
+}
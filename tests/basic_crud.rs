@@ -41,6 +41,66 @@ fn test_primary_key_constraint() {
         result.is_err() || result.unwrap().contains("ERROR"),
         "duplicate PK should fail"
     );
+
+    // Keys are checked as rows are written, so deferring the check is refused
+    let err = db.execute_sql("CREATE TABLE test_deferred (id INT, PRIMARY KEY (id) DEFERRABLE INITIALLY DEFERRED);")
+        .expect_err("DEFERRABLE should be refused");
+    assert!(err.contains("DEFERRABLE constraints are not supported"), "unexpected error: {}", err);
+}
+
+#[test]
+#[serial]
+fn test_string_primary_key() {
+    let db = TestDb::new();
+
+    db.execute_sql("CREATE TABLE accounts (email STRING, balance INT, PRIMARY KEY (email));")
+        .expect("CREATE TABLE failed");
+
+    db.execute_sql("INSERT INTO accounts VALUES ('bob@example.com', 10), ('alice@example.com', 20);")
+        .expect("INSERT failed");
+
+    let result = db.execute_sql("INSERT INTO accounts VALUES ('bob@example.com', 30);");
+    assert!(
+        result.is_err() || result.unwrap().contains("ERROR"),
+        "duplicate string PK should fail"
+    );
+
+    let output = db.execute_sql("SELECT * FROM accounts;").expect("SELECT failed");
+    assert!(output.contains("alice@example.com"), "alice not found in output");
+    assert!(!output.contains("30"), "rejected row was stored");
+}
+
+#[test]
+#[serial]
+fn test_string_primary_keys_sharing_a_prefix() {
+    let db = TestDb::new();
+
+    db.execute_sql("CREATE TABLE orders (code STRING, qty INT, PRIMARY KEY (code));")
+        .expect("CREATE TABLE failed");
+    db.execute_sql("INSERT INTO orders VALUES ('order-00001', 1);").expect("INSERT failed");
+    db.execute_sql("INSERT INTO orders VALUES ('order-00002', 2), ('order-00003', 3);")
+        .expect("keys sharing their first 8 bytes should be distinct");
+
+    let err = db.execute_sql("INSERT INTO orders VALUES ('order-00002', 20);").expect_err("duplicate should fail");
+    assert!(err.contains("duplicate key value violates unique constraint \"orders_pkey\""), "unexpected error: {}", err);
+    let err = db.execute_sql("INSERT INTO orders VALUES ('order-00004', 4), ('order-00004', 40);")
+        .expect_err("duplicate within one INSERT should fail");
+    assert!(err.contains("duplicate key value"), "unexpected error: {}", err);
+
+    let result = db.execute_sql("SELECT qty FROM orders WHERE code = 'order-00002';").expect("SELECT failed");
+    assert!(result.contains("  2\n") && result.contains("(1 row)"), "lookup should find its own row: {}", result);
+
+    db.execute_sql("UPDATE orders SET code = 'order-00009' WHERE code = 'order-00001';").expect("UPDATE failed");
+    let err = db.execute_sql("UPDATE orders SET code = 'order-00003' WHERE code = 'order-00002';")
+        .expect_err("updating onto a taken key should fail");
+    assert!(err.contains("duplicate key value"), "unexpected error: {}", err);
+    db.execute_sql("DELETE FROM orders WHERE code = 'order-00003';").expect("DELETE failed");
+    db.execute_sql("INSERT INTO orders VALUES ('order-00001', 10) ON CONFLICT (code) DO UPDATE SET qty = 11;")
+        .expect("upsert failed");
+
+    let result = db.execute_sql("SELECT code, qty FROM orders ORDER BY code;").expect("SELECT failed");
+    assert!(result.contains("order-00001 |  10") && result.contains("order-00002 |   2") && result.contains("order-00009 |   1")
+        && result.contains("(3 rows)"), "unexpected rows: {}", result);
 }
 
 #[test]
@@ -117,4 +177,159 @@ fn test_table_already_exists() {
         result.is_err() || result.unwrap().contains("ERROR"),
         "duplicate CREATE TABLE should fail"
     );
-}
\ No newline at end of file
+}
+
+#[test]
+#[serial]
+fn test_create_table_if_not_exists() {
+    let db = TestDb::new();
+
+    db.execute_sql("CREATE TABLE migrated (id INT, PRIMARY KEY (id));").expect("CREATE TABLE failed");
+    db.execute_sql("INSERT INTO migrated VALUES (1);").expect("INSERT failed");
+
+    // An existing table is left as it is, rows and all
+    db.execute_sql("CREATE TABLE IF NOT EXISTS migrated (id INT, name STRING, PRIMARY KEY (id));")
+        .expect("CREATE TABLE IF NOT EXISTS on an existing table failed");
+    let result = db.execute_sql("SELECT * FROM migrated;").expect("SELECT failed");
+    assert!(result.contains("(1 row)") && !result.contains("name"), "existing table should be untouched: {}", result);
+
+    // Scripts racing to create the same table all succeed, and it is created once
+    let threads: Vec<_> = (0..4)
+        .map(|_| std::thread::spawn(|| {
+            std::process::Command::new("psql")
+                .args(["-h", "127.0.0.1", "-U", "postgres", "-d", "postgres", "-c",
+                    "CREATE TABLE IF NOT EXISTS raced (id INT, PRIMARY KEY (id));"])
+                .output()
+                .expect("failed to run psql")
+        }))
+        .collect();
+    for thread in threads {
+        let output = thread.join().unwrap();
+        assert!(output.status.success(), "concurrent CREATE TABLE IF NOT EXISTS failed: {}", String::from_utf8_lossy(&output.stderr));
+    }
+    db.execute_sql("INSERT INTO raced VALUES (1);").expect("INSERT into the raced table failed");
+}
+#[test]
+#[serial]
+fn test_select_column_names() {
+    let db = TestDb::new();
+
+    db.execute_sql("CREATE TABLE users (id INT, name STRING, PRIMARY KEY (id));")
+        .expect("CREATE TABLE failed");
+    db.execute_sql("INSERT INTO users VALUES (1, 'ann'), (2, 'bob');")
+        .expect("INSERT failed");
+
+    let result = db
+        .execute_sql("SELECT id AS user_id, name FROM users WHERE id = 2;")
+        .expect("SELECT with alias failed");
+    let header = result.lines().next().unwrap_or_default();
+    assert!(header.contains("user_id") && header.contains("name"), "alias not in header: {}", result);
+    assert!(result.contains("bob"), "row not found: {}", result);
+
+    // Unaliased expressions are named as Postgres names them
+    let result = db
+        .execute_sql("SELECT id + 1, flint_row_count('users') FROM users WHERE id = 1;")
+        .expect("SELECT expressions failed");
+    let header = result.lines().next().unwrap_or_default();
+    assert!(header.contains("?column?") && header.contains("flint_row_count"), "wrong header: {}", result);
+}
+
+#[test]
+#[serial]
+fn test_select_expression_types() {
+    let db = TestDb::new();
+
+    db.execute_sql("CREATE TABLE prices (id INT, price FLOAT, name STRING, PRIMARY KEY (id));")
+        .expect("CREATE TABLE failed");
+    db.execute_sql("INSERT INTO prices VALUES (1, 2.0, 'ab');").expect("INSERT failed");
+
+    // psql right-aligns numeric columns and left-aligns the rest, so the
+    // layout under a wide header shows the type each column was reported as
+    let result = db
+        .execute_sql("SELECT price * 1.1 AS float_column, id > 0 AS bool_column, name AS text_column FROM prices;")
+        .expect("SELECT expressions failed");
+    let row = result.lines().nth(2).unwrap_or_default();
+    let cells: Vec<&str> = row.split('|').collect();
+    assert_eq!(cells.len(), 3, "unexpected row: {}", result);
+    assert!(cells[0].trim_start() != cells[0] && cells[0].ends_with(' ') && cells[0].trim().starts_with("2.2"),
+        "float expression should be numeric: {}", result);
+    assert!(cells[1].starts_with(" t "), "comparison should be boolean: {}", result);
+    assert!(cells[2].starts_with(" ab"), "string column should be text: {}", result);
+}
+
+#[test]
+#[serial]
+fn test_multi_statement_error_position() {
+    let db = TestDb::new();
+
+    let result = db.execute_sql(
+        "CREATE TABLE script (id INT, PRIMARY KEY (id));\n\
+         INSERT INTO script VALUES (1);\n\
+         INSERT INTO script VALUES (1);\n\
+         INSERT INTO script VALUES (2);",
+    );
+    let err = result.expect_err("duplicate key should fail the script");
+    assert!(err.contains("LINE 3"), "error should point at the failing statement: {}", err);
+
+    // Statements before the failure keep their effects, those after it don't run
+    let result = db.execute_sql("SELECT * FROM script;").expect("SELECT failed");
+    assert!(result.contains("(1 row)") && result.contains(" 1"), "wrong rows: {}", result);
+}
+
+#[test]
+#[serial]
+fn test_limit_and_offset() {
+    let db = TestDb::new();
+
+    db.execute_sql("CREATE TABLE seq (id INT, PRIMARY KEY (id));").expect("CREATE TABLE failed");
+    db.execute_sql("INSERT INTO seq VALUES (1), (2), (3), (4), (5), (6), (7), (8), (9), (10), (11), (12);")
+        .expect("INSERT failed");
+
+    let result = db.execute_sql("SELECT * FROM seq OFFSET 10;").expect("OFFSET alone failed");
+    assert!(result.contains("(2 rows)") && result.contains(" 11") && result.contains(" 12"), "wrong rows: {}", result);
+
+    let result = db.execute_sql("SELECT * FROM seq LIMIT 5 * 2 OFFSET 1 + 1;").expect("expression LIMIT failed");
+    assert!(result.contains("(10 rows)") && !result.contains("  2\n"), "wrong rows: {}", result);
+
+    let result = db.execute_sql("SELECT * FROM seq LIMIT ALL OFFSET 11;").expect("LIMIT ALL failed");
+    assert!(result.contains("(1 row)"), "wrong rows: {}", result);
+    let result = db.execute_sql("SELECT * FROM seq LIMIT NULL;").expect("LIMIT NULL failed");
+    assert!(result.contains("(12 rows)"), "NULL limit should keep every row: {}", result);
+
+    let result = db.execute_sql("SELECT * FROM seq LIMIT 'many';");
+    assert!(result.is_err(), "non-integer LIMIT should be rejected");
+}
+
+#[test]
+#[serial]
+fn test_insert_column_list() {
+    let db = TestDb::new();
+
+    db.execute_sql("CREATE TABLE people (id INT, name STRING, age INT, PRIMARY KEY (id));").expect("CREATE TABLE failed");
+
+    // Values go to the named columns in the order named; the rest are NULL
+    db.execute_sql("INSERT INTO people (age, id) VALUES (30, 1), (40, 2);").expect("INSERT with columns failed");
+    db.execute_sql("INSERT INTO people (id, name, age) VALUES (3, DEFAULT, 50);").expect("INSERT with DEFAULT failed");
+    db.execute_sql("INSERT INTO people VALUES (4, 'dave');").expect("INSERT of leading columns failed");
+
+    let result = db.execute_sql("SELECT id FROM people WHERE name IS NULL AND age = 30;").expect("SELECT failed");
+    assert!(result.contains("(1 row)") && result.contains(" 1"), "wrong rows: {}", result);
+    let result = db.execute_sql("SELECT count(*) FROM people WHERE name IS NULL;").expect("SELECT failed");
+    assert!(result.contains(" 3\n"), "unnamed and DEFAULT columns should be NULL: {}", result);
+    let result = db.execute_sql("SELECT name FROM people WHERE id = 4 AND age IS NULL;").expect("SELECT failed");
+    assert!(result.contains("dave"), "wrong rows: {}", result);
+
+    for (sql, message) in [
+        ("INSERT INTO people (id, nope) VALUES (5, 1);", "column \"nope\" of relation \"people\" does not exist"),
+        ("INSERT INTO people (id, id) VALUES (5, 5);", "specified more than once"),
+        ("INSERT INTO people (id) VALUES (5, 'x');", "more expressions than target columns"),
+        ("INSERT INTO people (id, name) VALUES (5);", "more target columns than expressions"),
+        ("INSERT INTO people VALUES (5, 'x', 1, 2);", "more expressions than target columns"),
+    ] {
+        let err = db.execute_sql(sql).expect_err("INSERT should be refused");
+        assert!(err.contains(message), "{}: unexpected error: {}", sql, err);
+    }
+
+    // The primary key cannot be left out
+    assert!(db.execute_sql("INSERT INTO people (name) VALUES ('eve');").is_err(), "a NULL primary key should be refused");
+}
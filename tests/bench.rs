@@ -0,0 +1,73 @@
+mod common;
+
+use std::fs;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use common::TestDb;
+use serial_test::serial;
+
+#[test]
+#[serial]
+fn test_bench_mixes() {
+    let mut db = TestDb::new();
+    db.execute_sql("CREATE TABLE keep (id INT, PRIMARY KEY (id));").expect("CREATE TABLE failed");
+    db.stop();
+
+    let output = db.run_flint(&["bench", "tpcb", "50"]).expect("bench tpcb failed");
+    assert!(output.contains("tpcb: 50 transactions, 0 failed"), "unexpected report: {}", output);
+    assert!(output.contains("throughput: ") && output.contains(" tps"), "missing throughput: {}", output);
+    assert!(output.contains("latency ms: p50 ") && output.contains(" p99 "), "missing percentiles: {}", output);
+    let output = db.run_flint(&["bench", "select-only", "20"]).expect("bench select-only failed");
+    assert!(output.contains("select-only: 20 transactions, 0 failed"), "unexpected report: {}", output);
+
+    for args in [&["bench", "tpcc"][..], &["bench", "tpcb", "0"], &["bench"]] {
+        let err = db.run_flint(args).expect_err(&format!("{:?} should fail", args));
+        assert!(err.contains("usage: flint bench") || err.contains("Invalid transaction count"), "unexpected error: {}", err);
+    }
+
+    // The mixes run in a scratch directory and leave the database alone
+    assert!(!db.data_dir().join("bench").exists(), "the scratch directory should be removed");
+    db.restart().expect("restart failed");
+    let result = db.execute_sql("SELECT table_name FROM information_schema.tables;").expect("SELECT tables failed");
+    assert!(result.contains("keep") && !result.contains("bench_"), "unexpected tables: {}", result);
+}
+
+#[test]
+#[serial]
+fn test_bench_replay() {
+    let mut db = TestDb::new();
+
+    // Capture a workload in the server's JSON log
+    let nanos = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_nanos();
+    let log_dir = std::env::temp_dir().join(format!("flint-bench-test-{}", nanos));
+    let log_dir_arg = format!("--log-directory={}", log_dir.display());
+    db.restart_with_args(&[&log_dir_arg, "--log-format=json", "--log-rotation=never"])
+        .expect("restart with a log directory failed");
+    db.execute_sql("CREATE TABLE orders (id INT, qty INT, PRIMARY KEY (id));").expect("CREATE TABLE failed");
+    for id in 1..=5 {
+        db.execute_sql(&format!("INSERT INTO orders VALUES ({}, {});", id, id * 10)).expect("INSERT failed");
+    }
+    db.execute_sql("UPDATE orders SET qty = 0 WHERE id = 3;").expect("UPDATE failed");
+    db.execute_sql("SELECT * FROM orders;").expect("SELECT failed");
+    let log = log_dir.join("flint.log");
+
+    // Replayed against an empty directory, the log rebuilds the same data;
+    // it also holds the startup check's SELECT 1
+    db.stop_and_clear().expect("failed to clear data directory");
+    let output = db.run_flint(&["bench", "replay", log.to_str().unwrap()]).expect("bench replay failed");
+    assert!(output.contains("9 statements, 0 failed"), "unexpected report: {}", output);
+    assert!(output.contains(" qps"), "missing throughput: {}", output);
+    db.restart_with_args(&[]).expect("restart failed");
+    let result = db.execute_sql("SELECT sum(qty) FROM orders;").expect("SELECT sum failed");
+    assert!(result.contains("120"), "replay should have rebuilt the table: {}", result);
+
+    // A plain SQL file replays statement by statement; failures are counted
+    let script = db.data_dir().join("workload.sql");
+    fs::write(&script, "-- a script\nINSERT INTO orders VALUES (6, 1);\nINSERT INTO orders VALUES (6, 1);\nSELECT ';' FROM orders;\n")
+        .expect("failed to write script");
+    db.stop();
+    let output = db.run_flint(&["bench", "replay", script.to_str().unwrap()]).expect("bench replay of a script failed");
+    assert!(output.contains("3 statements, 1 failed"), "unexpected report: {}", output);
+
+    let _ = fs::remove_dir_all(&log_dir);
+}
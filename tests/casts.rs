@@ -0,0 +1,63 @@
+mod common;
+
+use common::TestDb;
+use serial_test::serial;
+
+#[test]
+#[serial]
+fn test_casts() {
+    let db = TestDb::new();
+
+    let result = db.execute_sql("SELECT '123'::int + 1, 1.5::float8, 2.5::int, 3.5::int, CAST(' 42 ' AS bigint);")
+        .expect("numeric casts failed");
+    assert!(result.contains("124 |    1.5 |    2 |    4 |   42"), "unexpected numeric casts: {}", result);
+    assert!(result.contains("?column? | float8 | int4 | int4 | int8"), "casts should be named after their type: {}", result);
+
+    let result = db.execute_sql("SELECT 'yes'::bool, 'off'::boolean, 0::bool, true::int, 12345::varchar(3), 'ab'::char(4) = 'ab  ';")
+        .expect("boolean and text casts failed");
+    assert!(result.contains(" t    | f    | f    |    1 | 123     | t"), "unexpected boolean and text casts: {}", result);
+
+    let result = db.execute_sql("SELECT TRY_CAST('nope' AS int) IS NULL, NULL::int IS NULL;").expect("TRY_CAST failed");
+    assert!(result.contains(" t        | t\n"), "TRY_CAST should give NULL where a cast fails: {}", result);
+
+    let err = db.execute_sql("SELECT 'abc'::int;").expect_err("casting text that is not a number should fail");
+    assert!(err.contains("invalid input syntax for type integer: \"abc\""), "unexpected error: {}", err);
+    let err = db.execute_sql("SELECT 40000::smallint;").expect_err("an out of range cast should fail");
+    assert!(err.contains("smallint out of range"), "unexpected error: {}", err);
+    let err = db.execute_sql("SELECT 1::json;").expect_err("casts to unknown types should fail");
+    assert!(err.contains("cannot cast to type"), "unexpected error: {}", err);
+}
+
+#[test]
+#[serial]
+fn test_typed_literals() {
+    let db = TestDb::new();
+
+    let result = db.execute_sql("SELECT DATE '2024-02-29', TIMESTAMP '2024-01-01T10:00:00.500', '2024-01-01 10:00'::timestamptz;")
+        .expect("typed literals failed");
+    assert!(result.contains("2024-02-29 | 2024-01-01 10:00:00.5 | 2024-01-01 10:00:00+00"), "unexpected typed literals: {}", result);
+    assert!(result.contains(" date "), "a typed literal should be named after its type: {}", result);
+
+    let err = db.execute_sql("SELECT DATE '2023-02-29';").expect_err("a day that does not exist should be rejected");
+    assert!(err.contains("invalid input syntax for type date"), "unexpected error: {}", err);
+    let err = db.execute_sql("SELECT '2024-01-01 25:00'::timestamp;").expect_err("an invalid time should be rejected");
+    assert!(err.contains("invalid input syntax for type timestamp"), "unexpected error: {}", err);
+}
+
+#[test]
+#[serial]
+fn test_casts_in_statements() {
+    let db = TestDb::new();
+
+    db.execute_sql("CREATE TABLE events (id INT, day STRING, score FLOAT, PRIMARY KEY (id));").expect("CREATE TABLE failed");
+    db.execute_sql("INSERT INTO events VALUES ('1'::int, DATE '2024-03-01', '2.5'::float), (2, '2024-03-02', 7);")
+        .expect("INSERT with casts failed");
+
+    // A cast on the primary key is still a point lookup
+    let result = db.execute_sql("SELECT id::text, score::int FROM events WHERE id = '1'::int;").expect("SELECT failed");
+    assert!(result.contains("(1 row)") && result.contains(" 1  |     2"), "unexpected result: {}", result);
+    assert!(result.contains(" id | score"), "a cast column should keep the column's name: {}", result);
+
+    let result = db.execute_sql("SELECT id FROM events WHERE day = DATE '2024-03-02';").expect("SELECT failed");
+    assert!(result.contains("(1 row)") && result.contains(" 2\n"), "unexpected result: {}", result);
+}
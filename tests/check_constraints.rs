@@ -0,0 +1,62 @@
+mod common;
+
+use common::TestDb;
+use serial_test::serial;
+
+#[test]
+#[serial]
+fn test_check_constraints() {
+    let mut db = TestDb::new();
+
+    db.execute_sql(
+        "CREATE TABLE items (id INT, price INT CHECK (price > 0), discount INT, \
+         CONSTRAINT discount_below_price CHECK (discount < price), PRIMARY KEY (id));",
+    ).expect("CREATE TABLE failed");
+
+    db.execute_sql("INSERT INTO items VALUES (1, 10, 2);").expect("INSERT failed");
+    // A constraint that evaluates to NULL passes
+    db.execute_sql("INSERT INTO items VALUES (2, 5, NULL);").expect("INSERT with NULL failed");
+
+    let err = db.execute_sql("INSERT INTO items VALUES (3, 0, NULL);").expect_err("INSERT should violate the column check");
+    assert!(err.contains("violates check constraint \"items_price_check\""), "unexpected error: {}", err);
+    let err = db.execute_sql("INSERT INTO items VALUES (4, 10, 20);").expect_err("INSERT should violate the table check");
+    assert!(err.contains("violates check constraint \"discount_below_price\""), "unexpected error: {}", err);
+
+    // A violating row rejects the whole statement
+    let result = db.execute_sql("INSERT INTO items VALUES (5, 1, NULL), (6, -1, NULL);");
+    assert!(result.is_err(), "INSERT should be rejected: {:?}", result);
+    let err = db.execute_sql("UPDATE items SET discount = 20 WHERE id = 1;").expect_err("UPDATE should violate the table check");
+    assert!(err.contains("new row for relation \"items\" violates check constraint \"discount_below_price\""), "unexpected error: {}", err);
+    let result = db.execute_sql("SELECT id, discount FROM items ORDER BY id;").expect("SELECT failed");
+    assert!(result.contains("(2 rows)") && result.contains("  1 |        2"), "rejected writes should leave no trace: {}", result);
+
+    // Constraints are part of the catalog and follow a renamed column
+    db.restart().expect("restart failed");
+    db.execute_sql("ALTER TABLE items RENAME COLUMN price TO list_price;").expect("RENAME COLUMN failed");
+    let err = db.execute_sql("UPDATE items SET list_price = -5 WHERE id = 2;").expect_err("check lost on restart or rename");
+    assert!(err.contains("items_price_check"), "unexpected error: {}", err);
+    db.execute_sql("UPDATE items SET list_price = 50 WHERE id = 2;").expect("UPDATE failed");
+}
+
+#[test]
+#[serial]
+fn test_check_constraint_definition_errors() {
+    let db = TestDb::new();
+
+    let err = db.execute_sql("CREATE TABLE bad (id INT, CHECK (missing > 0), PRIMARY KEY (id));")
+        .expect_err("a check on an unknown column should be refused");
+    assert!(err.contains("column \"missing\" of relation \"bad\" does not exist"), "unexpected error: {}", err);
+
+    let err = db.execute_sql(
+        "CREATE TABLE bad (id INT, CONSTRAINT c CHECK (id > 0), CONSTRAINT c CHECK (id < 9), PRIMARY KEY (id));",
+    ).expect_err("duplicate constraint names should be refused");
+    assert!(err.contains("constraint \"c\" for relation \"bad\" already exists"), "unexpected error: {}", err);
+
+    // Unnamed constraints over several columns, or the same column twice, get distinct names
+    db.execute_sql("CREATE TABLE ranges (id INT, lo INT, hi INT, CHECK (lo <= hi), CHECK (id > 0), CHECK (id < 100), PRIMARY KEY (id));")
+        .expect("CREATE TABLE failed");
+    let err = db.execute_sql("INSERT INTO ranges VALUES (1, 5, 2);").expect_err("INSERT should violate lo <= hi");
+    assert!(err.contains("\"ranges_check\""), "unexpected error: {}", err);
+    let err = db.execute_sql("INSERT INTO ranges VALUES (200, 1, 2);").expect_err("INSERT should violate id < 100");
+    assert!(err.contains("\"ranges_id_check1\""), "unexpected error: {}", err);
+}
@@ -0,0 +1,35 @@
+mod common;
+
+use common::TestDb;
+use serial_test::serial;
+
+#[test]
+#[serial]
+fn test_command_line() {
+    let mut db = TestDb::new();
+    db.execute_sql("CREATE TABLE items (id INT PRIMARY KEY);").expect("CREATE TABLE failed");
+    db.stop();
+
+    let output = db.run_flint(&["--version"]).expect("--version failed");
+    assert!(output.starts_with("flint "), "unexpected version: {}", output);
+    let output = db.run_flint(&["--help"]).expect("--help failed");
+    assert!(output.contains("--data-dir") && output.contains("snapshot"), "unexpected help: {}", output);
+
+    // Invalid and incomplete flags are refused before anything starts
+    for args in [&["--work-mem=lots"][..], &["--port=http"], &["--synchronous-commit=maybe"], &["--tls-cert=cert.pem"]] {
+        let err = db.run_flint(args).expect_err(&format!("{:?} should fail", args));
+        assert!(err.contains("error:"), "unexpected error for {:?}: {}", args, err);
+    }
+    let err = db.run_flint(&["--no-such-flag"]).expect_err("unknown flag should fail");
+    assert!(err.contains("--no-such-flag"), "unexpected error: {}", err);
+
+    // Flags may follow the command
+    let snapshot = db.data_dir().join("items.snap");
+    let output = db.run_flint(&["snapshot", "create", snapshot.to_str().unwrap(), "--log-level=warn"])
+        .expect("snapshot with a trailing flag failed");
+    assert!(output.contains("created snapshot"), "unexpected output: {}", output);
+
+    db.restart_with_args(&["--log-level=debug", "--synchronous-commit=off", "--extensions=point-ext"])
+        .expect("restart with flags failed");
+    db.execute_sql("SELECT * FROM items;").expect("SELECT failed");
+}
@@ -0,0 +1,67 @@
+mod common;
+
+use std::time::{Duration, Instant};
+
+use common::TestDb;
+use serial_test::serial;
+
+/// The timestamps in psql's output, in order
+fn timestamps(output: &str) -> Vec<String> {
+    output.split(['|', '\n'])
+        .map(str::trim)
+        .filter(|field| field.ends_with("+00") && field.len() >= 22)
+        .map(str::to_string)
+        .collect()
+}
+
+#[test]
+#[serial]
+fn test_pg_sleep() {
+    let db = TestDb::new();
+
+    let started = Instant::now();
+    let result = db.execute_sql("SELECT pg_sleep(0.5);").expect("pg_sleep failed");
+    assert!(started.elapsed() >= Duration::from_millis(500), "pg_sleep should wait: {:?}", started.elapsed());
+    assert!(result.contains("pg_sleep") && result.contains("(1 row)"), "unexpected pg_sleep result: {}", result);
+
+    // Sleeps once per row, and may be used in a filter
+    db.execute_sql("CREATE TABLE jobs (id INT, PRIMARY KEY (id));").expect("CREATE TABLE failed");
+    db.execute_sql("INSERT INTO jobs VALUES (1), (2), (3);").expect("INSERT failed");
+    let started = Instant::now();
+    let result = db.execute_sql("SELECT id FROM jobs WHERE pg_sleep(0.2) IS NULL;").expect("pg_sleep in WHERE failed");
+    assert!(started.elapsed() >= Duration::from_millis(600), "pg_sleep should run per row: {:?}", started.elapsed());
+    assert!(result.contains("(3 rows)"), "unexpected filter result: {}", result);
+
+    let err = db.execute_sql("SELECT pg_sleep();").expect_err("pg_sleep without arguments should fail");
+    assert!(err.contains("takes 1 argument, got 0"), "unexpected error: {}", err);
+    let err = db.execute_sql("SELECT pg_sleep('soon');").expect_err("pg_sleep of text should fail");
+    assert!(err.contains("expects a number of seconds"), "unexpected error: {}", err);
+
+    let err = db.execute_sql("CREATE FUNCTION pg_sleep(x INT) RETURNS INT LANGUAGE SQL AS 'SELECT x';")
+        .expect_err("built-in functions should not be redefinable");
+    assert!(err.contains("built in"), "unexpected error: {}", err);
+}
+
+#[test]
+#[serial]
+fn test_clock_timestamps() {
+    let db = TestDb::new();
+
+    let result = db.execute_sql(
+        "SELECT statement_timestamp(), clock_timestamp(); SELECT pg_sleep(0.2); \
+         SELECT statement_timestamp(), clock_timestamp();",
+    ).expect("clock functions failed");
+    let found = timestamps(&result);
+    assert_eq!(found.len(), 4, "expected four timestamps: {}", result);
+
+    // Fixed for the whole query, while the clock moves on
+    assert_eq!(found[0], found[2], "statement_timestamp should not change within a query: {}", result);
+    assert!(found[3] > found[1], "clock_timestamp should advance: {}", result);
+    assert!(found[1] >= found[0], "clock_timestamp should not precede the statement: {}", result);
+
+    let date = &found[0][..10];
+    assert!(date.starts_with("20") && date.as_bytes()[4] == b'-' && date.as_bytes()[7] == b'-', "unexpected timestamp format: {}", found[0]);
+
+    let err = db.execute_sql("SELECT clock_timestamp(1);").expect_err("clock_timestamp takes no arguments");
+    assert!(err.contains("takes no arguments"), "unexpected error: {}", err);
+}
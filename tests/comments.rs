@@ -0,0 +1,47 @@
+mod common;
+
+use common::TestDb;
+use serial_test::serial;
+
+#[test]
+#[serial]
+fn test_comments_visible_in_pg_description() {
+    let mut db = TestDb::new();
+
+    db.execute_sql("CREATE TABLE documented (id INT, name STRING, PRIMARY KEY (id));")
+        .expect("CREATE TABLE failed");
+    db.execute_sql("COMMENT ON TABLE documented IS 'people we know';")
+        .expect("COMMENT ON TABLE failed");
+    db.execute_sql("COMMENT ON COLUMN documented.name IS 'full name';")
+        .expect("COMMENT ON COLUMN failed");
+
+    let result = db
+        .execute_sql("SELECT * FROM pg_catalog.pg_description;")
+        .expect("SELECT pg_description failed");
+    assert!(result.contains("people we know"), "table comment missing: {}", result);
+    assert!(result.contains("full name"), "column comment missing: {}", result);
+
+    let result = db
+        .execute_sql("SELECT * FROM information_schema.columns;")
+        .expect("SELECT information_schema.columns failed");
+    assert!(result.contains("documented") && result.contains("name"), "columns missing: {}", result);
+
+    // Comments are part of the catalog and survive a restart
+    db.restart().expect("restart failed");
+    let result = db
+        .execute_sql("SELECT * FROM pg_description;")
+        .expect("SELECT pg_description after restart failed");
+    assert!(result.contains("people we know"), "table comment lost on restart: {}", result);
+
+    // IS NULL removes a comment
+    db.execute_sql("COMMENT ON TABLE documented IS NULL;")
+        .expect("COMMENT ON TABLE IS NULL failed");
+    let result = db
+        .execute_sql("SELECT * FROM pg_description;")
+        .expect("SELECT pg_description failed");
+    assert!(!result.contains("people we know"), "table comment not cleared: {}", result);
+    assert!(result.contains("full name"), "column comment should remain: {}", result);
+
+    let result = db.execute_sql("COMMENT ON COLUMN documented.missing IS 'x';");
+    assert!(result.is_err(), "commenting on a missing column should fail");
+}
@@ -1,6 +1,7 @@
 use std::fs;
+use std::io::Write;
 use std::path::PathBuf;
-use std::process::{Command, Child};
+use std::process::{Command, Child, Stdio};
 use std::thread;
 use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
@@ -8,6 +9,8 @@ use std::time::{Duration, SystemTime, UNIX_EPOCH};
 pub struct TestDb {
     dir: PathBuf,
     server_process: Option<Child>,
+    /// Command line arguments the server is started with
+    args: Vec<String>,
 }
 
 impl TestDb {
@@ -31,7 +34,7 @@ impl TestDb {
         fs::create_dir_all(&dir).expect("failed to create temp dir");
 
         // Start server in temp directory
-        let server_process = Self::spawn_server(&dir);
+        let server_process = Self::spawn_server(&dir, &[]);
 
         // Wait for server to be ready
         Self::wait_for_server(30);
@@ -39,16 +42,18 @@ impl TestDb {
         TestDb {
             dir,
             server_process: Some(server_process),
+            args: Vec::new(),
         }
     }
 
     /// Spawn the flint server binary in the given directory
-    fn spawn_server(dir: &PathBuf) -> Child {
+    fn spawn_server(dir: &PathBuf, args: &[String]) -> Child {
         let binary_path = std::env::current_dir()
             .expect("failed to get current dir")
             .join("target/debug/flint");
 
         let child = Command::new(&binary_path)
+            .args(args)
             .current_dir(dir)
             .spawn()
             .expect("failed to spawn flint server");
@@ -77,6 +82,59 @@ impl TestDb {
         panic!("server failed to start after retries");
     }
 
+    /// Directory holding the database's files
+    pub fn data_dir(&self) -> &PathBuf {
+        &self.dir
+    }
+
+    /// Run the flint binary with `args` in the data directory, as for an
+    /// offline command, returning its output
+    pub fn run_flint(&self, args: &[&str]) -> Result<String, String> {
+        let binary_path = std::env::current_dir()
+            .expect("failed to get current dir")
+            .join("target/debug/flint");
+        let output = Command::new(&binary_path)
+            .args(args)
+            .current_dir(&self.dir)
+            .output()
+            .map_err(|e| format!("failed to run flint: {}", e))?;
+
+        if !output.status.success() {
+            return Err(String::from_utf8_lossy(&output.stderr).to_string());
+        }
+        Ok(String::from_utf8_lossy(&output.stdout).to_string())
+    }
+
+    /// Run the flint binary in the data directory with `input` on its stdin,
+    /// returning its stdout and stderr
+    /// HOME is the data directory, so nothing is written to the user's home
+    pub fn run_flint_with_input(&self, args: &[&str], input: &str) -> Result<(String, String), String> {
+        let binary_path = std::env::current_dir()
+            .expect("failed to get current dir")
+            .join("target/debug/flint");
+        let mut child = Command::new(&binary_path)
+            .args(args)
+            .current_dir(&self.dir)
+            .env("HOME", &self.dir)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|e| format!("failed to run flint: {}", e))?;
+        child.stdin.take()
+            .expect("stdin is piped")
+            .write_all(input.as_bytes())
+            .map_err(|e| format!("failed to write to flint: {}", e))?;
+        let output = child.wait_with_output()
+            .map_err(|e| format!("failed to run flint: {}", e))?;
+
+        let stderr = String::from_utf8_lossy(&output.stderr).to_string();
+        if !output.status.success() {
+            return Err(stderr);
+        }
+        Ok((String::from_utf8_lossy(&output.stdout).to_string(), stderr))
+    }
+
     /// Execute SQL statement via psql
     pub fn execute_sql(&self, sql: &str) -> Result<String, String> {
         let output = Command::new("psql")
@@ -99,6 +157,19 @@ impl TestDb {
         Ok(stdout)
     }
 
+    /// Open a psql connection that stays open, reading statements from its
+    /// stdin, for tests that need a session to outlive a single query
+    /// The connection closes when the child's stdin is dropped
+    pub fn open_session(&self) -> Child {
+        Command::new("psql")
+            .args(&["-h", "127.0.0.1", "-U", "postgres", "-d", "postgres", "-q"])
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .expect("failed to spawn psql")
+    }
+
     /// Restart database (kill server, delete files, restart)
     pub fn restart(&mut self) -> Result<(), String> {
         // Kill server
@@ -117,11 +188,32 @@ impl TestDb {
         }
 
         // Restart server
-        self.server_process = Some(Self::spawn_server(&self.dir));
+        self.server_process = Some(Self::spawn_server(&self.dir, &self.args));
         Self::wait_for_server(30);
 
         Ok(())
     }
+
+    /// Restart the server with different command line arguments
+    pub fn restart_with_args(&mut self, args: &[&str]) -> Result<(), String> {
+        self.args = args.iter().map(|arg| arg.to_string()).collect();
+        self.restart()
+    }
+
+    /// Stop the server; the next restart starts it again
+    pub fn stop(&mut self) {
+        if let Some(mut proc) = self.server_process.take() {
+            let _ = proc.kill();
+            let _ = proc.wait();
+        }
+    }
+
+    /// Stop the server and delete everything in its data directory
+    pub fn stop_and_clear(&mut self) -> Result<(), String> {
+        self.stop();
+        fs::remove_dir_all(&self.dir).map_err(|e| format!("failed to clear data directory: {}", e))?;
+        fs::create_dir_all(&self.dir).map_err(|e| format!("failed to recreate data directory: {}", e))
+    }
 }
 
 impl Drop for TestDb {
@@ -150,4 +242,11 @@ impl Drop for TestDb {
             thread::sleep(Duration::from_millis(100));
         }
     }
-}
\ No newline at end of file
+}
+
+/// The single integer value of a one-row, one-column result
+pub fn scalar(result: &str) -> i64 {
+    result.lines()
+        .find_map(|line| line.trim().parse().ok())
+        .unwrap_or_else(|| panic!("no integer in result: {}", result))
+}
@@ -0,0 +1,44 @@
+mod common;
+
+use std::process::Command;
+use std::thread;
+use std::time::Duration;
+
+use common::TestDb;
+use serial_test::serial;
+
+/// Try to connect and run a query, returning psql's stderr if it fails
+fn try_connect() -> Result<(), String> {
+    let output = Command::new("psql")
+        .args(["-h", "127.0.0.1", "-U", "postgres", "-d", "postgres", "-c", "SELECT 1;"])
+        .output()
+        .map_err(|e| format!("failed to execute psql: {}", e))?;
+    if !output.status.success() {
+        return Err(String::from_utf8_lossy(&output.stderr).to_string());
+    }
+    Ok(())
+}
+
+#[test]
+#[serial]
+fn test_max_connections() {
+    let mut db = TestDb::new();
+    db.restart_with_args(&["--max-connections=2"]).expect("restart failed");
+
+    let mut first = db.open_session();
+    let mut second = db.open_session();
+    thread::sleep(Duration::from_millis(500));
+
+    // Past the limit, clients are refused with Postgres's error
+    let err = try_connect().unwrap_err();
+    assert!(err.contains("sorry, too many clients already"), "unexpected error: {}", err);
+
+    // A closed connection makes room for another
+    drop(first.stdin.take());
+    first.wait_with_output().expect("psql did not exit");
+    thread::sleep(Duration::from_millis(200));
+    try_connect().expect("connection after one closed failed");
+
+    drop(second.stdin.take());
+    second.wait_with_output().expect("psql did not exit");
+}
@@ -0,0 +1,168 @@
+mod common;
+
+use std::io::Write;
+
+use common::TestDb;
+use serial_test::serial;
+
+/// Run a psql script, whose COPY FROM STDIN statements read the lines after
+/// them up to `\.`, returning psql's stdout and stderr
+fn run_script(db: &TestDb, script: &str) -> (String, String) {
+    let mut psql = db.open_session();
+    let mut stdin = psql.stdin.take().expect("psql stdin");
+    stdin.write_all(script.as_bytes()).expect("write to psql failed");
+    drop(stdin);
+    let output = psql.wait_with_output().expect("psql did not exit");
+    (
+        String::from_utf8_lossy(&output.stdout).to_string(),
+        String::from_utf8_lossy(&output.stderr).to_string(),
+    )
+}
+
+#[test]
+#[serial]
+fn test_copy_csv() {
+    let db = TestDb::new();
+    db.execute_sql("CREATE TABLE items (id INT PRIMARY KEY, name STRING, price FLOAT, active BOOLEAN);")
+        .expect("CREATE TABLE failed");
+    db.execute_sql("CREATE INDEX items_name ON items (name);").expect("CREATE INDEX failed");
+
+    let script = "COPY items FROM STDIN (FORMAT csv, HEADER true);\n\
+        id,name,price,active\n\
+        1,bolt,2.5,true\n\
+        2,\"nut, hex\",0.75,f\n\
+        3,\"say \"\"hi\"\"\",,yes\n\
+        4,\"two\nlines\",1,\n\
+        \\.\n\
+        \\a\n\
+        SELECT name FROM items ORDER BY id;\n";
+    let (stdout, stderr) = run_script(&db, script);
+    assert!(stderr.is_empty(), "COPY failed: {}", stderr);
+    assert!(stdout.contains("\nnut, hex\n"), "quoted delimiter lost: {}", stdout);
+    assert!(stdout.contains("\nsay \"hi\"\n"), "doubled quotes not unescaped: {}", stdout);
+    assert!(stdout.contains("\ntwo\nlines\n"), "quoted newline lost: {}", stdout);
+    let result = db.execute_sql("SELECT count(*) FROM items WHERE price IS NULL OR active IS NULL;")
+        .expect("SELECT failed");
+    assert!(result.contains(" 2\n"), "empty fields should be NULL: {}", result);
+
+    // Enough rows for several batches, found through the index afterwards
+    let mut script = String::from("COPY items (id, name) FROM STDIN WITH (FORMAT csv);\n");
+    for id in 100..2600 {
+        script.push_str(&format!("{},item{}\n", id, id));
+    }
+    script.push_str("\\.\n");
+    let (stdout, stderr) = run_script(&db, &script);
+    assert!(stderr.is_empty(), "COPY failed: {}", stderr);
+    assert!(stdout.is_empty() || stdout.contains("COPY 2500"), "unexpected output: {}", stdout);
+    let result = db.execute_sql("SELECT count(*) FROM items;").expect("SELECT failed");
+    assert!(result.contains(" 2504\n"), "unexpected count: {}", result);
+    let result = db.execute_sql("SELECT id FROM items WHERE name = 'item2345';").expect("SELECT failed");
+    assert!(result.contains(" 2345\n"), "index lookup failed: {}", result);
+}
+
+#[test]
+#[serial]
+fn test_copy_text() {
+    let db = TestDb::new();
+    db.execute_sql("CREATE TABLE notes (id SERIAL PRIMARY KEY, author STRING, body STRING);")
+        .expect("CREATE TABLE failed");
+
+    // Tab-separated, with \N for NULL and backslash escapes; the serial
+    // column the data leaves out takes its sequence's values
+    let script = "COPY notes (author, body) FROM STDIN;\n\
+        ann\tfirst\\tline\n\
+        \\N\tsecond\\\\line\\nwrapped\n\
+        \\.\n\
+        \\a\n\
+        SELECT body FROM notes ORDER BY id;\n";
+    let (stdout, stderr) = run_script(&db, script);
+    assert!(stderr.is_empty(), "COPY failed: {}", stderr);
+    assert!(stdout.contains("\nfirst\tline\n"), "tab escape not read: {}", stdout);
+    assert!(stdout.contains("\nsecond\\line\nwrapped\n"), "escapes not read: {}", stdout);
+    let result = db.execute_sql("SELECT id FROM notes WHERE author IS NULL;").expect("SELECT failed");
+    assert!(result.contains(" 2\n"), "\\N should be NULL: {}", result);
+    let result = db.execute_sql("SELECT max(id) FROM notes;").expect("SELECT failed");
+    assert!(result.contains(" 2\n"), "serial column not filled: {}", result);
+}
+
+#[test]
+#[serial]
+fn test_copy_errors() {
+    let db = TestDb::new();
+    db.execute_sql("CREATE TABLE items (id INT PRIMARY KEY, name STRING);").expect("CREATE TABLE failed");
+
+    // A bad row fails the COPY with the line it is on, and the session goes on
+    let script = "COPY items FROM STDIN (FORMAT csv);\n\
+        1,bolt\n\
+        2,nut,extra\n\
+        \\.\n\
+        SELECT count(*) FROM items;\n";
+    let (stdout, stderr) = run_script(&db, script);
+    assert!(stderr.contains("extra data after last expected column") && stderr.contains("line 2"),
+        "unexpected error: {}", stderr);
+    assert!(stdout.contains("count"), "session should go on after the error: {}", stdout);
+
+    let (_, stderr) = run_script(&db, "COPY items FROM STDIN (FORMAT csv);\n3\n\\.\n");
+    assert!(stderr.contains("missing data for column \"name\""), "unexpected error: {}", stderr);
+    let (_, stderr) = run_script(&db, "COPY items FROM STDIN (FORMAT csv);\nthree,bolt\n\\.\n");
+    assert!(stderr.contains("invalid input syntax"), "unexpected error: {}", stderr);
+    let (_, stderr) = run_script(&db, "COPY items (id, missing) FROM STDIN;\n\\.\n");
+    assert!(stderr.contains("column \"missing\" of relation \"items\" does not exist"), "unexpected error: {}", stderr);
+    let (_, stderr) = run_script(&db, "COPY items FROM STDIN (FORMAT binary);\n\\.\n");
+    assert!(stderr.contains("not supported"), "unexpected error: {}", stderr);
+
+    // Keys are checked as for INSERT
+    db.execute_sql("INSERT INTO items VALUES (10, 'washer');").expect("INSERT failed");
+    let (_, stderr) = run_script(&db, "COPY items FROM STDIN (FORMAT csv);\n10,again\n\\.\n");
+    assert!(stderr.contains("ERROR"), "duplicate key should fail: {}", stderr);
+    let result = db.execute_sql("SELECT name FROM items WHERE id = 10;").expect("SELECT failed");
+    assert!(result.contains("washer") && !result.contains("again"), "unexpected result: {}", result);
+}
+
+#[test]
+#[serial]
+fn test_copy_to_stdout() {
+    let db = TestDb::new();
+    db.execute_sql("CREATE TABLE items (id INT PRIMARY KEY, name STRING, price FLOAT, active BOOLEAN);")
+        .expect("CREATE TABLE failed");
+    db.execute_sql("INSERT INTO items VALUES (1, 'bolt', 2.5, true), (2, 'nut, hex', 1.0, false), \
+        (3, 'say \"hi\"', NULL, NULL), (4, '', 0.25, true);")
+        .expect("INSERT failed");
+
+    // CSV quotes what needs it, and an empty string apart from NULL
+    let result = db.execute_sql("COPY items TO STDOUT (FORMAT csv, HEADER true);").expect("COPY failed");
+    assert_eq!(
+        result,
+        "id,name,price,active\n1,bolt,2.5,t\n2,\"nut, hex\",1,f\n3,\"say \"\"hi\"\"\",,\n4,\"\",0.25,t\n"
+    );
+
+    // Text escapes tabs and newlines, and writes NULL as \N
+    db.execute_sql("INSERT INTO items VALUES (5, 'tab\there\nnext', NULL, false);").expect("INSERT failed");
+    let result = db.execute_sql("COPY items (id, name, price) TO STDOUT;").expect("COPY failed");
+    assert!(result.contains("3\tsay \"hi\"\t\\N\n"), "unexpected output: {}", result);
+    assert!(result.contains("5\ttab\\there\\nnext\t\\N\n"), "unexpected output: {}", result);
+
+    // A query's rows, among other statements of the same query
+    let result = db.execute_sql("SELECT 'before'; COPY (SELECT name FROM items WHERE id < 3 ORDER BY id) TO STDOUT; SELECT 'after';")
+        .expect("COPY failed");
+    let before = result.find("before").expect("missing first result");
+    let copied = result.find("bolt\nnut, hex\n").expect("missing copied rows");
+    let after = result.find("after").expect("missing last result");
+    assert!(before < copied && copied < after, "results out of order: {}", result);
+
+    // What COPY TO writes, COPY FROM reads back
+    db.execute_sql("CREATE TABLE copied (id INT PRIMARY KEY, name STRING, price FLOAT, active BOOLEAN);")
+        .expect("CREATE TABLE failed");
+    for format in ["text", "csv"] {
+        db.execute_sql("DELETE FROM copied;").expect("DELETE failed");
+        let data = db.execute_sql(&format!("COPY items TO STDOUT (FORMAT {});", format)).expect("COPY failed");
+        let script = format!("COPY copied FROM STDIN (FORMAT {});\n{}\\.\n", format, data);
+        let (_, stderr) = run_script(&db, &script);
+        assert!(stderr.is_empty(), "COPY FROM failed: {}", stderr);
+        let copied = db.execute_sql(&format!("COPY copied TO STDOUT (FORMAT {});", format)).expect("COPY failed");
+        assert_eq!(copied, data, "{} round trip changed the rows", format);
+    }
+
+    let err = db.execute_sql("COPY items TO '/tmp/items.csv';").unwrap_err();
+    assert!(err.contains("not supported"), "unexpected error: {}", err);
+}
@@ -0,0 +1,46 @@
+mod common;
+
+use common::TestDb;
+use serial_test::serial;
+
+#[test]
+#[serial]
+fn test_cursor_fetch_pages() {
+    let db = TestDb::new();
+
+    db.execute_sql("CREATE TABLE items (id INT, name STRING, PRIMARY KEY (id));")
+        .expect("CREATE TABLE failed");
+    db.execute_sql("INSERT INTO items VALUES (1, 'one'), (2, 'two'), (3, 'three'), (4, 'four'), (5, 'five');")
+        .expect("INSERT failed");
+
+    // Each call is its own connection, so a cursor is used within one query
+    let result = db.execute_sql(
+        "BEGIN; DECLARE c CURSOR FOR SELECT * FROM items; \
+         FETCH 2 FROM c; FETCH NEXT FROM c; FETCH ALL FROM c; COMMIT;",
+    )
+    .expect("DECLARE and FETCH failed");
+    let pages: Vec<&str> = result.matches("row").collect();
+    assert_eq!(pages.len(), 3, "expected three pages: {}", result);
+    assert!(result.contains("(2 rows)") && result.contains("(1 row)"), "wrong page sizes: {}", result);
+    for name in ["one", "two", "three", "four", "five"] {
+        assert_eq!(result.matches(name).count(), 1, "{} should be fetched once: {}", name, result);
+    }
+
+    let result = db.execute_sql("DECLARE c CURSOR FOR SELECT * FROM items;");
+    assert!(result.is_err(), "cursor without HOLD needs a transaction block");
+
+    let result = db.execute_sql("BEGIN; DECLARE c CURSOR FOR SELECT * FROM items; COMMIT; FETCH 1 FROM c;");
+    assert!(result.is_err(), "cursor should be closed at the end of the transaction");
+
+    let result = db.execute_sql(
+        "DECLARE h CURSOR WITH HOLD FOR SELECT * FROM items; FETCH 4 FROM h; CLOSE h;",
+    )
+    .expect("WITH HOLD cursor failed");
+    assert!(result.contains("(4 rows)") && result.contains("CLOSE CURSOR"), "wrong result: {}", result);
+
+    let result = db.execute_sql("BEGIN; DECLARE c CURSOR FOR SELECT * FROM items; FETCH PRIOR FROM c;");
+    assert!(result.is_err(), "cursors only scan forward");
+
+    let result = db.execute_sql("FETCH 1 FROM h;");
+    assert!(result.is_err(), "cursor should not outlive its connection");
+}
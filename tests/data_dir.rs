@@ -0,0 +1,36 @@
+mod common;
+
+use std::fs;
+
+use common::TestDb;
+use serial_test::serial;
+
+#[test]
+#[serial]
+fn test_data_dir() {
+    let mut db = TestDb::new();
+    let data_dir = db.data_dir().join("data");
+    db.restart_with_args(&["--data-dir=data"]).expect("restart with a data directory failed");
+
+    db.execute_sql("CREATE TABLE items (id INT PRIMARY KEY, name STRING);").expect("CREATE TABLE failed");
+    db.execute_sql("INSERT INTO items VALUES (1, 'bolt');").expect("INSERT failed");
+
+    // Every file is made in the data directory, none in the working directory
+    assert!(data_dir.join("table_items.tbl").exists(), "table file not in the data directory");
+    let stray: Vec<String> = fs::read_dir(db.data_dir()).unwrap()
+        .flatten()
+        .map(|entry| entry.file_name().to_string_lossy().to_string())
+        .filter(|name| name.ends_with(".tbl") || name.ends_with(".idx") || name.starts_with("catalog_"))
+        .collect();
+    assert!(stray.is_empty(), "files made outside the data directory: {:?}", stray);
+
+    // A data directory that was moved opens with its tables
+    db.stop();
+    let moved = db.data_dir().join("moved");
+    fs::rename(&data_dir, &moved).expect("failed to move the data directory");
+    db.restart_with_args(&[&format!("--data-dir={}", moved.display())]).expect("restart in the moved directory failed");
+    let result = db.execute_sql("SELECT name FROM items;").expect("SELECT after move failed");
+    assert!(result.contains("bolt"), "rows lost in move: {}", result);
+    db.execute_sql("INSERT INTO items VALUES (2, 'nut');").expect("INSERT after move failed");
+    assert!(!data_dir.exists(), "files written to the old data directory");
+}
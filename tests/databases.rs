@@ -0,0 +1,84 @@
+mod common;
+
+use std::process::{Command, Stdio};
+use std::thread;
+use std::time::Duration;
+
+use common::TestDb;
+use serial_test::serial;
+
+/// Run `sql` in database `database`, returning psql's stdout or, if it
+/// fails, its stderr
+fn psql_in(database: &str, sql: &str) -> Result<String, String> {
+    let output = Command::new("psql")
+        .args(["-h", "127.0.0.1", "-U", "postgres", "-d", database, "-c", sql])
+        .output()
+        .map_err(|e| format!("failed to execute psql: {}", e))?;
+    if !output.status.success() {
+        return Err(String::from_utf8_lossy(&output.stderr).to_string());
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).to_string())
+}
+
+#[test]
+#[serial]
+fn test_create_and_drop_database() {
+    let mut db = TestDb::new();
+    db.execute_sql("CREATE TABLE items (id INT PRIMARY KEY, name STRING);").expect("CREATE TABLE failed");
+    db.execute_sql("INSERT INTO items VALUES (1, 'bolt');").expect("INSERT failed");
+
+    let err = psql_in("analytics", "SELECT 1;").unwrap_err();
+    assert!(err.contains("database \"analytics\" does not exist"), "unexpected error: {}", err);
+
+    db.execute_sql("CREATE DATABASE analytics;").expect("CREATE DATABASE failed");
+    let err = db.execute_sql("CREATE DATABASE analytics;").unwrap_err();
+    assert!(err.contains("database \"analytics\" already exists"), "unexpected error: {}", err);
+    db.execute_sql("CREATE DATABASE IF NOT EXISTS analytics;").expect("CREATE DATABASE IF NOT EXISTS failed");
+
+    // Each database has tables of its own, even of the same name
+    psql_in("analytics", "CREATE TABLE items (id INT PRIMARY KEY, label STRING);").expect("CREATE TABLE failed");
+    psql_in("analytics", "INSERT INTO items VALUES (7, 'event');").expect("INSERT failed");
+    let result = psql_in("analytics", "SELECT id, label FROM items;").expect("SELECT failed");
+    assert!(result.contains("  7 | event") && !result.contains("bolt"), "unexpected rows: {}", result);
+    let result = db.execute_sql("SELECT id, name FROM items;").expect("SELECT failed");
+    assert!(result.contains("  1 | bolt") && !result.contains("event"), "unexpected rows: {}", result);
+
+    // Databases are found again after a restart
+    db.restart().expect("restart failed");
+    let result = psql_in("analytics", "SELECT label FROM items;").expect("SELECT after restart failed");
+    assert!(result.contains("event"), "rows lost in restart: {}", result);
+
+    let err = psql_in("analytics", "DROP DATABASE analytics;").unwrap_err();
+    assert!(err.contains("cannot drop the currently open database"), "unexpected error: {}", err);
+    let err = db.execute_sql("BEGIN; DROP DATABASE analytics;").unwrap_err();
+    assert!(err.contains("DROP DATABASE cannot run inside a transaction block"), "unexpected error: {}", err);
+
+    db.execute_sql("DROP DATABASE analytics;").expect("DROP DATABASE failed");
+    assert!(!db.data_dir().join("databases/analytics").exists(), "database files left behind");
+    let err = psql_in("analytics", "SELECT 1;").unwrap_err();
+    assert!(err.contains("does not exist"), "unexpected error: {}", err);
+    db.execute_sql("DROP DATABASE IF EXISTS analytics;").expect("DROP DATABASE IF EXISTS failed");
+}
+
+#[test]
+#[serial]
+fn test_drop_database_in_use() {
+    let db = TestDb::new();
+    db.execute_sql("CREATE DATABASE reports;").expect("CREATE DATABASE failed");
+
+    let mut session = Command::new("psql")
+        .args(["-h", "127.0.0.1", "-U", "postgres", "-d", "reports"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .expect("failed to spawn psql");
+    thread::sleep(Duration::from_millis(500));
+
+    let err = db.execute_sql("DROP DATABASE reports;").unwrap_err();
+    assert!(err.contains("database \"reports\" is being accessed by other users"), "unexpected error: {}", err);
+
+    drop(session.stdin.take());
+    session.wait_with_output().expect("psql did not exit");
+    thread::sleep(Duration::from_millis(200));
+    db.execute_sql("DROP DATABASE reports;").expect("DROP DATABASE after disconnect failed");
+}
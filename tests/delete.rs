@@ -0,0 +1,160 @@
+mod common;
+
+use common::TestDb;
+use serial_test::serial;
+
+#[test]
+#[serial]
+fn test_delete_rows() {
+    let db = TestDb::new();
+
+    db.execute_sql("CREATE TABLE jobs (id INT, state STRING, PRIMARY KEY (id));")
+        .expect("CREATE TABLE failed");
+    db.execute_sql("INSERT INTO jobs VALUES (1, 'done'), (2, 'queued'), (3, 'done'), (4, 'running');")
+        .expect("INSERT failed");
+
+    let result = db.execute_sql("DELETE FROM jobs WHERE state = 'done';").expect("DELETE failed");
+    assert!(result.contains("DELETE 2"), "expected two rows deleted: {}", result);
+
+    let result = db.execute_sql("SELECT * FROM jobs;").expect("SELECT failed");
+    assert!(result.contains("(2 rows)") && !result.contains("done"), "scan should skip deleted rows: {}", result);
+    let result = db.execute_sql("SELECT * FROM jobs WHERE id = 1;").expect("SELECT failed");
+    assert!(!result.contains("done"), "index lookup should not find a deleted row: {}", result);
+
+    let result = db.execute_sql("DELETE FROM jobs WHERE state = 'missing';").expect("DELETE failed");
+    assert!(result.contains("DELETE 0"), "nothing should match: {}", result);
+
+    let result = db.execute_sql("SELECT flint_row_count('jobs'), flint_approx_row_count('jobs');")
+        .expect("row count failed");
+    assert_eq!(result.matches(" 2").count(), 2, "both counts should drop: {}", result);
+
+    let result = db.execute_sql("DELETE FROM jobs;").expect("DELETE without WHERE failed");
+    assert!(result.contains("DELETE 2"), "every remaining row should go: {}", result);
+    let result = db.execute_sql("SELECT * FROM jobs;").expect("SELECT failed");
+    assert!(!result.contains("queued") && !result.contains("running"), "table should be empty: {}", result);
+}
+
+#[test]
+#[serial]
+fn test_deleted_key_is_reusable() {
+    let mut db = TestDb::new();
+
+    db.execute_sql("CREATE TABLE tags (name STRING, uses INT, PRIMARY KEY (name));")
+        .expect("CREATE TABLE failed");
+    db.execute_sql("INSERT INTO tags VALUES ('red', 1), ('green', 2), ('blue', 3);")
+        .expect("INSERT failed");
+
+    db.execute_sql("DELETE FROM tags WHERE name = 'green';").expect("DELETE failed");
+    db.execute_sql("INSERT INTO tags VALUES ('green', 20), ('cyan', 4);")
+        .expect("re-inserting a deleted key failed");
+
+    let result = db.execute_sql("SELECT * FROM tags WHERE name = 'green';").expect("SELECT failed");
+    assert!(result.contains("20") && result.contains("(1 row)"), "lookup should find the new row: {}", result);
+
+    db.execute_sql("DELETE FROM tags WHERE uses > 3;").expect("DELETE failed");
+    db.restart().expect("restart failed");
+
+    let result = db.execute_sql("SELECT * FROM tags;").expect("SELECT failed");
+    assert!(result.contains("red") && result.contains("blue") && result.contains("(2 rows)"),
+        "deletes should survive a restart: {}", result);
+    let result = db.execute_sql("SELECT * FROM tags WHERE name = 'cyan';").expect("SELECT failed");
+    assert!(!result.contains("cyan"), "deleted key should stay gone after a restart: {}", result);
+}
+
+#[test]
+#[serial]
+fn test_reused_slot_is_not_found_by_old_key() {
+    let db = TestDb::new();
+
+    db.execute_sql("CREATE TABLE jobs (id INT, state STRING, PRIMARY KEY (id));")
+        .expect("CREATE TABLE failed");
+    db.execute_sql("CREATE INDEX jobs_state ON jobs (state);").expect("CREATE INDEX failed");
+    db.execute_sql("INSERT INTO jobs VALUES (1, 'done'), (2, 'queued');").expect("INSERT failed");
+
+    // The new row takes the freed slot of the deleted one
+    db.execute_sql("DELETE FROM jobs WHERE id = 1;").expect("DELETE failed");
+    db.execute_sql("INSERT INTO jobs VALUES (3, 'running');").expect("INSERT failed");
+
+    let result = db.execute_sql("SELECT count(*) FROM jobs WHERE id = 1;").expect("SELECT failed");
+    assert!(result.contains(" 0\n"), "old key should not find the slot's new row: {}", result);
+    let result = db.execute_sql("SELECT count(*) FROM jobs WHERE state = 'done';").expect("SELECT failed");
+    assert!(result.contains(" 0\n"), "old index value should not find the slot's new row: {}", result);
+    let result = db.execute_sql("SELECT * FROM jobs WHERE id = 3;").expect("SELECT failed");
+    assert!(result.contains("running") && result.contains("(1 row)"), "new key should find its row: {}", result);
+    db.execute_sql("INSERT INTO jobs VALUES (1, 'again');").expect("re-inserting the old key failed");
+}
+
+#[test]
+#[serial]
+fn test_vacuum_forgets_deleted_keys() {
+    let mut db = TestDb::new();
+
+    db.execute_sql("CREATE TABLE v (id INT PRIMARY KEY, pad TEXT);").expect("CREATE TABLE failed");
+    db.execute_sql("INSERT INTO v VALUES (1, 'a'), (2, 'b'), (3, 'c'), (4, 'd');").expect("INSERT failed");
+    db.execute_sql("DELETE FROM v WHERE id IN (1, 2);").expect("DELETE failed");
+    db.execute_sql("VACUUM v;").expect("VACUUM failed");
+
+    // The surviving rows move into the deleted rows' slots
+    let result = db.execute_sql("SELECT count(*) FROM v WHERE id = 1;").expect("SELECT failed");
+    assert!(result.contains(" 0\n"), "deleted key should find nothing after VACUUM: {}", result);
+    let result = db.execute_sql("SELECT * FROM v WHERE id = 3;").expect("SELECT failed");
+    assert!(result.contains("  3 | c\n") && result.contains("(1 row)"), "moved row should be found by its key: {}", result);
+
+    db.execute_sql("INSERT INTO v VALUES (1, 'again');").expect("re-inserting a vacuumed key failed");
+    let result = db.execute_sql("SELECT * FROM v WHERE id = 1;").expect("SELECT failed");
+    assert!(result.contains("  1 | again\n") && result.contains("(1 row)"), "re-inserted key should be found: {}", result);
+
+    // VACUUM replaced the table file, which is the one opened again
+    db.restart().expect("restart failed");
+    let result = db.execute_sql("SELECT * FROM v ORDER BY id;").expect("SELECT failed");
+    assert!(result.contains("  1 | again\n") && result.contains("  4 | d\n") && result.contains("(3 rows)"),
+        "compacted rows should survive a restart: {}", result);
+    let compacted = std::fs::read_dir(db.data_dir()).expect("read_dir failed")
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.file_name().to_string_lossy().ends_with(".compact"))
+        .count();
+    assert_eq!(compacted, 0, "VACUUM should leave no copy of the table behind");
+}
+
+#[test]
+#[serial]
+fn test_truncate_table() {
+    let mut db = TestDb::new();
+
+    db.execute_sql("CREATE TABLE events (id INT, kind STRING, PRIMARY KEY (id));").expect("CREATE TABLE failed");
+    db.execute_sql("CREATE TABLE users (id INT, name STRING, PRIMARY KEY (id));").expect("CREATE TABLE failed");
+    db.execute_sql("CREATE INDEX events_kind ON events (kind);").expect("CREATE INDEX failed");
+    db.execute_sql("INSERT INTO events VALUES (1, 'click'), (2, 'view'), (3, 'click');").expect("INSERT failed");
+    db.execute_sql("INSERT INTO users VALUES (1, 'alice'), (2, 'bob');").expect("INSERT failed");
+
+    let result = db.execute_sql("TRUNCATE events;").expect("TRUNCATE failed");
+    assert!(result.contains("TRUNCATE TABLE"), "unexpected command tag: {}", result);
+
+    let result = db.execute_sql("SELECT * FROM events;").expect("SELECT failed");
+    assert!(!result.contains("row"), "table should be empty: {}", result);
+    let result = db.execute_sql("SELECT flint_row_count('events'), flint_approx_row_count('events');")
+        .expect("row count failed");
+    assert_eq!(result.matches(" 0").count(), 2, "both counts should be zero: {}", result);
+    let result = db.execute_sql("SELECT * FROM users;").expect("SELECT failed");
+    assert!(result.contains("(2 rows)"), "other tables are untouched: {}", result);
+
+    // Keys and indexes start over, with the schema kept
+    db.execute_sql("INSERT INTO events VALUES (1, 'view'), (2, 'click');").expect("re-inserting truncated keys failed");
+    db.execute_sql("UPDATE events SET kind = 'scroll' WHERE kind = 'click';").expect("UPDATE failed");
+    let result = db.execute_sql("SELECT kind FROM events WHERE id = 2;").expect("SELECT failed");
+    assert!(result.contains("scroll") && result.contains("(1 row)"), "lookup should find the new row: {}", result);
+
+    db.restart().expect("restart failed");
+    let result = db.execute_sql("SELECT id, kind FROM events ORDER BY id;").expect("SELECT failed");
+    assert!(result.contains("(2 rows)") && result.contains("view"), "truncation should survive a restart: {}", result);
+
+    // Every table is checked before any is emptied
+    let result = db.execute_sql("TRUNCATE TABLE users, missing;");
+    assert!(result.is_err(), "truncating a missing table should fail: {:?}", result);
+    let result = db.execute_sql("SELECT * FROM users;").expect("SELECT failed");
+    assert!(result.contains("(2 rows)"), "a failed TRUNCATE empties nothing: {}", result);
+
+    db.execute_sql("TRUNCATE TABLE users, events RESTART IDENTITY;").expect("TRUNCATE of two tables failed");
+    let result = db.execute_sql("SELECT * FROM users;").expect("SELECT failed");
+    assert!(!result.contains("row"), "table should be empty: {}", result);
+}
@@ -0,0 +1,62 @@
+mod common;
+
+use common::TestDb;
+use serial_test::serial;
+
+#[test]
+#[serial]
+fn test_explain_estimates() {
+    let db = TestDb::new();
+
+    db.execute_sql("CREATE TABLE users (id INT, name STRING, age INT, PRIMARY KEY (id));").expect("CREATE TABLE failed");
+    db.execute_sql("INSERT INTO users VALUES (1, 'alice', 30), (2, 'bob', 40), (3, 'carol', 50), (4, 'dave', 60);")
+        .expect("INSERT failed");
+
+    // Every node carries its estimate, children indented under their parent
+    let result = db.execute_sql("EXPLAIN SELECT name FROM users WHERE age > 30 ORDER BY name;").expect("EXPLAIN failed");
+    assert!(result.contains("QUERY PLAN"), "unexpected EXPLAIN output: {}", result);
+    assert!(result.contains(" Project  (cost="), "unexpected root node: {}", result);
+    assert!(result.contains("   ->  Sort  (cost="), "unexpected EXPLAIN output: {}", result);
+    assert!(result.contains("Sort Key: name"), "unexpected EXPLAIN output: {}", result);
+    assert!(result.contains("Filter: (age > 30)"), "unexpected EXPLAIN output: {}", result);
+    assert!(result.contains("->  Seq Scan on users  (cost=0.00.."), "unexpected EXPLAIN output: {}", result);
+    // The scan reads all 4 rows; a third of them are expected to pass the filter
+    assert!(result.contains("rows=4)"), "the scan should estimate the table's rows: {}", result);
+    assert!(result.contains("Filter  (cost=") && result.contains("rows=1)"), "unexpected filter estimate: {}", result);
+
+    // Only the primary key answers lookups; other columns are scanned
+    let result = db.execute_sql("EXPLAIN SELECT * FROM users WHERE id = 2;").expect("EXPLAIN failed");
+    assert!(result.contains("Index Scan on users") && result.contains("Index Cond: (id = 2)"), "unexpected plan: {}", result);
+    let result = db.execute_sql("EXPLAIN SELECT * FROM users WHERE name = 'bob';").expect("EXPLAIN failed");
+    assert!(result.contains("Seq Scan on users") && result.contains("Filter: (name = 'bob')"), "unexpected plan: {}", result);
+
+    let result = db.execute_sql("EXPLAIN SELECT * FROM users u JOIN users v ON u.id = v.id;").expect("EXPLAIN failed");
+    assert!(result.contains("Nested Loop") && result.contains("Join Filter: (u.id = v.id)"), "unexpected plan: {}", result);
+    assert!(result.contains("Seq Scan on users u") && result.contains("Seq Scan on users v"), "unexpected plan: {}", result);
+
+    // Writes are planned but not run
+    let result = db.execute_sql("EXPLAIN DELETE FROM users WHERE age < 50;").expect("EXPLAIN DELETE failed");
+    assert!(result.contains("Delete on users  (cost=") && result.contains("rows=0)"), "unexpected plan: {}", result);
+    let result = db.execute_sql("SELECT count(*) FROM users;").expect("SELECT failed");
+    assert!(result.contains(" 4\n"), "EXPLAIN must not delete rows: {}", result);
+}
+
+#[test]
+#[serial]
+fn test_explain_options() {
+    let db = TestDb::new();
+
+    db.execute_sql("CREATE TABLE users (id INT, age INT, PRIMARY KEY (id));").expect("CREATE TABLE failed");
+
+    let result = db.execute_sql("EXPLAIN (COSTS OFF) SELECT age, count(*) FROM users GROUP BY age;").expect("EXPLAIN failed");
+    assert!(!result.contains("cost="), "COSTS OFF should leave out estimates: {}", result);
+    assert!(result.contains("Aggregate") && result.contains("Group Key: age"), "unexpected plan: {}", result);
+
+    let result = db.execute_sql("EXPLAIN (COSTS true, FORMAT TEXT) SELECT 1;").expect("EXPLAIN failed");
+    assert!(result.contains("Result  (cost=0.00..0.01 rows=1)"), "unexpected plan: {}", result);
+
+    for sql in ["EXPLAIN ANALYZE SELECT 1;", "EXPLAIN (ANALYZE) SELECT 1;", "EXPLAIN (FORMAT JSON) SELECT 1;"] {
+        let result = db.execute_sql(sql);
+        assert!(result.is_err(), "{} should be refused: {:?}", sql, result);
+    }
+}
@@ -0,0 +1,266 @@
+mod common;
+
+use std::io::{Read, Write};
+use std::net::TcpStream;
+
+use common::TestDb;
+use serial_test::serial;
+
+const INT8_OID: i32 = 20;
+const FLOAT8_OID: i32 = 701;
+const VARCHAR_OID: i32 = 1043;
+const BOOL_OID: i32 = 16;
+const TEXT_OID: i32 = 25;
+
+/// A bare connection speaking the extended query protocol, which psql
+/// can't be made to do with binary formats
+struct Connection {
+    stream: TcpStream,
+}
+
+impl Connection {
+    fn open() -> Self {
+        let mut stream = TcpStream::connect("127.0.0.1:5432").expect("connect failed");
+        let mut startup = Vec::new();
+        startup.extend_from_slice(&196608i32.to_be_bytes());
+        startup.extend_from_slice(b"user\0postgres\0database\0postgres\0\0");
+        let mut message = ((startup.len() + 4) as i32).to_be_bytes().to_vec();
+        message.extend_from_slice(&startup);
+        stream.write_all(&message).expect("startup failed");
+        let mut conn = Connection { stream };
+        conn.read_until_ready();
+        conn
+    }
+
+    fn send(&mut self, tag: u8, body: &[u8]) {
+        let mut message = vec![tag];
+        message.extend_from_slice(&((body.len() + 4) as i32).to_be_bytes());
+        message.extend_from_slice(body);
+        self.stream.write_all(&message).expect("write failed");
+    }
+
+    fn parse(&mut self, query: &str, param_types: &[i32]) {
+        let mut body = b"\0".to_vec();
+        body.extend_from_slice(query.as_bytes());
+        body.push(0);
+        body.extend_from_slice(&(param_types.len() as i16).to_be_bytes());
+        for oid in param_types {
+            body.extend_from_slice(&oid.to_be_bytes());
+        }
+        self.send(b'P', &body);
+    }
+
+    fn bind(&mut self, param_formats: &[i16], params: &[Option<Vec<u8>>], result_formats: &[i16]) {
+        let mut body = b"\0\0".to_vec();
+        body.extend_from_slice(&(param_formats.len() as i16).to_be_bytes());
+        for format in param_formats {
+            body.extend_from_slice(&format.to_be_bytes());
+        }
+        body.extend_from_slice(&(params.len() as i16).to_be_bytes());
+        for param in params {
+            match param {
+                Some(bytes) => {
+                    body.extend_from_slice(&(bytes.len() as i32).to_be_bytes());
+                    body.extend_from_slice(bytes);
+                }
+                None => body.extend_from_slice(&(-1i32).to_be_bytes()),
+            }
+        }
+        body.extend_from_slice(&(result_formats.len() as i16).to_be_bytes());
+        for format in result_formats {
+            body.extend_from_slice(&format.to_be_bytes());
+        }
+        self.send(b'B', &body);
+    }
+
+    fn describe(&mut self, target: u8) {
+        self.send(b'D', &[target, 0]);
+    }
+
+    /// Execute the unnamed portal and sync, returning the messages up to
+    /// ReadyForQuery as (type, body)
+    fn execute(&mut self) -> Vec<(u8, Vec<u8>)> {
+        let mut body = b"\0".to_vec();
+        body.extend_from_slice(&0i32.to_be_bytes());
+        self.send(b'E', &body);
+        self.sync()
+    }
+
+    fn sync(&mut self) -> Vec<(u8, Vec<u8>)> {
+        self.send(b'S', &[]);
+        self.read_until_ready()
+    }
+
+    fn read_until_ready(&mut self) -> Vec<(u8, Vec<u8>)> {
+        let mut messages = Vec::new();
+        loop {
+            let mut header = [0u8; 5];
+            self.stream.read_exact(&mut header).expect("read failed");
+            let len = i32::from_be_bytes(header[1..].try_into().unwrap()) as usize;
+            let mut body = vec![0u8; len - 4];
+            self.stream.read_exact(&mut body).expect("read failed");
+            if header[0] == b'Z' {
+                return messages;
+            }
+            messages.push((header[0], body));
+        }
+    }
+}
+
+/// Fields of a DataRow body, None for NULL
+fn data_row(body: &[u8]) -> Vec<Option<Vec<u8>>> {
+    let count = i16::from_be_bytes(body[..2].try_into().unwrap()) as usize;
+    let mut rest = &body[2..];
+    let mut fields = Vec::with_capacity(count);
+    for _ in 0..count {
+        let len = i32::from_be_bytes(rest[..4].try_into().unwrap());
+        rest = &rest[4..];
+        if len < 0 {
+            fields.push(None);
+        } else {
+            fields.push(Some(rest[..len as usize].to_vec()));
+            rest = &rest[len as usize..];
+        }
+    }
+    fields
+}
+
+/// Type OID and format code of each column of a RowDescription body
+fn row_description(body: &[u8]) -> Vec<(i32, i16)> {
+    let count = i16::from_be_bytes(body[..2].try_into().unwrap()) as usize;
+    let mut rest = &body[2..];
+    let mut columns = Vec::with_capacity(count);
+    for _ in 0..count {
+        let name_end = rest.iter().position(|&b| b == 0).unwrap();
+        let field = &rest[name_end + 1..];
+        let type_oid = i32::from_be_bytes(field[6..10].try_into().unwrap());
+        let format = i16::from_be_bytes(field[16..18].try_into().unwrap());
+        columns.push((type_oid, format));
+        rest = &field[18..];
+    }
+    columns
+}
+
+fn messages_of(messages: &[(u8, Vec<u8>)], tag: u8) -> Vec<&Vec<u8>> {
+    messages.iter().filter(|(t, _)| *t == tag).map(|(_, body)| body).collect()
+}
+
+#[test]
+#[serial]
+fn test_binary_results() {
+    let db = TestDb::new();
+    db.execute_sql("CREATE TABLE items (id INT PRIMARY KEY, price FLOAT, name STRING, active BOOLEAN);")
+        .expect("CREATE TABLE failed");
+    db.execute_sql("INSERT INTO items VALUES (1, 2.5, 'bolt', true), (2, 0.75, 'nut', false);")
+        .expect("INSERT failed");
+    let mut conn = Connection::open();
+
+    // Describing the statement gives its parameter and column types
+    conn.parse("SELECT id, price, name, active FROM items WHERE id = $1", &[INT8_OID]);
+    conn.describe(b'S');
+    let messages = conn.sync();
+    let parameters = messages_of(&messages, b't');
+    assert_eq!(parameters.len(), 1, "missing ParameterDescription: {:?}", messages);
+    assert_eq!(&parameters[0][..2], &1i16.to_be_bytes());
+    assert_eq!(&parameters[0][2..6], &INT8_OID.to_be_bytes());
+    let columns = row_description(messages_of(&messages, b'T')[0]);
+    let types: Vec<i32> = columns.iter().map(|(type_oid, _)| *type_oid).collect();
+    assert_eq!(types, vec![INT8_OID, FLOAT8_OID, VARCHAR_OID, BOOL_OID]);
+
+    // A binary parameter, and every column in binary
+    conn.bind(&[1], &[Some(1i64.to_be_bytes().to_vec())], &[1]);
+    conn.describe(b'P');
+    let messages = conn.execute();
+    let columns = row_description(messages_of(&messages, b'T')[0]);
+    assert!(columns.iter().all(|(_, format)| *format == 1), "columns should be binary: {:?}", columns);
+    let rows = messages_of(&messages, b'D');
+    assert_eq!(rows.len(), 1, "unexpected messages: {:?}", messages);
+    let fields = data_row(rows[0]);
+    assert_eq!(fields[0].as_deref(), Some(&1i64.to_be_bytes()[..]));
+    assert_eq!(fields[1].as_deref(), Some(&2.5f64.to_be_bytes()[..]));
+    assert_eq!(fields[2].as_deref(), Some(&b"bolt"[..]));
+    assert_eq!(fields[3].as_deref(), Some(&[1u8][..]));
+
+    // A format per column, and a text parameter
+    conn.bind(&[0], &[Some(b"2".to_vec())], &[0, 1, 0, 1]);
+    let messages = conn.execute();
+    let fields = data_row(messages_of(&messages, b'D')[0]);
+    assert_eq!(fields[0].as_deref(), Some(&b"2"[..]));
+    assert_eq!(fields[1].as_deref(), Some(&0.75f64.to_be_bytes()[..]));
+    assert_eq!(fields[2].as_deref(), Some(&b"nut"[..]));
+    assert_eq!(fields[3].as_deref(), Some(&[0u8][..]));
+
+    // A described query with no rows still completes as a query
+    conn.bind(&[1], &[Some(9i64.to_be_bytes().to_vec())], &[1]);
+    conn.describe(b'P');
+    let messages = conn.execute();
+    assert!(messages_of(&messages, b'D').is_empty() && !messages_of(&messages, b'T').is_empty(),
+        "unexpected messages: {:?}", messages);
+    let complete = messages_of(&messages, b'C');
+    assert_eq!(complete.len(), 1, "unexpected messages: {:?}", messages);
+    assert!(complete[0].starts_with(b"SELECT 0"), "unexpected command tag: {:?}", complete[0]);
+}
+
+#[test]
+#[serial]
+fn test_extended_query_statements() {
+    let db = TestDb::new();
+    db.execute_sql("CREATE TABLE notes (id INT PRIMARY KEY, body STRING);").expect("CREATE TABLE failed");
+    let mut conn = Connection::open();
+
+    // Parameters of unspecified type are described, and bound, as text
+    conn.parse("INSERT INTO notes VALUES ($1, $2)", &[INT8_OID, 0]);
+    conn.describe(b'S');
+    let messages = conn.sync();
+    let parameters = messages_of(&messages, b't');
+    assert_eq!(&parameters[0][2..], &[INT8_OID.to_be_bytes(), TEXT_OID.to_be_bytes()].concat()[..]);
+    assert!(messages_of(&messages, b'T').iter().all(|body| row_description(body).is_empty()),
+        "an INSERT returns no rows: {:?}", messages);
+    conn.bind(&[0], &[Some(b"7".to_vec()), Some(b"it's here".to_vec())], &[]);
+    let messages = conn.execute();
+    assert!(messages_of(&messages, b'E').is_empty(), "INSERT failed: {:?}", messages);
+    let result = db.execute_sql("SELECT body FROM notes WHERE id = 7;").expect("SELECT failed");
+    assert!(result.contains("it's here"), "unexpected result: {}", result);
+
+    // A wrong number of parameters, several statements, and a binary
+    // parameter of the wrong size are errors
+    conn.parse("SELECT body FROM notes WHERE id = $1", &[INT8_OID]);
+    conn.bind(&[], &[], &[]);
+    let messages = conn.execute();
+    assert!(!messages_of(&messages, b'E').is_empty(), "missing parameter should fail: {:?}", messages);
+    conn.parse("SELECT 1; SELECT 2", &[]);
+    let messages = conn.sync();
+    let errors = messages_of(&messages, b'E');
+    assert!(errors.iter().any(|body| String::from_utf8_lossy(body).contains("multiple commands")),
+        "several statements should fail: {:?}", messages);
+    conn.parse("SELECT body FROM notes WHERE id = $1", &[INT8_OID]);
+    conn.bind(&[1], &[Some(7i32.to_be_bytes().to_vec())], &[]);
+    let messages = conn.execute();
+    let errors = messages_of(&messages, b'E');
+    assert!(errors.iter().any(|body| String::from_utf8_lossy(body).contains("incorrect binary data format")),
+        "a 4-byte int8 should fail: {:?}", messages);
+
+    // The connection still works after the errors
+    conn.bind(&[1], &[Some(7i64.to_be_bytes().to_vec())], &[]);
+    let messages = conn.execute();
+    let fields = data_row(messages_of(&messages, b'D')[0]);
+    assert_eq!(fields[0].as_deref(), Some(&b"it's here"[..]));
+}
+
+#[test]
+#[serial]
+fn test_copy_to_stdout() {
+    let db = TestDb::new();
+    db.execute_sql("CREATE TABLE notes (id INT PRIMARY KEY, body STRING);").expect("CREATE TABLE failed");
+    db.execute_sql("INSERT INTO notes VALUES (1, 'first'), (2, NULL);").expect("INSERT failed");
+    let mut conn = Connection::open();
+
+    // The rows follow the CopyOutResponse, then CopyDone and the command tag
+    conn.parse("COPY notes TO STDOUT (FORMAT csv)", &[]);
+    conn.bind(&[], &[], &[]);
+    let messages = conn.execute();
+    let tags: Vec<u8> = messages.iter().map(|(tag, _)| *tag).collect();
+    assert_eq!(tags, b"12HdcC".to_vec(), "unexpected messages: {:?}", messages);
+    assert_eq!(messages_of(&messages, b'd')[0], &b"1,first\n2,\n".to_vec());
+    assert!(messages_of(&messages, b'C')[0].starts_with(b"COPY 2"), "unexpected command tag: {:?}", messages);
+}
@@ -0,0 +1,148 @@
+mod common;
+
+use common::{scalar, TestDb};
+use serial_test::serial;
+
+#[test]
+#[serial]
+fn test_foreign_key_checks() {
+    let mut db = TestDb::new();
+
+    db.execute_sql("CREATE TABLE authors (id INT, name STRING, PRIMARY KEY (id));").expect("CREATE TABLE failed");
+    db.execute_sql("CREATE TABLE books (id INT, author_id INT REFERENCES authors, title STRING, PRIMARY KEY (id));")
+        .expect("CREATE TABLE failed");
+    db.execute_sql("INSERT INTO authors VALUES (1, 'le guin'), (2, 'lem');").expect("INSERT failed");
+
+    db.execute_sql("INSERT INTO books VALUES (10, 1, 'earthsea'), (11, NULL, 'anonymous');").expect("INSERT failed");
+    let err = db.execute_sql("INSERT INTO books VALUES (12, 3, 'nobody');").expect_err("a missing author should be refused");
+    assert!(err.contains("violates foreign key constraint \"books_author_id_fkey\""), "unexpected error: {}", err);
+    assert!(err.contains("Key (author_id)=(3) is not present in table \"authors\""), "unexpected error: {}", err);
+    let err = db.execute_sql("UPDATE books SET author_id = 9 WHERE id = 10;").expect_err("a missing author should be refused");
+    assert!(err.contains("books_author_id_fkey"), "unexpected error: {}", err);
+
+    // A referenced row can neither go nor change its key
+    let err = db.execute_sql("DELETE FROM authors WHERE id = 1;").expect_err("a referenced author should stay");
+    assert!(err.contains("is still referenced from table \"books\""), "unexpected error: {}", err);
+    let err = db.execute_sql("UPDATE authors SET id = 5 WHERE id = 1;").expect_err("a referenced key should not change");
+    assert!(err.contains("books_author_id_fkey"), "unexpected error: {}", err);
+    let err = db.execute_sql("TRUNCATE authors;").expect_err("a referenced table should not be truncated");
+    assert!(err.contains("cannot truncate a table referenced in a foreign key constraint"), "unexpected error: {}", err);
+    db.execute_sql("DELETE FROM authors WHERE id = 2;").expect("an unreferenced author should be deleted");
+    db.execute_sql("UPDATE authors SET name = 'ursula' WHERE id = 1;").expect("UPDATE of other columns failed");
+
+    // Constraints survive a restart and follow renames
+    db.restart().expect("restart failed");
+    db.execute_sql("ALTER TABLE authors RENAME TO writers;").expect("RENAME failed");
+    db.execute_sql("ALTER TABLE books RENAME COLUMN author_id TO writer_id;").expect("RENAME COLUMN failed");
+    let err = db.execute_sql("INSERT INTO books VALUES (13, 7, 'lost');").expect_err("check lost on restart or rename");
+    assert!(err.contains("Key (writer_id)=(7) is not present in table \"writers\""), "unexpected error: {}", err);
+    let err = db.execute_sql("DELETE FROM writers;").expect_err("a referenced writer should stay");
+    assert!(err.contains("books_author_id_fkey"), "unexpected error: {}", err);
+    let result = db.execute_sql("SELECT count(*) FROM writers;").expect("SELECT failed");
+    assert!(result.contains(" 1\n"), "a refused delete should delete nothing: {}", result);
+
+    // Once nothing refers to it, the row can go
+    db.execute_sql("DELETE FROM books WHERE id = 10;").expect("DELETE failed");
+    db.execute_sql("DELETE FROM writers;").expect("DELETE failed");
+}
+
+#[test]
+#[serial]
+fn test_foreign_key_on_delete_cascade() {
+    let db = TestDb::new();
+
+    db.execute_sql("CREATE TABLE orders (id INT, customer STRING, PRIMARY KEY (id));").expect("CREATE TABLE failed");
+    db.execute_sql(
+        "CREATE TABLE order_lines (id INT, order_id INT, item STRING, PRIMARY KEY (id), \
+         CONSTRAINT lines_order FOREIGN KEY (order_id) REFERENCES orders (id) ON DELETE CASCADE);",
+    ).expect("CREATE TABLE failed");
+    db.execute_sql(
+        "CREATE TABLE shipments (id INT, line_id INT, PRIMARY KEY (id), \
+         FOREIGN KEY (line_id) REFERENCES order_lines (id) ON DELETE RESTRICT);",
+    ).expect("CREATE TABLE failed");
+    db.execute_sql("INSERT INTO orders VALUES (1, 'ann'), (2, 'bo');").expect("INSERT failed");
+    db.execute_sql("INSERT INTO order_lines VALUES (10, 1, 'pen'), (11, 1, 'ink'), (20, 2, 'pad');").expect("INSERT failed");
+    db.execute_sql("INSERT INTO shipments VALUES (100, 20);").expect("INSERT failed");
+
+    // Deleting an order deletes its lines; only the orders are counted
+    let result = db.execute_sql("DELETE FROM orders WHERE id = 1;").expect("DELETE failed");
+    assert!(result.contains("DELETE 1"), "unexpected tag: {}", result);
+    let result = db.execute_sql("SELECT id FROM order_lines ORDER BY id;").expect("SELECT failed");
+    assert!(result.contains("(1 row)") && result.contains("20"), "lines should have been cascaded to: {}", result);
+
+    // A cascade that reaches a restricted reference deletes nothing
+    let err = db.execute_sql("DELETE FROM orders WHERE id = 2;").expect_err("a shipped line should stay");
+    assert!(err.contains("violates foreign key constraint") && err.contains("on table \"shipments\""), "unexpected error: {}", err);
+    let result = db.execute_sql("SELECT id FROM orders;").expect("SELECT failed");
+    assert!(result.contains("(1 row)"), "the order should remain: {}", result);
+
+    // A self-referencing table cascades through its own rows
+    db.execute_sql("CREATE TABLE nodes (id INT, parent INT REFERENCES nodes (id) ON DELETE CASCADE, PRIMARY KEY (id));")
+        .expect("CREATE TABLE failed");
+    db.execute_sql("INSERT INTO nodes VALUES (1, NULL), (2, 1), (3, 2), (4, NULL);").expect("INSERT failed");
+    db.execute_sql("DELETE FROM nodes WHERE id = 1;").expect("DELETE failed");
+    let result = db.execute_sql("SELECT id FROM nodes;").expect("SELECT failed");
+    assert!(result.contains("(1 row)") && result.contains(" 4"), "the subtree should be gone: {}", result);
+}
+
+#[test]
+#[serial]
+fn test_foreign_key_lookups_use_index() {
+    let db = TestDb::new();
+    let reads = || scalar(&db.execute_sql("SELECT reads FROM pg_stat_io WHERE object = 'relation';")
+        .expect("SELECT pg_stat_io failed"));
+
+    // Lines spread over many blocks, only a few of them for any one order
+    db.execute_sql("CREATE TABLE orders (id INT, PRIMARY KEY (id));").expect("CREATE TABLE failed");
+    db.execute_sql(
+        "CREATE TABLE order_lines (id INT, order_id INT REFERENCES orders ON DELETE CASCADE, body STRING, \
+         PRIMARY KEY (id)) WITH (fillfactor = 10);",
+    ).expect("CREATE TABLE failed");
+    let orders: Vec<String> = (1..=20).map(|id| format!("({})", id)).collect();
+    db.execute_sql(&format!("INSERT INTO orders VALUES {};", orders.join(", "))).expect("INSERT failed");
+    let body = "x".repeat(1000);
+    let lines: Vec<String> = (1..=80).map(|id| format!("({}, {}, '{}')", id, (id - 1) / 4 + 1, body)).collect();
+    db.execute_sql(&format!("INSERT INTO order_lines VALUES {};", lines.join(", "))).expect("INSERT failed");
+
+    let before = reads();
+    db.execute_sql("SELECT count(*) FROM order_lines;").expect("SELECT failed");
+    let scan_reads = reads() - before;
+
+    // With an index on the referencing column, a cascade doesn't scan the lines
+    db.execute_sql("CREATE INDEX order_lines_order ON order_lines (order_id);").expect("CREATE INDEX failed");
+    let before = reads();
+    db.execute_sql("DELETE FROM orders WHERE id = 1;").expect("DELETE failed");
+    let delete_reads = reads() - before;
+    assert!(delete_reads < scan_reads, "cascade should go through the index: {} reads vs {} for a scan", delete_reads, scan_reads);
+
+    let result = db.execute_sql("SELECT count(*) FROM order_lines WHERE order_id = 1;").expect("SELECT failed");
+    assert_eq!(scalar(&result), 0, "lines of the deleted order should be gone: {}", result);
+    let result = db.execute_sql("SELECT count(*) FROM order_lines;").expect("SELECT failed");
+    assert_eq!(scalar(&result), 76, "other lines should stay: {}", result);
+
+    // Restricted references are found the same way
+    db.execute_sql("CREATE TABLE notes (id INT, line_id INT REFERENCES order_lines, PRIMARY KEY (id));").expect("CREATE TABLE failed");
+    db.execute_sql("CREATE INDEX notes_line ON notes (line_id);").expect("CREATE INDEX failed");
+    db.execute_sql("INSERT INTO notes VALUES (1, 5), (2, 6);").expect("INSERT failed");
+    let err = db.execute_sql("DELETE FROM orders WHERE id = 2;").expect_err("a noted line should stay");
+    assert!(err.contains("Key (id)=(5) is still referenced from table \"notes\""), "unexpected error: {}", err);
+    db.execute_sql("DELETE FROM orders WHERE id = 3;").expect("an order with no noted lines should go");
+}
+
+#[test]
+#[serial]
+fn test_foreign_key_definition_errors() {
+    let db = TestDb::new();
+
+    db.execute_sql("CREATE TABLE parents (id INT, code STRING, PRIMARY KEY (id));").expect("CREATE TABLE failed");
+    let cases = [
+        ("CREATE TABLE c (id INT, p INT REFERENCES missing, PRIMARY KEY (id));", "Table not found"),
+        ("CREATE TABLE c (id INT, p STRING REFERENCES parents (code), PRIMARY KEY (id));", "no unique constraint"),
+        ("CREATE TABLE c (id INT, p STRING REFERENCES parents, PRIMARY KEY (id));", "incompatible types"),
+        ("CREATE TABLE c (id INT, p INT REFERENCES parents ON DELETE SET NULL, PRIMARY KEY (id));", "not supported"),
+    ];
+    for (sql, expected) in cases {
+        let err = db.execute_sql(sql).expect_err("CREATE TABLE should be refused");
+        assert!(err.contains(expected), "{}: unexpected error: {}", sql, err);
+    }
+}
@@ -0,0 +1,45 @@
+mod common;
+
+use common::TestDb;
+use serial_test::serial;
+
+#[test]
+#[serial]
+fn test_sql_functions_persist() {
+    let mut db = TestDb::new();
+
+    db.execute_sql("CREATE FUNCTION add_one(x INT) RETURNS INT LANGUAGE SQL AS 'SELECT x + 1';")
+        .expect("CREATE FUNCTION failed");
+    db.execute_sql("CREATE FUNCTION twice(INT) RETURNS INT RETURN $1 * 2;")
+        .expect("CREATE FUNCTION with RETURN failed");
+
+    let result = db.execute_sql("SELECT twice(add_one(20));").expect("SELECT function failed");
+    assert!(result.contains("42"), "nested calls should be inlined: {}", result);
+
+    db.execute_sql("CREATE TABLE items (id INT, name STRING, PRIMARY KEY (id));")
+        .expect("CREATE TABLE failed");
+    db.execute_sql("INSERT INTO items VALUES (add_one(6), 'seven'), (twice(4), 'eight');")
+        .expect("INSERT with functions failed");
+
+    // Functions are part of the catalog and survive a restart
+    db.restart().expect("restart failed");
+
+    let result = db.execute_sql("SELECT * FROM items WHERE id = twice(4);")
+        .expect("SELECT with function in WHERE failed");
+    assert!(result.contains("eight") && !result.contains("seven"), "wrong rows: {}", result);
+
+    let result = db.execute_sql("CREATE FUNCTION add_one(x INT) RETURNS INT RETURN x + 2;");
+    assert!(result.is_err(), "redefining without OR REPLACE should fail");
+    db.execute_sql("CREATE OR REPLACE FUNCTION add_one(x INT) RETURNS INT RETURN x + 100;")
+        .expect("CREATE OR REPLACE FUNCTION failed");
+    let result = db.execute_sql("SELECT add_one(1);").expect("SELECT replaced function failed");
+    assert!(result.contains("101"), "replacement should take effect: {}", result);
+
+    let result = db.execute_sql("CREATE FUNCTION bad(x INT) RETURNS INT RETURN y + 1;");
+    assert!(result.is_err(), "body referring to an unknown name should be rejected");
+
+    db.execute_sql("DROP FUNCTION add_one(INT);").expect("DROP FUNCTION failed");
+    db.restart().expect("restart failed");
+    assert!(db.execute_sql("SELECT add_one(1);").is_err(), "dropped function should stay dropped");
+    db.execute_sql("DROP FUNCTION IF EXISTS add_one;").expect("DROP FUNCTION IF EXISTS failed");
+}
@@ -0,0 +1,224 @@
+mod common;
+
+use common::{scalar, TestDb};
+use serial_test::serial;
+
+#[test]
+#[serial]
+fn test_gin_index() {
+    let db = TestDb::new();
+
+    db.execute_sql("CREATE TABLE docs (id INT, tag STRING, PRIMARY KEY (id));").expect("CREATE TABLE failed");
+    db.execute_sql("INSERT INTO docs VALUES (1, 'a'), (2, 'b'), (3, 'a'), (4, NULL);").expect("INSERT failed");
+
+    // Built over the rows already in the table, then kept up to date
+    db.execute_sql("CREATE INDEX docs_tag ON docs USING GIN (tag);").expect("CREATE INDEX USING GIN failed");
+    db.execute_sql("INSERT INTO docs VALUES (5, 'a');").expect("INSERT failed");
+    db.execute_sql("UPDATE docs SET tag = 'c' WHERE tag = 'a';").expect("UPDATE failed");
+    db.execute_sql("DELETE FROM docs WHERE id IN (2, 3);").expect("DELETE failed");
+
+    let result = db.execute_sql("SELECT id, tag FROM docs ORDER BY id;").expect("SELECT failed");
+    assert!(result.contains("(3 rows)"), "unexpected rows after writes to an indexed table: {}", result);
+    assert!(result.contains("  1 | c\n") && result.contains("  5 | c\n"), "unexpected rows: {}", result);
+
+    let result = db.execute_sql("CREATE INDEX docs_missing ON docs USING GIN (missing);");
+    assert!(result.is_err(), "an index on a missing column should fail: {:?}", result);
+}
+
+#[test]
+#[serial]
+fn test_create_index_progress_view() {
+    let db = TestDb::new();
+
+    db.execute_sql("CREATE TABLE docs (id INT, tag STRING, PRIMARY KEY (id));").expect("CREATE TABLE failed");
+    db.execute_sql("INSERT INTO docs VALUES (1, 'a'), (2, 'b');").expect("INSERT failed");
+    db.execute_sql("CREATE INDEX docs_tag ON docs (tag);").expect("CREATE INDEX failed");
+
+    // Builds are only listed while they run
+    let result = db.execute_sql("SELECT relname, index_name, phase, tuples_done, tuples_total FROM pg_stat_progress_create_index;")
+        .expect("SELECT pg_stat_progress_create_index failed");
+    assert!(!result.contains("row"), "no build should be running: {}", result);
+
+    // Secondary indexes follow the rows VACUUM moves
+    db.execute_sql("DELETE FROM docs WHERE id = 1;").expect("DELETE failed");
+    db.execute_sql("VACUUM docs;").expect("VACUUM failed");
+    db.execute_sql("UPDATE docs SET tag = 'c';").expect("UPDATE after VACUUM failed");
+    let result = db.execute_sql("SELECT id, tag FROM docs;").expect("SELECT failed");
+    assert!(result.contains("  2 | c\n") && result.contains("(1 row)"), "unexpected rows after VACUUM: {}", result);
+}
+
+#[test]
+#[serial]
+fn test_create_index_if_not_exists() {
+    let db = TestDb::new();
+
+    db.execute_sql("CREATE TABLE docs (id INT, tag STRING, PRIMARY KEY (id));").expect("CREATE TABLE failed");
+    db.execute_sql("CREATE TABLE notes (id INT, tag STRING, PRIMARY KEY (id));").expect("CREATE TABLE failed");
+    db.execute_sql("CREATE INDEX docs_tag ON docs (tag);").expect("CREATE INDEX failed");
+
+    // Index names are shared by every table
+    let err = db.execute_sql("CREATE INDEX docs_tag ON notes (tag);").expect_err("duplicate index name should fail");
+    assert!(err.contains("Index already exists: docs_tag"), "unexpected error: {}", err);
+    db.execute_sql("CREATE INDEX IF NOT EXISTS docs_tag ON notes (tag);").expect("CREATE INDEX IF NOT EXISTS failed");
+    db.execute_sql("CREATE INDEX IF NOT EXISTS notes_tag ON notes (tag);").expect("CREATE INDEX IF NOT EXISTS on a new name failed");
+
+    db.execute_sql("INSERT INTO notes VALUES (1, 'a'), (2, 'b');").expect("INSERT failed");
+    let result = db.execute_sql("SELECT id FROM notes WHERE tag = 'b';").expect("SELECT failed");
+    assert!(result.contains("(1 row)") && result.contains(" 2"), "the index should be kept up to date: {}", result);
+}
+
+#[test]
+#[serial]
+fn test_multi_column_index() {
+    let db = TestDb::new();
+
+    db.execute_sql("CREATE TABLE people (id INT, last STRING, first STRING, age INT, PRIMARY KEY (id));")
+        .expect("CREATE TABLE failed");
+    // Long runs of each name, and last names that differ only past the
+    // bytes a key keeps
+    let lasts = ["lee", "kim", "abcdefghij-1", "abcdefghij-2"];
+    let firsts = ["ann", "bo", "cy"];
+    let person = |id: usize| {
+        let first = if id.is_multiple_of(7) { None } else { Some(firsts[id % 3]) };
+        (lasts[id % 4], first, (id % 50) as i64)
+    };
+    let values: Vec<String> = (1..=600)
+        .map(|id| {
+            let (last, first, age) = person(id);
+            let first = first.map_or("NULL".to_string(), |first| format!("'{}'", first));
+            format!("({}, '{}', {}, {})", id, last, first, age)
+        })
+        .collect();
+    db.execute_sql(&format!("INSERT INTO people VALUES {};", values.join(", "))).expect("INSERT failed");
+    db.execute_sql("CREATE INDEX people_name ON people (last, first);").expect("CREATE INDEX failed");
+
+    let count = |sql: &str| -> usize {
+        let result = db.execute_sql(sql).expect("SELECT failed");
+        result.lines().nth(2).and_then(|line| line.trim().parse().ok()).unwrap_or_else(|| panic!("no count: {}", result))
+    };
+    let expected = |pred: &dyn Fn(&str, Option<&str>, i64) -> bool| {
+        (1..=600).filter(|id| { let (last, first, age) = person(*id); pred(last, first, age) }).count()
+    };
+
+    // The whole key, a prefix of it with other terms, and the leading column alone
+    assert_eq!(
+        count("SELECT COUNT(*) FROM people WHERE last = 'abcdefghij-2' AND first = 'bo';"),
+        expected(&|last, first, _| last == "abcdefghij-2" && first == Some("bo")),
+    );
+    assert_eq!(
+        count("SELECT COUNT(*) FROM people WHERE last = 'lee' AND age > 30;"),
+        expected(&|last, _, age| last == "lee" && age > 30),
+    );
+    assert_eq!(count("SELECT COUNT(*) FROM people WHERE last = 'kim';"), expected(&|last, _, _| last == "kim"));
+    // A column after a gap in the key can't be looked up
+    assert_eq!(
+        count("SELECT COUNT(*) FROM people WHERE first = 'cy' AND age = 3;"),
+        expected(&|_, first, age| first == Some("cy") && age == 3),
+    );
+
+    let result = db.execute_sql("EXPLAIN SELECT * FROM people WHERE last = 'lee' AND first = 'ann';").expect("EXPLAIN failed");
+    assert!(
+        result.contains("Index Scan on people") && result.contains("Index Cond: ((last = 'lee') AND (first = 'ann'))"),
+        "the index should be used: {}", result
+    );
+    let result = db.execute_sql("EXPLAIN SELECT * FROM people WHERE first = 'cy' AND age = 3;").expect("EXPLAIN failed");
+    assert!(result.contains("Seq Scan on people"), "no index leads with first: {}", result);
+
+    // The index follows writes
+    db.execute_sql("UPDATE people SET first = 'dee' WHERE id <= 100;").expect("UPDATE failed");
+    db.execute_sql("DELETE FROM people WHERE id > 500;").expect("DELETE failed");
+    assert_eq!(count("SELECT COUNT(*) FROM people WHERE last = 'lee' AND first = 'dee';"), 25);
+    assert_eq!(
+        count("SELECT COUNT(*) FROM people WHERE last = 'kim' AND first = 'ann';"),
+        (101..=500).filter(|id| { let (last, first, _) = person(*id); last == "kim" && first == Some("ann") }).count(),
+    );
+}
+
+#[test]
+#[serial]
+fn test_multi_column_index_errors() {
+    let db = TestDb::new();
+
+    db.execute_sql("CREATE TABLE t (id INT, a INT, b INT, PRIMARY KEY (id));").expect("CREATE TABLE failed");
+    let cases = [
+        ("CREATE INDEX t_hash ON t USING HASH (a, b);", "access method \"hash\" does not support multicolumn indexes"),
+        ("CREATE INDEX t_mixed ON t (a ASC, b DESC);", "not supported"),
+        ("CREATE INDEX t_wide ON t (a, b, a, b, a, b, a, b, a);", "cannot use more than 8 columns in an index"),
+        ("CREATE INDEX t_missing ON t (a, missing);", "Column not found"),
+    ];
+    for (sql, expected) in cases {
+        let err = db.execute_sql(sql).expect_err("CREATE INDEX should be refused");
+        assert!(err.contains(expected), "{}: unexpected error: {}", sql, err);
+    }
+
+    // A descending key is searched from the other end
+    db.execute_sql("INSERT INTO t VALUES (1, 1, 1), (2, 1, 2), (3, 2, 1), (4, -1, NULL);").expect("INSERT failed");
+    db.execute_sql("CREATE INDEX t_desc ON t (a DESC, b DESC);").expect("CREATE INDEX failed");
+    let result = db.execute_sql("SELECT id FROM t WHERE a = 1 AND id > 0 ORDER BY id;").expect("SELECT failed");
+    assert!(result.contains("(2 rows)") && result.contains(" 1\n") && result.contains(" 2\n"), "unexpected rows: {}", result);
+    let result = db.execute_sql("SELECT id FROM t WHERE a = -1 AND b IS NULL;").expect("SELECT failed");
+    assert!(result.contains("(1 row)") && result.contains(" 4\n"), "unexpected rows: {}", result);
+}
+
+#[test]
+#[serial]
+fn test_range_scan_stops_at_limit() {
+    let db = TestDb::new();
+    let reads = || scalar(&db.execute_sql("SELECT reads FROM pg_stat_io WHERE object = 'relation';")
+        .expect("SELECT pg_stat_io failed"));
+
+    // A tenth of each block filled, so the range covers many blocks
+    db.execute_sql("CREATE TABLE events (id INT, body STRING, PRIMARY KEY (id)) WITH (fillfactor = 10);")
+        .expect("CREATE TABLE failed");
+    let body = "x".repeat(1000);
+    let values: Vec<String> = (1..=80).map(|id| format!("({}, '{}')", id, body)).collect();
+    db.execute_sql(&format!("INSERT INTO events VALUES {};", values.join(", "))).expect("INSERT failed");
+
+    let before = reads();
+    let result = db.execute_sql("SELECT id FROM events WHERE id BETWEEN 1 AND 80;").expect("SELECT failed");
+    let range_reads = reads() - before;
+    assert!(result.contains("(80 rows)"), "unexpected rows: {}", result);
+
+    let before = reads();
+    let result = db.execute_sql("SELECT id FROM events WHERE id BETWEEN 1 AND 80 LIMIT 2 OFFSET 1;").expect("SELECT failed");
+    let limit_reads = reads() - before;
+    assert!(result.contains("(2 rows)") && result.contains(" 2\n") && result.contains(" 3\n"), "unexpected rows: {}", result);
+    assert!(limit_reads * 10 < range_reads, "LIMIT should stop the scan early: {} reads vs {}", limit_reads, range_reads);
+
+    // Scanned backward for the ORDER BY, the range stops at the LIMIT too
+    let before = reads();
+    let result = db.execute_sql("SELECT id FROM events WHERE id BETWEEN 1 AND 80 ORDER BY id DESC LIMIT 2;").expect("SELECT failed");
+    let backward_reads = reads() - before;
+    assert!(result.contains(" 80\n 79\n"), "unexpected rows: {}", result);
+    assert!(backward_reads * 10 < range_reads, "LIMIT should stop the scan early: {} reads vs {}", backward_reads, range_reads);
+}
+
+#[test]
+#[serial]
+fn test_order_by_primary_key_scans_index() {
+    let db = TestDb::new();
+
+    db.execute_sql("CREATE TABLE events (id INT, score INT, PRIMARY KEY (id));").expect("CREATE TABLE failed");
+    db.execute_sql("INSERT INTO events VALUES (3, 30), (1, NULL), (5, 10), (2, 50), (4, 20);").expect("INSERT failed");
+
+    // The primary index already holds the rows in order, either way round
+    let plan = db.execute_sql("EXPLAIN SELECT id FROM events ORDER BY id DESC LIMIT 3;").expect("EXPLAIN failed");
+    assert!(plan.contains("Index Scan Backward on events"), "unexpected EXPLAIN output: {}", plan);
+    assert!(!plan.contains("Sort"), "unexpected EXPLAIN output: {}", plan);
+    let result = db.execute_sql("SELECT id FROM events ORDER BY id DESC LIMIT 3;").expect("SELECT failed");
+    assert!(result.contains("  5\n  4\n  3\n"), "unexpected rows: {}", result);
+
+    let plan = db.execute_sql("EXPLAIN SELECT id FROM events WHERE id BETWEEN 2 AND 4 ORDER BY id;").expect("EXPLAIN failed");
+    assert!(plan.contains("Index Scan on events"), "unexpected EXPLAIN output: {}", plan);
+    assert!(plan.contains("Index Cond: (id BETWEEN 2 AND 4)"), "unexpected EXPLAIN output: {}", plan);
+    assert!(!plan.contains("Sort"), "unexpected EXPLAIN output: {}", plan);
+    let result = db.execute_sql("SELECT id FROM events WHERE id BETWEEN 2 AND 4 ORDER BY id DESC;").expect("SELECT failed");
+    assert!(result.contains("  4\n  3\n  2\n"), "unexpected rows: {}", result);
+
+    // Without an index on the column, the rows are still sorted, NULL first
+    // when descending
+    let plan = db.execute_sql("EXPLAIN SELECT id FROM events ORDER BY score DESC;").expect("EXPLAIN failed");
+    assert!(plan.contains("Sort Key: score DESC"), "unexpected EXPLAIN output: {}", plan);
+    let result = db.execute_sql("SELECT id FROM events ORDER BY score DESC;").expect("SELECT failed");
+    assert!(result.contains("  1\n  2\n  3\n  4\n  5\n"), "unexpected rows: {}", result);
+}
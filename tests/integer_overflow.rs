@@ -0,0 +1,58 @@
+mod common;
+
+use common::TestDb;
+use serial_test::serial;
+
+#[test]
+#[serial]
+fn test_integer_overflow_errors() {
+    let db = TestDb::new();
+
+    // Integers are 64-bit all the way to the client
+    let result = db.execute_sql("SELECT 3000000000, 2147483647 + 1, -9223372036854775807 - 1;").expect("SELECT failed");
+    assert!(
+        result.contains(" 3000000000 | 2147483648 | -9223372036854775808"),
+        "bigint results should not wrap: {}",
+        result
+    );
+
+    for sql in [
+        "SELECT 9223372036854775807 + 1;",
+        "SELECT -9223372036854775807 - 2;",
+        "SELECT 4611686018427387904 * 2;",
+        "SELECT -(-9223372036854775807 - 1);",
+        "SELECT (-9223372036854775807 - 1) / -1;",
+    ] {
+        let err = db.execute_sql(sql).expect_err("overflowing arithmetic should fail");
+        assert!(err.contains("bigint out of range"), "unexpected error for {}: {}", sql, err);
+    }
+
+    db.execute_sql("CREATE TABLE totals (id INT, v INT, PRIMARY KEY (id));").expect("CREATE TABLE failed");
+    db.execute_sql("INSERT INTO totals VALUES (1, 9223372036854775807), (2, 1);").expect("INSERT failed");
+    let err = db.execute_sql("SELECT sum(v) FROM totals;").expect_err("an overflowing sum should fail");
+    assert!(err.contains("bigint out of range"), "unexpected error: {}", err);
+
+    let err = db.execute_sql("UPDATE totals SET v = v + 1;").expect_err("an overflowing update should fail");
+    assert!(err.contains("bigint out of range"), "unexpected error: {}", err);
+    let result = db.execute_sql("SELECT v FROM totals WHERE id = 1;").expect("SELECT failed");
+    assert!(result.contains("9223372036854775807"), "a failed update should change nothing: {}", result);
+}
+
+#[test]
+#[serial]
+fn test_integer_overflow_promotes_to_float() {
+    let mut db = TestDb::new();
+    db.restart_with_args(&["--integer-overflow=float"]).expect("restart failed");
+
+    let result = db.execute_sql("SELECT 9223372036854775807 * 2, 1 + 1;").expect("promoted arithmetic failed");
+    assert!(result.contains(" 1.8446744073709552e19 |        2\n"), "unexpected promoted result: {}", result);
+
+    db.execute_sql("CREATE TABLE totals (id INT, v INT, PRIMARY KEY (id));").expect("CREATE TABLE failed");
+    db.execute_sql("INSERT INTO totals VALUES (1, 9223372036854775807), (2, 1);").expect("INSERT failed");
+    let result = db.execute_sql("SELECT sum(v) FROM totals;").expect("promoted sum failed");
+    assert!(result.contains("9.223372036854776e18"), "unexpected promoted sum: {}", result);
+
+    // Explicit casts still check their range
+    let err = db.execute_sql("SELECT 40000::smallint;").expect_err("an out of range cast should fail");
+    assert!(err.contains("smallint out of range"), "unexpected error: {}", err);
+}
@@ -0,0 +1,49 @@
+mod common;
+
+use common::{scalar, TestDb};
+use serial_test::serial;
+
+#[test]
+#[serial]
+fn test_pg_stat_io() {
+    let db = TestDb::new();
+
+    let result = db.execute_sql("SELECT backend_type, object, context FROM pg_stat_io;").expect("SELECT pg_stat_io failed");
+    assert!(result.contains("(3 rows)"), "one row per kind of object: {}", result);
+    for object in ["relation", "index", "wal"] {
+        assert!(result.contains(&format!("client backend | {}", object)), "missing {} row: {}", object, result);
+    }
+
+    let writes = |object: &str| scalar(&db.execute_sql(&format!("SELECT writes FROM pg_stat_io WHERE object = '{}';", object))
+        .expect("SELECT pg_stat_io failed"));
+    let relation_writes = writes("relation");
+    let index_writes = writes("index");
+
+    db.execute_sql("CREATE TABLE events (id INT, kind STRING, PRIMARY KEY (id));").expect("CREATE TABLE failed");
+    db.execute_sql("INSERT INTO events VALUES (1, 'click'), (2, 'view');").expect("INSERT failed");
+    assert!(writes("relation") > relation_writes, "table writes should be counted");
+    assert!(writes("index") > index_writes, "index writes should be counted");
+
+    // There is no buffer pool, so every read goes to disk
+    let result = db.execute_sql("SELECT hits, evictions, hit_ratio FROM pg_stat_io WHERE object = 'relation';")
+        .expect("SELECT pg_stat_io failed");
+    assert!(result.contains("    0 |         0 |         0"), "unexpected cache counters: {}", result);
+}
+
+#[test]
+#[serial]
+fn test_pg_stat_wal() {
+    let db = TestDb::new();
+
+    let records = || scalar(&db.execute_sql("SELECT wal_records FROM pg_stat_wal;").expect("SELECT pg_stat_wal failed"));
+    let before = records();
+    db.execute_sql("CREATE SEQUENCE ids;").expect("CREATE SEQUENCE failed");
+    db.execute_sql("SELECT nextval('ids');").expect("nextval failed");
+    assert!(records() > before, "the sequence's WAL record should be counted");
+
+    // Both views report the same WAL counters
+    let wal = db.execute_sql("SELECT wal_write, wal_sync FROM pg_stat_wal;").expect("SELECT pg_stat_wal failed");
+    let io = db.execute_sql("SELECT writes, fsyncs FROM pg_stat_io WHERE object = 'wal';").expect("SELECT pg_stat_io failed");
+    let values = |result: &str| result.lines().nth(2).map(|line| line.split('|').map(|v| v.trim().to_string()).collect::<Vec<_>>());
+    assert_eq!(values(&wal), values(&io), "pg_stat_wal and pg_stat_io disagree: {} vs {}", wal, io);
+}
@@ -0,0 +1,105 @@
+mod common;
+
+use std::fs;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use common::TestDb;
+use serial_test::serial;
+
+/// Rows of a psql result as trimmed cells
+fn result_rows(result: &str) -> Vec<Vec<String>> {
+    result.lines()
+        .skip(2)
+        .take_while(|line| !line.starts_with('('))
+        .map(|line| line.split('|').map(|cell| cell.trim().to_string()).collect())
+        .collect()
+}
+
+fn create_tables(db: &TestDb) {
+    db.execute_sql("CREATE TABLE users (id INT, name STRING, PRIMARY KEY (id));")
+        .expect("CREATE TABLE users failed");
+    db.execute_sql("CREATE TABLE orders (id INT, user_id INT, total INT, PRIMARY KEY (id));")
+        .expect("CREATE TABLE orders failed");
+    db.execute_sql("INSERT INTO users VALUES (1, 'ann'), (2, 'bob'), (3, 'cy');")
+        .expect("INSERT users failed");
+    db.execute_sql("INSERT INTO orders VALUES (10, 1, 5), (11, 1, 7), (12, 2, 3), (13, 9, 1);")
+        .expect("INSERT orders failed");
+}
+
+#[test]
+#[serial]
+fn test_inner_join() {
+    let db = TestDb::new();
+    create_tables(&db);
+
+    let result = db.execute_sql(
+        "SELECT users.name, orders.total FROM users JOIN orders ON users.id = orders.user_id ORDER BY orders.id;",
+    ).expect("JOIN failed");
+    assert_eq!(result_rows(&result), [["ann", "5"], ["ann", "7"], ["bob", "3"]], "wrong join: {}", result);
+
+    // Aliases qualify columns, * keeps both tables' columns, and unique
+    // column names need no qualifier
+    let result = db.execute_sql("SELECT * FROM users u INNER JOIN orders o ON u.id = o.user_id WHERE total > 6;")
+        .expect("JOIN with aliases failed");
+    assert_eq!(result_rows(&result), [["1", "ann", "11", "1", "7"]], "wrong join: {}", result);
+
+    let result = db.execute_sql(
+        "SELECT name, COUNT(*), SUM(total) FROM users JOIN orders ON users.id = user_id GROUP BY name ORDER BY name;",
+    ).expect("JOIN with GROUP BY failed");
+    assert_eq!(result_rows(&result), [["ann", "2", "12"], ["bob", "1", "3"]], "wrong groups: {}", result);
+
+    let result = db.execute_sql("SELECT a.name, b.name FROM users a JOIN users b ON a.id + 1 = b.id ORDER BY a.id;")
+        .expect("self join failed");
+    assert_eq!(result_rows(&result), [["ann", "bob"], ["bob", "cy"]], "wrong self join: {}", result);
+}
+
+#[test]
+#[serial]
+fn test_cross_join_and_errors() {
+    let db = TestDb::new();
+    create_tables(&db);
+
+    let result = db.execute_sql("SELECT COUNT(*) FROM users, orders;").expect("cross join failed");
+    assert_eq!(result_rows(&result), [["12"]], "every pair should be joined: {}", result);
+    let result = db.execute_sql("SELECT COUNT(*) FROM users CROSS JOIN orders WHERE users.id = orders.user_id;")
+        .expect("CROSS JOIN failed");
+    assert_eq!(result_rows(&result), [["3"]], "wrong matches: {}", result);
+
+    let result = db.execute_sql("SELECT id FROM users JOIN orders ON users.id = orders.user_id;");
+    assert!(result.is_err_and(|err| err.contains("ambiguous")), "unqualified shared column should be ambiguous");
+    let result = db.execute_sql("SELECT * FROM users JOIN users ON users.id = users.id;");
+    assert!(result.is_err(), "joining a table to itself needs an alias");
+    let result = db.execute_sql("SELECT * FROM users LEFT JOIN orders ON users.id = orders.user_id;");
+    assert!(result.is_err(), "outer joins are not supported yet");
+}
+
+#[test]
+#[serial]
+fn test_join_switches_to_hash_join_on_misestimate() {
+    let mut db = TestDb::new();
+    let nanos = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_nanos();
+    let log_dir = std::env::temp_dir().join(format!("flint-join-test-{}", nanos));
+    let log_dir_arg = format!("--log-directory={}", log_dir.display());
+    db.restart_with_args(&[&log_dir_arg, "--log-rotation=never"]).expect("restart with a log directory failed");
+
+    // Every row shares one grp, so a JOIN b yields 10000 rows where the
+    // planner expects a few dozen, and the join with c above it explodes
+    for table in ["a", "b", "c"] {
+        db.execute_sql(&format!("CREATE TABLE {} (id INT, grp INT, PRIMARY KEY (id));", table)).expect("CREATE TABLE failed");
+        let values: Vec<String> = (1..=100).map(|id| format!("({}, 1)", id)).collect();
+        db.execute_sql(&format!("INSERT INTO {} VALUES {};", table, values.join(", "))).expect("INSERT failed");
+    }
+    db.execute_sql("INSERT INTO c VALUES (101, NULL);").expect("INSERT failed");
+
+    let result = db.execute_sql("SELECT COUNT(*) FROM a JOIN b ON a.grp = b.grp JOIN c ON b.id = c.id AND c.id > 50;")
+        .expect("JOIN failed");
+    assert_eq!(result_rows(&result), [["5000"]], "wrong join: {}", result);
+    let result = db.execute_sql("SELECT a.id, b.id, c.id FROM a JOIN b ON a.grp = b.grp JOIN c ON c.id = b.id LIMIT 3;")
+        .expect("JOIN failed");
+    assert_eq!(result_rows(&result), [["1", "1", "1"], ["1", "2", "2"], ["1", "3", "3"]], "order should be kept: {}", result);
+
+    let log = fs::read_to_string(log_dir.join("flint.log")).expect("log file should exist");
+    assert!(log.contains("switching to hash join"), "the switch should be logged: {}", log);
+
+    let _ = fs::remove_dir_all(&log_dir);
+}
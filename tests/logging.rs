@@ -0,0 +1,56 @@
+mod common;
+
+use std::fs;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use common::TestDb;
+use serial_test::serial;
+
+#[test]
+#[serial]
+fn test_json_log_file() {
+    let mut db = TestDb::new();
+
+    let nanos = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_nanos();
+    let log_dir = std::env::temp_dir().join(format!("flint-log-test-{}", nanos));
+    let log_dir_arg = format!("--log-directory={}", log_dir.display());
+    db.restart_with_args(&[&log_dir_arg, "--log-format=json", "--log-rotation=never"])
+        .expect("restart with a log directory failed");
+    db.execute_sql("CREATE TABLE events (id INT, PRIMARY KEY (id));").expect("CREATE TABLE failed");
+
+    // Without rotation there is a single file of one JSON object per line
+    let log = fs::read_to_string(log_dir.join("flint.log")).expect("log file should exist");
+    assert!(log.contains("server listening"), "startup should be logged: {}", log);
+    assert!(log.contains("CREATE TABLE events"), "queries should be logged: {}", log);
+    assert!(log.contains("\"level\":\"INFO\""), "log should be JSON: {}", log);
+    for line in log.lines() {
+        assert!(line.starts_with('{') && line.ends_with('}'), "not a JSON object: {}", line);
+        assert!(!line.contains('\u{1b}'), "file logs should have no color codes: {}", line);
+    }
+
+    let _ = fs::remove_dir_all(&log_dir);
+}
+
+#[test]
+#[serial]
+fn test_rotated_log_files() {
+    let mut db = TestDb::new();
+
+    let nanos = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_nanos();
+    let log_dir = std::env::temp_dir().join(format!("flint-log-test-{}", nanos));
+    let log_dir_arg = format!("--log-directory={}", log_dir.display());
+    db.restart_with_args(&[&log_dir_arg, "--log-rotation=daily", "--log-max-files=7"])
+        .expect("restart with a log directory failed");
+    db.execute_sql("SELECT 1;").expect("SELECT failed");
+
+    // Rotated files are named by the period they cover
+    let files: Vec<String> = fs::read_dir(&log_dir).expect("log directory should exist")
+        .map(|entry| entry.unwrap().file_name().to_string_lossy().to_string())
+        .collect();
+    assert_eq!(files.len(), 1, "unexpected log files: {:?}", files);
+    assert!(files[0].starts_with("flint.log."), "unexpected log file name: {:?}", files);
+    let log = fs::read_to_string(log_dir.join(&files[0])).expect("failed to read log file");
+    assert!(log.contains("INFO") && log.contains("server listening"), "startup should be logged as text: {}", log);
+
+    let _ = fs::remove_dir_all(&log_dir);
+}
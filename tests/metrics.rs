@@ -0,0 +1,65 @@
+mod common;
+
+use std::io::{Read, Write};
+use std::net::TcpStream;
+use std::thread;
+use std::time::Duration;
+
+use common::TestDb;
+use serial_test::serial;
+
+const METRICS_ADDR: &str = "127.0.0.1:9464";
+
+/// Send an HTTP GET for `path` to the metrics endpoint, returning the whole
+/// response
+fn http_get(path: &str) -> String {
+    let mut stream = TcpStream::connect(METRICS_ADDR).expect("failed to connect to metrics endpoint");
+    write!(stream, "GET {} HTTP/1.1\r\nHost: {}\r\n\r\n", path, METRICS_ADDR).expect("failed to send request");
+    let mut response = String::new();
+    stream.read_to_string(&mut response).expect("failed to read response");
+    response
+}
+
+/// Value of an unlabelled sample, or of one given with its labels
+fn sample(metrics: &str, name: &str) -> f64 {
+    metrics.lines()
+        .find_map(|line| line.strip_prefix(name)?.strip_prefix(' '))
+        .unwrap_or_else(|| panic!("no sample {} in:\n{}", name, metrics))
+        .parse()
+        .expect("sample is not a number")
+}
+
+#[test]
+#[serial]
+fn test_metrics_endpoint() {
+    let mut db = TestDb::new();
+    db.restart_with_args(&[&format!("--metrics-addr={}", METRICS_ADDR)]).expect("restart failed");
+
+    db.execute_sql("CREATE TABLE items (id INT PRIMARY KEY, name STRING);").expect("CREATE TABLE failed");
+    db.execute_sql("INSERT INTO items VALUES (1, 'bolt'), (2, 'nut');").expect("INSERT failed");
+    db.execute_sql("SELECT name FROM items WHERE id = 2;").expect("SELECT failed");
+    db.execute_sql("SELECT * FROM no_such_table;").unwrap_err();
+    let mut session = db.open_session();
+    thread::sleep(Duration::from_millis(500));
+
+    let response = http_get("/metrics");
+    assert!(response.starts_with("HTTP/1.1 200 OK"), "unexpected response: {}", response);
+    assert!(response.contains("Content-Type: text/plain; version=0.0.4"), "unexpected response: {}", response);
+    let metrics = &response[response.find("\r\n\r\n").unwrap() + 4..];
+
+    assert!(sample(metrics, "flint_queries_total") >= 4.0, "queries not counted:\n{}", metrics);
+    assert!(sample(metrics, "flint_query_errors_total") >= 1.0, "failed query not counted:\n{}", metrics);
+    assert!(metrics.contains("# TYPE flint_query_duration_seconds histogram"), "no latency histogram:\n{}", metrics);
+    assert_eq!(sample(metrics, "flint_query_duration_seconds_bucket{le=\"+Inf\"}"),
+        sample(metrics, "flint_query_duration_seconds_count"));
+    assert!(sample(metrics, "flint_connections_active") >= 1.0, "open session not counted:\n{}", metrics);
+    assert!(sample(metrics, "flint_blocks_written_total{object=\"relation\"}") >= 1.0, "writes not counted:\n{}", metrics);
+    assert!(sample(metrics, "flint_index_lookups_total") >= 1.0, "index lookups not counted:\n{}", metrics);
+
+    // Only /metrics is served
+    let response = http_get("/");
+    assert!(response.starts_with("HTTP/1.1 404"), "unexpected response: {}", response);
+
+    drop(session.stdin.take());
+    session.wait_with_output().expect("psql did not exit");
+}
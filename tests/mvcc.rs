@@ -0,0 +1,45 @@
+mod common;
+
+use std::fs;
+
+use common::TestDb;
+use serial_test::serial;
+
+/// Table file size with one data block: segment 0's header, its reserved
+/// block 0 and block 1
+const ONE_BLOCK_TABLE_SIZE: u64 = 3 * 64 * 1024;
+
+#[test]
+#[serial]
+fn test_row_versions() {
+    let mut db = TestDb::new();
+
+    db.execute_sql("CREATE TABLE counters (id INT, hits INT, note STRING, PRIMARY KEY (id));").expect("CREATE TABLE failed");
+    let note = "x".repeat(200);
+    db.execute_sql(&format!("INSERT INTO counters VALUES (1, 0, '{0}'), (2, 0, '{0}'), (3, 0, '{0}');", note))
+        .expect("INSERT failed");
+
+    // Every UPDATE leaves the versions it replaced behind, far more than fit
+    // in a block; once it commits their slots are reused, so the table never
+    // needs a second block
+    for _ in 0..500 {
+        db.execute_sql("UPDATE counters SET hits = hits + 1 WHERE id <> 3;").expect("UPDATE failed");
+    }
+    db.execute_sql("DELETE FROM counters WHERE id = 2;").expect("DELETE failed");
+
+    let result = db.execute_sql("SELECT id, hits FROM counters ORDER BY id;").expect("SELECT failed");
+    assert!(result.contains("(2 rows)") && result.contains(" 500") && !result.contains(" 2 "), "wrong rows: {}", result);
+    let result = db.execute_sql("SELECT hits FROM counters WHERE id = 2;").expect("SELECT failed");
+    assert!(!result.contains("500"), "index lookup should not find a deleted row: {}", result);
+    let size = fs::metadata(db.data_dir().join("table_counters.tbl")).expect("table file should exist").len();
+    assert_eq!(size, ONE_BLOCK_TABLE_SIZE, "dead versions should not take up new blocks");
+
+    // Transaction ids keep counting up from where they were, so versions
+    // written before the restart stay visible and deleted ones stay gone
+    db.restart().expect("restart failed");
+    let result = db.execute_sql("SELECT id, hits FROM counters ORDER BY id;").expect("SELECT failed");
+    assert!(result.contains("(2 rows)") && result.contains(" 500") && !result.contains(" 2 "), "wrong rows after restart: {}", result);
+    db.execute_sql("UPDATE counters SET hits = hits + 1 WHERE id = 1;").expect("UPDATE after restart failed");
+    let result = db.execute_sql("SELECT hits FROM counters WHERE id = 1;").expect("SELECT failed");
+    assert!(result.contains(" 501"), "the new version should be visible: {}", result);
+}
@@ -0,0 +1,85 @@
+mod common;
+
+use common::TestDb;
+use serial_test::serial;
+
+#[test]
+#[serial]
+fn test_arithmetic_operators() {
+    let db = TestDb::new();
+
+    let result = db.execute_sql("SELECT -5, -(2 + 3) * 2, 2 + 3 * 4, 7 % 3, -7 % 3, 7.5 % 2, @ -4, +3;")
+        .expect("arithmetic failed");
+    assert!(
+        result.contains("       -5 |      -10 |       14 |        1 |       -1 |      1.5 |        4 |        3"),
+        "unexpected arithmetic results: {}",
+        result
+    );
+
+    let result = db.execute_sql("SELECT 2 ^ 10 = 1024, 1 + NULL IS NULL, -NULL::int IS NULL;").expect("arithmetic failed");
+    assert!(result.contains(" t        | t        | t\n"), "unexpected results: {}", result);
+
+    let err = db.execute_sql("SELECT 9223372036854775807 + 1;").expect_err("overflow should fail");
+    assert!(err.contains("bigint out of range"), "unexpected error: {}", err);
+    let err = db.execute_sql("SELECT 5 % 0;").expect_err("modulo by zero should fail");
+    assert!(err.contains("Division by zero"), "unexpected error: {}", err);
+    let err = db.execute_sql("SELECT -'abc';").expect_err("negating text should fail");
+    assert!(err.contains("Type mismatch in unary -"), "unexpected error: {}", err);
+}
+
+#[test]
+#[serial]
+fn test_bitwise_and_logical_operators() {
+    let db = TestDb::new();
+
+    let result = db.execute_sql("SELECT 6 & 3, 6 | 3, 6 # 3, 1 << 4, 256 >> 2, ~0;").expect("bitwise operators failed");
+    assert!(
+        result.contains("        2 |        7 |        5 |       16 |       64 |       -1"),
+        "unexpected bitwise results: {}",
+        result
+    );
+    let err = db.execute_sql("SELECT 1.5 & 1;").expect_err("bitwise operators on floats should fail");
+    assert!(err.contains("Type mismatch in &"), "unexpected error: {}", err);
+
+    // Three-valued logic: NULL only decides the result when the other operand can't
+    let result = db.execute_sql("SELECT NOT true, false AND NULL, true OR NULL, (true AND NULL) IS NULL, (NOT NULL::bool) IS NULL, false OR true, true AND false;")
+        .expect("logical operators failed");
+    assert!(result.contains(" f        | f        | t        | t        | t        | t        | f\n"), "unexpected logical results: {}", result);
+}
+
+#[test]
+#[serial]
+fn test_operators_in_filters() {
+    let db = TestDb::new();
+
+    db.execute_sql("CREATE TABLE nums (id INT, v INT, PRIMARY KEY (id));").expect("CREATE TABLE failed");
+    db.execute_sql("INSERT INTO nums VALUES (1, -3), (2, 4), (3, 5), (4, 6);").expect("INSERT failed");
+
+    // NOT binds more loosely than =, and AND more loosely than NOT
+    let result = db.execute_sql("SELECT id FROM nums WHERE NOT v % 2 = 0 AND -v < 0;").expect("SELECT failed");
+    assert!(result.contains("(1 row)") && result.contains(" 3\n"), "unexpected filter result: {}", result);
+
+    let result = db.execute_sql("SELECT id, -v FROM nums WHERE id = -(-2);").expect("SELECT failed");
+    assert!(result.contains("  2 |       -4"), "unexpected result: {}", result);
+
+    db.execute_sql("UPDATE nums SET v = v % 4 WHERE v & 1 = 0;").expect("UPDATE failed");
+    let result = db.execute_sql("SELECT id FROM nums WHERE v = 0 OR v = 2 ORDER BY id;").expect("SELECT failed");
+    assert!(result.contains("(2 rows)"), "UPDATE with % and & should change the even values: {}", result);
+}
+
+#[test]
+#[serial]
+fn test_operator_without_extension() {
+    let db = TestDb::new();
+
+    db.execute_sql("CREATE TABLE docs (id INT, tags STRING, PRIMARY KEY (id));").expect("CREATE TABLE failed");
+    db.execute_sql("CREATE INDEX docs_tags ON docs USING GIN (tags);").expect("CREATE INDEX failed");
+    db.execute_sql("INSERT INTO docs VALUES (1, 'a');").expect("INSERT failed");
+
+    // No operator class puts @> in the GIN index, so every row is checked
+    let result = db.execute_sql("EXPLAIN SELECT id FROM docs WHERE tags @> 'a';").expect("EXPLAIN failed");
+    assert!(result.contains("Seq Scan on docs") && result.contains("Filter: (tags @> 'a')"), "unexpected plan: {}", result);
+
+    let err = db.execute_sql("SELECT id FROM docs WHERE tags @> 'a';").expect_err("an operator no extension provides should be refused");
+    assert!(err.contains("Unsupported binary operator"), "unexpected error: {}", err);
+}
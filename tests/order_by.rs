@@ -0,0 +1,65 @@
+mod common;
+
+use common::TestDb;
+use serial_test::serial;
+
+/// Values of the first column of a psql result, in order
+fn first_column(result: &str) -> Vec<String> {
+    result.lines()
+        .skip(2)
+        .take_while(|line| !line.starts_with('('))
+        .map(|line| line.split('|').next().unwrap_or_default().trim().to_string())
+        .collect()
+}
+
+#[test]
+#[serial]
+fn test_order_by_keys() {
+    let db = TestDb::new();
+
+    db.execute_sql("CREATE TABLE scores (id INT, name STRING, score INT, PRIMARY KEY (id));")
+        .expect("CREATE TABLE failed");
+    db.execute_sql("INSERT INTO scores VALUES (1, 'b', 5), (2, 'a', NULL), (3, 'c', 5), (4, 'a', 9);")
+        .expect("INSERT failed");
+
+    let result = db.execute_sql("SELECT * FROM scores ORDER BY name, id DESC;").expect("ORDER BY failed");
+    assert_eq!(first_column(&result), ["4", "2", "1", "3"], "wrong order: {}", result);
+
+    // NULL sorts as larger than any value unless told otherwise
+    let result = db.execute_sql("SELECT * FROM scores ORDER BY score, id;").expect("ORDER BY failed");
+    assert_eq!(first_column(&result), ["1", "3", "4", "2"], "NULL should sort last: {}", result);
+    let result = db.execute_sql("SELECT * FROM scores ORDER BY score DESC, id;").expect("ORDER BY DESC failed");
+    assert_eq!(first_column(&result), ["2", "4", "1", "3"], "NULL should sort first: {}", result);
+    let result = db.execute_sql("SELECT * FROM scores ORDER BY score DESC NULLS LAST, id;")
+        .expect("ORDER BY NULLS LAST failed");
+    assert_eq!(first_column(&result), ["4", "1", "3", "2"], "wrong order: {}", result);
+
+    // Keys need not be selected, and run before LIMIT
+    let result = db.execute_sql("SELECT name FROM scores ORDER BY id DESC LIMIT 2;").expect("ORDER BY failed");
+    assert_eq!(first_column(&result), ["a", "c"], "wrong order: {}", result);
+}
+
+#[test]
+#[serial]
+fn test_order_by_select_list() {
+    let db = TestDb::new();
+
+    db.execute_sql("CREATE TABLE words (id INT, word STRING, PRIMARY KEY (id));")
+        .expect("CREATE TABLE failed");
+    db.execute_sql("INSERT INTO words VALUES (1, 'pear'), (2, 'apple'), (3, 'fig');")
+        .expect("INSERT failed");
+
+    let result = db.execute_sql("SELECT word, id AS n FROM words ORDER BY n DESC;").expect("ORDER BY alias failed");
+    assert_eq!(first_column(&result), ["fig", "apple", "pear"], "wrong order: {}", result);
+
+    let result = db.execute_sql("SELECT word, id FROM words ORDER BY 1;").expect("ORDER BY position failed");
+    assert_eq!(first_column(&result), ["apple", "fig", "pear"], "wrong order: {}", result);
+    let result = db.execute_sql("SELECT * FROM words ORDER BY 2 DESC;").expect("ORDER BY position under * failed");
+    assert_eq!(first_column(&result), ["1", "3", "2"], "wrong order: {}", result);
+
+    let result = db.execute_sql("SELECT word FROM words ORDER BY 2;");
+    assert!(result.is_err(), "position past the select list should be rejected");
+
+    let result = db.execute_sql("VALUES (3, 'x'), (1, 'y'), (2, 'z') ORDER BY 1;").expect("ORDER BY on VALUES failed");
+    assert_eq!(first_column(&result), ["1", "2", "3"], "wrong order: {}", result);
+}
@@ -1,6 +1,6 @@
 mod common;
 
-use common::TestDb;
+use common::{scalar, TestDb};
 use serial_test::serial;
 
 #[test]
@@ -163,4 +163,63 @@ fn test_large_dataset_persistence() {
         count_after.contains("100"),
         "should have 100 rows after restart"
     );
-}
\ No newline at end of file
+}
+#[test]
+#[serial]
+fn test_renames_persist() {
+    let mut db = TestDb::new();
+
+    db.execute_sql("CREATE TABLE before_rename (id INT, name STRING, PRIMARY KEY (id));")
+        .expect("CREATE TABLE failed");
+    db.execute_sql("INSERT INTO before_rename VALUES (1, 'one'), (2, 'two');")
+        .expect("INSERT failed");
+    db.execute_sql("ALTER TABLE before_rename RENAME TO after_rename;")
+        .expect("RENAME TABLE failed");
+    db.execute_sql("ALTER TABLE after_rename RENAME COLUMN name TO label;")
+        .expect("RENAME COLUMN failed");
+
+    // The old name is free again and must not clobber the renamed table's files
+    db.execute_sql("CREATE TABLE before_rename (id INT, PRIMARY KEY (id));")
+        .expect("CREATE TABLE with the old name failed");
+
+    db.restart().expect("restart failed");
+
+    let result = db
+        .execute_sql("SELECT * FROM after_rename WHERE id = 2;")
+        .expect("SELECT renamed table after restart failed");
+    assert!(result.contains("label"), "renamed column should survive restart: {}", result);
+    assert!(result.contains("two"), "data should follow the renamed table: {}", result);
+
+    let result = db.execute_sql("SELECT * FROM before_rename;")
+        .expect("SELECT new table after restart failed");
+    assert!(!result.contains("two"), "new table must start empty: {}", result);
+}
+
+#[test]
+#[serial]
+fn test_table_statistics_persist() {
+    let mut db = TestDb::new();
+
+    db.execute_sql("CREATE TABLE counted (id INT, name STRING, PRIMARY KEY (id));")
+        .expect("CREATE TABLE failed");
+    db.execute_sql("INSERT INTO counted VALUES (1, 'a'), (2, 'b'), (3, 'c');")
+        .expect("INSERT failed");
+    db.execute_sql("INSERT INTO counted VALUES (4, 'd');")
+        .expect("INSERT failed");
+
+    let count = |db: &TestDb, function: &str| scalar(&db
+        .execute_sql(&format!("SELECT {}('counted');", function))
+        .unwrap_or_else(|e| panic!("{} failed: {}", function, e)));
+
+    assert_eq!(count(&db, "flint_row_count"), 4);
+    assert_eq!(count(&db, "flint_approx_row_count"), 4);
+    assert!(count(&db, "flint_table_size") > 0, "table size should include its files");
+
+    // The estimate is kept in the catalog, so it is available without a scan after a restart
+    db.restart().expect("restart failed");
+    assert_eq!(count(&db, "flint_approx_row_count"), 4);
+    assert_eq!(count(&db, "flint_row_count"), 4);
+
+    let result = db.execute_sql("SELECT flint_row_count('missing');");
+    assert!(result.is_err(), "statistics on a missing table should fail");
+}
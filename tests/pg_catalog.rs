@@ -0,0 +1,93 @@
+mod common;
+
+use common::TestDb;
+use serial_test::serial;
+
+#[test]
+#[serial]
+fn test_pg_catalog_views() {
+    let db = TestDb::new();
+    db.execute_sql("CREATE TABLE items (id INT PRIMARY KEY, name VARCHAR(20), price FLOAT);")
+        .expect("CREATE TABLE failed");
+
+    let result = db.execute_sql("SELECT nspname FROM pg_catalog.pg_namespace;").expect("SELECT pg_namespace failed");
+    assert!(result.contains("pg_catalog") && result.contains("public"), "schemas missing: {}", result);
+
+    // Columns by table, with their types' object ids and modifiers
+    let result = db.execute_sql(
+        "SELECT a.attnum, a.attname, a.atttypid, a.atttypmod, a.attnotnull FROM pg_attribute a \
+         JOIN pg_class c ON a.attrelid = c.oid WHERE c.relname = 'items' ORDER BY a.attnum;",
+    ).expect("SELECT pg_attribute failed");
+    assert!(result.contains("1 | id      |       20 |        -1 | t"), "unexpected id column: {}", result);
+    assert!(result.contains("2 | name    |     1043 |        24 | f"), "unexpected name column: {}", result);
+    assert!(result.contains("3 | price   |      701 |        -1 | f"), "unexpected price column: {}", result);
+
+    let result = db.execute_sql("SELECT typname FROM pg_type WHERE oid = 1043;").expect("SELECT pg_type failed");
+    assert!(result.contains("varchar"), "varchar type missing: {}", result);
+    let result = db.execute_sql("SELECT relname FROM pg_class WHERE relnamespace = 2200;").expect("SELECT pg_class failed");
+    assert!(result.contains("items"), "table missing from public: {}", result);
+
+    let result = db.execute_sql("SELECT version();").expect("SELECT version() failed");
+    assert!(result.contains("PostgreSQL 16.6 (flint "), "unexpected version: {}", result);
+    let result = db.execute_sql("SELECT pg_catalog.version();").expect("SELECT pg_catalog.version() failed");
+    assert!(result.contains("PostgreSQL"), "unexpected version: {}", result);
+}
+
+#[test]
+#[serial]
+fn test_psql_list_relations() {
+    let db = TestDb::new();
+    db.execute_sql("CREATE TABLE items (id INT PRIMARY KEY, name STRING);").expect("CREATE TABLE failed");
+    db.execute_sql("CREATE TABLE notes (id SERIAL PRIMARY KEY, body STRING);").expect("CREATE TABLE failed");
+    db.execute_sql("COMMENT ON TABLE notes IS 'scratch pad';").expect("COMMENT failed");
+
+    let result = db.execute_sql("\\dt").expect("\\dt failed");
+    assert!(result.contains(" public | items | table | postgres"), "items missing: {}", result);
+    assert!(result.contains(" public | notes | table | postgres"), "notes missing: {}", result);
+    assert!(!result.contains("notes_id_seq"), "\\dt should only list tables: {}", result);
+
+    let result = db.execute_sql("\\d").expect("\\d failed");
+    assert!(result.contains("notes_id_seq | sequence"), "sequence missing: {}", result);
+
+    // Patterns are matched as psql's regular expressions
+    let result = db.execute_sql("\\dt+ no*").expect("\\dt+ failed");
+    assert!(result.contains("notes") && !result.contains("items"), "pattern not applied: {}", result);
+    assert!(result.contains("scratch pad"), "description missing: {}", result);
+
+    let result = db.execute_sql("\\dn").expect("\\dn failed");
+    assert!(result.contains(" public | postgres"), "public schema missing: {}", result);
+
+    // What tab completion asks for when completing a table name
+    let result = db.execute_sql(
+        "SELECT c.relname, NULL::pg_catalog.text FROM pg_catalog.pg_class c \
+         WHERE c.relkind IN ('r', 'S', 'v', 'm', 'f', 'p') AND (c.relname) LIKE 'it%' \
+         AND pg_catalog.pg_table_is_visible(c.oid) \
+         AND c.relnamespace <> (SELECT oid FROM pg_catalog.pg_namespace WHERE nspname = 'pg_catalog')\n\
+         UNION ALL\n\
+         SELECT NULL::pg_catalog.text, n.nspname FROM pg_catalog.pg_namespace n \
+         WHERE n.nspname LIKE 'it%' AND n.nspname NOT LIKE E'pg\\\\_%'\n\
+         LIMIT 1000",
+    ).expect("completion query failed");
+    assert!(result.contains(" items ") && result.contains("(1 row)"), "unexpected completions: {}", result);
+}
+
+#[test]
+#[serial]
+fn test_psql_describe_table() {
+    let db = TestDb::new();
+    db.execute_sql("CREATE TABLE items (id INT PRIMARY KEY, name VARCHAR(20), price FLOAT);")
+        .expect("CREATE TABLE failed");
+    db.execute_sql("COMMENT ON COLUMN items.price IS 'in cents';").expect("COMMENT failed");
+
+    let result = db.execute_sql("\\d items").expect("\\d items failed");
+    assert!(result.contains("Table \"public.items\""), "missing title: {}", result);
+    assert!(result.contains(" id     | bigint                |           | not null |"), "unexpected id: {}", result);
+    assert!(result.contains(" name   | character varying(20) |"), "unexpected name: {}", result);
+    assert!(result.contains(" price  | double precision      |"), "unexpected price: {}", result);
+
+    let result = db.execute_sql("\\d+ items").expect("\\d+ items failed");
+    assert!(result.contains("| in cents"), "column description missing: {}", result);
+
+    let result = db.execute_sql("\\d missing").expect("\\d missing failed");
+    assert!(!result.contains("Table"), "unexpected description: {}", result);
+}
@@ -0,0 +1,144 @@
+mod common;
+
+use common::TestDb;
+use serial_test::serial;
+
+#[test]
+#[serial]
+fn test_in_list() {
+    let db = TestDb::new();
+
+    db.execute_sql("CREATE TABLE orders (id INT, status STRING, PRIMARY KEY (id));")
+        .expect("CREATE TABLE failed");
+    db.execute_sql("INSERT INTO orders VALUES (1, 'a'), (2, 'b'), (3, 'c'), (4, 'd'), (5, NULL);")
+        .expect("INSERT failed");
+
+    // On the primary key: point lookups, each row once however often it is listed
+    let result = db.execute_sql("SELECT id FROM orders WHERE id IN (4, 2, 2, 99, NULL) ORDER BY id;")
+        .expect("SELECT failed");
+    assert!(result.contains("(2 rows)"), "unexpected IN result on the primary key: {}", result);
+    assert!(result.contains(" 2\n") && result.contains(" 4\n"), "unexpected IN result on the primary key: {}", result);
+
+    // On any other column
+    let result = db.execute_sql("SELECT id FROM orders WHERE status IN ('a', 'c', 'z') ORDER BY id;")
+        .expect("SELECT failed");
+    assert!(result.contains("(2 rows)"), "unexpected IN result: {}", result);
+    assert!(result.contains(" 1\n") && result.contains(" 3\n"), "unexpected IN result: {}", result);
+
+    let result = db.execute_sql("SELECT id FROM orders WHERE status NOT IN ('a', 'b');")
+        .expect("SELECT failed");
+    assert!(result.contains("(2 rows)"), "NULL status should not be NOT IN the list: {}", result);
+
+    // A NULL in the list means NOT IN is never true
+    let result = db.execute_sql("SELECT id FROM orders WHERE status NOT IN ('a', NULL);")
+        .expect("SELECT failed");
+    assert!(!result.contains("row"), "NOT IN with a NULL item should match nothing: {}", result);
+
+    let result = db.execute_sql("SELECT id FROM orders WHERE id IN (1, 2) AND status IN ('b');")
+        .expect("SELECT failed");
+    assert!(result.contains("(1 row)"), "IN combined with AND failed: {}", result);
+
+    db.execute_sql("DELETE FROM orders WHERE id IN (1, 3);").expect("DELETE failed");
+    let result = db.execute_sql("SELECT id FROM orders;").expect("SELECT failed");
+    assert!(result.contains("(3 rows)"), "DELETE ... IN should remove two rows: {}", result);
+}
+
+#[test]
+#[serial]
+fn test_in_list_on_string_primary_key() {
+    let db = TestDb::new();
+
+    db.execute_sql("CREATE TABLE users (name STRING, age INT, PRIMARY KEY (name));")
+        .expect("CREATE TABLE failed");
+    db.execute_sql("INSERT INTO users VALUES ('alice', 30), ('alicia', 31), ('bob', 40);")
+        .expect("INSERT failed");
+
+    // Keys sharing a prefix must not be confused
+    let result = db.execute_sql("SELECT age FROM users WHERE name IN ('alicia', 'bob') ORDER BY age;")
+        .expect("SELECT failed");
+    assert!(result.contains("(2 rows)"), "unexpected IN result: {}", result);
+    assert!(result.contains("31") && result.contains("40") && !result.contains("30"), "unexpected IN result: {}", result);
+}
+
+#[test]
+#[serial]
+fn test_between() {
+    let db = TestDb::new();
+
+    db.execute_sql("CREATE TABLE readings (id INT, value FLOAT, PRIMARY KEY (id));")
+        .expect("CREATE TABLE failed");
+    let rows: Vec<String> = (1..=50).map(|i| format!("({}, {}.5)", i, i)).collect();
+    db.execute_sql(&format!("INSERT INTO readings VALUES {}, (51, NULL);", rows.join(", ")))
+        .expect("INSERT failed");
+
+    // On the primary key: an index range scan, both bounds inclusive
+    let result = db.execute_sql("SELECT id FROM readings WHERE id BETWEEN 10 AND 14;")
+        .expect("SELECT failed");
+    assert!(result.contains("(5 rows)"), "unexpected BETWEEN result on the primary key: {}", result);
+    assert!(result.contains(" 10\n") && result.contains(" 14\n"), "bounds should be inclusive: {}", result);
+
+    let result = db.execute_sql("SELECT id FROM readings WHERE id BETWEEN 14 AND 10;").expect("SELECT failed");
+    assert!(!result.contains("row"), "a reversed range should match nothing: {}", result);
+
+    let result = db.execute_sql("SELECT id FROM readings WHERE id BETWEEN 9.5 AND 11.5;").expect("SELECT failed");
+    assert!(result.contains("(2 rows)"), "float bounds on an integer key failed: {}", result);
+
+    // On any other column, and negated
+    let result = db.execute_sql("SELECT id FROM readings WHERE value BETWEEN 2 AND 4;").expect("SELECT failed");
+    assert!(result.contains("(2 rows)"), "unexpected BETWEEN result: {}", result);
+
+    let result = db.execute_sql("SELECT id FROM readings WHERE value NOT BETWEEN 2 AND 49;").expect("SELECT failed");
+    assert!(result.contains("(3 rows)"), "NOT BETWEEN should skip the NULL value: {}", result);
+
+    let result = db.execute_sql("SELECT id BETWEEN 1 AND 3 FROM readings WHERE id = 2;").expect("SELECT failed");
+    assert!(result.contains(" t\n"), "BETWEEN should evaluate to a boolean: {}", result);
+
+    db.execute_sql("DELETE FROM readings WHERE id BETWEEN 1 AND 40;").expect("DELETE failed");
+    let result = db.execute_sql("SELECT id FROM readings;").expect("SELECT failed");
+    assert!(result.contains("(11 rows)"), "DELETE ... BETWEEN should remove 40 rows: {}", result);
+}
+
+#[test]
+#[serial]
+fn test_between_on_string_primary_key() {
+    let db = TestDb::new();
+
+    db.execute_sql("CREATE TABLE words (word STRING, PRIMARY KEY (word));").expect("CREATE TABLE failed");
+    db.execute_sql("INSERT INTO words VALUES ('apple'), ('applesauce'), ('banana'), ('cherry'), ('cherrypie');")
+        .expect("INSERT failed");
+
+    // Keys sharing a prefix with a bound must still be checked against it
+    let result = db.execute_sql("SELECT word FROM words WHERE word BETWEEN 'apples' AND 'cherry';")
+        .expect("SELECT failed");
+    assert!(result.contains("(3 rows)"), "unexpected BETWEEN result: {}", result);
+    assert!(!result.contains("cherrypie") && !result.contains(" apple\n"), "bounds should be exact: {}", result);
+}
+
+#[test]
+#[serial]
+fn test_is_null() {
+    let db = TestDb::new();
+
+    db.execute_sql("CREATE TABLE contacts (id INT, email STRING, phone STRING, PRIMARY KEY (id));")
+        .expect("CREATE TABLE failed");
+    db.execute_sql("INSERT INTO contacts VALUES (1, 'a@x.io', NULL), (2, NULL, '555'), (3, NULL, NULL), (4, 'd@x.io', '777');")
+        .expect("INSERT failed");
+
+    let result = db.execute_sql("SELECT id FROM contacts WHERE email IS NULL;").expect("SELECT failed");
+    assert!(result.contains("(2 rows)"), "unexpected IS NULL result: {}", result);
+
+    let result = db.execute_sql("SELECT id FROM contacts WHERE email IS NOT NULL AND phone IS NOT NULL;")
+        .expect("SELECT failed");
+    assert!(result.contains("(1 row)") && result.contains(" 4\n"), "unexpected IS NOT NULL result: {}", result);
+
+    let result = db.execute_sql("SELECT phone IS NULL, email IS NULL FROM contacts WHERE id = 1;").expect("SELECT failed");
+    assert!(result.contains(" t        | f\n"), "IS NULL should evaluate to a boolean: {}", result);
+
+    db.execute_sql("UPDATE contacts SET phone = 'none' WHERE phone IS NULL;").expect("UPDATE failed");
+    let result = db.execute_sql("SELECT id FROM contacts WHERE phone = 'none';").expect("SELECT failed");
+    assert!(result.contains("(2 rows)"), "UPDATE ... IS NULL should change two rows: {}", result);
+
+    db.execute_sql("DELETE FROM contacts WHERE email IS NULL;").expect("DELETE failed");
+    let result = db.execute_sql("SELECT id FROM contacts;").expect("SELECT failed");
+    assert!(result.contains("(2 rows)"), "DELETE ... IS NULL should remove two rows: {}", result);
+}
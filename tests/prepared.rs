@@ -0,0 +1,65 @@
+mod common;
+
+use common::TestDb;
+use serial_test::serial;
+
+#[test]
+#[serial]
+fn test_prepare_execute_deallocate() {
+    let db = TestDb::new();
+
+    db.execute_sql("CREATE TABLE items (id INT, name STRING, PRIMARY KEY (id));").expect("CREATE TABLE failed");
+
+    // Prepared statements belong to the connection, so each check runs in one query
+    let result = db.execute_sql(
+        "PREPARE add_item (int, text) AS INSERT INTO items VALUES ($1, $2); \
+         EXECUTE add_item(1, 'one'); EXECUTE add_item(2, 'two'); EXECUTE add_item(1 + 2, NULL); \
+         PREPARE find AS SELECT name FROM items WHERE id = $1; \
+         EXECUTE find(2);",
+    ).expect("PREPARE/EXECUTE failed");
+    assert!(result.contains("two") && result.contains("(1 row)"), "unexpected EXECUTE result: {}", result);
+    let result = db.execute_sql("SELECT id FROM items;").expect("SELECT failed");
+    assert!(result.contains("(3 rows)"), "prepared inserts should have run: {}", result);
+
+    let err = db.execute_sql("PREPARE find AS SELECT name FROM items WHERE id = $1; EXECUTE find(1, 2);")
+        .expect_err("EXECUTE with the wrong argument count should fail");
+    assert!(err.contains("expected 1, got 2"), "unexpected error: {}", err);
+
+    let err = db.execute_sql("PREPARE find AS SELECT 1; PREPARE find AS SELECT 2;")
+        .expect_err("preparing a name twice should fail");
+    assert!(err.contains("already exists"), "unexpected error: {}", err);
+
+    let err = db.execute_sql("PREPARE find AS SELECT 1; DEALLOCATE find; EXECUTE find;")
+        .expect_err("a deallocated statement should be gone");
+    assert!(err.contains("does not exist"), "unexpected error: {}", err);
+
+    let err = db.execute_sql("PREPARE a AS SELECT 1; PREPARE b AS SELECT 2; DEALLOCATE ALL; EXECUTE b;")
+        .expect_err("DEALLOCATE ALL should remove every statement");
+    assert!(err.contains("does not exist"), "unexpected error: {}", err);
+
+    let err = db.execute_sql("EXECUTE find(1);").expect_err("statements should not outlive their connection");
+    assert!(err.contains("does not exist"), "unexpected error: {}", err);
+}
+
+#[test]
+#[serial]
+fn test_prepared_statement_limits() {
+    let mut db = TestDb::new();
+    db.restart_with_args(&["--max-prepared-statements=2", "--prepared-statement-mem=200B"])
+        .expect("restart failed");
+
+    // The least recently executed statement makes room for a new one
+    let result = db.execute_sql(
+        "PREPARE a AS SELECT 'a'; PREPARE b AS SELECT 'b'; EXECUTE a; PREPARE c AS SELECT 'c'; \
+         EXECUTE a; EXECUTE c;",
+    ).expect("statements within the limit should stay prepared");
+    assert!(result.contains(" a\n") && result.contains(" c\n"), "unexpected result: {}", result);
+    let err = db.execute_sql("PREPARE a AS SELECT 'a'; PREPARE b AS SELECT 'b'; EXECUTE a; PREPARE c AS SELECT 'c'; EXECUTE b;")
+        .expect_err("the least recently used statement should have been evicted");
+    assert!(err.contains("\"b\" does not exist"), "unexpected error: {}", err);
+
+    let long_query = format!("SELECT '{}'", "x".repeat(300));
+    let err = db.execute_sql(&format!("PREPARE big AS {};", long_query))
+        .expect_err("a statement larger than the memory cap should be rejected");
+    assert!(err.contains("more than the 200 bytes"), "unexpected error: {}", err);
+}
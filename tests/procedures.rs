@@ -0,0 +1,45 @@
+mod common;
+
+use common::TestDb;
+use serial_test::serial;
+
+#[test]
+#[serial]
+fn test_procedures_persist() {
+    let mut db = TestDb::new();
+
+    db.execute_sql("CREATE TABLE log (id INT, name STRING, PRIMARY KEY (id));")
+        .expect("CREATE TABLE failed");
+    db.execute_sql(
+        "CREATE PROCEDURE add_entry(id INT, label STRING) LANGUAGE SQL AS BEGIN \
+         INSERT INTO log VALUES (id, label); \
+         INSERT INTO log VALUES ($1 + 100, 'copy'); \
+         END",
+    )
+    .expect("CREATE PROCEDURE failed");
+    db.execute_sql("CREATE PROCEDURE add_two() LANGUAGE SQL AS BEGIN CALL add_entry(2, 'two'); END")
+        .expect("CREATE PROCEDURE calling a procedure failed");
+
+    db.execute_sql("CALL add_entry(1, 'one');").expect("CALL failed");
+    let result = db.execute_sql("SELECT * FROM log WHERE id = 101;").expect("SELECT failed");
+    assert!(result.contains("copy"), "positional parameter should be bound: {}", result);
+
+    assert!(db.execute_sql("CALL add_entry('x', 'bad');").is_err(), "argument type should be checked");
+    assert!(db.execute_sql("CALL add_entry(3);").is_err(), "argument count should be checked");
+
+    // Procedures are part of the catalog and survive a restart
+    db.restart().expect("restart failed");
+
+    db.execute_sql("CALL add_two();").expect("nested CALL failed");
+    let result = db.execute_sql("SELECT * FROM log WHERE id = 2;").expect("SELECT failed");
+    assert!(result.contains("two"), "nested call should insert: {}", result);
+
+    db.execute_sql("CREATE PROCEDURE forever() LANGUAGE SQL AS BEGIN CALL forever(); END")
+        .expect("CREATE PROCEDURE failed");
+    assert!(db.execute_sql("CALL forever();").is_err(), "recursion should be stopped");
+
+    db.execute_sql("DROP PROCEDURE add_entry;").expect("DROP PROCEDURE failed");
+    db.restart().expect("restart failed");
+    assert!(db.execute_sql("CALL add_entry(4, 'four');").is_err(), "dropped procedure should stay dropped");
+    db.execute_sql("DROP PROCEDURE IF EXISTS add_entry;").expect("DROP PROCEDURE IF EXISTS failed");
+}
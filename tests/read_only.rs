@@ -0,0 +1,100 @@
+mod common;
+
+use common::TestDb;
+use serial_test::serial;
+
+#[test]
+#[serial]
+fn test_read_only_server() {
+    let mut db = TestDb::new();
+
+    db.execute_sql("CREATE TABLE notes (id INT, body STRING, PRIMARY KEY (id));")
+        .expect("CREATE TABLE failed");
+    db.execute_sql("INSERT INTO notes VALUES (1, 'kept');").expect("INSERT failed");
+
+    db.restart_with_args(&["--read-only"]).expect("restart failed");
+
+    let result = db.execute_sql("SELECT * FROM notes;").expect("reads should still work");
+    assert!(result.contains("kept"), "existing rows should be readable: {}", result);
+
+    for sql in [
+        "INSERT INTO notes VALUES (2, 'lost');",
+        "UPDATE notes SET body = 'changed';",
+        "DELETE FROM notes;",
+        "CREATE TABLE other (id INT, PRIMARY KEY (id));",
+        "VACUUM;",
+    ] {
+        let err = db.execute_sql(sql).expect_err("write on a read-only server should fail");
+        assert!(err.contains("read-only"), "unexpected error for {}: {}", sql, err);
+    }
+    let result = db.execute_sql("BEGIN READ WRITE;");
+    assert!(result.is_err(), "a read-only server should not start READ WRITE transactions");
+
+    db.restart_with_args(&[]).expect("restart failed");
+    db.execute_sql("INSERT INTO notes VALUES (2, 'added');").expect("writes should work again");
+    let result = db.execute_sql("SELECT * FROM notes;").expect("SELECT failed");
+    assert!(result.contains("(2 rows)"), "read-only mode should not have changed anything: {}", result);
+}
+
+#[test]
+#[serial]
+fn test_read_only_transaction() {
+    let db = TestDb::new();
+
+    db.execute_sql("CREATE TABLE notes (id INT, body STRING, PRIMARY KEY (id)); CREATE SEQUENCE ids;")
+        .expect("CREATE failed");
+
+    let err = db.execute_sql("BEGIN READ ONLY; INSERT INTO notes VALUES (1, 'lost');")
+        .expect_err("write in a READ ONLY transaction should fail");
+    assert!(err.contains("cannot execute INSERT in a read-only transaction"), "unexpected error: {}", err);
+
+    let err = db.execute_sql("BEGIN; SET TRANSACTION READ ONLY; SELECT nextval('ids');")
+        .expect_err("nextval in a read-only transaction should fail");
+    assert!(err.contains("nextval()"), "unexpected error: {}", err);
+
+    // The mode ends with the transaction, and does nothing outside one
+    db.execute_sql("BEGIN READ ONLY; COMMIT; INSERT INTO notes VALUES (1, 'a');")
+        .expect("write after a read-only transaction failed");
+    db.execute_sql("SET TRANSACTION READ ONLY; INSERT INTO notes VALUES (2, 'b');")
+        .expect("SET TRANSACTION outside a transaction block should do nothing");
+    db.execute_sql("BEGIN READ ONLY; SET TRANSACTION READ WRITE; INSERT INTO notes VALUES (3, 'c'); COMMIT;")
+        .expect("READ WRITE should lift the transaction's read-only mode");
+
+    let result = db.execute_sql("SELECT * FROM notes;").expect("SELECT failed");
+    assert!(result.contains("(3 rows)"), "expected three rows: {}", result);
+}
+
+#[test]
+#[serial]
+fn test_promotion() {
+    let mut db = TestDb::new();
+    db.execute_sql("CREATE TABLE notes (id INT, PRIMARY KEY (id));").expect("CREATE TABLE failed");
+
+    let result = db.execute_sql("SELECT flint_promote();");
+    assert!(result.is_err(), "a server that accepts writes cannot be promoted");
+
+    db.restart_with_args(&["--read-only"]).expect("restart failed");
+    db.execute_sql("INSERT INTO notes VALUES (1);").expect_err("read-only server should refuse writes");
+    let result = db.execute_sql("SELECT flint_promote();").expect("promotion failed");
+    assert!(result.contains(" t"), "promotion should return true: {}", result);
+    db.execute_sql("INSERT INTO notes VALUES (1);").expect("promoted server should accept writes");
+
+    // An orchestrator can promote by creating the trigger file instead
+    let trigger_file = std::env::temp_dir().join(format!("flint-promote-{}", std::process::id()));
+    let trigger_arg = format!("--promote-trigger-file={}", trigger_file.display());
+    db.restart_with_args(&["--read-only", &trigger_arg]).expect("restart failed");
+    db.execute_sql("INSERT INTO notes VALUES (2);").expect_err("read-only server should refuse writes");
+
+    std::fs::write(&trigger_file, "").expect("failed to create trigger file");
+    for _ in 0..50 {
+        if !trigger_file.exists() {
+            break;
+        }
+        std::thread::sleep(std::time::Duration::from_millis(100));
+    }
+    assert!(!trigger_file.exists(), "trigger file should be removed on promotion");
+    db.execute_sql("INSERT INTO notes VALUES (2);").expect("promoted server should accept writes");
+
+    let result = db.execute_sql("SELECT * FROM notes;").expect("SELECT failed");
+    assert!(result.contains("(2 rows)"), "both writes should have landed: {}", result);
+}
@@ -0,0 +1,192 @@
+mod common;
+
+use std::fs;
+use std::os::unix::fs::FileExt;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use common::TestDb;
+use serial_test::serial;
+
+/// Offset of the first data block in a table file: segment 0's header and
+/// its reserved block 0 come first
+const FIRST_DATA_BLOCK_OFFSET: u64 = 2 * 64 * 1024;
+
+/// Last recovery report in a JSON log
+fn last_report(log: &str) -> &str {
+    log.lines().rev().find(|line| line.contains("recovery report")).expect("recovery report should be logged")
+}
+
+#[test]
+#[serial]
+fn test_recovery_report() {
+    let mut db = TestDb::new();
+
+    let nanos = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_nanos();
+    let log_dir = std::env::temp_dir().join(format!("flint-recovery-test-{}", nanos));
+    let log_dir_arg = format!("--log-directory={}", log_dir.display());
+    let log_file = log_dir.join("flint.log");
+
+    db.execute_sql("CREATE TABLE items (id INT, name STRING, PRIMARY KEY (id));").expect("CREATE TABLE failed");
+    db.execute_sql("INSERT INTO items VALUES (1, 'one'), (2, 'two'), (3, 'three');").expect("INSERT failed");
+
+    // A restart with nothing to repair says so
+    db.restart_with_args(&[&log_dir_arg, "--log-format=json", "--log-rotation=never"]).expect("restart failed");
+    let log = fs::read_to_string(&log_file).expect("log file should exist");
+    let report = last_report(&log);
+    assert!(report.contains("\"clean\":true") && report.contains("\"tables_recovered\":1"), "unexpected report: {}", report);
+
+    // Break the header of the block holding every row
+    db.stop();
+    let table_file = fs::OpenOptions::new().write(true).open(db.data_dir().join("table_items.tbl"))
+        .expect("failed to open table file");
+    table_file.write_all_at(&[0xff; 16], FIRST_DATA_BLOCK_OFFSET).expect("failed to corrupt block");
+    drop(table_file);
+
+    db.restart().expect("restart after corruption failed");
+    let log = fs::read_to_string(&log_file).expect("log file should exist");
+    let report = last_report(&log);
+    assert!(report.contains("\"clean\":false"), "unexpected report: {}", report);
+    assert!(report.contains("\"blocks_quarantined\":1"), "unexpected report: {}", report);
+    assert!(report.contains("\"indexes_rebuilt\":\"[\\\"items\\\"]\""), "unexpected report: {}", report);
+    assert!(log.contains("quarantined corrupt block"), "the block should be named in the log: {}", log);
+    assert!(db.data_dir().join("quarantine/items.0.1.blk").exists(), "the block should be copied aside");
+
+    // The table is usable: its rows are gone and their keys free again
+    let result = db.execute_sql("SELECT count(*) FROM items;").expect("SELECT failed");
+    assert!(result.contains(" 0\n"), "quarantined rows should be gone: {}", result);
+    db.execute_sql("INSERT INTO items VALUES (1, 'again');").expect("INSERT after recovery failed");
+    let result = db.execute_sql("SELECT name FROM items WHERE id = 1;").expect("SELECT failed");
+    assert!(result.contains("again"), "wrong rows: {}", result);
+
+    let _ = fs::remove_dir_all(&log_dir);
+}
+
+#[test]
+#[serial]
+fn test_wal_replay() {
+    let mut db = TestDb::new();
+
+    let nanos = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_nanos();
+    let log_dir = std::env::temp_dir().join(format!("flint-replay-test-{}", nanos));
+    let log_dir_arg = format!("--log-directory={}", log_dir.display());
+    let log_file = log_dir.join("flint.log");
+
+    db.execute_sql("CREATE TABLE items (id INT, name STRING, PRIMARY KEY (id));").expect("CREATE TABLE failed");
+    // Enough rows that a few more don't move the saved row count, which
+    // would save the catalog and with it discard the log
+    let values: Vec<String> = (10..110).map(|id| format!("({}, 'spare')", id)).collect();
+    db.execute_sql(&format!("INSERT INTO items VALUES (1, 'one'), {};", values.join(", "))).expect("INSERT failed");
+    db.restart_with_args(&[&log_dir_arg, "--log-format=json", "--log-rotation=never"]).expect("restart failed");
+
+    // Keep the table as it is now, then change it
+    db.stop();
+    let table_path = db.data_dir().join("table_items.tbl");
+    let saved = fs::read(&table_path).expect("failed to read table file");
+    db.restart().expect("restart failed");
+    db.execute_sql("INSERT INTO items VALUES (2, 'two'), (3, 'three');").expect("INSERT failed");
+    db.execute_sql("UPDATE items SET name = 'uno' WHERE id = 1;").expect("UPDATE failed");
+    db.execute_sql("DELETE FROM items WHERE id = 3;").expect("DELETE failed");
+
+    // Lose every change that only made it to the log
+    db.stop();
+    fs::write(&table_path, &saved).expect("failed to restore table file");
+
+    db.restart().expect("restart after losing writes failed");
+    let log = fs::read_to_string(&log_file).expect("log file should exist");
+    let report = last_report(&log);
+    assert!(report.contains("\"wal_records_replayed\":3"), "unexpected report: {}", report);
+    assert!(report.contains("\"clean\":true"), "unexpected report: {}", report);
+
+    let result = db.execute_sql("SELECT id, name FROM items WHERE id < 10 ORDER BY id;").expect("SELECT failed");
+    assert!(result.contains("uno") && result.contains("two") && !result.contains("three") && !result.contains("one"),
+        "logged changes should be redone: {}", result);
+    let result = db.execute_sql("SELECT name FROM items WHERE id = 2;").expect("SELECT failed");
+    assert!(result.contains("two"), "the primary index should find redone rows: {}", result);
+    let result = db.execute_sql("SELECT count(*) FROM items;").expect("SELECT failed");
+    assert!(result.contains(" 102\n"), "wrong row count: {}", result);
+
+    let _ = fs::remove_dir_all(&log_dir);
+}
+
+#[test]
+#[serial]
+fn test_torn_wal_tail() {
+    let mut db = TestDb::new();
+
+    let nanos = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_nanos();
+    let log_dir = std::env::temp_dir().join(format!("flint-torn-wal-test-{}", nanos));
+    let log_dir_arg = format!("--log-directory={}", log_dir.display());
+    let log_file = log_dir.join("flint.log");
+
+    db.execute_sql("CREATE TABLE items (id INT, name STRING, PRIMARY KEY (id));").expect("CREATE TABLE failed");
+    let values: Vec<String> = (10..110).map(|id| format!("({}, 'spare')", id)).collect();
+    db.execute_sql(&format!("INSERT INTO items VALUES {};", values.join(", "))).expect("INSERT failed");
+    db.restart_with_args(&[&log_dir_arg, "--log-format=json", "--log-rotation=never"]).expect("restart failed");
+    db.execute_sql("INSERT INTO items VALUES (1, 'one');").expect("INSERT failed");
+
+    // A crash halfway through writing the next entry: its header made it to
+    // the disk, the rest of its block did not
+    db.stop();
+    let wal_path = db.data_dir().join("flint.wal");
+    let mut wal = fs::read(&wal_path).expect("failed to read WAL");
+    // The INSERT and its commit are the last entries logged
+    assert!(wal.len() >= 2 * 4096, "the INSERT should be logged: {} bytes", wal.len());
+    let mut torn = wal[wal.len() - 4096..].to_vec();
+    torn[40..56].fill(0xab);
+    wal.extend_from_slice(&torn);
+    fs::write(&wal_path, &wal).expect("failed to write WAL");
+
+    db.restart().expect("restart after torn write failed");
+    let log = fs::read_to_string(&log_file).expect("log file should exist");
+    let report = last_report(&log);
+    assert!(report.contains("\"clean\":false"), "unexpected report: {}", report);
+    assert!(report.contains("\"wal_end\":\"torn last entry\""), "unexpected report: {}", report);
+    assert!(report.contains("\"wal_bytes_ignored\":4096"), "unexpected report: {}", report);
+    assert!(report.contains("\"wal_records_replayed\":1"), "unexpected report: {}", report);
+
+    // The entry before the torn one still counts, and the log starts over
+    let result = db.execute_sql("SELECT name FROM items WHERE id = 1;").expect("SELECT failed");
+    assert!(result.contains("one"), "the logged INSERT should survive: {}", result);
+    db.stop();
+    assert_eq!(fs::metadata(&wal_path).expect("WAL should exist").len(), 0, "the WAL should be reset");
+
+    let _ = fs::remove_dir_all(&log_dir);
+}
+
+#[test]
+#[serial]
+fn test_corrupt_wal_entry_mid_log() {
+    let mut db = TestDb::new();
+
+    db.execute_sql("CREATE TABLE items (id INT, name STRING, PRIMARY KEY (id));").expect("CREATE TABLE failed");
+    let values: Vec<String> = (10..110).map(|id| format!("({}, 'spare')", id)).collect();
+    db.execute_sql(&format!("INSERT INTO items VALUES {};", values.join(", "))).expect("INSERT failed");
+    db.restart().expect("restart failed");
+    db.execute_sql("INSERT INTO items VALUES (1, 'one');").expect("INSERT failed");
+    db.execute_sql("INSERT INTO items VALUES (2, 'two');").expect("INSERT failed");
+
+    // Damage the first INSERT's entry, leaving the second's after it
+    db.stop();
+    let wal_path = db.data_dir().join("flint.wal");
+    let mut wal = fs::read(&wal_path).expect("failed to read WAL");
+    // Entries are a block each, their type following the header's magic
+    let first_insert = wal.chunks(4096).position(|entry| entry[4] == 1).expect("the INSERTs should be logged");
+    assert!(wal.len() > (first_insert + 2) * 4096, "the second INSERT should be logged after the first");
+    wal[first_insert * 4096 + 56] ^= 0xff;
+    fs::write(&wal_path, &wal).expect("failed to write WAL");
+
+    // Opening the database refuses to lose acknowledged writes, and leaves
+    // the log for the operator to look at
+    let snapshot = db.data_dir().join("items.snap");
+    let err = db.run_flint(&["snapshot", "create", snapshot.to_str().unwrap()])
+        .expect_err("opening a database with a corrupt WAL should fail");
+    assert!(err.contains("WAL is corrupt") && err.contains("--allow-lossy-recovery"), "unexpected error: {}", err);
+    assert_eq!(fs::read(&wal_path).expect("failed to read WAL"), wal, "the WAL should be left as it was");
+
+    // Unless the operator accepts losing everything from the damaged entry on
+    db.restart_with_args(&["--allow-lossy-recovery"]).expect("restart with lossy recovery failed");
+    let result = db.execute_sql("SELECT count(*) FROM items WHERE id < 10;").expect("SELECT failed");
+    assert!(result.contains(" 0\n"), "changes from the corrupt entry on should be lost: {}", result);
+    let result = db.execute_sql("SELECT count(*) FROM items;").expect("SELECT failed");
+    assert!(result.contains(" 100\n"), "wrong row count: {}", result);
+}
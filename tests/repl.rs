@@ -0,0 +1,43 @@
+mod common;
+
+use common::TestDb;
+use serial_test::serial;
+
+#[test]
+#[serial]
+fn test_repl() {
+    let mut db = TestDb::new();
+    db.execute_sql("CREATE TABLE notes (id INT, body STRING, PRIMARY KEY (id));").expect("CREATE TABLE failed");
+    db.execute_sql("INSERT INTO notes VALUES (1, 'from the server');").expect("INSERT failed");
+
+    // The server holds the data directory
+    let err = db.run_flint_with_input(&["repl"], "\\q\n").expect_err("the repl should not open a directory in use");
+    assert!(err.contains("in use"), "unexpected error: {}", err);
+
+    db.stop();
+    let input = "\
+INSERT INTO notes VALUES (2, 'typed; over');
+UPDATE notes
+   SET body = 'edited'
+ WHERE id = 1;
+SELECT id, body
+  FROM notes -- a comment; not the end
+ ORDER BY id;
+SELECT * FROM missing;
+\\dt
+\\q
+SELECT 'never run';
+";
+    let (stdout, stderr) = db.run_flint_with_input(&["repl"], input).expect("repl failed");
+    assert!(stdout.contains("UPDATE 1"), "missing command tag: {}", stdout);
+    assert!(stdout.contains(" id |    body\n----+-------------\n  1 | edited\n  2 | typed; over\n(2 rows)"), "unexpected result: {}", stdout);
+    assert!(stdout.contains(" table_name\n------------\n notes\n(1 row)"), "\\dt should list tables: {}", stdout);
+    assert!(!stdout.contains("never run"), "input after \\q should be ignored: {}", stdout);
+    assert!(stderr.contains("ERROR:  "), "errors should be reported: {}", stderr);
+    assert!(!stderr.contains("INFO"), "per-query logs should stay off the terminal: {}", stderr);
+
+    // What the repl wrote is there for the server
+    db.restart().expect("restart failed");
+    let result = db.execute_sql("SELECT body FROM notes WHERE id = 2;").expect("SELECT failed");
+    assert!(result.contains("typed; over"), "repl writes should persist: {}", result);
+}
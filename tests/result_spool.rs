@@ -0,0 +1,47 @@
+mod common;
+
+use std::fs;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use common::TestDb;
+use serial_test::serial;
+
+#[test]
+#[serial]
+fn test_large_results_are_spooled_to_disk() {
+    let mut db = TestDb::new();
+    let nanos = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_nanos();
+    let log_dir = std::env::temp_dir().join(format!("flint-spool-test-{}", nanos));
+    let log_dir_arg = format!("--log-directory={}", log_dir.display());
+    db.restart_with_args(&[&log_dir_arg, "--log-rotation=never", "--result-spool-size=1kB"]).expect("restart failed");
+
+    db.execute_sql("CREATE TABLE items (id INT, label STRING, PRIMARY KEY (id));").expect("CREATE TABLE failed");
+    let values: Vec<String> = (1..=1000).map(|id| format!("({}, 'item number {}')", id, id)).collect();
+    db.execute_sql(&format!("INSERT INTO items VALUES {};", values.join(", "))).expect("INSERT failed");
+
+    // A result within the spool size stays in memory
+    let result = db.execute_sql("SELECT label FROM items WHERE id = 7;").expect("SELECT failed");
+    assert!(result.contains("item number 7") && result.contains("(1 row)"), "unexpected rows: {}", result);
+    let log = fs::read_to_string(log_dir.join("flint.log")).expect("log file should exist");
+    assert!(!log.contains("spooling result to disk"), "a small result should not be spooled: {}", log);
+
+    // A larger one is streamed back from disk, whole and in order
+    let result = db.execute_sql("SELECT id, label FROM items ORDER BY id;").expect("SELECT failed");
+    assert!(result.contains("(1000 rows)"), "every row should be returned: {}", result);
+    let ids: Vec<i64> = result.lines()
+        .skip(2)
+        .take_while(|line| !line.starts_with('('))
+        .map(|line| line.split('|').next().unwrap().trim().parse().expect("id"))
+        .collect();
+    assert_eq!(ids, (1..=1000).collect::<Vec<_>>(), "rows out of order");
+    assert!(result.contains("item number 1000"), "unexpected last row: {}", result);
+    let log = fs::read_to_string(log_dir.join("flint.log")).expect("log file should exist");
+    assert!(log.contains("spooling result to disk"), "the spool should be logged: {}", log);
+
+    // Spool files don't outlive their response
+    let spool_dir = db.data_dir().join("tmp");
+    let leftovers = fs::read_dir(&spool_dir).map(|entries| entries.count()).unwrap_or(0);
+    assert_eq!(leftovers, 0, "spool files left in {}", spool_dir.display());
+
+    let _ = fs::remove_dir_all(&log_dir);
+}
@@ -0,0 +1,49 @@
+mod common;
+
+use common::TestDb;
+use serial_test::serial;
+
+#[test]
+#[serial]
+fn test_values_as_table_source() {
+    let db = TestDb::new();
+
+    let result = db.execute_sql("SELECT * FROM (VALUES (1, 'a'), (2, 'b')) AS t(id, name);")
+        .expect("SELECT from VALUES failed");
+    assert!(result.contains("id") && result.contains("name"), "alias should name the columns: {}", result);
+    assert!(result.contains("(2 rows)"), "expected both rows: {}", result);
+
+    let result = db.execute_sql("SELECT * FROM (VALUES (1, 'a'), (2, 'b'), (3, 'c')) AS t(id) WHERE id > 1;")
+        .expect("SELECT from VALUES with WHERE failed");
+    assert!(result.contains("column2"), "unaliased columns should be named columnN: {}", result);
+    assert!(result.contains("b") && result.contains("c") && result.contains("(2 rows)"), "wrong rows: {}", result);
+
+    let result = db.execute_sql("VALUES (1, 2.5), (3, 4);").expect("bare VALUES failed");
+    assert!(result.contains("column1") && result.contains("2.5"), "wrong result: {}", result);
+
+    let result = db.execute_sql("SELECT * FROM (VALUES (1), (2, 3)) AS t;");
+    assert!(result.is_err(), "rows of different lengths should be rejected");
+}
+
+#[test]
+#[serial]
+fn test_row_comparison() {
+    let db = TestDb::new();
+
+    db.execute_sql("CREATE TABLE pairs (tag STRING, a INT, b INT, PRIMARY KEY (tag));")
+        .expect("CREATE TABLE failed");
+    db.execute_sql("INSERT INTO pairs VALUES ('x', 1, 1), ('y', 1, 2), ('z', 2, 1), ('w', 3, 0);")
+        .expect("INSERT failed");
+
+    let result = db.execute_sql("SELECT * FROM pairs WHERE (a, b) = (1, 2);").expect("row = failed");
+    assert!(result.contains(" y ") && result.contains("(1 row)"), "wrong rows: {}", result);
+
+    let result = db.execute_sql("SELECT * FROM pairs WHERE (a, b) <> (1, 1);").expect("row <> failed");
+    assert!(!result.contains(" x ") && result.contains("(3 rows)"), "wrong rows: {}", result);
+
+    // Ordering compares field by field, like a sort on (a, b)
+    let result = db.execute_sql("SELECT * FROM pairs WHERE (a, b) > (1, 2);").expect("row > failed");
+    assert!(result.contains(" z ") && result.contains(" w ") && result.contains("(2 rows)"), "wrong rows: {}", result);
+    let result = db.execute_sql("SELECT * FROM pairs WHERE (a, b) <= (2, 1);").expect("row <= failed");
+    assert!(!result.contains(" w ") && result.contains("(3 rows)"), "wrong rows: {}", result);
+}
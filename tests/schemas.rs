@@ -0,0 +1,68 @@
+mod common;
+
+use common::TestDb;
+use serial_test::serial;
+
+#[test]
+#[serial]
+fn test_schema_qualified_tables() {
+    let mut db = TestDb::new();
+    db.execute_sql("CREATE SCHEMA analytics;").expect("CREATE SCHEMA failed");
+    let err = db.execute_sql("CREATE SCHEMA analytics;").unwrap_err();
+    assert!(err.contains("schema \"analytics\" already exists"), "unexpected error: {}", err);
+    db.execute_sql("CREATE SCHEMA IF NOT EXISTS analytics;").expect("CREATE SCHEMA IF NOT EXISTS failed");
+
+    // Tables of the same name in different schemas are different tables
+    db.execute_sql("CREATE TABLE analytics.events (id INT PRIMARY KEY, kind STRING);").expect("CREATE TABLE failed");
+    db.execute_sql("CREATE TABLE events (id INT PRIMARY KEY, note STRING);").expect("CREATE TABLE failed");
+    db.execute_sql("INSERT INTO analytics.events VALUES (1, 'click');").expect("INSERT failed");
+    db.execute_sql("INSERT INTO public.events VALUES (2, 'plain');").expect("INSERT failed");
+
+    let result = db.execute_sql("SELECT events.kind FROM analytics.events WHERE events.id = 1;").expect("SELECT failed");
+    assert!(result.contains("click"), "unexpected rows: {}", result);
+    let result = db.execute_sql("SELECT id, note FROM events;").expect("SELECT failed");
+    assert!(result.contains("  2 | plain") && !result.contains("click"), "unexpected rows: {}", result);
+
+    let err = db.execute_sql("SELECT * FROM nowhere.events;").unwrap_err();
+    assert!(err.contains("schema \"nowhere\" does not exist"), "unexpected error: {}", err);
+
+    let result = db.execute_sql(
+        "SELECT table_schema, table_name FROM information_schema.tables WHERE table_name = 'events';",
+    ).expect("SELECT from information_schema failed");
+    assert!(result.contains("analytics") && result.contains("public"), "unexpected tables: {}", result);
+
+    // Schemas and their tables are found again after a restart
+    db.restart().expect("restart failed");
+    let result = db.execute_sql("SELECT kind FROM analytics.events;").expect("SELECT after restart failed");
+    assert!(result.contains("click"), "rows lost in restart: {}", result);
+
+    let err = db.execute_sql("DROP SCHEMA analytics;").unwrap_err();
+    assert!(err.contains("other objects depend on it"), "unexpected error: {}", err);
+    db.execute_sql("CREATE SCHEMA scratch;").expect("CREATE SCHEMA failed");
+    db.execute_sql("DROP SCHEMA scratch;").expect("DROP SCHEMA failed");
+    db.execute_sql("DROP SCHEMA IF EXISTS scratch;").expect("DROP SCHEMA IF EXISTS failed");
+}
+
+#[test]
+#[serial]
+fn test_search_path() {
+    let db = TestDb::new();
+    db.execute_sql("CREATE SCHEMA analytics;").expect("CREATE SCHEMA failed");
+    db.execute_sql("CREATE TABLE analytics.events (id INT PRIMARY KEY, kind STRING);").expect("CREATE TABLE failed");
+    db.execute_sql("CREATE TABLE events (id INT PRIMARY KEY, note STRING);").expect("CREATE TABLE failed");
+    db.execute_sql("INSERT INTO analytics.events VALUES (1, 'click');").expect("INSERT failed");
+    db.execute_sql("INSERT INTO events VALUES (2, 'plain');").expect("INSERT failed");
+
+    // Unqualified names are looked up along the session's search path, and
+    // new tables go in its first schema
+    let result = db.execute_sql(
+        "SET search_path TO analytics, public; SELECT * FROM events; CREATE TABLE sessions (id INT PRIMARY KEY);",
+    ).expect("queries with a search path failed");
+    assert!(result.contains("click") && !result.contains("plain"), "unexpected rows: {}", result);
+    let result = db.execute_sql("SELECT id FROM analytics.sessions;");
+    assert!(result.is_ok(), "table not created in the first schema: {:?}", result);
+
+    // Other sessions keep the default search path
+    let result = db.execute_sql("SELECT * FROM events;").expect("SELECT failed");
+    assert!(result.contains("plain") && !result.contains("click"), "unexpected rows: {}", result);
+}
@@ -0,0 +1,78 @@
+mod common;
+
+use common::TestDb;
+use serial_test::serial;
+
+#[test]
+#[serial]
+fn test_set_and_show() {
+    let db = TestDb::new();
+
+    // What clients send as they connect
+    let result = db.execute_sql(
+        "SET application_name = 'loader'; SET client_encoding TO 'UTF8'; SET extra_float_digits = 3; \
+         SET DateStyle = ISO; SHOW application_name; SHOW extra_float_digits;",
+    ).expect("SET failed");
+    assert!(result.contains(" loader\n"), "application_name not set: {}", result);
+    assert!(result.contains(" 3\n"), "extra_float_digits not set: {}", result);
+
+    // Names are matched in any case, and SHOW names its column as Postgres does
+    let result = db.execute_sql("SET TIME ZONE 'Europe/Berlin'; SHOW timezone;").expect("SHOW failed");
+    assert!(result.contains("TimeZone") && result.contains("Europe/Berlin"), "unexpected time zone: {}", result);
+    let result = db.execute_sql("SHOW server_version;").expect("SHOW failed");
+    assert!(result.contains(" 16.6\n"), "unexpected server version: {}", result);
+
+    // Placeholders with a dot in their name can be set by any session
+    let result = db.execute_sql("SET myapp.tenant = 'acme'; SHOW myapp.tenant;").expect("SET failed");
+    assert!(result.contains(" acme\n"), "placeholder not set: {}", result);
+
+    // Each session starts from the defaults
+    let result = db.execute_sql("SHOW application_name;").expect("SHOW failed");
+    assert!(!result.contains("loader"), "setting leaked into another session: {}", result);
+
+    let result = db.execute_sql("SET application_name = 'loader'; SET workload_class = batch; SHOW ALL;")
+        .expect("SHOW ALL failed");
+    assert!(result.contains(" application_name ") && result.contains(" loader "), "unexpected settings: {}", result);
+    assert!(result.contains(" workload_class ") && result.contains(" batch "), "unexpected settings: {}", result);
+}
+
+#[test]
+#[serial]
+fn test_reset_and_set_local() {
+    let db = TestDb::new();
+
+    let result = db.execute_sql(
+        "SET application_name = 'loader'; RESET application_name; SHOW application_name;",
+    ).expect("RESET failed");
+    assert!(!result.contains("loader"), "RESET had no effect: {}", result);
+    let result = db.execute_sql(
+        "SET statement_timeout = '5s'; SET workload_class = batch; RESET ALL; SHOW statement_timeout; SHOW workload_class;",
+    ).expect("RESET ALL failed");
+    assert!(result.contains(" 0\n") && result.contains(" default\n"), "RESET ALL had no effect: {}", result);
+    let result = db.execute_sql("SET lock_timeout = 10; SET lock_timeout TO DEFAULT; SHOW lock_timeout;")
+        .expect("SET TO DEFAULT failed");
+    assert!(result.contains(" 0\n"), "DEFAULT not restored: {}", result);
+
+    // SET LOCAL lasts until the transaction block ends
+    let result = db.execute_sql(
+        "BEGIN; SET LOCAL lock_timeout = '2s'; SHOW lock_timeout; COMMIT; SHOW lock_timeout;",
+    ).expect("SET LOCAL failed");
+    let during = result.find(" 2s\n").expect("SET LOCAL not applied");
+    let after = result.rfind(" 0\n").expect("SET LOCAL outlived its transaction");
+    assert!(during < after, "unexpected results: {}", result);
+}
+
+#[test]
+#[serial]
+fn test_set_errors() {
+    let db = TestDb::new();
+
+    let err = db.execute_sql("SET no_such_parameter = 1;").unwrap_err();
+    assert!(err.contains("unrecognized configuration parameter \"no_such_parameter\""), "unexpected error: {}", err);
+    let err = db.execute_sql("SHOW no_such_parameter;").unwrap_err();
+    assert!(err.contains("unrecognized configuration parameter"), "unexpected error: {}", err);
+    let err = db.execute_sql("SET server_version = '9.6';").unwrap_err();
+    assert!(err.contains("parameter \"server_version\" cannot be changed"), "unexpected error: {}", err);
+    let err = db.execute_sql("SET client_encoding = 'LATIN1';").unwrap_err();
+    assert!(err.contains("invalid value for parameter \"client_encoding\""), "unexpected error: {}", err);
+}
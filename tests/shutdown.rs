@@ -0,0 +1,65 @@
+mod common;
+
+use std::process::{Command, Stdio};
+use std::thread;
+use std::time::Duration;
+
+use common::TestDb;
+use serial_test::serial;
+
+/// Send a signal to the server, as an init system or Ctrl-C would
+fn signal_server(signal: &str) {
+    Command::new("pkill")
+        .args([signal, "-x", "flint"])
+        .output()
+        .expect("failed to run pkill");
+}
+
+/// Whether a client can connect and run a query
+fn connects() -> bool {
+    Command::new("psql")
+        .args(["-h", "127.0.0.1", "-U", "postgres", "-d", "postgres", "-c", "SELECT 1;"])
+        .output()
+        .is_ok_and(|output| output.status.success())
+}
+
+#[test]
+#[serial]
+fn test_graceful_shutdown() {
+    let mut db = TestDb::new();
+    db.execute_sql("CREATE TABLE items (id SERIAL PRIMARY KEY, name STRING);").expect("CREATE TABLE failed");
+    db.execute_sql("INSERT INTO items (name) VALUES ('bolt'), ('nut');").expect("INSERT failed");
+
+    // A query running when the signal arrives is let finish
+    let running = Command::new("psql")
+        .args(["-h", "127.0.0.1", "-U", "postgres", "-d", "postgres", "-c", "SELECT pg_sleep(1), 'finished';"])
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .expect("failed to spawn psql");
+    thread::sleep(Duration::from_millis(300));
+    signal_server("-TERM");
+    thread::sleep(Duration::from_millis(200));
+
+    // New connections are refused meanwhile
+    assert!(!connects(), "server still accepting connections");
+
+    let output = running.wait_with_output().expect("psql did not exit");
+    assert!(String::from_utf8_lossy(&output.stdout).contains("finished"),
+        "running query was cut off: {}", String::from_utf8_lossy(&output.stderr));
+
+    // After a clean shutdown nothing is lost, and sequences carry on from
+    // the last value handed out
+    thread::sleep(Duration::from_millis(500));
+    db.restart().expect("restart failed");
+    db.execute_sql("INSERT INTO items (name) VALUES ('washer');").expect("INSERT failed");
+    let result = db.execute_sql("SELECT id, name FROM items ORDER BY id;").expect("SELECT failed");
+    assert!(result.contains("  3 | washer"), "unexpected rows after restart: {}", result);
+
+    // SIGINT shuts down the same way
+    signal_server("-INT");
+    thread::sleep(Duration::from_millis(500));
+    db.restart().expect("restart failed");
+    let result = db.execute_sql("SELECT count(*) FROM items;").expect("SELECT failed");
+    assert!(result.contains(" 3\n"), "unexpected count after restart: {}", result);
+}
@@ -0,0 +1,35 @@
+mod common;
+
+use std::fs;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use common::TestDb;
+use serial_test::serial;
+
+#[test]
+#[serial]
+fn test_slow_query_log() {
+    let mut db = TestDb::new();
+
+    let nanos = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_nanos();
+    let log_dir = std::env::temp_dir().join(format!("flint-log-test-{}", nanos));
+    let log_dir_arg = format!("--log-directory={}", log_dir.display());
+    db.restart_with_args(&[&log_dir_arg, "--log-format=json", "--log-rotation=never", "--log-min-duration-statement=200"])
+        .expect("restart with a slow query threshold failed");
+
+    db.execute_sql("CREATE TABLE items (id INT PRIMARY KEY, name STRING);").expect("CREATE TABLE failed");
+    db.execute_sql("INSERT INTO items VALUES (1, 'bolt'), (2, 'nut'), (3, 'washer');").expect("INSERT failed");
+    db.execute_sql("SELECT id, name FROM items;").expect("SELECT failed");
+    db.execute_sql("SELECT pg_sleep(0.3), name FROM items WHERE id < 3;").expect("slow SELECT failed");
+
+    // Only the query past the threshold is logged, with what it returned
+    let log = fs::read_to_string(log_dir.join("flint.log")).expect("log file should exist");
+    let slow: Vec<&str> = log.lines().filter(|line| line.contains("slow query")).collect();
+    assert_eq!(slow.len(), 1, "unexpected slow queries: {:?}", slow);
+    assert!(slow[0].contains("pg_sleep(0.3)"), "query not logged: {}", slow[0]);
+    assert!(slow[0].contains("\"rows\":2"), "rows returned not logged: {}", slow[0]);
+    assert!(slow[0].contains("Scan on items"), "plan not logged: {}", slow[0]);
+    assert!(slow[0].contains("\"duration_ms\":"), "duration not logged: {}", slow[0]);
+
+    let _ = fs::remove_dir_all(&log_dir);
+}
@@ -0,0 +1,62 @@
+mod common;
+
+use common::{scalar, TestDb};
+use serial_test::serial;
+
+/// Blocks read by a full scan of a table
+fn scan_reads(db: &TestDb, table: &str) -> i64 {
+    let reads = || scalar(&db.execute_sql("SELECT reads FROM pg_stat_io WHERE object = 'relation';")
+        .expect("SELECT pg_stat_io failed"));
+    let before = reads();
+    db.execute_sql(&format!("SELECT count(*) FROM {};", table)).expect("SELECT count failed");
+    reads() - before
+}
+
+#[test]
+#[serial]
+fn test_fillfactor() {
+    let mut db = TestDb::new();
+
+    db.execute_sql("CREATE TABLE packed (id INT, body STRING, PRIMARY KEY (id));").expect("CREATE TABLE failed");
+    db.execute_sql("CREATE TABLE sparse (id INT, body STRING, PRIMARY KEY (id)) WITH (fillfactor = 20);")
+        .expect("CREATE TABLE WITH fillfactor failed");
+
+    // About 40 KB of rows: one block when packed, several at a fifth full
+    let body = "x".repeat(1000);
+    let values: Vec<String> = (1..=40).map(|id| format!("({}, '{}')", id, body)).collect();
+    for table in ["packed", "sparse"] {
+        db.execute_sql(&format!("INSERT INTO {} VALUES {};", table, values.join(", "))).expect("INSERT failed");
+    }
+    let packed_reads = scan_reads(&db, "packed");
+    let sparse_reads = scan_reads(&db, "sparse");
+    assert!(sparse_reads >= packed_reads + 3, "fillfactor 20 should spread rows over more blocks: {} vs {}", sparse_reads, packed_reads);
+
+    let result = db.execute_sql("SELECT relname, reloptions FROM pg_class WHERE relname = 'sparse';").expect("SELECT pg_class failed");
+    assert!(result.contains("{fillfactor=20}"), "missing reloptions: {}", result);
+
+    // Setting one option leaves the others as they were
+    db.execute_sql("ALTER TABLE sparse SET (ttl_column = id, ttl = '1 day');").expect("ALTER TABLE SET ttl failed");
+    db.execute_sql("ALTER TABLE sparse SET (fillfactor = 90);").expect("ALTER TABLE SET fillfactor failed");
+    db.restart().expect("restart failed");
+    let result = db.execute_sql("SELECT reloptions FROM pg_class WHERE relname = 'sparse';").expect("SELECT pg_class failed");
+    assert!(result.contains("{fillfactor=90,ttl_column=id,ttl=86400}"), "options lost: {}", result);
+
+    // VACUUM FULL repacks the table to its new fillfactor
+    db.execute_sql("VACUUM FULL sparse;").expect("VACUUM FULL failed");
+    let reads = scan_reads(&db, "sparse");
+    assert!(reads < sparse_reads, "VACUUM FULL should repack to fillfactor 90: {} vs {}", reads, sparse_reads);
+    let result = db.execute_sql("SELECT count(*) FROM sparse;").expect("SELECT count failed");
+    assert_eq!(scalar(&result), 40, "rows lost: {}", result);
+
+    for (sql, expected) in [
+        ("CREATE TABLE bad (id INT PRIMARY KEY) WITH (fillfactor = 5);", "out of bounds for option \"fillfactor\""),
+        ("CREATE TABLE bad (id INT PRIMARY KEY) WITH (fillfactor = 101);", "out of bounds for option \"fillfactor\""),
+        ("CREATE TABLE bad (id INT PRIMARY KEY) WITH (fillfactor = 'half');", "invalid value for integer option \"fillfactor\""),
+        ("ALTER TABLE packed SET (fillfactor = 1000);", "out of bounds for option \"fillfactor\""),
+        ("CREATE TABLE bad (id INT PRIMARY KEY) WITH (compression = 'zstd');", "Unsupported table option"),
+        ("CREATE TABLE bad (id INT PRIMARY KEY) WITH (autovacuum_enabled = false);", "Unsupported table option"),
+    ] {
+        let err = db.execute_sql(sql).expect_err(&format!("{} should fail", sql));
+        assert!(err.contains(expected), "unexpected error for {}: {}", sql, err);
+    }
+}
@@ -0,0 +1,98 @@
+mod common;
+
+use std::io::Write;
+use std::thread;
+use std::time::Duration;
+
+use common::TestDb;
+use serial_test::serial;
+
+#[test]
+#[serial]
+fn test_temp_table_lives_for_its_session() {
+    let db = TestDb::new();
+    db.execute_sql("CREATE TABLE notes (id INT PRIMARY KEY, body STRING);").expect("CREATE TABLE failed");
+    db.execute_sql("INSERT INTO notes VALUES (1, 'permanent');").expect("INSERT failed");
+
+    // Within its session a temporary table hides the permanent one of the same name
+    let result = db.execute_sql("\
+        CREATE TEMP TABLE notes (id INT PRIMARY KEY, body STRING);
+        INSERT INTO notes VALUES (1, 'temporary'), (2, 'also temporary');
+        UPDATE notes SET body = 'edited' WHERE notes.id = 2;
+        SELECT body FROM notes ORDER BY id;")
+        .expect("temporary table session failed");
+    assert!(result.contains("temporary") && result.contains("edited"), "unexpected result: {}", result);
+    assert!(!result.contains("permanent"), "the temporary table should hide the permanent one: {}", result);
+
+    // Other sessions, and this one once it reconnects, see the permanent table
+    let result = db.execute_sql("SELECT body FROM notes;").expect("SELECT failed");
+    assert!(result.contains("permanent") && result.contains("(1 row)"), "unexpected result: {}", result);
+
+    let result = db.execute_sql("\
+        CREATE TEMPORARY TABLE scratch (id SERIAL PRIMARY KEY, n INT);
+        INSERT INTO scratch (n) VALUES (10), (20);
+        SELECT table_schema, table_name, table_type FROM information_schema.tables WHERE table_name = 'scratch';")
+        .expect("temporary table session failed");
+    assert!(result.contains("pg_temp_") && result.contains("LOCAL TEMPORARY"), "unexpected result: {}", result);
+    let err = db.execute_sql("SELECT * FROM scratch;").expect_err("a closed session's temporary table should be gone");
+    assert!(err.contains("scratch"), "unexpected error: {}", err);
+
+    // Temporary tables can be dropped and can reference each other; only
+    // temporary tables can be dropped
+    let result = db.execute_sql("\
+        CREATE TEMP TABLE parents (id INT PRIMARY KEY);
+        CREATE TEMP TABLE children (id INT PRIMARY KEY, parent INT REFERENCES parents);
+        INSERT INTO parents VALUES (1);
+        INSERT INTO children VALUES (1, 1);
+        DROP TABLE children;
+        DROP TABLE pg_temp.parents;
+        CREATE TEMP TABLE parents (id INT PRIMARY KEY);
+        SELECT count(*) FROM parents;")
+        .expect("temporary table session failed");
+    assert!(result.contains(" 0\n"), "a recreated temporary table should be empty: {}", result);
+    let err = db.execute_sql("DROP TABLE notes;").expect_err("dropping a permanent table should fail");
+    assert!(err.contains("only supported for temporary tables"), "unexpected error: {}", err);
+    let err = db.execute_sql("CREATE TEMP TABLE t (id INT PRIMARY KEY) ON COMMIT DROP;")
+        .expect_err("ON COMMIT DROP should be rejected");
+    assert!(err.contains("ON COMMIT PRESERVE ROWS"), "unexpected error: {}", err);
+}
+
+#[test]
+#[serial]
+fn test_temp_tables_are_not_kept() {
+    let mut db = TestDb::new();
+    let temp_dir = db.data_dir().join("pg_temp");
+
+    let mut session = db.open_session();
+    let mut stdin = session.stdin.take().expect("psql stdin");
+    stdin.write_all(b"CREATE TEMP TABLE work (id INT PRIMARY KEY);\nINSERT INTO work VALUES (1), (2);\n")
+        .expect("write to psql failed");
+    stdin.flush().expect("flush to psql failed");
+    thread::sleep(Duration::from_millis(500));
+
+    // Only its own session sees a temporary table
+    let err = db.execute_sql("SELECT * FROM work;").expect_err("another session should not see the table");
+    assert!(err.contains("work"), "unexpected error: {}", err);
+    assert!(temp_dir.read_dir().is_ok_and(|mut entries| entries.next().is_some()), "temporary table files should be in pg_temp");
+
+    // Its files go when the session does
+    drop(stdin);
+    session.wait().expect("psql did not exit");
+    thread::sleep(Duration::from_millis(200));
+    assert!(temp_dir.read_dir().is_ok_and(|mut entries| entries.next().is_none()), "temporary table files should be removed");
+
+    // A server killed with a session open forgets its temporary tables
+    let mut session = db.open_session();
+    let mut stdin = session.stdin.take().expect("psql stdin");
+    stdin.write_all(b"CREATE TEMP TABLE work (id INT PRIMARY KEY);\n").expect("write to psql failed");
+    stdin.flush().expect("flush to psql failed");
+    thread::sleep(Duration::from_millis(500));
+    db.execute_sql("CREATE TABLE kept (id INT PRIMARY KEY);").expect("CREATE TABLE failed");
+    db.restart().expect("restart failed");
+    drop(stdin);
+    let _ = session.wait();
+
+    assert!(!temp_dir.exists(), "leftover temporary table files should be removed at startup");
+    let result = db.execute_sql("SELECT table_name FROM information_schema.tables;").expect("SELECT tables failed");
+    assert!(result.contains("kept") && !result.contains("work"), "unexpected tables: {}", result);
+}
@@ -0,0 +1,49 @@
+mod common;
+
+use std::process::Command;
+
+use common::TestDb;
+use serial_test::serial;
+
+/// Run `sql` with psql connecting in the given sslmode, returning its stdout
+/// or, if it fails, its stderr
+fn psql_with_sslmode(sslmode: &str, sql: &str) -> Result<String, String> {
+    let output = Command::new("psql")
+        .args(["-h", "127.0.0.1", "-U", "postgres", "-d", "postgres", "-c", sql])
+        .env("PGSSLMODE", sslmode)
+        .output()
+        .map_err(|e| format!("failed to execute psql: {}", e))?;
+    if !output.status.success() {
+        return Err(String::from_utf8_lossy(&output.stderr).to_string());
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).to_string())
+}
+
+#[test]
+#[serial]
+fn test_tls_connections() {
+    let mut db = TestDb::new();
+
+    // Without a certificate, clients that require TLS are turned away
+    let err = psql_with_sslmode("require", "SELECT 1;").unwrap_err();
+    assert!(err.contains("server does not support SSL"), "unexpected error: {}", err);
+
+    let status = Command::new("openssl")
+        .args(["req", "-x509", "-newkey", "rsa:2048", "-nodes", "-keyout", "key.pem", "-out", "cert.pem",
+            "-subj", "/CN=localhost", "-days", "1"])
+        .current_dir(db.data_dir())
+        .output()
+        .expect("failed to run openssl")
+        .status;
+    assert!(status.success(), "openssl could not make a certificate");
+    db.restart_with_args(&["--tls-cert=cert.pem", "--tls-key=key.pem"]).expect("restart with TLS failed");
+
+    let result = psql_with_sslmode("require", "\\conninfo").expect("TLS connection failed");
+    assert!(result.contains("SSL connection"), "connection not encrypted: {}", result);
+    db.execute_sql("CREATE TABLE items (id INT PRIMARY KEY, name STRING);").expect("CREATE TABLE failed");
+    psql_with_sslmode("require", "INSERT INTO items VALUES (1, 'bolt');").expect("INSERT over TLS failed");
+
+    // Clients that don't ask for TLS can still connect
+    let result = psql_with_sslmode("disable", "SELECT name FROM items;").expect("plain connection failed");
+    assert!(result.contains("bolt"), "unexpected result: {}", result);
+}
@@ -0,0 +1,240 @@
+mod common;
+
+use std::io::Write;
+use std::process::Command;
+use std::thread;
+use std::time::Duration;
+
+use common::TestDb;
+use serial_test::serial;
+
+fn create_accounts(db: &TestDb) {
+    db.execute_sql("CREATE TABLE accounts (id INT, owner STRING, balance INT, PRIMARY KEY (id));").expect("CREATE TABLE failed");
+    db.execute_sql("CREATE INDEX accounts_owner ON accounts (owner);").expect("CREATE INDEX failed");
+    db.execute_sql("INSERT INTO accounts VALUES (1, 'ann', 100), (2, 'bob', 50);").expect("INSERT failed");
+}
+
+#[test]
+#[serial]
+fn test_rollback() {
+    let db = TestDb::new();
+    create_accounts(&db);
+
+    let result = db.execute_sql(
+        "BEGIN; INSERT INTO accounts VALUES (3, 'cy', 10); \
+         UPDATE accounts SET owner = 'ann2', balance = balance - 30 WHERE id = 1; \
+         DELETE FROM accounts WHERE id = 2; \
+         SELECT * FROM accounts ORDER BY id; ROLLBACK;",
+    ).expect("transaction block failed");
+    assert!(result.contains("cy") && result.contains("ann2") && !result.contains("bob"), "the block should see its own writes: {}", result);
+
+    let result = db.execute_sql("SELECT * FROM accounts ORDER BY id;").expect("SELECT failed");
+    assert!(result.contains("(2 rows)") && result.contains("bob") && result.contains(" 100"), "rows should be as before the block: {}", result);
+    assert!(!result.contains("cy") && !result.contains("ann2"), "rolled back rows should be gone: {}", result);
+
+    // Index entries are put back as they were
+    let result = db.execute_sql("SELECT balance FROM accounts WHERE id = 2;").expect("SELECT failed");
+    assert!(result.contains(" 50"), "primary key lookup should find the undeleted row: {}", result);
+    let result = db.execute_sql("SELECT balance FROM accounts WHERE id = 1;").expect("SELECT failed");
+    assert!(result.contains(" 100"), "primary key lookup should find the old version: {}", result);
+    let result = db.execute_sql("SELECT balance FROM accounts WHERE owner = 'ann';").expect("SELECT failed");
+    assert!(result.contains(" 100"), "secondary index lookup should find the old version: {}", result);
+    let result = db.execute_sql("SELECT count(*) FROM accounts WHERE owner = 'ann2';").expect("SELECT failed");
+    assert!(result.contains(" 0\n"), "secondary index should not find the new version: {}", result);
+    db.execute_sql("INSERT INTO accounts VALUES (3, 'cy', 10);").expect("key of a rolled back row should be free");
+
+    // A failed statement rolls back the whole block, which then ignores
+    // everything until it ends
+    let mut session = db.open_session();
+    let mut stdin = session.stdin.take().expect("psql stdin");
+    stdin.write_all(
+        b"BEGIN;\nINSERT INTO accounts VALUES (4, 'dee', 1);\nINSERT INTO accounts VALUES (1, 'dup', 0);\n\
+          SELECT * FROM accounts;\nCOMMIT;\n",
+    ).expect("write to psql failed");
+    drop(stdin);
+    let output = session.wait_with_output().expect("psql did not exit");
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("current transaction is aborted"), "statements after the failure should be refused: {}", stderr);
+    let result = db.execute_sql("SELECT count(*) FROM accounts WHERE id = 4;").expect("SELECT failed");
+    assert!(result.contains(" 0\n"), "writes of a failed block should be rolled back: {}", result);
+
+    // Closing the connection rolls back its open block
+    db.execute_sql("BEGIN; DELETE FROM accounts;").expect("DELETE failed");
+    let result = db.execute_sql("SELECT * FROM accounts;").expect("SELECT failed");
+    assert!(result.contains("(3 rows)"), "an unfinished block should be rolled back: {}", result);
+}
+
+#[test]
+#[serial]
+fn test_commit_isolation() {
+    let mut db = TestDb::new();
+    create_accounts(&db);
+
+    let mut writer = db.open_session();
+    let mut stdin = writer.stdin.take().expect("psql stdin");
+    stdin.write_all(b"BEGIN;\nINSERT INTO accounts VALUES (3, 'cy', 10);\nUPDATE accounts SET balance = 0 WHERE id = 1;\n")
+        .expect("write to psql failed");
+    stdin.flush().expect("flush to psql failed");
+    thread::sleep(Duration::from_millis(500));
+
+    // Other sessions see the rows as they were, through indexes too
+    let result = db.execute_sql("SELECT * FROM accounts ORDER BY id;").expect("SELECT failed");
+    assert!(result.contains("(2 rows)") && result.contains(" 100") && !result.contains("cy"), "uncommitted writes should be invisible: {}", result);
+    let result = db.execute_sql("SELECT balance FROM accounts WHERE id = 1;").expect("SELECT failed");
+    assert!(result.contains(" 100"), "primary key lookup should find the committed version: {}", result);
+    let result = db.execute_sql("SELECT balance FROM accounts WHERE owner = 'ann';").expect("SELECT failed");
+    assert!(result.contains(" 100"), "secondary index lookup should find the committed version: {}", result);
+
+    // Writes of other sessions wait for the transaction to end
+    let waiting = thread::spawn(|| {
+        Command::new("psql")
+            .args(["-h", "127.0.0.1", "-U", "postgres", "-d", "postgres", "-c", "UPDATE accounts SET balance = balance + 1 WHERE id = 1;"])
+            .output()
+            .expect("failed to execute psql")
+    });
+    thread::sleep(Duration::from_millis(500));
+    assert!(!waiting.is_finished(), "a write should wait for the open transaction");
+
+    stdin.write_all(b"COMMIT;\n").expect("write to psql failed");
+    drop(stdin);
+    writer.wait().expect("psql did not exit");
+    let output = waiting.join().expect("waiting write panicked");
+    assert!(output.status.success(), "waiting write failed: {}", String::from_utf8_lossy(&output.stderr));
+
+    let result = db.execute_sql("SELECT * FROM accounts ORDER BY id;").expect("SELECT failed");
+    assert!(result.contains("(3 rows)") && result.contains("cy") && result.contains(" 1\n"), "committed writes should be visible: {}", result);
+
+    // Committed writes survive a crash, and ones still running don't
+    let mut writer = db.open_session();
+    let mut stdin = writer.stdin.take().expect("psql stdin");
+    stdin.write_all(b"BEGIN;\nINSERT INTO accounts VALUES (4, 'dee', 1);\nDELETE FROM accounts WHERE id = 2;\n")
+        .expect("write to psql failed");
+    stdin.flush().expect("flush to psql failed");
+    thread::sleep(Duration::from_millis(500));
+    db.restart().expect("restart failed");
+    drop(stdin);
+    let _ = writer.wait();
+
+    let result = db.execute_sql("SELECT * FROM accounts ORDER BY id;").expect("SELECT failed");
+    assert!(result.contains("(3 rows)") && result.contains("cy") && result.contains("bob"), "wrong rows after restart: {}", result);
+    assert!(!result.contains("dee"), "uncommitted rows should not survive a crash: {}", result);
+    let result = db.execute_sql("SELECT balance FROM accounts WHERE id = 2;").expect("SELECT failed");
+    assert!(result.contains(" 50"), "an uncommitted delete should not survive a crash: {}", result);
+}
+
+#[test]
+#[serial]
+fn test_lock_waits_give_up_their_place() {
+    let mut db = TestDb::new();
+    db.restart_with_args(&["--max-interactive-queries=2"]).expect("restart failed");
+    create_accounts(&db);
+
+    let mut writer = db.open_session();
+    let mut stdin = writer.stdin.take().expect("psql stdin");
+    stdin.write_all(b"BEGIN;\nINSERT INTO accounts VALUES (3, 'cy', 10);\n").expect("write to psql failed");
+    stdin.flush().expect("flush to psql failed");
+    thread::sleep(Duration::from_millis(500));
+
+    // Writes waiting for the open transaction fill every interactive place
+    let insert = |id: i32| thread::spawn(move || {
+        Command::new("psql")
+            .args(["-h", "127.0.0.1", "-U", "postgres", "-d", "postgres", "-c", &format!("INSERT INTO accounts VALUES ({}, 'w', 0);", id)])
+            .output()
+            .expect("failed to execute psql")
+    });
+    let waiting = [insert(4), insert(5)];
+    thread::sleep(Duration::from_millis(500));
+    assert!(waiting.iter().all(|write| !write.is_finished()), "writes should wait for the open transaction");
+
+    // They give their places up while they wait, so other queries still run
+    let select = thread::spawn(|| Command::new("psql")
+        .args(["-h", "127.0.0.1", "-U", "postgres", "-d", "postgres", "-c", "SELECT 1;"])
+        .output()
+        .expect("failed to execute psql"));
+    thread::sleep(Duration::from_millis(1000));
+    assert!(select.is_finished(), "a query should not wait behind writes waiting for a lock");
+
+    // A wait past the session's lock_timeout fails
+    let err = db.execute_sql("SET lock_timeout = '200ms'; INSERT INTO accounts VALUES (6, 'x', 0);")
+        .expect_err("a wait past lock_timeout should fail");
+    assert!(err.contains("canceling statement due to lock timeout"), "unexpected error: {}", err);
+    let err = db.execute_sql("SET lock_timeout = 'soon';").expect_err("an invalid lock_timeout should be refused");
+    assert!(err.contains("invalid value for parameter \"lock_timeout\""), "unexpected error: {}", err);
+
+    // The transaction holding the lock can still end, and the writes go on
+    stdin.write_all(b"COMMIT;\n").expect("write to psql failed");
+    drop(stdin);
+    let output = writer.wait_with_output().expect("psql did not exit");
+    assert!(output.status.success(), "COMMIT failed: {}", String::from_utf8_lossy(&output.stderr));
+    for write in waiting {
+        let output = write.join().expect("waiting write panicked");
+        assert!(output.status.success(), "waiting write failed: {}", String::from_utf8_lossy(&output.stderr));
+    }
+    let result = db.execute_sql("SELECT count(*) FROM accounts;").expect("SELECT failed");
+    assert!(result.contains(" 5\n"), "every write should have been made: {}", result);
+}
+
+#[test]
+#[serial]
+fn test_index_lookups_around_open_writes() {
+    let db = TestDb::new();
+    create_accounts(&db);
+
+    let mut writer = db.open_session();
+    let mut stdin = writer.stdin.take().expect("psql stdin");
+    stdin.write_all(b"BEGIN;\nUPDATE accounts SET owner = 'ann2' WHERE id = 1;\nDELETE FROM accounts WHERE id = 2;\n")
+        .expect("write to psql failed");
+    stdin.flush().expect("flush to psql failed");
+    thread::sleep(Duration::from_millis(500));
+
+    // Index entries of rows another transaction deleted still lead to them
+    let result = db.execute_sql("SELECT balance FROM accounts WHERE id = 2;").expect("SELECT failed");
+    assert!(result.contains(" 50"), "primary key lookup should find the uncommitted delete's row: {}", result);
+    let result = db.execute_sql("SELECT balance FROM accounts WHERE owner = 'bob';").expect("SELECT failed");
+    assert!(result.contains(" 50"), "secondary index lookup should find the uncommitted delete's row: {}", result);
+    let result = db.execute_sql("SELECT count(*) FROM accounts WHERE id >= 1 AND id <= 2;").expect("SELECT failed");
+    assert!(result.contains(" 2\n"), "range scan should find each row once: {}", result);
+    let result = db.execute_sql("SELECT count(*) FROM accounts WHERE owner = 'ann2';").expect("SELECT failed");
+    assert!(result.contains(" 0\n"), "secondary index should not find the uncommitted version: {}", result);
+
+    stdin.write_all(b"COMMIT;\n").expect("write to psql failed");
+    drop(stdin);
+    writer.wait().expect("psql did not exit");
+
+    // Once committed, the entries of the versions replaced are gone
+    let result = db.execute_sql("SELECT owner FROM accounts WHERE id = 1;").expect("SELECT failed");
+    assert!(result.contains("ann2") && result.contains("(1 row)"), "primary key lookup should find only the new version: {}", result);
+    let result = db.execute_sql("SELECT count(*) FROM accounts WHERE owner = 'ann' OR id = 2;").expect("SELECT failed");
+    assert!(result.contains(" 0\n"), "replaced and deleted rows should be gone: {}", result);
+    let result = db.execute_sql("SELECT count(*) FROM accounts WHERE owner = 'bob';").expect("SELECT failed");
+    assert!(result.contains(" 0\n"), "secondary index should not find the deleted row: {}", result);
+}
+
+#[test]
+#[serial]
+fn test_definitions_refused_in_blocks() {
+    let db = TestDb::new();
+    create_accounts(&db);
+
+    // ROLLBACK couldn't undo them, so they are refused rather than applied
+    let err = db.execute_sql("BEGIN; TRUNCATE accounts; ROLLBACK;").expect_err("TRUNCATE in a block should fail");
+    assert!(err.contains("TRUNCATE cannot run inside a transaction block"), "unexpected error: {}", err);
+    let result = db.execute_sql("SELECT count(*) FROM accounts;").expect("SELECT failed");
+    assert!(result.contains(" 2\n"), "rows should survive a refused TRUNCATE: {}", result);
+
+    let err = db.execute_sql("BEGIN; CREATE TABLE tx_new (id INT, PRIMARY KEY (id)); ROLLBACK;")
+        .expect_err("CREATE TABLE in a block should fail");
+    assert!(err.contains("CREATE TABLE cannot run inside a transaction block"), "unexpected error: {}", err);
+    db.execute_sql("SELECT * FROM tx_new;").expect_err("a refused CREATE TABLE should leave no table");
+
+    let err = db.execute_sql("BEGIN; INSERT INTO accounts VALUES (3, 'cy', 10); ALTER TABLE accounts RENAME TO accounts2; COMMIT;")
+        .expect_err("ALTER TABLE in a block should fail");
+    assert!(err.contains("ALTER TABLE cannot run inside a transaction block"), "unexpected error: {}", err);
+    let result = db.execute_sql("SELECT count(*) FROM accounts;").expect("a refused rename should keep the table's name");
+    assert!(result.contains(" 2\n"), "a refused statement should roll back its block: {}", result);
+
+    // Outside a block they still run
+    db.execute_sql("TRUNCATE accounts;").expect("TRUNCATE failed");
+    let result = db.execute_sql("SELECT count(*) FROM accounts;").expect("SELECT failed");
+    assert!(result.contains(" 0\n"), "TRUNCATE should empty the table: {}", result);
+}
@@ -0,0 +1,94 @@
+mod common;
+
+use common::TestDb;
+use serial_test::serial;
+use std::thread;
+use std::time::Duration;
+
+/// Long enough for the expiry worker, started with a one second interval, to
+/// have made a pass
+const EXPIRY_WAIT: Duration = Duration::from_millis(2500);
+
+/// Far enough in the future that no TTL expires it
+const FUTURE: i64 = 4102444800;
+
+#[test]
+#[serial]
+fn test_ttl_expires_old_rows() {
+    let mut db = TestDb::new();
+    db.restart_with_args(&["--ttl-check-interval=1"]).expect("restart failed");
+
+    db.execute_sql("CREATE TABLE events (id INT, ts INT, body STRING, PRIMARY KEY (id)) WITH (ttl_column = ts, ttl = '7 days');")
+        .expect("CREATE TABLE failed");
+    db.execute_sql(&format!(
+        "INSERT INTO events VALUES (1, 1000, 'old'), (2, {}, 'new'), (3, NULL, 'undated');",
+        FUTURE
+    )).expect("INSERT failed");
+
+    thread::sleep(EXPIRY_WAIT);
+    let result = db.execute_sql("SELECT id FROM events ORDER BY id;").expect("SELECT failed");
+    assert!(result.contains("(2 rows)"), "the old row should have expired: {}", result);
+    assert!(!result.contains(" 1\n"), "the old row should have expired: {}", result);
+
+    // The policy is kept in the catalog across restarts
+    db.restart_with_args(&["--ttl-check-interval=1"]).expect("restart failed");
+    db.execute_sql("INSERT INTO events VALUES (4, 2000, 'old');").expect("INSERT failed");
+    thread::sleep(EXPIRY_WAIT);
+    let result = db.execute_sql("SELECT id FROM events;").expect("SELECT failed");
+    assert!(result.contains("(2 rows)"), "the policy should survive a restart: {}", result);
+}
+
+#[test]
+#[serial]
+fn test_alter_table_ttl() {
+    let mut db = TestDb::new();
+    db.restart_with_args(&["--ttl-check-interval=1"]).expect("restart failed");
+
+    db.execute_sql("CREATE TABLE metrics (id INT, ts INT, PRIMARY KEY (id));").expect("CREATE TABLE failed");
+    db.execute_sql(&format!("INSERT INTO metrics VALUES (1, 1000), (2, {});", FUTURE)).expect("INSERT failed");
+    thread::sleep(EXPIRY_WAIT);
+    let result = db.execute_sql("SELECT id FROM metrics;").expect("SELECT failed");
+    assert!(result.contains("(2 rows)"), "rows should be kept without a TTL: {}", result);
+
+    db.execute_sql("ALTER TABLE metrics SET (ttl_column = ts, ttl = '1 hour');").expect("ALTER TABLE failed");
+    thread::sleep(EXPIRY_WAIT);
+    let result = db.execute_sql("SELECT id FROM metrics;").expect("SELECT failed");
+    assert!(result.contains("(1 row)"), "the old row should have expired: {}", result);
+
+    // The policy follows its column when it is renamed
+    db.execute_sql("ALTER TABLE metrics RENAME COLUMN ts TO recorded_at;").expect("RENAME COLUMN failed");
+    db.execute_sql("INSERT INTO metrics VALUES (3, 1000);").expect("INSERT failed");
+    thread::sleep(EXPIRY_WAIT);
+    let result = db.execute_sql("SELECT id FROM metrics;").expect("SELECT failed");
+    assert!(result.contains("(1 row)"), "the renamed column should still expire rows: {}", result);
+
+    db.execute_sql("ALTER TABLE metrics SET (ttl = 'off');").expect("ALTER TABLE failed");
+    db.execute_sql("INSERT INTO metrics VALUES (4, 1000);").expect("INSERT failed");
+    thread::sleep(EXPIRY_WAIT);
+    let result = db.execute_sql("SELECT id FROM metrics;").expect("SELECT failed");
+    assert!(result.contains("(2 rows)"), "rows should be kept once the TTL is off: {}", result);
+}
+
+#[test]
+#[serial]
+fn test_ttl_validation() {
+    let db = TestDb::new();
+
+    db.execute_sql("CREATE TABLE logs (id INT, ts INT, line STRING, PRIMARY KEY (id));").expect("CREATE TABLE failed");
+
+    for (sql, expected) in [
+        ("ALTER TABLE logs SET (ttl_column = line, ttl = '1 day');", "must be an integer"),
+        ("ALTER TABLE logs SET (ttl_column = missing, ttl = '1 day');", "Column not found"),
+        ("ALTER TABLE logs SET (ttl_column = ts, ttl = '3 fortnights');", "Invalid ttl"),
+        ("ALTER TABLE logs SET (ttl_column = ts, ttl = 0);", "at least one second"),
+        ("ALTER TABLE logs SET (ttl = '1 day');", "requires a ttl_column"),
+        ("ALTER TABLE logs SET (compression = 'zstd');", "Unsupported table option"),
+        ("CREATE TABLE other (id INT, PRIMARY KEY (id)) WITH (ttl_column = id);", "requires a ttl"),
+    ] {
+        let err = db.execute_sql(sql).expect_err("invalid TTL should be rejected");
+        assert!(err.contains(expected), "unexpected error for {}: {}", sql, err);
+    }
+
+    let result = db.execute_sql("SELECT * FROM other;");
+    assert!(result.is_err(), "a table with an invalid TTL should not be created");
+}
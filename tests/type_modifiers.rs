@@ -0,0 +1,56 @@
+mod common;
+
+use common::TestDb;
+use serial_test::serial;
+
+#[test]
+#[serial]
+fn test_varchar_length() {
+    let mut db = TestDb::new();
+
+    db.execute_sql("CREATE TABLE people (id INT PRIMARY KEY, name VARCHAR(5), code CHAR(2), note TEXT);")
+        .expect("CREATE TABLE failed");
+    db.execute_sql("INSERT INTO people VALUES (1, 'alice', 'ab', 'anything at all');")
+        .expect("INSERT within the length failed");
+
+    let err = db.execute_sql("INSERT INTO people VALUES (2, 'bernard', 'cd', '');")
+        .expect_err("a string longer than VARCHAR(5) should be rejected");
+    assert!(err.contains("value too long for type character varying(5)"), "unexpected error: {}", err);
+    let err = db.execute_sql("INSERT INTO people (id, code) VALUES (2, 'xyz');")
+        .expect_err("a string longer than CHAR(2) should be rejected");
+    assert!(err.contains("value too long for type character varying(2)"), "unexpected error: {}", err);
+
+    // Trailing spaces past the length are dropped rather than rejected
+    db.execute_sql("INSERT INTO people VALUES (3, 'bob    ', 'ef', '');")
+        .expect("INSERT with trailing spaces failed");
+    let result = db.execute_sql("SELECT id FROM people WHERE name = 'bob  ';").expect("SELECT failed");
+    assert!(result.contains("(1 row)"), "trailing spaces should be cut to the length: {}", result);
+
+    // The length is counted in characters, not bytes
+    db.execute_sql("INSERT INTO people VALUES (4, 'héllo', 'gh', '');")
+        .expect("INSERT of multi-byte characters failed");
+
+    let err = db.execute_sql("UPDATE people SET name = 'alexandra' WHERE id = 1;")
+        .expect_err("an UPDATE past the length should be rejected");
+    assert!(err.contains("value too long"), "unexpected error: {}", err);
+    let err = db.execute_sql("INSERT INTO people VALUES (1, 'al', 'ab', '') ON CONFLICT (id) DO UPDATE SET name = 'alexandra';")
+        .expect_err("an upsert past the length should be rejected");
+    assert!(err.contains("value too long"), "unexpected error: {}", err);
+    let result = db.execute_sql("SELECT name FROM people WHERE id = 1;").expect("SELECT failed");
+    assert!(result.contains("alice"), "a rejected write should change nothing: {}", result);
+
+    let result = db.execute_sql("SELECT column_name, character_maximum_length FROM information_schema.columns WHERE table_name = 'people';")
+        .expect("SELECT information_schema.columns failed");
+    assert!(result.contains("name        |                        5"), "missing VARCHAR length: {}", result);
+    assert!(result.contains("code        |                        2"), "missing CHAR length: {}", result);
+
+    // The length is part of the catalog and survives a restart
+    db.restart().expect("restart failed");
+    let err = db.execute_sql("INSERT INTO people VALUES (5, 'charlotte', 'ij', '');")
+        .expect_err("the length should still be enforced after a restart");
+    assert!(err.contains("value too long for type character varying(5)"), "unexpected error: {}", err);
+
+    let err = db.execute_sql("CREATE TABLE empty (id INT PRIMARY KEY, name VARCHAR(0));")
+        .expect_err("VARCHAR(0) should be rejected");
+    assert!(err.contains("length for type varchar must be at least 1"), "unexpected error: {}", err);
+}
@@ -0,0 +1,72 @@
+mod common;
+
+use common::TestDb;
+use serial_test::serial;
+
+#[test]
+#[serial]
+fn test_update_rows() {
+    let db = TestDb::new();
+
+    db.execute_sql("CREATE TABLE items (id INT, name STRING, qty INT, PRIMARY KEY (id));")
+        .expect("CREATE TABLE failed");
+    db.execute_sql("INSERT INTO items VALUES (1, 'bolt', 10), (2, 'nut', 20), (3, 'gear', 30);")
+        .expect("INSERT failed");
+
+    let result = db.execute_sql("UPDATE items SET qty = qty + 1 WHERE id >= 2;").expect("UPDATE failed");
+    assert!(result.contains("UPDATE 2"), "expected two rows updated: {}", result);
+
+    let result = db.execute_sql("SELECT * FROM items WHERE id = 2;").expect("SELECT failed");
+    assert!(result.contains("21"), "qty should be incremented: {}", result);
+    let result = db.execute_sql("SELECT * FROM items WHERE id = 1;").expect("SELECT failed");
+    assert!(result.contains("10"), "unmatched row should be untouched: {}", result);
+
+    // Every assignment sees the row as it was before the update
+    db.execute_sql("UPDATE items SET qty = 0, name = 'spent' WHERE qty = 10;").expect("UPDATE failed");
+    let result = db.execute_sql("SELECT * FROM items WHERE id = 1;").expect("SELECT failed");
+    assert!(result.contains("spent") && result.contains(" 0"), "both columns should change: {}", result);
+
+    let result = db.execute_sql("UPDATE items SET missing = 1;");
+    assert!(result.is_err(), "unknown column should be rejected");
+
+    let result = db.execute_sql("SELECT * FROM items;").expect("SELECT failed");
+    assert!(result.contains("(3 rows)"), "UPDATE must not add or drop rows: {}", result);
+}
+
+#[test]
+#[serial]
+fn test_update_primary_key() {
+    let mut db = TestDb::new();
+
+    db.execute_sql("CREATE TABLE keyed (id INT, name STRING, PRIMARY KEY (id));")
+        .expect("CREATE TABLE failed");
+    db.execute_sql("INSERT INTO keyed VALUES (1, 'one'), (2, 'two'), (3, 'three');")
+        .expect("INSERT failed");
+
+    db.execute_sql("UPDATE keyed SET id = 10 WHERE id = 1;").expect("UPDATE of key failed");
+    let result = db.execute_sql("SELECT * FROM keyed WHERE id = 10;").expect("SELECT failed");
+    assert!(result.contains("one"), "row should be found under its new key: {}", result);
+    let result = db.execute_sql("SELECT * FROM keyed WHERE id = 1;").expect("SELECT failed");
+    assert!(!result.contains("one"), "old key should no longer find the row: {}", result);
+
+    // The old key is free again
+    db.execute_sql("INSERT INTO keyed VALUES (1, 'uno');").expect("INSERT of freed key failed");
+
+    let result = db.execute_sql("UPDATE keyed SET id = 2 WHERE id = 3;");
+    assert!(result.is_err(), "new key colliding with another row should be rejected");
+    let result = db.execute_sql("SELECT * FROM keyed WHERE id = 3;").expect("SELECT failed");
+    assert!(result.contains("three"), "failed UPDATE must leave the row alone: {}", result);
+
+    // Keys shifting onto each other within one statement do not conflict
+    db.execute_sql("UPDATE keyed SET id = id + 1;").expect("UPDATE shifting keys failed");
+    for (id, name) in [(2, "uno"), (3, "two"), (4, "three"), (11, "one")] {
+        let result = db.execute_sql(&format!("SELECT * FROM keyed WHERE id = {};", id)).expect("SELECT failed");
+        assert!(result.contains(name), "key {} should find '{}': {}", id, name, result);
+    }
+
+    db.restart().expect("restart failed");
+    let result = db.execute_sql("SELECT * FROM keyed WHERE id = 11;").expect("SELECT failed");
+    assert!(result.contains("one"), "updated key should survive a restart: {}", result);
+    let result = db.execute_sql("SELECT * FROM keyed WHERE id = 10;").expect("SELECT failed");
+    assert!(!result.contains("one"), "stale key should not come back after a restart: {}", result);
+}
@@ -0,0 +1,108 @@
+mod common;
+
+use common::TestDb;
+use serial_test::serial;
+
+/// Rows of a psql result as trimmed cells
+fn result_rows(result: &str) -> Vec<Vec<String>> {
+    result.lines()
+        .skip(2)
+        .take_while(|line| !line.starts_with('('))
+        .map(|line| line.split('|').map(|cell| cell.trim().to_string()).collect())
+        .collect()
+}
+
+#[test]
+#[serial]
+fn test_insert_on_conflict_do_update() {
+    let db = TestDb::new();
+
+    db.execute_sql("CREATE TABLE counters (name STRING, hits INT, CHECK (hits < 100), PRIMARY KEY (name));")
+        .expect("CREATE TABLE failed");
+    db.execute_sql("CREATE INDEX counters_hits ON counters (hits);").expect("CREATE INDEX failed");
+    let upsert = "INSERT INTO counters VALUES ('home', 1), ('about', 5) \
+                  ON CONFLICT (name) DO UPDATE SET hits = counters.hits + excluded.hits;";
+    db.execute_sql(upsert).expect("first upsert failed");
+    db.execute_sql(upsert).expect("second upsert failed");
+    let result = db.execute_sql("SELECT name, hits FROM counters ORDER BY name;").expect("SELECT failed");
+    assert_eq!(result_rows(&result), [["about", "10"], ["home", "2"]], "unexpected counters: {}", result);
+
+    // An alias qualifies the existing row, and WHERE decides which rows change
+    db.execute_sql(
+        "INSERT INTO counters AS c VALUES ('home', 50), ('about', 50), ('new', 3) \
+         ON CONFLICT ON CONSTRAINT counters_pkey DO UPDATE SET hits = c.hits + excluded.hits WHERE c.hits < 5;",
+    ).expect("upsert with WHERE failed");
+    let result = db.execute_sql("SELECT name, hits FROM counters ORDER BY name;").expect("SELECT failed");
+    assert_eq!(result_rows(&result), [["about", "10"], ["home", "52"], ["new", "3"]], "unexpected counters: {}", result);
+
+    // Secondary indexes follow the updated rows
+    let result = db.execute_sql("SELECT name FROM counters WHERE hits = 52;").expect("SELECT failed");
+    assert_eq!(result_rows(&result), [["home"]], "the index should follow the update: {}", result);
+    let result = db.execute_sql("SELECT name FROM counters WHERE hits = 2;").expect("SELECT failed");
+    assert!(!result.contains("home"), "the old entry should be gone: {}", result);
+
+    // Updated rows are checked like any other, and a failure writes nothing
+    let err = db.execute_sql(
+        "INSERT INTO counters VALUES ('other', 1), ('home', 60) ON CONFLICT (name) DO UPDATE SET hits = counters.hits + excluded.hits;",
+    ).expect_err("an update past the CHECK should be refused");
+    assert!(err.contains("violates check constraint"), "unexpected error: {}", err);
+    let result = db.execute_sql("SELECT count(*) FROM counters;").expect("SELECT failed");
+    assert!(result.contains(" 3\n"), "nothing should have been inserted: {}", result);
+}
+
+#[test]
+#[serial]
+fn test_insert_on_conflict_do_nothing() {
+    let db = TestDb::new();
+
+    db.execute_sql("CREATE TABLE users (id INT, name STRING, PRIMARY KEY (id));").expect("CREATE TABLE failed");
+    db.execute_sql("INSERT INTO users VALUES (1, 'ann');").expect("INSERT failed");
+
+    // Rows whose key is taken, here or earlier in the same INSERT, are skipped
+    db.execute_sql("INSERT INTO users VALUES (1, 'bo'), (2, 'cy'), (2, 'dee') ON CONFLICT DO NOTHING;")
+        .expect("DO NOTHING failed");
+    db.execute_sql("INSERT INTO users VALUES (3, 'eve') ON CONFLICT (id) DO NOTHING;").expect("DO NOTHING failed");
+    let result = db.execute_sql("SELECT id, name FROM users ORDER BY id;").expect("SELECT failed");
+    assert_eq!(result_rows(&result), [["1", "ann"], ["2", "cy"], ["3", "eve"]], "unexpected users: {}", result);
+
+    // Without ON CONFLICT a taken key is still an error
+    let err = db.execute_sql("INSERT INTO users VALUES (1, 'bo');").expect_err("a duplicate key should be refused");
+    assert!(err.contains("duplicate key value violates unique constraint \"users_pkey\""), "unexpected error: {}", err);
+}
+
+#[test]
+#[serial]
+fn test_insert_on_conflict_errors() {
+    let db = TestDb::new();
+
+    db.execute_sql("CREATE TABLE users (id INT, name STRING, PRIMARY KEY (id));").expect("CREATE TABLE failed");
+    db.execute_sql("INSERT INTO users VALUES (1, 'ann');").expect("INSERT failed");
+    let cases = [
+        (
+            "INSERT INTO users VALUES (2, 'bo'), (2, 'cy') ON CONFLICT (id) DO UPDATE SET name = excluded.name;",
+            "cannot affect row a second time",
+        ),
+        (
+            "INSERT INTO users VALUES (1, 'bo') ON CONFLICT (name) DO NOTHING;",
+            "there is no unique or exclusion constraint matching the ON CONFLICT specification",
+        ),
+        (
+            "INSERT INTO users VALUES (1, 'bo') ON CONFLICT ON CONSTRAINT users_name_key DO NOTHING;",
+            "constraint \"users_name_key\" for table \"users\" does not exist",
+        ),
+        (
+            "INSERT INTO users VALUES (1, 'bo') ON CONFLICT DO UPDATE SET name = 'x';",
+            "ON CONFLICT DO UPDATE requires inference specification or constraint name",
+        ),
+        (
+            "INSERT INTO users VALUES (1, 'bo') ON CONFLICT (id) DO UPDATE SET name = name || 'x';",
+            "ambiguous",
+        ),
+    ];
+    for (sql, expected) in cases {
+        let err = db.execute_sql(sql).expect_err("the upsert should be refused");
+        assert!(err.contains(expected), "{}: unexpected error: {}", sql, err);
+    }
+    let result = db.execute_sql("SELECT id, name FROM users ORDER BY id;").expect("SELECT failed");
+    assert_eq!(result_rows(&result), [["1", "ann"]], "a refused upsert should write nothing: {}", result);
+}
@@ -0,0 +1,49 @@
+mod common;
+
+use common::{scalar, TestDb};
+use serial_test::serial;
+
+#[test]
+#[serial]
+fn test_writes_are_logged() {
+    let mut db = TestDb::new();
+    let wal_counter = |db: &TestDb, column: &str| scalar(&db.execute_sql(&format!("SELECT {} FROM pg_stat_wal;", column))
+        .expect("SELECT pg_stat_wal failed"));
+
+    let before = wal_counter(&db, "wal_records");
+    db.execute_sql("CREATE TABLE items (id INT, name STRING, PRIMARY KEY (id));").expect("CREATE TABLE failed");
+    assert_eq!(wal_counter(&db, "wal_records"), before + 1, "CREATE TABLE should be logged");
+
+    // One record per statement, however many rows it writes, and its commit
+    for sql in [
+        "INSERT INTO items VALUES (1, 'bolt'), (2, 'nut'), (3, 'washer');",
+        "UPDATE items SET name = 'hex nut' WHERE id = 2;",
+        "DELETE FROM items WHERE id = 3;",
+    ] {
+        let before = wal_counter(&db, "wal_records");
+        db.execute_sql(sql).unwrap_or_else(|e| panic!("{} failed: {}", sql, e));
+        assert_eq!(wal_counter(&db, "wal_records"), before + 2, "{} should be logged once", sql);
+    }
+
+    // Temporary tables don't outlive the server, so aren't logged
+    let before = wal_counter(&db, "wal_records");
+    db.execute_sql("CREATE TEMP TABLE scratch (id INT, PRIMARY KEY (id)); INSERT INTO scratch VALUES (1);")
+        .expect("temporary table failed");
+    assert_eq!(wal_counter(&db, "wal_records"), before, "temporary tables should not be logged");
+
+    // Asynchronous commits log without flushing; the WAL is only flushed as
+    // it is discarded when the catalog is saved
+    let values: Vec<String> = (10..110).map(|id| format!("({}, 'spare')", id)).collect();
+    db.execute_sql(&format!("INSERT INTO items VALUES {};", values.join(", "))).expect("INSERT failed");
+    db.restart_with_args(&["--synchronous-commit=off"]).expect("restart failed");
+    let (records, syncs) = (wal_counter(&db, "wal_records"), wal_counter(&db, "wal_sync"));
+    for id in 4..9 {
+        db.execute_sql(&format!("INSERT INTO items VALUES ({}, 'rivet');", id)).expect("INSERT failed");
+    }
+    assert_eq!(wal_counter(&db, "wal_records"), records + 10, "every INSERT should be logged");
+    assert!(wal_counter(&db, "wal_sync") < syncs + 5, "the WAL should not be flushed on every commit");
+
+    db.restart().expect("restart failed");
+    let result = db.execute_sql("SELECT id, name FROM items ORDER BY id;").expect("SELECT failed");
+    assert!(result.contains("hex nut") && result.contains("rivet") && !result.contains("washer"), "unexpected rows: {}", result);
+}
@@ -0,0 +1,32 @@
+mod common;
+
+use common::TestDb;
+use serial_test::serial;
+
+#[test]
+#[serial]
+fn test_work_mem_limit() {
+    let mut db = TestDb::new();
+
+    db.execute_sql("CREATE TABLE items (id INT, label STRING, PRIMARY KEY (id));")
+        .expect("CREATE TABLE failed");
+    db.execute_sql("INSERT INTO items VALUES (1, 'a'), (2, 'b'), (3, 'c'), (4, 'd'), (5, 'e'), (6, 'f'), (7, 'g'), (8, 'h');")
+        .expect("INSERT failed");
+
+    // The default budget is ample for small queries
+    let result = db.execute_sql("SELECT COUNT(*) FROM items a, items b, items c;").expect("cross join failed");
+    assert!(result.contains("512"), "expected every triple: {}", result);
+
+    db.restart_with_args(&["--work-mem=4kB"]).expect("restart failed");
+
+    let err = db.execute_sql("SELECT COUNT(*) FROM items a, items b, items c;")
+        .expect_err("join past work_mem should fail");
+    assert!(err.contains("work_mem"), "expected a resource error: {}", err);
+
+    // Queries within the budget still run, and the server survives the failure
+    let result = db.execute_sql("SELECT label FROM items ORDER BY label DESC LIMIT 1;").expect("small sort failed");
+    assert!(result.contains(" h"), "wrong sort: {}", result);
+    let result = db.execute_sql("SELECT label, COUNT(*) FROM items GROUP BY label ORDER BY label LIMIT 1;")
+        .expect("small GROUP BY failed");
+    assert!(result.contains(" a "), "wrong groups: {}", result);
+}
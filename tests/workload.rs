@@ -0,0 +1,58 @@
+mod common;
+
+use std::io::Write;
+use std::thread;
+use std::time::Duration;
+
+use common::TestDb;
+use serial_test::serial;
+
+#[test]
+#[serial]
+fn test_batch_queries_queue_behind_their_limit() {
+    let mut db = TestDb::new();
+    db.restart_with_args(&["--max-batch-queries=1"]).expect("restart failed");
+    db.execute_sql("CREATE TABLE events (id INT, kind STRING, PRIMARY KEY (id));").expect("CREATE TABLE failed");
+
+    // A batch session runs a slow query in the only batch place
+    let mut sleeper = db.open_session();
+    let mut sleeper_stdin = sleeper.stdin.take().expect("psql stdin");
+    sleeper_stdin.write_all(b"SET workload_class = batch;\nSELECT pg_sleep(2);\n").expect("write to psql failed");
+    sleeper_stdin.flush().expect("flush to psql failed");
+    thread::sleep(Duration::from_millis(500));
+
+    thread::scope(|scope| {
+        // Bulk work queues behind it...
+        let create_index = scope.spawn(|| db.execute_sql("CREATE INDEX events_kind ON events (kind);"));
+        thread::sleep(Duration::from_millis(500));
+        assert!(!create_index.is_finished(), "CREATE INDEX should wait for a batch place");
+
+        // ...while interactive queries don't
+        let result = db.execute_sql("SELECT count(*) FROM events;").expect("SELECT failed");
+        assert!(result.contains(" 0\n"), "unexpected count: {}", result);
+
+        // Once the batch session's query ends, the queued one runs
+        create_index.join().expect("CREATE INDEX thread panicked").expect("CREATE INDEX failed");
+    });
+
+    drop(sleeper_stdin);
+    sleeper.wait().expect("psql did not exit");
+    db.execute_sql("INSERT INTO events VALUES (1, 'click');").expect("INSERT failed");
+    let result = db.execute_sql("SELECT id FROM events WHERE kind = 'click';").expect("SELECT failed");
+    assert!(result.contains("(1 row)"), "the index should have been built: {}", result);
+}
+
+#[test]
+#[serial]
+fn test_set_workload_class() {
+    let db = TestDb::new();
+
+    let result = db.execute_sql("SET workload_class = batch; SET workload_class TO 'interactive'; SET SESSION workload_class = DEFAULT;")
+        .expect("SET workload_class failed");
+    assert_eq!(result.matches("SET").count(), 3, "unexpected tags: {}", result);
+
+    let err = db.execute_sql("SET workload_class = urgent;").expect_err("an unknown class should be refused");
+    assert!(err.contains("invalid value for parameter \"workload_class\""), "unexpected error: {}", err);
+    let err = db.execute_sql("SET LOCAL workload_class = batch;").expect_err("SET LOCAL should be refused");
+    assert!(err.contains("can only be set for the session"), "unexpected error: {}", err);
+}